@@ -0,0 +1,170 @@
+//! Command line
+//! `rcrt <noun> <verb>` over the rcrt-server HTTP API
+
+use clap::{Args, Parser, Subcommand};
+use rcrt_core::models::DeliveryChannel;
+use uuid::Uuid;
+
+#[derive(Debug, Parser)]
+#[command(name = "rcrt", version, about = "Operate an RCRT deployment from the shell")]
+pub struct Cli {
+    #[command(flatten)]
+    pub global: GlobalArgs,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+/// Connection settings; each falls back to its env var, then ~/.rcrt/config.toml
+#[derive(Debug, Clone, Default, Args)]
+pub struct GlobalArgs {
+    /// rcrt-server base URL [default: http://localhost:8081]
+    #[arg(long, env = "RCRT_URL", global = true)]
+    pub url: Option<String>,
+    /// JWT sent as `Authorization: Bearer`
+    #[arg(long, env = "RCRT_TOKEN", global = true, hide_env_values = true)]
+    pub token: Option<String>,
+    /// API key sent as `Authorization: ApiKey`; --token wins when both are set
+    #[arg(long, env = "RCRT_API_KEY", global = true, hide_env_values = true)]
+    pub api_key: Option<String>,
+    /// Tenant for `token mint`
+    #[arg(long = "owner", env = "RCRT_OWNER_ID", global = true)]
+    pub owner_id: Option<Uuid>,
+    /// Agent for `token mint` and `agent register`
+    #[arg(long = "agent", env = "RCRT_AGENT_ID", global = true)]
+    pub agent_id: Option<Uuid>,
+    /// Print the server's JSON instead of a table
+    #[arg(long, global = true)]
+    pub json: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Create, read, list, search and delete breadcrumbs
+    #[command(subcommand)]
+    Breadcrumb(BreadcrumbCmd),
+    /// The calling agent's selector subscriptions
+    #[command(subcommand)]
+    Selector(SelectorCmd),
+    /// Failed webhook deliveries (curator)
+    #[command(subcommand)]
+    Dlq(DlqCmd),
+    /// Agent registration
+    #[command(subcommand)]
+    Agent(AgentCmd),
+    /// JWTs from POST /auth/token (needs JWT_PRIVATE_KEY_PEM on the server; meant for dev setups)
+    #[command(subcommand)]
+    Token(TokenCmd),
+}
+
+/// Filters shared by `breadcrumb list` and `breadcrumb search`
+#[derive(Debug, Clone, Default, Args)]
+pub struct FilterArgs {
+    #[arg(long)]
+    pub tag: Option<String>,
+    /// Session id, with or without the `session:` prefix
+    #[arg(long)]
+    pub session: Option<String>,
+    #[arg(long = "schema")]
+    pub schema_name: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum BreadcrumbCmd {
+    Create {
+        #[arg(long)]
+        title: String,
+        #[arg(long = "schema")]
+        schema_name: Option<String>,
+        /// Repeatable
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Context as a JSON object
+        #[arg(long, default_value = "{}")]
+        context: String,
+        #[arg(long)]
+        description: Option<String>,
+    },
+    Get {
+        id: Uuid,
+    },
+    List {
+        #[command(flatten)]
+        filter: FilterArgs,
+        #[arg(long, default_value_t = 20)]
+        limit: i64,
+    },
+    /// Vector search, embedding the query on the server
+    Search {
+        query: String,
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// Results to return
+        #[arg(long, default_value_t = 5)]
+        nn: i64,
+    },
+    Delete {
+        id: Uuid,
+        /// Delete even though other breadcrumbs reference it
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SelectorCmd {
+    List,
+    Create {
+        /// Match breadcrumbs with any of these tags (repeatable)
+        #[arg(long = "any-tag")]
+        any_tags: Vec<String>,
+        /// Match breadcrumbs with all of these tags (repeatable)
+        #[arg(long = "all-tag")]
+        all_tags: Vec<String>,
+        /// Reject breadcrumbs with any of these tags (repeatable)
+        #[arg(long = "none-tag")]
+        none_tags: Vec<String>,
+        #[arg(long = "schema")]
+        schema_name: Option<String>,
+        /// sse, webhook, nats or inbox (repeatable); every push channel when omitted
+        #[arg(long = "channel", value_parser = parse_channel)]
+        channels: Vec<DeliveryChannel>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DlqCmd {
+    List,
+    /// Redeliver one entry and drop it from the queue
+    Retry {
+        id: Uuid,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AgentCmd {
+    /// Register (or re-role) an agent; defaults to --agent
+    Register {
+        id: Option<Uuid>,
+        /// Repeatable: curator, emitter, subscriber
+        #[arg(long = "role", required = true)]
+        roles: Vec<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TokenCmd {
+    /// Mint a JWT for --owner and --agent
+    Mint {
+        /// Repeatable; the server grants curator, emitter and subscriber when omitted
+        #[arg(long = "role")]
+        roles: Vec<String>,
+        /// Lifetime in seconds (server default 3600)
+        #[arg(long)]
+        ttl_sec: Option<i64>,
+    },
+}
+
+fn parse_channel(value: &str) -> Result<DeliveryChannel, String> {
+    serde_json::from_value(serde_json::Value::String(value.to_string()))
+        .map_err(|_| format!("unknown channel {} (expected sse, webhook, nats or inbox)", value))
+}
@@ -0,0 +1,120 @@
+//! HTTP client
+//! One method per rcrt-server endpoint the CLI uses, typed with rcrt-core's models where the server has one
+
+use anyhow::{anyhow, Result};
+use rcrt_core::models::{BreadcrumbContextView, BreadcrumbCreate, DeliveryChannel, Selector, SelectorSubscription};
+use reqwest::{Method, RequestBuilder};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::cli::FilterArgs;
+use crate::config::Config;
+
+pub struct Client {
+    http: reqwest::Client,
+    base: String,
+    authorization: Option<String>,
+}
+
+impl Client {
+    pub fn new(config: &Config) -> Self {
+        Client { http: reqwest::Client::new(), base: config.url.clone(), authorization: config.authorization() }
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let req = self.http.request(method, format!("{}{}", self.base, path));
+        match &self.authorization {
+            Some(value) => req.header(reqwest::header::AUTHORIZATION, value),
+            None => req,
+        }
+    }
+
+    /// Non-2xx responses become errors carrying the status and the server's message
+    async fn send<T: DeserializeOwned>(&self, req: RequestBuilder) -> Result<T> {
+        let res = req.send().await?;
+        let status = res.status();
+        if !status.is_success() {
+            let body = res.text().await.unwrap_or_default();
+            return Err(anyhow!("{}: {}", status, body.trim()));
+        }
+        Ok(res.json().await?)
+    }
+
+    /// `{"id": ...}`
+    pub async fn create_breadcrumb(&self, create: &BreadcrumbCreate) -> Result<Value> {
+        self.send(self.request(Method::POST, "/breadcrumbs").json(create)).await
+    }
+
+    pub async fn get_breadcrumb(&self, id: Uuid) -> Result<BreadcrumbContextView> {
+        self.send(self.request(Method::GET, &format!("/breadcrumbs/{}", id))).await
+    }
+
+    pub async fn list_breadcrumbs(&self, filter: &FilterArgs, limit: i64) -> Result<Vec<BreadcrumbContextView>> {
+        let mut query = filter_query(filter);
+        query.push(("limit", limit.to_string()));
+        self.send(self.request(Method::GET, "/breadcrumbs").query(&query)).await
+    }
+
+    pub async fn search_breadcrumbs(&self, q: &str, filter: &FilterArgs, nn: i64) -> Result<Vec<BreadcrumbContextView>> {
+        let mut query = filter_query(filter);
+        query.push(("q", q.to_string()));
+        query.push(("nn", nn.to_string()));
+        self.send(self.request(Method::GET, "/breadcrumbs/search").query(&query)).await
+    }
+
+    /// `force` breaks references other breadcrumbs declare to it instead of failing with 409
+    pub async fn delete_breadcrumb(&self, id: Uuid, force: bool) -> Result<Value> {
+        let mut req = self.request(Method::DELETE, &format!("/breadcrumbs/{}", id));
+        if force {
+            req = req.query(&[("force", "true")]);
+        }
+        self.send(req).await
+    }
+
+    pub async fn list_selectors(&self) -> Result<Vec<SelectorSubscription>> {
+        self.send(self.request(Method::GET, "/subscriptions/selectors")).await
+    }
+
+    /// An empty `channels` leaves the server default (every channel)
+    pub async fn create_selector(&self, selector: &Selector, channels: &[DeliveryChannel]) -> Result<SelectorSubscription> {
+        let mut body = serde_json::to_value(selector)?;
+        if !channels.is_empty() {
+            body["channels"] = json!(channels);
+        }
+        self.send(self.request(Method::POST, "/subscriptions/selectors").json(&body)).await
+    }
+
+    /// The server builds these ad hoc, so they stay JSON
+    pub async fn list_dlq(&self) -> Result<Vec<Value>> {
+        self.send(self.request(Method::GET, "/dlq")).await
+    }
+
+    pub async fn retry_dlq(&self, id: Uuid) -> Result<Value> {
+        self.send(self.request(Method::POST, &format!("/dlq/{}/retry", id))).await
+    }
+
+    pub async fn register_agent(&self, id: Uuid, roles: &[String]) -> Result<Value> {
+        self.send(self.request(Method::POST, &format!("/agents/{}", id)).json(&json!({ "roles": roles }))).await
+    }
+
+    /// `{"token", "owner_id", "agent_id", "roles", "exp"}`
+    pub async fn mint_token(&self, owner_id: Uuid, agent_id: Uuid, roles: &[String], ttl_sec: Option<i64>) -> Result<Value> {
+        let mut body = json!({ "owner_id": owner_id, "agent_id": agent_id });
+        if !roles.is_empty() {
+            body["roles"] = json!(roles);
+        }
+        if let Some(ttl) = ttl_sec {
+            body["ttl_sec"] = json!(ttl);
+        }
+        self.send(self.request(Method::POST, "/auth/token").json(&body)).await
+    }
+}
+
+/// Context views rather than list items, so list and search share one response type
+fn filter_query(filter: &FilterArgs) -> Vec<(&'static str, String)> {
+    let mut query = vec![("include_context", "true".to_string())];
+    let fields = [("tag", &filter.tag), ("session", &filter.session), ("schema_name", &filter.schema_name)];
+    query.extend(fields.into_iter().filter_map(|(key, value)| value.clone().map(|v| (key, v))));
+    query
+}
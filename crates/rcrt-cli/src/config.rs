@@ -0,0 +1,110 @@
+//! CLI configuration
+//! Flags and RCRT_* env vars (both via clap), then ~/.rcrt/config.toml, then defaults
+
+use std::path::PathBuf;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::cli::GlobalArgs;
+
+pub const DEFAULT_URL: &str = "http://localhost:8081";
+
+/// `~/.rcrt/config.toml`, or the file named by RCRT_CONFIG; every key is optional:
+///
+/// ```toml
+/// url = "https://rcrt.example.com"
+/// api_key = "rcrt_..."
+/// owner_id = "00000000-0000-0000-0000-000000000001"
+/// agent_id = "00000000-0000-0000-0000-0000000000aa"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    pub url: Option<String>,
+    pub token: Option<String>,
+    pub api_key: Option<String>,
+    pub owner_id: Option<Uuid>,
+    pub agent_id: Option<Uuid>,
+}
+
+impl FileConfig {
+    pub fn path() -> Option<PathBuf> {
+        std::env::var_os("RCRT_CONFIG")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".rcrt").join("config.toml")))
+    }
+
+    /// The config file, or the defaults when there is none
+    pub fn load() -> Result<Self> {
+        let Some(path) = Self::path().filter(|p| p.exists()) else {
+            return Ok(FileConfig::default());
+        };
+        let text = std::fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+        toml::from_str(&text).with_context(|| format!("parsing {}", path.display()))
+    }
+}
+
+/// What one invocation talks to and as whom
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub url: String,
+    pub token: Option<String>,
+    pub api_key: Option<String>,
+    pub owner_id: Option<Uuid>,
+    pub agent_id: Option<Uuid>,
+}
+
+impl Config {
+    pub fn resolve(args: &GlobalArgs, file: FileConfig) -> Self {
+        let url = args.url.clone().or(file.url).unwrap_or_else(|| DEFAULT_URL.to_string());
+        Config {
+            url: url.trim_end_matches('/').to_string(),
+            token: args.token.clone().or(file.token),
+            api_key: args.api_key.clone().or(file.api_key),
+            owner_id: args.owner_id.or(file.owner_id),
+            agent_id: args.agent_id.or(file.agent_id),
+        }
+    }
+
+    pub fn load(args: &GlobalArgs) -> Result<Self> {
+        Ok(Self::resolve(args, FileConfig::load()?))
+    }
+
+    /// The Authorization header value, if any credential is set
+    pub fn authorization(&self) -> Option<String> {
+        match (&self.token, &self.api_key) {
+            (Some(token), _) => Some(format!("Bearer {}", token)),
+            (None, Some(key)) => Some(format!("ApiKey {}", key)),
+            (None, None) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flags_win_over_the_file() {
+        let file: FileConfig = toml::from_str(r#"
+            url = "https://rcrt.example.com/"
+            api_key = "rcrt_abc"
+            owner_id = "00000000-0000-0000-0000-000000000001"
+        "#).unwrap();
+        let config = Config::resolve(&GlobalArgs::default(), file.clone());
+        assert_eq!(config.url, "https://rcrt.example.com");
+        assert_eq!(config.authorization().as_deref(), Some("ApiKey rcrt_abc"));
+        assert_eq!(config.owner_id, Some(Uuid::from_u128(1)));
+
+        let args = GlobalArgs { url: Some("http://localhost:9000".into()), token: Some("jwt".into()), ..Default::default() };
+        let config = Config::resolve(&args, file);
+        assert_eq!(config.url, "http://localhost:9000");
+        assert_eq!(config.authorization().as_deref(), Some("Bearer jwt"));
+
+        let config = Config::resolve(&GlobalArgs::default(), FileConfig::default());
+        assert_eq!(config.url, DEFAULT_URL);
+        assert_eq!(config.authorization(), None);
+        assert!(toml::from_str::<FileConfig>("uri = \"typo\"").is_err());
+    }
+}
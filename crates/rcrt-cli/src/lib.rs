@@ -0,0 +1,182 @@
+//! RCRT CLI
+//! The `rcrt` binary's commands as a library, so tests can run them against a mock server
+
+use std::io::Write;
+use anyhow::{anyhow, Context, Result};
+use rcrt_core::models::{BreadcrumbContextView, BreadcrumbCreate, Selector};
+use serde_json::Value;
+
+pub mod cli;
+pub mod client;
+pub mod config;
+mod output;
+
+use cli::{AgentCmd, BreadcrumbCmd, Cli, Command, DlqCmd, SelectorCmd, TokenCmd};
+use client::Client;
+use config::Config;
+
+/// Run one command, writing its result to `out`
+pub async fn run(cli: Cli, config: &Config, out: &mut dyn Write) -> Result<()> {
+    let client = Client::new(config);
+    let as_json = cli.global.json;
+    match cli.command {
+        Command::Breadcrumb(cmd) => breadcrumb(&client, cmd, as_json, out).await,
+        Command::Selector(SelectorCmd::List) => {
+            let selectors = client.list_selectors().await?;
+            if as_json {
+                return output::json(out, &selectors);
+            }
+            let rows: Vec<Vec<String>> = selectors.iter().map(|s| vec![
+                s.id.to_string(),
+                s.selector.schema_name.clone().unwrap_or_default(),
+                tag_summary(&s.selector),
+                s.channels.iter().filter_map(|c| serde_json::to_value(c).ok()?.as_str().map(String::from)).collect::<Vec<_>>().join(","),
+            ]).collect();
+            Ok(output::table(out, &["ID", "SCHEMA", "TAGS", "CHANNELS"], &rows)?)
+        }
+        Command::Selector(SelectorCmd::Create { any_tags, all_tags, none_tags, schema_name, channels }) => {
+            let non_empty = |tags: Vec<String>| (!tags.is_empty()).then_some(tags);
+            let selector = Selector {
+                any_tags: non_empty(any_tags),
+                all_tags: non_empty(all_tags),
+                none_tags: non_empty(none_tags),
+                schema_name,
+                context_match: None,
+            };
+            let created = client.create_selector(&selector, &channels).await?;
+            if as_json {
+                return output::json(out, &created);
+            }
+            Ok(writeln!(out, "{}", created.id)?)
+        }
+        Command::Dlq(DlqCmd::List) => {
+            let items = client.list_dlq().await?;
+            if as_json {
+                return output::json(out, &items);
+            }
+            let text = |item: &Value, key: &str| match &item[key] {
+                Value::String(s) => s.clone(),
+                Value::Null => String::new(),
+                other => other.to_string(),
+            };
+            let rows: Vec<Vec<String>> = items.iter().map(|item| vec![
+                text(item, "id"),
+                text(item, "agent_id"),
+                text(item, "url"),
+                text(item, "last_status"),
+                output::truncate(&text(item, "last_error"), 60),
+                text(item, "created_at"),
+            ]).collect();
+            Ok(output::table(out, &["ID", "AGENT", "URL", "STATUS", "ERROR", "CREATED"], &rows)?)
+        }
+        Command::Dlq(DlqCmd::Retry { id }) => {
+            let res = client.retry_dlq(id).await?;
+            if as_json {
+                return output::json(out, &res);
+            }
+            Ok(writeln!(out, "retried {}", id)?)
+        }
+        Command::Agent(AgentCmd::Register { id, roles }) => {
+            let id = id.or(config.agent_id).ok_or_else(|| anyhow!("agent register needs an id or --agent (RCRT_AGENT_ID)"))?;
+            let res = client.register_agent(id, &roles).await?;
+            if as_json {
+                return output::json(out, &res);
+            }
+            Ok(writeln!(out, "registered {} as {}", id, roles.join(","))?)
+        }
+        Command::Token(TokenCmd::Mint { roles, ttl_sec }) => {
+            let (Some(owner_id), Some(agent_id)) = (config.owner_id, config.agent_id) else {
+                return Err(anyhow!("token mint needs --owner and --agent (RCRT_OWNER_ID, RCRT_AGENT_ID)"));
+            };
+            let res = client.mint_token(owner_id, agent_id, &roles, ttl_sec).await?;
+            if as_json {
+                return output::json(out, &res);
+            }
+            // Bare token so `export RCRT_TOKEN=$(rcrt token mint)` works
+            let token = res["token"].as_str().ok_or_else(|| anyhow!("no token in response: {}", res))?;
+            Ok(writeln!(out, "{}", token)?)
+        }
+    }
+}
+
+async fn breadcrumb(client: &Client, cmd: BreadcrumbCmd, as_json: bool, out: &mut dyn Write) -> Result<()> {
+    match cmd {
+        BreadcrumbCmd::Create { title, schema_name, tags, context, description } => {
+            let context: Value = serde_json::from_str(&context).context("--context must be JSON")?;
+            let create = BreadcrumbCreate {
+                title,
+                description,
+                semantic_version: None,
+                context,
+                tags,
+                schema_name,
+                llm_hints: None,
+                visibility: None,
+                sensitivity: None,
+                ttl: None,
+                ttl_type: None,
+                ttl_config: None,
+                ttl_source: None,
+                entity_keywords: None,
+                entities: None,
+            };
+            let created = client.create_breadcrumb(&create).await?;
+            if as_json {
+                return output::json(out, &created);
+            }
+            Ok(writeln!(out, "{}", created["id"].as_str().unwrap_or_default())?)
+        }
+        BreadcrumbCmd::Get { id } => {
+            let bc = client.get_breadcrumb(id).await?;
+            if as_json {
+                return output::json(out, &bc);
+            }
+            writeln!(out, "id:       {}", bc.id)?;
+            writeln!(out, "title:    {}", bc.title)?;
+            writeln!(out, "schema:   {}", bc.schema_name.as_deref().unwrap_or("-"))?;
+            writeln!(out, "tags:     {}", bc.tags.join(", "))?;
+            writeln!(out, "version:  {}", bc.version)?;
+            writeln!(out, "updated:  {}", bc.updated_at.to_rfc3339())?;
+            writeln!(out, "context:")?;
+            Ok(writeln!(out, "{}", serde_json::to_string_pretty(&bc.context)?)?)
+        }
+        BreadcrumbCmd::List { filter, limit } => {
+            let list = client.list_breadcrumbs(&filter, limit).await?;
+            breadcrumb_table(&list, as_json, out)
+        }
+        BreadcrumbCmd::Search { query, filter, nn } => {
+            let found = client.search_breadcrumbs(&query, &filter, nn).await?;
+            breadcrumb_table(&found, as_json, out)
+        }
+        BreadcrumbCmd::Delete { id, force } => {
+            let res = client.delete_breadcrumb(id, force).await?;
+            if as_json {
+                return output::json(out, &res);
+            }
+            Ok(writeln!(out, "deleted {}", id)?)
+        }
+    }
+}
+
+fn breadcrumb_table(list: &[BreadcrumbContextView], as_json: bool, out: &mut dyn Write) -> Result<()> {
+    if as_json {
+        return output::json(out, &list);
+    }
+    let rows: Vec<Vec<String>> = list.iter().map(|bc| vec![
+        bc.id.to_string(),
+        bc.schema_name.clone().unwrap_or_default(),
+        output::truncate(&bc.title, 50),
+        output::truncate(&bc.tags.join(","), 40),
+        bc.updated_at.format("%Y-%m-%d %H:%M").to_string(),
+    ]).collect();
+    Ok(output::table(out, &["ID", "SCHEMA", "TITLE", "TAGS", "UPDATED"], &rows)?)
+}
+
+/// `any:a,b all:c none:d`
+fn tag_summary(selector: &Selector) -> String {
+    let parts = [("any", &selector.any_tags), ("all", &selector.all_tags), ("none", &selector.none_tags)];
+    parts.iter()
+        .filter_map(|(label, tags)| tags.as_ref().filter(|t| !t.is_empty()).map(|t| format!("{}:{}", label, t.join(","))))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,29 @@
+/*!
+ * rcrt: operator CLI for rcrt-server
+ *
+ * Environment (each also a flag, and a key in ~/.rcrt/config.toml):
+ * - RCRT_URL      (default http://localhost:8081)
+ * - RCRT_TOKEN    JWT, or RCRT_API_KEY for an agent API key
+ * - RCRT_OWNER_ID, RCRT_AGENT_ID  (--owner, --agent)
+ * - RCRT_CONFIG   config file path (default ~/.rcrt/config.toml)
+ */
+
+use std::process::ExitCode;
+use clap::Parser;
+use rcrt_cli::{cli::Cli, config::Config, run};
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match Config::load(&cli.global) {
+        Ok(config) => run(cli, &config, &mut std::io::stdout().lock()).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,58 @@
+//! Output
+//! Plain left-aligned tables for people, pretty JSON for scripts (`--json`)
+
+use std::io::{self, Write};
+use serde::Serialize;
+
+pub fn json(out: &mut dyn Write, value: &impl Serialize) -> anyhow::Result<()> {
+    serde_json::to_writer_pretty(&mut *out, value)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Columns padded to their widest cell; an empty table prints `(none)`
+pub fn table(out: &mut dyn Write, headers: &[&str], rows: &[Vec<String>]) -> io::Result<()> {
+    if rows.is_empty() {
+        return writeln!(out, "(none)");
+    }
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let header: Vec<String> = headers.iter().map(|h| h.to_string()).collect();
+    for row in std::iter::once(&header).chain(rows) {
+        let cells: Vec<String> = row.iter().zip(&widths).map(|(cell, width)| format!("{:<width$}", cell, width = width)).collect();
+        writeln!(out, "{}", cells.join("  ").trim_end())?;
+    }
+    Ok(())
+}
+
+/// Long titles and tag lists cut to fit a terminal row
+pub fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut cut: String = text.chars().take(max.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_pads_columns() {
+        let mut out = Vec::new();
+        table(&mut out, &["ID", "TITLE"], &[vec!["1".into(), "short".into()], vec!["22".into(), "a longer one".into()]]).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "ID  TITLE\n1   short\n22  a longer one\n");
+
+        let mut out = Vec::new();
+        table(&mut out, &["ID"], &[]).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "(none)\n");
+        assert_eq!(truncate("abcdef", 4), "abc…");
+        assert_eq!(truncate("abc", 4), "abc");
+    }
+}
@@ -0,0 +1,211 @@
+//! Each subcommand against a wiremock server: the request it sends and what it prints
+
+use clap::Parser;
+use rcrt_cli::{cli::Cli, config::{Config, FileConfig}, run};
+use serde_json::{json, Value};
+use wiremock::matchers::{body_json, body_partial_json, header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const ID: &str = "00000000-0000-0000-0000-0000000000b1";
+const OWNER: &str = "00000000-0000-0000-0000-000000000001";
+const AGENT: &str = "00000000-0000-0000-0000-0000000000aa";
+
+/// `rcrt --url <mock> --token t0k <args>`
+async fn rcrt(server: &MockServer, args: &[&str]) -> anyhow::Result<String> {
+    let uri = server.uri();
+    let mut argv = vec!["rcrt", "--url", &uri, "--token", "t0k"];
+    argv.extend_from_slice(args);
+    let cli = Cli::try_parse_from(argv)?;
+    let config = Config::resolve(&cli.global, FileConfig::default());
+    let mut out = Vec::new();
+    run(cli, &config, &mut out).await?;
+    Ok(String::from_utf8(out)?)
+}
+
+fn view(title: &str, tags: &[&str]) -> Value {
+    json!({
+        "id": ID, "title": title, "description": null, "semantic_version": null, "context": { "text": "hi" },
+        "tags": tags, "schema_name": "note.v1", "llm_hints": null, "version": 2, "updated_at": "2025-06-01T12:30:00Z"
+    })
+}
+
+fn authorized(verb: &str, route: &str) -> wiremock::MockBuilder {
+    Mock::given(method(verb)).and(path(route)).and(header("authorization", "Bearer t0k"))
+}
+
+#[tokio::test]
+async fn test_breadcrumb_create() {
+    let server = MockServer::start().await;
+    authorized("POST", "/breadcrumbs")
+        .and(body_partial_json(json!({
+            "title": "Hello", "schema_name": "note.v1", "tags": ["a", "b"], "context": { "text": "hi" }
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": ID })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let out = rcrt(&server, &["breadcrumb", "create", "--title", "Hello", "--schema", "note.v1", "--tag", "a", "--tag", "b", "--context", r#"{"text":"hi"}"#]).await.unwrap();
+    assert_eq!(out, format!("{}\n", ID));
+    let err = rcrt(&server, &["breadcrumb", "create", "--title", "Bad", "--context", "not json"]).await.unwrap_err();
+    assert!(err.to_string().contains("--context must be JSON"));
+}
+
+#[tokio::test]
+async fn test_breadcrumb_get() {
+    let server = MockServer::start().await;
+    authorized("GET", &format!("/breadcrumbs/{}", ID))
+        .respond_with(ResponseTemplate::new(200).set_body_json(view("Hello", &["a"])))
+        .mount(&server)
+        .await;
+
+    let out = rcrt(&server, &["breadcrumb", "get", ID]).await.unwrap();
+    assert!(out.contains("title:    Hello"));
+    assert!(out.contains("\"text\": \"hi\""));
+    let out: Value = serde_json::from_str(&rcrt(&server, &["--json", "breadcrumb", "get", ID]).await.unwrap()).unwrap();
+    assert_eq!(out["version"], 2);
+}
+
+#[tokio::test]
+async fn test_breadcrumb_list() {
+    let server = MockServer::start().await;
+    authorized("GET", "/breadcrumbs")
+        .and(query_param("include_context", "true"))
+        .and(query_param("tag", "kb"))
+        .and(query_param("session", "s1"))
+        .and(query_param("limit", "3"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([view("First", &["kb", "session:s1"])])))
+        .mount(&server)
+        .await;
+
+    let out = rcrt(&server, &["breadcrumb", "list", "--tag", "kb", "--session", "s1", "--limit", "3"]).await.unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+    assert!(lines[0].starts_with("ID") && lines[0].contains("TITLE"));
+    assert!(lines[1].starts_with(ID) && lines[1].contains("First") && lines[1].contains("2025-06-01 12:30"));
+}
+
+#[tokio::test]
+async fn test_breadcrumb_search() {
+    let server = MockServer::start().await;
+    authorized("GET", "/breadcrumbs/search")
+        .and(query_param("q", "deploy steps"))
+        .and(query_param("nn", "2"))
+        .and(query_param("schema_name", "note.v1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(&server)
+        .await;
+
+    let out = rcrt(&server, &["breadcrumb", "search", "deploy steps", "--nn", "2", "--schema", "note.v1"]).await.unwrap();
+    assert_eq!(out, "(none)\n");
+}
+
+#[tokio::test]
+async fn test_breadcrumb_delete_reports_server_errors() {
+    let server = MockServer::start().await;
+    authorized("DELETE", &format!("/breadcrumbs/{}", ID))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "ok": true })))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    authorized("DELETE", &format!("/breadcrumbs/{}", ID))
+        .respond_with(ResponseTemplate::new(404).set_body_string("not found"))
+        .mount(&server)
+        .await;
+
+    assert_eq!(rcrt(&server, &["breadcrumb", "delete", ID]).await.unwrap(), format!("deleted {}\n", ID));
+    let err = rcrt(&server, &["breadcrumb", "delete", ID]).await.unwrap_err();
+    assert_eq!(err.to_string(), "404 Not Found: not found");
+}
+
+#[tokio::test]
+async fn test_breadcrumb_delete_force() {
+    let server = MockServer::start().await;
+    authorized("DELETE", &format!("/breadcrumbs/{}", ID))
+        .and(query_param("force", "true"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "ok": true, "broken_references": 2 })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let out: Value = serde_json::from_str(&rcrt(&server, &["--json", "breadcrumb", "delete", ID, "--force"]).await.unwrap()).unwrap();
+    assert_eq!(out["broken_references"], 2);
+}
+
+#[tokio::test]
+async fn test_selector_list_and_create() {
+    let server = MockServer::start().await;
+    let subscription = json!({
+        "id": ID, "owner_id": OWNER, "agent_id": AGENT,
+        "selector": { "any_tags": ["a"], "all_tags": null, "schema_name": "note.v1", "context_match": null },
+        "channels": ["sse", "webhook"]
+    });
+    authorized("GET", "/subscriptions/selectors")
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([subscription])))
+        .mount(&server)
+        .await;
+    authorized("POST", "/subscriptions/selectors")
+        .and(body_json(json!({
+            "any_tags": ["a"], "all_tags": null, "schema_name": "note.v1", "context_match": null, "channels": ["sse", "webhook"]
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(subscription))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let out = rcrt(&server, &["selector", "list"]).await.unwrap();
+    assert!(out.lines().nth(1).unwrap().contains("any:a") && out.contains("sse,webhook"));
+    let out = rcrt(&server, &["selector", "create", "--any-tag", "a", "--schema", "note.v1", "--channel", "sse", "--channel", "webhook"]).await.unwrap();
+    assert_eq!(out, format!("{}\n", ID));
+    assert!(rcrt(&server, &["selector", "create", "--channel", "pigeon"]).await.is_err());
+}
+
+#[tokio::test]
+async fn test_dlq_list_and_retry() {
+    let server = MockServer::start().await;
+    authorized("GET", "/dlq")
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "id": ID, "agent_id": AGENT, "url": "https://hooks.example.com", "payload": {},
+            "last_error": "connection refused", "last_status": 502, "created_at": "2025-06-01T12:00:00Z"
+        }])))
+        .mount(&server)
+        .await;
+    authorized("POST", &format!("/dlq/{}/retry", ID))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "ok": true })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let out = rcrt(&server, &["dlq", "list"]).await.unwrap();
+    assert!(out.contains("https://hooks.example.com") && out.contains("502") && out.contains("connection refused"));
+    assert_eq!(rcrt(&server, &["dlq", "retry", ID]).await.unwrap(), format!("retried {}\n", ID));
+}
+
+#[tokio::test]
+async fn test_agent_register_defaults_to_agent_flag() {
+    let server = MockServer::start().await;
+    authorized("POST", &format!("/agents/{}", AGENT))
+        .and(body_json(json!({ "roles": ["emitter", "subscriber"] })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "ok": true })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let out = rcrt(&server, &["--agent", AGENT, "agent", "register", "--role", "emitter", "--role", "subscriber"]).await.unwrap();
+    assert_eq!(out, format!("registered {} as emitter,subscriber\n", AGENT));
+}
+
+#[tokio::test]
+async fn test_token_mint() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/auth/token"))
+        .and(body_json(json!({ "owner_id": OWNER, "agent_id": AGENT, "roles": ["emitter"], "ttl_sec": 60 })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "token": "jwt.jwt.jwt", "owner_id": OWNER, "agent_id": AGENT, "roles": ["emitter"], "exp": 1
+        })))
+        .mount(&server)
+        .await;
+
+    let out = rcrt(&server, &["--owner", OWNER, "--agent", AGENT, "token", "mint", "--role", "emitter", "--ttl-sec", "60"]).await.unwrap();
+    assert_eq!(out, "jwt.jwt.jwt\n");
+}
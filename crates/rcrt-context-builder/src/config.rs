@@ -2,8 +2,9 @@
  * Configuration management for Context Builder service
  */
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use serde::Deserialize;
+use uuid::Uuid;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
@@ -13,11 +14,8 @@ pub struct Config {
     /// PostgreSQL connection string
     pub database_url: String,
     
-    /// Owner ID for authentication
-    pub owner_id: String,
-    
-    /// Agent ID for this service
-    pub agent_id: String,
+    /// Tenants served by this instance (one token and SSE subscription each)
+    pub owners: Vec<OwnerConfig>,
     
     /// Maximum database connections
     #[serde(default = "default_max_db_connections")]
@@ -30,6 +28,95 @@ pub struct Config {
     /// Maximum sessions to cache
     #[serde(default = "default_max_sessions")]
     pub max_sessions: usize,
+    
+    /// Serialized context bytes a cached graph node keeps; larger contexts are trimmed
+    #[serde(default = "default_graph_node_max_context_bytes")]
+    pub graph_node_max_context_bytes: usize,
+    
+    /// Total token budget for an assembled context when the consumer's agent.def.v1 names no
+    /// llm.config.v1 with a `context_window`
+    #[serde(default = "default_context_token_budget")]
+    pub context_token_budget: usize,
+    
+    /// Tokens reserved for system prompt and formatting overhead when the consumer's agent.def.v1
+    /// has no `system_prompt` to count
+    #[serde(default = "default_context_overhead_tokens")]
+    pub context_overhead_tokens: usize,
+    
+    /// API retries before a context publish counts as failed
+    #[serde(default = "default_publish_retries")]
+    pub publish_retries: u32,
+    
+    /// Retries of an API call that got a 5xx or no response
+    #[serde(default = "default_api_retries")]
+    pub api_retries: u32,
+    
+    /// Backoff before the first API retry, doubling per attempt
+    #[serde(default = "default_api_retry_backoff_ms")]
+    pub api_retry_backoff_ms: u64,
+    
+    /// Write contexts directly to Postgres when the API is unreachable
+    #[serde(default = "default_context_db_fallback")]
+    pub context_db_fallback: bool,
+    
+    /// How far back the event handler catches up via /events/missed on startup
+    #[serde(default = "default_startup_catchup_secs")]
+    pub startup_catchup_secs: i64,
+    
+    /// Entity extraction tasks per owner; a breadcrumb's events always go to the same task
+    #[serde(default = "default_entity_worker_concurrency")]
+    pub entity_worker_concurrency: usize,
+    
+    /// Breadcrumbs queued for entity extraction per owner before the oldest are dropped for backfill
+    #[serde(default = "default_entity_queue_capacity")]
+    pub entity_queue_capacity: usize,
+    
+    /// Title share of find_similar distances (0..=1); 0 uses the content embedding alone
+    #[serde(default)]
+    pub similarity_title_weight: f32,
+    
+    /// Semantic seeds scoring below this similarity (0..=1) are left out; agent.def.v1
+    /// `context_sources.semantic.min_similarity` overrides it per consumer. 0 keeps every candidate
+    #[serde(default)]
+    pub semantic_seed_min_similarity: f64,
+    
+    /// Address of the /metrics listener; empty disables it
+    #[serde(default = "default_metrics_addr")]
+    pub metrics_addr: String,
+    
+    /// /ready fails once an SSE stream has gone this long without an event or heartbeat
+    #[serde(default = "default_ready_sse_max_age_secs")]
+    pub ready_sse_max_age_secs: i64,
+    
+    /// Restarts of a worker (or failed SSE reconnects) within the window before giving up
+    #[serde(default = "default_worker_max_restarts")]
+    pub worker_max_restarts: u32,
+    
+    /// Window the restart budget counts over; a worker restarted within it makes /ready degraded
+    #[serde(default = "default_worker_restart_window_secs")]
+    pub worker_restart_window_secs: u64,
+    
+    /// Backoff before the first restart in the window, doubling per restart up to a minute
+    #[serde(default = "default_worker_restart_backoff_ms")]
+    pub worker_restart_backoff_ms: u64,
+    
+    /// Bearer token for POST /debug/assemble on the metrics listener; unset or empty leaves it off
+    #[serde(default)]
+    pub debug_token: Option<String>,
+}
+
+/// One tenant served by this instance
+#[derive(Debug, Clone, Deserialize)]
+pub struct OwnerConfig {
+    pub owner_id: Uuid,
+    
+    /// Agent ID this service authenticates as for the owner
+    #[serde(default = "default_agent_id")]
+    pub agent_id: String,
+}
+
+fn default_agent_id() -> String {
+    std::env::var("AGENT_ID").unwrap_or_else(|_| "context-builder-service".to_string())
 }
 
 fn default_max_db_connections() -> u32 {
@@ -44,6 +131,66 @@ fn default_max_sessions() -> usize {
     100
 }
 
+fn default_graph_node_max_context_bytes() -> usize {
+    16 * 1024
+}
+
+fn default_context_token_budget() -> usize {
+    16000
+}
+
+fn default_context_overhead_tokens() -> usize {
+    1500
+}
+
+fn default_publish_retries() -> u32 {
+    2
+}
+
+fn default_api_retries() -> u32 {
+    2
+}
+
+fn default_api_retry_backoff_ms() -> u64 {
+    250
+}
+
+fn default_context_db_fallback() -> bool {
+    true
+}
+
+fn default_startup_catchup_secs() -> i64 {
+    300
+}
+
+fn default_entity_worker_concurrency() -> usize {
+    4
+}
+
+fn default_entity_queue_capacity() -> usize {
+    1000
+}
+
+fn default_metrics_addr() -> String {
+    "0.0.0.0:9091".to_string()
+}
+
+fn default_ready_sse_max_age_secs() -> i64 {
+    30 // six missed 5s heartbeats
+}
+
+fn default_worker_max_restarts() -> u32 {
+    5
+}
+
+fn default_worker_restart_window_secs() -> u64 {
+    300
+}
+
+fn default_worker_restart_backoff_ms() -> u64 {
+    1000
+}
+
 impl Config {
     pub fn from_env() -> Result<Self> {
         dotenvy::dotenv().ok();
@@ -53,10 +200,7 @@ impl Config {
                 .unwrap_or_else(|_| "http://localhost:8080".to_string()),
             database_url: std::env::var("DATABASE_URL")
                 .expect("DATABASE_URL must be set"),
-            owner_id: std::env::var("OWNER_ID")
-                .unwrap_or_else(|_| "00000000-0000-0000-0000-000000000001".to_string()),
-            agent_id: std::env::var("AGENT_ID")
-                .unwrap_or_else(|_| "context-builder-service".to_string()),
+            owners: load_owners()?,
             max_db_connections: std::env::var("MAX_DB_CONNECTIONS")
                 .ok()
                 .and_then(|v| v.parse().ok())
@@ -69,9 +213,138 @@ impl Config {
                 .ok()
                 .and_then(|v| v.parse().ok())
                 .unwrap_or_else(default_max_sessions),
+            graph_node_max_context_bytes: std::env::var("GRAPH_NODE_MAX_CONTEXT_BYTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_else(default_graph_node_max_context_bytes),
+            context_token_budget: std::env::var("CONTEXT_TOKEN_BUDGET")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_else(default_context_token_budget),
+            context_overhead_tokens: std::env::var("CONTEXT_OVERHEAD_TOKENS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_else(default_context_overhead_tokens),
+            publish_retries: std::env::var("PUBLISH_RETRIES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_else(default_publish_retries),
+            api_retries: std::env::var("API_RETRIES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_else(default_api_retries),
+            api_retry_backoff_ms: std::env::var("API_RETRY_BACKOFF_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_else(default_api_retry_backoff_ms),
+            context_db_fallback: std::env::var("CONTEXT_DB_FALLBACK")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_else(default_context_db_fallback),
+            startup_catchup_secs: std::env::var("STARTUP_CATCHUP_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_else(default_startup_catchup_secs),
+            entity_worker_concurrency: std::env::var("ENTITY_WORKER_CONCURRENCY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_else(default_entity_worker_concurrency)
+                .max(1),
+            entity_queue_capacity: std::env::var("ENTITY_QUEUE_CAPACITY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_else(default_entity_queue_capacity)
+                .max(1),
+            similarity_title_weight: std::env::var("SIMILARITY_TITLE_WEIGHT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_default(),
+            semantic_seed_min_similarity: std::env::var("SEMANTIC_SEED_MIN_SIMILARITY")
+                .ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .unwrap_or_default()
+                .clamp(0.0, 1.0),
+            metrics_addr: std::env::var("METRICS_ADDR")
+                .unwrap_or_else(|_| default_metrics_addr()),
+            ready_sse_max_age_secs: std::env::var("READY_SSE_MAX_AGE_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_else(default_ready_sse_max_age_secs),
+            worker_max_restarts: std::env::var("WORKER_MAX_RESTARTS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_else(default_worker_max_restarts),
+            worker_restart_window_secs: std::env::var("WORKER_RESTART_WINDOW_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_else(default_worker_restart_window_secs),
+            worker_restart_backoff_ms: std::env::var("WORKER_RESTART_BACKOFF_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_else(default_worker_restart_backoff_ms),
+            debug_token: std::env::var("DEBUG_TOKEN")
+                .ok()
+                .filter(|token| !token.is_empty()),
         };
         
         Ok(config)
     }
 }
 
+/// OWNERS_JSON (inline) or OWNERS_FILE (path to the same JSON), e.g.
+/// `[{"owner_id": "...", "agent_id": "..."}]`; without either, the single OWNER_ID/AGENT_ID pair
+fn load_owners() -> Result<Vec<OwnerConfig>> {
+    if let Ok(json) = std::env::var("OWNERS_JSON") {
+        return parse_owners(&json).context("invalid OWNERS_JSON");
+    }
+    if let Ok(path) = std::env::var("OWNERS_FILE") {
+        let json = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read OWNERS_FILE {}", path))?;
+        return parse_owners(&json).with_context(|| format!("invalid OWNERS_FILE {}", path));
+    }
+    let owner_id = std::env::var("OWNER_ID")
+        .unwrap_or_else(|_| "00000000-0000-0000-0000-000000000001".to_string());
+    Ok(vec![OwnerConfig {
+        owner_id: owner_id.parse().with_context(|| format!("OWNER_ID is not a UUID: {}", owner_id))?,
+        agent_id: default_agent_id(),
+    }])
+}
+
+fn parse_owners(json: &str) -> Result<Vec<OwnerConfig>> {
+    let owners: Vec<OwnerConfig> = serde_json::from_str(json)?;
+    if owners.is_empty() {
+        anyhow::bail!("owner list is empty");
+    }
+    let mut seen = std::collections::HashSet::new();
+    for owner in &owners {
+        if !seen.insert(owner.owner_id) {
+            anyhow::bail!("owner {} is listed twice", owner.owner_id);
+        }
+    }
+    Ok(owners)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_owners() {
+        let owners = parse_owners(r#"[
+            {"owner_id": "00000000-0000-0000-0000-000000000001", "agent_id": "00000000-0000-0000-0000-0000000000cb"},
+            {"owner_id": "00000000-0000-0000-0000-000000000002"}
+        ]"#).unwrap();
+        assert_eq!(owners.len(), 2);
+        assert_eq!(owners[0].agent_id, "00000000-0000-0000-0000-0000000000cb");
+        assert_eq!(owners[1].owner_id, Uuid::from_u128(2));
+        assert_eq!(owners[1].agent_id, default_agent_id());
+    }
+
+    #[test]
+    fn test_parse_owners_rejects_empty_duplicate_and_bad_ids() {
+        assert!(parse_owners("[]").is_err());
+        assert!(parse_owners(r#"[{"owner_id": "not-a-uuid"}]"#).is_err());
+        let dup = r#"[{"owner_id": "00000000-0000-0000-0000-000000000001"}, {"owner_id": "00000000-0000-0000-0000-000000000001"}]"#;
+        assert!(parse_owners(dup).is_err());
+    }
+}
@@ -0,0 +1,287 @@
+/*!
+ * Context assembly dry runs: POST /debug/assemble
+ *
+ * Runs the event handler's pipeline (trigger → seeds → session graph → paths → formatting)
+ * for a message that was never written, and returns what would have been published, marked
+ * `"simulation": true`. Nothing is created or published: assembly reads through a pool whose
+ * transactions are read-only, the llm_hints fetch asks the server not to record access, and
+ * the publisher's write and unchanged-context bookkeeping are never reached. Mounted on the
+ * metrics listener only when DEBUG_TOKEN is set, and requests must send it as a bearer token.
+ */
+
+use axum::{body::Bytes, extract::State, http::{header, HeaderMap, StatusCode}, Json};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::postgres::{PgConnectOptions, PgPool, PgPoolOptions};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use uuid::Uuid;
+
+use crate::event_handler::{Assembly, ContextPipeline, Trigger};
+use crate::latency::AssemblyTiming;
+use crate::output::{ContextApi, ContextPublisher};
+use crate::rcrt_client::RcrtClient;
+
+/// Connections of the read-only pool; dry runs are occasional
+const POOL_SIZE: u32 = 2;
+
+#[derive(Debug, Deserialize)]
+pub struct DryRunRequest {
+    /// The consumer to assemble for, as `consumer_id` in published contexts; its agent.def.v1 applies
+    pub agent_id: String,
+    /// `session:<id>`; a bare id gets the prefix
+    pub session_tag: String,
+    pub hypothetical_message: HypotheticalMessage,
+    /// Only needed when this instance serves more than one owner
+    #[serde(default)]
+    pub owner_id: Option<Uuid>,
+}
+
+/// The trigger as it would be written, e.g. a user.message.v1
+#[derive(Debug, Deserialize)]
+pub struct HypotheticalMessage {
+    #[serde(default)]
+    pub title: String,
+    pub context: Value,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// A pool whose every transaction is read-only, so a dry run can't write even by mistake
+pub fn read_only_pool(options: PgConnectOptions) -> PgPool {
+    PgPoolOptions::new()
+        .max_connections(POOL_SIZE)
+        .connect_lazy_with(options.options([("default_transaction_read_only", "on")]))
+}
+
+/// Every owner's dry-run pipeline, filled in as owners start
+pub struct DryRun {
+    token: String,
+    pool: PgPool,
+    owners: RwLock<HashMap<Uuid, Arc<OwnerDryRun>>>,
+}
+
+impl DryRun {
+    pub fn new(token: String, pool: PgPool) -> Self {
+        DryRun { token, pool, owners: RwLock::new(HashMap::new()) }
+    }
+
+    /// The read-only pool owner stores are put on (see VectorStore::on_pool)
+    pub fn pool(&self) -> &PgPool {
+        &self.pool
+    }
+
+    /// Called once an owner's workers are running
+    pub fn add_owner(&self, owner_id: Uuid, dry_run: OwnerDryRun) {
+        self.owners.write().unwrap().insert(owner_id, Arc::new(dry_run));
+    }
+}
+
+/// One owner's pipeline over the read-only pool, and a publisher that is only asked to render
+pub struct OwnerDryRun<C: ContextApi = RcrtClient> {
+    pipeline: ContextPipeline,
+    publisher: ContextPublisher<C>,
+}
+
+impl<C: ContextApi> OwnerDryRun<C> {
+    pub fn new(pipeline: ContextPipeline, publisher: ContextPublisher<C>) -> Self {
+        OwnerDryRun { pipeline, publisher }
+    }
+
+    /// The context `req`'s message would have got, laid out like a published agent.context.v1
+    pub async fn simulate(&self, req: DryRunRequest) -> anyhow::Result<Value> {
+        let session_tag = if req.session_tag.starts_with("session:") {
+            req.session_tag
+        } else {
+            format!("session:{}", req.session_tag)
+        };
+        let message = req.hypothetical_message;
+        let trigger = Trigger::Hypothetical { title: message.title.clone(), context: message.context };
+        let mut timing = AssemblyTiming::default();
+        let Assembly { config, context, budget, agent_def } = self.pipeline
+            .assemble(&req.agent_id, &session_tag, Some(trigger), &mut timing)
+            .await?;
+        let rendered = self.publisher
+            .render_context(&config.consumer_id, &context, &budget, agent_def.as_ref(), false)
+            .await?;
+
+        let mut response = json!({
+            "simulation": true,
+            "consumer_id": config.consumer_id,
+            "session_tag": session_tag,
+            "trigger": { "title": message.title, "tags": message.tags, "tokens": budget.trigger },
+            "semantic_path": config.semantic_path.map(|path| path.as_str()),
+            "token_estimate": rendered.token_estimate,
+            "token_budget": budget.total,
+            "truncated": rendered.truncated,
+            "sources_assembled": context.sources_count,
+            "breadcrumbs": rendered.breadcrumbs,
+            "formatted_context": rendered.formatted_context,
+        });
+        for (key, value) in rendered.provenance {
+            response[key.as_str()] = value;
+        }
+        if context.provenance.is_some() {
+            response["provenance_timing"] = json!(timing);
+        }
+        Ok(response)
+    }
+}
+
+/// `Authorization: Bearer <DEBUG_TOKEN>`, compared in constant time
+fn authorized(headers: &HeaderMap, token: &str) -> bool {
+    let presented = headers.get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or("");
+    presented.len() == token.len()
+        && presented.bytes().zip(token.bytes()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// POST /debug/assemble; the body is only read once the token checks out
+pub async fn assemble(State(dry_run): State<Arc<DryRun>>, headers: HeaderMap, body: Bytes) -> Result<Json<Value>, (StatusCode, String)> {
+    if !authorized(&headers, &dry_run.token) {
+        return Err((StatusCode::UNAUTHORIZED, "missing or wrong debug token".to_string()));
+    }
+    let req: DryRunRequest = serde_json::from_slice(&body)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("invalid request: {}", e)))?;
+    let (owner_id, owner) = {
+        let owners = dry_run.owners.read().unwrap();
+        match req.owner_id {
+            Some(owner_id) => owners.get(&owner_id)
+                .map(|owner| (owner_id, owner.clone()))
+                .ok_or((StatusCode::NOT_FOUND, format!("owner {} is not served here or still starting", owner_id)))?,
+            None if owners.len() == 1 => owners.iter().next().map(|(id, owner)| (*id, owner.clone())).unwrap(),
+            None => return Err((StatusCode::BAD_REQUEST, format!("{} owners are served here; pass owner_id", owners.len()))),
+        }
+    };
+    let mut response = owner.simulate(req).await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("dry run failed: {:#}", e)))?;
+    response["owner_id"] = json!(owner_id);
+    Ok(Json(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_authorized_needs_the_exact_bearer_token() {
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::AUTHORIZATION, value.parse().unwrap());
+            headers
+        };
+        assert!(authorized(&headers("Bearer s3cret"), "s3cret"));
+        assert!(!authorized(&headers("Bearer s3cre"), "s3cret"));
+        assert!(!authorized(&headers("Bearer s3cret!"), "s3cret"));
+        assert!(!authorized(&headers("s3cret"), "s3cret"));
+        assert!(!authorized(&HeaderMap::new(), "s3cret"));
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_dry_run_writes_nothing(pool: PgPool) -> anyhow::Result<()> {
+        use crate::entity_extractor::EntityExtractor;
+        use crate::event_handler::AssemblySettings;
+        use crate::graph::SessionGraphCache;
+        use crate::rcrt_client::{BreadcrumbContextView, BulkContextViews};
+        use crate::token_counter::TokenCounter;
+        use crate::vector_store::VectorStore;
+        use rcrt_core::db::Db;
+        use rcrt_core::models::BreadcrumbCreate;
+        use std::sync::Mutex;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        const SESSION: &str = "session:dry-run-test";
+
+        /// Serves bulk_get from the database rows it is given and counts writes
+        #[derive(Default)]
+        struct RecordingApi {
+            contexts: HashMap<Uuid, Value>,
+            record_access: Mutex<Vec<bool>>,
+            upserts: AtomicUsize,
+        }
+
+        impl ContextApi for RecordingApi {
+            async fn get_breadcrumbs(&self, ids: &[Uuid], record_access: bool) -> anyhow::Result<BulkContextViews> {
+                self.record_access.lock().unwrap().push(record_access);
+                let breadcrumbs = ids.iter().filter_map(|id| self.contexts.get(id).map(|context| BreadcrumbContextView {
+                    id: *id,
+                    title: "message".to_string(),
+                    context: context.clone(),
+                    tags: vec![SESSION.to_string()],
+                    schema_name: Some("user.message.v1".to_string()),
+                    version: 1,
+                    updated_at: chrono::Utc::now(),
+                })).collect();
+                Ok(BulkContextViews { breadcrumbs, missing: Vec::new() })
+            }
+
+            async fn upsert_breadcrumb(&self, _schema_name: &str, _title: &str, _tags: Vec<String>, _key_tags: &[String], _context: Value) -> anyhow::Result<(Uuid, i32)> {
+                self.upserts.fetch_add(1, Ordering::SeqCst);
+                anyhow::bail!("dry runs must not publish")
+            }
+        }
+
+        let db = Db { pool: pool.clone() };
+        let owner = Uuid::new_v4();
+        db.ensure_tenant(owner, "dry-run-test").await?;
+        let mut contexts = HashMap::new();
+        for content in ["How do I deploy the rust service?", "Use the release pipeline."] {
+            let bc = db.create_breadcrumb_for(owner, None, None, BreadcrumbCreate {
+                title: content.to_string(),
+                description: None,
+                semantic_version: None,
+                context: json!({ "content": content }),
+                tags: vec![SESSION.to_string()],
+                schema_name: Some("user.message.v1".to_string()),
+                llm_hints: None,
+                visibility: None,
+                sensitivity: None,
+                ttl: None,
+                ttl_type: None,
+                ttl_config: None,
+                ttl_source: None,
+                entity_keywords: None,
+                entities: None,
+            }).await?;
+            contexts.insert(bc.id, json!({ "content": content }));
+        }
+        let count = || sqlx::query_scalar::<_, i64>("SELECT count(*) FROM breadcrumbs WHERE owner_id = $1").bind(owner).fetch_one(&pool);
+        let before = count().await?;
+
+        let read_only = read_only_pool((*pool.connect_options()).clone());
+        let store = Arc::new(VectorStore::new(pool.clone(), owner).on_pool(read_only.clone()));
+        let token_counter = Arc::new(TokenCounter::new("missing-tokenizer.json"));
+        let settings = AssemblySettings { context_token_budget: 16000, context_overhead_tokens: 0, semantic_seed_min_similarity: 0.0 };
+        let pipeline = ContextPipeline::new(store, Arc::new(SessionGraphCache::new(1, 1, 4096)), Arc::new(EntityExtractor::new()?), token_counter.clone(), settings);
+        let api = Arc::new(RecordingApi { contexts: contexts.clone(), ..Default::default() });
+        let dry_run = OwnerDryRun::new(pipeline, ContextPublisher::new(api.clone(), token_counter, 1));
+
+        let response = dry_run.simulate(serde_json::from_value(json!({
+            "agent_id": "default-chat-assistant",
+            "session_tag": "dry-run-test",
+            "hypothetical_message": { "title": "Follow-up", "context": { "content": "And how do I roll the rust service back?" }, "tags": [SESSION] },
+        }))?).await?;
+
+        assert_eq!(response["simulation"], true);
+        assert_eq!(response["session_tag"], SESSION);
+        // No embedder for a message that was never written
+        assert_ne!(response["semantic_path"], "hybrid");
+        let returned: Vec<Uuid> = response["breadcrumbs"].as_array().unwrap().iter()
+            .map(|bc| serde_json::from_value(bc["id"].clone()).unwrap())
+            .collect();
+        assert_eq!(returned.len(), 2);
+        assert!(returned.iter().all(|id| contexts.contains_key(id)));
+        assert!(response["formatted_context"].as_str().unwrap().contains("release pipeline"));
+
+        // Nothing published, nothing counted as read, nothing written
+        assert_eq!(api.upserts.load(Ordering::SeqCst), 0);
+        assert_eq!(*api.record_access.lock().unwrap(), vec![false]);
+        assert_eq!(count().await?, before);
+        let write = sqlx::query("UPDATE breadcrumbs SET title = title WHERE owner_id = $1").bind(owner).execute(&read_only).await;
+        assert!(write.is_err(), "the dry-run pool accepted a write");
+        Ok(())
+    }
+}
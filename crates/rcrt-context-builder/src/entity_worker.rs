@@ -5,25 +5,40 @@
  * for hybrid search. Uses SSE fan-out pattern for:
  * - Simplicity (consistent with other services)
  * - Multiple subscribers (all services receive all events)
- * - Idempotency (skips already-processed breadcrumbs)
+ * - Idempotency (skips already-processed breadcrumbs; rcrt-server's provisional
+ *   creation-time keywords are overwritten)
  */
 
 use anyhow::Result;
-use tracing::{info, warn, error};
+use tracing::{info, warn, error, Instrument};
 use uuid::Uuid;
-use std::sync::Arc;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use sqlx;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, Notify};
+use tokio::task::{JoinHandle, JoinSet};
 
-use crate::entity_extractor::EntityExtractor;
+use crate::entity_extractor::{breadcrumb_text, needs_worker_extraction, EntityExtractor, EXTRACTED_BY_WORKER};
 use crate::vector_store::VectorStore;
-use crate::rcrt_client::{RcrtClient, BreadcrumbEvent};
+use crate::rcrt_client::RcrtClient;
+use crate::metrics;
 
-/// Entity extraction worker that subscribes to SSE events
+/// How often the dispatcher checks whether drops left work for a backfill
+const DROP_BACKFILL_CHECK: Duration = Duration::from_secs(30);
+
+/// Entity extraction worker that subscribes to SSE events; a dispatcher feeds a pool of
+/// extraction tasks through a bounded queue
 pub struct EntityWorker {
     rcrt_client: Arc<RcrtClient>,
-    vector_store: Arc<VectorStore>,
-    entity_extractor: Arc<EntityExtractor>,
+    extraction: Extraction,
+    /// Extraction tasks (ENTITY_WORKER_CONCURRENCY)
+    concurrency: usize,
+    /// Jobs queued across all tasks before the oldest are dropped (ENTITY_QUEUE_CAPACITY)
+    queue_capacity: usize,
+    /// Set by the first `start`; later ones are supervisor restarts
+    started: AtomicBool,
 }
 
 impl EntityWorker {
@@ -31,45 +46,197 @@ impl EntityWorker {
         rcrt_client: Arc<RcrtClient>,
         vector_store: Arc<VectorStore>,
         entity_extractor: Arc<EntityExtractor>,
+        concurrency: usize,
+        queue_capacity: usize,
     ) -> Self {
         Self {
             rcrt_client,
-            vector_store,
-            entity_extractor,
+            extraction: Extraction { vector_store, entity_extractor },
+            concurrency,
+            queue_capacity,
+            started: AtomicBool::new(false),
         }
     }
 
     /// Start consuming from SSE and processing entity extraction
     pub async fn start(&self) -> Result<()> {
         info!("🔧 Entity worker starting SSE subscription...");
+        // After a restart, what the last run had queued or never received is left without entities
+        if self.started.swap(true, Ordering::SeqCst) {
+            startup_backfill(self.extraction.vector_store.clone(), self.extraction.entity_extractor.clone()).await?;
+        }
         
         let (tx, mut rx) = mpsc::unbounded_channel();
         
-        // Start SSE stream
-        self.rcrt_client.start_sse_stream(tx).await?;
+        // Start SSE stream; startup_backfill already covered what came before
+        self.rcrt_client.start_sse_stream(tx, chrono::Utc::now()).await?;
         
-        info!("✅ Entity worker started, listening for breadcrumb creation events via SSE...");
+        let queue = Arc::new(WorkQueue::new(self.concurrency, self.queue_capacity));
+        let mut workers = spawn_workers(&self.extraction, &queue);
+        info!("✅ Entity worker started with {} extraction tasks, listening for breadcrumb creation events via SSE...", workers.len());
         
-        // Process events
-        while let Some(event) = rx.recv().await {
-            // Only process breadcrumb creation events
-            if event.event_type == "bc.created" {
-                if let Err(e) = self.process_event(event).await {
-                    error!("❌ Entity extraction failed: {}", e);
+        // Dropped jobs are left to a backfill, run once the queue has drained
+        let mut backfill: Option<JoinHandle<()>> = None;
+        let mut check = tokio::time::interval(DROP_BACKFILL_CHECK);
+        loop {
+            tokio::select! {
+                event = rx.recv() => {
+                    let Some(event) = event else { break };
+                    // Only process breadcrumb creation events
+                    if event.event_type != "bc.created" {
+                        continue;
+                    }
+                    metrics::events().with_label_values(&["entities", &event.event_type, "received"]).inc();
+                    let Some(breadcrumb_id) = event.breadcrumb_id else { continue };
+                    queue.push(Job { breadcrumb_id, schema_name: event.schema_name, queued_at: Instant::now() });
+                }
+                // A task only ends early by panicking; its shard would never drain, so stop and
+                // let the supervisor restart the worker (dropping the set aborts the other tasks)
+                Some(stopped) = workers.join_next() => {
+                    let cause = stopped.err().map(|e| e.to_string()).unwrap_or_else(|| "returned".to_string());
+                    anyhow::bail!("entity extraction task stopped: {}", cause);
+                }
+                _ = check.tick() => {
+                    if backfill.as_ref().is_some_and(|b| !b.is_finished()) || queue.depth() > 0 || !queue.take_dropped() {
+                        continue;
+                    }
+                    let extraction = self.extraction.clone();
+                    backfill = Some(tokio::spawn(async move {
+                        info!("🔄 Entity queue dropped jobs, backfilling...");
+                        if let Err(e) = startup_backfill(extraction.vector_store, extraction.entity_extractor).await {
+                            error!("⚠️  Backfill after queue drops failed: {}", e);
+                        }
+                    }.in_current_span()));
                 }
             }
         }
         
+        queue.close();
+        while workers.join_next().await.is_some() {}
         Ok(())
     }
+}
 
-    /// Process a single SSE event
-    async fn process_event(&self, event: BreadcrumbEvent) -> Result<()> {
-        // Skip events without breadcrumb_id
-        let Some(bc_id) = event.breadcrumb_id else {
-            return Ok(());
+/// A breadcrumb waiting for extraction
+struct Job {
+    breadcrumb_id: Uuid,
+    schema_name: Option<String>,
+    queued_at: Instant,
+}
+
+/// One extraction task's share of the queue; full means the oldest job is dropped
+struct Shard {
+    jobs: Mutex<VecDeque<Job>>,
+    ready: Notify,
+}
+
+/// Bounded queue sharded by breadcrumb id, so events for one breadcrumb are handled by one task in order
+struct WorkQueue {
+    shards: Vec<Shard>,
+    shard_capacity: usize,
+    closed: AtomicBool,
+    /// Jobs dropped since the last `take_dropped`
+    dropped: AtomicU64,
+}
+
+impl WorkQueue {
+    fn new(concurrency: usize, capacity: usize) -> Self {
+        let concurrency = concurrency.max(1);
+        WorkQueue {
+            shards: (0..concurrency).map(|_| Shard { jobs: Mutex::new(VecDeque::new()), ready: Notify::new() }).collect(),
+            shard_capacity: (capacity / concurrency).max(1),
+            closed: AtomicBool::new(false),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    fn shard_of(&self, breadcrumb_id: Uuid) -> usize {
+        (breadcrumb_id.as_u128() % self.shards.len() as u128) as usize
+    }
+
+    fn push(&self, job: Job) {
+        let shard = &self.shards[self.shard_of(job.breadcrumb_id)];
+        let dropped = {
+            let mut jobs = shard.jobs.lock().unwrap();
+            jobs.push_back(job);
+            if jobs.len() > self.shard_capacity { jobs.pop_front() } else { None }
         };
-        let schema = event.schema_name.as_deref().unwrap_or("unknown");
+        match dropped {
+            Some(old) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                metrics::entity_queue_dropped().inc();
+                warn!("⚠️  Entity queue full, dropped breadcrumb {} (left to backfill)", old.breadcrumb_id);
+            }
+            None => metrics::entity_queue_depth().inc(),
+        }
+        shard.ready.notify_one();
+    }
+
+    /// The next job for task `shard`; None once the queue is closed and that shard drained
+    async fn pop(&self, shard: usize) -> Option<Job> {
+        let shard = &self.shards[shard];
+        loop {
+            if let Some(job) = shard.jobs.lock().unwrap().pop_front() {
+                metrics::entity_queue_depth().dec();
+                return Some(job);
+            }
+            if self.closed.load(Ordering::Acquire) {
+                return None;
+            }
+            shard.ready.notified().await;
+        }
+    }
+
+    fn depth(&self) -> usize {
+        self.shards.iter().map(|s| s.jobs.lock().unwrap().len()).sum()
+    }
+
+    /// Whether jobs were dropped since the last call
+    fn take_dropped(&self) -> bool {
+        self.dropped.swap(0, Ordering::Relaxed) > 0
+    }
+
+    /// Let the tasks finish what is queued and stop
+    fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        for shard in &self.shards {
+            shard.ready.notify_one();
+        }
+    }
+}
+
+/// One extraction task per shard, each in the caller's span
+fn spawn_workers(extraction: &Extraction, queue: &Arc<WorkQueue>) -> JoinSet<()> {
+    let mut workers = JoinSet::new();
+    for shard in 0..queue.shards.len() {
+        let (extraction, queue) = (extraction.clone(), queue.clone());
+        workers.spawn(async move {
+            while let Some(job) = queue.pop(shard).await {
+                let queued_at = job.queued_at;
+                if let Err(e) = extraction.process(job).await {
+                    metrics::events().with_label_values(&["entities", "bc.created", "errored"]).inc();
+                    error!("❌ Entity extraction failed: {}", e);
+                } else {
+                    metrics::events().with_label_values(&["entities", "bc.created", "processed"]).inc();
+                }
+                metrics::entity_extraction_latency().observe(queued_at.elapsed().as_secs_f64());
+            }
+        }.in_current_span());
+    }
+    workers
+}
+
+/// Fetch, extract and store for one breadcrumb; shared by the extraction tasks
+#[derive(Clone)]
+struct Extraction {
+    vector_store: Arc<VectorStore>,
+    entity_extractor: Arc<EntityExtractor>,
+}
+
+impl Extraction {
+    async fn process(&self, job: Job) -> Result<()> {
+        let bc_id = job.breadcrumb_id;
+        let schema = job.schema_name.as_deref().unwrap_or("unknown");
         
         info!("📨 Processing breadcrumb {} (schema: {})", bc_id, schema);
         
@@ -82,13 +249,13 @@ impl EntityWorker {
             }
         };
         
-        // Skip if already has entities (idempotent)
-        if bc_row.entities.is_some() && bc_row.entity_keywords.is_some() {
+        // Skip if already extracted (idempotent); heuristic keywords from creation are replaced
+        if !needs_worker_extraction(bc_row.entities.as_ref(), bc_row.entity_keywords.is_some()) {
             return Ok(());
         }
         
         // Extract text from breadcrumb
-        let text = self.extract_text_from_breadcrumb(&bc_row);
+        let text = breadcrumb_text(bc_row.title.as_deref(), &bc_row.context);
         
         if text.trim().is_empty() {
             return Ok(());
@@ -97,62 +264,28 @@ impl EntityWorker {
         info!("🔍 Extracting entities from {} chars of text...", text.len());
         
         // Extract entities
-        let entities = self.entity_extractor.extract(&text)?;
+        let entities = match self.entity_extractor.extract(&text) {
+            Ok(entities) => entities,
+            Err(e) => {
+                metrics::entity_extractions().with_label_values(&["worker", "failed"]).inc();
+                return Err(e);
+            }
+        };
         
         if entities.keywords.is_empty() {
+            metrics::entity_extractions().with_label_values(&["worker", "empty"]).inc();
             return Ok(());
         }
         
         // Save to database
-        let entities_json = serde_json::to_value(&entities.entities)?;
+        let entities_json = entities.to_entities_json(EXTRACTED_BY_WORKER);
         self.vector_store.update_entities(bc_id, &entities_json, &entities.keywords).await?;
+        metrics::entity_extractions().with_label_values(&["worker", "extracted"]).inc();
         
         info!("✨ Extracted entities for {}: {:?}", bc_id, entities.keywords);
         
         Ok(())
     }
-
-    /// Extract text from all relevant breadcrumb fields
-    fn extract_text_from_breadcrumb(&self, bc: &crate::vector_store::BreadcrumbRow) -> String {
-        let mut parts = Vec::new();
-        
-        // Add title
-        if let Some(title) = &bc.title {
-            if !title.is_empty() {
-                parts.push(title.clone());
-            }
-        }
-        
-        // Add common context fields
-        if let Some(content) = bc.context.get("content").and_then(|v| v.as_str()) {
-            if !content.is_empty() {
-                parts.push(content.to_string());
-            }
-        }
-        
-        if let Some(description) = bc.context.get("description").and_then(|v| v.as_str()) {
-            if !description.is_empty() {
-                parts.push(description.to_string());
-            }
-        }
-        
-        if let Some(summary) = bc.context.get("summary").and_then(|v| v.as_str()) {
-            if !summary.is_empty() {
-                parts.push(summary.to_string());
-            }
-        }
-        
-        // For code breadcrumbs, add code content
-        if let Some(code) = bc.context.get("code") {
-            if let Some(source) = code.get("source").and_then(|v| v.as_str()) {
-                if !source.is_empty() {
-                    parts.push(source.to_string());
-                }
-            }
-        }
-        
-        parts.join(" ")
-    }
 }
 
 /// Struct for backfill query results
@@ -164,30 +297,33 @@ struct BackfillRow {
     schema_name: Option<String>,
 }
 
-/// Run startup backfill for breadcrumbs without entities
+/// Extract entities for the store owner's breadcrumbs that have none; on startup, and after the
+/// entity queue dropped jobs
 pub async fn startup_backfill(
     vector_store: Arc<VectorStore>,
     entity_extractor: Arc<EntityExtractor>,
-    db_pool: &sqlx::PgPool,
 ) -> Result<()> {
     info!("🔄 Starting entity backfill for existing breadcrumbs...");
     
-    // Query breadcrumbs without entity_keywords that have embeddings
+    // Query breadcrumbs without worker entity_keywords that have embeddings
     let rows: Vec<BackfillRow> = sqlx::query_as(
         r#"
         SELECT id, title, context, schema_name
         FROM breadcrumbs 
-        WHERE entity_keywords IS NULL 
+        WHERE owner_id = $1
+        AND (entity_keywords IS NULL OR entities->>'extracted_by' = 'heuristic')
         AND embedding IS NOT NULL
         ORDER BY created_at DESC
         LIMIT 10000
         "#
     )
-    .fetch_all(db_pool)
+    .bind(vector_store.owner_id())
+    .fetch_all(vector_store.pool())
     .await?;
     
     let total = rows.len();
     info!("📊 Found {} breadcrumbs to backfill", total);
+    metrics::backfill_rows().with_label_values(&["found"]).inc_by(total as u64);
     
     if total == 0 {
         info!("✅ No breadcrumbs need backfilling");
@@ -199,16 +335,11 @@ pub async fn startup_backfill(
     
     for (i, row) in rows.iter().enumerate() {
         // Extract text
-        let text = format!(
-            "{} {} {} {}",
-            row.title.as_deref().unwrap_or(""),
-            row.context.get("content").and_then(|v| v.as_str()).unwrap_or(""),
-            row.context.get("description").and_then(|v| v.as_str()).unwrap_or(""),
-            row.context.get("summary").and_then(|v| v.as_str()).unwrap_or("")
-        );
+        let text = breadcrumb_text(row.title.as_deref(), &row.context);
         
         if text.trim().is_empty() {
             skipped += 1;
+            metrics::backfill_rows().with_label_values(&["skipped"]).inc();
             continue;
         }
         
@@ -216,30 +347,31 @@ pub async fn startup_backfill(
         match entity_extractor.extract(&text) {
             Ok(entities) => {
                 if !entities.keywords.is_empty() {
-                    match serde_json::to_value(&entities.entities) {
-                        Ok(entities_json) => {
-                            if let Err(e) = vector_store.update_entities(row.id, &entities_json, &entities.keywords).await {
-                                error!("❌ Failed to update entities for {}: {}", row.id, e);
-                            } else {
-                                processed += 1;
-                                if (i + 1) % 100 == 0 {
-                                    info!("📊 Backfilled {}/{} breadcrumbs ({} processed, {} skipped)", 
-                                        i + 1, total, processed, skipped);
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            error!("❌ Failed to serialize entities for {}: {}", row.id, e);
-                            skipped += 1;
+                    let entities_json = entities.to_entities_json(EXTRACTED_BY_WORKER);
+                    if let Err(e) = vector_store.update_entities(row.id, &entities_json, &entities.keywords).await {
+                        error!("❌ Failed to update entities for {}: {}", row.id, e);
+                        metrics::entity_extractions().with_label_values(&["backfill", "failed"]).inc();
+                        metrics::backfill_rows().with_label_values(&["skipped"]).inc();
+                    } else {
+                        processed += 1;
+                        metrics::entity_extractions().with_label_values(&["backfill", "extracted"]).inc();
+                        metrics::backfill_rows().with_label_values(&["processed"]).inc();
+                        if (i + 1) % 100 == 0 {
+                            info!("📊 Backfilled {}/{} breadcrumbs ({} processed, {} skipped)", 
+                                i + 1, total, processed, skipped);
                         }
                     }
                 } else {
                     skipped += 1;
+                    metrics::entity_extractions().with_label_values(&["backfill", "empty"]).inc();
+                    metrics::backfill_rows().with_label_values(&["skipped"]).inc();
                 }
             }
             Err(e) => {
                 error!("❌ Failed to extract entities for {}: {}", row.id, e);
                 skipped += 1;
+                metrics::entity_extractions().with_label_values(&["backfill", "failed"]).inc();
+                metrics::backfill_rows().with_label_values(&["skipped"]).inc();
             }
         }
     }
@@ -250,3 +382,96 @@ pub async fn startup_backfill(
     Ok(())
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(breadcrumb_id: Uuid) -> Job {
+        Job { breadcrumb_id, schema_name: None, queued_at: Instant::now() }
+    }
+
+    #[tokio::test]
+    async fn test_full_shard_drops_its_oldest_job() {
+        let queue = WorkQueue::new(2, 4);
+        let ids: Vec<Uuid> = (0..4u128).map(|n| Uuid::from_u128(n * 2)).collect();
+        for id in &ids {
+            queue.push(job(*id));
+        }
+        // Even ids share shard 0, which holds 2
+        assert!(ids.iter().all(|id| queue.shard_of(*id) == 0));
+        assert_eq!(queue.depth(), 2);
+        assert!(queue.take_dropped());
+        assert!(!queue.take_dropped());
+        assert_eq!(queue.pop(0).await.unwrap().breadcrumb_id, ids[2]);
+        assert_eq!(queue.pop(0).await.unwrap().breadcrumb_id, ids[3]);
+
+        queue.push(job(Uuid::from_u128(1)));
+        queue.close();
+        assert_eq!(queue.pop(1).await.unwrap().breadcrumb_id, Uuid::from_u128(1));
+        assert!(queue.pop(1).await.is_none());
+        assert!(queue.pop(0).await.is_none());
+    }
+}
+
+#[cfg(all(test, feature = "db-tests"))]
+mod db_tests {
+    use super::*;
+    use rcrt_core::db::Db;
+    use rcrt_core::models::BreadcrumbCreate;
+    use sqlx::PgPool;
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_burst_past_capacity_is_fully_extracted(pool: PgPool) -> Result<()> {
+        let db = Db { pool: pool.clone() };
+        let owner = Uuid::new_v4();
+        db.ensure_tenant(owner, "entity-worker-test").await?;
+        let mut ids = Vec::new();
+        for n in 0..60 {
+            let bc = db.create_breadcrumb_for(owner, None, None, BreadcrumbCreate {
+                title: format!("Agent breadcrumb schema embedding note {}", n),
+                description: None,
+                semantic_version: None,
+                context: serde_json::json!({ "text": "The agent stores each breadcrumb with a schema and an embedding" }),
+                tags: vec![],
+                schema_name: Some("note.v1".to_string()),
+                llm_hints: None,
+                visibility: None,
+                sensitivity: None,
+                ttl: None,
+                ttl_type: None,
+                ttl_config: None,
+                ttl_source: None,
+                entity_keywords: None,
+                entities: None,
+            }).await?;
+            db.set_breadcrumb_embedding(owner, None, bc.id, vec![0.5; 384]).await?;
+            ids.push(bc.id);
+        }
+
+        let extraction = Extraction {
+            vector_store: Arc::new(VectorStore::new(pool.clone(), owner)),
+            entity_extractor: Arc::new(EntityExtractor::new()?),
+        };
+        // A burst three times the capacity, queued before any task runs
+        let queue = Arc::new(WorkQueue::new(4, 20));
+        for id in &ids {
+            queue.push(Job { breadcrumb_id: *id, schema_name: Some("note.v1".to_string()), queued_at: Instant::now() });
+        }
+        assert!(queue.take_dropped());
+        queue.close();
+        let mut workers = spawn_workers(&extraction, &queue);
+        while let Some(worker) = workers.join_next().await {
+            worker?;
+        }
+
+        // Dropped ids are left to the backfill
+        startup_backfill(extraction.vector_store.clone(), extraction.entity_extractor.clone()).await?;
+        for id in ids {
+            let row = extraction.vector_store.get_by_id(id).await?.expect("own breadcrumb");
+            assert!(row.entity_keywords.is_some_and(|k| !k.is_empty()), "{} has no keywords", id);
+            assert_eq!(row.entities.unwrap()["extracted_by"], EXTRACTED_BY_WORKER);
+        }
+        Ok(())
+    }
+}
@@ -5,45 +5,108 @@
  */
 
 use crate::{
-    config::Config,
+    config::{Config, OwnerConfig},
     rcrt_client::{RcrtClient, BreadcrumbEvent},
-    vector_store::VectorStore,
+    vector_store::{BreadcrumbRow, VectorStore},
     graph::SessionGraphCache,
-    retrieval::ContextAssembler,
-    output::ContextPublisher,
+    retrieval::{prompt_window, AssembledContext, ContextAssembler, ContextBudget, ContextConfig},
+    output::{ContextPublisher, DbFallback},
     entity_extractor::EntityExtractor,  // NEW
+    token_counter::TokenCounter,
+    request_id,
+    metrics,
+    latency::AssemblyTiming,
 };
 use anyhow::Result;
+use rcrt_core::models::EMBEDDING_CONFIG_SCHEMA;
 use std::sync::Arc;
 use tokio::sync::mpsc;
-use tracing::{info, warn, error};
+use tracing::{info, info_span, warn, error, Instrument};
 
 pub struct EventHandler {
     rcrt_client: Arc<RcrtClient>,
     vector_store: Arc<VectorStore>,
     graph_cache: Arc<SessionGraphCache>,
-    assembler: ContextAssembler,
+    pipeline: ContextPipeline,
     publisher: ContextPublisher,
     entity_extractor: Arc<EntityExtractor>,  // NEW: GLiNER for hybrid search
     config: Config,
 }
 
+/// What a context is assembled for
+pub enum Trigger {
+    /// A breadcrumb that was written, by id
+    Stored(uuid::Uuid),
+    /// A message that only exists in memory (dry runs). This service has no embedder, so its
+    /// semantic search is keyword-only; the title is the query when the context has no `content`
+    Hypothetical { title: String, context: serde_json::Value },
+}
+
+/// An assembled context and what it was assembled under
+pub struct Assembly {
+    pub config: ContextConfig,
+    pub context: AssembledContext,
+    pub budget: ContextBudget,
+    /// The consumer's agent.def.v1, which lays out the formatted context
+    pub agent_def: Option<BreadcrumbRow>,
+}
+
+/// Trigger → seeds → session graph → paths: everything before formatting and publishing,
+/// shared by the event handler and dry runs. It only reads
+pub struct ContextPipeline {
+    vector_store: Arc<VectorStore>,
+    graph_cache: Arc<SessionGraphCache>,
+    assembler: ContextAssembler,
+    entity_extractor: Arc<EntityExtractor>,
+    token_counter: Arc<TokenCounter>,
+    settings: AssemblySettings,
+}
+
+/// The Config values assembly runs under
+#[derive(Debug, Clone, Copy)]
+pub struct AssemblySettings {
+    pub context_token_budget: usize,
+    pub context_overhead_tokens: usize,
+    pub semantic_seed_min_similarity: f64,
+}
+
+impl From<&Config> for AssemblySettings {
+    fn from(config: &Config) -> Self {
+        AssemblySettings {
+            context_token_budget: config.context_token_budget,
+            context_overhead_tokens: config.context_overhead_tokens,
+            semantic_seed_min_similarity: config.semantic_seed_min_similarity,
+        }
+    }
+}
+
 impl EventHandler {
     pub fn new(
         rcrt_client: Arc<RcrtClient>,
         vector_store: Arc<VectorStore>,
         graph_cache: Arc<SessionGraphCache>,
         entity_extractor: Arc<EntityExtractor>,  // NEW
+        token_counter: Arc<TokenCounter>,
+        owner: &OwnerConfig,
         config: Config,
     ) -> Self {
-        let assembler = ContextAssembler::new(vector_store.clone());
-        let publisher = ContextPublisher::new(rcrt_client.clone());
+        let pipeline = ContextPipeline::new(vector_store.clone(), graph_cache.clone(), entity_extractor.clone(), token_counter.clone(), AssemblySettings::from(&config));
+        let mut publisher = ContextPublisher::new(rcrt_client.clone(), token_counter.clone(), config.publish_retries);
+        if config.context_db_fallback {
+            match owner.agent_id.parse() {
+                Ok(agent_id) => {
+                    publisher = publisher.with_db_fallback(DbFallback::new(vector_store.pool().clone(), owner.owner_id, agent_id));
+                    info!("✅ Context DB fallback enabled");
+                }
+                Err(_) => warn!("⚠️  CONTEXT_DB_FALLBACK needs a UUID agent_id, fallback disabled"),
+            }
+        }
         
         EventHandler {
             rcrt_client,
             vector_store,
             graph_cache,
-            assembler,
+            pipeline,
             publisher,
             entity_extractor,  // NEW
             config,
@@ -54,21 +117,67 @@ impl EventHandler {
         let (tx, mut rx) = mpsc::unbounded_channel();
         
         // Start SSE stream
-        self.rcrt_client.start_sse_stream(tx).await?;
+        // Replay recent user messages that arrived while we were down
+        let since = chrono::Utc::now() - chrono::Duration::seconds(self.config.startup_catchup_secs);
+        self.rcrt_client.start_sse_stream(tx, since).await?;
         
         info!("✅ Event handler started, listening for events...");
         
         // Process events
         while let Some(event) = rx.recv().await {
-            if let Err(e) = self.handle_event(event).await {
+            let received_at = chrono::Utc::now();
+            let event_type = event.event_type.clone();
+            metrics::events().with_label_values(&["context", &event_type, "received"]).inc();
+            // Log, and call the server back, under the id of the request that raised the event
+            let request_id = event.request_id.clone().unwrap_or_else(request_id::generate);
+            // The span continues the trace of the server span that raised the event, if it names one
+            let span = info_span!("event", request_id = %request_id, breadcrumb_id = ?event.breadcrumb_id, traceparent = event.traceparent.as_deref());
+            let handled = request_id::scope(request_id, self.handle_event(event, received_at).instrument(span)).await;
+            if let Err(e) = handled {
+                metrics::events().with_label_values(&["context", &event_type, "errored"]).inc();
                 error!("Error handling event: {}", e);
+            } else {
+                metrics::events().with_label_values(&["context", &event_type, "processed"]).inc();
             }
         }
         
         Ok(())
     }
     
-    async fn handle_event(&self, event: BreadcrumbEvent) -> Result<()> {
+    async fn handle_event(&self, event: BreadcrumbEvent, received_at: chrono::DateTime<chrono::Utc>) -> Result<()> {
+        // Closed sessions: drop their graph so the cache doesn't hold dead sessions
+        if event.schema_name.as_deref() == Some("session.closed.v1") {
+            let session_tag = event.context
+                .as_ref()
+                .and_then(|c| c.get("session_tag"))
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string());
+            if let Some(session) = session_tag {
+                self.graph_cache.remove(self.vector_store.owner_id(), &session);
+                info!("🧹 Session {} closed, evicted graph from cache", session);
+            }
+            return Ok(());
+        }
+        
+        // The server dropped events during a NATS outage; catch up on missed entity extraction
+        if event.schema_name.as_deref() == Some("system.events.gap.v1") {
+            // Gap reports go out as created + updated; backfill once
+            if event.event_type != "breadcrumb.created" {
+                return Ok(());
+            }
+            warn!("⚠️  Server reported an event gap: {}", event.context.clone().unwrap_or_default());
+            crate::entity_worker::startup_backfill(
+                self.vector_store.clone(),
+                self.entity_extractor.clone(),
+            ).await?;
+            return Ok(());
+        }
+        
+        // A cutover switched the model similarity ranks by
+        if event.schema_name.as_deref() == Some(EMBEDDING_CONFIG_SCHEMA) {
+            return self.vector_store.load_embedding_model().await;
+        }
+        
         // For MVP, we only process user.message.v1 events
         if let Some(schema) = &event.schema_name {
             if schema == "user.message.v1" {
@@ -83,7 +192,20 @@ impl EventHandler {
                 if let Some(session) = session_tag {
                     // For MVP, use simple recent retrieval
                     // TODO: Load context.config.v1 and use dynamic retrieval
-                    self.assemble_and_publish(&session, event.breadcrumb_id).await?;
+                    metrics::assemblies().with_label_values(&["started"]).inc();
+                    let timer = metrics::assembly_timer();
+                    let timing = AssemblyTiming::from_event(&event, received_at);
+                    match self.assemble_and_publish(&session, event.breadcrumb_id, timing).await {
+                        Ok(()) => {
+                            timer.observe_duration();
+                            metrics::assemblies().with_label_values(&["completed"]).inc();
+                        }
+                        Err(e) => {
+                            timer.stop_and_discard();
+                            metrics::assemblies().with_label_values(&["failed"]).inc();
+                            return Err(e);
+                        }
+                    }
                 }
             }
         }
@@ -95,8 +217,57 @@ impl EventHandler {
         &self,
         session_tag: &str,
         trigger_id: Option<uuid::Uuid>,
+        mut timing: AssemblyTiming,
     ) -> Result<()> {
-        use crate::retrieval::{ContextConfig, SourceConfig, SourceMethod};
+        let consumer_id = "default-chat-assistant";
+        let Assembly { config, context, budget, agent_def } = self.pipeline
+            .assemble(consumer_id, session_tag, trigger_id.map(Trigger::Stored), &mut timing)
+            .await?;
+        
+        // NOTE: Entity extraction is now handled by dedicated NATS JetStream worker
+        // (see entity_worker.rs). This ensures all breadcrumbs get entities automatically
+        // via durable work queue, with retries and horizontal scalability.
+        
+        // Publish context breadcrumb
+        self.publisher.publish_context(
+            &config.consumer_id,
+            session_tag,
+            trigger_id,
+            &context,
+            &budget,
+            agent_def.as_ref(),
+            &timing,
+        ).instrument(info_span!("publish", consumer_id = %config.consumer_id)).await?;
+        
+        info!("✅ Context published for {}", config.consumer_id);
+        
+        Ok(())
+    }
+}
+
+impl ContextPipeline {
+    pub fn new(
+        vector_store: Arc<VectorStore>,
+        graph_cache: Arc<SessionGraphCache>,
+        entity_extractor: Arc<EntityExtractor>,
+        token_counter: Arc<TokenCounter>,
+        settings: AssemblySettings,
+    ) -> Self {
+        let assembler = ContextAssembler::new(vector_store.clone(), token_counter.clone());
+        ContextPipeline { vector_store, graph_cache, assembler, entity_extractor, token_counter, settings }
+    }
+    
+    /// Assemble `consumer_id`'s context for the session; `timing` gets the assembly's start and end
+    pub async fn assemble(
+        &self,
+        consumer_id: &str,
+        session_tag: &str,
+        trigger: Option<Trigger>,
+        timing: &mut AssemblyTiming,
+    ) -> Result<Assembly> {
+        use crate::retrieval::{min_similarity, provenance_enabled, read_policy, semantic_source, SemanticPath, SourceConfig, SourceMethod};
+        
+        timing.assembly_started_at = Some(chrono::Utc::now());
         
         // Build sources list
         let mut sources = vec![
@@ -115,65 +286,116 @@ impl EventHandler {
             },
         ];
         
-        // 🔍 HYBRID SEARCH: Extract entities from query and search with vector + keywords
-        if let Some(trigger) = trigger_id {
-            if let Ok(Some(trigger_bc)) = self.vector_store.get_by_id(trigger).await {
-                // Extract query text from trigger breadcrumb
-                let query_text = trigger_bc.context
-                    .get("content")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("");
-                
-                // Extract entities from query using GLiNER
-                let query_entities = self.entity_extractor.extract(query_text)?;
-                
-                if let Some(embedding) = trigger_bc.embedding {
-                    info!("🔍 Hybrid search with keywords: {:?}", query_entities.keywords);
-                    sources.push(SourceConfig {
-                        method: SourceMethod::HybridGlobal {
-                            query_embedding: embedding,
-                            query_keywords: query_entities.keywords,
-                        },
-                        limit: 10,  // Can use lower limit with better accuracy!
-                    });
-                } else {
-                    warn!("⚠️  Trigger breadcrumb {} has no embedding, skipping hybrid search", trigger);
+        // Reserve the trigger's tokens up front so retrieval can't crowd it out
+        let mut trigger_tokens = 0;
+        let mut semantic_path = None;
+        
+        // (log label, title, context, embedding) of the trigger
+        let trigger = match trigger {
+            Some(Trigger::Stored(id)) => match self.vector_store.get_by_id(id).await {
+                Ok(Some(trigger_bc)) => {
+                    // No embedding (server without embed, or not backfilled yet) falls back to keywords alone
+                    let embedding = self.vector_store.model_embedding(id, trigger_bc.embedding).await?;
+                    Some((format!("Trigger breadcrumb {}", id), None, trigger_bc.context, embedding))
                 }
+                _ => None,
+            },
+            Some(Trigger::Hypothetical { title, context }) => Some(("Hypothetical message".to_string(), Some(title), context, None)),
+            None => None,
+        };
+        
+        // 🔍 HYBRID SEARCH: Extract entities from query and search with vector + keywords
+        if let Some((label, title, context, embedding)) = trigger {
+            trigger_tokens = self.token_counter.count_json(&context);
+            
+            // Extract query text from trigger breadcrumb
+            let query_text = context
+                .get("content")
+                .and_then(|v| v.as_str())
+                .or(title.as_deref())
+                .unwrap_or("");
+            
+            // Extract entities from query using GLiNER
+            let query_entities = self.entity_extractor.extract(query_text)?;
+            
+            let (source, path) = semantic_source(embedding, query_entities.keywords.clone(), 10);
+            match path {
+                SemanticPath::Hybrid => info!("🔍 Hybrid search with keywords: {:?}", query_entities.keywords),
+                SemanticPath::Keyword => warn!("⚠️  {} has no embedding, keyword-only search with {:?}", label, query_entities.keywords),
+                SemanticPath::None => warn!("⚠️  {} has no embedding or keywords, skipping semantic search", label),
             }
+            sources.extend(source);
+            semantic_path = Some(path);
         }
         
+        // Provenance unless the consumer's agent.def.v1 opts out; its context_formatting shapes the published text
+        let agent_def = match self.vector_store.get_agent_def(consumer_id).await {
+            Ok(def) => def,
+            Err(e) => {
+                warn!("⚠️  Failed to load agent.def.v1 for {}: {}", consumer_id, e);
+                None
+            }
+        };
+        let budget = self.budget(agent_def.as_ref(), trigger_tokens).await;
+        
         let config = ContextConfig {
-            consumer_id: "default-chat-assistant".to_string(),
+            consumer_id: consumer_id.to_string(),
             sources,
+            token_budget: Some(budget.available()),
+            provenance: provenance_enabled(agent_def.as_ref().map(|def| &def.context)),
+            read_policy: read_policy(agent_def.as_ref().map(|def| &def.context)),
+            semantic_path,
+            min_similarity: min_similarity(agent_def.as_ref().map(|def| &def.context), self.settings.semantic_seed_min_similarity),
         };
         
+        // Session graph, when one is cached; causal sources fall back to the database without it
+        let graph = info_span!("graph_load", session = session_tag)
+            .in_scope(|| self.graph_cache.get(self.vector_store.owner_id(), session_tag));
+        
         // Assemble context
         let context = self.assembler.assemble(
             &config,
             Some(session_tag),
-            None, // TODO: Use session graph
+            graph.as_ref(),
         ).await?;
+        timing.assembly_finished_at = Some(chrono::Utc::now());
         
-        info!("✅ Context assembled: {} breadcrumbs, ~{} tokens", 
+        info!("✅ Context assembled: {} breadcrumbs, ~{} tokens (budget {}, trigger {}, overhead {})", 
             context.breadcrumbs.len(),
-            context.token_estimate
+            context.token_estimate,
+            budget.available(),
+            budget.trigger,
+            budget.overhead
         );
         
-        // NOTE: Entity extraction is now handled by dedicated NATS JetStream worker
-        // (see entity_worker.rs). This ensures all breadcrumbs get entities automatically
-        // via durable work queue, with retries and horizontal scalability.
-        
-        // Publish context breadcrumb
-        self.publisher.publish_context(
-            &config.consumer_id,
-            session_tag,
-            trigger_id,
-            &context,
-        ).await?;
-        
-        info!("✅ Context published for {}", config.consumer_id);
-        
-        Ok(())
+        Ok(Assembly { config, context, budget, agent_def })
+    }
+    
+    /// The consumer's budget: its llm.config.v1 window (CONTEXT_TOKEN_BUDGET without one) and its
+    /// agent.def.v1 system prompt as overhead (CONTEXT_OVERHEAD_TOKENS without one)
+    async fn budget(&self, agent_def: Option<&BreadcrumbRow>, trigger_tokens: usize) -> ContextBudget {
+        let llm_config_id = agent_def
+            .and_then(|def| def.context.get("llm_config_id"))
+            .and_then(|v| v.as_str())
+            .and_then(|id| id.parse().ok());
+        let window = match llm_config_id {
+            Some(id) => match self.vector_store.get_llm_config(id).await {
+                Ok(llm_config) => llm_config.and_then(|row| prompt_window(&row.context)),
+                Err(e) => {
+                    warn!("⚠️  Failed to load llm.config.v1 {}: {}", id, e);
+                    None
+                }
+            },
+            None => None,
+        };
+        let overhead = agent_def
+            .and_then(|def| def.context.get("system_prompt"))
+            .and_then(|v| v.as_str())
+            .map(|prompt| self.token_counter.count(prompt));
+        ContextBudget::new(
+            window.unwrap_or(self.settings.context_token_budget),
+            overhead.unwrap_or(self.settings.context_overhead_tokens),
+            trigger_tokens,
+        )
     }
 }
-
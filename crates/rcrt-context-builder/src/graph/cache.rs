@@ -1,66 +1,105 @@
 /*!
- * LRU cache for session graphs
+ * LRU cache for session graphs, keyed by owner and session tag
+ * (session tags are only unique within a tenant)
  */
 
 use super::types::SessionGraph;
+use crate::metrics;
 use lru::LruCache;
 use std::num::NonZeroUsize;
 use std::sync::RwLock;
 use tracing::info;
+use uuid::Uuid;
+
+type SessionKey = (Uuid, String);
 
 pub struct SessionGraphCache {
-    cache: RwLock<LruCache<String, SessionGraph>>,
-    max_memory_mb: usize,
+    cache: RwLock<Cached>,
+    max_memory_bytes: usize,
+    node_max_context_bytes: usize,
+}
+
+/// Graphs with their estimate_memory_usage at insert, and the running total
+struct Cached {
+    graphs: LruCache<SessionKey, (SessionGraph, usize)>,
+    bytes: usize,
 }
 
 impl SessionGraphCache {
-    pub fn new(max_memory_mb: usize) -> Self {
-        // Estimate ~10MB per session graph
-        let capacity = (max_memory_mb / 10).max(10);
+    /// At most `max_sessions` graphs and `max_memory_mb` of them; node contexts are trimmed to
+    /// `node_max_context_bytes` on the way in
+    pub fn new(max_memory_mb: usize, max_sessions: usize, node_max_context_bytes: usize) -> Self {
+        let capacity = NonZeroUsize::new(max_sessions.max(1)).unwrap();
         
-        info!("📊 Session graph cache capacity: {} sessions (~{}MB)", capacity, max_memory_mb);
+        info!("📊 Session graph cache: up to {} sessions, {}MB, {} bytes of context per node",
+            capacity, max_memory_mb, node_max_context_bytes);
         
         SessionGraphCache {
-            cache: RwLock::new(LruCache::new(NonZeroUsize::new(capacity).unwrap())),
-            max_memory_mb,
+            cache: RwLock::new(Cached { graphs: LruCache::new(capacity), bytes: 0 }),
+            max_memory_bytes: max_memory_mb * 1024 * 1024,
+            node_max_context_bytes,
         }
     }
     
-    pub fn get(&self, session_id: &str) -> Option<SessionGraph> {
+    pub fn get(&self, owner_id: Uuid, session_id: &str) -> Option<SessionGraph> {
         let mut cache = self.cache.write().unwrap();
-        cache.get(session_id).cloned()
+        let graph = cache.graphs.get(&(owner_id, session_id.to_string())).map(|(graph, _)| graph.clone());
+        metrics::graph_cache_lookups().with_label_values(&[if graph.is_some() { "hit" } else { "miss" }]).inc();
+        graph
     }
     
-    pub fn put(&self, session_id: String, graph: SessionGraph) {
+    /// Trims the graph's node contexts, then evicts least recently used sessions until it fits
+    pub fn put(&self, owner_id: Uuid, session_id: String, mut graph: SessionGraph) {
+        graph.trim_contexts(self.node_max_context_bytes);
+        let size = graph.estimate_memory_usage();
         let mut cache = self.cache.write().unwrap();
         
-        // Check memory usage
-        let graph_size_mb = graph.estimate_memory_usage() / (1024 * 1024);
-        if graph_size_mb > self.max_memory_mb / 10 {
+        if size > self.max_memory_bytes {
             tracing::warn!(
-                "Session graph {} is very large ({}MB), skipping cache",
+                "Session graph {} is larger than the whole cache ({} bytes), skipping cache",
                 session_id,
-                graph_size_mb
+                size
             );
             return;
         }
         
-        cache.put(session_id, graph);
+        if let Some((_, old)) = cache.graphs.pop(&(owner_id, session_id.clone())) {
+            cache.bytes -= old;
+        }
+        while cache.bytes + size > self.max_memory_bytes {
+            let Some((_, (_, evicted))) = cache.graphs.pop_lru() else { break };
+            cache.bytes -= evicted;
+        }
+        if let Some((_, (_, evicted))) = cache.graphs.push((owner_id, session_id), (graph, size)) {
+            cache.bytes -= evicted;
+        }
+        cache.bytes += size;
+        record(&cache);
     }
     
-    pub fn remove(&self, session_id: &str) {
+    pub fn remove(&self, owner_id: Uuid, session_id: &str) {
         let mut cache = self.cache.write().unwrap();
-        cache.pop(session_id);
+        if let Some((_, size)) = cache.graphs.pop(&(owner_id, session_id.to_string())) {
+            cache.bytes -= size;
+        }
+        record(&cache);
     }
     
     pub fn clear(&self) {
         let mut cache = self.cache.write().unwrap();
-        cache.clear();
+        cache.graphs.clear();
+        cache.bytes = 0;
+        record(&cache);
     }
     
     pub fn len(&self) -> usize {
         let cache = self.cache.read().unwrap();
-        cache.len()
+        cache.graphs.len()
+    }
+    
+    /// Estimated bytes held by the cached graphs
+    pub fn memory_bytes(&self) -> usize {
+        self.cache.read().unwrap().bytes
     }
     
     pub fn is_empty(&self) -> bool {
@@ -68,3 +107,101 @@ impl SessionGraphCache {
     }
 }
 
+fn record(cache: &Cached) {
+    metrics::graph_cache_sessions().set(cache.graphs.len() as i64);
+    metrics::graph_cache_bytes().set(cache.bytes as i64);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::BreadcrumbNode;
+    use serde_json::json;
+
+    fn page_node(session: &str, dom_bytes: usize) -> BreadcrumbNode {
+        BreadcrumbNode {
+            id: Uuid::new_v4(),
+            schema_name: "browser.page.context.v1".to_string(),
+            tags: vec![session.to_string()],
+            context: json!({
+                "url": "https://example.com",
+                "summary": "s".repeat(30 * 1024),
+                "dom": "x".repeat(dom_bytes),
+            }),
+            embedding: None,
+            created_at: chrono::Utc::now(),
+            trigger_event_id: None,
+            context_truncated: false,
+        }
+    }
+
+    fn page_graph(session: &str, pages: usize, dom_bytes: usize) -> SessionGraph {
+        let mut graph = SessionGraph::new(session.to_string());
+        for _ in 0..pages {
+            graph.add_node(page_node(session, dom_bytes));
+        }
+        graph
+    }
+
+    fn cached_bytes(cache: &SessionGraphCache, owner: Uuid, sessions: usize) -> usize {
+        (0..sessions)
+            .filter_map(|i| cache.get(owner, &format!("session:{}", i)))
+            .map(|graph| graph.estimate_memory_usage())
+            .sum()
+    }
+
+    #[test]
+    fn test_large_contexts_are_trimmed_and_the_byte_cap_holds() {
+        let cache = SessionGraphCache::new(1, 100, 64 * 1024);
+        let owner = Uuid::new_v4();
+        for i in 0..40 {
+            // ~3.3MB of DOM dumps per session, more than the whole cache untrimmed
+            let session = format!("session:{}", i);
+            cache.put(owner, session.clone(), page_graph(&session, 10, 300 * 1024));
+            assert!(cache.memory_bytes() <= 1024 * 1024, "{} bytes cached", cache.memory_bytes());
+        }
+        assert!(cache.len() > 1 && cache.len() < 40);
+        assert!(cache.get(owner, "session:0").is_none());
+        assert_eq!(cache.memory_bytes(), cached_bytes(&cache, owner, 40));
+
+        let graph = cache.get(owner, "session:39").expect("newest session stays cached");
+        let node = graph.nodes.values().next().unwrap();
+        assert!(node.context_truncated);
+        assert_eq!(node.context["truncated"], true);
+        assert_eq!(node.context["summary"].as_str().map(str::len), Some(30 * 1024));
+        assert!(node.context.get("dom").is_none());
+
+        cache.remove(owner, "session:39");
+        assert_eq!(cache.memory_bytes(), cached_bytes(&cache, owner, 40));
+        cache.clear();
+        assert_eq!(cache.memory_bytes(), 0);
+    }
+
+    #[test]
+    fn test_small_contexts_are_kept_whole() {
+        let mut node = page_node("session:1", 100);
+        let before = node.context.clone();
+        node.trim_context(64 * 1024);
+        assert!(!node.context_truncated);
+        assert_eq!(node.context, before);
+
+        node.context = json!("x".repeat(5000));
+        node.trim_context(4096);
+        assert!(node.context_truncated && node.context.is_null());
+    }
+
+    #[test]
+    fn test_same_session_tag_is_separate_per_owner() {
+        let cache = SessionGraphCache::new(100, 100, 4096);
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        cache.put(a, "session:1".to_string(), SessionGraph::new("session:1".to_string()));
+        assert!(cache.get(a, "session:1").is_some());
+        assert!(cache.get(b, "session:1").is_none());
+
+        cache.put(b, "session:1".to_string(), SessionGraph::new("session:1".to_string()));
+        cache.remove(a, "session:1");
+        assert!(cache.get(a, "session:1").is_none());
+        assert!(cache.get(b, "session:1").is_some());
+        assert_eq!(cache.len(), 1);
+    }
+}
@@ -17,11 +17,50 @@ pub struct BreadcrumbNode {
     pub embedding: Option<Vector>,
     pub created_at: DateTime<Utc>,
     pub trigger_event_id: Option<Uuid>,
+    /// Set once `trim_context` dropped fields; the full context comes from bulk_get at publish time
+    pub context_truncated: bool,
+}
+
+impl BreadcrumbNode {
+    /// Keep `context` under `max_bytes` of JSON: top-level fields that fit are kept in order, the
+    /// rest dropped, and `"truncated": true` added. Non-object contexts over the limit become null
+    pub fn trim_context(&mut self, max_bytes: usize) {
+        if json_len(&self.context) <= max_bytes {
+            return;
+        }
+        let mut trimmed = serde_json::Map::new();
+        if let serde_json::Value::Object(fields) = &self.context {
+            let mut used = json_len(&serde_json::json!({ "truncated": true }));
+            for (key, value) in fields {
+                let size = key.len() + json_len(value) + 4; // quotes, colon and comma
+                if used + size <= max_bytes {
+                    trimmed.insert(key.clone(), value.clone());
+                    used += size;
+                }
+            }
+            trimmed.insert("truncated".to_string(), serde_json::Value::Bool(true));
+        }
+        self.context = if trimmed.is_empty() { serde_json::Value::Null } else { serde_json::Value::Object(trimmed) };
+        self.context_truncated = true;
+    }
+
+    /// Heap and inline bytes this node holds, with the context at its serialized size
+    pub fn memory_bytes(&self) -> usize {
+        std::mem::size_of::<BreadcrumbNode>()
+            + self.schema_name.len()
+            + self.tags.iter().map(|tag| tag.len() + std::mem::size_of::<String>()).sum::<usize>()
+            + json_len(&self.context)
+            + self.embedding.as_ref().map_or(0, |e| e.as_slice().len() * std::mem::size_of::<f32>())
+    }
+}
+
+fn json_len(value: &serde_json::Value) -> usize {
+    serde_json::to_vec(value).map_or(0, |bytes| bytes.len())
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum EdgeType {
-    /// Causal relationship (trigger_event_id)
+    /// Causal relationship (a triggered_by reference, or trigger_event_id in the context)
     Causal,
     /// Temporal (close in time)
     Temporal,
@@ -118,13 +157,24 @@ impl SessionGraph {
         chain
     }
     
-    /// Estimate memory usage in bytes
+    /// Trim every node's context to `max_bytes`; see BreadcrumbNode::trim_context
+    pub fn trim_contexts(&mut self, max_bytes: usize) {
+        for node in self.nodes.values_mut() {
+            node.trim_context(max_bytes);
+        }
+    }
+    
+    /// Memory usage in bytes, counting each node's context at its serialized size
     pub fn estimate_memory_usage(&self) -> usize {
-        let nodes_size = self.nodes.len() * std::mem::size_of::<BreadcrumbNode>();
+        let nodes_size: usize = self.nodes.values()
+            .map(|node| node.memory_bytes() + std::mem::size_of::<Uuid>())
+            .sum();
         let edges_size = self.edges.len() * std::mem::size_of::<Edge>();
-        let adjacency_size = self.adjacency.len() * 100; // Rough estimate
+        let adjacency_size: usize = self.adjacency.values()
+            .map(|neighbors| std::mem::size_of::<Uuid>() + neighbors.len() * std::mem::size_of::<(Uuid, EdgeType, f32)>())
+            .sum();
         
-        nodes_size + edges_size + adjacency_size
+        self.session_id.len() + nodes_size + edges_size + adjacency_size
     }
 }
 
@@ -0,0 +1,222 @@
+/*!
+ * Readiness for GET /ready
+ *
+ * /health only says the process is up. /ready is 503 until the DB pool answers and,
+ * for every configured owner, the blacklist is loaded, the latest agent.def.v1 lookup
+ * succeeded and each SSE stream has sent an event or heartbeat ping within
+ * READY_SSE_MAX_AGE_SECS; the body names what is failing. A worker the supervisor restarted
+ * within WORKER_RESTART_WINDOW_SECS also fails it, as `degraded` when nothing else does.
+ */
+
+use axum::{extract::State, http::StatusCode, Json};
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::rcrt_client::RcrtClient;
+use crate::supervisor::{Flapping, Supervisor};
+use crate::vector_store::VectorStore;
+
+const DB_PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub struct Readiness {
+    pool: PgPool,
+    sse_max_age: chrono::Duration,
+    /// Configured owners; one that hasn't finished starting is not ready
+    expected: Vec<Uuid>,
+    owners: RwLock<HashMap<Uuid, (Arc<VectorStore>, Arc<RcrtClient>)>>,
+    supervisor: Arc<Supervisor>,
+}
+
+/// What /ready saw for one owner
+struct OwnerSnapshot {
+    owner_id: Uuid,
+    started: bool,
+    blacklist_loaded: bool,
+    agent_def_error: Option<String>,
+    sse_last_seen: Option<DateTime<Utc>>,
+}
+
+impl Readiness {
+    pub fn new(pool: PgPool, sse_max_age_secs: i64, expected: Vec<Uuid>, supervisor: Arc<Supervisor>) -> Self {
+        Readiness {
+            pool,
+            sse_max_age: chrono::Duration::seconds(sse_max_age_secs),
+            expected,
+            owners: RwLock::new(HashMap::new()),
+            supervisor,
+        }
+    }
+
+    /// Called once an owner's workers are running
+    pub fn add_owner(&self, owner_id: Uuid, vector_store: Arc<VectorStore>, rcrt_client: Arc<RcrtClient>) {
+        self.owners.write().unwrap().insert(owner_id, (vector_store, rcrt_client));
+    }
+
+    async fn ping_db(&self) -> Option<String> {
+        match tokio::time::timeout(DB_PING_TIMEOUT, sqlx::query("SELECT 1").execute(&self.pool)).await {
+            Ok(Ok(_)) => None,
+            Ok(Err(e)) => Some(e.to_string()),
+            Err(_) => Some(format!("no response within {}s", DB_PING_TIMEOUT.as_secs())),
+        }
+    }
+
+    async fn snapshots(&self) -> Vec<OwnerSnapshot> {
+        let started = self.owners.read().unwrap().clone();
+        let mut snapshots = Vec::with_capacity(self.expected.len());
+        for owner_id in &self.expected {
+            let snapshot = match started.get(owner_id) {
+                Some((vector_store, rcrt_client)) => OwnerSnapshot {
+                    owner_id: *owner_id,
+                    started: true,
+                    blacklist_loaded: vector_store.blacklist_loaded().await,
+                    agent_def_error: vector_store.agent_def_error(),
+                    sse_last_seen: rcrt_client.sse_last_seen(),
+                },
+                None => OwnerSnapshot {
+                    owner_id: *owner_id,
+                    started: false,
+                    blacklist_loaded: false,
+                    agent_def_error: None,
+                    sse_last_seen: None,
+                },
+            };
+            snapshots.push(snapshot);
+        }
+        snapshots
+    }
+}
+
+/// `{"dependency", "owner_id"?, "error"}` for each failing check
+fn failures(db_error: Option<String>, owners: &[OwnerSnapshot], workers: &[Flapping], now: DateTime<Utc>, sse_max_age: chrono::Duration) -> Vec<Value> {
+    let mut failing = Vec::new();
+    if let Some(error) = db_error {
+        failing.push(json!({ "dependency": "database", "error": error }));
+    }
+    for owner in owners {
+        let mut fail = |dependency: &str, error: String| {
+            failing.push(json!({ "dependency": dependency, "owner_id": owner.owner_id, "error": error }));
+        };
+        if !owner.started {
+            fail("owner", "still starting".to_string());
+            continue;
+        }
+        if !owner.blacklist_loaded {
+            fail("blacklist", "context.blacklist.v1 not loaded".to_string());
+        }
+        if let Some(error) = &owner.agent_def_error {
+            fail("agent_definitions", error.clone());
+        }
+        match owner.sse_last_seen {
+            None => fail("sse", "not connected".to_string()),
+            Some(seen) if now - seen > sse_max_age => {
+                fail("sse", format!("no event or heartbeat for {}s", (now - seen).num_seconds()));
+            }
+            Some(_) => {}
+        }
+    }
+    for worker in workers {
+        failing.push(json!({
+            "dependency": "worker",
+            "owner_id": worker.owner_id,
+            "worker": worker.worker,
+            "restarts": worker.restarts,
+            "error": worker.last_error,
+        }));
+    }
+    failing
+}
+
+/// 200 `{"status": "ready"}`, or 503 `{"status": "not_ready" | "degraded", "failing": [...]}`
+pub async fn ready(State(readiness): State<Arc<Readiness>>) -> (StatusCode, Json<Value>) {
+    let db_error = readiness.ping_db().await;
+    let owners = readiness.snapshots().await;
+    let failing = failures(db_error, &owners, &readiness.supervisor.flapping(), Utc::now(), readiness.sse_max_age);
+    (status(&failing), Json(body(failing)))
+}
+
+fn status(failing: &[Value]) -> StatusCode {
+    if failing.is_empty() { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE }
+}
+
+/// Degraded when the only failures are restarted workers: dependencies are fine, but a worker is flapping
+fn body(failing: Vec<Value>) -> Value {
+    if failing.is_empty() {
+        return json!({ "status": "ready" });
+    }
+    let status = if failing.iter().all(|f| f["dependency"] == "worker") { "degraded" } else { "not_ready" };
+    json!({ "status": status, "failing": failing })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owner(n: u128, sse_last_seen: Option<DateTime<Utc>>) -> OwnerSnapshot {
+        OwnerSnapshot { owner_id: Uuid::from_u128(n), started: true, blacklist_loaded: true, agent_def_error: None, sse_last_seen }
+    }
+
+    #[test]
+    fn test_failures_name_the_failing_dependency() {
+        let now = Utc::now();
+        let max_age = chrono::Duration::seconds(30);
+        assert!(failures(None, &[owner(1, Some(now - chrono::Duration::seconds(5)))], &[], now, max_age).is_empty());
+
+        let stale = owner(1, Some(now - chrono::Duration::seconds(90)));
+        let mut broken = owner(2, None);
+        broken.blacklist_loaded = false;
+        broken.agent_def_error = Some("pool timed out".to_string());
+        let starting = OwnerSnapshot { started: false, ..owner(3, None) };
+        let failing = failures(Some("connection refused".to_string()), &[stale, broken, starting], &[], now, max_age);
+
+        let names: Vec<(&str, Option<&str>)> = failing.iter()
+            .map(|f| (f["dependency"].as_str().unwrap(), f["owner_id"].as_str()))
+            .collect();
+        let (o1, o2, o3) = (Uuid::from_u128(1).to_string(), Uuid::from_u128(2).to_string(), Uuid::from_u128(3).to_string());
+        assert_eq!(names, vec![
+            ("database", None),
+            ("sse", Some(o1.as_str())),
+            ("blacklist", Some(o2.as_str())),
+            ("agent_definitions", Some(o2.as_str())),
+            ("sse", Some(o2.as_str())),
+            ("owner", Some(o3.as_str())),
+        ]);
+        assert_eq!(failing[1]["error"], "no event or heartbeat for 90s");
+    }
+
+    #[tokio::test]
+    async fn test_flapping_worker_degrades_readiness() {
+        let supervisor = Supervisor::new(crate::supervisor::RestartPolicy { max_restarts: 5, window: std::time::Duration::from_secs(60), backoff: std::time::Duration::from_millis(1) });
+        let owner_id = Uuid::from_u128(1);
+        let runs = std::sync::atomic::AtomicUsize::new(0);
+        // Panics once, then keeps running after the restart
+        let supervised = supervisor.supervise(owner_id, "event_handler", || {
+            let run = runs.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            async move {
+                if run == 0 {
+                    panic!("unexpected JSON shape");
+                }
+                std::future::pending().await
+            }
+        });
+        let _ = tokio::time::timeout(std::time::Duration::from_millis(200), supervised).await;
+
+        let now = Utc::now();
+        let failing = failures(None, &[owner(1, Some(now))], &supervisor.flapping(), now, chrono::Duration::seconds(30));
+        assert_eq!(status(&failing), StatusCode::SERVICE_UNAVAILABLE);
+        let degraded = body(failing);
+        assert_eq!(degraded["status"], "degraded");
+        assert_eq!(degraded["failing"][0]["dependency"], "worker");
+        assert_eq!(degraded["failing"][0]["worker"], "event_handler");
+        assert_eq!(degraded["failing"][0]["restarts"], 1);
+        assert_eq!(degraded["failing"][0]["error"], "panic: unexpected JSON shape");
+
+        // Anything else failing too is plain not_ready
+        let failing = failures(Some("connection refused".to_string()), &[], &supervisor.flapping(), now, chrono::Duration::seconds(30));
+        assert_eq!(body(failing)["status"], "not_ready");
+    }
+}
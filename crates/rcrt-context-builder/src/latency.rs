@@ -0,0 +1,112 @@
+/*!
+ * End-to-end assembly latency
+ *
+ * Each assembly carries the moments it passed through: the trigger's creation and the server
+ * taking its request (both from the event, payload version 3), the handler taking the event off
+ * the stream, and assembly start and end. They are written to the context payload as
+ * `provenance_timing`; publish completion can't be in the payload it completes, so it only
+ * reaches `context_assembly_e2e_seconds` (publish completion minus the trigger's creation).
+ *
+ * The two ends come from different clocks, the server's and ours. A negative span means they
+ * disagree; it is logged and not observed.
+ */
+
+use chrono::{DateTime, Utc};
+use prometheus::Histogram;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::rcrt_client::BreadcrumbEvent;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AssemblyTiming {
+    /// The trigger breadcrumb's created_at (server clock); only for its first version, since an edit
+    /// of an old message isn't a new trigger
+    pub trigger_created_at: Option<DateTime<Utc>>,
+    /// When the server took the request that created the trigger (server clock)
+    pub server_received_at: Option<DateTime<Utc>>,
+    /// When the event handler took the event off the stream
+    pub event_received_at: Option<DateTime<Utc>>,
+    pub assembly_started_at: Option<DateTime<Utc>>,
+    pub assembly_finished_at: Option<DateTime<Utc>>,
+}
+
+impl AssemblyTiming {
+    /// The server's timestamps from `event`, received at `event_received_at`
+    pub fn from_event(event: &BreadcrumbEvent, event_received_at: DateTime<Utc>) -> Self {
+        AssemblyTiming {
+            trigger_created_at: event.created_at.filter(|_| event.version == Some(1)),
+            server_received_at: event.received_at,
+            event_received_at: Some(event_received_at),
+            ..Default::default()
+        }
+    }
+
+    /// Seconds from the trigger's creation to `published_at`; None without a created_at
+    pub fn end_to_end(&self, published_at: DateTime<Utc>) -> Option<f64> {
+        let created_at = self.trigger_created_at?;
+        Some((published_at - created_at).num_microseconds()? as f64 / 1_000_000.0)
+    }
+
+    /// Observe the end-to-end latency of a context published at `published_at` into `histogram`;
+    /// returns what was observed
+    pub fn observe(&self, published_at: DateTime<Utc>, histogram: &Histogram) -> Option<f64> {
+        let seconds = self.end_to_end(published_at)?;
+        if seconds < 0.0 {
+            warn!("⚠️  Context published {:.3}s before its trigger was created; server and context-builder clocks disagree", -seconds);
+            return None;
+        }
+        histogram.observe(seconds);
+        Some(seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use prometheus::HistogramOpts;
+
+    fn at(millis: i64) -> DateTime<Utc> {
+        Utc.timestamp_millis_opt(1_700_000_000_000 + millis).unwrap()
+    }
+
+    fn histogram() -> Histogram {
+        Histogram::with_opts(HistogramOpts::new("test_e2e_seconds", "test")).unwrap()
+    }
+
+    #[test]
+    fn test_observes_publish_minus_trigger_creation() {
+        let timing = AssemblyTiming { trigger_created_at: Some(at(0)), server_received_at: Some(at(-5)), ..Default::default() };
+        let histogram = histogram();
+        assert_eq!(timing.observe(at(1250), &histogram), Some(1.25));
+        assert_eq!(histogram.get_sample_count(), 1);
+        assert_eq!(histogram.get_sample_sum(), 1.25);
+    }
+
+    #[test]
+    fn test_skewed_clocks_and_missing_timestamps_observe_nothing() {
+        let histogram = histogram();
+        let skewed = AssemblyTiming { trigger_created_at: Some(at(500)), ..Default::default() };
+        assert_eq!(skewed.end_to_end(at(0)), Some(-0.5));
+        assert_eq!(skewed.observe(at(0), &histogram), None);
+        assert_eq!(AssemblyTiming::default().observe(at(0), &histogram), None);
+        assert_eq!(histogram.get_sample_count(), 0);
+    }
+
+    #[test]
+    fn test_timing_comes_from_the_event() {
+        let event: BreadcrumbEvent = serde_json::from_value(serde_json::json!({
+            "type": "breadcrumb.updated", "version": 1, "created_at": at(0), "received_at": at(-3),
+        })).unwrap();
+        let timing = AssemblyTiming::from_event(&event, at(40));
+        assert_eq!((timing.trigger_created_at, timing.server_received_at, timing.event_received_at), (Some(at(0)), Some(at(-3)), Some(at(40))));
+        // An edit isn't a new trigger: nothing to time end to end, though its request is still stamped
+        let edited = BreadcrumbEvent { version: Some(2), ..event };
+        let timing = AssemblyTiming::from_event(&edited, at(40));
+        assert_eq!((timing.trigger_created_at, timing.server_received_at), (None, Some(at(-3))));
+        // Events below payload version 3 carry neither
+        let old: BreadcrumbEvent = serde_json::from_value(serde_json::json!({ "type": "breadcrumb.updated", "version": 1 })).unwrap();
+        assert_eq!(AssemblyTiming::from_event(&old, at(40)).end_to_end(at(50)), None);
+    }
+}
@@ -12,9 +12,12 @@
  */
 
 use anyhow::Result;
-use tracing::{info, error};
+use tracing::{info, warn, error, info_span, Instrument};
+use tokio::task::JoinSet;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::env;
+use std::time::Duration;
 
 mod config;
 mod rcrt_client;
@@ -25,26 +28,37 @@ mod event_handler;
 mod output;
 mod entity_extractor;  // Entity extraction (regex-based)
 mod entity_worker;     // SSE-based worker for entity extraction
+mod token_counter;     // Tokenizer-based context budgeting
+mod reprocess;         // `reprocess` subcommand for targeted re-extraction
+mod request_id;        // X-Request-Id carried from server events to outgoing calls
+mod metrics;           // Prometheus registry and the /metrics listener
+mod health;            // /ready checks for the metrics listener
+mod telemetry;         // Trace spans, traceparent propagation and optional OTLP export
+mod supervisor;        // Restarts for panicking or failing workers, with a restart budget
+mod latency;           // End-to-end timing from a trigger's creation to its published context
+mod dry_run;           // POST /debug/assemble: assembly for a hypothetical message, writing nothing
 
-use config::Config;
-use rcrt_client::RcrtClient;
+use config::{Config, OwnerConfig};
+use rcrt_client::{RcrtClient, RetryPolicy};
 use vector_store::VectorStore;
 use graph::SessionGraphCache;
-use event_handler::EventHandler;
+use event_handler::{AssemblySettings, ContextPipeline, EventHandler};
+use dry_run::{DryRun, OwnerDryRun};
+use output::ContextPublisher;
 use entity_extractor::EntityExtractor;  // NEW
+use token_counter::TokenCounter;
+use health::Readiness;
+use supervisor::{RestartPolicy, Supervisor};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "rcrt_context_builder=info".into()),
-        )
-        .with_target(false)
-        .with_thread_ids(true)
-        .with_line_number(true)
-        .init();
+    // Logs and traces; LOG_FORMAT=json for the log pipeline, OTEL_EXPORTER_OTLP_ENDPOINT with the `otel` feature
+    telemetry::init("rcrt-context-builder");
+
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(String::as_str) == Some("reprocess") {
+        return run_reprocess(&args[2..]).await;
+    }
 
     info!("🚀 RCRT Context Builder starting...");
 
@@ -71,46 +85,148 @@ async fn main() -> Result<()> {
     
     info!("✅ pgvector extension verified");
 
-    // Initialize vector store
-    let vector_store = Arc::new(VectorStore::new(db_pool.clone()));
-    info!("✅ Vector store initialized");
+    // Initialize session graph cache (shared; keyed by owner and session)
+    let graph_cache = Arc::new(SessionGraphCache::new(config.cache_size_mb, config.max_sessions, config.graph_node_max_context_bytes));
+    info!("✅ Session graph cache initialized");
+
+    // Initialize entity extractor (regex-based)
+    let entity_extractor = Arc::new(EntityExtractor::new()?);
+    info!("✅ Entity extractor initialized");
+
+    // Token counter for context budgets (shares the embedding tokenizer)
+    let tokenizer_path = env::var("GLINER_TOKENIZER_PATH")
+        .unwrap_or_else(|_| "/app/models/tokenizer.json".to_string());
+    let token_counter = Arc::new(TokenCounter::new(&tokenizer_path));
+
+    let supervisor = Arc::new(Supervisor::new(RestartPolicy {
+        max_restarts: config.worker_max_restarts,
+        window: Duration::from_secs(config.worker_restart_window_secs),
+        backoff: Duration::from_millis(config.worker_restart_backoff_ms),
+    }));
+    let readiness = Arc::new(Readiness::new(
+        db_pool.clone(),
+        config.ready_sse_max_age_secs,
+        config.owners.iter().map(|owner| owner.owner_id).collect(),
+        supervisor.clone(),
+    ));
+    // Dry runs read through their own read-only pool
+    let dry_run = match &config.debug_token {
+        Some(token) => {
+            let options: sqlx::postgres::PgConnectOptions = config.database_url.parse()?;
+            Some(Arc::new(DryRun::new(token.clone(), dry_run::read_only_pool(options))))
+        }
+        None => None,
+    };
+    let shared = Shared { db_pool, graph_cache, entity_extractor, token_counter, readiness: readiness.clone(), supervisor, dry_run: dry_run.clone(), config: config.clone() };
+    let mut tasks = JoinSet::new();
+
+    // Prometheus scrape endpoint and probes; bind up front so a taken port fails startup
+    metrics::init();
+    if !config.metrics_addr.is_empty() {
+        let listener = tokio::net::TcpListener::bind(&config.metrics_addr).await
+            .map_err(|e| anyhow::anyhow!("failed to bind METRICS_ADDR {}: {}", config.metrics_addr, e))?;
+        info!("📊 Metrics on http://{}/metrics (probes at /health and /ready)", config.metrics_addr);
+        if dry_run.is_some() {
+            info!("🧪 Context dry runs on http://{}/debug/assemble", config.metrics_addr);
+        }
+        tasks.spawn(async move {
+            if let Err(e) = metrics::serve(listener, readiness, dry_run).await {
+                error!("❌ Metrics listener failed: {}", e);
+            }
+            "Metrics listener".to_string()
+        });
+    } else if dry_run.is_some() {
+        warn!("⚠️  DEBUG_TOKEN is set but METRICS_ADDR is empty, so dry runs aren't served");
+    }
+    for owner in &config.owners {
+        start_owner(owner, &shared, &mut tasks)
+            .instrument(info_span!("owner", owner_id = %owner.owner_id))
+            .await?;
+    }
+
+    // Keep running until shutdown signal
+    info!("💚 Context Builder is running for {} owner(s)", config.owners.len());
+    info!("   - Entity extraction: SSE stream");
+    info!("   - Context assembly: SSE stream");
+    
+    // Workers are restarted in place; a task only ends once its restart budget is spent
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {
+            info!("🛑 Received shutdown signal...");
+        }
+        Some(stopped) = tasks.join_next() => {
+            let stopped = stopped.unwrap_or_else(|e| format!("task panicked: {}", e));
+            error!("❌ {} stopped for good, exiting so the container is restarted", stopped);
+            anyhow::bail!("{} stopped", stopped);
+        }
+    }
+    
+    info!("🛑 Shutting down...");
+
+    Ok(())
+}
+
+/// Resources every owner's workers share
+struct Shared {
+    db_pool: sqlx::PgPool,
+    graph_cache: Arc<SessionGraphCache>,
+    entity_extractor: Arc<EntityExtractor>,
+    token_counter: Arc<TokenCounter>,
+    readiness: Arc<Readiness>,
+    supervisor: Arc<Supervisor>,
+    /// Set when DEBUG_TOKEN is
+    dry_run: Option<Arc<DryRun>>,
+    config: Config,
+}
+
+/// Token, SSE subscriptions, entity worker and event handler for one owner; their
+/// logs carry the owner span and every direct-DB query is scoped to the owner
+async fn start_owner(owner: &OwnerConfig, shared: &Shared, tasks: &mut JoinSet<String>) -> Result<()> {
+    info!("🏢 Starting workers for owner {} as agent {}", owner.owner_id, owner.agent_id);
+
+    let vector_store = Arc::new(
+        VectorStore::new(shared.db_pool.clone(), owner.owner_id)
+            .with_title_weight(shared.config.similarity_title_weight)
+    );
     
     // Load context blacklist from database
     // NO FALLBACKS - system must have proper configuration
     info!("📋 Loading context blacklist configuration...");
     vector_store.load_blacklist().await.map_err(|e| {
-        error!("❌ FATAL: Failed to load context blacklist");
+        error!("❌ FATAL: Failed to load context blacklist for owner {}", owner.owner_id);
         error!("{}", e);
         error!("\nThe system cannot start without proper configuration.");
         error!("See error message above for fix instructions.");
         e
     })?;
-
-    // Initialize session graph cache
-    let graph_cache = Arc::new(SessionGraphCache::new(config.cache_size_mb));
-    info!("✅ Session graph cache initialized");
+    // Without a readable config similarity stays on the column model until the next config event
+    if let Err(e) = vector_store.load_embedding_model().await {
+        warn!("⚠️  Failed to load the embedding model config for owner {}: {}", owner.owner_id, e);
+    }
 
     // Initialize RCRT API client
     let rcrt_client = Arc::new(
-        RcrtClient::new(&config.rcrt_api_url, &config.owner_id, &config.agent_id).await?
+        RcrtClient::new(&shared.config.rcrt_api_url, &owner.owner_id.to_string(), &owner.agent_id).await?
+            .with_retry(RetryPolicy {
+                retries: shared.config.api_retries,
+                backoff: Duration::from_millis(shared.config.api_retry_backoff_ms),
+                ..RetryPolicy::default()
+            })
+            .with_restart_policy(shared.supervisor.policy())
     );
     info!("✅ RCRT client connected");
 
-    // Initialize entity extractor (regex-based)
-    let model_path = env::var("GLINER_MODEL_PATH")
-        .unwrap_or_else(|_| "/app/models/gliner_small.onnx".to_string());
-    let tokenizer_path = env::var("GLINER_TOKENIZER_PATH")
-        .unwrap_or_else(|_| "/app/models/tokenizer.json".to_string());
-    
-    let entity_extractor = Arc::new(EntityExtractor::new(&model_path, &tokenizer_path)?);
-    info!("✅ Entity extractor initialized");
+    // Listed in the dashboard under its id and roles rather than as an unknown agent
+    match rcrt_client.register_agent(&["subscriber", "emitter"]).await {
+        Ok(()) => info!("✅ Registered as agent {}", owner.agent_id),
+        Err(e) => warn!("⚠️  Agent registration failed: {}. Continuing anyway.", e),
+    }
 
     // Run startup backfill for existing breadcrumbs without entities
     info!("🔄 Running startup backfill...");
     if let Err(e) = entity_worker::startup_backfill(
         vector_store.clone(),
-        entity_extractor.clone(),
-        &db_pool,
+        shared.entity_extractor.clone(),
     ).await {
         error!("⚠️  Startup backfill failed: {}. Continuing anyway.", e);
     } else {
@@ -121,53 +237,97 @@ async fn main() -> Result<()> {
     let event_handler = EventHandler::new(
         rcrt_client.clone(),
         vector_store.clone(),
-        graph_cache.clone(),
-        entity_extractor.clone(),
-        config.clone(),
+        shared.graph_cache.clone(),
+        shared.entity_extractor.clone(),
+        shared.token_counter.clone(),
+        owner,
+        shared.config.clone(),
     );
-    info!("✅ Event handler initialized");
 
     // Start entity extraction worker (SSE)
     let entity_worker = entity_worker::EntityWorker::new(
         rcrt_client.clone(),
         vector_store.clone(),
-        entity_extractor.clone(),
+        shared.entity_extractor.clone(),
+        shared.config.entity_worker_concurrency,
+        shared.config.entity_queue_capacity,
     );
     
-    info!("🔧 Starting entity extraction worker...");
-    let entity_worker_handle = tokio::spawn(async move {
-        if let Err(e) = entity_worker.start().await {
-            error!("❌ Entity worker failed: {}", e);
-        }
-    });
+    // Each runs under the supervisor; its task ends only when the restart budget is spent
+    let span = info_span!("owner", owner_id = %owner.owner_id);
+    let (owner_id, supervisor) = (owner.owner_id, shared.supervisor.clone());
+    let entity_worker = Arc::new(entity_worker);
+    let label = format!("Entity worker for owner {}", owner.owner_id);
+    tasks.spawn(async move {
+        let e = supervisor.supervise(owner_id, "entity_worker", || {
+            let entity_worker = entity_worker.clone();
+            async move { entity_worker.start().await }
+        }).await;
+        error!("❌ Entity worker gave up: {:#}", e);
+        label
+    }.instrument(span.clone()));
 
     // Start SSE event stream (for context assembly triggers)
-    info!("📡 Starting SSE event stream for context assembly...");
-    let event_handler_handle = tokio::spawn(async move {
-        if let Err(e) = event_handler.start().await {
-            error!("❌ Event handler failed: {}", e);
+    let supervisor = shared.supervisor.clone();
+    let event_handler = Arc::new(event_handler);
+    let label = format!("Event handler for owner {}", owner.owner_id);
+    tasks.spawn(async move {
+        let e = supervisor.supervise(owner_id, "event_handler", || {
+            let event_handler = event_handler.clone();
+            async move { event_handler.start().await }
+        }).await;
+        error!("❌ Event handler gave up: {:#}", e);
+        label
+    }.instrument(span));
+
+    // Same pipeline over the read-only pool; its publisher only renders
+    if let Some(dry_run) = &shared.dry_run {
+        let pipeline = ContextPipeline::new(
+            Arc::new(vector_store.on_pool(dry_run.pool().clone())),
+            shared.graph_cache.clone(),
+            shared.entity_extractor.clone(),
+            shared.token_counter.clone(),
+            AssemblySettings::from(&shared.config),
+        );
+        let publisher = ContextPublisher::new(rcrt_client.clone(), shared.token_counter.clone(), shared.config.publish_retries);
+        dry_run.add_owner(owner.owner_id, OwnerDryRun::new(pipeline, publisher));
+    }
+
+    shared.readiness.add_owner(owner.owner_id, vector_store, rcrt_client);
+    info!("✅ Owner {} ready", owner.owner_id);
+    Ok(())
+}
+
+/// One-off targeted entity re-extraction; runs instead of the service and exits.
+/// Needs only DATABASE_URL, so it is limited to whoever holds database credentials.
+async fn run_reprocess(args: &[String]) -> Result<()> {
+    let args = reprocess::ReprocessArgs::parse(args)?;
+    let config = Config::from_env()?;
+    let owner_id = match (args.owner, config.owners.as_slice()) {
+        (Some(owner_id), _) => owner_id,
+        (None, [only]) => only.owner_id,
+        (None, _) => anyhow::bail!("{} owners are configured; pass --owner", config.owners.len()),
+    };
+    let db_pool = sqlx::postgres::PgPoolOptions::new()
+        .max_connections(2)
+        .connect(&config.database_url)
+        .await?;
+    let vector_store = VectorStore::new(db_pool, owner_id);
+    let entity_extractor = EntityExtractor::new()?;
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    let flag = cancel.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            info!("🛑 Cancelling after the current batch...");
+            flag.store(true, Ordering::SeqCst);
         }
     });
 
-    // Keep running until shutdown signal
-    info!("💚 Context Builder is running");
-    info!("   - Entity extraction: SSE stream");
-    info!("   - Context assembly: SSE stream");
-    
-    tokio::select! {
-        _ = tokio::signal::ctrl_c() => {
-            info!("🛑 Received shutdown signal...");
-        }
-        _ = entity_worker_handle => {
-            error!("❌ Entity worker stopped unexpectedly");
-        }
-        _ = event_handler_handle => {
-            error!("❌ Event handler stopped unexpectedly");
-        }
+    let summary = reprocess::run(&args, &vector_store, &entity_extractor, cancel).await?;
+    if summary.failed > 0 {
+        anyhow::bail!("{} breadcrumbs failed to reprocess", summary.failed);
     }
-    
-    info!("🛑 Shutting down...");
-
     Ok(())
 }
 
@@ -0,0 +1,201 @@
+/*!
+ * Prometheus metrics and the /metrics listener (which also answers /health, /ready and,
+ * with DEBUG_TOKEN set, POST /debug/assemble)
+ *
+ * Named like rcrt-server's: `_total` counters split by an `outcome` label and
+ * `_duration_seconds` histograms. The DB fallback counter lives with the
+ * fallback writer (output/fallback.rs).
+ */
+
+use anyhow::Result;
+use axum::{routing::{get, post}, Router};
+use prometheus::{Encoder, Histogram, HistogramTimer, HistogramVec, IntCounter, IntCounterVec, IntGauge, TextEncoder};
+use prometheus::{register_histogram, register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge};
+use std::sync::{Arc, OnceLock};
+
+use crate::dry_run::{self, DryRun};
+use crate::health::{self, Readiness};
+
+static EVENTS: OnceLock<IntCounterVec> = OnceLock::new();
+static ASSEMBLIES: OnceLock<IntCounterVec> = OnceLock::new();
+static ASSEMBLIES_SKIPPED: OnceLock<IntCounter> = OnceLock::new();
+static ASSEMBLY_DURATION: OnceLock<Histogram> = OnceLock::new();
+static ASSEMBLY_E2E: OnceLock<Histogram> = OnceLock::new();
+static PUBLISH_FAILURES: OnceLock<IntCounterVec> = OnceLock::new();
+static ENTITY_EXTRACTIONS: OnceLock<IntCounterVec> = OnceLock::new();
+static BACKFILL_ROWS: OnceLock<IntCounterVec> = OnceLock::new();
+static ENTITY_QUEUE_DEPTH: OnceLock<IntGauge> = OnceLock::new();
+static ENTITY_QUEUE_DROPPED: OnceLock<IntCounter> = OnceLock::new();
+static ENTITY_EXTRACTION_LATENCY: OnceLock<Histogram> = OnceLock::new();
+static GRAPH_CACHE_LOOKUPS: OnceLock<IntCounterVec> = OnceLock::new();
+static GRAPH_CACHE_SESSIONS: OnceLock<IntGauge> = OnceLock::new();
+static GRAPH_CACHE_BYTES: OnceLock<IntGauge> = OnceLock::new();
+static SSE_RECONNECTS: OnceLock<IntCounterVec> = OnceLock::new();
+static SSE_SERVER_RESTARTS: OnceLock<IntCounter> = OnceLock::new();
+static DB_QUERY_DURATION: OnceLock<HistogramVec> = OnceLock::new();
+static WORKER_RESTARTS: OnceLock<IntCounterVec> = OnceLock::new();
+
+/// SSE events by consumer (`context` or `entities`), event type and outcome
+/// (`received`, `processed`, `errored`)
+pub fn events() -> &'static IntCounterVec {
+    EVENTS.get_or_init(|| register_int_counter_vec!("context_builder_events_total", "SSE events seen by the context-builder", &["consumer", "type", "outcome"]).unwrap())
+}
+
+/// Context assemblies `started`, `completed` or `failed` (assembly or publish)
+pub fn assemblies() -> &'static IntCounterVec {
+    ASSEMBLIES.get_or_init(|| register_int_counter_vec!("context_assemblies_total", "Context assemblies by outcome", &["outcome"]).unwrap())
+}
+
+/// Completed assemblies not published because the context breadcrumb already holds the same content
+pub fn assemblies_skipped() -> &'static IntCounter {
+    ASSEMBLIES_SKIPPED.get_or_init(|| register_int_counter!("context_assembly_skipped_total", "Assemblies whose unchanged context was not republished").unwrap())
+}
+
+fn assembly_duration() -> &'static Histogram {
+    ASSEMBLY_DURATION.get_or_init(|| register_histogram!(
+        "context_assembly_duration_seconds", "Time from trigger to published context, for completed assemblies",
+        vec![0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0]
+    ).unwrap())
+}
+
+/// Observe with `observe_duration` on success; dropping it on failure also observes, so use `stop_and_discard`
+pub fn assembly_timer() -> HistogramTimer {
+    assembly_duration().start_timer()
+}
+
+/// Time from the trigger's creation on the server to its context being published; see latency.rs
+pub fn assembly_e2e() -> &'static Histogram {
+    ASSEMBLY_E2E.get_or_init(|| register_histogram!(
+        "context_assembly_e2e_seconds", "Time from a trigger's creation to its published context, across server and context-builder",
+        vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0]
+    ).unwrap())
+}
+
+/// API upserts of a context breadcrumb that failed and were `retried`, or that `gave_up` after the last retry
+pub fn publish_failures() -> &'static IntCounterVec {
+    PUBLISH_FAILURES.get_or_init(|| register_int_counter_vec!("context_publish_failures_total", "Failed context upserts through the RCRT API", &["outcome"]).unwrap())
+}
+
+/// Entity extractions by `source` (`worker` or `backfill`) and outcome (`extracted`, `empty`, `failed`)
+pub fn entity_extractions() -> &'static IntCounterVec {
+    ENTITY_EXTRACTIONS.get_or_init(|| register_int_counter_vec!("entity_extractions_total", "Entity extractions by source and outcome", &["source", "outcome"]).unwrap())
+}
+
+/// Backfill rows `found`, then `processed` or `skipped`; progress is (processed + skipped) / found
+pub fn backfill_rows() -> &'static IntCounterVec {
+    BACKFILL_ROWS.get_or_init(|| register_int_counter_vec!("entity_backfill_rows_total", "Breadcrumbs found and worked through by entity backfills", &["outcome"]).unwrap())
+}
+
+/// Breadcrumbs waiting in the entity worker queues
+pub fn entity_queue_depth() -> &'static IntGauge {
+    ENTITY_QUEUE_DEPTH.get_or_init(|| register_int_gauge!("entity_queue_depth", "Breadcrumbs queued for entity extraction").unwrap())
+}
+
+/// Queued breadcrumbs dropped to make room; the backfill after the queue drains picks them up
+pub fn entity_queue_dropped() -> &'static IntCounter {
+    ENTITY_QUEUE_DROPPED.get_or_init(|| register_int_counter!("entity_queue_dropped_total", "Breadcrumbs dropped from a full entity queue").unwrap())
+}
+
+/// Time from a bc.created event being queued to its extraction finishing
+pub fn entity_extraction_latency() -> &'static Histogram {
+    ENTITY_EXTRACTION_LATENCY.get_or_init(|| register_histogram!(
+        "entity_extraction_latency_seconds", "Time from queueing a breadcrumb to finishing its entity extraction",
+        vec![0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0]
+    ).unwrap())
+}
+
+/// Session graph cache lookups, `hit` or `miss`
+pub fn graph_cache_lookups() -> &'static IntCounterVec {
+    GRAPH_CACHE_LOOKUPS.get_or_init(|| register_int_counter_vec!("graph_cache_lookups_total", "Session graph cache lookups by result", &["result"]).unwrap())
+}
+
+/// Session graphs currently cached
+pub fn graph_cache_sessions() -> &'static IntGauge {
+    GRAPH_CACHE_SESSIONS.get_or_init(|| register_int_gauge!("graph_cache_sessions", "Session graphs held in the LRU cache").unwrap())
+}
+
+/// Estimated bytes of the cached session graphs, capped by CACHE_SIZE_MB
+pub fn graph_cache_bytes() -> &'static IntGauge {
+    GRAPH_CACHE_BYTES.get_or_init(|| register_int_gauge!("graph_cache_bytes", "Estimated memory held by cached session graphs").unwrap())
+}
+
+/// SSE reconnects after the stream `ended`, went `stale` (missed heartbeats), was `unauthorized`
+/// (token renewed first) or hit an `error`
+pub fn sse_reconnects() -> &'static IntCounterVec {
+    SSE_RECONNECTS.get_or_init(|| register_int_counter_vec!("sse_reconnects_total", "SSE stream reconnects by reason", &["reason"]).unwrap())
+}
+
+/// Server restarts noticed by the ping `seq` going backwards
+pub fn sse_server_restarts() -> &'static IntCounter {
+    SSE_SERVER_RESTARTS.get_or_init(|| register_int_counter!("sse_server_restarts_total", "Server restarts seen in SSE ping sequence numbers").unwrap())
+}
+
+/// Supervised worker runs that ended, by worker (`entity_worker`, `event_handler`) and how
+/// (`panicked`, `failed`, `stopped`); each is followed by a restart unless the budget is spent
+pub fn worker_restarts() -> &'static IntCounterVec {
+    WORKER_RESTARTS.get_or_init(|| register_int_counter_vec!("worker_restarts_total", "Supervised worker runs that ended, by how", &["worker", "reason"]).unwrap())
+}
+
+fn db_query_duration() -> &'static HistogramVec {
+    DB_QUERY_DURATION.get_or_init(|| register_histogram_vec!(
+        "db_query_duration_seconds", "Database time for VectorStore queries", &["method"],
+        vec![0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5]
+    ).unwrap())
+}
+
+/// Observes on drop; `method` is the VectorStore method name
+pub fn db_timer(method: &str) -> HistogramTimer {
+    db_query_duration().with_label_values(&[method]).start_timer()
+}
+
+/// Register the unlabeled metrics so they are scraped as zero before anything happens
+pub fn init() {
+    assembly_duration();
+    assembly_e2e();
+    assemblies_skipped();
+    graph_cache_sessions();
+    graph_cache_bytes();
+    entity_queue_depth();
+    entity_queue_dropped();
+    entity_extraction_latency();
+}
+
+/// Everything registered, in the Prometheus text format
+pub fn render() -> Vec<u8> {
+    let mut buf = Vec::new();
+    let _ = TextEncoder::new().encode(&prometheus::gather(), &mut buf);
+    buf
+}
+
+async fn metrics() -> ([(&'static str, &'static str); 1], Vec<u8>) {
+    ([("content-type", "text/plain; version=0.0.4")], render())
+}
+
+/// Serve GET /metrics, GET /health and GET /ready on an already bound listener, and
+/// POST /debug/assemble when dry runs are enabled
+pub async fn serve(listener: tokio::net::TcpListener, readiness: Arc<Readiness>, dry_run: Option<Arc<DryRun>>) -> Result<()> {
+    let mut app = Router::new()
+        .route("/metrics", get(metrics))
+        .route("/health", get(|| async { "ok" }))
+        .route("/ready", get(health::ready))
+        .with_state(readiness);
+    if let Some(dry_run) = dry_run {
+        app = app.merge(Router::new().route("/debug/assemble", post(dry_run::assemble)).with_state(dry_run));
+    }
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_before_anything_is_recorded() {
+        init();
+        let text = String::from_utf8(render()).unwrap();
+        assert!(text.contains("# TYPE context_assembly_duration_seconds histogram"));
+        assert!(text.contains("# TYPE context_assembly_e2e_seconds histogram"));
+        assert!(text.contains("# TYPE graph_cache_sessions gauge"));
+    }
+}
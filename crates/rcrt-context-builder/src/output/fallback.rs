@@ -0,0 +1,61 @@
+/*!
+ * Direct database fallback for context publishing
+ *
+ * When rcrt-server is unreachable (deploys, restarts) the assembled context
+ * is written straight to Postgres through rcrt-core, so the version bump,
+ * checksum and history row match what the API would have produced. No NATS
+ * event is emitted on this path; consumers see the row on their next read.
+ */
+
+use anyhow::Result;
+use prometheus::{IntCounterVec, register_int_counter_vec};
+use rcrt_core::db::Db;
+use rcrt_core::models::BreadcrumbCreate;
+use sqlx::PgPool;
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+pub const CONTEXT_SCHEMA: &str = "agent.context.v1";
+
+static FALLBACK_WRITES: OnceLock<IntCounterVec> = OnceLock::new();
+
+/// Context publishes that bypassed the API, by outcome
+pub fn fallback_writes() -> &'static IntCounterVec {
+    FALLBACK_WRITES.get_or_init(|| register_int_counter_vec!("context_publish_db_fallback_total", "Context publishes written directly to Postgres", &["result"]).unwrap())
+}
+
+pub struct DbFallback {
+    db: Db,
+    owner_id: Uuid,
+    agent_id: Uuid,
+}
+
+impl DbFallback {
+    pub fn new(pool: PgPool, owner_id: Uuid, agent_id: Uuid) -> Self {
+        DbFallback { db: Db { pool }, owner_id, agent_id }
+    }
+
+    /// Create or update the context breadcrumb keyed by `key_tags`, under the same
+    /// upsert key the API uses; returns its id
+    pub async fn write(&self, title: &str, tags: &[String], key_tags: &[String], context: serde_json::Value) -> Result<Uuid> {
+        let create = BreadcrumbCreate {
+            title: title.to_string(),
+            description: None,
+            semantic_version: None,
+            context,
+            tags: tags.to_vec(),
+            schema_name: Some(CONTEXT_SCHEMA.to_string()),
+            llm_hints: None,
+            visibility: None,
+            sensitivity: None,
+            ttl: None,
+            ttl_type: None,
+            ttl_config: None,
+            ttl_source: None,
+            entity_keywords: None,
+            entities: None,
+        };
+        let upserted = self.db.upsert_breadcrumb_by_key(self.owner_id, self.agent_id, key_tags, create, None, None).await?;
+        Ok(upserted.breadcrumb.id)
+    }
+}
@@ -0,0 +1,283 @@
+/*!
+ * Context formatting
+ *
+ * Renders the published breadcrumbs into `formatted_context`, a text block
+ * grouped into titled sections. An agent.def.v1 can replace the layout with a
+ * `context_formatting` block:
+ *
+ * ```json
+ * "context_formatting": {
+ *   "sections": [
+ *     { "title": "AVAILABLE TOOLS", "schemas": ["tool.catalog.v1", "tool.code.v1"] },
+ *     { "title": "CONVERSATION", "schemas": ["user.message.v1", "agent.response.v1"] },
+ *     { "title": "SYSTEM", "schemas": ["system.*"] }
+ *   ],
+ *   "templates": { "user.message.v1": "User: {{content.content}}" }
+ * }
+ * ```
+ *
+ * A breadcrumb goes in the first section with a matching schema (exact,
+ * `prefix.*`, or `*`). Breadcrumbs no section claims go in a trailing
+ * ADDITIONAL CONTEXT section. Templates are handlebars and see the published
+ * breadcrumb (`id`, `schema_name`, `created_at`, `content`). A schema without a
+ * template, or whose template fails, is rendered as pretty-printed JSON.
+ */
+
+use crate::vector_store::BreadcrumbRow;
+use chrono::{DateTime, Utc};
+use handlebars::Handlebars;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+/// Title of the section for breadcrumbs no other section claims
+pub const ADDITIONAL_SECTION: &str = "ADDITIONAL CONTEXT";
+
+/// Used without a `context_formatting` block; the same groups as `schema_section`
+const DEFAULT_SECTIONS: &[(&str, &[&str])] = &[
+    ("CONVERSATION", &["user.message.v1", "agent.response.v1"]),
+    ("TOOL RESULTS", &["tool.response.v1"]),
+    ("AVAILABLE TOOLS", &["tool.catalog.v1"]),
+    ("KNOWLEDGE", &["document.v1", "code.snippet.v1", "workflow.result.v1"]),
+    ("TOOL REQUESTS", &["tool.request.v1"]),
+    ("SYSTEM", &["system.*"]),
+];
+
+handlebars::handlebars_helper!(json_helper: |value: Json| serde_json::to_string_pretty(value).unwrap_or_default());
+
+#[derive(Debug, Clone, Deserialize)]
+struct SectionSpec {
+    title: String,
+    schemas: Vec<String>,
+}
+
+/// agent.def.v1 `context_formatting`
+#[derive(Debug, Clone, Default, Deserialize)]
+struct FormattingSpec {
+    #[serde(default)]
+    sections: Vec<SectionSpec>,
+    /// Handlebars template per schema name
+    #[serde(default)]
+    templates: HashMap<String, String>,
+}
+
+fn schema_matches(pattern: &str, schema_name: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => schema_name.starts_with(prefix),
+        None => pattern == schema_name,
+    }
+}
+
+/// A compiled section layout plus item templates
+pub struct ContextFormatter {
+    sections: Vec<SectionSpec>,
+    /// Templates registered under their schema name
+    templates: Handlebars<'static>,
+}
+
+impl Default for ContextFormatter {
+    fn default() -> Self {
+        Self::from_spec(FormattingSpec::default())
+    }
+}
+
+impl ContextFormatter {
+    fn from_spec(spec: FormattingSpec) -> Self {
+        let sections = if spec.sections.is_empty() {
+            DEFAULT_SECTIONS.iter()
+                .map(|(title, schemas)| SectionSpec { title: title.to_string(), schemas: schemas.iter().map(|s| s.to_string()).collect() })
+                .collect()
+        } else {
+            spec.sections
+        };
+        let mut templates = Handlebars::new();
+        templates.set_strict_mode(false);
+        // Plain text for an LLM, not HTML
+        templates.register_escape_fn(handlebars::no_escape);
+        templates.register_helper("json", Box::new(json_helper));
+        for (schema_name, template) in spec.templates {
+            if let Err(e) = templates.register_template_string(&schema_name, template) {
+                tracing::warn!("⚠️  Invalid context_formatting template for {}, using JSON: {}", schema_name, e);
+            }
+        }
+        ContextFormatter { sections, templates }
+    }
+
+    /// From an agent.def.v1 context; a missing or malformed `context_formatting` gives the defaults
+    pub fn from_agent_def(context: &serde_json::Value) -> Self {
+        let spec = match context.get("context_formatting") {
+            Some(block) => serde_json::from_value(block.clone()).unwrap_or_else(|e| {
+                tracing::warn!("⚠️  Ignoring malformed context_formatting: {}", e);
+                FormattingSpec::default()
+            }),
+            None => FormattingSpec::default(),
+        };
+        Self::from_spec(spec)
+    }
+
+    /// Title of the section `schema_name` is rendered in
+    pub fn section_for(&self, schema_name: &str) -> &str {
+        self.sections.iter()
+            .find(|section| section.schemas.iter().any(|p| schema_matches(p, schema_name)))
+            .map(|section| section.title.as_str())
+            .unwrap_or(ADDITIONAL_SECTION)
+    }
+
+    /// One published breadcrumb (`{id, schema_name, created_at, content}`) as text
+    pub fn render_item(&self, item: &serde_json::Value) -> String {
+        let schema_name = item["schema_name"].as_str().unwrap_or_default();
+        if self.templates.has_template(schema_name) {
+            match self.templates.render(schema_name, item) {
+                Ok(text) => return text,
+                Err(e) => tracing::warn!("⚠️  context_formatting template for {} failed, using JSON: {}", schema_name, e),
+            }
+        }
+        serde_json::to_string_pretty(&item["content"]).unwrap_or_default()
+    }
+
+    /// All items grouped by section, sections in layout order, items in their given order
+    pub fn render(&self, items: &[serde_json::Value]) -> String {
+        let mut grouped: Vec<(&str, Vec<String>)> = self.sections.iter().map(|s| (s.title.as_str(), Vec::new())).collect();
+        grouped.push((ADDITIONAL_SECTION, Vec::new()));
+        for item in items {
+            let title = self.section_for(item["schema_name"].as_str().unwrap_or_default());
+            if let Some((_, rendered)) = grouped.iter_mut().find(|(t, _)| *t == title) {
+                rendered.push(self.render_item(item));
+            }
+        }
+        grouped.into_iter()
+            .filter(|(_, rendered)| !rendered.is_empty())
+            .map(|(title, rendered)| format!("=== {} ===\n{}", title, rendered.join("\n\n")))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+/// Formatters per consumer, rebuilt when its agent.def.v1 changes
+#[derive(Default)]
+pub struct FormatterCache {
+    default: Arc<ContextFormatter>,
+    /// consumer id -> (definition id, updated_at, formatter); updated_at moves with every version
+    by_consumer: Mutex<HashMap<String, (Uuid, DateTime<Utc>, Arc<ContextFormatter>)>>,
+}
+
+impl FormatterCache {
+    pub fn get(&self, consumer_id: &str, agent_def: Option<&BreadcrumbRow>) -> Arc<ContextFormatter> {
+        let Some(def) = agent_def.filter(|def| def.context.get("context_formatting").is_some()) else {
+            return self.default.clone();
+        };
+        let Ok(mut cache) = self.by_consumer.lock() else { return self.default.clone() };
+        if let Some((id, updated_at, formatter)) = cache.get(consumer_id) {
+            if *id == def.id && *updated_at == def.updated_at {
+                return formatter.clone();
+            }
+        }
+        let formatter = Arc::new(ContextFormatter::from_agent_def(&def.context));
+        cache.insert(consumer_id.to_string(), (def.id, def.updated_at, formatter.clone()));
+        formatter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn item(schema_name: &str, content: serde_json::Value) -> serde_json::Value {
+        json!({ "id": Uuid::nil(), "schema_name": schema_name, "created_at": "2025-01-01T00:00:00Z", "content": content })
+    }
+
+    /// What the publisher passes in: newest conversation first, then whatever retrieval found
+    fn fixture() -> Vec<serde_json::Value> {
+        vec![
+            item("user.message.v1", json!({ "content": "What's the weather in Oslo?" })),
+            item("tool.catalog.v1", json!({ "tools": [{ "name": "weather" }] })),
+            item("system.stats.v1", json!({ "load": 0.5 })),
+            item("note.v1", json!({ "text": "prefers metric" })),
+            item("agent.response.v1", json!({ "content": "Let me check." })),
+        ]
+    }
+
+    fn agent_def(formatting: serde_json::Value) -> BreadcrumbRow {
+        BreadcrumbRow {
+            id: Uuid::new_v4(),
+            schema_name: "agent.def.v1".to_string(),
+            title: None,
+            tags: vec![],
+            context: json!({ "agent_id": "chat", "context_formatting": formatting }),
+            embedding: None,
+            entities: None,
+            entity_keywords: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            score: None,
+        }
+    }
+
+    #[test]
+    fn test_default_layout_matches_schema_sections() {
+        let text = ContextFormatter::default().render(&fixture());
+        let conversation = text.find("=== CONVERSATION ===").unwrap();
+        let tools = text.find("=== AVAILABLE TOOLS ===").unwrap();
+        let system = text.find("=== SYSTEM ===").unwrap();
+        let additional = text.find("=== ADDITIONAL CONTEXT ===").unwrap();
+        assert!(conversation < tools && tools < system && system < additional);
+        assert!(!text.contains("=== KNOWLEDGE ==="), "empty sections are left out");
+        // No templates: content is pretty-printed JSON
+        assert!(text.contains("{\n  \"text\": \"prefers metric\"\n}"));
+    }
+
+    #[test]
+    fn test_custom_layout_and_templates() {
+        let formatter = ContextFormatter::from_agent_def(&agent_def(json!({
+            "sections": [
+                { "title": "TOOLS", "schemas": ["tool.*"] },
+                { "title": "CHAT", "schemas": ["user.message.v1", "agent.response.v1"] }
+            ],
+            "templates": {
+                "user.message.v1": "User: {{content.content}}",
+                "agent.response.v1": "Assistant: {{content.content}}",
+                "tool.catalog.v1": "{{#each content.tools}}- {{name}}\n{{/each}}"
+            }
+        })).context);
+
+        let text = formatter.render(&fixture());
+        assert_eq!(text, [
+            "=== TOOLS ===\n- weather\n",
+            "=== CHAT ===\nUser: What's the weather in Oslo?\n\nAssistant: Let me check.",
+            "=== ADDITIONAL CONTEXT ===\n{\n  \"load\": 0.5\n}\n\n{\n  \"text\": \"prefers metric\"\n}",
+        ].join("\n\n"));
+    }
+
+    #[test]
+    fn test_bad_templates_and_blocks_fall_back() {
+        let formatter = ContextFormatter::from_agent_def(&agent_def(json!({
+            "templates": { "user.message.v1": "{{#if}}", "note.v1": "{{json content}}" }
+        })).context);
+        // No sections given: the default layout, with the valid template still applied
+        assert_eq!(formatter.section_for("tool.catalog.v1"), "AVAILABLE TOOLS");
+        assert!(formatter.render_item(&fixture()[0]).contains("\"content\": \"What's the weather in Oslo?\""));
+        assert_eq!(formatter.render_item(&fixture()[3]), "{\n  \"text\": \"prefers metric\"\n}");
+
+        let malformed = ContextFormatter::from_agent_def(&json!({ "context_formatting": { "sections": "nope" } }));
+        assert_eq!(malformed.section_for("user.message.v1"), "CONVERSATION");
+    }
+
+    #[test]
+    fn test_cache_rebuilds_when_the_definition_changes() {
+        let cache = FormatterCache::default();
+        let mut def = agent_def(json!({ "sections": [{ "title": "ONLY", "schemas": ["*"] }] }));
+        let first = cache.get("chat", Some(&def));
+        assert!(Arc::ptr_eq(&first, &cache.get("chat", Some(&def))));
+        assert_eq!(first.section_for("note.v1"), "ONLY");
+
+        def.updated_at += chrono::Duration::seconds(1);
+        def.context["context_formatting"] = json!({ "sections": [{ "title": "NOTES", "schemas": ["note.v1"] }] });
+        let second = cache.get("chat", Some(&def));
+        assert!(!Arc::ptr_eq(&first, &second));
+        assert_eq!(second.section_for("note.v1"), "NOTES");
+
+        assert_eq!(cache.get("chat", None).section_for("note.v1"), ADDITIONAL_SECTION);
+    }
+}
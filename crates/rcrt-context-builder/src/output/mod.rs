@@ -5,6 +5,9 @@
  */
 
 mod publisher;
+mod fallback;
+mod formatting;
 
-pub use publisher::ContextPublisher;
+pub use publisher::{ContextApi, ContextPublisher, RenderedContext};
+pub use fallback::DbFallback;
 
@@ -2,100 +2,600 @@
  * Context breadcrumb publisher
  */
 
+use super::fallback::{DbFallback, CONTEXT_SCHEMA, fallback_writes};
+use super::formatting::FormatterCache;
 use crate::{
-    rcrt_client::RcrtClient,
-    retrieval::AssembledContext,
+    latency::AssemblyTiming,
+    metrics,
+    rcrt_client::{RcrtClient, BulkContextViews},
+    retrieval::{AssembledContext, ContextBudget, ProvenanceEntry, schema_priority, schema_section, fit_to_budget, provenance_fields},
+    token_counter::TokenCounter,
+    vector_store::BreadcrumbRow,
 };
 use anyhow::Result;
-use std::sync::Arc;
+use lru::LruCache;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use uuid::Uuid;
 
-pub struct ContextPublisher {
-    rcrt_client: Arc<RcrtClient>,
+/// (consumer, session) contexts remembered for skipping unchanged publishes; past this the
+/// least recently published are forgotten and republish once on their next trigger
+const PUBLISHED_SESSIONS: usize = 10_000;
+/// Trigger ids kept in a context's `recent_triggers`, newest first
+const RECENT_TRIGGERS: usize = 10;
+
+/// RCRT API calls made by the publisher (lets tests stand in for the server)
+pub trait ContextApi: Send + Sync {
+    fn get_breadcrumbs(&self, ids: &[Uuid], record_access: bool) -> impl Future<Output = Result<BulkContextViews>> + Send;
+    fn upsert_breadcrumb(&self, schema_name: &str, title: &str, tags: Vec<String>, key_tags: &[String], context: serde_json::Value) -> impl Future<Output = Result<(Uuid, i32)>> + Send;
+}
+
+impl ContextApi for RcrtClient {
+    async fn get_breadcrumbs(&self, ids: &[Uuid], record_access: bool) -> Result<BulkContextViews> {
+        RcrtClient::get_breadcrumbs(self, ids, record_access).await
+    }
+
+    async fn upsert_breadcrumb(&self, schema_name: &str, title: &str, tags: Vec<String>, key_tags: &[String], context: serde_json::Value) -> Result<(Uuid, i32)> {
+        RcrtClient::upsert_breadcrumb(self, schema_name, title, tags, key_tags, context).await
+    }
+}
+
+pub struct ContextPublisher<C: ContextApi = RcrtClient> {
+    rcrt_client: Arc<C>,
+    token_counter: Arc<TokenCounter>,
+    /// API retries after the first failed write
+    publish_retries: u32,
+    db_fallback: Option<DbFallback>,
+    /// `formatted_context` layouts from each consumer's agent.def.v1
+    formatters: FormatterCache,
+    /// What was last written for each (consumer, session)
+    published: Mutex<LruCache<(String, String), Published>>,
 }
 
-impl ContextPublisher {
-    pub fn new(rcrt_client: Arc<RcrtClient>) -> Self {
-        ContextPublisher { rcrt_client }
+/// An assembled context laid out as it is published, before the unchanged-context check and the write
+pub struct RenderedContext {
+    /// `{id, schema_name, created_at, content}` with llm_hints applied, cut to the budget
+    pub breadcrumbs: Vec<serde_json::Value>,
+    pub formatted_context: String,
+    pub token_estimate: usize,
+    pub truncated: bool,
+    /// `provenance`, `provenance_dropped`, `provenance_omitted` and `provenance_semantic_path`;
+    /// empty when the consumer opted out of provenance
+    pub provenance: serde_json::Map<String, serde_json::Value>,
+}
+
+/// The last context written for a (consumer, session) and the triggers seen since
+#[derive(Default)]
+struct Published {
+    /// `content_hash` of the last successful write; empty until one succeeds
+    content_hash: String,
+    recent_triggers: VecDeque<Uuid>,
+}
+
+impl<C: ContextApi> ContextPublisher<C> {
+    pub fn new(rcrt_client: Arc<C>, token_counter: Arc<TokenCounter>, publish_retries: u32) -> Self {
+        ContextPublisher {
+            rcrt_client,
+            token_counter,
+            publish_retries,
+            db_fallback: None,
+            formatters: FormatterCache::default(),
+            published: Mutex::new(LruCache::new(NonZeroUsize::new(PUBLISHED_SESSIONS).unwrap())),
+        }
+    }
+    
+    /// Write contexts straight to Postgres when the API stays unreachable
+    pub fn with_db_fallback(mut self, fallback: DbFallback) -> Self {
+        self.db_fallback = Some(fallback);
+        self
     }
     
-    /// Extract LLM-optimized content from a breadcrumb using server-side llm_hints
-    async fn extract_llm_content(&self, breadcrumb_id: Uuid) -> Result<serde_json::Value> {
-        // Fetch breadcrumb context from server (applies llm_hints automatically)
-        let bc = self.rcrt_client.get_breadcrumb(breadcrumb_id).await
-            .map_err(|e| anyhow::anyhow!("Failed to fetch LLM content for {}: {}", breadcrumb_id, e))?;
-        Ok(bc.context)
+    /// LLM-optimized content for each breadcrumb, fetched in one call (the server applies llm_hints)
+    async fn extract_llm_content(&self, ids: &[Uuid], record_access: bool) -> Result<HashMap<Uuid, serde_json::Value>> {
+        let views = self.rcrt_client.get_breadcrumbs(ids, record_access).await
+            .map_err(|e| anyhow::anyhow!("Failed to fetch LLM content for {} breadcrumbs: {}", ids.len(), e))?;
+        if !views.missing.is_empty() {
+            tracing::warn!("⚠️  {} breadcrumbs deleted or not visible since retrieval, leaving them out: {:?}", views.missing.len(), views.missing);
+        }
+        Ok(views.breadcrumbs.into_iter().map(|bc| (bc.id, bc.context)).collect())
     }
     
+    /// `agent_def` is the consumer's agent.def.v1, whose `context_formatting` lays out `formatted_context`.
+    /// `timing` goes into the provenance, and a completed publish observes its end-to-end latency
+    #[allow(clippy::too_many_arguments)]
     pub async fn publish_context(
         &self,
         consumer_id: &str,
         session_tag: &str,
         trigger_id: Option<Uuid>,
         context: &AssembledContext,
+        budget: &ContextBudget,
+        agent_def: Option<&BreadcrumbRow>,
+        timing: &AssemblyTiming,
     ) -> Result<()> {
+        let rendered = self.render_context(consumer_id, context, budget, agent_def, true).await?;
+        
+        // An assembly that would write what is already there only records its trigger; the
+        // context breadcrumb keeps its version and subscribers see no update
+        let content_hash = content_hash(&rendered.formatted_context, &rendered.breadcrumbs);
+        let key = (consumer_id.to_string(), session_tag.to_string());
+        let recent_triggers = {
+            let mut published = self.published.lock().unwrap();
+            let last = published.get_or_insert_mut(key.clone(), Published::default);
+            if let Some(id) = trigger_id {
+                last.recent_triggers.push_front(id);
+                last.recent_triggers.truncate(RECENT_TRIGGERS);
+            }
+            if last.content_hash == content_hash {
+                metrics::assemblies_skipped().inc();
+                tracing::debug!("⏭️  Context for {} in {} unchanged ({}), skipping publish", consumer_id, session_tag, content_hash);
+                return Ok(());
+            }
+            last.recent_triggers.clone()
+        };
+        
+        let breadcrumb_count = rendered.breadcrumbs.len();
+        let mut context_payload = serde_json::json!({
+            "consumer_id": consumer_id,
+            "trigger_event_id": trigger_id,
+            "assembled_at": chrono::Utc::now().to_rfc3339(),
+            "token_estimate": rendered.token_estimate,
+            "token_budget": budget.total,
+            "truncated": rendered.truncated,
+            "sources_assembled": context.sources_count,
+            "breadcrumbs": rendered.breadcrumbs,
+            "formatted_context": rendered.formatted_context,
+            "content_hash": content_hash,
+            "recent_triggers": recent_triggers,
+        });
+        for (key, value) in rendered.provenance {
+            context_payload[key.as_str()] = value;
+        }
+        if context.provenance.is_some() {
+            context_payload["provenance_timing"] = serde_json::json!(timing);
+        }
+        
+        if let Err(e) = self.write_via_api(consumer_id, session_tag, &context_payload).await {
+            let Some(fallback) = &self.db_fallback else { return Err(e) };
+            tracing::warn!("⚠️  RCRT API unreachable ({}), writing context for {} directly to database", e, consumer_id);
+            let mut payload = context_payload;
+            payload["published_via"] = serde_json::json!("db-fallback");
+            let title = format!("Context for {}", consumer_id);
+            match fallback.write(&title, &context_tags(consumer_id, session_tag), &context_key(consumer_id, session_tag), payload).await {
+                Ok(id) => {
+                    fallback_writes().with_label_values(&["written"]).inc();
+                    tracing::warn!("⚠️  Context {} written via db-fallback", id);
+                }
+                Err(db_err) => {
+                    fallback_writes().with_label_values(&["failed"]).inc();
+                    return Err(db_err.context(format!("db-fallback after API failure: {}", e)));
+                }
+            }
+        }
+        
+        if let Some(last) = self.published.lock().unwrap().get_mut(&key) {
+            last.content_hash = content_hash;
+        }
+        timing.observe(chrono::Utc::now(), metrics::assembly_e2e());
+        
+        tracing::info!("✅ Published context with {} breadcrumbs (~{} tokens)", 
+            breadcrumb_count, rendered.token_estimate);
+        
+        Ok(())
+    }
+    
+    /// Fetch the llm_hints views of an assembled context, cut them to the budget and lay them out;
+    /// writes nothing. `record_access` counts the fetch as a context assembly on the server
+    pub async fn render_context(
+        &self,
+        consumer_id: &str,
+        context: &AssembledContext,
+        budget: &ContextBudget,
+        agent_def: Option<&BreadcrumbRow>,
+        record_access: bool,
+    ) -> Result<RenderedContext> {
         // Extract lightweight LLM-optimized content from each breadcrumb
         // The server applies llm_hints transforms automatically
-        let mut formatted_breadcrumbs = Vec::new();
+        let ids: Vec<Uuid> = context.breadcrumbs.iter().map(|bc| bc.id).collect();
+        // With a fallback configured, an unreachable API degrades to raw context
+        // (no llm_hints) instead of dropping the whole publish
+        let llm_content = match self.extract_llm_content(&ids, record_access).await {
+            Ok(content) => Some(content),
+            Err(e) if self.db_fallback.is_some() => {
+                tracing::warn!("⚠️  {}; using raw context", e);
+                None
+            }
+            Err(e) => return Err(e),
+        };
         
+        let mut included = Vec::new();
+        let mut formatted_breadcrumbs = Vec::new();
         for bc in &context.breadcrumbs {
-            let llm_content = self.extract_llm_content(bc.id).await?;
+            let content = match &llm_content {
+                Some(content) => match content.get(&bc.id) {
+                    Some(content) => content.clone(),
+                    None => continue,
+                },
+                None => bc.context.clone(),
+            };
             
             // Build lightweight breadcrumb with transformed content
+            included.push(bc);
             formatted_breadcrumbs.push(serde_json::json!({
                 "id": bc.id,
                 "schema_name": bc.schema_name,
                 "created_at": bc.created_at,
-                "content": llm_content,  // Transformed by llm_hints
+                "content": content,  // Transformed by llm_hints
             }));
         }
         
-        // Recalculate token estimate based on actual formatted content
-        let formatted_json = serde_json::to_string(&formatted_breadcrumbs)?;
-        let token_estimate = formatted_json.len() / 3; // ~3 chars per token
+        // Recalculate token count based on actual formatted content
+        let costs: Vec<(u8, usize)> = included.iter()
+            .zip(&formatted_breadcrumbs)
+            .map(|(bc, formatted)| (schema_priority(&bc.schema_name), self.token_counter.count_json(formatted)))
+            .collect();
+        let mut token_estimate: usize = costs.iter().map(|(_, cost)| cost).sum();
+        let mut truncated = context.truncated;
         
-        // Build context payload
-        let context_payload = serde_json::json!({
-            "consumer_id": consumer_id,
-            "trigger_event_id": trigger_id,
-            "assembled_at": chrono::Utc::now().to_rfc3339(),
-            "token_estimate": token_estimate,
-            "sources_assembled": context.sources_count,
-            "breadcrumbs": formatted_breadcrumbs,
-        });
+        // llm_hints can expand content, so enforce the budget again on the final output
+        let available = budget.available();
+        let mut keep = vec![true; costs.len()];
+        if token_estimate > available {
+            keep = fit_to_budget(&costs, available);
+            tracing::warn!("⚠️  Formatted context over budget ({} > {} tokens, trigger={}, overhead={}), dropping {} low-priority breadcrumbs",
+                token_estimate, available, budget.trigger, budget.overhead, keep.iter().filter(|k| !**k).count());
+            token_estimate = costs.iter().zip(&keep).filter(|(_, k)| **k).map(|((_, cost), _)| cost).sum();
+            formatted_breadcrumbs = formatted_breadcrumbs.into_iter()
+                .zip(&keep)
+                .filter(|(_, k)| **k)
+                .map(|(bc, _)| bc)
+                .collect();
+            truncated = true;
+        }
         
-        // Check for existing context breadcrumb
-        let consumer_tag = format!("consumer:{}", consumer_id);
-        let existing = self.rcrt_client.search_breadcrumbs(
-            "agent.context.v1",
-            Some(vec![session_tag.to_string(), consumer_tag.clone()]),
-        ).await?;
+        // `breadcrumbs` for structured consumers, `formatted_context` to paste into a prompt
+        let formatted_context = self.formatters.get(consumer_id, agent_def).render(&formatted_breadcrumbs);
         
-        if let Some(existing_bc) = existing.first() {
-            // Update existing
-            self.rcrt_client.update_breadcrumb(
-                existing_bc.id,
-                existing_bc.version,
-                context_payload,
-            ).await?;
-        } else {
-            // Create new
-            self.rcrt_client.create_breadcrumb(
-                "agent.context.v1",
-                &format!("Context for {}", consumer_id),
-                vec![
-                    "agent:context".to_string(),
-                    consumer_tag,
-                    session_tag.to_string(),
-                ],
-                context_payload,
-            ).await?;
+        // Provenance: every included breadcrumb's selection and final token cost, plus
+        // whatever the assembler or this second budget pass cut
+        let mut provenance_payload = serde_json::Map::new();
+        if let Some(provenance) = &context.provenance {
+            let entry = |i: usize| {
+                let bc = included[i];
+                ProvenanceEntry {
+                    id: bc.id,
+                    schema_name: bc.schema_name.clone(),
+                    selection: provenance.selections.get(&bc.id).cloned().unwrap_or_default(),
+                    tokens: costs[i].1,
+                    section: schema_section(&bc.schema_name),
+                }
+            };
+            let kept = (0..included.len()).filter(|&i| keep[i]).map(entry).collect();
+            let mut dropped = provenance.dropped.clone();
+            dropped.extend((0..included.len()).filter(|&i| !keep[i]).map(entry));
+            provenance_payload = provenance_fields(kept, dropped);
+            if let Some(path) = provenance.semantic_path {
+                provenance_payload.insert("provenance_semantic_path".into(), serde_json::json!(path));
+            }
         }
         
-        tracing::info!("✅ Published context with {} breadcrumbs (~{} tokens)", 
-            formatted_breadcrumbs.len(), token_estimate);
-        
+        Ok(RenderedContext {
+            breadcrumbs: formatted_breadcrumbs,
+            formatted_context,
+            token_estimate,
+            truncated,
+            provenance: provenance_payload,
+        })
+    }
+    
+    /// Upsert the context breadcrumb through the API, retrying with backoff
+    async fn write_via_api(&self, consumer_id: &str, session_tag: &str, context_payload: &serde_json::Value) -> Result<()> {
+        let mut attempt = 0;
+        loop {
+            match self.upsert_via_api(consumer_id, session_tag, context_payload.clone()).await {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt < self.publish_retries => {
+                    metrics::publish_failures().with_label_values(&["retried"]).inc();
+                    attempt += 1;
+                    let backoff = Duration::from_millis(250 * 2u64.pow(attempt - 1));
+                    tracing::warn!("⚠️  Context publish failed ({}), retry {}/{} in {:?}", e, attempt, self.publish_retries, backoff);
+                    tokio::time::sleep(backoff).await;
+                }
+                Err(e) => {
+                    metrics::publish_failures().with_label_values(&["gave_up"]).inc();
+                    return Err(e);
+                }
+            }
+        }
+    }
+    
+    /// One context breadcrumb per (consumer, session); the server creates or updates it in one transaction
+    async fn upsert_via_api(&self, consumer_id: &str, session_tag: &str, context_payload: serde_json::Value) -> Result<()> {
+        self.rcrt_client.upsert_breadcrumb(
+            CONTEXT_SCHEMA,
+            &format!("Context for {}", consumer_id),
+            context_tags(consumer_id, session_tag),
+            &context_key(consumer_id, session_tag),
+            context_payload,
+        ).await?;
         Ok(())
     }
 }
 
+/// "sha256:<hex>" over `formatted_context` and the set of included breadcrumb ids
+fn content_hash(formatted_context: &str, breadcrumbs: &[serde_json::Value]) -> String {
+    let mut ids: Vec<&str> = breadcrumbs.iter().filter_map(|bc| bc["id"].as_str()).collect();
+    ids.sort_unstable();
+    let mut hasher = Sha256::new();
+    hasher.update(formatted_context.as_bytes());
+    for id in ids {
+        hasher.update(b"\n");
+        hasher.update(id.as_bytes());
+    }
+    format!("sha256:{:x}", hasher.finalize())
+}
+
+fn context_tags(consumer_id: &str, session_tag: &str) -> Vec<String> {
+    vec![
+        "agent:context".to_string(),
+        format!("consumer:{}", consumer_id),
+        session_tag.to_string(),
+    ]
+}
+
+/// The tags that identify a consumer's context breadcrumb for a session
+fn context_key(consumer_id: &str, session_tag: &str) -> Vec<String> {
+    vec![format!("consumer:{}", consumer_id), session_tag.to_string()]
+}
+
+#[cfg(all(test, feature = "db-tests"))]
+mod tests {
+    use super::*;
+    use crate::graph::BreadcrumbNode;
+    use rcrt_core::db::Db;
+    use sqlx::PgPool;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const SESSION: &str = "session:fallback-test";
+
+    /// Stands in for an rcrt-server that refuses every request
+    #[derive(Default)]
+    struct DownApi {
+        upserts: AtomicUsize,
+    }
+
+    impl ContextApi for DownApi {
+        async fn get_breadcrumbs(&self, _ids: &[Uuid], _record_access: bool) -> Result<BulkContextViews> {
+            anyhow::bail!("connection refused")
+        }
+
+        async fn upsert_breadcrumb(&self, _schema_name: &str, _title: &str, _tags: Vec<String>, _key_tags: &[String], _context: serde_json::Value) -> Result<(Uuid, i32)> {
+            self.upserts.fetch_add(1, Ordering::SeqCst);
+            anyhow::bail!("connection refused")
+        }
+    }
+
+    /// Serves bulk_get for every id except `hidden` and records the published context
+    #[derive(Default)]
+    struct PartialApi {
+        hidden: Uuid,
+        published: std::sync::Mutex<Option<serde_json::Value>>,
+        upserts: AtomicUsize,
+    }
+
+    impl ContextApi for PartialApi {
+        async fn get_breadcrumbs(&self, ids: &[Uuid], _record_access: bool) -> Result<BulkContextViews> {
+            let (missing, visible): (Vec<Uuid>, Vec<Uuid>) = ids.iter().partition(|id| **id == self.hidden);
+            let breadcrumbs = visible.into_iter().map(|id| crate::rcrt_client::BreadcrumbContextView {
+                id,
+                title: "visible".to_string(),
+                context: serde_json::json!({ "summary": "hinted" }),
+                tags: vec![SESSION.to_string()],
+                schema_name: Some("user.message.v1".to_string()),
+                version: 1,
+                updated_at: chrono::Utc::now(),
+            }).collect();
+            Ok(BulkContextViews { breadcrumbs, missing })
+        }
+
+        async fn upsert_breadcrumb(&self, _schema_name: &str, _title: &str, _tags: Vec<String>, _key_tags: &[String], context: serde_json::Value) -> Result<(Uuid, i32)> {
+            let version = self.upserts.fetch_add(1, Ordering::SeqCst) + 1;
+            *self.published.lock().unwrap() = Some(context);
+            Ok((Uuid::new_v4(), version as i32))
+        }
+    }
+
+    async fn tenant(pool: &PgPool) -> Result<(Uuid, Uuid)> {
+        let db = Db { pool: pool.clone() };
+        let (owner, agent) = (Uuid::new_v4(), Uuid::new_v4());
+        db.ensure_tenant(owner, "fallback-test").await?;
+        db.upsert_agent(owner, agent, vec!["curator".into()]).await?;
+        Ok((owner, agent))
+    }
+
+    fn node() -> BreadcrumbNode {
+        BreadcrumbNode {
+            id: Uuid::new_v4(),
+            schema_name: "user.message.v1".to_string(),
+            tags: vec![SESSION.to_string()],
+            context: serde_json::json!({ "content": "hello" }),
+            embedding: None,
+            created_at: chrono::Utc::now(),
+            trigger_event_id: None,
+            context_truncated: false,
+        }
+    }
+
+    fn assembled() -> AssembledContext {
+        AssembledContext {
+            breadcrumbs: vec![node()],
+            token_estimate: 10,
+            sources_count: 1,
+            truncated: false,
+            provenance: None,
+        }
+    }
+
+    fn publisher<C: ContextApi>(api: Arc<C>) -> ContextPublisher<C> {
+        ContextPublisher::new(api, Arc::new(TokenCounter::new("/nonexistent/tokenizer.json")), 1)
+    }
+
+    async fn context_row(pool: &PgPool, owner: Uuid) -> Result<Option<(Uuid, i32, serde_json::Value)>> {
+        Ok(sqlx::query_as("SELECT id, version, context FROM breadcrumbs WHERE owner_id = $1 AND schema_name = $2 AND $3 = ANY(tags)")
+            .bind(owner)
+            .bind(CONTEXT_SCHEMA)
+            .bind(SESSION)
+            .fetch_optional(pool)
+            .await?)
+    }
+
+    async fn history_count(pool: &PgPool, id: Uuid) -> Result<i64> {
+        Ok(sqlx::query_scalar("SELECT count(*) FROM breadcrumb_history WHERE breadcrumb_id = $1")
+            .bind(id)
+            .fetch_one(pool)
+            .await?)
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_api_failure_writes_context_to_db(pool: PgPool) -> Result<()> {
+        let (owner, agent) = tenant(&pool).await?;
+        let api = Arc::new(DownApi::default());
+        let publisher = publisher(api.clone()).with_db_fallback(DbFallback::new(pool.clone(), owner, agent));
+
+        publisher.publish_context("chat", SESSION, None, &assembled(), &ContextBudget::new(16000, 0, 0), None, &AssemblyTiming::default()).await?;
+
+        // One attempt plus one retry before falling back
+        assert_eq!(api.upserts.load(Ordering::SeqCst), 2);
+        let (id, version, context) = context_row(&pool, owner).await?.expect("context row");
+        assert_eq!(version, 1);
+        assert_eq!(context["published_via"], "db-fallback");
+        assert_eq!(context["consumer_id"], "chat");
+        // No llm_hints without the API; raw context is used
+        assert_eq!(context["breadcrumbs"][0]["content"]["content"], "hello");
+        assert_eq!(history_count(&pool, id).await?, 1);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_fallback_updates_existing_context(pool: PgPool) -> Result<()> {
+        let (owner, agent) = tenant(&pool).await?;
+        let publisher = publisher(Arc::new(DownApi::default())).with_db_fallback(DbFallback::new(pool.clone(), owner, agent));
+        let budget = ContextBudget::new(16000, 0, 0);
+
+        publisher.publish_context("chat", SESSION, None, &assembled(), &budget, None, &AssemblyTiming::default()).await?;
+        let (first_id, _, _) = context_row(&pool, owner).await?.expect("context row");
+        publisher.publish_context("chat", SESSION, None, &assembled(), &budget, None, &AssemblyTiming::default()).await?;
+
+        let (id, version, _) = context_row(&pool, owner).await?.expect("context row");
+        assert_eq!(id, first_id);
+        assert_eq!(version, 2);
+        assert_eq!(history_count(&pool, id).await?, 2);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_disabled_fallback_surfaces_api_error(pool: PgPool) -> Result<()> {
+        let (owner, _) = tenant(&pool).await?;
+        let publisher = publisher(Arc::new(DownApi::default()));
+
+        let result = publisher.publish_context("chat", SESSION, None, &assembled(), &ContextBudget::new(16000, 0, 0), None, &AssemblyTiming::default()).await;
+
+        assert!(result.is_err());
+        assert!(context_row(&pool, owner).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_breadcrumbs_missing_from_bulk_get_are_left_out() -> Result<()> {
+        let mut context = assembled();
+        context.breadcrumbs.push(node());
+        let hidden = context.breadcrumbs[0].id;
+        let api = Arc::new(PartialApi { hidden, ..Default::default() });
+
+        publisher(api.clone()).publish_context("chat", SESSION, None, &context, &ContextBudget::new(16000, 0, 0), None, &AssemblyTiming::default()).await?;
+
+        let published = api.published.lock().unwrap().clone().expect("context published");
+        let breadcrumbs = published["breadcrumbs"].as_array().unwrap();
+        assert_eq!(breadcrumbs.len(), 1);
+        assert_eq!(breadcrumbs[0]["id"], serde_json::json!(context.breadcrumbs[1].id));
+        assert_eq!(breadcrumbs[0]["content"]["summary"], "hinted");
+        assert_eq!(published["formatted_context"], "=== CONVERSATION ===\n{\n  \"summary\": \"hinted\"\n}");
+        assert!(published.get("provenance").is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_provenance_covers_included_and_dropped() -> Result<()> {
+        use crate::retrieval::{AssemblyProvenance, Selection};
+
+        let mut context = assembled();
+        let kept = context.breadcrumbs[0].id;
+        let cut = ProvenanceEntry {
+            id: Uuid::new_v4(),
+            schema_name: "system.stats.v1".to_string(),
+            selection: Selection::new("recent", None, None),
+            tokens: 40,
+            section: "system",
+        };
+        context.provenance = Some(AssemblyProvenance {
+            selections: [(kept, Selection::new("hybrid_global", Some(0.9), None))].into_iter().collect(),
+            dropped: vec![cut.clone()],
+            semantic_path: Some("keyword"),
+        });
+        let api = Arc::new(PartialApi { hidden: Uuid::new_v4(), ..Default::default() });
+        let created_at = chrono::Utc::now() - chrono::Duration::milliseconds(300);
+        let timing = AssemblyTiming { trigger_created_at: Some(created_at), event_received_at: Some(created_at + chrono::Duration::milliseconds(20)), ..Default::default() };
+
+        publisher(api.clone()).publish_context("chat", SESSION, None, &context, &ContextBudget::new(16000, 0, 0), None, &timing).await?;
+
+        let published = api.published.lock().unwrap().clone().expect("context published");
+        assert_eq!(published["provenance_timing"], serde_json::json!(timing));
+        let provenance = published["provenance"].as_array().unwrap();
+        assert_eq!(provenance.len(), 1);
+        assert_eq!(provenance[0]["id"], serde_json::json!(kept));
+        assert_eq!(provenance[0]["sources"], serde_json::json!(["hybrid_global"]));
+        assert_eq!(provenance[0]["section"], "conversation");
+        assert!(provenance[0]["tokens"].as_u64().unwrap() > 0);
+        assert!(provenance[0].get("content").is_none());
+        assert_eq!(published["provenance_dropped"], serde_json::json!([cut]));
+        assert_eq!(published["provenance_omitted"], 0);
+        assert_eq!(published["provenance_semantic_path"], "keyword");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_context_is_published_once() -> Result<()> {
+        let api = Arc::new(PartialApi { hidden: Uuid::new_v4(), ..Default::default() });
+        let publisher = publisher(api.clone());
+        let budget = ContextBudget::new(16000, 0, 0);
+        let mut context = assembled();
+        let triggers: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+
+        publisher.publish_context("chat", SESSION, Some(triggers[0]), &context, &budget, None, &AssemblyTiming::default()).await?;
+        let first = api.published.lock().unwrap().clone().expect("context published");
+        publisher.publish_context("chat", SESSION, Some(triggers[1]), &context, &budget, None, &AssemblyTiming::default()).await?;
+
+        // Same breadcrumbs, same formatted_context: one version
+        assert_eq!(api.upserts.load(Ordering::SeqCst), 1);
+        assert!(first["content_hash"].as_str().unwrap().starts_with("sha256:"));
+        assert_eq!(first["recent_triggers"], serde_json::json!([triggers[0]]));
+
+        // Another session's context is tracked separately
+        publisher.publish_context("chat", "session:other", None, &context, &budget, None, &AssemblyTiming::default()).await?;
+        assert_eq!(api.upserts.load(Ordering::SeqCst), 2);
+
+        context.breadcrumbs.push(node());
+        publisher.publish_context("chat", SESSION, Some(triggers[2]), &context, &budget, None, &AssemblyTiming::default()).await?;
+
+        assert_eq!(api.upserts.load(Ordering::SeqCst), 3);
+        let second = api.published.lock().unwrap().clone().expect("context published");
+        assert_ne!(second["content_hash"], first["content_hash"]);
+        // The skipped trigger is still recorded, newest first
+        assert_eq!(second["recent_triggers"], serde_json::json!([triggers[2], triggers[1], triggers[0]]));
+        Ok(())
+    }
+}
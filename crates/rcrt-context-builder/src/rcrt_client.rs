@@ -3,26 +3,110 @@
  * 
  * Handles:
  * - JWT authentication
- * - SSE event stream
- * - Breadcrumb CRUD operations
+ * - SSE event stream, with /events/missed catch-up on (re)connect
+ * - Self-registration as an agent
+ * - Breadcrumb CRUD operations, retried on 5xx and connection errors
  */
 
 use anyhow::{Result, Context};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::Duration;
 use tokio::sync::{mpsc, RwLock};
-use tracing::{info, error, warn};
+use tracing::{info, error, warn, Instrument};
 use uuid::Uuid;
 use futures::stream::StreamExt;
 
+use crate::request_id;
+use crate::supervisor::{RestartBudget, RestartPolicy};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BreadcrumbEvent {
     #[serde(rename = "type")]
     pub event_type: String,
     pub breadcrumb_id: Option<Uuid>,
+    pub version: Option<i32>,
     pub schema_name: Option<String>,
     pub tags: Option<Vec<String>>,
     pub context: Option<serde_json::Value>,
+    /// X-Request-Id of the write that raised the event
+    pub request_id: Option<String>,
+    /// The server span that raised the event; handling continues its trace
+    pub traceparent: Option<String>,
+    /// When the breadcrumb was created (payload version 3)
+    pub created_at: Option<DateTime<Utc>>,
+    /// When the server took the request that raised the event (payload version 3)
+    pub received_at: Option<DateTime<Utc>>,
+}
+
+// Response of GET /events/missed
+#[derive(Debug, Deserialize)]
+struct MissedPage {
+    events: Vec<BreadcrumbEvent>,
+    next_cursor: Option<String>,
+    has_more: bool,
+}
+
+/// Event payload version asked of SSE and /events/missed; 3 adds the timestamps latency is measured from
+const PAYLOAD_VERSION: &str = "3";
+/// Oldest `since` the server's /events/missed accepts
+const MISSED_WINDOW_DAYS: i64 = 7;
+/// Re-scan this much before the last sign of life to absorb clock skew with the server
+const CATCHUP_OVERLAP_SECS: i64 = 30;
+/// Ping interval assumed until the server's first ping advertises its own
+const DEFAULT_HEARTBEAT_SECS: u64 = 5;
+/// Ping intervals without any data before a stream counts as dead
+const MISSED_HEARTBEATS: u32 = 3;
+
+/// The server's keepalive: `{"type":"ping","ts","seq","interval_secs"}`; `seq` only grows while
+/// the server process lives. Older servers send neither field
+#[derive(Debug, Default, Deserialize)]
+struct Ping {
+    seq: Option<u64>,
+    interval_secs: Option<u64>,
+}
+
+/// Why a stream was torn down, where the reconnect differs from a plain error
+#[derive(Debug, thiserror::Error)]
+enum StreamError {
+    #[error("no SSE data for {0}s")]
+    Stale(u64),
+    #[error("SSE connection rejected: 401 Unauthorized")]
+    Unauthorized,
+}
+
+/// What one SSE subscription carries across reconnects
+struct StreamState {
+    /// Catch-up point: the latest chunk received, or the caller's `since` before the first
+    last_seen: DateTime<Utc>,
+    /// Ping interval the server last advertised
+    heartbeat: Duration,
+    /// Latest ping `seq`; a lower one means the server restarted
+    last_seq: Option<u64>,
+}
+
+impl StreamState {
+    fn new(since: DateTime<Utc>) -> Self {
+        StreamState { last_seen: since, heartbeat: Duration::from_secs(DEFAULT_HEARTBEAT_SECS), last_seq: None }
+    }
+    
+    /// Adopt the ping's interval and seq; true when seq went backwards (server restart)
+    fn observe_ping(&mut self, ping: &Ping) -> bool {
+        if let Some(secs) = ping.interval_secs.filter(|secs| *secs > 0) {
+            self.heartbeat = Duration::from_secs(secs);
+        }
+        let Some(seq) = ping.seq else { return false };
+        let restarted = self.last_seq.is_some_and(|last| seq <= last);
+        self.last_seq = Some(seq);
+        if restarted {
+            crate::metrics::sse_server_restarts().inc();
+            warn!("🔄 SSE ping seq went back to {}: the server restarted", seq);
+        }
+        restarted
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -49,15 +133,14 @@ pub struct BreadcrumbContextView {
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
 
-// Lightweight breadcrumb from list endpoint
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct BreadcrumbListItem {
-    pub id: Uuid,
-    pub schema_name: String,
-    pub title: Option<String>,
-    pub tags: Vec<String>,
-    pub version: i32,
-    pub updated_at: chrono::DateTime<chrono::Utc>,
+/// Server-side cap on ids per POST /breadcrumbs/bulk_get
+const BULK_GET_MAX_IDS: usize = 100;
+
+// Response of POST /breadcrumbs/bulk_get with view=context
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BulkContextViews {
+    pub breadcrumbs: Vec<BreadcrumbContextView>,
+    pub missing: Vec<Uuid>,
 }
 
 #[derive(Debug, Serialize)]
@@ -72,14 +155,42 @@ struct TokenResponse {
     token: String,
 }
 
+// Response of PUT /breadcrumbs/upsert
 #[derive(Debug, Deserialize)]
-struct CreateResponse {
+struct UpsertResponse {
     id: Uuid,
+    version: i32,
 }
 
+// Response of POST /breadcrumbs, and of a 409 for a repeated Idempotency-Key once the server names the original
 #[derive(Debug, Deserialize)]
-struct OkResponse {
-    ok: bool,
+struct CreateResponse {
+    id: Uuid,
+}
+
+/// A breadcrumb created by `create_breadcrumb`
+#[derive(Debug, Clone, Copy)]
+pub struct Created {
+    pub id: Uuid,
+    /// Attempts after the first; a retry may find the create already committed and get its id back
+    pub retries: u32,
+}
+
+/// Retries of API calls that fail with a 5xx or never get a response (connect errors, timeouts)
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Attempts after the first (API_RETRIES)
+    pub retries: u32,
+    /// Wait before the first retry, doubling per attempt (API_RETRY_BACKOFF_MS)
+    pub backoff: Duration,
+    /// Per-attempt request timeout
+    pub timeout: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy { retries: 2, backoff: Duration::from_millis(250), timeout: Duration::from_secs(30) }
+    }
 }
 
 pub struct RcrtClient {
@@ -88,6 +199,12 @@ pub struct RcrtClient {
     token: Arc<RwLock<String>>,
     owner_id: String,
     agent_id: String,
+    /// Unix millis of each SSE stream's latest chunk (events and heartbeat pings); 0 until it connects.
+    /// A stream leaves the list when it ends
+    sse_seen: Arc<std::sync::Mutex<Vec<Arc<AtomicI64>>>>,
+    retry: RetryPolicy,
+    /// Budget for SSE reconnects that deliver nothing
+    restart: RestartPolicy,
 }
 
 impl RcrtClient {
@@ -102,6 +219,9 @@ impl RcrtClient {
             token: Arc::new(RwLock::new(String::new())),
             owner_id: owner_id.to_string(),
             agent_id: agent_id.to_string(),
+            sse_seen: Arc::default(),
+            retry: RetryPolicy::default(),
+            restart: RestartPolicy::default(),
         };
         
         // Get initial token
@@ -110,18 +230,58 @@ impl RcrtClient {
         Ok(client)
     }
     
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+    
+    pub fn with_restart_policy(mut self, restart: RestartPolicy) -> Self {
+        self.restart = restart;
+        self
+    }
+    
+    /// Send the request `build` makes, again after a backoff while it fails with a 5xx or no
+    /// response at all; returns any other response and how many retries it took
+    async fn send_with_retry(&self, what: &str, build: impl Fn() -> reqwest::RequestBuilder) -> Result<(reqwest::Response, u32)> {
+        let mut retries = 0;
+        loop {
+            let failure = match build().timeout(self.retry.timeout).send().await {
+                Ok(response) if !response.status().is_server_error() => return Ok((response, retries)),
+                Ok(response) if retries >= self.retry.retries => return Ok((response, retries)),
+                Ok(response) => response.status().to_string(),
+                Err(e) if retries < self.retry.retries && (e.is_connect() || e.is_timeout() || e.is_request()) => e.to_string(),
+                Err(e) => return Err(anyhow::Error::new(e).context(format!("{} failed after {} retries", what, retries))),
+            };
+            retries += 1;
+            let backoff = self.retry.backoff * 2u32.saturating_pow(retries - 1);
+            warn!(retries, "⚠️  {} failed ({}), retry {}/{} in {:?}", what, failure, retries, self.retry.retries, backoff);
+            tokio::time::sleep(backoff).await;
+        }
+    }
+    
     async fn refresh_token(&self) -> Result<()> {
+        Self::fetch_token(&self.http_client, &self.base_url, &self.owner_id, &self.agent_id, &self.token).await
+    }
+    
+    /// Mint a token for the owner/agent into `token`; associated so the SSE task can renew it on a 401
+    async fn fetch_token(
+        http_client: &reqwest::Client,
+        base_url: &str,
+        owner_id: &str,
+        agent_id: &str,
+        token: &RwLock<String>,
+    ) -> Result<()> {
         let request = TokenRequest {
-            owner_id: self.owner_id.clone(),
-            agent_id: self.agent_id.clone(),
+            owner_id: owner_id.to_string(),
+            agent_id: agent_id.to_string(),
             roles: vec!["curator".to_string(), "emitter".to_string(), "subscriber".to_string()],
         };
         
-        let url = format!("{}/auth/token", self.base_url);
+        let url = format!("{}/auth/token", base_url);
         info!("🔐 Requesting JWT token from {}", url);
         info!("🔐 Request payload: {:?}", request);
         
-        let response = self.http_client
+        let response = http_client
             .post(&url)
             .header("Content-Type", "application/json")
             .json(&request)
@@ -137,32 +297,79 @@ impl RcrtClient {
         }
         
         let token_response: TokenResponse = response.json().await?;
-        *self.token.write().await = token_response.token;
+        *token.write().await = token_response.token;
         
         info!("🔐 JWT token refreshed successfully");
         Ok(())
     }
     
+    /// Forward SSE events to `tx`, first replaying what /events/missed has since `since`.
+    /// Reconnects catch up from the last chunk received (pings keep it current), so an
+    /// outage leaves no gap. A stream silent for MISSED_HEARTBEATS ping intervals is taken
+    /// for a half-open connection and replaced; a 401 renews the token before reconnecting.
+    /// Reconnects back off under the client's RestartPolicy, which starts over whenever a
+    /// connection delivers; once it is spent, or `tx`'s receiver is gone, the stream ends and
+    /// drops `tx`, so the worker reading it stops and its supervisor takes over
     pub async fn start_sse_stream(
         &self,
         tx: mpsc::UnboundedSender<BreadcrumbEvent>,
+        since: DateTime<Utc>,
     ) -> Result<()> {
         let base_url = self.base_url.clone();
-        let token = self.token.read().await.clone();
-        
+        let token = self.token.clone();
+        let (owner_id, agent_id) = (self.owner_id.clone(), self.agent_id.clone());
+        let http_client = self.http_client.clone();
+        let seen = Arc::new(AtomicI64::new(0));
+        let streams = self.sse_seen.clone();
+        streams.lock().unwrap().push(seen.clone());
+        let restart = self.restart;
+        
+        // Keep the caller's span (the owner) on reconnect logs
         tokio::spawn(async move {
-            loop {
-                match Self::sse_connection_loop(&base_url, &token, tx.clone()).await {
+            let mut stream_state = StreamState::new(since);
+            let mut budget = RestartBudget::new(restart);
+            while !tx.is_closed() {
+                // Read per connection, so a token renewed by any caller is picked up
+                let current = token.read().await.clone();
+                let delivered_before = stream_state.last_seen;
+                let result = Self::sse_connection_loop(&base_url, &current, &http_client, &mut stream_state, &seen, tx.clone()).await;
+                if tx.is_closed() {
+                    break;
+                }
+                if stream_state.last_seen != delivered_before {
+                    budget.reset();
+                }
+                match result {
                     Ok(_) => {
+                        crate::metrics::sse_reconnects().with_label_values(&["ended"]).inc();
                         warn!("SSE stream ended, reconnecting...");
                     }
-                    Err(e) => {
-                        error!("SSE connection error: {}, reconnecting in 5s...", e);
-                        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-                    }
+                    Err(e) => match e.downcast_ref::<StreamError>() {
+                        Some(StreamError::Stale(_)) => {
+                            crate::metrics::sse_reconnects().with_label_values(&["stale"]).inc();
+                            warn!("💔 {}, reconnecting...", e);
+                        }
+                        Some(StreamError::Unauthorized) => {
+                            crate::metrics::sse_reconnects().with_label_values(&["unauthorized"]).inc();
+                            warn!("🔐 SSE stream rejected the token, renewing it...");
+                            if let Err(e) = Self::fetch_token(&http_client, &base_url, &owner_id, &agent_id, &token).await {
+                                error!("Token renewal failed: {}", e);
+                            }
+                        }
+                        None => {
+                            crate::metrics::sse_reconnects().with_label_values(&["error"]).inc();
+                            error!("SSE connection error: {}", e);
+                        }
+                    },
                 }
+                let Some(delay) = budget.spend() else {
+                    error!("❌ SSE stream failed {} times within {}s without delivering, giving up", budget.spent(), restart.window.as_secs());
+                    break;
+                };
+                tokio::time::sleep(delay).await;
             }
-        });
+            streams.lock().unwrap().retain(|s| !Arc::ptr_eq(s, &seen));
+        }.instrument(tracing::Span::current()));
         
         Ok(())
     }
@@ -170,28 +377,52 @@ impl RcrtClient {
     async fn sse_connection_loop(
         base_url: &str,
         token: &str,
+        http_client: &reqwest::Client,
+        state: &mut StreamState,
+        seen: &AtomicI64,
         tx: mpsc::UnboundedSender<BreadcrumbEvent>,
     ) -> Result<()> {
         let url = format!("{}/events/stream", base_url);
         let response = reqwest::Client::new()
             .get(&url)
+            .query(&[("payload_version", PAYLOAD_VERSION)])
             .header("Authorization", format!("Bearer {}", token))
             .header("Accept", "text/event-stream")
             .header("Cache-Control", "no-cache")
             .send()
             .await?;
         
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(StreamError::Unauthorized.into());
+        }
         if !response.status().is_success() {
             anyhow::bail!("SSE connection failed: {}", response.status());
         }
         
         info!("✅ SSE stream connected");
+        seen.store(Utc::now().timestamp_millis(), Ordering::Relaxed);
+        
+        // Subscribed already, so catching up now leaves no gap; live events the
+        // catch-up already delivered at the same or a newer version are dropped
+        let since = state.last_seen - chrono::Duration::seconds(CATCHUP_OVERLAP_SECS);
+        let mut caught_up = Self::catch_up(base_url, token, http_client, since, &tx).await?;
+        // This connection's catch-up covers a restart noticed on its first ping
+        let mut pinged = false;
         
         let mut stream = response.bytes_stream();
         let mut buffer = String::new();
         
-        while let Some(chunk_result) = stream.next().await {
+        loop {
+            let deadline = state.heartbeat * MISSED_HEARTBEATS;
+            let chunk_result = match tokio::time::timeout(deadline, stream.next()).await {
+                Ok(Some(chunk_result)) => chunk_result,
+                Ok(None) => break,
+                Err(_) => return Err(StreamError::Stale(deadline.as_secs()).into()),
+            };
             let chunk = chunk_result?;
+            let previously_seen = state.last_seen;
+            state.last_seen = Utc::now();
+            seen.store(state.last_seen.timestamp_millis(), Ordering::Relaxed);
             let text = String::from_utf8_lossy(&chunk);
             buffer.push_str(&text);
             
@@ -204,7 +435,18 @@ impl RcrtClient {
                     let data = &line[6..];
                     
                     if let Ok(event) = serde_json::from_str::<BreadcrumbEvent>(data) {
-                        if event.event_type != "ping" {
+                        if event.event_type == "ping" {
+                            let ping: Ping = serde_json::from_str(data).unwrap_or_default();
+                            if state.observe_ping(&ping) && pinged {
+                                // The stream survived the restart (e.g. behind a reconnecting proxy), so nothing else catches up
+                                let since = previously_seen - chrono::Duration::seconds(CATCHUP_OVERLAP_SECS);
+                                caught_up.extend(Self::catch_up(base_url, token, http_client, since, &tx).await?);
+                            }
+                            pinged = true;
+                        } else if event.event_type == "overflow" {
+                            // Server dropped us for falling behind; the stream ends and we reconnect
+                            warn!("⚠️ SSE queue overflowed on server, events were dropped");
+                        } else if !Self::already_caught_up(&caught_up, &event) {
                             if tx.send(event).is_err() {
                                 warn!("Event receiver dropped");
                                 return Ok(());
@@ -218,169 +460,422 @@ impl RcrtClient {
         Ok(())
     }
     
-    pub async fn search_breadcrumbs(
-        &self,
-        schema_name: &str,
-        tags: Option<Vec<String>>,
-    ) -> Result<Vec<BreadcrumbListItem>> {
-        let token = self.token.read().await.clone();
-        
-        // API only supports single tag parameter, so we'll use the first one
-        // and filter the rest client-side
-        let url = if let Some(tag_list) = &tags {
-            if let Some(first_tag) = tag_list.first() {
-                format!("{}/breadcrumbs?tag={}", self.base_url, first_tag)
-            } else {
-                format!("{}/breadcrumbs", self.base_url)
+    /// Page through GET /events/missed into `tx`; returns the version sent per breadcrumb
+    async fn catch_up(
+        base_url: &str,
+        token: &str,
+        http_client: &reqwest::Client,
+        since: DateTime<Utc>,
+        tx: &mpsc::UnboundedSender<BreadcrumbEvent>,
+    ) -> Result<HashMap<Uuid, i32>> {
+        // Older than the server's window is a 400; replay what's still there
+        let since = since.max(Utc::now() - chrono::Duration::days(MISSED_WINDOW_DAYS) + chrono::Duration::minutes(1));
+        let url = format!("{}/events/missed", base_url);
+        let mut query = vec![("since", since.to_rfc3339()), ("payload_version", PAYLOAD_VERSION.to_string())];
+        let mut sent = HashMap::new();
+        
+        loop {
+            let response = http_client
+                .get(&url)
+                .header("Authorization", format!("Bearer {}", token))
+                .query(&query)
+                .send()
+                .await?;
+            
+            if !response.status().is_success() {
+                let status = response.status();
+                let body = response.text().await.unwrap_or_default();
+                anyhow::bail!("Missed events request failed: {} - {}", status, body);
             }
-        } else {
-            format!("{}/breadcrumbs", self.base_url)
-        };
-        
-        let response = self.http_client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", token))
-            .send()
-            .await?;
-        
-        let status = response.status();
-        
-        // Handle 404 as empty result (no breadcrumbs found)
-        if status == reqwest::StatusCode::NOT_FOUND {
-            return Ok(vec![]);
-        }
-        
-        if !status.is_success() {
-            let body = response.text().await.unwrap_or_else(|_| "Unable to read response".to_string());
-            error!("❌ Search failed: {} - {}", status, body);
-            anyhow::bail!("Search failed: {} - {}", status, body);
-        }
-        
-        let all_breadcrumbs: Vec<BreadcrumbListItem> = response.json().await?;
-        
-        // Filter by schema_name and remaining tags client-side
-        let filtered: Vec<BreadcrumbListItem> = all_breadcrumbs
-            .into_iter()
-            .filter(|b| {
-                // Must match schema
-                if b.schema_name != schema_name {
-                    return false;
+            
+            let page: MissedPage = response.json().await
+                .context("Failed to deserialize /events/missed response")?;
+            for event in page.events {
+                if let (Some(id), Some(version)) = (event.breadcrumb_id, event.version) {
+                    sent.insert(id, version);
                 }
-                
-                // If tags were specified, breadcrumb must have ALL of them
-                if let Some(required_tags) = &tags {
-                    for req_tag in required_tags {
-                        if !b.tags.contains(req_tag) {
-                            return false;
-                        }
-                    }
+                if tx.send(event).is_err() {
+                    warn!("Event receiver dropped");
+                    return Ok(sent);
                 }
-                
-                true
-            })
-            .collect();
+            }
+            match page.next_cursor {
+                Some(cursor) if page.has_more => query = vec![("cursor", cursor), ("payload_version", PAYLOAD_VERSION.to_string())],
+                _ => break,
+            }
+        }
         
-        Ok(filtered)
+        if !sent.is_empty() {
+            info!("⏪ Caught up on {} missed events since {}", sent.len(), since);
+        }
+        Ok(sent)
+    }
+    
+    /// Latest sign of life from the stalest SSE stream; None before every stream has connected
+    pub fn sse_last_seen(&self) -> Option<DateTime<Utc>> {
+        let streams = self.sse_seen.lock().unwrap();
+        let oldest = streams.iter().map(|seen| seen.load(Ordering::Relaxed)).min()?;
+        if oldest == 0 {
+            return None;
+        }
+        DateTime::from_timestamp_millis(oldest)
     }
     
-    /// Get breadcrumb with llm_hints applied (returns BreadcrumbContextView)
-    pub async fn get_breadcrumb(&self, id: Uuid) -> Result<BreadcrumbContextView> {
+    /// POST /agents/:id for the agent this client authenticates as, so it is listed with its roles
+    pub async fn register_agent(&self, roles: &[&str]) -> Result<()> {
+        let agent_id = Uuid::parse_str(&self.agent_id)
+            .with_context(|| format!("agent id {} is not a UUID", self.agent_id))?;
         let token = self.token.read().await.clone();
-        let url = format!("{}/breadcrumbs/{}", self.base_url, id);
+        let url = format!("{}/agents/{}", self.base_url, agent_id);
         
-        let response = self.http_client
-            .get(&url)
+        let (response, _) = self.send_with_retry("Agent registration", || self.http_client
+            .post(&url)
             .header("Authorization", format!("Bearer {}", token))
-            .send()
-            .await?;
+            .json(&serde_json::json!({ "roles": roles }))
+        ).await?;
         
         if !response.status().is_success() {
             let status = response.status();
             let body = response.text().await.unwrap_or_default();
-            anyhow::bail!("Get breadcrumb failed: {} - {}", status, body);
+            anyhow::bail!("Agent registration failed: {} - {}", status, body);
         }
-        
-        let breadcrumb = response.json::<BreadcrumbContextView>().await
-            .context("Failed to deserialize BreadcrumbContextView")?;
-        Ok(breadcrumb)
+        Ok(())
     }
     
-    pub async fn create_breadcrumb(
+    fn already_caught_up(caught_up: &HashMap<Uuid, i32>, event: &BreadcrumbEvent) -> bool {
+        match (event.breadcrumb_id, event.version) {
+            (Some(id), Some(version)) => caught_up.get(&id).is_some_and(|v| *v >= version),
+            _ => false,
+        }
+    }
+    
+    /// Get breadcrumbs with llm_hints applied, in the order requested; ids the server
+    /// doesn't return (deleted, or not visible to this agent) come back in `missing`.
+    /// With `record_access` the server counts them as a context assembly in its access
+    /// analytics; dry runs pass false and leave no trace
+    pub async fn get_breadcrumbs(&self, ids: &[Uuid], record_access: bool) -> Result<BulkContextViews> {
+        let token = self.token.read().await.clone();
+        let url = format!("{}/breadcrumbs/bulk_get", self.base_url);
+        let access = if record_access { "context_assembly" } else { "none" };
+        let mut result = BulkContextViews::default();
+        
+        for chunk in ids.chunks(BULK_GET_MAX_IDS) {
+            let (response, _) = self.send_with_retry("Bulk get breadcrumbs", || self.http_client
+                .post(&url)
+                .header("Authorization", format!("Bearer {}", token))
+                .headers(request_id::headers())
+                .json(&serde_json::json!({ "ids": chunk, "view": "context", "access": access }))
+            ).await?;
+            
+            if !response.status().is_success() {
+                let status = response.status();
+                let body = response.text().await.unwrap_or_default();
+                anyhow::bail!("Bulk get breadcrumbs failed: {} - {}", status, body);
+            }
+            
+            let page = response.json::<BulkContextViews>().await
+                .context("Failed to deserialize bulk_get response")?;
+            result.breadcrumbs.extend(page.breadcrumbs);
+            result.missing.extend(page.missing);
+        }
+        Ok(result)
+    }
+    
+    /// Create or update the one `schema_name` breadcrumb carrying all of `key_tags`, atomically
+    /// on the server; returns its id and new version
+    pub async fn upsert_breadcrumb(
         &self,
         schema_name: &str,
         title: &str,
         tags: Vec<String>,
+        key_tags: &[String],
         context: serde_json::Value,
-    ) -> Result<Uuid> {
+    ) -> Result<(Uuid, i32)> {
         let token = self.token.read().await.clone();
-        let url = format!("{}/breadcrumbs", self.base_url);
+        let url = format!("{}/breadcrumbs/upsert", self.base_url);
         
         let payload = serde_json::json!({
-            "schema_name": schema_name,
             "title": title,
             "tags": tags,
             "context": context,
         });
         
-        info!("📤 Creating breadcrumb: POST {}", url);
-        info!("📤 Schema: {}, Title: {}, Tags: {:?}", schema_name, title, tags);
-        
-        let response = self.http_client
-            .post(&url)
+        // Safe to repeat: a retry lands on the breadcrumb the first attempt created
+        let (response, retries) = self.send_with_retry("Upsert breadcrumb", || self.http_client
+            .put(&url)
+            .query(&[("schema", schema_name.to_string()), ("key_tags", key_tags.join(","))])
             .header("Authorization", format!("Bearer {}", token))
-            .header("Content-Type", "application/json")
+            .headers(request_id::headers())
             .json(&payload)
-            .send()
-            .await?;
+        ).await?;
         
         let status = response.status();
         
         if !status.is_success() {
             let body = response.text().await.unwrap_or_else(|_| "Unable to read response".to_string());
-            error!("❌ Create breadcrumb failed: {} - {}", status, body);
-            error!("❌ Payload was: {}", serde_json::to_string_pretty(&payload)?);
-            anyhow::bail!("Create breadcrumb failed: {} - {}", status, body);
+            error!("❌ Upsert breadcrumb failed: {} - {}", status, body);
+            anyhow::bail!("Upsert breadcrumb failed: {} - {}", status, body);
         }
         
-        let create_response: CreateResponse = response.json().await?;
-        info!("✅ Breadcrumb created successfully: {}", create_response.id);
-        Ok(create_response.id)
+        let upserted: UpsertResponse = response.json().await?;
+        info!(retries, "✅ Breadcrumb {} upserted (version {})", upserted.id, upserted.version);
+        Ok((upserted.id, upserted.version))
     }
     
-    pub async fn update_breadcrumb(
+    /// POST /breadcrumbs with one Idempotency-Key for every attempt, so a retry after a commit
+    /// whose response was lost doesn't create a second breadcrumb
+    pub async fn create_breadcrumb(
         &self,
-        id: Uuid,
-        version: i32,
+        schema_name: &str,
+        title: &str,
+        tags: Vec<String>,
         context: serde_json::Value,
-    ) -> Result<()> {
+    ) -> Result<Created> {
         let token = self.token.read().await.clone();
-        let url = format!("{}/breadcrumbs/{}", self.base_url, id);
+        let url = format!("{}/breadcrumbs", self.base_url);
+        let idempotency_key = Uuid::new_v4().to_string();
         
         let payload = serde_json::json!({
+            "schema_name": schema_name,
+            "title": title,
+            "tags": tags,
             "context": context,
         });
         
-        info!("🔄 Updating breadcrumb: PATCH {}", url);
-        
-        let response = self.http_client
-            .patch(&url)
+        let (response, retries) = self.send_with_retry("Create breadcrumb", || self.http_client
+            .post(&url)
             .header("Authorization", format!("Bearer {}", token))
-            .header("If-Match", version.to_string())
+            .header("Idempotency-Key", &idempotency_key)
+            .headers(request_id::headers())
             .json(&payload)
-            .send()
-            .await?;
+        ).await?;
         
         let status = response.status();
-        
+        let body = response.text().await.unwrap_or_else(|_| "Unable to read response".to_string());
+        
+        // A retry whose key the server has already seen: an earlier attempt committed
+        if status == reqwest::StatusCode::CONFLICT && retries > 0 {
+            let original: CreateResponse = serde_json::from_str(&body)
+                .with_context(|| format!("Create breadcrumb was committed by an earlier attempt, but the server didn't return its id: {}", body))?;
+            info!(retries, "✅ Breadcrumb {} created by an earlier attempt", original.id);
+            return Ok(Created { id: original.id, retries });
+        }
         if !status.is_success() {
-            let body = response.text().await.unwrap_or_else(|_| "Unable to read response".to_string());
-            error!("❌ Update breadcrumb failed: {} - {}", status, body);
-            anyhow::bail!("Update breadcrumb failed: {} - {}", status, body);
+            error!("❌ Create breadcrumb failed: {} - {}", status, body);
+            anyhow::bail!("Create breadcrumb failed: {} - {}", status, body);
         }
         
-        let _ok_response: OkResponse = response.json().await?;
-        info!("✅ Breadcrumb updated successfully");
-        Ok(())
+        let created: CreateResponse = serde_json::from_str(&body).context("Failed to deserialize create response")?;
+        info!(retries, "✅ Breadcrumb {} created", created.id);
+        Ok(Created { id: created.id, retries })
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, extract::State, response::{IntoResponse, Response}, routing::{get, post}, Json, Router};
+    use std::convert::Infallible;
+    use std::sync::atomic::AtomicUsize;
+
+    /// Counts each endpoint's calls; `stream` answers the n-th /events/stream connection
+    #[derive(Clone)]
+    struct MockServer {
+        tokens: Arc<AtomicUsize>,
+        streams: Arc<AtomicUsize>,
+        missed: Arc<AtomicUsize>,
+        stream: Arc<dyn Fn(usize) -> Response + Send + Sync>,
+    }
+
+    async fn mock(stream: impl Fn(usize) -> Response + Send + Sync + 'static) -> (String, MockServer) {
+        let server = MockServer {
+            tokens: Arc::default(),
+            streams: Arc::default(),
+            missed: Arc::default(),
+            stream: Arc::new(stream),
+        };
+        let app = Router::new()
+            .route("/auth/token", post(|State(s): State<MockServer>| async move {
+                s.tokens.fetch_add(1, Ordering::SeqCst);
+                Json(serde_json::json!({ "token": "t0k" }))
+            }))
+            .route("/events/stream", get(|State(s): State<MockServer>| async move {
+                let n = s.streams.fetch_add(1, Ordering::SeqCst);
+                (s.stream)(n)
+            }))
+            .route("/events/missed", get(|State(s): State<MockServer>| async move {
+                s.missed.fetch_add(1, Ordering::SeqCst);
+                Json(serde_json::json!({ "events": [], "next_cursor": null, "has_more": false }))
+            }))
+            .with_state(server.clone());
+        (serve(app).await, server)
+    }
+
+    async fn serve(app: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        url
+    }
+
+    /// A client against `routes` plus /auth/token, retrying quickly with a 200ms request timeout
+    async fn api_client(routes: Router) -> RcrtClient {
+        let app = routes.route("/auth/token", post(|| async { Json(serde_json::json!({ "token": "t0k" })) }));
+        let url = serve(app).await;
+        RcrtClient::new(&url, "00000000-0000-0000-0000-000000000001", "00000000-0000-0000-0000-0000000000cb").await.unwrap()
+            .with_retry(RetryPolicy { retries: 2, backoff: Duration::from_millis(10), timeout: Duration::from_millis(200) })
+    }
+
+    /// One `data:` line per ping, then silence on a connection that stays open (a half-open TCP
+    /// connection looks the same to the client)
+    fn pings_then_silence(pings: Vec<serde_json::Value>) -> Response {
+        let chunks: Vec<Result<String, Infallible>> = pings.iter().map(|ping| Ok(format!("data: {}\n\n", ping))).collect();
+        let body = Body::from_stream(futures::stream::iter(chunks).chain(futures::stream::pending()));
+        ([("content-type", "text/event-stream")], body).into_response()
+    }
+
+    async fn reaches(counter: &AtomicUsize, n: usize, within: Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + within;
+        while counter.load(Ordering::SeqCst) < n {
+            if tokio::time::Instant::now() > deadline {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        true
+    }
+
+    async fn subscribe(url: &str) -> (RcrtClient, mpsc::UnboundedReceiver<BreadcrumbEvent>) {
+        let client = RcrtClient::new(url, "00000000-0000-0000-0000-000000000001", "00000000-0000-0000-0000-0000000000cb").await.unwrap();
+        let (tx, rx) = mpsc::unbounded_channel();
+        client.start_sse_stream(tx, Utc::now()).await.unwrap();
+        (client, rx)
+    }
+
+    #[tokio::test]
+    async fn test_silent_stream_is_replaced_after_missed_heartbeats() {
+        let (url, server) = mock(|_| pings_then_silence(vec![serde_json::json!({ "type": "ping", "seq": 1, "interval_secs": 1 })])).await;
+        let stale_before = crate::metrics::sse_reconnects().with_label_values(&["stale"]).get();
+        let (_client, _rx) = subscribe(&url).await;
+
+        // 3 missed 1s pings, then a new connection
+        assert!(reaches(&server.streams, 2, Duration::from_secs(6)).await);
+        assert!(crate::metrics::sse_reconnects().with_label_values(&["stale"]).get() > stale_before);
+        // Each connection catches up
+        assert!(reaches(&server.missed, 2, Duration::from_secs(1)).await);
+    }
+
+    #[tokio::test]
+    async fn test_unauthorized_stream_renews_token_and_reconnects() {
+        let (url, server) = mock(|n| match n {
+            0 => axum::http::StatusCode::UNAUTHORIZED.into_response(),
+            _ => pings_then_silence(vec![]),
+        }).await;
+        let (_client, _rx) = subscribe(&url).await;
+
+        // Without the 5s error backoff
+        assert!(reaches(&server.streams, 2, Duration::from_secs(2)).await);
+        assert_eq!(server.tokens.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_stream_that_never_connects_ends_once_its_budget_is_spent() {
+        let (url, server) = mock(|_| axum::http::StatusCode::BAD_GATEWAY.into_response()).await;
+        let client = RcrtClient::new(&url, "00000000-0000-0000-0000-000000000001", "00000000-0000-0000-0000-0000000000cb").await.unwrap()
+            .with_restart_policy(RestartPolicy { max_restarts: 2, window: Duration::from_secs(60), backoff: Duration::from_millis(10) });
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        client.start_sse_stream(tx, Utc::now()).await.unwrap();
+
+        // The first attempt and two reconnects, then the sender is dropped for the supervisor to act on
+        let ended = tokio::time::timeout(Duration::from_secs(2), rx.recv()).await;
+        assert!(matches!(ended, Ok(None)));
+        assert_eq!(server.streams.load(Ordering::SeqCst), 3);
+        assert!(client.sse_seen.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_ping_seq_reset_triggers_catch_up() {
+        let (url, server) = mock(|_| pings_then_silence(vec![
+            serde_json::json!({ "type": "ping", "seq": 41, "interval_secs": 60 }),
+            serde_json::json!({ "type": "ping", "seq": 42, "interval_secs": 60 }),
+            serde_json::json!({ "type": "ping", "seq": 1, "interval_secs": 60 }),
+        ])).await;
+        let (_client, _rx) = subscribe(&url).await;
+
+        // On connect, then again for the restart; the stream itself stays up
+        assert!(reaches(&server.missed, 2, Duration::from_secs(2)).await);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(server.missed.load(Ordering::SeqCst), 2);
+        assert_eq!(server.streams.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_create_retried_after_a_lost_response_returns_the_original() {
+        // Idempotency-Key -> created id; the first create commits but answers too late
+        let keys: Arc<std::sync::Mutex<HashMap<String, Uuid>>> = Arc::default();
+        let routes = Router::new()
+            .route("/breadcrumbs", post(|State(keys): State<Arc<std::sync::Mutex<HashMap<String, Uuid>>>>, headers: axum::http::HeaderMap| async move {
+                let key = headers["Idempotency-Key"].to_str().unwrap().to_string();
+                let (id, first) = {
+                    let mut keys = keys.lock().unwrap();
+                    let first = keys.is_empty();
+                    if let Some(id) = keys.get(&key) {
+                        return (axum::http::StatusCode::CONFLICT, Json(serde_json::json!({ "id": id }))).into_response();
+                    }
+                    let id = Uuid::new_v4();
+                    keys.insert(key, id);
+                    (id, first)
+                };
+                if first {
+                    tokio::time::sleep(Duration::from_millis(500)).await;
+                }
+                Json(serde_json::json!({ "id": id })).into_response()
+            }))
+            .with_state(keys.clone());
+        let client = api_client(routes).await;
+
+        let created = client.create_breadcrumb("knowledge.v1", "Note", vec![], serde_json::json!({ "text": "hi" })).await.unwrap();
+        assert_eq!(created.retries, 1);
+        let keys = keys.lock().unwrap();
+        assert_eq!(keys.len(), 1, "every attempt carries the same key");
+        assert_eq!(keys.values().next(), Some(&created.id));
+    }
+
+    #[tokio::test]
+    async fn test_server_errors_are_retried_until_the_limit() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let routes = Router::new()
+            .route("/breadcrumbs/upsert", axum::routing::put(|State(calls): State<Arc<AtomicUsize>>| async move {
+                match calls.fetch_add(1, Ordering::SeqCst) {
+                    0 => axum::http::StatusCode::SERVICE_UNAVAILABLE.into_response(),
+                    _ => Json(serde_json::json!({ "id": Uuid::nil(), "version": 3 })).into_response(),
+                }
+            }))
+            .route("/breadcrumbs/bulk_get", post(|State(calls): State<Arc<AtomicUsize>>| async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                axum::http::StatusCode::BAD_GATEWAY
+            }))
+            .with_state(calls.clone());
+        let client = api_client(routes).await;
+
+        assert_eq!(client.upsert_breadcrumb("agent.context.v1", "Context", vec![], &[], serde_json::json!({})).await.unwrap(), (Uuid::nil(), 3));
+        assert_eq!(calls.swap(0, Ordering::SeqCst), 2);
+
+        // Once the retries are spent the last 5xx is the result
+        assert!(client.get_breadcrumbs(&[Uuid::new_v4()], true).await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_observe_ping_adopts_interval_and_spots_restarts() {
+        let mut state = StreamState::new(Utc::now());
+        assert!(!state.observe_ping(&Ping::default()));
+        assert_eq!(state.heartbeat, Duration::from_secs(DEFAULT_HEARTBEAT_SECS));
+        assert!(!state.observe_ping(&Ping { seq: Some(7), interval_secs: Some(2) }));
+        assert_eq!(state.heartbeat, Duration::from_secs(2));
+        assert!(!state.observe_ping(&Ping { seq: Some(8), interval_secs: Some(0) }));
+        assert_eq!(state.heartbeat, Duration::from_secs(2));
+        assert!(state.observe_ping(&Ping { seq: Some(1), interval_secs: None }));
+    }
+}
@@ -0,0 +1,205 @@
+/*!
+ * Targeted reprocessing
+ *
+ * `rcrt-context-builder reprocess --tag project:apollo [--schema note.v1] [--what entities] [--owner UUID]`
+ *
+ * Re-runs entity extraction for a slice of existing breadcrumbs, overwriting
+ * their keywords, so extraction tuning doesn't need a wiped database. Walks
+ * matches with keyset pagination on id; Ctrl-C stops after the current batch
+ * and prints the id to pass as `--after` to resume.
+ */
+
+use anyhow::{bail, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::{info, warn, error};
+use uuid::Uuid;
+
+use crate::entity_extractor::{breadcrumb_text, EntityExtractor, EXTRACTED_BY_WORKER};
+use crate::vector_store::VectorStore;
+
+const USAGE: &str = "usage: rcrt-context-builder reprocess [--tag TAG] [--schema SCHEMA] [--what entities] [--batch-size N] [--after UUID] [--owner UUID]";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReprocessArgs {
+    pub tag: Option<String>,
+    pub schema: Option<String>,
+    pub batch_size: i64,
+    /// Resume after this id (printed when a run is cancelled)
+    pub after: Option<Uuid>,
+    /// Owner to reprocess; required when more than one owner is configured
+    pub owner: Option<Uuid>,
+}
+
+impl ReprocessArgs {
+    /// Parse the arguments following `reprocess`
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut parsed = ReprocessArgs { tag: None, schema: None, batch_size: 200, after: None, owner: None };
+        let mut iter = args.iter();
+        while let Some(flag) = iter.next() {
+            let Some(value) = iter.next() else { bail!("{} needs a value\n{}", flag, USAGE) };
+            match flag.as_str() {
+                "--tag" => parsed.tag = Some(value.clone()),
+                "--schema" => parsed.schema = Some(value.clone()),
+                "--batch-size" => parsed.batch_size = value.parse().ok().filter(|n| *n > 0)
+                    .ok_or_else(|| anyhow::anyhow!("--batch-size must be a positive integer"))?,
+                "--after" => parsed.after = Some(value.parse()?),
+                "--owner" => parsed.owner = Some(value.parse()?),
+                "--what" => {
+                    for what in value.split(',').map(str::trim) {
+                        match what {
+                            "entities" => {}
+                            // Graph edges are derived in memory per session and never stored
+                            "edges" => bail!("edges are rebuilt in memory when a session graph loads; there are no stored edges to reprocess"),
+                            other => bail!("unknown --what value '{}' (expected: entities)", other),
+                        }
+                    }
+                }
+                other => bail!("unknown argument '{}'\n{}", other, USAGE),
+            }
+        }
+        if parsed.tag.is_none() && parsed.schema.is_none() {
+            bail!("reprocess needs --tag and/or --schema; use the startup backfill for everything\n{}", USAGE);
+        }
+        Ok(parsed)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ReprocessSummary {
+    pub scanned: usize,
+    pub updated: usize,
+    pub empty: usize,
+    pub failed: usize,
+    pub last_id: Option<Uuid>,
+    pub cancelled: bool,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct ReprocessRow {
+    id: Uuid,
+    title: Option<String>,
+    context: serde_json::Value,
+}
+
+pub async fn run(
+    args: &ReprocessArgs,
+    vector_store: &VectorStore,
+    entity_extractor: &EntityExtractor,
+    cancel: Arc<AtomicBool>,
+) -> Result<ReprocessSummary> {
+    info!("🔄 Reprocessing entities for owner {} (tag: {:?}, schema: {:?}, after: {:?})", vector_store.owner_id(), args.tag, args.schema, args.after);
+    let mut summary = ReprocessSummary { last_id: args.after, ..Default::default() };
+
+    loop {
+        if cancel.load(Ordering::SeqCst) {
+            summary.cancelled = true;
+            break;
+        }
+
+        let rows: Vec<ReprocessRow> = sqlx::query_as(
+            r#"
+            SELECT id, title, context
+            FROM breadcrumbs
+            WHERE ($1::text IS NULL OR $1 = ANY(tags))
+            AND ($2::text IS NULL OR schema_name = $2)
+            AND ($3::uuid IS NULL OR id > $3)
+            AND owner_id = $5
+            ORDER BY id
+            LIMIT $4
+            "#
+        )
+        .bind(&args.tag)
+        .bind(&args.schema)
+        .bind(summary.last_id)
+        .bind(args.batch_size)
+        .bind(vector_store.owner_id())
+        .fetch_all(vector_store.pool())
+        .await?;
+
+        if rows.is_empty() {
+            break;
+        }
+
+        for row in &rows {
+            summary.scanned += 1;
+            // Overwrite unconditionally: empty keywords replace stale ones from older patterns
+            let text = breadcrumb_text(row.title.as_deref(), &row.context);
+            let result = entity_extractor.extract(&text)
+                .map(|entities| (entities.to_entities_json(EXTRACTED_BY_WORKER), entities.keywords));
+            match result {
+                Ok((entities_json, keywords)) => {
+                    match vector_store.update_entities(row.id, &entities_json, &keywords).await {
+                        Ok(()) if keywords.is_empty() => summary.empty += 1,
+                        Ok(()) => summary.updated += 1,
+                        Err(e) => {
+                            error!("❌ Failed to update entities for {}: {}", row.id, e);
+                            summary.failed += 1;
+                        }
+                    }
+                }
+                Err(e) => {
+                    error!("❌ Failed to extract entities for {}: {}", row.id, e);
+                    summary.failed += 1;
+                }
+            }
+            summary.last_id = Some(row.id);
+        }
+
+        info!("📊 Reprocessed {} breadcrumbs ({} updated, {} empty, {} failed)",
+            summary.scanned, summary.updated, summary.empty, summary.failed);
+    }
+
+    if summary.cancelled {
+        warn!("🛑 Reprocess cancelled after {} breadcrumbs; resume with --after {}",
+            summary.scanned, summary.last_id.map(|id| id.to_string()).unwrap_or_else(|| "<none>".to_string()));
+    } else {
+        info!("✅ Reprocess complete: {} scanned, {} updated, {} empty, {} failed",
+            summary.scanned, summary.updated, summary.empty, summary.failed);
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_tag_and_what() {
+        let parsed = ReprocessArgs::parse(&args(&["--tag", "project:apollo", "--what", "entities", "--batch-size", "50"])).unwrap();
+        assert_eq!(parsed.tag.as_deref(), Some("project:apollo"));
+        assert_eq!(parsed.schema, None);
+        assert_eq!(parsed.batch_size, 50);
+    }
+
+    #[test]
+    fn test_parse_requires_a_filter() {
+        assert!(ReprocessArgs::parse(&args(&["--what", "entities"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_rejects_edges_and_unknown_flags() {
+        assert!(ReprocessArgs::parse(&args(&["--tag", "t", "--what", "entities,edges"])).is_err());
+        assert!(ReprocessArgs::parse(&args(&["--tag", "t", "--force", "yes"])).is_err());
+        assert!(ReprocessArgs::parse(&args(&["--tag"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_resume_after() {
+        let id = Uuid::new_v4();
+        let parsed = ReprocessArgs::parse(&args(&["--schema", "note.v1", "--after", &id.to_string()])).unwrap();
+        assert_eq!(parsed.after, Some(id));
+    }
+
+    #[test]
+    fn test_parse_owner() {
+        let owner = Uuid::new_v4();
+        let parsed = ReprocessArgs::parse(&args(&["--tag", "t", "--owner", &owner.to_string()])).unwrap();
+        assert_eq!(parsed.owner, Some(owner));
+        assert!(ReprocessArgs::parse(&args(&["--tag", "t", "--owner", "tenant-a"])).is_err());
+    }
+}
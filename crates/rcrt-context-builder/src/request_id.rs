@@ -0,0 +1,60 @@
+/*!
+ * Request IDs
+ *
+ * rcrt-server stamps the X-Request-Id of the write that raised an event onto
+ * the event. Handling runs inside `scope` with that id, so its log lines carry
+ * it and the RcrtClient calls made on the event's behalf send it back,
+ * correlating a user message across services. Those calls also carry the
+ * `traceparent` of the span making them.
+ */
+
+use std::future::Future;
+use rcrt_core::trace_context::TRACEPARENT;
+use uuid::Uuid;
+
+use crate::telemetry;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// For events raised outside a request (hygiene, outbox replays)
+pub fn generate() -> String {
+    Uuid::new_v4().to_string()
+}
+
+/// Run `f` on behalf of request `id`
+pub async fn scope<F: Future>(id: String, f: F) -> F::Output {
+    REQUEST_ID.scope(id, f).await
+}
+
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// X-Request-Id and traceparent for an outgoing call; empty outside `scope` and any traced span
+pub fn headers() -> reqwest::header::HeaderMap {
+    let mut headers = reqwest::header::HeaderMap::new();
+    if let Some(value) = current().and_then(|id| reqwest::header::HeaderValue::from_str(&id).ok()) {
+        headers.insert(REQUEST_ID_HEADER, value);
+    }
+    if let Some(value) = telemetry::current_traceparent().and_then(|tp| reqwest::header::HeaderValue::from_str(&tp).ok()) {
+        headers.insert(TRACEPARENT, value);
+    }
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_headers_follow_the_scope() {
+        assert!(headers().is_empty());
+        let sent = scope("abc-123".to_string(), async { headers() }).await;
+        assert_eq!(sent.get(REQUEST_ID_HEADER).unwrap(), "abc-123");
+        assert_eq!(current(), None);
+    }
+}
@@ -5,18 +5,59 @@
  */
 
 use crate::graph::{SessionGraph, BreadcrumbNode};
-use crate::vector_store::{VectorStore, BreadcrumbRow};
-use crate::retrieval::PathFinder;
+use crate::vector_store::{VectorStore, BreadcrumbRow, ReadPolicy};
+use crate::retrieval::{PathFinder, schema_priority, schema_section, fit_to_budget};
+use crate::retrieval::{AssemblyProvenance, ProvenanceEntry, Selection};
+use crate::token_counter::TokenCounter;
 use anyhow::Result;
 use pgvector::Vector;
-use std::collections::HashSet;
+use rcrt_core::models::Sensitivity;
+use std::collections::HashMap;
 use std::sync::Arc;
+use tracing::{info, info_span, warn, Instrument};
 use uuid::Uuid;
 
 #[derive(Debug, Clone)]
 pub struct ContextConfig {
     pub consumer_id: String,
     pub sources: Vec<SourceConfig>,
+    /// Tokens available for retrieved breadcrumbs (None = unbounded)
+    pub token_budget: Option<usize>,
+    /// Record why each breadcrumb was selected (agent.def.v1 `context_provenance`)
+    pub provenance: bool,
+    /// What every source may return, from the consumer's agent.def.v1
+    pub read_policy: ReadPolicy,
+    /// How the trigger's semantic source was picked, recorded in provenance
+    pub semantic_path: Option<SemanticPath>,
+    /// Vector and hybrid rows scoring below this are left out (see `min_similarity`)
+    pub min_similarity: f64,
+}
+
+/// From agent.def.v1: `context_max_sensitivity` (`low`, `pii` or `secret`; unset or unknown is `pii`),
+/// which only reaches `secret` alongside `"context_allow_secret": true`, and `"context_include_private": true`
+/// for private breadcrumbs. Without a definition the consumer gets public and team breadcrumbs up to pii
+pub fn read_policy(agent_def: Option<&serde_json::Value>) -> ReadPolicy {
+    let flag = |name: &str| agent_def.and_then(|def| def.get(name)).and_then(|v| v.as_bool()).unwrap_or(false);
+    let allow_secret = flag("context_allow_secret");
+    let max_sensitivity = agent_def
+        .and_then(|def| def.get("context_max_sensitivity"))
+        .and_then(|v| v.as_str())
+        .and_then(Sensitivity::parse)
+        .unwrap_or(if allow_secret { Sensitivity::Secret } else { Sensitivity::Pii });
+    ReadPolicy {
+        max_sensitivity: if allow_secret { max_sensitivity } else { max_sensitivity.min(Sensitivity::Pii) },
+        include_private: flag("context_include_private"),
+    }
+}
+
+/// agent.def.v1 `context_sources.semantic.min_similarity` (0..=1), else the instance's
+/// SEMANTIC_SEED_MIN_SIMILARITY. Applies to the score find_similar and find_similar_hybrid return
+pub fn min_similarity(agent_def: Option<&serde_json::Value>, default: f64) -> f64 {
+    agent_def
+        .and_then(|def| def.pointer("/context_sources/semantic/min_similarity"))
+        .and_then(|v| v.as_f64())
+        .unwrap_or(default)
+        .clamp(0.0, 1.0)
 }
 
 #[derive(Debug, Clone)]
@@ -33,28 +74,86 @@ pub enum SourceMethod {
         query_embedding: Vector, 
         query_keywords: Vec<String>,
     },  // NEW: Hybrid search (vector + entity keywords)
+    KeywordGlobal { query_keywords: Vec<String> },  // Keyword half of hybrid, for triggers without an embedding
     Recent { schema_name: Option<String> },
     Latest { schema_name: String },
     Tagged { tag: String },
     Causal { seed_ids: Vec<Uuid> },
 }
 
+impl SourceMethod {
+    /// Source name recorded in provenance
+    pub fn name(&self) -> &'static str {
+        match self {
+            SourceMethod::Vector { .. } => "vector",
+            SourceMethod::VectorGlobal { .. } => "vector_global",
+            SourceMethod::HybridGlobal { .. } => "hybrid_global",
+            SourceMethod::KeywordGlobal { .. } => "keyword_global",
+            SourceMethod::Recent { .. } => "recent",
+            SourceMethod::Latest { .. } => "latest",
+            SourceMethod::Tagged { .. } => "tagged",
+            SourceMethod::Causal { .. } => "causal",
+        }
+    }
+}
+
+/// Which semantic search a trigger got
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SemanticPath {
+    /// Embedding + entity keywords
+    Hybrid,
+    /// No embedding yet (server without the embed feature, or not backfilled): keywords only
+    Keyword,
+    /// Neither an embedding nor keywords; session sources only
+    None,
+}
+
+impl SemanticPath {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SemanticPath::Hybrid => "hybrid",
+            SemanticPath::Keyword => "keyword",
+            SemanticPath::None => "none",
+        }
+    }
+}
+
+/// The global semantic source for a trigger: hybrid with an embedding, else keyword-only
+pub fn semantic_source(embedding: Option<Vector>, keywords: Vec<String>, limit: usize) -> (Option<SourceConfig>, SemanticPath) {
+    match embedding {
+        Some(query_embedding) => (
+            Some(SourceConfig { method: SourceMethod::HybridGlobal { query_embedding, query_keywords: keywords }, limit }),
+            SemanticPath::Hybrid,
+        ),
+        None if !keywords.is_empty() => (
+            Some(SourceConfig { method: SourceMethod::KeywordGlobal { query_keywords: keywords }, limit }),
+            SemanticPath::Keyword,
+        ),
+        None => (None, SemanticPath::None),
+    }
+}
+
 pub struct AssembledContext {
     pub breadcrumbs: Vec<BreadcrumbNode>,
     pub token_estimate: usize,
     pub sources_count: usize,
+    pub truncated: bool,
+    /// Set when ContextConfig::provenance is on
+    pub provenance: Option<AssemblyProvenance>,
 }
 
 pub struct ContextAssembler {
     vector_store: Arc<VectorStore>,
     path_finder: PathFinder,
+    token_counter: Arc<TokenCounter>,
 }
 
 impl ContextAssembler {
-    pub fn new(vector_store: Arc<VectorStore>) -> Self {
+    pub fn new(vector_store: Arc<VectorStore>, token_counter: Arc<TokenCounter>) -> Self {
         ContextAssembler {
             vector_store,
             path_finder: PathFinder::new(5, 50), // max_depth=5, max_results=50
+            token_counter,
         }
     }
     
@@ -64,52 +163,114 @@ impl ContextAssembler {
         session_id: Option<&str>,
         graph: Option<&SessionGraph>,
     ) -> Result<AssembledContext> {
-        let mut all_ids = HashSet::new();
+        let mut selections: HashMap<Uuid, Selection> = HashMap::new();
         let mut all_breadcrumbs = Vec::new();
+        let mut below_threshold = 0;
         
         // Execute each source
         for source in &config.sources {
-            let breadcrumbs = self.execute_source(source, session_id, graph).await?;
+            let (breadcrumbs, below) = self.execute_source(source, session_id, graph, config)
+                .instrument(info_span!("seed_collection", source = source.method.name()))
+                .await?;
+            below_threshold += below;
             
-            for bc in breadcrumbs {
-                if !all_ids.contains(&bc.id) {
-                    all_ids.insert(bc.id);
+            for (bc, selection) in breadcrumbs {
+                if let Some(existing) = selections.get_mut(&bc.id) {
+                    existing.merge(selection);
+                } else {
+                    selections.insert(bc.id, selection);
                     all_breadcrumbs.push(bc);
                 }
             }
         }
+        if config.min_similarity > 0.0 {
+            info!("🔎 {} semantic candidates below similarity {} for {}", below_threshold, config.min_similarity, config.consumer_id);
+        }
+        
+        // Declared triggered_by references win over a trigger_event_id read out of the context
+        let ids: Vec<Uuid> = all_breadcrumbs.iter().map(|bc| bc.id).collect();
+        let triggers = self.vector_store.triggered_by(&ids).await?;
+        for bc in &mut all_breadcrumbs {
+            if let Some(trigger) = triggers.get(&bc.id) {
+                bc.trigger_event_id = Some(*trigger);
+            }
+        }
         
         // Sort by created_at (most recent first)
         all_breadcrumbs.sort_by(|a, b| b.created_at.cmp(&a.created_at));
         
-        // Estimate token count (rough: 3 chars per token, accounting for lightweight formatting)
+        // Count tokens per breadcrumb
         // Note: Actual token count will be recalculated in publisher after llm_hints transformations
-        let token_estimate = all_breadcrumbs.iter()
-            .map(|bc| bc.context.to_string().len() / 3)
-            .sum();
+        let costs: Vec<(u8, usize)> = all_breadcrumbs.iter()
+            .map(|bc| (schema_priority(&bc.schema_name), self.token_counter.count_json(&bc.context)))
+            .collect();
+        let mut token_estimate: usize = costs.iter().map(|(_, cost)| cost).sum();
+        let mut truncated = false;
+        let mut dropped = Vec::new();
+        
+        // Trim lowest priority breadcrumbs until we fit the budget
+        if let Some(budget) = config.token_budget {
+            if token_estimate > budget {
+                let keep = fit_to_budget(&costs, budget);
+                let (kept, cut): (Vec<_>, Vec<_>) = all_breadcrumbs.into_iter()
+                    .enumerate()
+                    .partition(|(i, _)| keep[*i]);
+                warn!("⚠️  Context over budget ({} > {} tokens), dropping {} low-priority breadcrumbs",
+                    token_estimate, budget, cut.len());
+                token_estimate = kept.iter().map(|(i, _)| costs[*i].1).sum();
+                if config.provenance {
+                    dropped = cut.into_iter()
+                        .map(|(i, bc)| ProvenanceEntry {
+                            selection: selections.remove(&bc.id).unwrap_or_default(),
+                            section: schema_section(&bc.schema_name),
+                            tokens: costs[i].1,
+                            id: bc.id,
+                            schema_name: bc.schema_name,
+                        })
+                        .collect();
+                }
+                all_breadcrumbs = kept.into_iter().map(|(_, bc)| bc).collect();
+                truncated = true;
+            }
+        }
         
         Ok(AssembledContext {
             breadcrumbs: all_breadcrumbs,
             token_estimate,
             sources_count: config.sources.len(),
+            truncated,
+            provenance: config.provenance.then_some(AssemblyProvenance {
+                selections,
+                dropped,
+                semantic_path: config.semantic_path.map(|path| path.as_str()),
+            }),
         })
     }
     
+    /// The source's breadcrumbs, and how many vector or hybrid rows fell below `min_similarity`
     async fn execute_source(
         &self,
         source: &SourceConfig,
         session_id: Option<&str>,
         graph: Option<&SessionGraph>,
-    ) -> Result<Vec<BreadcrumbNode>> {
+        config: &ContextConfig,
+    ) -> Result<(Vec<(BreadcrumbNode, Selection)>, usize)> {
+        let name = source.method.name();
+        let policy = &config.read_policy;
+        let similar = |rows: Vec<BreadcrumbRow>| {
+            let (rows, below) = similar_enough(rows, config.min_similarity);
+            (selected(name, rows), below)
+        };
         match &source.method {
             SourceMethod::Vector { query_embedding } => {
                 let rows = self.vector_store.find_similar(
                     query_embedding,
                     source.limit,
                     session_id,
+                    policy,
                 ).await?;
                 
-                Ok(rows.into_iter().map(breadcrumb_row_to_node).collect())
+                Ok(similar(rows))
             }
             
             SourceMethod::VectorGlobal { query_embedding } => {
@@ -118,9 +279,10 @@ impl ContextAssembler {
                     query_embedding,
                     source.limit,
                     None,  // ← No session filter!
+                    policy,
                 ).await?;
                 
-                Ok(rows.into_iter().map(breadcrumb_row_to_node).collect())
+                Ok(similar(rows))
             }
             
             SourceMethod::HybridGlobal { query_embedding, query_keywords } => {
@@ -131,9 +293,20 @@ impl ContextAssembler {
                     query_keywords,
                     source.limit,
                     None,  // Global: no session filter
+                    policy,
+                ).await?;
+                
+                Ok(similar(rows))
+            }
+            
+            SourceMethod::KeywordGlobal { query_keywords } => {
+                let rows = self.vector_store.find_by_keywords(
+                    query_keywords,
+                    source.limit,
+                    policy,
                 ).await?;
                 
-                Ok(rows.into_iter().map(breadcrumb_row_to_node).collect())
+                Ok((selected(name, rows), 0))
             }
             
             SourceMethod::Recent { schema_name } => {
@@ -141,19 +314,21 @@ impl ContextAssembler {
                     schema_name.as_deref(),
                     session_id,
                     source.limit,
+                    policy,
                 ).await?;
                 
-                Ok(rows.into_iter().map(breadcrumb_row_to_node).collect())
+                Ok((selected(name, rows), 0))
             }
             
             SourceMethod::Latest { schema_name } => {
                 if let Some(row) = self.vector_store.get_latest(
                     schema_name,
                     session_id,
+                    policy,
                 ).await? {
-                    Ok(vec![breadcrumb_row_to_node(row)])
+                    Ok((selected(name, vec![row]), 0))
                 } else {
-                    Ok(vec![])
+                    Ok((vec![], 0))
                 }
             }
             
@@ -161,38 +336,61 @@ impl ContextAssembler {
                 let rows = self.vector_store.get_by_tag(
                     tag,
                     source.limit,
+                    policy,
                 ).await?;
                 
-                Ok(rows.into_iter().map(breadcrumb_row_to_node).collect())
+                Ok((selected(name, rows), 0))
             }
             
             SourceMethod::Causal { seed_ids } => {
                 if let Some(g) = graph {
-                    let result_ids = self.path_finder.get_causal_chains(g, seed_ids.clone());
+                    let result_ids = info_span!("pathfinding", seeds = seed_ids.len())
+                        .in_scope(|| self.path_finder.get_causal_chains(g, seed_ids.clone(), config.token_budget, |node| self.token_counter.count_json(&node.context)));
                     
                     let mut nodes = Vec::new();
-                    for id in result_ids {
+                    for (id, path_weight) in result_ids {
                         if let Some(node) = g.nodes.get(&id) {
-                            nodes.push(node.clone());
+                            nodes.push((node.clone(), Selection::new(name, None, Some(path_weight))));
                         }
                     }
                     
-                    Ok(nodes)
+                    Ok((nodes, 0))
                 } else {
                     // Fallback to database if no graph
-                    let mut nodes = Vec::new();
+                    let mut rows = Vec::new();
                     for id in seed_ids {
-                        if let Some(row) = self.vector_store.get_by_id(*id).await? {
-                            nodes.push(breadcrumb_row_to_node(row));
+                        if let Some(row) = self.vector_store.get_by_id_within(*id, policy).await? {
+                            rows.push(row);
                         }
                     }
-                    Ok(nodes)
+                    Ok((selected(name, rows), 0))
                 }
             }
         }
     }
 }
 
+/// Rows scoring at least `min_similarity`, and how many didn't; unscored rows are kept
+fn similar_enough(rows: Vec<BreadcrumbRow>, min_similarity: f64) -> (Vec<BreadcrumbRow>, usize) {
+    let total = rows.len();
+    let kept: Vec<BreadcrumbRow> = rows.into_iter()
+        .filter(|row| !matches!(row.score, Some(score) if score < min_similarity))
+        .collect();
+    let below = total - kept.len();
+    (kept, below)
+}
+
+/// Nodes for a source's rows, each with the row's search score
+fn selected(source: &'static str, rows: Vec<BreadcrumbRow>) -> Vec<(BreadcrumbNode, Selection)> {
+    rows.into_iter()
+        .map(|row| {
+            let selection = Selection::new(source, row.score.map(|s| s as f32), None);
+            (breadcrumb_row_to_node(row), selection)
+        })
+        .collect()
+}
+
+// trigger_event_id from the context, for breadcrumbs written without a triggered_by reference
 fn breadcrumb_row_to_node(row: BreadcrumbRow) -> BreadcrumbNode {
     let trigger_event_id = row.context
         .get("trigger_event_id")
@@ -207,6 +405,222 @@ fn breadcrumb_row_to_node(row: BreadcrumbRow) -> BreadcrumbNode {
         embedding: row.embedding,
         created_at: row.created_at,
         trigger_event_id,
+        context_truncated: false,
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn keywords() -> Vec<String> {
+        vec!["rust".to_string(), "tokio".to_string()]
+    }
+
+    #[test]
+    fn test_trigger_without_embedding_gets_keyword_source() {
+        let (source, path) = semantic_source(None, keywords(), 10);
+        assert_eq!(path, SemanticPath::Keyword);
+        let source = source.expect("keyword source");
+        assert_eq!(source.method.name(), "keyword_global");
+        assert!(matches!(source.method, SourceMethod::KeywordGlobal { ref query_keywords } if *query_keywords == keywords()));
+        assert_eq!(source.limit, 10);
+
+        let (source, path) = semantic_source(Some(Vector::from(vec![0.5; 384])), keywords(), 10);
+        assert_eq!((source.unwrap().method.name(), path.as_str()), ("hybrid_global", "hybrid"));
+
+        let (source, path) = semantic_source(None, Vec::new(), 10);
+        assert!(source.is_none());
+        assert_eq!(path.as_str(), "none");
+    }
+
+    #[test]
+    fn test_read_policy_from_agent_def() {
+        let policy = |def: serde_json::Value| read_policy(Some(&def));
+        assert_eq!(read_policy(None), ReadPolicy { max_sensitivity: Sensitivity::Pii, include_private: false });
+        assert_eq!(policy(json!({ "context_max_sensitivity": "low" })).max_sensitivity, Sensitivity::Low);
+        // Secret takes the explicit flag; the ceiling alone stops at pii
+        assert_eq!(policy(json!({ "context_max_sensitivity": "secret" })).max_sensitivity, Sensitivity::Pii);
+        assert_eq!(policy(json!({ "context_allow_secret": true })).max_sensitivity, Sensitivity::Secret);
+        assert_eq!(policy(json!({ "context_max_sensitivity": "low", "context_allow_secret": true })).max_sensitivity, Sensitivity::Low);
+        assert!(policy(json!({ "context_include_private": true })).include_private);
+        assert!(!policy(json!({ "context_include_private": "yes" })).include_private);
+    }
+
+    #[test]
+    fn test_min_similarity_from_agent_def() {
+        let threshold = |def: serde_json::Value| min_similarity(Some(&def), 0.2);
+        assert_eq!(min_similarity(None, 0.2), 0.2);
+        assert_eq!(threshold(json!({ "context_sources": { "semantic": { "min_similarity": 0.45 } } })), 0.45);
+        assert_eq!(threshold(json!({ "context_sources": { "semantic": { "min_similarity": 3 } } })), 1.0);
+        assert_eq!(threshold(json!({ "context_sources": { "semantic": { "min_similarity": "high" } } })), 0.2);
+        assert_eq!(threshold(json!({ "context_sources": { "recent": { "min_similarity": 0.9 } } })), 0.2);
+    }
+
+    #[test]
+    fn test_rows_straddling_the_threshold() {
+        let row = |score: Option<f64>| BreadcrumbRow {
+            id: Uuid::new_v4(),
+            schema_name: "note.v1".into(),
+            title: None,
+            tags: vec![],
+            context: json!({}),
+            embedding: None,
+            entities: None,
+            entity_keywords: None,
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+            score,
+        };
+        let rows = vec![row(Some(0.81)), row(Some(0.8)), row(Some(0.79)), row(Some(0.1)), row(None)];
+        let ids: Vec<Uuid> = rows.iter().map(|r| r.id).collect();
+        let (kept, below) = similar_enough(rows, 0.8);
+        assert_eq!(kept.iter().map(|r| r.id).collect::<Vec<_>>(), vec![ids[0], ids[1], ids[4]]);
+        assert_eq!(below, 2);
+
+        let (kept, below) = similar_enough(vec![row(Some(0.0))], 0.0);
+        assert_eq!((kept.len(), below), (1, 0));
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_assembled_context_leaves_out_what_the_consumer_may_not_read(pool: sqlx::PgPool) -> Result<()> {
+        use rcrt_core::db::Db;
+        use rcrt_core::models::{BreadcrumbCreate, Visibility};
+        use std::collections::HashSet;
+
+        const SESSION: &str = "session:read-policy-test";
+        let db = Db { pool: pool.clone() };
+        let owner = Uuid::new_v4();
+        db.ensure_tenant(owner, "read-policy-test").await?;
+        let crumb = |title: &str, schema: &str, tags: &[&str], visibility: Visibility, sensitivity: Sensitivity| BreadcrumbCreate {
+            title: title.to_string(),
+            description: None,
+            semantic_version: None,
+            context: json!({ "content": title, "excluded_schemas": [{ "schema_name": "system.internal.v1" }] }),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            schema_name: Some(schema.to_string()),
+            llm_hints: None,
+            visibility: Some(visibility),
+            sensitivity: Some(sensitivity),
+            ttl: None,
+            ttl_type: None,
+            ttl_config: None,
+            ttl_source: None,
+            entity_keywords: None,
+            entities: None,
+        };
+        db.create_breadcrumb_for(owner, None, None, crumb("blacklist", "context.blacklist.v1", &[], Visibility::Team, Sensitivity::Low)).await?;
+        let store = Arc::new(VectorStore::new(pool.clone(), owner));
+        store.load_blacklist().await?;
+        let rows = [
+            ("open", Visibility::Team, Sensitivity::Low),
+            ("public pii", Visibility::Public, Sensitivity::Pii),
+            ("private", Visibility::Private, Sensitivity::Low),
+            ("secret", Visibility::Team, Sensitivity::Secret),
+        ];
+        let mut ids = Vec::new();
+        for (title, visibility, sensitivity) in rows {
+            let bc = db.create_breadcrumb_for(owner, None, None, crumb(title, "note.v1", &[SESSION, "kb"], visibility, sensitivity)).await?;
+            db.set_breadcrumb_embedding(owner, None, bc.id, vec![0.5; 384]).await?;
+            store.update_entities(bc.id, &json!({}), &["rust".to_string()]).await?;
+            ids.push(bc.id);
+        }
+
+        let query_embedding = Vector::from(vec![0.5; 384]);
+        let keywords = vec!["rust".to_string()];
+        let source = |method: SourceMethod| SourceConfig { method, limit: 10 };
+        let sources = vec![
+            source(SourceMethod::Vector { query_embedding: query_embedding.clone() }),
+            source(SourceMethod::VectorGlobal { query_embedding: query_embedding.clone() }),
+            source(SourceMethod::HybridGlobal { query_embedding, query_keywords: keywords.clone() }),
+            source(SourceMethod::KeywordGlobal { query_keywords: keywords }),
+            source(SourceMethod::Recent { schema_name: None }),
+            source(SourceMethod::Recent { schema_name: Some("note.v1".into()) }),
+            source(SourceMethod::Latest { schema_name: "note.v1".into() }),
+            source(SourceMethod::Tagged { tag: "kb".into() }),
+            source(SourceMethod::Causal { seed_ids: ids.clone() }),
+        ];
+        let assembler = ContextAssembler::new(store, Arc::new(TokenCounter::new("missing-tokenizer.json")));
+        let assembled = |read_policy: ReadPolicy| {
+            let config = ContextConfig { consumer_id: "reader".into(), sources: sources.clone(), token_budget: None, provenance: false, read_policy, semantic_path: None, min_similarity: 0.0 };
+            let assembler = &assembler;
+            async move {
+                let context = assembler.assemble(&config, Some(SESSION), None).await?;
+                Ok::<_, anyhow::Error>(context.breadcrumbs.into_iter().map(|bc| bc.id).collect::<HashSet<Uuid>>())
+            }
+        };
+
+        // No agent.def.v1: public and team, up to pii, from every source
+        assert_eq!(assembled(read_policy(None)).await?, HashSet::from([ids[0], ids[1]]));
+        assert_eq!(assembled(read_policy(Some(&json!({ "context_max_sensitivity": "low" })))).await?, HashSet::from([ids[0]]));
+        let everything = json!({ "context_allow_secret": true, "context_include_private": true });
+        assert_eq!(assembled(read_policy(Some(&everything))).await?, ids.iter().copied().collect());
+        Ok(())
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_vector_seeds_below_the_agents_threshold_are_left_out(pool: sqlx::PgPool) -> Result<()> {
+        use rcrt_core::db::Db;
+        use rcrt_core::models::BreadcrumbCreate;
+        use std::collections::HashSet;
+
+        let db = Db { pool: pool.clone() };
+        let owner = Uuid::new_v4();
+        db.ensure_tenant(owner, "similarity-test").await?;
+        // Cosine similarity to the query: 1, 0.5 and 0
+        let axis = |x: f32, y: f32| {
+            let mut v = vec![0.0; 384];
+            v[0] = x;
+            v[1] = y;
+            v
+        };
+        let vectors = [axis(1.0, 0.0), axis(1.0, 3f32.sqrt()), axis(0.0, 1.0)];
+        let mut ids = Vec::new();
+        for (i, vector) in vectors.into_iter().enumerate() {
+            let bc = db.create_breadcrumb_for(owner, None, None, BreadcrumbCreate {
+                title: format!("candidate {}", i),
+                description: None,
+                semantic_version: None,
+                context: json!({ "content": i }),
+                tags: vec!["kb".into()],
+                schema_name: Some("note.v1".into()),
+                llm_hints: None,
+                visibility: None,
+                sensitivity: None,
+                ttl: None,
+                ttl_type: None,
+                ttl_config: None,
+                ttl_source: None,
+                entity_keywords: None,
+                entities: None,
+            }).await?;
+            db.set_breadcrumb_embedding(owner, None, bc.id, vector).await?;
+            ids.push(bc.id);
+        }
+
+        let store = Arc::new(VectorStore::new(pool.clone(), owner));
+        let assembler = ContextAssembler::new(store, Arc::new(TokenCounter::new("missing-tokenizer.json")));
+        let sources = vec![SourceConfig { method: SourceMethod::VectorGlobal { query_embedding: Vector::from(axis(1.0, 0.0)) }, limit: 10 }];
+        let assembled = |min_similarity: f64| {
+            let config = ContextConfig {
+                consumer_id: "reader".into(), sources: sources.clone(), token_budget: None, provenance: false,
+                read_policy: read_policy(None), semantic_path: None, min_similarity,
+            };
+            let assembler = &assembler;
+            async move {
+                let context = assembler.assemble(&config, None, None).await?;
+                Ok::<_, anyhow::Error>(context.breadcrumbs.into_iter().map(|bc| bc.id).collect::<HashSet<Uuid>>())
+            }
+        };
+
+        assert_eq!(assembled(0.0).await?, ids.iter().copied().collect());
+        let def = json!({ "context_sources": { "semantic": { "min_similarity": 0.6 } } });
+        assert_eq!(assembled(min_similarity(Some(&def), 0.0)).await?, HashSet::from([ids[0]]));
+        assert_eq!(assembled(min_similarity(None, 0.4)).await?, HashSet::from([ids[0], ids[1]]));
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+/*!
+ * Context token budget
+ *
+ * Splits the model's context window into a fixed overhead (system prompt,
+ * tool instructions), the trigger message, and what remains for retrieval.
+ * The window comes from the agent's llm.config.v1 when it names one. When
+ * assembled context still exceeds the budget, the lowest priority sections
+ * are dropped first.
+ */
+
+/// Token budget for one context assembly
+#[derive(Debug, Clone, Copy)]
+pub struct ContextBudget {
+    /// Total tokens available for the assembled context
+    pub total: usize,
+    /// Fixed overhead reserved for system prompt / formatting
+    pub overhead: usize,
+    /// Tokens consumed by the trigger breadcrumb
+    pub trigger: usize,
+}
+
+impl ContextBudget {
+    pub fn new(total: usize, overhead: usize, trigger: usize) -> Self {
+        ContextBudget { total, overhead, trigger }
+    }
+
+    /// Tokens left for retrieved breadcrumbs after reservations
+    pub fn available(&self) -> usize {
+        self.total.saturating_sub(self.overhead).saturating_sub(self.trigger)
+    }
+}
+
+/// Tokens an llm.config.v1 leaves for the prompt: the model's `context_window` less the
+/// `max_tokens` kept for the reply. None without a window
+pub fn prompt_window(llm_config: &serde_json::Value) -> Option<usize> {
+    let window = llm_config.get("context_window")?.as_u64()? as usize;
+    let reply = llm_config.get("max_tokens").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+    Some(window.saturating_sub(reply))
+}
+
+/// Section of the context a schema's breadcrumbs belong to
+pub fn schema_section(schema_name: &str) -> &'static str {
+    match schema_name {
+        "user.message.v1" | "agent.response.v1" => "conversation",
+        "tool.response.v1" => "tool_results",
+        "tool.catalog.v1" => "tools",
+        "document.v1" | "code.snippet.v1" | "workflow.result.v1" => "knowledge",
+        "tool.request.v1" => "tool_requests",
+        s if s.starts_with("system.") => "system",
+        _ => "other",
+    }
+}
+
+/// Priority of a schema when trimming context (higher is kept longer)
+pub fn schema_priority(schema_name: &str) -> u8 {
+    match schema_section(schema_name) {
+        // The conversation itself is what the model must answer
+        "conversation" => 100,
+        // Tool results the agent asked for
+        "tool_results" => 80,
+        // Tool catalog is needed to call tools at all
+        "tools" => 70,
+        // Retrieved knowledge
+        "knowledge" => 50,
+        "tool_requests" => 40,
+        // System/stats breadcrumbs are the first to go
+        "system" => 0,
+        _ => 30,
+    }
+}
+
+/// Select which items fit into `budget` tokens, dropping lowest priority first.
+///
+/// Ties drop the later item (callers pass items newest-first, so older
+/// items go first). Returns whether to keep each item, in original order.
+pub fn fit_to_budget(costs: &[(u8, usize)], budget: usize) -> Vec<bool> {
+    let mut total: usize = costs.iter().map(|(_, cost)| cost).sum();
+    let mut keep = vec![true; costs.len()];
+
+    let mut drop_order: Vec<usize> = (0..costs.len()).collect();
+    drop_order.sort_by(|&a, &b| costs[a].0.cmp(&costs[b].0).then(b.cmp(&a)));
+
+    for idx in drop_order {
+        if total <= budget {
+            break;
+        }
+        keep[idx] = false;
+        total -= costs[idx].1;
+    }
+
+    keep
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Indices `fit_to_budget` keeps
+    fn kept(costs: &[(u8, usize)], budget: usize) -> Vec<usize> {
+        fit_to_budget(costs, budget).iter().enumerate().filter(|(_, keep)| **keep).map(|(i, _)| i).collect()
+    }
+
+    #[test]
+    fn test_available_reserves_trigger_and_overhead() {
+        let budget = ContextBudget::new(1000, 200, 300);
+        assert_eq!(budget.available(), 500);
+
+        let tiny = ContextBudget::new(100, 200, 300);
+        assert_eq!(tiny.available(), 0);
+    }
+
+    #[test]
+    fn test_prompt_window_leaves_room_for_the_reply() {
+        assert_eq!(prompt_window(&json!({ "model": "m", "context_window": 128000, "max_tokens": 4000 })), Some(124000));
+        assert_eq!(prompt_window(&json!({ "context_window": 8192 })), Some(8192));
+        assert_eq!(prompt_window(&json!({ "context_window": 1000, "max_tokens": 2000 })), Some(0));
+        assert_eq!(prompt_window(&json!({ "model": "m", "max_tokens": 4000 })), None);
+    }
+
+    #[test]
+    fn test_schema_sections_and_priorities() {
+        assert_eq!(schema_section("user.message.v1"), "conversation");
+        assert_eq!(schema_section("system.stats.v1"), "system");
+        assert_eq!(schema_section("note.v1"), "other");
+        assert_eq!(schema_priority("agent.response.v1"), 100);
+        assert_eq!(schema_priority("code.snippet.v1"), 50);
+        assert_eq!(schema_priority("system.stats.v1"), 0);
+        assert_eq!(schema_priority("note.v1"), 30);
+    }
+
+    #[test]
+    fn test_fit_drops_lowest_priority_first() {
+        // (priority, cost)
+        let costs = vec![(100, 50), (0, 50), (70, 50), (30, 50)];
+        assert_eq!(kept(&costs, 200), vec![0, 1, 2, 3]);
+        assert_eq!(kept(&costs, 150), vec![0, 2, 3]);
+        assert_eq!(kept(&costs, 100), vec![0, 2]);
+    }
+
+    #[test]
+    fn test_fit_drops_older_items_on_tie() {
+        let costs = vec![(100, 50), (100, 50), (100, 50)];
+        assert_eq!(kept(&costs, 100), vec![0, 1]);
+    }
+}
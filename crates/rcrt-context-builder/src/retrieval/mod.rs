@@ -6,7 +6,11 @@
 
 mod path_finder;
 mod assembler;
+mod budget;
+mod provenance;
 
 pub use path_finder::PathFinder;
-pub use assembler::{ContextAssembler, AssembledContext, ContextConfig, SourceConfig, SourceMethod};
+pub use assembler::{ContextAssembler, AssembledContext, ContextConfig, SourceConfig, SourceMethod, SemanticPath, min_similarity, read_policy, semantic_source};
+pub use budget::{ContextBudget, prompt_window, schema_priority, schema_section, fit_to_budget};
+pub use provenance::{AssemblyProvenance, ProvenanceEntry, Selection, provenance_enabled, provenance_fields};
 
@@ -4,11 +4,14 @@
  * Constrained shortest paths algorithm for finding relevant breadcrumbs
  */
 
-use crate::graph::{SessionGraph, EdgeType};
-use std::collections::{BinaryHeap, HashSet};
+use crate::graph::{BreadcrumbNode, SessionGraph, EdgeType};
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::cmp::Ordering;
 use uuid::Uuid;
 
+/// Cost of following one causal edge; also the per-hop path weight of causal chains
+const CAUSAL_EDGE_COST: f32 = 0.1;
+
 #[derive(Debug, Clone)]
 struct PathNode {
     id: Uuid,
@@ -93,7 +96,7 @@ impl PathFinder {
                 if !visited.contains(&neighbor_id) {
                     // Calculate new cost (lower is better)
                     let edge_cost = match edge_type {
-                        EdgeType::Causal => 0.1,     // Lowest cost (highest priority)
+                        EdgeType::Causal => CAUSAL_EDGE_COST,     // Lowest cost (highest priority)
                         EdgeType::Temporal => 0.3,
                         EdgeType::TagRelated => 0.5,
                         EdgeType::Semantic => 1.0 - weight, // Use similarity as inverse cost
@@ -111,20 +114,84 @@ impl PathFinder {
         results
     }
     
-    /// Get causal chains for all seed nodes
+    /// Get causal chains for all seed nodes, with each node's path weight from its nearest seed.
+    /// With a `token_budget`, nodes are taken nearest first until their `tokens` would exceed it
     pub fn get_causal_chains(
         &self,
         graph: &SessionGraph,
         seed_nodes: Vec<Uuid>,
-    ) -> Vec<Uuid> {
-        let mut all_nodes = HashSet::new();
+        token_budget: Option<usize>,
+        tokens: impl Fn(&BreadcrumbNode) -> usize,
+    ) -> Vec<(Uuid, f32)> {
+        let mut all_nodes: HashMap<Uuid, f32> = HashMap::new();
         
         for seed_id in seed_nodes {
             let chain = graph.causal_chain(seed_id, self.max_depth);
-            all_nodes.extend(chain);
+            for (depth, id) in chain.into_iter().enumerate() {
+                let weight = depth as f32 * CAUSAL_EDGE_COST;
+                all_nodes.entry(id)
+                    .and_modify(|w| *w = w.min(weight))
+                    .or_insert(weight);
+            }
         }
         
-        all_nodes.into_iter().collect()
+        let mut nodes: Vec<(Uuid, f32)> = all_nodes.into_iter().collect();
+        let Some(budget) = token_budget else {
+            return nodes;
+        };
+        nodes.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal).then(a.0.cmp(&b.0)));
+        let mut spent = 0;
+        nodes.into_iter()
+            .take_while(|(id, _)| {
+                spent += graph.nodes.get(id).map(&tokens).unwrap_or(0);
+                spent <= budget
+            })
+            .collect()
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: Uuid, trigger_event_id: Option<Uuid>) -> BreadcrumbNode {
+        BreadcrumbNode {
+            id,
+            schema_name: "user.message.v1".to_string(),
+            tags: vec![],
+            context: serde_json::json!({}),
+            embedding: None,
+            created_at: chrono::Utc::now(),
+            trigger_event_id,
+            context_truncated: false,
+        }
+    }
+
+    #[test]
+    fn test_causal_chain_weights_from_nearest_seed() {
+        let (a, b, c) = (Uuid::from_u128(1), Uuid::from_u128(2), Uuid::from_u128(3));
+        let mut graph = SessionGraph::new("session:test".to_string());
+        graph.add_node(node(a, None));
+        graph.add_node(node(b, Some(a)));
+        graph.add_node(node(c, Some(b)));
+
+        let mut weights = PathFinder::new(5, 50).get_causal_chains(&graph, vec![c, b], None, |_| 10);
+        weights.sort_by_key(|(id, _)| *id);
+        assert_eq!(weights, vec![(a, CAUSAL_EDGE_COST), (b, 0.0), (c, 0.0)]);
+    }
+
+    #[test]
+    fn test_causal_chains_stop_at_the_token_budget() {
+        let (a, b, c) = (Uuid::from_u128(1), Uuid::from_u128(2), Uuid::from_u128(3));
+        let mut graph = SessionGraph::new("session:test".to_string());
+        graph.add_node(node(a, None));
+        graph.add_node(node(b, Some(a)));
+        graph.add_node(node(c, Some(b)));
+
+        // The seed first, then its causes, until the next one would overflow
+        let finder = PathFinder::new(5, 50);
+        assert_eq!(finder.get_causal_chains(&graph, vec![c], Some(25), |_| 10), vec![(c, 0.0), (b, CAUSAL_EDGE_COST)]);
+        assert_eq!(finder.get_causal_chains(&graph, vec![c], Some(30), |_| 10).len(), 3);
+        assert!(finder.get_causal_chains(&graph, vec![c], Some(5), |_| 10).is_empty());
+    }
+}
@@ -0,0 +1,134 @@
+/*!
+ * Context provenance
+ *
+ * Why each breadcrumb landed in (or was cut from) an assembled context, so a
+ * bad answer can be traced to retrieval or to the LLM. Ids, schema names and
+ * numbers only; breadcrumb content never goes in here.
+ */
+
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Entries kept per list; the rest are only counted in `provenance_omitted`
+pub const MAX_PROVENANCE_ENTRIES: usize = 100;
+
+/// How a breadcrumb was retrieved
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Selection {
+    /// Every source that returned it, in source order
+    pub sources: Vec<&'static str>,
+    /// Vector or hybrid similarity (best across sources, higher is closer)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<f32>,
+    /// PathFinder cost from the nearest seed (lower is closer)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path_weight: Option<f32>,
+}
+
+impl Selection {
+    pub fn new(source: &'static str, score: Option<f32>, path_weight: Option<f32>) -> Self {
+        Selection { sources: vec![source], score, path_weight }
+    }
+
+    /// Fold in another source that returned the same breadcrumb
+    pub fn merge(&mut self, other: Selection) {
+        for source in other.sources {
+            if !self.sources.contains(&source) {
+                self.sources.push(source);
+            }
+        }
+        self.score = match (self.score, other.score) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+        self.path_weight = match (self.path_weight, other.path_weight) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+    }
+}
+
+/// One breadcrumb's line in the published `provenance` / `provenance_dropped` arrays
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProvenanceEntry {
+    pub id: Uuid,
+    pub schema_name: String,
+    #[serde(flatten)]
+    pub selection: Selection,
+    /// Tokens it contributed (or would have, when dropped)
+    pub tokens: usize,
+    pub section: &'static str,
+}
+
+/// What the assembler knows: selections for every breadcrumb it kept, and those it dropped for budget
+#[derive(Debug, Clone, Default)]
+pub struct AssemblyProvenance {
+    pub selections: HashMap<Uuid, Selection>,
+    pub dropped: Vec<ProvenanceEntry>,
+    /// `hybrid`, `keyword` or `none` when assembly had a trigger; see SemanticPath
+    pub semantic_path: Option<&'static str>,
+}
+
+/// agent.def.v1 `context_provenance: false` turns provenance off for that consumer; on by default
+pub fn provenance_enabled(agent_def: Option<&serde_json::Value>) -> bool {
+    agent_def
+        .and_then(|def| def.get("context_provenance"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true)
+}
+
+/// The `provenance`, `provenance_dropped` and `provenance_omitted` payload fields, capped in size
+pub fn provenance_fields(mut included: Vec<ProvenanceEntry>, mut dropped: Vec<ProvenanceEntry>) -> serde_json::Map<String, serde_json::Value> {
+    let omitted = included.len().saturating_sub(MAX_PROVENANCE_ENTRIES)
+        + dropped.len().saturating_sub(MAX_PROVENANCE_ENTRIES);
+    included.truncate(MAX_PROVENANCE_ENTRIES);
+    dropped.truncate(MAX_PROVENANCE_ENTRIES);
+    let mut fields = serde_json::Map::new();
+    fields.insert("provenance".into(), json!(included));
+    fields.insert("provenance_dropped".into(), json!(dropped));
+    fields.insert("provenance_omitted".into(), json!(omitted));
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(selection: Selection) -> ProvenanceEntry {
+        ProvenanceEntry { id: Uuid::nil(), schema_name: "note.v1".into(), selection, tokens: 12, section: "other" }
+    }
+
+    #[test]
+    fn test_merge_keeps_sources_best_score_and_shortest_path() {
+        let mut selection = Selection::new("recent", None, Some(0.3));
+        selection.merge(Selection::new("hybrid_global", Some(0.8), None));
+        selection.merge(Selection::new("hybrid_global", Some(0.5), Some(0.1)));
+        assert_eq!(selection, Selection { sources: vec!["recent", "hybrid_global"], score: Some(0.8), path_weight: Some(0.1) });
+    }
+
+    #[test]
+    fn test_entry_serializes_flat_without_missing_scores() {
+        let value = serde_json::to_value(entry(Selection::new("recent", None, None))).unwrap();
+        assert_eq!(value, json!({
+            "id": Uuid::nil(), "schema_name": "note.v1", "sources": ["recent"], "tokens": 12, "section": "other",
+        }));
+    }
+
+    #[test]
+    fn test_fields_are_capped() {
+        let many = vec![entry(Selection::new("recent", None, None)); MAX_PROVENANCE_ENTRIES + 3];
+        let fields = provenance_fields(many, vec![entry(Selection::new("vector", Some(0.4), None))]);
+        assert_eq!(fields["provenance"].as_array().unwrap().len(), MAX_PROVENANCE_ENTRIES);
+        assert_eq!(fields["provenance_dropped"][0]["score"], json!(0.4f32));
+        assert_eq!(fields["provenance_omitted"], 3);
+    }
+
+    #[test]
+    fn test_enabled_unless_agent_def_opts_out() {
+        assert!(provenance_enabled(None));
+        assert!(provenance_enabled(Some(&json!({ "agent_id": "chat" }))));
+        assert!(!provenance_enabled(Some(&json!({ "context_provenance": false }))));
+    }
+}
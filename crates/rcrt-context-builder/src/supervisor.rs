@@ -0,0 +1,230 @@
+/*!
+ * Worker supervision
+ *
+ * Each owner's entity worker and event handler run under `Supervisor::supervise`: one that
+ * panics, fails or stops is restarted after a backoff that doubles per restart. More than
+ * WORKER_MAX_RESTARTS restarts within WORKER_RESTART_WINDOW_SECS spends the budget, and main
+ * exits so the orchestrator restarts the container. The SSE reconnect loop in RcrtClient keeps
+ * the same kind of budget for connections that fail before delivering anything; spending it
+ * ends the stream, which stops (and so restarts) the worker reading it.
+ *
+ * Restarts are counted in `worker_restarts_total`, and /ready reports a worker restarted
+ * within the window as degraded.
+ */
+
+use anyhow::{anyhow, Result};
+use std::any::Any;
+use std::collections::{BTreeMap, VecDeque};
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{error, Instrument};
+use uuid::Uuid;
+
+use crate::metrics;
+
+/// Longest wait between restarts, however many came before
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy)]
+pub struct RestartPolicy {
+    /// Restarts allowed within `window` (WORKER_MAX_RESTARTS)
+    pub max_restarts: u32,
+    /// WORKER_RESTART_WINDOW_SECS
+    pub window: Duration,
+    /// Wait before the first restart in the window, doubling per restart (WORKER_RESTART_BACKOFF_MS)
+    pub backoff: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        RestartPolicy { max_restarts: 5, window: Duration::from_secs(300), backoff: Duration::from_secs(1) }
+    }
+}
+
+/// Restarts spent within the policy's window
+pub struct RestartBudget {
+    policy: RestartPolicy,
+    recent: VecDeque<Instant>,
+}
+
+impl RestartBudget {
+    pub fn new(policy: RestartPolicy) -> Self {
+        RestartBudget { policy, recent: VecDeque::new() }
+    }
+
+    /// Spend a restart: the wait before it, or None once the window holds `max_restarts` already
+    pub fn spend(&mut self) -> Option<Duration> {
+        let now = Instant::now();
+        while self.recent.front().is_some_and(|at| now.duration_since(*at) > self.policy.window) {
+            self.recent.pop_front();
+        }
+        if self.recent.len() >= self.policy.max_restarts as usize {
+            return None;
+        }
+        self.recent.push_back(now);
+        let doublings = (self.recent.len() - 1).min(16) as u32;
+        Some((self.policy.backoff * 2u32.pow(doublings)).min(MAX_BACKOFF))
+    }
+
+    /// Start over, e.g. once a reconnected stream delivers again
+    pub fn reset(&mut self) {
+        self.recent.clear();
+    }
+
+    pub fn spent(&self) -> usize {
+        self.recent.len()
+    }
+}
+
+/// What /ready shows for a worker restarted within the window
+pub struct Flapping {
+    pub owner_id: Uuid,
+    pub worker: &'static str,
+    pub restarts: usize,
+    pub last_error: String,
+}
+
+struct WorkerRecord {
+    restarts: Vec<Instant>,
+    last_error: String,
+}
+
+/// Restarts workers and remembers recent restarts for /ready
+pub struct Supervisor {
+    policy: RestartPolicy,
+    workers: Mutex<BTreeMap<(Uuid, &'static str), WorkerRecord>>,
+}
+
+impl Supervisor {
+    pub fn new(policy: RestartPolicy) -> Self {
+        Supervisor { policy, workers: Mutex::new(BTreeMap::new()) }
+    }
+
+    pub fn policy(&self) -> RestartPolicy {
+        self.policy
+    }
+
+    /// Run what `start` returns in its own task, again whenever it panics, fails or stops, until the
+    /// restart budget is spent; returns why it gave up
+    pub async fn supervise<F, Fut>(&self, owner_id: Uuid, worker: &'static str, start: F) -> anyhow::Error
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let mut budget = RestartBudget::new(self.policy);
+        loop {
+            // Spawned, so a panic ends only this task and comes back as a JoinError
+            let (reason, message) = match tokio::spawn(start().in_current_span()).await {
+                Ok(Ok(())) => ("stopped", "stopped".to_string()),
+                Ok(Err(e)) => ("failed", format!("{:#}", e)),
+                Err(e) if e.is_panic() => ("panicked", panic_message(e.into_panic())),
+                Err(e) => ("cancelled", e.to_string()),
+            };
+            metrics::worker_restarts().with_label_values(&[worker, reason]).inc();
+            let Some(delay) = budget.spend() else {
+                return anyhow!("{} {} ({}) after {} restarts within {}s", worker, reason, message, budget.spent(), self.policy.window.as_secs());
+            };
+            error!("❌ {} {}: {}; restart {}/{} in {:?}", worker, reason, message, budget.spent(), self.policy.max_restarts, delay);
+            self.record(owner_id, worker, message);
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    fn record(&self, owner_id: Uuid, worker: &'static str, message: String) {
+        let mut workers = self.workers.lock().unwrap();
+        let record = workers.entry((owner_id, worker)).or_insert_with(|| WorkerRecord { restarts: Vec::new(), last_error: String::new() });
+        record.restarts.push(Instant::now());
+        record.last_error = message;
+    }
+
+    /// Workers restarted within the window, by owner and worker
+    pub fn flapping(&self) -> Vec<Flapping> {
+        let now = Instant::now();
+        let mut workers = self.workers.lock().unwrap();
+        workers.iter_mut().filter_map(|((owner_id, worker), record)| {
+            record.restarts.retain(|at| now.duration_since(*at) <= self.policy.window);
+            (!record.restarts.is_empty()).then(|| Flapping {
+                owner_id: *owner_id,
+                worker: *worker,
+                restarts: record.restarts.len(),
+                last_error: record.last_error.clone(),
+            })
+        }).collect()
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    let message = payload.downcast_ref::<&str>().map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "non-string payload".to_string());
+    format!("panic: {}", message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn quick(max_restarts: u32) -> RestartPolicy {
+        RestartPolicy { max_restarts, window: Duration::from_secs(60), backoff: Duration::from_millis(5) }
+    }
+
+    #[test]
+    fn test_budget_doubles_the_backoff_until_spent() {
+        let mut budget = RestartBudget::new(RestartPolicy { max_restarts: 3, window: Duration::from_secs(60), backoff: Duration::from_millis(100) });
+        assert_eq!(budget.spend(), Some(Duration::from_millis(100)));
+        assert_eq!(budget.spend(), Some(Duration::from_millis(200)));
+        assert_eq!(budget.spend(), Some(Duration::from_millis(400)));
+        assert_eq!(budget.spend(), None);
+        budget.reset();
+        assert_eq!(budget.spend(), Some(Duration::from_millis(100)));
+    }
+
+    #[tokio::test]
+    async fn test_panicking_worker_is_restarted_and_reported_as_flapping() {
+        let supervisor = Arc::new(Supervisor::new(quick(5)));
+        let owner_id = Uuid::from_u128(1);
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = runs.clone();
+        let task = tokio::spawn({
+            let supervisor = supervisor.clone();
+            async move {
+                supervisor.supervise(owner_id, "event_handler", move || {
+                    let run = counter.fetch_add(1, Ordering::SeqCst);
+                    async move {
+                        if run < 2 {
+                            panic!("bad regex in run {}", run);
+                        }
+                        std::future::pending::<Result<()>>().await
+                    }
+                }).await
+            }
+        });
+
+        while runs.load(Ordering::SeqCst) < 3 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let flapping = supervisor.flapping();
+        assert_eq!(flapping.len(), 1);
+        assert_eq!((flapping[0].owner_id, flapping[0].worker, flapping[0].restarts), (owner_id, "event_handler", 2));
+        assert_eq!(flapping[0].last_error, "panic: bad regex in run 1");
+        assert!(metrics::worker_restarts().with_label_values(&["event_handler", "panicked"]).get() >= 2);
+        assert!(!task.is_finished());
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_spent_budget_gives_up() {
+        let supervisor = Supervisor::new(quick(2));
+        let runs = AtomicUsize::new(0);
+        let err = supervisor.supervise(Uuid::from_u128(1), "entity_worker", || {
+            runs.fetch_add(1, Ordering::SeqCst);
+            async { Err(anyhow!("database gone")) }
+        }).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        assert_eq!(err.to_string(), "entity_worker failed (database gone) after 2 restarts within 60s");
+    }
+}
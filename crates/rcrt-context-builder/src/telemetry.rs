@@ -0,0 +1,65 @@
+/*!
+ * Telemetry
+ *
+ * Log output (LOG_FORMAT=json for JSON lines) over the shared tracing in
+ * `rcrt_core::telemetry`. Each event is handled in a span continuing the
+ * `traceparent` rcrt-server stamped on it, and RcrtClient calls made on its
+ * behalf send their own span on, so one trace covers a user message from the
+ * write through context publishing.
+ */
+
+use tracing_subscriber::layer::Layer;
+use tracing_subscriber::{fmt as log_fmt, EnvFilter};
+
+pub use rcrt_core::telemetry::current_traceparent;
+
+/// Set up logging and tracing; call once, inside the runtime
+pub fn init(service: &str) {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| "rcrt_context_builder=info".into());
+    let log = log_fmt::layer()
+        .with_target(false)
+        .with_thread_ids(true)
+        .with_line_number(true);
+    // JSON for the log pipeline, with span fields (request_id, owner_id) as keys
+    let log = if std::env::var("LOG_FORMAT").as_deref() == Ok("json") {
+        log.json().with_current_span(true).with_span_list(true).boxed()
+    } else {
+        log.boxed()
+    };
+    rcrt_core::telemetry::init(service, "rcrt_context_builder", log.with_filter(filter));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::request_id;
+    use rcrt_core::telemetry::Capture;
+    use rcrt_core::trace_context::{TraceContext, TRACEPARENT};
+    use tracing::Instrument;
+
+    const REMOTE: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[tokio::test]
+    async fn test_event_handling_joins_the_servers_trace() {
+        let (capture, _guard) = Capture::install("rcrt_context_builder");
+        let remote = TraceContext::parse(REMOTE).unwrap();
+
+        let event = tracing::info_span!("event", traceparent = REMOTE, request_id = "abc-123");
+        let sent = request_id::scope("abc-123".to_string(), async {
+            async {
+                tracing::info_span!("pathfinding").in_scope(|| {});
+                request_id::headers()
+            }.instrument(tracing::info_span!("seed_collection", source = "recent")).await
+        }.instrument(event)).await;
+
+        let event = &capture.named("event")[0];
+        assert_eq!(event.context.trace_id, remote.trace_id);
+        assert_eq!(event.parent_span_id, Some(remote.span_id));
+        let seeds = &capture.named("seed_collection")[0];
+        assert_eq!(seeds.parent_span_id, Some(event.context.span_id));
+        assert_eq!(capture.named("pathfinding")[0].parent_span_id, Some(seeds.context.span_id));
+        // Calls back to the server continue from the span that made them
+        assert_eq!(sent.get(TRACEPARENT).unwrap(), seeds.context.header().as_str());
+        assert_eq!(sent.get(request_id::REQUEST_ID_HEADER).unwrap(), "abc-123");
+    }
+}
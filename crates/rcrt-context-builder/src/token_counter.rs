@@ -0,0 +1,44 @@
+/*!
+ * Token counting for context budgets
+ *
+ * Reuses the embedding tokenizer so budget math matches what the model sees.
+ * Falls back to a chars/3 heuristic when the tokenizer file is unavailable.
+ */
+
+use tokenizers::Tokenizer;
+use tracing::{info, warn};
+
+pub struct TokenCounter {
+    tokenizer: Option<Tokenizer>,
+}
+
+impl TokenCounter {
+    /// Load the tokenizer from `tokenizer_path`; missing files degrade to the heuristic
+    pub fn new(tokenizer_path: &str) -> Self {
+        match Tokenizer::from_file(tokenizer_path) {
+            Ok(tokenizer) => {
+                info!("✅ Token counter using tokenizer at {}", tokenizer_path);
+                TokenCounter { tokenizer: Some(tokenizer) }
+            }
+            Err(e) => {
+                warn!("⚠️  Failed to load tokenizer from {}: {}. Falling back to chars/3 estimate", tokenizer_path, e);
+                TokenCounter { tokenizer: None }
+            }
+        }
+    }
+
+    /// Count tokens in a string
+    pub fn count(&self, text: &str) -> usize {
+        if let Some(tokenizer) = &self.tokenizer {
+            if let Ok(encoding) = tokenizer.encode(text, false) {
+                return encoding.get_ids().len();
+            }
+        }
+        text.len().div_ceil(3)
+    }
+
+    /// Count tokens in the compact JSON serialization of a value
+    pub fn count_json(&self, value: &serde_json::Value) -> usize {
+        self.count(&value.to_string())
+    }
+}
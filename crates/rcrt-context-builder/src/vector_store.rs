@@ -1,16 +1,22 @@
 /*!
  * Vector Store
  * 
- * Direct PostgreSQL/pgvector queries for semantic search
+ * Direct PostgreSQL/pgvector queries for semantic search, scoped to one owner
+ * (the pool doesn't set RLS, so every query filters on owner_id itself). The
+ * retrieval queries also take the consuming agent's ReadPolicy, since what they
+ * return ends up in an LLM prompt
  */
 
 use anyhow::Result;
 use pgvector::Vector;
+use rcrt_core::models::{EmbeddingModelConfig, Sensitivity, EMBEDDING_CONFIG_SCHEMA, TRIGGERED_BY};
 use sqlx::PgPool;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
+use crate::metrics;
 
 #[derive(Debug, sqlx::FromRow)]
 pub struct BreadcrumbRow {
@@ -24,22 +30,80 @@ pub struct BreadcrumbRow {
     pub entity_keywords: Option<Vec<String>>, // NEW: High-confidence keywords
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Similarity to the query; only set by find_similar, find_similar_hybrid and find_by_keywords
+    #[sqlx(default)]
+    pub score: Option<f64>,
+}
+
+/// What the consuming agent may be shown (see `retrieval::read_policy`); applied by every retrieval
+/// query, but not get_by_id, which also loads triggers and definitions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadPolicy {
+    /// Most sensitive breadcrumb returned
+    pub max_sensitivity: Sensitivity,
+    /// Also return visibility=private breadcrumbs; public and team ones are always returned
+    pub include_private: bool,
+}
+
+impl ReadPolicy {
+    /// Every breadcrumb of the owner
+    pub fn unrestricted() -> Self {
+        ReadPolicy { max_sensitivity: Sensitivity::Secret, include_private: true }
+    }
 }
 
 pub struct VectorStore {
     pool: PgPool,
+    owner_id: Uuid,
     blacklist_cache: Arc<RwLock<Vec<String>>>,
+    /// Error from the latest get_agent_def, cleared by the next success; read by /ready
+    agent_def_error: std::sync::Mutex<Option<String>>,
+    /// Title share of the similarity distance; 0 ranks by the content embedding alone
+    title_weight: f32,
+    /// The owner's active embedding model, see load_embedding_model
+    model: Arc<std::sync::RwLock<EmbeddingModelConfig>>,
 }
 
 impl VectorStore {
-    pub fn new(pool: PgPool) -> Self {
+    pub fn new(pool: PgPool, owner_id: Uuid) -> Self {
         VectorStore { 
             pool,
+            owner_id,
             blacklist_cache: Arc::new(RwLock::new(Vec::new())),
+            agent_def_error: std::sync::Mutex::new(None),
+            title_weight: 0.0,
+            model: Arc::new(std::sync::RwLock::new(EmbeddingModelConfig::default())),
+        }
+    }
+    
+    /// Mix the title embedding into find_similar/find_similar_hybrid distances, 0..=1
+    pub fn with_title_weight(mut self, title_weight: f32) -> Self {
+        self.title_weight = title_weight.clamp(0.0, 1.0);
+        self
+    }
+
+    /// The same owner's store over another pool (the dry run's read-only one); shares the loaded
+    /// blacklist and embedding model, so reloads on this store show up in both
+    pub fn on_pool(&self, pool: PgPool) -> Self {
+        VectorStore {
+            pool,
+            owner_id: self.owner_id,
+            blacklist_cache: self.blacklist_cache.clone(),
+            agent_def_error: std::sync::Mutex::new(None),
+            title_weight: self.title_weight,
+            model: self.model.clone(),
         }
     }
+
+    pub fn pool(&self) -> &PgPool {
+        &self.pool
+    }
+    
+    pub fn owner_id(&self) -> Uuid {
+        self.owner_id
+    }
     
-    /// Load blacklist from context.blacklist.v1 breadcrumb
+    /// Load blacklist from the owner's context.blacklist.v1 breadcrumb
     /// NO FALLBACKS - fails fast if configuration is missing
     pub async fn load_blacklist(&self) -> Result<()> {
         // Query for the blacklist configuration
@@ -47,11 +111,13 @@ impl VectorStore {
             r#"
             SELECT id, schema_name, title, tags, context, embedding, entities, entity_keywords, created_at, updated_at
             FROM breadcrumbs
-            WHERE schema_name = 'context.blacklist.v1'
+            WHERE owner_id = $1
+              AND schema_name = 'context.blacklist.v1'
             ORDER BY updated_at DESC
             LIMIT 1
             "#
         )
+        .bind(self.owner_id)
         .fetch_optional(&self.pool)
         .await?;
         
@@ -100,51 +166,138 @@ impl VectorStore {
         Ok(())
     }
     
+    /// Load the active embedding model from the owner's newest valid system.embedding.config.v1, the
+    /// server's column model without one. Similarity then ranks by that model's vectors only
+    pub async fn load_embedding_model(&self) -> Result<()> {
+        let rows = sqlx::query_as::<_, (Uuid, serde_json::Value)>(
+            "SELECT id, context FROM breadcrumbs WHERE owner_id = $1 AND schema_name = $2 ORDER BY updated_at DESC"
+        )
+        .bind(self.owner_id)
+        .bind(EMBEDDING_CONFIG_SCHEMA)
+        .fetch_all(&self.pool)
+        .await?;
+        let mut config = EmbeddingModelConfig::default();
+        for (id, context) in rows {
+            match EmbeddingModelConfig::from_context(&context) {
+                Ok(valid) => {
+                    config = valid;
+                    break;
+                }
+                Err(e) => tracing::warn!("Ignoring invalid {} breadcrumb {}: {}", EMBEDDING_CONFIG_SCHEMA, id, e),
+            }
+        }
+        tracing::info!("✅ Active embedding model: {}", config.active_model);
+        *self.model.write().unwrap() = config;
+        Ok(())
+    }
+    
+    /// The active model's vector of breadcrumb `id`: `column` (its embedding column) unless the model's
+    /// vectors live in breadcrumb_embeddings
+    pub async fn model_embedding(&self, id: Uuid, column: Option<Vector>) -> Result<Option<Vector>> {
+        let sql = {
+            let model = self.model.read().unwrap();
+            if model.in_column() {
+                return Ok(column);
+            }
+            format!("SELECT {} FROM breadcrumbs WHERE owner_id = $1 AND id = $2", model.vector_sql().vector)
+        };
+        let vector = sqlx::query_scalar::<_, Option<Vector>>(&sql)
+            .bind(self.owner_id)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(vector.flatten())
+    }
+    
+    /// Whether load_blacklist has succeeded
+    pub async fn blacklist_loaded(&self) -> bool {
+        !self.blacklist_cache.read().await.is_empty()
+    }
+    
     /// Get current blacklist (from cache)
     async fn get_blacklist(&self) -> Vec<String> {
         self.blacklist_cache.read().await.clone()
     }
     
+    /// Cosine distance to the query in $1, the same mix as the server's search?target=both; rows
+    /// without a title embedding use their content distance for the title share. With the active
+    /// model outside the embedding column there are no title vectors, so only its vector counts.
+    /// Also the condition for rows having a vector of the active model
+    fn distance_sql(&self) -> (String, String) {
+        let model = self.model.read().unwrap();
+        let sql = model.vector_sql();
+        let distance = if !model.in_column() {
+            format!("({} <=> $1)", sql.vector)
+        } else if self.title_weight > 0.0 {
+            format!(
+                "({} * (embedding <=> $1) + {} * COALESCE(title_embedding <=> $1, embedding <=> $1))",
+                1.0 - self.title_weight, self.title_weight
+            )
+        } else {
+            "(embedding <=> $1)".to_string()
+        };
+        (distance, sql.embedded)
+    }
+    
     /// Find similar breadcrumbs using pgvector cosine similarity
     pub async fn find_similar(
         &self,
         query_embedding: &Vector,
         limit: usize,
         session_filter: Option<&str>,
+        policy: &ReadPolicy,
     ) -> Result<Vec<BreadcrumbRow>> {
+        let _timer = metrics::db_timer("find_similar");
         // Load blacklist from cache (configured via context.blacklist.v1)
         let blacklist = self.get_blacklist().await;
+        let (distance, embedded) = self.distance_sql();
         
-        let query = if let Some(session) = session_filter {
-            sqlx::query_as::<_, BreadcrumbRow>(
-                r#"
-                SELECT id, schema_name, title, tags, context, embedding, entities, entity_keywords, created_at, updated_at
+        let sql = if session_filter.is_some() {
+            format!(r#"
+                SELECT id, schema_name, title, tags, context, embedding, entities, entity_keywords, created_at, updated_at,
+                       (1 - {distance})::float8 AS score
                 FROM breadcrumbs
-                WHERE embedding IS NOT NULL
+                WHERE owner_id = $5
+                  AND {embedded}
                   AND $2 = ANY(tags)
                   AND schema_name != ALL($4)
-                ORDER BY embedding <=> $1
+                  AND sensitivity <= $6::sensitivity
+                  AND (visibility <> 'private' OR $7)
+                ORDER BY {distance}
                 LIMIT $3
-                "#
-            )
+                "#)
+        } else {
+            format!(r#"
+                SELECT id, schema_name, title, tags, context, embedding, entities, entity_keywords, created_at, updated_at,
+                       (1 - {distance})::float8 AS score
+                FROM breadcrumbs
+                WHERE owner_id = $4
+                  AND {embedded}
+                  AND schema_name != ALL($3)
+                  AND sensitivity <= $5::sensitivity
+                  AND (visibility <> 'private' OR $6)
+                ORDER BY {distance}
+                LIMIT $2
+                "#)
+        };
+        
+        let query = if let Some(session) = session_filter {
+            sqlx::query_as::<_, BreadcrumbRow>(&sql)
             .bind(query_embedding)
             .bind(session)
             .bind(limit as i64)
             .bind(&blacklist)
+            .bind(self.owner_id)
+            .bind(policy.max_sensitivity.as_str())
+            .bind(policy.include_private)
         } else {
-            sqlx::query_as::<_, BreadcrumbRow>(
-                r#"
-                SELECT id, schema_name, title, tags, context, embedding, entities, entity_keywords, created_at, updated_at
-                FROM breadcrumbs
-                WHERE embedding IS NOT NULL
-                  AND schema_name != ALL($3)
-                ORDER BY embedding <=> $1
-                LIMIT $2
-                "#
-            )
+            sqlx::query_as::<_, BreadcrumbRow>(&sql)
             .bind(query_embedding)
             .bind(limit as i64)
             .bind(&blacklist)
+            .bind(self.owner_id)
+            .bind(policy.max_sensitivity.as_str())
+            .bind(policy.include_private)
         };
         
         let results = query.fetch_all(&self.pool).await?;
@@ -158,7 +311,9 @@ impl VectorStore {
         schema_name: Option<&str>,
         session_filter: Option<&str>,
         limit: usize,
+        policy: &ReadPolicy,
     ) -> Result<Vec<BreadcrumbRow>> {
+        let _timer = metrics::db_timer("get_recent");
         // Load blacklist from cache (configured via context.blacklist.v1)
         let blacklist = self.get_blacklist().await;
         
@@ -168,9 +323,12 @@ impl VectorStore {
                     r#"
                     SELECT id, schema_name, title, tags, context, embedding, entities, entity_keywords, created_at, updated_at
                     FROM breadcrumbs
-                    WHERE schema_name = $1
+                    WHERE owner_id = $5
+                      AND schema_name = $1
                       AND $2 = ANY(tags)
                       AND schema_name != ALL($4)
+                      AND sensitivity <= $6::sensitivity
+                      AND (visibility <> 'private' OR $7)
                     ORDER BY created_at DESC
                     LIMIT $3
                     "#
@@ -179,14 +337,20 @@ impl VectorStore {
                 .bind(session)
                 .bind(limit as i64)
                 .bind(&blacklist)
+                .bind(self.owner_id)
+                .bind(policy.max_sensitivity.as_str())
+                .bind(policy.include_private)
             }
             (Some(schema), None) => {
                 sqlx::query_as::<_, BreadcrumbRow>(
                     r#"
                     SELECT id, schema_name, title, tags, context, embedding, entities, entity_keywords, created_at, updated_at
                     FROM breadcrumbs
-                    WHERE schema_name = $1
+                    WHERE owner_id = $4
+                      AND schema_name = $1
                       AND schema_name != ALL($3)
+                      AND sensitivity <= $5::sensitivity
+                      AND (visibility <> 'private' OR $6)
                     ORDER BY created_at DESC
                     LIMIT $2
                     "#
@@ -194,6 +358,9 @@ impl VectorStore {
                 .bind(schema)
                 .bind(limit as i64)
                 .bind(&blacklist)
+                .bind(self.owner_id)
+                .bind(policy.max_sensitivity.as_str())
+                .bind(policy.include_private)
             }
             (None, Some(session)) => {
                 // THE RCRT WAY: Get everything, exclude system internals via dynamic blacklist
@@ -201,8 +368,11 @@ impl VectorStore {
                     r#"
                     SELECT id, schema_name, title, tags, context, embedding, entities, entity_keywords, created_at, updated_at
                     FROM breadcrumbs
-                    WHERE $1 = ANY(tags)
+                    WHERE owner_id = $4
+                      AND $1 = ANY(tags)
                       AND schema_name != ALL($3)
+                      AND sensitivity <= $5::sensitivity
+                      AND (visibility <> 'private' OR $6)
                     ORDER BY created_at DESC
                     LIMIT $2
                     "#
@@ -210,19 +380,28 @@ impl VectorStore {
                 .bind(session)
                 .bind(limit as i64)
                 .bind(&blacklist)
+                .bind(self.owner_id)
+                .bind(policy.max_sensitivity.as_str())
+                .bind(policy.include_private)
             }
             (None, None) => {
                 sqlx::query_as::<_, BreadcrumbRow>(
                     r#"
                     SELECT id, schema_name, title, tags, context, embedding, entities, entity_keywords, created_at, updated_at
                     FROM breadcrumbs
-                    WHERE schema_name != ALL($2)
+                    WHERE owner_id = $3
+                      AND schema_name != ALL($2)
+                      AND sensitivity <= $4::sensitivity
+                      AND (visibility <> 'private' OR $5)
                     ORDER BY created_at DESC
                     LIMIT $1
                     "#
                 )
                 .bind(limit as i64)
                 .bind(&blacklist)
+                .bind(self.owner_id)
+                .bind(policy.max_sensitivity.as_str())
+                .bind(policy.include_private)
             }
         };
         
@@ -235,31 +414,45 @@ impl VectorStore {
         &self,
         schema_name: &str,
         session_filter: Option<&str>,
+        policy: &ReadPolicy,
     ) -> Result<Option<BreadcrumbRow>> {
+        let _timer = metrics::db_timer("get_latest");
         let query = if let Some(session) = session_filter {
             sqlx::query_as::<_, BreadcrumbRow>(
                 r#"
                 SELECT id, schema_name, title, tags, context, embedding, entities, entity_keywords, created_at, updated_at
                 FROM breadcrumbs
-                WHERE schema_name = $1
+                WHERE owner_id = $3
+                  AND schema_name = $1
                   AND $2 = ANY(tags)
+                  AND sensitivity <= $4::sensitivity
+                  AND (visibility <> 'private' OR $5)
                 ORDER BY created_at DESC
                 LIMIT 1
                 "#
             )
             .bind(schema_name)
             .bind(session)
+            .bind(self.owner_id)
+            .bind(policy.max_sensitivity.as_str())
+            .bind(policy.include_private)
         } else {
             sqlx::query_as::<_, BreadcrumbRow>(
                 r#"
                 SELECT id, schema_name, title, tags, context, embedding, entities, entity_keywords, created_at, updated_at
                 FROM breadcrumbs
-                WHERE schema_name = $1
+                WHERE owner_id = $2
+                  AND schema_name = $1
+                  AND sensitivity <= $3::sensitivity
+                  AND (visibility <> 'private' OR $4)
                 ORDER BY created_at DESC
                 LIMIT 1
                 "#
             )
             .bind(schema_name)
+            .bind(self.owner_id)
+            .bind(policy.max_sensitivity.as_str())
+            .bind(policy.include_private)
         };
         
         let result = query.fetch_optional(&self.pool).await?;
@@ -271,18 +464,26 @@ impl VectorStore {
         &self,
         tag: &str,
         limit: usize,
+        policy: &ReadPolicy,
     ) -> Result<Vec<BreadcrumbRow>> {
+        let _timer = metrics::db_timer("get_by_tag");
         let results = sqlx::query_as::<_, BreadcrumbRow>(
             r#"
-            SELECT id, schema_name, title, tags, context, embedding, created_at, updated_at
+            SELECT id, schema_name, title, tags, context, embedding, entities, entity_keywords, created_at, updated_at
             FROM breadcrumbs
-            WHERE $1 = ANY(tags)
+            WHERE owner_id = $3
+              AND $1 = ANY(tags)
+              AND sensitivity <= $4::sensitivity
+              AND (visibility <> 'private' OR $5)
             ORDER BY created_at DESC
             LIMIT $2
             "#
         )
         .bind(tag)
         .bind(limit as i64)
+        .bind(self.owner_id)
+        .bind(policy.max_sensitivity.as_str())
+        .bind(policy.include_private)
         .fetch_all(&self.pool)
         .await?;
         
@@ -291,20 +492,119 @@ impl VectorStore {
     
     /// Get breadcrumb by ID
     pub async fn get_by_id(&self, id: Uuid) -> Result<Option<BreadcrumbRow>> {
+        let _timer = metrics::db_timer("get_by_id");
         let result = sqlx::query_as::<_, BreadcrumbRow>(
             r#"
             SELECT id, schema_name, title, tags, context, embedding, entities, entity_keywords, created_at, updated_at
             FROM breadcrumbs
-            WHERE id = $1
+            WHERE owner_id = $2
+              AND id = $1
+            "#
+        )
+        .bind(id)
+        .bind(self.owner_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        
+        Ok(result)
+    }
+    
+    /// get_by_id, or None when `policy` hides the breadcrumb
+    pub async fn get_by_id_within(&self, id: Uuid, policy: &ReadPolicy) -> Result<Option<BreadcrumbRow>> {
+        let _timer = metrics::db_timer("get_by_id_within");
+        let result = sqlx::query_as::<_, BreadcrumbRow>(
+            r#"
+            SELECT id, schema_name, title, tags, context, embedding, entities, entity_keywords, created_at, updated_at
+            FROM breadcrumbs
+            WHERE owner_id = $2
+              AND id = $1
+              AND sensitivity <= $3::sensitivity
+              AND (visibility <> 'private' OR $4)
             "#
         )
         .bind(id)
+        .bind(self.owner_id)
+        .bind(policy.max_sensitivity.as_str())
+        .bind(policy.include_private)
         .fetch_optional(&self.pool)
         .await?;
         
         Ok(result)
     }
     
+    /// What each of `ids` declares it was triggered by (a `triggered_by` reference in breadcrumb_references);
+    /// breadcrumbs without one are absent
+    pub async fn triggered_by(&self, ids: &[Uuid]) -> Result<HashMap<Uuid, Uuid>> {
+        if ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let _timer = metrics::db_timer("triggered_by");
+        let rows = sqlx::query_as::<_, (Uuid, Uuid)>(
+            r#"
+            SELECT DISTINCT ON (source_id) source_id, target_id
+            FROM breadcrumb_references
+            WHERE owner_id = $2
+              AND source_id = ANY($1)
+              AND relation = $3
+            ORDER BY source_id, created_at
+            "#
+        )
+        .bind(ids)
+        .bind(self.owner_id)
+        .bind(TRIGGERED_BY)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().collect())
+    }
+
+    /// Latest agent.def.v1 whose context.agent_id is `agent_id`
+    pub async fn get_agent_def(&self, agent_id: &str) -> Result<Option<BreadcrumbRow>> {
+        let _timer = metrics::db_timer("get_agent_def");
+        let result = sqlx::query_as::<_, BreadcrumbRow>(
+            r#"
+            SELECT id, schema_name, title, tags, context, embedding, entities, entity_keywords, created_at, updated_at
+            FROM breadcrumbs
+            WHERE owner_id = $2
+              AND schema_name = 'agent.def.v1'
+              AND context->>'agent_id' = $1
+            ORDER BY updated_at DESC
+            LIMIT 1
+            "#
+        )
+        .bind(agent_id)
+        .bind(self.owner_id)
+        .fetch_optional(&self.pool)
+        .await;
+        
+        *self.agent_def_error.lock().unwrap() = result.as_ref().err().map(|e| e.to_string());
+        Ok(result?)
+    }
+    
+    /// The llm.config.v1 an agent.def.v1 names in `llm_config_id`
+    pub async fn get_llm_config(&self, id: Uuid) -> Result<Option<BreadcrumbRow>> {
+        let _timer = metrics::db_timer("get_llm_config");
+        let row = sqlx::query_as::<_, BreadcrumbRow>(
+            r#"
+            SELECT id, schema_name, title, tags, context, embedding, entities, entity_keywords, created_at, updated_at
+            FROM breadcrumbs
+            WHERE id = $1
+              AND owner_id = $2
+              AND schema_name = 'llm.config.v1'
+            "#
+        )
+        .bind(id)
+        .bind(self.owner_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row)
+    }
+    
+    /// Why the latest agent.def.v1 lookup failed, if it did
+    pub fn agent_def_error(&self) -> Option<String> {
+        self.agent_def_error.lock().unwrap().clone()
+    }
+    
     /// Hybrid search: combines vector similarity with entity keyword matching
     /// Improves accuracy from ~70% to ~95% for knowledge breadcrumb retrieval
     pub async fn find_similar_hybrid(
@@ -313,20 +613,23 @@ impl VectorStore {
         query_keywords: &[String],
         limit: usize,
         session_filter: Option<&str>,
+        policy: &ReadPolicy,
     ) -> Result<Vec<BreadcrumbRow>> {
+        let _timer = metrics::db_timer("find_similar_hybrid");
         // Load blacklist from cache (configured via context.blacklist.v1)
         let blacklist = self.get_blacklist().await;
         let keyword_count = query_keywords.len() as f32;
+        let (distance, embedded) = self.distance_sql();
         
-        let sql = if let Some(session) = session_filter {
-            r#"
+        let sql = if session_filter.is_some() {
+            format!(r#"
             WITH scored AS (
                 SELECT 
                     id, schema_name, title, tags, context, embedding, 
                     entities, entity_keywords, created_at, updated_at,
                     -- Vector similarity (0-1, higher is better)
-                    CASE WHEN embedding IS NOT NULL 
-                        THEN 1.0 / (1.0 + (embedding <=> $1))
+                    CASE WHEN {embedded}
+                        THEN 1.0 / (1.0 + {distance})
                         ELSE 0.0 
                     END as vec_score,
                     -- Entity keyword matches (0-1)
@@ -339,26 +642,30 @@ impl VectorStore {
                         ELSE 0.0
                     END as keyword_score
                 FROM breadcrumbs
-                WHERE $4 = ANY(tags)
+                WHERE owner_id = $7
+                  AND $4 = ANY(tags)
                   AND schema_name != ALL($6)
+                  AND sensitivity <= $8::sensitivity
+                  AND (visibility <> 'private' OR $9)
             )
             SELECT 
                 id, schema_name, title, tags, context, embedding,
-                entities, entity_keywords, created_at, updated_at
+                entities, entity_keywords, created_at, updated_at,
+                (vec_score * 0.6 + keyword_score * 0.4)::float8 AS score
             FROM scored
             WHERE vec_score > 0 OR keyword_score > 0
             ORDER BY (vec_score * 0.6 + keyword_score * 0.4) DESC
             LIMIT $5
-            "#
+            "#)
         } else {
-            r#"
+            format!(r#"
             WITH scored AS (
                 SELECT 
                     id, schema_name, title, tags, context, embedding,
                     entities, entity_keywords, created_at, updated_at,
                     -- Vector similarity (0-1, higher is better)
-                    CASE WHEN embedding IS NOT NULL 
-                        THEN 1.0 / (1.0 + (embedding <=> $1))
+                    CASE WHEN {embedded}
+                        THEN 1.0 / (1.0 + {distance})
                         ELSE 0.0 
                     END as vec_score,
                     -- Entity keyword matches (0-1)
@@ -371,39 +678,102 @@ impl VectorStore {
                         ELSE 0.0
                     END as keyword_score
                 FROM breadcrumbs
-                WHERE schema_name != ALL($5)
+                WHERE owner_id = $6
+                  AND schema_name != ALL($5)
+                  AND sensitivity <= $7::sensitivity
+                  AND (visibility <> 'private' OR $8)
             )
             SELECT 
                 id, schema_name, title, tags, context, embedding,
-                entities, entity_keywords, created_at, updated_at
+                entities, entity_keywords, created_at, updated_at,
+                (vec_score * 0.6 + keyword_score * 0.4)::float8 AS score
             FROM scored
             WHERE vec_score > 0 OR keyword_score > 0
             ORDER BY (vec_score * 0.6 + keyword_score * 0.4) DESC
             LIMIT $4
-            "#
+            "#)
         };
         
         let query = if let Some(session) = session_filter {
-            sqlx::query_as::<_, BreadcrumbRow>(sql)
+            sqlx::query_as::<_, BreadcrumbRow>(&sql)
                 .bind(query_embedding)
                 .bind(query_keywords)
                 .bind(keyword_count)
                 .bind(session)
                 .bind(limit as i64)
                 .bind(&blacklist)
+                .bind(self.owner_id)
+                .bind(policy.max_sensitivity.as_str())
+                .bind(policy.include_private)
         } else {
-            sqlx::query_as::<_, BreadcrumbRow>(sql)
+            sqlx::query_as::<_, BreadcrumbRow>(&sql)
                 .bind(query_embedding)
                 .bind(query_keywords)
                 .bind(keyword_count)
                 .bind(limit as i64)
                 .bind(&blacklist)
+                .bind(self.owner_id)
+                .bind(policy.max_sensitivity.as_str())
+                .bind(policy.include_private)
         };
         
         let results = query.fetch_all(&self.pool).await?;
         Ok(results)
     }
-    
+
+    /// The keyword half of find_similar_hybrid on its own, across sessions: the share of
+    /// `query_keywords` in each row's entity_keywords. For a trigger without an embedding
+    pub async fn find_by_keywords(
+        &self,
+        query_keywords: &[String],
+        limit: usize,
+        policy: &ReadPolicy,
+    ) -> Result<Vec<BreadcrumbRow>> {
+        let _timer = metrics::db_timer("find_by_keywords");
+        if query_keywords.is_empty() {
+            return Ok(Vec::new());
+        }
+        let blacklist = self.get_blacklist().await;
+
+        let results = sqlx::query_as::<_, BreadcrumbRow>(
+            r#"
+            WITH scored AS (
+                SELECT
+                    id, schema_name, title, tags, context, embedding,
+                    entities, entity_keywords, created_at, updated_at,
+                    (
+                        SELECT COUNT(DISTINCT kw)::float / $2
+                        FROM unnest(entity_keywords) kw
+                        WHERE kw = ANY($1)
+                    ) as keyword_score
+                FROM breadcrumbs
+                WHERE owner_id = $5
+                  AND entity_keywords && $1
+                  AND schema_name != ALL($4)
+                  AND sensitivity <= $6::sensitivity
+                  AND (visibility <> 'private' OR $7)
+            )
+            SELECT
+                id, schema_name, title, tags, context, embedding,
+                entities, entity_keywords, created_at, updated_at,
+                keyword_score::float8 AS score
+            FROM scored
+            ORDER BY keyword_score DESC, updated_at DESC
+            LIMIT $3
+            "#
+        )
+        .bind(query_keywords)
+        .bind(query_keywords.len() as f32)
+        .bind(limit as i64)
+        .bind(&blacklist)
+        .bind(self.owner_id)
+        .bind(policy.max_sensitivity.as_str())
+        .bind(policy.include_private)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(results)
+    }
+
     /// Update entities for a breadcrumb (async backfill)
     pub async fn update_entities(
         &self,
@@ -411,16 +781,18 @@ impl VectorStore {
         entities: &serde_json::Value,
         keywords: &[String],
     ) -> Result<()> {
+        let _timer = metrics::db_timer("update_entities");
         sqlx::query(
             r#"
             UPDATE breadcrumbs 
             SET entities = $2, entity_keywords = $3
-            WHERE id = $1
+            WHERE id = $1 AND owner_id = $4
             "#
         )
         .bind(breadcrumb_id)
         .bind(entities)
         .bind(keywords)
+        .bind(self.owner_id)
         .execute(&self.pool)
         .await?;
         
@@ -428,3 +800,185 @@ impl VectorStore {
     }
 }
 
+
+#[cfg(all(test, feature = "db-tests"))]
+mod tests {
+    use super::*;
+    use rcrt_core::db::Db;
+    use rcrt_core::models::BreadcrumbCreate;
+
+    const SESSION: &str = "session:owner-scope-test";
+
+    fn capped(max_sensitivity: Sensitivity) -> ReadPolicy {
+        ReadPolicy { max_sensitivity, include_private: true }
+    }
+
+    async fn tenant_with_breadcrumbs(db: &Db, schemas: &[&str]) -> Result<(Uuid, Vec<Uuid>)> {
+        let owner = Uuid::new_v4();
+        db.ensure_tenant(owner, "vector-store-test").await?;
+        let mut ids = Vec::new();
+        for schema in schemas {
+            let bc = db.create_breadcrumb_for(owner, None, None, BreadcrumbCreate {
+                title: format!("{} for {}", schema, owner),
+                description: None,
+                semantic_version: None,
+                context: serde_json::json!({ "excluded_schemas": [{ "schema_name": "system.internal.v1" }] }),
+                tags: vec![SESSION.to_string()],
+                schema_name: Some(schema.to_string()),
+                llm_hints: None,
+                visibility: None,
+                sensitivity: None,
+                ttl: None,
+                ttl_type: None,
+                ttl_config: None,
+                ttl_source: None,
+                entity_keywords: None,
+                entities: None,
+            }).await?;
+            ids.push(bc.id);
+        }
+        Ok((owner, ids))
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_queries_only_see_the_stores_owner(pool: PgPool) -> Result<()> {
+        let db = Db { pool: pool.clone() };
+        let (owner_a, ids_a) = tenant_with_breadcrumbs(&db, &["context.blacklist.v1", "note.v1"]).await?;
+        let (owner_b, ids_b) = tenant_with_breadcrumbs(&db, &["note.v1"]).await?;
+
+        let store_a = VectorStore::new(pool.clone(), owner_a);
+        store_a.load_blacklist().await?;
+        assert!(store_a.get_by_id(ids_a[1]).await?.is_some());
+        assert!(store_a.get_by_id(ids_b[0]).await?.is_none());
+
+        let recent: Vec<Uuid> = store_a.get_recent(Some("note.v1"), Some(SESSION), 10, &ReadPolicy::unrestricted()).await?.into_iter().map(|r| r.id).collect();
+        assert_eq!(recent, vec![ids_a[1]]);
+        let latest = store_a.get_latest("note.v1", None, &ReadPolicy::unrestricted()).await?.expect("owner a has a note");
+        assert_eq!(latest.id, ids_a[1]);
+        let tagged: Vec<Uuid> = store_a.get_by_tag(SESSION, 10, &ReadPolicy::unrestricted()).await?.into_iter().map(|r| r.id).collect();
+        assert!(tagged.iter().all(|id| ids_a.contains(id)) && tagged.len() == 2);
+
+        // Owner b has no blacklist of its own, even though owner a does
+        let store_b = VectorStore::new(pool.clone(), owner_b);
+        assert!(store_b.load_blacklist().await.is_err());
+
+        // Entity writes can't reach another owner's breadcrumb
+        store_b.update_entities(ids_a[1], &serde_json::json!([]), &["leak".to_string()]).await?;
+        assert!(store_a.get_by_id(ids_a[1]).await?.unwrap().entity_keywords.is_none());
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_search_scores_and_agent_def(pool: PgPool) -> Result<()> {
+        let db = Db { pool: pool.clone() };
+        let (owner, ids) = tenant_with_breadcrumbs(&db, &["context.blacklist.v1", "note.v1", "agent.def.v1"]).await?;
+        db.set_breadcrumb_embedding(owner, None, ids[1], vec![0.5; 384]).await?;
+        sqlx::query("UPDATE breadcrumbs SET context = $2 WHERE id = $1")
+            .bind(ids[2])
+            .bind(serde_json::json!({ "agent_id": "chat", "context_provenance": false }))
+            .execute(&pool)
+            .await?;
+        let store = VectorStore::new(pool.clone(), owner);
+        store.load_blacklist().await?;
+        store.update_entities(ids[1], &serde_json::json!({}), &["rust".to_string()]).await?;
+
+        // Identical embedding: cosine similarity 1, and the one keyword matches too
+        let query = Vector::from(vec![0.5; 384]);
+        let similar = store.find_similar(&query, 5, None, &ReadPolicy::unrestricted()).await?;
+        assert_eq!(similar[0].id, ids[1]);
+        assert!((similar[0].score.unwrap() - 1.0).abs() < 1e-6);
+        let hybrid = store.find_similar_hybrid(&query, &["rust".to_string()], 5, None, &ReadPolicy::unrestricted()).await?;
+        assert_eq!(hybrid[0].id, ids[1]);
+        assert!((hybrid[0].score.unwrap() - 1.0).abs() < 1e-6);
+        assert!(store.get_by_id(ids[1]).await?.unwrap().score.is_none());
+
+        // A pii breadcrumb is out of reach for a consumer capped at low
+        sqlx::query("UPDATE breadcrumbs SET sensitivity = 'pii' WHERE id = $1").bind(ids[1]).execute(&pool).await?;
+        assert!(store.find_similar(&query, 5, None, &capped(Sensitivity::Low)).await?.is_empty());
+        assert!(store.find_similar_hybrid(&query, &["rust".to_string()], 5, None, &capped(Sensitivity::Low)).await?.is_empty());
+        assert_eq!(store.find_similar(&query, 5, None, &capped(Sensitivity::Pii)).await?[0].id, ids[1]);
+
+        let def = store.get_agent_def("chat").await?.expect("agent def");
+        assert_eq!(def.id, ids[2]);
+        assert!(store.get_agent_def("someone-else").await?.is_none());
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_keyword_search_without_embeddings(pool: PgPool) -> Result<()> {
+        let db = Db { pool: pool.clone() };
+        let (owner, ids) = tenant_with_breadcrumbs(&db, &["context.blacklist.v1", "note.v1", "note.v1", "note.v1"]).await?;
+        let store = VectorStore::new(pool.clone(), owner);
+        store.load_blacklist().await?;
+        let keywords = |kws: &[&str]| kws.iter().map(|k| k.to_string()).collect::<Vec<_>>();
+        store.update_entities(ids[1], &serde_json::json!({}), &keywords(&["rust", "tokio"])).await?;
+        store.update_entities(ids[2], &serde_json::json!({}), &keywords(&["rust"])).await?;
+        store.update_entities(ids[3], &serde_json::json!({}), &keywords(&["python"])).await?;
+
+        // Nothing is embedded, so hybrid's vector half has nothing to go on; keywords still rank
+        let found = store.find_by_keywords(&keywords(&["rust", "tokio"]), 5, &ReadPolicy::unrestricted()).await?;
+        assert_eq!(found.iter().map(|r| r.id).collect::<Vec<_>>(), vec![ids[1], ids[2]]);
+        assert!((found[0].score.unwrap() - 1.0).abs() < 1e-6);
+        assert!((found[1].score.unwrap() - 0.5).abs() < 1e-6);
+        assert!(store.find_by_keywords(&[], 5, &ReadPolicy::unrestricted()).await?.is_empty());
+
+        sqlx::query("UPDATE breadcrumbs SET sensitivity = 'pii' WHERE id = $1").bind(ids[1]).execute(&pool).await?;
+        let found = store.find_by_keywords(&keywords(&["rust", "tokio"]), 5, &capped(Sensitivity::Low)).await?;
+        assert_eq!(found.iter().map(|r| r.id).collect::<Vec<_>>(), vec![ids[2]]);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_title_weight_reorders_similarity(pool: PgPool) -> Result<()> {
+        let db = Db { pool: pool.clone() };
+        let (owner, ids) = tenant_with_breadcrumbs(&db, &["context.blacklist.v1", "note.v1", "note.v1"]).await?;
+        let unit = |i: usize| { let mut v = vec![0.0f32; 384]; v[i] = 1.0; v };
+        let between: Vec<f32> = unit(0).iter().zip(unit(1)).map(|(a, b)| (a + b) / 2f32.sqrt()).collect();
+        // ids[1]: title matches the query exactly; ids[2]: content does
+        db.set_breadcrumb_embedding(owner, None, ids[1], between).await?;
+        db.set_breadcrumb_title_embedding(owner, None, ids[1], unit(0)).await?;
+        db.set_breadcrumb_embedding(owner, None, ids[2], unit(0)).await?;
+        db.set_breadcrumb_title_embedding(owner, None, ids[2], unit(1)).await?;
+        let query = Vector::from(unit(0));
+
+        let store = VectorStore::new(pool.clone(), owner);
+        store.load_blacklist().await?;
+        assert_eq!(store.find_similar(&query, 5, None, &ReadPolicy::unrestricted()).await?[0].id, ids[2]);
+        let store = VectorStore::new(pool.clone(), owner).with_title_weight(0.5);
+        store.load_blacklist().await?;
+        assert_eq!(store.find_similar(&query, 5, None, &ReadPolicy::unrestricted()).await?[0].id, ids[1]);
+        assert_eq!(store.find_similar_hybrid(&query, &[], 5, None, &ReadPolicy::unrestricted()).await?[0].id, ids[1]);
+        Ok(())
+    }
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_similarity_ranks_by_active_model(pool: PgPool) -> Result<()> {
+        let db = Db { pool: pool.clone() };
+        let (owner, ids) = tenant_with_breadcrumbs(&db, &["context.blacklist.v1", "note.v1", "note.v1", EMBEDDING_CONFIG_SCHEMA]).await?;
+        let unit = |dim: usize, i: usize| { let mut v = vec![0.0f32; dim]; v[i] = 1.0; v };
+        // Mid-migration: both notes have a column vector, only ids[2] one of the target model
+        db.set_breadcrumb_embedding(owner, None, ids[1], unit(384, 0)).await?;
+        db.set_breadcrumb_embedding(owner, None, ids[2], unit(384, 1)).await?;
+        db.set_breadcrumb_model_embedding(owner, None, ids[2], "target-8", unit(8, 0)).await?;
+
+        let store = VectorStore::new(pool.clone(), owner);
+        store.load_blacklist().await?;
+        // The config breadcrumb doesn't name a model yet
+        store.load_embedding_model().await?;
+        let hits = store.find_similar(&Vector::from(unit(384, 0)), 5, None, &ReadPolicy::unrestricted()).await?;
+        assert_eq!(hits.iter().map(|r| r.id).collect::<Vec<_>>(), vec![ids[1], ids[2]]);
+
+        sqlx::query("UPDATE breadcrumbs SET context = $1 WHERE id = $2")
+            .bind(serde_json::json!({ "active_model": "target-8" }))
+            .bind(ids[3])
+            .execute(&pool)
+            .await?;
+        store.load_embedding_model().await?;
+        let query = Vector::from(unit(8, 0));
+        let hits = store.find_similar(&query, 5, None, &ReadPolicy::unrestricted()).await?;
+        assert_eq!(hits.iter().map(|r| r.id).collect::<Vec<_>>(), vec![ids[2]]);
+        assert_eq!(store.find_similar_hybrid(&query, &[], 5, None, &ReadPolicy::unrestricted()).await?.len(), 1);
+        assert_eq!(store.model_embedding(ids[2], None).await?, Some(query));
+        assert_eq!(store.model_embedding(ids[1], None).await?, None);
+        Ok(())
+    }
+}
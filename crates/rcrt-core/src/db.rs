@@ -1,68 +1,124 @@
-use anyhow::Result;
 use sqlx::{Pool, Postgres, postgres::PgPoolOptions, postgres::PgConnection};
 use uuid::Uuid;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde_json::Value as JsonValue;
-use crate::models::{Breadcrumb, BreadcrumbCreate, BreadcrumbUpdate, Visibility, Sensitivity, BreadcrumbContextView, BreadcrumbFull, Selector, SelectorSubscription};
+use crate::models::{AccessCount, AccessType, AgentDependents, AgentOffboarding, AgentRun, AgentRunStage, AgentWebhook, ApiKey, Attachment, AttachmentBody, AttachmentMeta, AttachOutcome, Breadcrumb, BreadcrumbCreate, BreadcrumbShare, BreadcrumbReference, BrokenReference, BreadcrumbUpdate, ChecksumCheck, ChecksumScan, ChecksumSource, ChecksumStatus, DeletedBreadcrumb, EmbeddingCoverage, EmbeddingModelConfig, EncryptedContext, COLUMN_EMBEDDING_MODEL, HistoryAsOf, InboxEntry, NewAttachment, NewBreadcrumbReference, OwnerWebhook, PurgeFilter, ReferencedDelete, SessionOrder, SessionStats, TenantDeletion, TopBreadcrumb, VersionMismatch, Visibility, Sensitivity, BreadcrumbContextView, BreadcrumbFull, DeliveryChannel, Selector, SelectorSubscription, SchemaUsage, Topology, TopologyAction, TopologyAgent, TopologyImport, TopologyItem, TopologyKind, TopologySelector, TopologyWebhook, UpsertedBreadcrumb, WebhookOrder, WebhookStatus};
 use sha2::{Digest, Sha256};
 use pgvector::Vector;
+use crate::error::{DbError, Result};
+use crate::extraction::{ExtractedEntities, EXTRACTED_BY_CLIENT, EXTRACTED_BY_ENCRYPTED};
 
 #[derive(Clone)]
 pub struct Db {
     pub pool: Pool<Postgres>,
 }
 
+/// `PurgeFilter` as a where clause over $1 (owner) through $6; unset fields match everything
+const PURGE_MATCH: &str = "owner_id = $1 and ($2::text is null or schema_name = $2) and tags @> $3::text[] \
+    and ($4::timestamptz is null or created_at < $4) and ($5::timestamptz is null or created_at >= $5) \
+    and ($6::uuid is null or created_by = $6)";
+
+/// Every table holding a tenant's rows, with a where clause over $1 (the tenant) selecting them, in the
+/// order a tenant deletion empties them: rows before the rows they reference. Other tenants' ACL
+/// grants to the tenant or its agents go with them by foreign key (0036)
+pub const TENANT_TABLES: &[(&str, &str)] = &[
+    ("webhook_deliveries", "owner_id = $1"),
+    ("webhook_dlq", "owner_id = $1"),
+    ("owner_webhooks", "owner_id = $1"),
+    ("idempotency_keys", "owner_id = $1"),
+    ("event_outbox", "owner_id = $1"),
+    ("breadcrumb_outbox", "owner_id = $1"),
+    ("acl_entries", "owner_id = $1"),
+    ("subscriptions", "owner_id = $1"),
+    ("selector_subscriptions", "owner_id = $1"),
+    ("secret_audit", "secret_id in (select id from secrets where owner_id = $1)"),
+    ("secrets", "owner_id = $1"),
+    ("breadcrumb_references", "owner_id = $1"),
+    ("breadcrumb_access", "owner_id = $1"),
+    ("breadcrumb_embeddings", "owner_id = $1"),
+    ("breadcrumb_attachments", "owner_id = $1"),
+    ("breadcrumb_shares", "owner_id = $1"),
+    ("breadcrumb_history", "breadcrumb_id in (select id from breadcrumbs where owner_id = $1)"),
+    ("breadcrumbs", "owner_id = $1"),
+    ("attachments", "owner_id = $1"),
+    ("session_stat_agents", "owner_id = $1"),
+    ("session_stats", "owner_id = $1"),
+    ("api_keys", "owner_id = $1"),
+    ("agent_runs", "owner_id = $1"),
+    ("agent_audit", "owner_id = $1"),
+    ("agent_inbox", "owner_id = $1"),
+    ("agent_webhooks", "agent_id in (select id from agents where owner_id = $1)"),
+    ("agents", "owner_id = $1"),
+];
+
+const TENANT_DELETION_COLUMNS: &str = "tenant_id, tenant_name, status, stage, export_to, export_location, deleted, error, started_at, updated_at, finished_at";
+
 impl Db {
     pub async fn connect(database_url: &str, current_owner_id: Uuid, current_agent_id: Option<Uuid>) -> Result<Self> {
-        let owner = current_owner_id;
-        let agent = current_agent_id;
-        let pool = PgPoolOptions::new()
-            .max_connections(10)
-            .after_connect(move |conn, _meta| {
-                let owner = owner;
-                let agent = agent;
-                Box::pin(async move {
-                    sqlx::query("select set_config('app.current_owner_id', $1, false)")
-                        .bind(owner.to_string())
-                        .execute(&mut *conn)
-                        .await?;
-                    if let Some(agent_id) = agent {
-                        sqlx::query("select set_config('app.current_agent_id', $1, false)")
-                            .bind(agent_id.to_string())
-                            .execute(&mut *conn)
-                            .await?;
-                    }
-                    Ok(())
-                })
-            })
+        let pool = pool_options(current_owner_id, current_agent_id)
             .connect(database_url)
             .await?;
 
         Ok(Self { pool })
     }
 
+    /// Like `connect`, without connecting until the first query; for a database that may be down
+    /// at startup, such as a read replica
+    pub fn connect_lazy(database_url: &str, current_owner_id: Uuid, current_agent_id: Option<Uuid>) -> Result<Self> {
+        let pool = pool_options(current_owner_id, current_agent_id).connect_lazy(database_url)?;
+        Ok(Self { pool })
+    }
+
+    #[tracing::instrument(name = "db", skip_all, fields(query = "create_breadcrumb_for"))]
     pub async fn create_breadcrumb_for(&self, owner_id: Uuid, agent_id: Option<Uuid>, created_by: Option<Uuid>, req: BreadcrumbCreate) -> Result<Breadcrumb> {
         let mut conn = self.pool.acquire().await?;
         set_rls(&mut conn, owner_id, agent_id).await?;
-        self.create_breadcrumb_conn(&mut conn, owner_id, created_by, req, None).await
+        self.create_breadcrumb_conn(&mut conn, owner_id, created_by, req, None, None, None).await
     }
 
     pub async fn create_breadcrumb_with_embedding_for(&self, owner_id: Uuid, agent_id: Option<Uuid>, created_by: Option<Uuid>, req: BreadcrumbCreate, embedding: Option<Vec<f32>>) -> Result<Breadcrumb> {
+        self.create_breadcrumb_with_embeddings_for(owner_id, agent_id, created_by, req, embedding, None).await
+    }
+
+    /// Like create_breadcrumb_with_embedding_for, also storing a separate title vector
+    #[tracing::instrument(name = "db", skip_all, fields(query = "create_breadcrumb_with_embeddings_for"))]
+    pub async fn create_breadcrumb_with_embeddings_for(&self, owner_id: Uuid, agent_id: Option<Uuid>, created_by: Option<Uuid>, req: BreadcrumbCreate, embedding: Option<Vec<f32>>, title_embedding: Option<Vec<f32>>) -> Result<Breadcrumb> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, agent_id).await?;
+        self.create_breadcrumb_conn(&mut conn, owner_id, created_by, req, embedding, title_embedding, None).await
+    }
+
+    /// Create with an envelope-encrypted context: the row and history v1 keep `sealed`, and the
+    /// `{"encrypted": true}` stub stands in for `req.context`. No embeddings, and entities are
+    /// marked so the extraction worker skips the row
+    #[tracing::instrument(name = "db", skip_all, fields(query = "create_encrypted_breadcrumb_for"))]
+    pub async fn create_encrypted_breadcrumb_for(&self, owner_id: Uuid, agent_id: Option<Uuid>, created_by: Option<Uuid>, req: BreadcrumbCreate, sealed: EncryptedContext) -> Result<Breadcrumb> {
         let mut conn = self.pool.acquire().await?;
         set_rls(&mut conn, owner_id, agent_id).await?;
-        self.create_breadcrumb_conn(&mut conn, owner_id, created_by, req, embedding).await
+        self.create_breadcrumb_conn(&mut conn, owner_id, created_by, req, None, None, Some(sealed)).await
     }
 
-    async fn create_breadcrumb_conn(&self, conn: &mut PgConnection, owner_id: Uuid, created_by: Option<Uuid>, req: BreadcrumbCreate, embedding: Option<Vec<f32>>) -> Result<Breadcrumb> {
-        let checksum = checksum_json(&req.context);
-        let size_bytes = serde_json::to_vec(&req.context)?.len() as i32;
+    #[allow(clippy::too_many_arguments)]
+    async fn create_breadcrumb_conn(&self, conn: &mut PgConnection, owner_id: Uuid, created_by: Option<Uuid>, mut req: BreadcrumbCreate, mut embedding: Option<Vec<f32>>, mut title_embedding: Option<Vec<f32>>, sealed: Option<EncryptedContext>) -> Result<Breadcrumb> {
+        if sealed.is_some() {
+            req.context = EncryptedContext::stub();
+            req.entity_keywords = Some(Vec::new());
+            req.entities = Some(ExtractedEntities::default().to_entities_json(EXTRACTED_BY_ENCRYPTED));
+            embedding = None;
+            title_embedding = None;
+        }
+        let (checksum, size_bytes) = stored_checksum_and_size(&req.context, sealed.as_ref())?;
         let visibility = req.visibility.unwrap_or(Visibility::Team);
         let sensitivity = req.sensitivity.unwrap_or(Sensitivity::Low);
+        // Keywords without entities came from the client; either way the row counts as extracted
+        let entities = req.entities.or_else(|| req.entity_keywords.as_ref().map(|_| ExtractedEntities::default().to_entities_json(EXTRACTED_BY_CLIENT)));
+        // Row, history v1 and outbox event commit together
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
 
         let rec = sqlx::query_as::<_, DbBreadcrumb>(
             r#"insert into breadcrumbs
-            (owner_id, title, description, semantic_version, context, tags, schema_name, llm_hints, visibility, sensitivity, version, checksum, ttl, ttl_type, ttl_config, ttl_source, created_by, updated_by, size_bytes, created_at, updated_at, embedding)
-            values ($1,$2,$3,$4,$5,$6,$7,$8,$9::visibility,$10::sensitivity,1,$11,$12,$13,$14,$15,$16,$16,$17, now(), now(), $18)
+            (owner_id, title, description, semantic_version, context, tags, schema_name, llm_hints, visibility, sensitivity, version, checksum, ttl, ttl_type, ttl_config, ttl_source, created_by, updated_by, size_bytes, created_at, updated_at, embedding, entity_keywords, entities, title_embedding, context_encrypted, context_dek_encrypted, context_kek_id, checksum_canonical)
+            values ($1,$2,$3,$4,$5,$6,$7,$8,$9::visibility,$10::sensitivity,1,$11,$12,$13,$14,$15,$16,$16,$17, now(), now(), $18, $19, $20, $21, $22, $23, $24, true)
             returning id, owner_id, title, description, semantic_version, context, tags, schema_name, llm_hints, visibility::text as visibility, sensitivity::text as sensitivity, version, checksum, ttl, ttl_type, ttl_config, read_count, ttl_source, created_at, updated_at, created_by, updated_by, size_bytes, embedding
             "#,
         )
@@ -84,24 +140,36 @@ impl Db {
         .bind(created_by)
         .bind(size_bytes)
         .bind(embedding.map(Vector::from))
-        .fetch_one(&mut *conn)
+        .bind(req.entity_keywords)          // NEW: client-supplied keywords mark row as extracted
+        .bind(entities)
+        .bind(title_embedding.map(Vector::from))
+        .bind(sealed.as_ref().map(|s| s.ciphertext.as_slice()))
+        .bind(sealed.as_ref().map(|s| s.dek_encrypted.as_slice()))
+        .bind(sealed.as_ref().map(|s| s.kek_id.as_str()))
+        .fetch_one(&mut *tx)
         .await?;
         // write history v1
         sqlx::query(
-            r#"insert into breadcrumb_history (breadcrumb_id, version, context, updated_at, updated_by, checksum)
-               values ($1, $2, $3, now(), $4, $5) on conflict do nothing"#
+            r#"insert into breadcrumb_history (breadcrumb_id, version, context, updated_at, updated_by, checksum, context_encrypted, context_dek_encrypted, context_kek_id, checksum_canonical)
+               values ($1, $2, $3, now(), $4, $5, $6, $7, $8, true) on conflict do nothing"#
         )
         .bind(rec.id)
         .bind(rec.version)
         .bind(&rec.context)
         .bind(rec.created_by)
         .bind(&rec.checksum)
-        .execute(&mut *conn)
+        .bind(sealed.as_ref().map(|s| s.ciphertext.as_slice()))
+        .bind(sealed.as_ref().map(|s| s.dek_encrypted.as_slice()))
+        .bind(sealed.as_ref().map(|s| s.kek_id.as_str()))
+        .execute(&mut *tx)
         .await?;
+        record_breadcrumb_event(&mut *tx, owner_id, rec.id, rec.version, "created").await?;
+        tx.commit().await?;
 
         Ok(rec.into())
     }
 
+    #[tracing::instrument(name = "db", skip_all, fields(query = "get_breadcrumb_context_for"))]
     pub async fn get_breadcrumb_context_for(&self, owner_id: Uuid, agent_id: Option<Uuid>, id: Uuid) -> Result<Option<BreadcrumbContextView>> {
         let mut conn = self.pool.acquire().await?;
         set_rls(&mut conn, owner_id, agent_id).await?;
@@ -117,110 +185,286 @@ impl Db {
         .fetch_optional(&mut *conn)
         .await?;
 
-        Ok(rec.map(|r| BreadcrumbContextView {
-            id: r.id,
-            title: r.title,
-            description: r.description,
-            semantic_version: r.semantic_version,
-            context: r.context,
-            tags: r.tags,
-            schema_name: r.schema_name,
-            llm_hints: r.llm_hints,
-            version: r.version,
-            updated_at: r.updated_at,
-        }))
+        Ok(rec.map(BreadcrumbContextView::from))
     }
 
-    pub async fn get_breadcrumb_full_for(&self, owner_id: Uuid, agent_id: Option<Uuid>, id: Uuid) -> Result<Option<BreadcrumbFull>> {
+    /// Current version of a breadcrumb the agent can read, under the same RLS as `get_breadcrumb_context_for`;
+    /// lets a cached view be checked without fetching the row
+    pub async fn get_breadcrumb_version_for(&self, owner_id: Uuid, agent_id: Option<Uuid>, id: Uuid) -> Result<Option<i32>> {
         let mut conn = self.pool.acquire().await?;
         set_rls(&mut conn, owner_id, agent_id).await?;
-        let rec = sqlx::query_as::<_, DbBreadcrumb>(
+        let version = sqlx::query_scalar::<_, i32>("select version from breadcrumbs where id = $1")
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await?;
+        Ok(version)
+    }
+
+    /// Context views for whichever of `ids` the agent can read, under the same RLS as
+    /// `get_breadcrumb_context_for`; unordered, missing ids are simply absent
+    pub async fn get_breadcrumbs_context_for(&self, owner_id: Uuid, agent_id: Option<Uuid>, ids: &[Uuid]) -> Result<Vec<BreadcrumbContextView>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, agent_id).await?;
+        let recs = sqlx::query_as::<_, DbBreadcrumb>(
             r#"select id, owner_id, title, description, semantic_version, context, tags, schema_name, llm_hints, visibility::text as visibility, sensitivity::text as sensitivity, version, checksum, ttl, ttl_type, ttl_config, read_count, ttl_source, created_at, updated_at, created_by, updated_by, size_bytes, embedding
+            from breadcrumbs where id = any($1)"#,
+        )
+        .bind(ids)
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(recs.into_iter().map(BreadcrumbContextView::from).collect())
+    }
+
+    /// The full record; the embedding vector itself only with `include_embedding`, `has_embedding`
+    /// and `embedding_dim` either way
+    #[tracing::instrument(name = "db", skip_all, fields(query = "get_breadcrumb_full_for"))]
+    pub async fn get_breadcrumb_full_for(&self, owner_id: Uuid, agent_id: Option<Uuid>, id: Uuid, include_embedding: bool) -> Result<Option<BreadcrumbFull>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, agent_id).await?;
+        let sql = format!(
+            r#"select id, owner_id, title, description, semantic_version, context, tags, schema_name, llm_hints, visibility::text as visibility, sensitivity::text as sensitivity, version, checksum, ttl, ttl_type, ttl_config, read_count, ttl_source, created_at, updated_at, created_by, updated_by, size_bytes, {}
             from breadcrumbs where id = $1"#,
+            embedding_columns(include_embedding)
+        );
+        let rec = sqlx::query_as::<_, DbBreadcrumb>(&sql)
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await?;
+        Ok(rec.map(BreadcrumbFull::from))
+    }
+
+    /// Current row as the event payload sees it; the outbox dispatcher reads with the owner only
+    #[tracing::instrument(name = "db", skip_all, fields(query = "get_breadcrumb_for"))]
+    pub async fn get_breadcrumb_for(&self, owner_id: Uuid, id: Uuid) -> Result<Option<Breadcrumb>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
+        let rec = sqlx::query_as::<_, DbBreadcrumb>(
+            r#"select id, owner_id, title, description, semantic_version, context, tags, schema_name, llm_hints, visibility::text as visibility, sensitivity::text as sensitivity, version, checksum, ttl, ttl_type, ttl_config, read_count, ttl_source, created_at, updated_at, created_by, updated_by, size_bytes, embedding
+            from breadcrumbs where id = $1 and owner_id = $2"#,
         )
         .bind(id)
+        .bind(owner_id)
         .fetch_optional(&mut *conn)
         .await?;
-        Ok(rec.map(|r| BreadcrumbFull {
-            id: r.id, owner_id: r.owner_id, title: r.title, description: r.description, semantic_version: r.semantic_version,
-            context: r.context, tags: r.tags, schema_name: r.schema_name, llm_hints: r.llm_hints,
-            visibility: match r.visibility.as_str() {"public"=>Visibility::Public, "team"=>Visibility::Team, _=>Visibility::Private},
-            sensitivity: match r.sensitivity.as_str() {"pii"=>Sensitivity::Pii, "secret"=>Sensitivity::Secret, _=>Sensitivity::Low},
-            version: r.version, checksum: r.checksum, ttl: r.ttl, ttl_type: r.ttl_type, ttl_config: r.ttl_config,
-            read_count: r.read_count, ttl_source: r.ttl_source, created_at: r.created_at, updated_at: r.updated_at,
-            created_by: r.created_by, updated_by: r.updated_by, size_bytes: r.size_bytes, embedding: r.embedding
-        }))
+        Ok(rec.map(Breadcrumb::from))
+    }
+
+    /// Full records for whichever of `ids` the agent can read; see `get_breadcrumbs_context_for`
+    /// and, for `include_embedding`, `get_breadcrumb_full_for`
+    pub async fn get_breadcrumbs_full_for(&self, owner_id: Uuid, agent_id: Option<Uuid>, ids: &[Uuid], include_embedding: bool) -> Result<Vec<BreadcrumbFull>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, agent_id).await?;
+        let sql = format!(
+            r#"select id, owner_id, title, description, semantic_version, context, tags, schema_name, llm_hints, visibility::text as visibility, sensitivity::text as sensitivity, version, checksum, ttl, ttl_type, ttl_config, read_count, ttl_source, created_at, updated_at, created_by, updated_by, size_bytes, {}
+            from breadcrumbs where id = any($1)"#,
+            embedding_columns(include_embedding)
+        );
+        let recs = sqlx::query_as::<_, DbBreadcrumb>(&sql)
+            .bind(ids)
+            .fetch_all(&mut *conn)
+            .await?;
+        Ok(recs.into_iter().map(BreadcrumbFull::from).collect())
     }
 
-    pub async fn create_selector_subscription(&self, owner_id: Uuid, agent_id: Uuid, selector: Selector) -> Result<SelectorSubscription> {
+    pub async fn create_selector_subscription(&self, owner_id: Uuid, agent_id: Uuid, selector: Selector, channels: &[DeliveryChannel], payload_version: Option<u16>, expires_at: Option<DateTime<Utc>>) -> Result<SelectorSubscription> {
         let mut conn = self.pool.acquire().await?;
         set_rls(&mut conn, owner_id, Some(agent_id)).await?;
         let rec = sqlx::query_as::<_, DbSelector>(
-            r#"insert into selector_subscriptions (owner_id, agent_id, selector)
-            values ($1,$2,$3) returning id, owner_id, agent_id, selector"#,
+            r#"insert into selector_subscriptions (owner_id, agent_id, selector, expires_at)
+            values ($1,$2,$3,$4) returning id, owner_id, agent_id, selector, expires_at"#,
         )
         .bind(owner_id)
         .bind(agent_id)
-        .bind(serde_json::to_value(&selector)?)
+        .bind(selector_to_db(&selector, channels, payload_version)?)
+        .bind(expires_at)
         .fetch_one(&mut *conn)
         .await?;
-        Ok(SelectorSubscription { id: rec.id, owner_id: rec.owner_id, agent_id: rec.agent_id, selector })
+        Ok(SelectorSubscription { id: rec.id, owner_id: rec.owner_id, agent_id: rec.agent_id, selector, channels: channels.to_vec(), expires_at: rec.expires_at, payload_version })
     }
 
-    pub async fn list_selector_subscriptions(&self, owner_id: Uuid, agent_id: Uuid) -> Result<Vec<SelectorSubscription>> {
+    /// The agent's selectors; expired ones (not yet removed by hygiene) only with `include_expired`
+    pub async fn list_selector_subscriptions(&self, owner_id: Uuid, agent_id: Uuid, include_expired: bool) -> Result<Vec<SelectorSubscription>> {
         let mut conn = self.pool.acquire().await?;
         set_rls(&mut conn, owner_id, Some(agent_id)).await?;
         let rows = sqlx::query_as::<_, DbSelector>(
-                r#"select id, owner_id, agent_id, selector from selector_subscriptions where owner_id = $1 and agent_id = $2"#,
+                r#"select id, owner_id, agent_id, selector, expires_at from selector_subscriptions
+                   where owner_id = $1 and agent_id = $2 and ($3 or expires_at is null or expires_at > now())"#,
             )
             .bind(owner_id)
             .bind(agent_id)
+            .bind(include_expired)
             .fetch_all(&mut *conn)
             .await?;
         let mut out = Vec::with_capacity(rows.len());
-        for r in rows { out.push(SelectorSubscription { id: r.id, owner_id: r.owner_id, agent_id: r.agent_id, selector: serde_json::from_value(r.selector)? }); }
+        for r in rows { out.push(r.try_into()?); }
         Ok(out)
     }
 
+    /// Delete the agent's selectors naming `tag` exactly in any_tags or all_tags; returns their ids
+    pub async fn delete_selectors_by_tag(&self, owner_id: Uuid, agent_id: Uuid, tag: &str) -> Result<Vec<Uuid>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, Some(agent_id)).await?;
+        let ids = sqlx::query_scalar::<_, Uuid>(
+            r#"delete from selector_subscriptions
+               where owner_id = $1 and agent_id = $2 and (selector->'any_tags' ? $3 or selector->'all_tags' ? $3)
+               returning id"#
+        )
+        .bind(owner_id)
+        .bind(agent_id)
+        .bind(tag)
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(ids)
+    }
+
     pub async fn list_selector_subscriptions_for_owner(&self, owner_id: Uuid) -> Result<Vec<SelectorSubscription>> {
         let mut conn = self.pool.acquire().await?;
         set_rls(&mut conn, owner_id, None).await?;
         let rows = sqlx::query_as::<_, DbSelector>(
-                r#"select id, owner_id, agent_id, selector from selector_subscriptions where owner_id = $1"#,
+                r#"select id, owner_id, agent_id, selector, expires_at from selector_subscriptions
+                   where owner_id = $1 and (expires_at is null or expires_at > now())"#,
             )
             .bind(owner_id)
             .fetch_all(&mut *conn)
             .await?;
         let mut out = Vec::with_capacity(rows.len());
-        for r in rows { out.push(SelectorSubscription { id: r.id, owner_id: r.owner_id, agent_id: r.agent_id, selector: serde_json::from_value(r.selector)? }); }
+        for r in rows { out.push(r.try_into()?); }
         Ok(out)
     }
 
-    pub async fn create_agent_webhook(&self, owner_id: Uuid, agent_id: Uuid, url: &str) -> Result<Uuid> {
+    /// Registering an existing URL again reactivates it, clears its failure streak and replaces its
+    /// template, payload version, selector and ordering. A `selector_id` must be one of the agent's selectors; otherwise nothing is written and
+    /// the result is None
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_agent_webhook(&self, owner_id: Uuid, agent_id: Uuid, url: &str, payload_template: Option<&str>, payload_version: Option<u16>, selector_id: Option<Uuid>, strict_ordering: bool) -> Result<Option<Uuid>> {
         let mut conn = self.pool.acquire().await?;
         set_rls(&mut conn, owner_id, Some(agent_id)).await?;
         let id = sqlx::query_scalar::<_, Uuid>(
-            r#"insert into agent_webhooks (agent_id, url) values ($1,$2)
-                on conflict (agent_id, url) do update set active = true
+            r#"insert into agent_webhooks (agent_id, url, payload_template, payload_version, selector_id, strict_ordering)
+                select $1, $2, $3, $4, $5, $7
+                 where $5::uuid is null or exists (select 1 from selector_subscriptions where id = $5 and owner_id = $6 and agent_id = $1)
+                on conflict (agent_id, url) do update set active = true, deactivated_reason = null, consecutive_failures = 0, payload_template = excluded.payload_template,
+                    payload_version = excluded.payload_version, selector_id = excluded.selector_id, strict_ordering = excluded.strict_ordering
                 returning id"#
         )
         .bind(agent_id)
         .bind(url)
-        .fetch_one(&mut *conn)
+        .bind(payload_template)
+        .bind(payload_version.map(|v| v as i16))
+        .bind(selector_id)
+        .bind(owner_id)
+        .bind(strict_ordering)
+        .fetch_optional(&mut *conn)
         .await?;
         Ok(id)
     }
 
-    pub async fn list_agent_webhooks(&self, owner_id: Uuid, agent_id: Uuid) -> Result<Vec<(Uuid, String)>> {
+    pub async fn list_agent_webhooks(&self, owner_id: Uuid, agent_id: Uuid) -> Result<Vec<AgentWebhook>> {
         let mut conn = self.pool.acquire().await?;
         set_rls(&mut conn, owner_id, Some(agent_id)).await?;
-        let rows = sqlx::query_as::<_, (Uuid, String)>(
-            r#"select id, url from agent_webhooks where agent_id = $1 and active = true"#
+        let rows = sqlx::query_as::<_, WebhookRow>(
+            r#"select id, url, payload_template, payload_version, selector_id, strict_ordering from agent_webhooks where agent_id = $1 and active = true"#
         )
         .bind(agent_id)
         .fetch_all(&mut *conn)
         .await?;
-        Ok(rows)
+        Ok(rows.into_iter().map(webhook_from_row).collect())
+    }
+
+    /// The agent's deactivated webhooks, each with why it was deactivated when that was recorded
+    pub async fn list_inactive_agent_webhooks(&self, owner_id: Uuid, agent_id: Uuid) -> Result<Vec<(AgentWebhook, Option<String>)>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, Some(agent_id)).await?;
+        let rows = sqlx::query_as::<_, (Uuid, String, Option<String>, Option<i16>, Option<Uuid>, bool, Option<String>)>(
+            r#"select id, url, payload_template, payload_version, selector_id, strict_ordering, deactivated_reason from agent_webhooks where agent_id = $1 and active = false"#
+        )
+        .bind(agent_id)
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(rows.into_iter().map(|(id, url, payload_template, payload_version, selector_id, strict_ordering, reason)| {
+            (webhook_from_row((id, url, payload_template, payload_version, selector_id, strict_ordering)), reason)
+        }).collect())
+    }
+
+    /// The agent's webhooks with their delivery stats: only active or only inactive ones when `active`
+    /// says so, and only those whose last delivery failed when `failing`
+    pub async fn list_agent_webhook_statuses(&self, owner_id: Uuid, agent_id: Uuid, active: Option<bool>, failing: bool, order: WebhookOrder) -> Result<Vec<WebhookStatus>> {
+        let order_by = match order {
+            WebhookOrder::CreatedAt => "created_at, id",
+            WebhookOrder::LastSuccessAt => "last_success_at desc nulls last, created_at",
+            WebhookOrder::LastFailureAt => "last_failure_at desc nulls last, created_at",
+            WebhookOrder::ConsecutiveFailures => "consecutive_failures desc, last_failure_at desc nulls last",
+            WebhookOrder::TotalDeliveries => "total_deliveries desc, created_at",
+        };
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, Some(agent_id)).await?;
+        let rows = sqlx::query_as::<_, WebhookStatusRow>(&format!(
+            r#"select id, url, payload_template, payload_version, selector_id, strict_ordering, active, deactivated_reason,
+                      created_at, last_success_at, last_failure_at, consecutive_failures, total_deliveries
+                 from agent_webhooks
+                where agent_id = $1 and ($2::boolean is null or active = $2) and (not $3 or consecutive_failures > 0)
+                order by {}"#,
+            order_by
+        ))
+        .bind(agent_id)
+        .bind(active)
+        .bind(failing)
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(rows.into_iter().map(|(id, url, payload_template, payload_version, selector_id, strict_ordering, active, deactivated_reason, created_at, last_success_at, last_failure_at, consecutive_failures, total_deliveries)| WebhookStatus {
+            webhook: webhook_from_row((id, url, payload_template, payload_version, selector_id, strict_ordering)),
+            active,
+            deactivated_reason,
+            created_at,
+            last_success_at,
+            last_failure_at,
+            consecutive_failures,
+            total_deliveries,
+        }).collect())
+    }
+
+    /// Count a finished delivery (delivered, or failed after its retries) toward the webhook's stats.
+    /// With `disable_after` > 0, the failure that makes that many in a row deactivates the webhook;
+    /// that returns the streak's length, anything else None
+    pub async fn record_webhook_outcome(&self, owner_id: Uuid, agent_id: Uuid, webhook_id: Uuid, delivered: bool, disable_after: u32) -> Result<Option<i32>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, Some(agent_id)).await?;
+        let row = sqlx::query_as::<_, (bool, i32)>(
+            r#"with hook as (select id, active from agent_webhooks where id = $1 and agent_id = $2 for update)
+               update agent_webhooks w
+                  set total_deliveries = w.total_deliveries + 1,
+                      last_success_at = case when $3 then now() else w.last_success_at end,
+                      last_failure_at = case when $3 then w.last_failure_at else now() end,
+                      consecutive_failures = case when $3 then 0 else w.consecutive_failures + 1 end,
+                      active = w.active and ($3 or $4 = 0 or w.consecutive_failures + 1 < $4),
+                      deactivated_reason = case when w.active and not $3 and $4 > 0 and w.consecutive_failures + 1 >= $4
+                          then 'disabled after ' || (w.consecutive_failures + 1) || ' consecutive failed deliveries'
+                          else w.deactivated_reason end
+                 from hook
+                where w.id = hook.id
+               returning hook.active and not w.active, w.consecutive_failures"#
+        )
+        .bind(webhook_id)
+        .bind(agent_id)
+        .bind(delivered)
+        .bind(disable_after.min(i32::MAX as u32) as i32)
+        .fetch_optional(&mut *conn)
+        .await?;
+        Ok(row.and_then(|(disabled, failures)| disabled.then_some(failures)))
+    }
+
+    /// One active webhook of `agent_id`
+    pub async fn get_agent_webhook(&self, owner_id: Uuid, agent_id: Uuid, webhook_id: Uuid) -> Result<Option<AgentWebhook>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, Some(agent_id)).await?;
+        let row = sqlx::query_as::<_, WebhookRow>(
+            r#"select id, url, payload_template, payload_version, selector_id, strict_ordering from agent_webhooks where id = $1 and agent_id = $2 and active = true"#
+        )
+        .bind(webhook_id)
+        .bind(agent_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+        Ok(row.map(webhook_from_row))
     }
 
     pub async fn deactivate_agent_webhook(&self, owner_id: Uuid, agent_id: Uuid, webhook_id: Uuid) -> Result<i64> {
@@ -278,6 +522,161 @@ impl Db {
         Ok(())
     }
 
+    /// Registering an existing URL again replaces its secret, filters and ordering, keeping its id
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_owner_webhook(&self, owner_id: Uuid, url: &str, secret: Option<&str>, schema_name: Option<&str>, any_tags: &[String], event_types: &[String], strict_ordering: bool) -> Result<OwnerWebhook> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
+        let row = sqlx::query_as::<_, OwnerWebhookRow>(
+            r#"insert into owner_webhooks (owner_id, url, secret, schema_name, any_tags, event_types, strict_ordering)
+               values ($1,$2,$3,$4,$5,$6,$7)
+               on conflict (owner_id, url) do update set secret = excluded.secret, schema_name = excluded.schema_name,
+                   any_tags = excluded.any_tags, event_types = excluded.event_types, strict_ordering = excluded.strict_ordering
+               returning id, url, secret, schema_name, any_tags, event_types, created_at, strict_ordering"#
+        )
+        .bind(owner_id)
+        .bind(url)
+        .bind(secret)
+        .bind(schema_name)
+        .bind(any_tags)
+        .bind(event_types)
+        .bind(strict_ordering)
+        .fetch_one(&mut *conn)
+        .await?;
+        Ok(owner_webhook_from_row(row))
+    }
+
+    /// The owner's webhooks, oldest first
+    pub async fn list_owner_webhooks(&self, owner_id: Uuid) -> Result<Vec<OwnerWebhook>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
+        let rows = sqlx::query_as::<_, OwnerWebhookRow>(
+            r#"select id, url, secret, schema_name, any_tags, event_types, created_at, strict_ordering from owner_webhooks where owner_id = $1 order by created_at, id"#
+        )
+        .bind(owner_id)
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(rows.into_iter().map(owner_webhook_from_row).collect())
+    }
+
+    pub async fn get_owner_webhook(&self, owner_id: Uuid, id: Uuid) -> Result<Option<OwnerWebhook>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
+        let row = sqlx::query_as::<_, OwnerWebhookRow>(
+            r#"select id, url, secret, schema_name, any_tags, event_types, created_at, strict_ordering from owner_webhooks where id = $1 and owner_id = $2"#
+        )
+        .bind(id)
+        .bind(owner_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+        Ok(row.map(owner_webhook_from_row))
+    }
+
+    /// Deletes the webhook with its delivery log and dead letters; false when there was none
+    pub async fn delete_owner_webhook(&self, owner_id: Uuid, id: Uuid) -> Result<bool> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
+        let res = sqlx::query(r#"delete from owner_webhooks where id = $1 and owner_id = $2"#)
+            .bind(id)
+            .bind(owner_id)
+            .execute(&mut *conn)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    pub async fn create_breadcrumb_share(&self, owner_id: Uuid, created_by: Uuid, breadcrumb_id: Uuid, view: &str, expires_at: DateTime<Utc>) -> Result<BreadcrumbShare> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, Some(created_by)).await?;
+        let row = sqlx::query_as::<_, BreadcrumbShareRow>(
+            r#"insert into breadcrumb_shares (id, owner_id, breadcrumb_id, created_by, view, expires_at)
+               values ($1,$2,$3,$4,$5,$6)
+               returning id, breadcrumb_id, created_by, view, expires_at, created_at, revoked_at"#
+        )
+        .bind(Uuid::new_v4())
+        .bind(owner_id)
+        .bind(breadcrumb_id)
+        .bind(created_by)
+        .bind(view)
+        .bind(expires_at)
+        .fetch_one(&mut *conn)
+        .await?;
+        Ok(breadcrumb_share_from_row(row))
+    }
+
+    /// The breadcrumb's share links, newest first, revoked and expired ones included
+    pub async fn list_breadcrumb_shares(&self, owner_id: Uuid, breadcrumb_id: Uuid) -> Result<Vec<BreadcrumbShare>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
+        let rows = sqlx::query_as::<_, BreadcrumbShareRow>(
+            r#"select id, breadcrumb_id, created_by, view, expires_at, created_at, revoked_at from breadcrumb_shares
+               where breadcrumb_id = $1 and owner_id = $2 order by created_at desc, id"#
+        )
+        .bind(breadcrumb_id)
+        .bind(owner_id)
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(rows.into_iter().map(breadcrumb_share_from_row).collect())
+    }
+
+    pub async fn get_breadcrumb_share(&self, owner_id: Uuid, id: Uuid) -> Result<Option<BreadcrumbShare>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
+        let row = sqlx::query_as::<_, BreadcrumbShareRow>(
+            r#"select id, breadcrumb_id, created_by, view, expires_at, created_at, revoked_at from breadcrumb_shares
+               where id = $1 and owner_id = $2"#
+        )
+        .bind(id)
+        .bind(owner_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+        Ok(row.map(breadcrumb_share_from_row))
+    }
+
+    /// Revoke one of the breadcrumb's share links; revoking it again keeps the first revoked_at.
+    /// None when the breadcrumb has no such link
+    pub async fn revoke_breadcrumb_share(&self, owner_id: Uuid, breadcrumb_id: Uuid, id: Uuid) -> Result<Option<BreadcrumbShare>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
+        let row = sqlx::query_as::<_, BreadcrumbShareRow>(
+            r#"update breadcrumb_shares set revoked_at = coalesce(revoked_at, now())
+               where id = $1 and breadcrumb_id = $2 and owner_id = $3
+               returning id, breadcrumb_id, created_by, view, expires_at, created_at, revoked_at"#
+        )
+        .bind(id)
+        .bind(breadcrumb_id)
+        .bind(owner_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+        Ok(row.map(breadcrumb_share_from_row))
+    }
+
+    /// Set (or with None clear) the agent's active session tag; false when the agent isn't registered under `owner_id`
+    pub async fn set_agent_session(&self, owner_id: Uuid, agent_id: Uuid, session_tag: Option<&str>) -> Result<bool> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, Some(agent_id)).await?;
+        let res = sqlx::query(
+            r#"update agents set active_session = $3, active_session_at = case when $3::text is null then null else now() end
+               where id = $1 and owner_id = $2"#
+        )
+        .bind(agent_id)
+        .bind(owner_id)
+        .bind(session_tag)
+        .execute(&mut *conn)
+        .await?;
+        Ok(res.rows_affected() == 1)
+    }
+
+    pub async fn agent_session(&self, owner_id: Uuid, agent_id: Uuid) -> Result<Option<String>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, Some(agent_id)).await?;
+        let session = sqlx::query_scalar::<_, Option<String>>("select active_session from agents where id = $1 and owner_id = $2")
+            .bind(agent_id)
+            .bind(owner_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+        Ok(session.flatten())
+    }
+
     // Secrets: create (expects caller to supply enc_blob and dek_encrypted)
     pub async fn create_secret(&self, owner_id: Uuid, name: &str, scope_type: &str, scope_id: Option<Uuid>, enc_blob: &[u8], dek_encrypted: &[u8], kek_id: &str) -> Result<Uuid> {
         let mut conn = self.pool.acquire().await?;
@@ -335,60 +734,693 @@ impl Db {
         Ok(())
     }
 
-    pub async fn enqueue_webhook_dlq(&self, owner_id: Uuid, agent_id: Uuid, url: &str, payload: &serde_json::Value, last_error: &str) -> Result<()> {
+    pub async fn set_breadcrumb_title_embedding(&self, owner_id: Uuid, agent_id: Option<Uuid>, id: Uuid, embedding: Vec<f32>) -> Result<()> {
         let mut conn = self.pool.acquire().await?;
-        set_rls(&mut conn, owner_id, Some(agent_id)).await?;
+        set_rls(&mut conn, owner_id, agent_id).await?;
         sqlx::query(
-            r#"insert into webhook_dlq (owner_id, agent_id, url, payload, last_error) values ($1,$2,$3,$4,$5)"#
+            r#"update breadcrumbs set title_embedding = $2 where id = $1"#
         )
-        .bind(owner_id)
-        .bind(agent_id)
-        .bind(url)
-        .bind(payload)
-        .bind(last_error)
+        .bind(id)
+        .bind(Vector::from(embedding))
         .execute(&mut *conn)
         .await?;
         Ok(())
     }
 
-    pub async fn purge_expired_for_owner(&self, owner_id: Uuid) -> Result<i64> {
+    /// Drop both embeddings of one breadcrumb, e.g. after its sensitivity was raised past the embedding limit
+    pub async fn clear_breadcrumb_embeddings(&self, owner_id: Uuid, agent_id: Option<Uuid>, id: Uuid) -> Result<()> {
         let mut conn = self.pool.acquire().await?;
-        set_rls(&mut conn, owner_id, None).await?;
-        let res = sqlx::query(r#"delete from breadcrumbs where owner_id = $1 and ttl is not null and ttl < now()"#)
+        set_rls(&mut conn, owner_id, agent_id).await?;
+        sqlx::query("update breadcrumbs set embedding = null, title_embedding = null where id = $1 and owner_id = $2")
+            .bind(id)
             .bind(owner_id)
             .execute(&mut *conn)
             .await?;
-        Ok(res.rows_affected() as i64)
+        sqlx::query("delete from breadcrumb_embeddings where breadcrumb_id = $1 and owner_id = $2")
+            .bind(id)
+            .bind(owner_id)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
     }
 
-    pub async fn list_webhook_dlq(&self, owner_id: Uuid) -> Result<Vec<(Uuid, Uuid, String, serde_json::Value, Option<String>, DateTime<Utc>)>> {
+    /// Null both embeddings of every owner breadcrumb more sensitive than `max`, and drop their other
+    /// models' vectors, in batches of `batch_size`; returns how many rows were cleared
+    pub async fn clear_embeddings_above_sensitivity(&self, owner_id: Uuid, max: &Sensitivity, batch_size: i64) -> Result<u64> {
         let mut conn = self.pool.acquire().await?;
         set_rls(&mut conn, owner_id, None).await?;
-        let rows = sqlx::query_as::<_, (Uuid, Uuid, String, JsonValue, Option<String>, DateTime<Utc>)>(
-            r#"select id, agent_id, url, payload, last_error, created_at from webhook_dlq where owner_id=$1 order by created_at desc"#
+        sqlx::query(
+            r#"delete from breadcrumb_embeddings e using breadcrumbs b
+               where e.breadcrumb_id = b.id and b.owner_id = $1 and b.sensitivity > $2::sensitivity"#
         )
         .bind(owner_id)
-        .fetch_all(&mut *conn)
+        .bind(max.as_str())
+        .execute(&mut *conn)
         .await?;
-        Ok(rows)
+        drop(conn);
+        let mut total = 0u64;
+        loop {
+            let mut conn = self.pool.acquire().await?;
+            set_rls(&mut conn, owner_id, None).await?;
+            let res = sqlx::query(
+                r#"update breadcrumbs set embedding = null, title_embedding = null
+                   where id in (
+                     select id from breadcrumbs
+                     where owner_id = $1 and sensitivity > $2::sensitivity
+                       and (embedding is not null or title_embedding is not null)
+                     limit $3
+                   )"#
+            )
+            .bind(owner_id)
+            .bind(max.as_str())
+            .bind(batch_size)
+            .execute(&mut *conn)
+            .await?;
+            total += res.rows_affected();
+            if res.rows_affected() < batch_size as u64 { break; }
+        }
+        Ok(total)
     }
 
-    pub async fn get_webhook_dlq(&self, owner_id: Uuid, id: Uuid) -> Result<Option<(Uuid, Uuid, String, serde_json::Value)>> {
+    /// The owner's breadcrumbs without a content (or, with `title`, title) embedding, by id after
+    /// `after_id`, so a backfill can page through them even when it leaves some unembedded
+    pub async fn list_breadcrumbs_missing_embedding(&self, owner_id: Uuid, agent_id: Option<Uuid>, title: bool, after_id: Option<Uuid>, limit: i64) -> Result<Vec<Breadcrumb>> {
         let mut conn = self.pool.acquire().await?;
-        set_rls(&mut conn, owner_id, None).await?;
-        let row = sqlx::query_as::<_, (Uuid, Uuid, String, JsonValue)>(
-            r#"select id, agent_id, url, payload from webhook_dlq where id=$1 and owner_id=$2"#
+        set_rls(&mut conn, owner_id, agent_id).await?;
+        let sql = format!(
+            r#"select id, owner_id, title, description, semantic_version, context, tags, schema_name, llm_hints, visibility::text as visibility, sensitivity::text as sensitivity, version, checksum, ttl, ttl_type, ttl_config, read_count, ttl_source, created_at, updated_at, created_by, updated_by, size_bytes, embedding
+            from breadcrumbs
+            where owner_id = $1 and {} is null and context_encrypted is null and ($2::uuid is null or id > $2)
+            order by id
+            limit $3"#,
+            if title { "title_embedding" } else { "embedding" },
+        );
+        let recs = sqlx::query_as::<_, DbBreadcrumb>(&sql)
+            .bind(owner_id)
+            .bind(after_id)
+            .bind(limit)
+            .fetch_all(&mut *conn)
+            .await?;
+        Ok(recs.into_iter().map(Breadcrumb::from).collect())
+    }
+
+    /// Store `model`'s vector of one breadcrumb, replacing an earlier one; for models other than
+    /// COLUMN_EMBEDDING_MODEL, whose vectors go in breadcrumbs.embedding
+    #[tracing::instrument(name = "db", skip_all, fields(query = "set_breadcrumb_model_embedding"))]
+    pub async fn set_breadcrumb_model_embedding(&self, owner_id: Uuid, agent_id: Option<Uuid>, id: Uuid, model: &str, embedding: Vec<f32>) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, agent_id).await?;
+        sqlx::query(
+            r#"insert into breadcrumb_embeddings (owner_id, breadcrumb_id, model, embedding)
+               select owner_id, id, $3, $4 from breadcrumbs where id = $1 and owner_id = $2
+               on conflict (breadcrumb_id, model) do update set embedding = excluded.embedding, created_at = now()"#
         )
         .bind(id)
         .bind(owner_id)
-        .fetch_optional(&mut *conn)
+        .bind(model)
+        .bind(Vector::from(embedding))
+        .execute(&mut *conn)
         .await?;
-        Ok(row)
+        Ok(())
     }
 
-    pub async fn delete_webhook_dlq(&self, owner_id: Uuid, id: Uuid) -> Result<i64> {
+    /// Like list_breadcrumbs_missing_embedding, for rows without a vector from `model` in breadcrumb_embeddings
+    pub async fn list_breadcrumbs_missing_model_embedding(&self, owner_id: Uuid, agent_id: Option<Uuid>, model: &str, after_id: Option<Uuid>, limit: i64) -> Result<Vec<Breadcrumb>> {
         let mut conn = self.pool.acquire().await?;
-        set_rls(&mut conn, owner_id, None).await?;
+        set_rls(&mut conn, owner_id, agent_id).await?;
+        let recs = sqlx::query_as::<_, DbBreadcrumb>(
+            r#"select id, owner_id, title, description, semantic_version, context, tags, schema_name, llm_hints, visibility::text as visibility, sensitivity::text as sensitivity, version, checksum, ttl, ttl_type, ttl_config, read_count, ttl_source, created_at, updated_at, created_by, updated_by, size_bytes, embedding
+            from breadcrumbs b
+            where owner_id = $1 and context_encrypted is null and ($3::uuid is null or id > $3)
+              and not exists (select 1 from breadcrumb_embeddings e where e.breadcrumb_id = b.id and e.model = $2)
+            order by id
+            limit $4"#
+        )
+        .bind(owner_id)
+        .bind(model)
+        .bind(after_id)
+        .bind(limit)
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(recs.into_iter().map(Breadcrumb::from).collect())
+    }
+
+    /// Models with vectors of the owner's breadcrumbs: COLUMN_EMBEDDING_MODEL first, then the
+    /// breadcrumb_embeddings ones by name
+    pub async fn list_embedding_models(&self, owner_id: Uuid) -> Result<Vec<String>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
+        let mut models = vec![COLUMN_EMBEDDING_MODEL.to_string()];
+        models.extend(sqlx::query_scalar::<_, String>(
+            "select distinct model from breadcrumb_embeddings where owner_id = $1 and model <> $2 order by model"
+        )
+        .bind(owner_id)
+        .bind(COLUMN_EMBEDDING_MODEL)
+        .fetch_all(&mut *conn)
+        .await?);
+        Ok(models)
+    }
+
+    /// How many of the owner's breadcrumbs embedded by any model `model` has a vector for
+    pub async fn embedding_coverage(&self, owner_id: Uuid, model: &str) -> Result<EmbeddingCoverage> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
+        let embedded = EmbeddingModelConfig { active_model: model.to_string() }.vector_sql().embedded;
+        let (embedded, eligible) = sqlx::query_as::<_, (i64, i64)>(&format!(
+            r#"select count(*) filter (where {}), count(*)
+               from breadcrumbs
+               where owner_id = $1
+                 and (embedding is not null or exists (select 1 from breadcrumb_embeddings e where e.breadcrumb_id = breadcrumbs.id))"#,
+            embedded
+        ))
+        .bind(owner_id)
+        .fetch_one(&mut *conn)
+        .await?;
+        let ratio = if eligible == 0 { 1.0 } else { embedded as f64 / eligible as f64 };
+        Ok(EmbeddingCoverage { model: model.to_string(), embedded, eligible, ratio })
+    }
+
+    pub async fn enqueue_webhook_dlq(&self, owner_id: Uuid, agent_id: Uuid, url: &str, payload: &serde_json::Value, last_error: &str, last_status: Option<i32>) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, Some(agent_id)).await?;
+        sqlx::query(
+            r#"insert into webhook_dlq (owner_id, agent_id, url, payload, last_error, last_status) values ($1,$2,$3,$4,$5,$6)"#
+        )
+        .bind(owner_id)
+        .bind(agent_id)
+        .bind(url)
+        .bind(payload)
+        .bind(last_error)
+        .bind(last_status)
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Dead-letter a delivery to an owner webhook
+    pub async fn enqueue_owner_webhook_dlq(&self, owner_id: Uuid, owner_webhook_id: Uuid, url: &str, payload: &serde_json::Value, last_error: &str, last_status: Option<i32>) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
+        sqlx::query(
+            r#"insert into webhook_dlq (owner_id, owner_webhook_id, url, payload, last_error, last_status) values ($1,$2,$3,$4,$5,$6)"#
+        )
+        .bind(owner_id)
+        .bind(owner_webhook_id)
+        .bind(url)
+        .bind(payload)
+        .bind(last_error)
+        .bind(last_status)
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Claim a delivery row for (webhook, breadcrumb, version).
+    /// Returns the delivery id to use, or None if it was already delivered.
+    pub async fn begin_webhook_delivery(&self, owner_id: Uuid, webhook_id: Uuid, breadcrumb_id: Uuid, version: i32) -> Result<Option<Uuid>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
+        let row = sqlx::query_as::<_, (Uuid,)>(
+            r#"insert into webhook_deliveries (owner_id, webhook_id, breadcrumb_id, version)
+               values ($1,$2,$3,$4)
+               on conflict (webhook_id, breadcrumb_id, version)
+               do update set status = 'pending', completed_at = null
+               where webhook_deliveries.status <> 'delivered'
+               returning delivery_id"#
+        )
+        .bind(owner_id)
+        .bind(webhook_id)
+        .bind(breadcrumb_id)
+        .bind(version)
+        .fetch_optional(&mut *conn)
+        .await?;
+        Ok(row.map(|r| r.0))
+    }
+
+    /// `begin_webhook_delivery` for an owner webhook
+    pub async fn begin_owner_webhook_delivery(&self, owner_id: Uuid, owner_webhook_id: Uuid, breadcrumb_id: Uuid, version: i32) -> Result<Option<Uuid>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
+        let row = sqlx::query_as::<_, (Uuid,)>(
+            r#"insert into webhook_deliveries (owner_id, owner_webhook_id, breadcrumb_id, version)
+               values ($1,$2,$3,$4)
+               on conflict (owner_webhook_id, breadcrumb_id, version)
+               do update set status = 'pending', completed_at = null
+               where webhook_deliveries.status <> 'delivered'
+               returning delivery_id"#
+        )
+        .bind(owner_id)
+        .bind(owner_webhook_id)
+        .bind(breadcrumb_id)
+        .bind(version)
+        .fetch_optional(&mut *conn)
+        .await?;
+        Ok(row.map(|r| r.0))
+    }
+
+    /// Queue an event in the agent's inbox; false when that version of the breadcrumb is already there
+    pub async fn enqueue_inbox(&self, owner_id: Uuid, agent_id: Uuid, breadcrumb_id: Uuid, version: i32, event: &JsonValue) -> Result<bool> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
+        let res = sqlx::query(
+            r#"insert into agent_inbox (owner_id, agent_id, breadcrumb_id, version, event)
+               values ($1,$2,$3,$4,$5)
+               on conflict (agent_id, breadcrumb_id, version) do nothing"#
+        )
+        .bind(owner_id)
+        .bind(agent_id)
+        .bind(breadcrumb_id)
+        .bind(version)
+        .bind(event)
+        .execute(&mut *conn)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Lease up to `limit` of the agent's oldest unacknowledged entries for `lease_secs`. Entries
+    /// another poller holds are skipped rather than waited on, so concurrent pollers get disjoint
+    /// sets; an entry whose lease ran out is due again
+    pub async fn lease_inbox(&self, owner_id: Uuid, agent_id: Uuid, limit: i64, lease_secs: i64) -> Result<Vec<InboxEntry>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, Some(agent_id)).await?;
+        let rows = sqlx::query_as::<_, InboxRow>(
+            r#"with due as (
+                 select id from agent_inbox
+                 where owner_id = $1 and agent_id = $2 and acked_at is null
+                   and (leased_until is null or leased_until <= now())
+                 order by created_at, id
+                 limit $3
+                 for update skip locked
+               )
+               update agent_inbox i
+               set leased_until = now() + make_interval(secs => $4::float8), deliveries = i.deliveries + 1
+               from due where i.id = due.id
+               returning i.id, i.breadcrumb_id, i.version, i.event, i.created_at, i.leased_until, i.deliveries"#
+        )
+        .bind(owner_id)
+        .bind(agent_id)
+        .bind(limit)
+        .bind(lease_secs as f64)
+        .fetch_all(&mut *conn)
+        .await?;
+        let mut entries: Vec<InboxEntry> = rows.into_iter().map(inbox_entry_from_row).collect();
+        entries.sort_by_key(|e| (e.created_at, e.id));
+        Ok(entries)
+    }
+
+    /// Acknowledge inbox entries so they are never handed out again; returns how many were
+    /// pending. Ids of other agents, or already acked, are ignored
+    pub async fn ack_inbox(&self, owner_id: Uuid, agent_id: Uuid, ids: &[Uuid]) -> Result<u64> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, Some(agent_id)).await?;
+        let res = sqlx::query(
+            r#"update agent_inbox set acked_at = now(), leased_until = null
+               where owner_id = $1 and agent_id = $2 and id = any($3) and acked_at is null"#
+        )
+        .bind(owner_id)
+        .bind(agent_id)
+        .bind(ids)
+        .execute(&mut *conn)
+        .await?;
+        Ok(res.rows_affected())
+    }
+
+    /// Unacknowledged inbox entries per agent of the owner; agents with none are absent
+    pub async fn inbox_depths(&self, owner_id: Uuid) -> Result<std::collections::HashMap<Uuid, i64>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
+        let rows = sqlx::query_as::<_, (Uuid, i64)>(
+            "select agent_id, count(*) from agent_inbox where owner_id = $1 and acked_at is null group by agent_id"
+        )
+        .bind(owner_id)
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(rows.into_iter().collect())
+    }
+
+    /// Drop inbox entries acknowledged before `before`, for every owner
+    pub async fn prune_inbox(&self, before: DateTime<Utc>) -> Result<u64> {
+        let res = sqlx::query("delete from agent_inbox where acked_at < $1")
+            .bind(before)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected())
+    }
+
+    /// Mark the outbox event for (breadcrumb, version) published after a direct fanout.
+    /// Rows the dispatcher currently holds are skipped rather than waited on; it marks them itself.
+    pub async fn mark_breadcrumb_event_published(&self, breadcrumb_id: Uuid, version: i32) -> Result<u64> {
+        let res = sqlx::query(
+            r#"update breadcrumb_outbox set published_at = now()
+               where id in (
+                 select id from breadcrumb_outbox
+                 where breadcrumb_id = $1 and version = $2 and published_at is null
+                 for update skip locked
+               )"#
+        )
+        .bind(breadcrumb_id)
+        .bind(version)
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected())
+    }
+
+    /// Record the outcome of a delivery ('delivered' or 'failed')
+    pub async fn complete_webhook_delivery(&self, owner_id: Uuid, delivery_id: Uuid, status: &str, attempts: i32, last_error: Option<&str>) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
+        sqlx::query(
+            r#"update webhook_deliveries
+               set status = $2, attempts = attempts + $3, last_error = $4, completed_at = now()
+               where delivery_id = $1"#
+        )
+        .bind(delivery_id)
+        .bind(status)
+        .bind(attempts)
+        .bind(last_error)
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Keep why a delivery went out as the raw event instead of its rendered template
+    pub async fn record_webhook_template_error(&self, owner_id: Uuid, delivery_id: Uuid, error: &str) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
+        sqlx::query(r#"update webhook_deliveries set template_error = $2 where delivery_id = $1"#)
+            .bind(delivery_id)
+            .bind(error)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Whether `agent_id` created any breadcrumb carrying `session_tag`
+    pub async fn is_session_emitter(&self, owner_id: Uuid, agent_id: Uuid, session_tag: &str) -> Result<bool> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, Some(agent_id)).await?;
+        let row = sqlx::query_as::<_, (bool,)>(
+            r#"select exists(select 1 from breadcrumbs where owner_id = $1 and $2 = any(tags) and created_by = $3)"#
+        )
+        .bind(owner_id)
+        .bind(session_tag)
+        .bind(agent_id)
+        .fetch_one(&mut *conn)
+        .await?;
+        Ok(row.0)
+    }
+
+    /// Bulk TTL stamp for every breadcrumb carrying `session_tag`.
+    ///
+    /// TTL-only path: the context is unchanged, so version and history are not bumped.
+    /// Rows that already expire before `ttl` are left alone. Runs in batches of
+    /// `batch_size`, each its own statement/transaction, to keep lock time bounded.
+    pub async fn stamp_session_ttl(&self, owner_id: Uuid, session_tag: &str, ttl: DateTime<Utc>, batch_size: i64) -> Result<u64> {
+        let mut total = 0u64;
+        loop {
+            let mut conn = self.pool.acquire().await?;
+            set_rls(&mut conn, owner_id, None).await?;
+            let res = sqlx::query(
+                r#"update breadcrumbs set ttl = $3, ttl_type = 'datetime', ttl_source = 'session-closed'
+                   where id in (
+                     select id from breadcrumbs
+                     where owner_id = $1 and $2 = any(tags) and (ttl is null or ttl > $3)
+                     limit $4
+                   )"#
+            )
+            .bind(owner_id)
+            .bind(session_tag)
+            .bind(ttl)
+            .bind(batch_size)
+            .execute(&mut *conn)
+            .await?;
+            total += res.rows_affected();
+            if res.rows_affected() < batch_size as u64 { break; }
+        }
+        Ok(total)
+    }
+
+    /// Delete every breadcrumb carrying `session_tag`, in batches of `batch_size`
+    pub async fn purge_session(&self, owner_id: Uuid, session_tag: &str, batch_size: i64) -> Result<u64> {
+        let mut total = 0u64;
+        loop {
+            let mut conn = self.pool.acquire().await?;
+            set_rls(&mut conn, owner_id, None).await?;
+            let res = sqlx::query(
+                r#"delete from breadcrumbs
+                   where id in (select id from breadcrumbs where owner_id = $1 and $2 = any(tags) limit $3)"#
+            )
+            .bind(owner_id)
+            .bind(session_tag)
+            .bind(batch_size)
+            .execute(&mut *conn)
+            .await?;
+            total += res.rows_affected();
+            if res.rows_affected() < batch_size as u64 { break; }
+        }
+        Ok(total)
+    }
+
+    /// A page of the owner's sessions from session_stats; ties break on the session tag
+    pub async fn list_session_stats(&self, owner_id: Uuid, order: SessionOrder, limit: i64, offset: i64) -> Result<Vec<SessionStats>> {
+        let order_by = match order {
+            SessionOrder::LastActivity => "s.last_activity_at desc",
+            SessionOrder::BreadcrumbCount => "s.breadcrumb_count desc, s.last_activity_at desc",
+            SessionOrder::MessageCount => "s.message_count desc, s.last_activity_at desc",
+        };
+        // session_stats has no RLS; filter on owner_id like api_keys
+        let rows = sqlx::query_as::<_, (String, i64, i64, DateTime<Utc>, Vec<Uuid>)>(&format!(
+            r#"select s.session_tag, s.breadcrumb_count, s.message_count, s.last_activity_at,
+                      array(select a.agent_id from session_stat_agents a
+                            where a.owner_id = s.owner_id and a.session_tag = s.session_tag
+                            order by a.breadcrumb_count desc, a.agent_id)
+               from session_stats s
+               where s.owner_id = $1
+               order by {}, s.session_tag
+               limit $2 offset $3"#,
+            order_by
+        ))
+        .bind(owner_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(|(session_tag, breadcrumb_count, message_count, last_activity_at, agents)| SessionStats {
+            session_tag, breadcrumb_count, message_count, last_activity_at, agents,
+        }).collect())
+    }
+
+    /// Recompute the owner's session_stats from its breadcrumbs; returns how many sessions it has
+    pub async fn rebuild_session_stats(&self, owner_id: Uuid) -> Result<i64> {
+        // One statement, so the delete and the recount commit together
+        let sessions: i64 = sqlx::query_scalar("select session_stats_rebuild($1)")
+            .bind(owner_id)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(sessions)
+    }
+
+    /// Add a batch of access counts to breadcrumb_access, and the reads among them to read_count, in
+    /// one transaction. Counts for breadcrumbs deleted since are dropped; returns the rows written
+    pub async fn record_breadcrumb_access(&self, counts: &[AccessCount]) -> Result<u64> {
+        let mut by_owner: std::collections::BTreeMap<Uuid, Vec<&AccessCount>> = std::collections::BTreeMap::new();
+        for c in counts {
+            by_owner.entry(c.owner_id).or_default().push(c);
+        }
+        let mut written = 0;
+        let mut tx = self.pool.begin().await?;
+        for (owner_id, counts) in by_owner {
+            // Each owner's batch sees only its own breadcrumbs, so counts can't land on another tenant's
+            sqlx::query("select set_config('app.current_owner_id', $1, true)")
+                .bind(owner_id.to_string())
+                .execute(&mut *tx)
+                .await?;
+            let breadcrumb_ids: Vec<Uuid> = counts.iter().map(|c| c.breadcrumb_id).collect();
+            let agent_ids: Vec<Uuid> = counts.iter().map(|c| c.agent_id).collect();
+            let types: Vec<&str> = counts.iter().map(|c| c.access_type.as_str()).collect();
+            let days: Vec<NaiveDate> = counts.iter().map(|c| c.day).collect();
+            let ns: Vec<i64> = counts.iter().map(|c| c.count).collect();
+            let reads: Vec<i64> = counts.iter().map(|c| if c.access_type.is_read() { c.count } else { 0 }).collect();
+            written += sqlx::query(
+                r#"insert into breadcrumb_access (owner_id, breadcrumb_id, agent_id, access_type, day, count)
+                   select b.owner_id, a.breadcrumb_id, a.agent_id, a.access_type, a.day, sum(a.n)
+                   from unnest($1::uuid[], $2::uuid[], $3::text[], $4::date[], $5::bigint[]) as a(breadcrumb_id, agent_id, access_type, day, n)
+                   join breadcrumbs b on b.id = a.breadcrumb_id and b.owner_id = $6
+                   group by 1, 2, 3, 4, 5
+                   on conflict (breadcrumb_id, agent_id, access_type, day) do update set count = breadcrumb_access.count + excluded.count"#
+            )
+            .bind(&breadcrumb_ids)
+            .bind(&agent_ids)
+            .bind(&types)
+            .bind(&days)
+            .bind(&ns)
+            .bind(owner_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+            sqlx::query(
+                r#"update breadcrumbs b set read_count = coalesce(b.read_count, 0) + r.n
+                   from (select id, sum(n)::int as n from unnest($1::uuid[], $2::bigint[]) as a(id, n) group by id having sum(n) > 0) r
+                   where b.id = r.id and b.owner_id = $3"#
+            )
+            .bind(&breadcrumb_ids)
+            .bind(&reads)
+            .bind(owner_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(written)
+    }
+
+    /// The owner's access counts for one breadcrumb from `since` on, newest day first (breadcrumb_access
+    /// has no RLS; filtered on owner_id like session_stats)
+    pub async fn breadcrumb_access_for(&self, owner_id: Uuid, breadcrumb_id: Uuid, since: NaiveDate) -> Result<Vec<AccessCount>> {
+        let rows = sqlx::query_as::<_, (Uuid, Uuid, String, NaiveDate, i64)>(
+            r#"select breadcrumb_id, agent_id, access_type, day, count from breadcrumb_access
+               where owner_id = $1 and breadcrumb_id = $2 and day >= $3
+               order by day desc, access_type, count desc, agent_id"#
+        )
+        .bind(owner_id)
+        .bind(breadcrumb_id)
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().filter_map(|(breadcrumb_id, agent_id, access_type, day, count)| Some(AccessCount {
+            owner_id, breadcrumb_id, agent_id, access_type: AccessType::parse(&access_type)?, day, count,
+        })).collect())
+    }
+
+    /// The owner's `limit` breadcrumbs with the most `access_type` accesses from `since` on; ties go to
+    /// the most recently accessed
+    pub async fn top_accessed_breadcrumbs(&self, owner_id: Uuid, access_type: AccessType, since: NaiveDate, limit: i64) -> Result<Vec<TopBreadcrumb>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
+        let rows = sqlx::query_as::<_, (Uuid, String, Option<String>, i64, i64, NaiveDate)>(
+            r#"select a.breadcrumb_id, b.title, b.schema_name, sum(a.count)::bigint, count(distinct a.agent_id), max(a.day)
+               from breadcrumb_access a
+               join breadcrumbs b on b.id = a.breadcrumb_id
+               where a.owner_id = $1 and a.access_type = $2 and a.day >= $3
+               group by a.breadcrumb_id, b.title, b.schema_name
+               order by 4 desc, 6 desc, a.breadcrumb_id
+               limit $4"#
+        )
+        .bind(owner_id)
+        .bind(access_type.as_str())
+        .bind(since)
+        .bind(limit)
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(rows.into_iter().map(|(breadcrumb_id, title, schema_name, count, agents, last_day)| TopBreadcrumb {
+            breadcrumb_id, title, schema_name, count, agents, last_day,
+        }).collect())
+    }
+
+    /// Drop access counts of days before `before`, for every owner
+    pub async fn prune_breadcrumb_access(&self, before: NaiveDate) -> Result<u64> {
+        let res = sqlx::query("delete from breadcrumb_access where day < $1")
+            .bind(before)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected())
+    }
+
+    /// How many of the owner's breadcrumbs `filter` matches, with up to `sample` of them (newest first)
+    pub async fn count_purge_matches(&self, owner_id: Uuid, filter: &PurgeFilter, sample: i64) -> Result<(i64, Vec<(Uuid, String)>)> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
+        let count: i64 = sqlx::query_scalar(&format!("select count(*) from breadcrumbs where {}", PURGE_MATCH))
+            .bind(owner_id)
+            .bind(&filter.schema_name)
+            .bind(&filter.all_tags)
+            .bind(filter.created_before)
+            .bind(filter.created_after)
+            .bind(filter.created_by)
+            .fetch_one(&mut *conn)
+            .await?;
+        let sample = sqlx::query_as::<_, (Uuid, String)>(&format!("select id, title from breadcrumbs where {} order by created_at desc limit $7", PURGE_MATCH))
+            .bind(owner_id)
+            .bind(&filter.schema_name)
+            .bind(&filter.all_tags)
+            .bind(filter.created_before)
+            .bind(filter.created_after)
+            .bind(filter.created_by)
+            .bind(sample)
+            .fetch_all(&mut *conn)
+            .await?;
+        Ok((count, sample))
+    }
+
+    /// Delete up to `limit` of the owner's breadcrumbs matching `filter`, one transaction per
+    /// `batch_size`; callers check `filter.is_narrowing()` first
+    pub async fn purge_matching(&self, owner_id: Uuid, filter: &PurgeFilter, batch_size: i64, limit: i64) -> Result<Vec<DeletedBreadcrumb>> {
+        let mut deleted = Vec::new();
+        while (deleted.len() as i64) < limit {
+            let batch = batch_size.min(limit - deleted.len() as i64);
+            let mut conn = self.pool.acquire().await?;
+            set_rls(&mut conn, owner_id, None).await?;
+            let rows = sqlx::query_as::<_, (Uuid, String, Vec<String>, Option<String>, String, String, Option<Uuid>)>(&format!(
+                r#"delete from breadcrumbs where id in (select id from breadcrumbs where {} limit $7)
+                   returning id, title, tags, schema_name, visibility::text, sensitivity::text, created_by"#, PURGE_MATCH))
+                .bind(owner_id)
+                .bind(&filter.schema_name)
+                .bind(&filter.all_tags)
+                .bind(filter.created_before)
+                .bind(filter.created_after)
+                .bind(filter.created_by)
+                .bind(batch)
+                .fetch_all(&mut *conn)
+                .await?;
+            let done = (rows.len() as i64) < batch;
+            deleted.extend(rows.into_iter().map(|(id, title, tags, schema_name, visibility, sensitivity, created_by)| {
+                DeletedBreadcrumb { id, title, tags, schema_name, visibility, sensitivity, created_by }
+            }));
+            if done { break; }
+        }
+        Ok(deleted)
+    }
+
+    pub async fn purge_expired_for_owner(&self, owner_id: Uuid) -> Result<i64> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
+        let res = sqlx::query(r#"delete from breadcrumbs where owner_id = $1 and ttl is not null and ttl < now()"#)
+            .bind(owner_id)
+            .execute(&mut *conn)
+            .await?;
+        Ok(res.rows_affected() as i64)
+    }
+
+    /// Dead letters, newest first: id, agent_id, url, payload, last_error, last_status, created_at and
+    /// owner_webhook_id. Exactly one of agent_id and owner_webhook_id is set
+    pub async fn list_webhook_dlq(&self, owner_id: Uuid) -> Result<Vec<(Uuid, Option<Uuid>, String, serde_json::Value, Option<String>, Option<i32>, DateTime<Utc>, Option<Uuid>)>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
+        let rows = sqlx::query_as::<_, (Uuid, Option<Uuid>, String, JsonValue, Option<String>, Option<i32>, DateTime<Utc>, Option<Uuid>)>(
+            r#"select id, agent_id, url, payload, last_error, last_status, created_at, owner_webhook_id from webhook_dlq where owner_id=$1 order by created_at desc"#
+        )
+        .bind(owner_id)
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(rows)
+    }
+
+    /// id, agent_id, url, payload and owner_webhook_id
+    pub async fn get_webhook_dlq(&self, owner_id: Uuid, id: Uuid) -> Result<Option<(Uuid, Option<Uuid>, String, serde_json::Value, Option<Uuid>)>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
+        let row = sqlx::query_as::<_, (Uuid, Option<Uuid>, String, JsonValue, Option<Uuid>)>(
+            r#"select id, agent_id, url, payload, owner_webhook_id from webhook_dlq where id=$1 and owner_id=$2"#
+        )
+        .bind(id)
+        .bind(owner_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+        Ok(row)
+    }
+
+    pub async fn delete_webhook_dlq(&self, owner_id: Uuid, id: Uuid) -> Result<i64> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
         let res = sqlx::query(r#"delete from webhook_dlq where id=$1 and owner_id=$2"#)
             .bind(id)
             .bind(owner_id)
@@ -427,143 +1459,834 @@ impl Db {
         Ok(rows)
     }
 
-    pub async fn update_breadcrumb(&self, owner_id: Uuid, agent_id: Uuid, id: Uuid, expected_version: Option<i32>, u: BreadcrumbUpdate) -> Result<Breadcrumb> {
-        tracing::info!("🔧 DB: update_breadcrumb called for {} by agent {}", id, agent_id);
-        tracing::info!("🔧 DB: Update contains - title: {:?}, context: {}, tags: {:?}", 
-            u.title, u.context.is_some(), u.tags);
-        
+    /// Context stored for one history version; None if it was never written or has been pruned
+    pub async fn get_breadcrumb_history_version(&self, owner_id: Uuid, agent_id: Option<Uuid>, id: Uuid, version: i32) -> Result<Option<JsonValue>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, agent_id).await?;
+        let context = sqlx::query_scalar::<_, JsonValue>(
+            r#"select context from breadcrumb_history where breadcrumb_id = $1 and version = $2"#
+        )
+        .bind(id)
+        .bind(version)
+        .fetch_optional(&mut *conn)
+        .await?;
+        Ok(context)
+    }
+
+    /// The history version of `id` that was current at `ts`: the latest written at or before it, with
+    /// the next retained version. None if `id` had no version yet, or its history isn't visible to the agent
+    pub async fn get_breadcrumb_history_as_of(&self, owner_id: Uuid, agent_id: Option<Uuid>, id: Uuid, ts: DateTime<Utc>) -> Result<Option<HistoryAsOf>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, agent_id).await?;
+        let row = sqlx::query_as::<_, (i32, JsonValue, DateTime<Utc>, Option<i32>, Option<DateTime<Utc>>)>(
+            r#"select h.version, h.context, h.updated_at, n.version, n.updated_at
+               from (select version, context, updated_at from breadcrumb_history
+                     where breadcrumb_id = $1 and updated_at <= $2
+                     order by updated_at desc, version desc limit 1) h
+               left join lateral (select version, updated_at from breadcrumb_history
+                                  where breadcrumb_id = $1 and version > h.version
+                                  order by version limit 1) n on true"#
+        )
+        .bind(id)
+        .bind(ts)
+        .fetch_optional(&mut *conn)
+        .await?;
+        Ok(row.map(|(version, context, updated_at, next_version, superseded_at)| HistoryAsOf { version, context, updated_at, next_version, superseded_at }))
+    }
+
+    /// The sealed context of `id`, or of its history `version`, when it is encrypted; None for a
+    /// plaintext row or one the agent can't see
+    pub async fn get_encrypted_context(&self, owner_id: Uuid, agent_id: Option<Uuid>, id: Uuid, version: Option<i32>) -> Result<Option<EncryptedContext>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, agent_id).await?;
+        load_encrypted_context(&mut conn, id, version).await
+    }
+
+    /// Recompute the checksum of breadcrumb `id` and, with `history`, of each retained history
+    /// version (oldest first, after the live row); None if the breadcrumb isn't visible
+    pub async fn verify_breadcrumb_checksums(&self, owner_id: Uuid, agent_id: Option<Uuid>, id: Uuid, history: bool) -> Result<Option<Vec<ChecksumCheck>>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, agent_id).await?;
+        let Some(row) = sqlx::query_as::<_, StoredChecksumRow>(
+            "select id, version, context, context_encrypted, checksum, checksum_canonical from breadcrumbs where id = $1"
+        )
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await? else {
+            return Ok(None);
+        };
+        let mut checks = vec![check_checksum(ChecksumSource::Current, row)];
+        if history {
+            let rows = sqlx::query_as::<_, StoredChecksumRow>(
+                "select breadcrumb_id, version, context, context_encrypted, checksum, checksum_canonical from breadcrumb_history where breadcrumb_id = $1 order by version"
+            )
+            .bind(id)
+            .fetch_all(&mut *conn)
+            .await?;
+            checks.extend(rows.into_iter().map(|row| check_checksum(ChecksumSource::History, row)));
+        }
+        Ok(Some(checks))
+    }
+
+    /// Recompute the checksums of up to `limit` breadcrumbs (with `history`, also their history
+    /// versions): the owner's, or every tenant's for `None`. Pages in id order after `after`, or
+    /// with `sample` picks random rows and returns no cursor
+    pub async fn verify_checksums_batch(&self, owner_id: Option<Uuid>, after: Option<Uuid>, limit: i64, sample: bool, history: bool) -> Result<ChecksumScan> {
+        let mut conn = self.pool.acquire().await?;
+        if let Some(owner_id) = owner_id {
+            set_rls(&mut conn, owner_id, None).await?;
+        }
+        let rows = sqlx::query_as::<_, (Uuid, Uuid, i32, JsonValue, Option<Vec<u8>>, String, bool)>(
+            r#"select owner_id, id, version, context, context_encrypted, checksum, checksum_canonical from breadcrumbs
+               where ($1::uuid is null or owner_id = $1) and ($2::uuid is null or id > $2)
+               order by case when $4 then random() end, id
+               limit $3"#
+        )
+        .bind(owner_id)
+        .bind(if sample { None } else { after })
+        .bind(limit)
+        .bind(sample)
+        .fetch_all(&mut *conn)
+        .await?;
+        let next_after = if sample { None } else { rows.last().map(|r| r.1) };
+        let owners: std::collections::HashMap<Uuid, Uuid> = rows.iter().map(|r| (r.1, r.0)).collect();
+        let mut checks: Vec<ChecksumCheck> = rows.into_iter()
+            .map(|(_, id, version, context, ciphertext, stored, canonical)| check_checksum(ChecksumSource::Current, (id, version, context, ciphertext, stored, canonical)))
+            .collect();
+        if history && !owners.is_empty() {
+            let ids: Vec<Uuid> = owners.keys().copied().collect();
+            let rows = sqlx::query_as::<_, StoredChecksumRow>(
+                "select breadcrumb_id, version, context, context_encrypted, checksum, checksum_canonical from breadcrumb_history where breadcrumb_id = any($1) order by breadcrumb_id, version"
+            )
+            .bind(&ids)
+            .fetch_all(&mut *conn)
+            .await?;
+            checks.extend(rows.into_iter().map(|row| check_checksum(ChecksumSource::History, row)));
+        }
+        let unverifiable = checks.iter().filter(|c| c.status == ChecksumStatus::Unverifiable).count() as i64;
+        let mismatches = checks.into_iter()
+            .filter(|c| c.status == ChecksumStatus::Mismatch)
+            .map(|c| (owners[&c.breadcrumb_id], c))
+            .collect();
+        Ok(ChecksumScan { scanned: owners.len() as i64, mismatches, unverifiable, next_after })
+    }
+
+    /// Retained history versions, their total context size in bytes and the oldest retained version
+    pub async fn breadcrumb_history_size(&self, owner_id: Uuid, agent_id: Option<Uuid>, id: Uuid) -> Result<(i64, i64, Option<i32>)> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, agent_id).await?;
+        let row = sqlx::query_as::<_, (i64, i64, Option<i32>)>(
+            r#"select count(*), coalesce(sum(pg_column_size(context)), 0)::bigint, min(version)
+            from breadcrumb_history where breadcrumb_id = $1"#
+        )
+        .bind(id)
+        .fetch_one(&mut *conn)
+        .await?;
+        Ok(row)
+    }
+
+    /// Breadcrumb count, first created and last updated per schema_name visible to the agent;
+    /// `schema_name` narrows to one schema
+    pub async fn list_schema_usage(&self, owner_id: Uuid, agent_id: Option<Uuid>, schema_name: Option<&str>) -> Result<Vec<SchemaUsage>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, agent_id).await?;
+        let rows = sqlx::query_as::<_, (String, i64, DateTime<Utc>, DateTime<Utc>)>(
+            r#"select schema_name, count(*), min(created_at), max(updated_at)
+            from breadcrumbs
+            where schema_name is not null and ($1::text is null or schema_name = $1)
+            group by schema_name
+            order by schema_name"#
+        )
+        .bind(schema_name)
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(rows.into_iter().map(|(schema_name, count, first_seen, last_used)| SchemaUsage { schema_name, count, first_seen, last_used }).collect())
+    }
+
+    /// Most recently updated breadcrumb ids for a schema, visible to the agent
+    pub async fn sample_breadcrumb_ids(&self, owner_id: Uuid, agent_id: Option<Uuid>, schema_name: &str, limit: i64) -> Result<Vec<Uuid>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, agent_id).await?;
+        let ids = sqlx::query_scalar::<_, Uuid>(
+            r#"select id from breadcrumbs where schema_name = $1 order by updated_at desc limit $2"#
+        )
+        .bind(schema_name)
+        .bind(limit)
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(ids)
+    }
+
+    /// The owner's breadcrumbs updated at or after `since`, oldest first. With `after_id`, only rows
+    /// strictly after (`since`, `after_id`), so a page can resume from its last row
+    pub async fn list_breadcrumbs_updated_since(&self, owner_id: Uuid, agent_id: Option<Uuid>, since: DateTime<Utc>, after_id: Option<Uuid>, limit: i64) -> Result<Vec<Breadcrumb>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, agent_id).await?;
+        let recs = sqlx::query_as::<_, DbBreadcrumb>(
+            r#"select id, owner_id, title, description, semantic_version, context, tags, schema_name, llm_hints, visibility::text as visibility, sensitivity::text as sensitivity, version, checksum, ttl, ttl_type, ttl_config, read_count, ttl_source, created_at, updated_at, created_by, updated_by, size_bytes, embedding
+            from breadcrumbs
+            where owner_id = $1
+            and (updated_at > $2 or (updated_at = $2 and ($3::uuid is null or id > $3)))
+            order by updated_at, id
+            limit $4"#,
+        )
+        .bind(owner_id)
+        .bind(since)
+        .bind(after_id)
+        .bind(limit)
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(recs.into_iter().map(Breadcrumb::from).collect())
+    }
+
+    /// schema.def.v1 breadcrumbs visible to the agent
+    pub async fn list_schema_definitions(&self, owner_id: Uuid, agent_id: Option<Uuid>) -> Result<Vec<Breadcrumb>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, agent_id).await?;
+        let recs = sqlx::query_as::<_, DbBreadcrumb>(
+            r#"select id, owner_id, title, description, semantic_version, context, tags, schema_name, llm_hints, visibility::text as visibility, sensitivity::text as sensitivity, version, checksum, ttl, ttl_type, ttl_config, read_count, ttl_source, created_at, updated_at, created_by, updated_by, size_bytes, embedding
+            from breadcrumbs where schema_name = 'schema.def.v1' order by updated_at desc"#,
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(recs.into_iter().map(Breadcrumb::from).collect())
+    }
+
+    /// The newest template.v1 breadcrumb named `name` (`context.name`, else its title)
+    pub async fn find_template(&self, owner_id: Uuid, agent_id: Option<Uuid>, name: &str) -> Result<Option<Breadcrumb>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, agent_id).await?;
+        let rec = sqlx::query_as::<_, DbBreadcrumb>(
+            r#"select id, owner_id, title, description, semantic_version, context, tags, schema_name, llm_hints, visibility::text as visibility, sensitivity::text as sensitivity, version, checksum, ttl, ttl_type, ttl_config, read_count, ttl_source, created_at, updated_at, created_by, updated_by, size_bytes, embedding
+            from breadcrumbs where schema_name = 'template.v1' and coalesce(context->>'name', title) = $1
+            order by updated_at desc limit 1"#,
+        )
+        .bind(name)
+        .fetch_optional(&mut *conn)
+        .await?;
+        Ok(rec.map(Breadcrumb::from))
+    }
+
+    #[tracing::instrument(name = "db", skip_all, fields(query = "update_breadcrumb"))]
+    pub async fn update_breadcrumb(&self, owner_id: Uuid, agent_id: Uuid, id: Uuid, expected_version: Option<i32>, u: BreadcrumbUpdate) -> Result<Breadcrumb> {
+        tracing::info!("🔧 DB: update_breadcrumb called for {} by agent {}", id, agent_id);
+        tracing::info!("🔧 DB: Update contains - title: {:?}, context: {}, tags: {:?}", 
+            u.title, u.context.is_some(), u.tags);
+        
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, Some(agent_id)).await?;
+        self.update_breadcrumb_conn(&mut conn, owner_id, agent_id, id, expected_version, u, None).await
+    }
+
+    /// Like update_breadcrumb, storing `sealed` in place of any `u.context`. A row that becomes
+    /// encrypted loses its embeddings and extracted entities; once encrypted, a new context must
+    /// come sealed (a plaintext one is `DbError::Invalid`)
+    #[tracing::instrument(name = "db", skip_all, fields(query = "update_encrypted_breadcrumb"))]
+    pub async fn update_encrypted_breadcrumb(&self, owner_id: Uuid, agent_id: Uuid, id: Uuid, expected_version: Option<i32>, u: BreadcrumbUpdate, sealed: EncryptedContext) -> Result<Breadcrumb> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, Some(agent_id)).await?;
+        self.update_breadcrumb_conn(&mut conn, owner_id, agent_id, id, expected_version, u, Some(sealed)).await
+    }
+
+    /// Add and remove tags in one UPDATE against the stored array, so concurrent writers touching
+    /// different tags all keep their changes. Added tags go at the end, duplicates are dropped, and
+    /// a result over `max_tags` is rolled back as `DbError::Invalid`. Bumps the version and appends
+    /// a history row (context unchanged) like any update
+    #[tracing::instrument(name = "db", skip_all, fields(query = "update_breadcrumb_tags"))]
+    pub async fn update_breadcrumb_tags(&self, owner_id: Uuid, agent_id: Uuid, id: Uuid, add: &[String], remove: &[String], max_tags: usize) -> Result<Breadcrumb> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, Some(agent_id)).await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        // array_cat, then the removed tags and duplicates filtered out in first-seen order
+        let rec = sqlx::query_as::<_, DbBreadcrumb>(
+            r#"update breadcrumbs set
+                 tags = array(select t from unnest(array_cat(tags, $2::text[])) with ordinality as u(t, ord)
+                              where t <> all($3::text[]) group by t order by min(ord)),
+                 version = version + 1, updated_at = now(), updated_by = $4
+               where id = $1
+               returning id, owner_id, title, description, semantic_version, context, tags, schema_name, llm_hints, visibility::text as visibility, sensitivity::text as sensitivity, version, checksum, ttl, ttl_type, ttl_config, read_count, ttl_source, created_at, updated_at, created_by, updated_by, size_bytes, embedding"#
+        )
+        .bind(id)
+        .bind(add)
+        .bind(remove)
+        .bind(agent_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| DbError::NotFound("breadcrumb".to_string()))?;
+        if rec.tags.len() > max_tags {
+            return Err(DbError::Invalid(format!("a breadcrumb carries at most {} tags; this would leave {}", max_tags, rec.tags.len())));
+        }
+        sqlx::query(r#"insert into breadcrumb_history (breadcrumb_id, version, context, updated_at, updated_by, checksum, context_encrypted, context_dek_encrypted, context_kek_id, checksum_canonical)
+                       select id, version, context, updated_at, updated_by, checksum, context_encrypted, context_dek_encrypted, context_kek_id, checksum_canonical from breadcrumbs where id = $1"#)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        record_breadcrumb_event(&mut *tx, owner_id, rec.id, rec.version, "updated").await?;
+        tx.commit().await?;
+        Ok(rec.into())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn update_breadcrumb_conn(&self, conn: &mut PgConnection, owner_id: Uuid, agent_id: Uuid, id: Uuid, expected_version: Option<i32>, u: BreadcrumbUpdate, sealed: Option<EncryptedContext>) -> Result<Breadcrumb> {
+        // Lock the row so the version check, update and history append see no concurrent writer
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        // Fetch current
+        let cur = sqlx::query_as::<_, DbBreadcrumb>(
+            r#"select id, owner_id, title, description, semantic_version, context, tags, schema_name, llm_hints, visibility::text as visibility, sensitivity::text as sensitivity, version, checksum, ttl, ttl_type, ttl_config, read_count, ttl_source, created_at, updated_at, created_by, updated_by, size_bytes, embedding from breadcrumbs where id = $1 for update"#
+        )
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| DbError::NotFound("breadcrumb".to_string()))?;
+        
+        tracing::info!("🔧 DB: Current breadcrumb version={}, context_preview={}", 
+            cur.version, 
+            serde_json::to_string(&cur.context).unwrap_or_default().chars().take(100).collect::<String>()
+        );
+        
+        if let Some(ev) = expected_version { 
+            if cur.version != ev { 
+                tracing::warn!("🔧 DB: Version mismatch! Expected: {}, Current: {}", ev, cur.version);
+                return Err(VersionMismatch {
+                    expected_version: ev,
+                    current_version: cur.version,
+                    updated_at: cur.updated_at,
+                    updated_by: cur.updated_by,
+                    context: cur.context,
+                }.into());
+            } 
+        }
+
+        let current_sealed = load_encrypted_context(&mut *tx, id, None).await?;
+        if current_sealed.is_some() && sealed.is_none() && u.context.is_some() {
+            return Err(DbError::Invalid("breadcrumb context is encrypted; a new context must be encrypted too".to_string()));
+        }
+        let newly_encrypted = current_sealed.is_none() && sealed.is_some();
+        let sealed = sealed.or(current_sealed);
+
+        let new_title = u.title.unwrap_or(cur.title);
+        let new_description = u.description.or(cur.description);              // NEW
+        let new_semantic_version = u.semantic_version.or(cur.semantic_version); // NEW
+        let new_context = if sealed.is_some() { EncryptedContext::stub() } else { u.context.unwrap_or(cur.context) };
+        let new_tags = u.tags.unwrap_or(cur.tags);
+        let new_schema = u.schema_name.or(cur.schema_name);
+        let new_llm_hints = u.llm_hints.or(cur.llm_hints);                    // NEW
+        let new_visibility = u.visibility.map(|v| visibility_to_db(&v)).unwrap_or(cur.visibility.as_str());
+        let new_sensitivity = u.sensitivity.map(|s| sensitivity_to_db(&s)).unwrap_or(cur.sensitivity.as_str());
+        let new_ttl = u.ttl.or(cur.ttl);
+        let new_ttl_type = u.ttl_type.or(cur.ttl_type);
+        let new_ttl_config = u.ttl_config.or(cur.ttl_config);
+        let new_ttl_source = u.ttl_source.or(cur.ttl_source);
+        let (new_checksum, new_size) = stored_checksum_and_size(&new_context, sealed.as_ref())?;
+        let new_version = cur.version + 1;
+        
+        tracing::info!("🔧 DB: New values - version: {} -> {}, context_size: {} bytes, context_preview: {}", 
+            cur.version, new_version, new_size,
+            serde_json::to_string(&new_context).unwrap_or_default().chars().take(100).collect::<String>()
+        );
+
+        let rec = sqlx::query_as::<_, DbBreadcrumb>(
+            r#"update breadcrumbs set title=$2, description=$3, semantic_version=$4, context=$5, tags=$6, schema_name=$7, llm_hints=$8,
+                 visibility=$9::visibility, sensitivity=$10::sensitivity, version=$11, checksum=$12,
+                 ttl=$13, ttl_type=$14, ttl_config=$15, ttl_source=$16, updated_at=now(), updated_by=$17, size_bytes=$18,
+                 context_encrypted=$19, context_dek_encrypted=$20, context_kek_id=$21, checksum_canonical=true
+               where id=$1 returning id, owner_id, title, description, semantic_version, context, tags, schema_name, llm_hints, visibility::text as visibility, sensitivity::text as sensitivity, version, checksum, ttl, ttl_type, ttl_config, read_count, ttl_source, created_at, updated_at, created_by, updated_by, size_bytes, embedding"#
+        )
+        .bind(id)
+        .bind(&new_title)
+        .bind(&new_description)             // NEW
+        .bind(&new_semantic_version)        // NEW
+        .bind(&new_context)
+        .bind(&new_tags)
+        .bind(&new_schema)
+        .bind(&new_llm_hints)               // NEW
+        .bind(new_visibility)
+        .bind(new_sensitivity)
+        .bind(new_version)
+        .bind(&new_checksum)
+        .bind(new_ttl)
+        .bind(&new_ttl_type)
+        .bind(&new_ttl_config)
+        .bind(&new_ttl_source)
+        .bind(agent_id)
+        .bind(new_size)
+        .bind(sealed.as_ref().map(|s| s.ciphertext.as_slice()))
+        .bind(sealed.as_ref().map(|s| s.dek_encrypted.as_slice()))
+        .bind(sealed.as_ref().map(|s| s.kek_id.as_str()))
+        .fetch_one(&mut *tx)
+        .await?;
+        if newly_encrypted {
+            // Vectors and entities of the plaintext would outlive it
+            sqlx::query("update breadcrumbs set embedding = null, title_embedding = null, entity_keywords = '{}', entities = $2 where id = $1")
+                .bind(id)
+                .bind(ExtractedEntities::default().to_entities_json(EXTRACTED_BY_ENCRYPTED))
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("delete from breadcrumb_embeddings where breadcrumb_id = $1")
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        
+        tracing::info!("🔧 DB: SQL UPDATE completed successfully! Returned version={}, context_preview={}", 
+            rec.version,
+            serde_json::to_string(&rec.context).unwrap_or_default().chars().take(100).collect::<String>()
+        );
+
+        // Append history
+        sqlx::query(r#"insert into breadcrumb_history (breadcrumb_id, version, context, updated_at, updated_by, checksum, context_encrypted, context_dek_encrypted, context_kek_id, checksum_canonical) values ($1,$2,$3, now(), $4, $5, $6, $7, $8, true)"#)
+            .bind(id)
+            .bind(new_version)
+            .bind(&new_context)
+            .bind(agent_id)
+            .bind(&new_checksum)
+            .bind(sealed.as_ref().map(|s| s.ciphertext.as_slice()))
+            .bind(sealed.as_ref().map(|s| s.dek_encrypted.as_slice()))
+            .bind(sealed.as_ref().map(|s| s.kek_id.as_str()))
+            .execute(&mut *tx)
+            .await?;
+        record_breadcrumb_event(&mut *tx, owner_id, rec.id, rec.version, "updated").await?;
+        tx.commit().await?;
+
+        Ok(rec.into())
+    }
+
+    /// Create or update the one live breadcrumb of `req.schema_name` keyed by `key_tags`, in one transaction.
+    ///
+    /// The row is found by its stored upsert key, or (for rows written before keys existed) by carrying
+    /// every key tag. When several match, the keyed or newest one is updated and the others are
+    /// expired (ttl = now, ttl_source `upsert-duplicate`) for hygiene to purge. A given `embedding`
+    /// replaces the stored one; a given `sealed` context is stored as by `create_encrypted_breadcrumb_for`.
+    #[tracing::instrument(name = "db", skip_all, fields(query = "upsert_breadcrumb_by_key"))]
+    pub async fn upsert_breadcrumb_by_key(&self, owner_id: Uuid, agent_id: Uuid, key_tags: &[String], req: BreadcrumbCreate, embedding: Option<Vec<f32>>, sealed: Option<EncryptedContext>) -> Result<UpsertedBreadcrumb> {
+        let schema_name = req.schema_name.clone().ok_or_else(|| DbError::Invalid("upsert needs a schema_name".to_string()))?;
+        let key = upsert_key(key_tags);
+        let mut key_tags: Vec<String> = key_tags.to_vec();
+        key_tags.sort();
+        key_tags.dedup();
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, Some(agent_id)).await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        // Serializes upserts of one key, including the create branch the row lock can't cover
+        sqlx::query("select pg_advisory_xact_lock(hashtextextended($1, 0))")
+            .bind(format!("{}|{}|{}", owner_id, schema_name, key))
+            .execute(&mut *tx)
+            .await?;
+        let matches: Vec<Uuid> = sqlx::query_scalar(
+            r#"select id from breadcrumbs
+               where owner_id = $1 and schema_name = $2
+                 and (upsert_key = $3 or (upsert_key is null and tags @> $4))
+                 and (ttl is null or ttl > now())
+               order by (upsert_key is not null) desc, updated_at desc, id
+               for update"#
+        )
+        .bind(owner_id)
+        .bind(&schema_name)
+        .bind(&key)
+        .bind(&key_tags)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let (breadcrumb, created) = match matches.first() {
+            Some(&id) => {
+                let update = BreadcrumbUpdate {
+                    title: Some(req.title),
+                    description: req.description,
+                    semantic_version: req.semantic_version,
+                    context: Some(req.context),
+                    tags: Some(req.tags),
+                    schema_name: None,
+                    llm_hints: req.llm_hints,
+                    visibility: req.visibility,
+                    sensitivity: req.sensitivity,
+                    ttl: req.ttl,
+                    ttl_type: req.ttl_type,
+                    ttl_config: req.ttl_config,
+                    ttl_source: req.ttl_source,
+                };
+                let bc = self.update_breadcrumb_conn(&mut *tx, owner_id, agent_id, id, None, update, sealed).await?;
+                if let Some(embedding) = embedding {
+                    sqlx::query("update breadcrumbs set embedding = $2 where id = $1")
+                        .bind(id)
+                        .bind(Vector::from(embedding))
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                (bc, false)
+            }
+            None => (self.create_breadcrumb_conn(&mut *tx, owner_id, Some(agent_id), req, embedding, None, sealed).await?, true),
+        };
+        // Any other holder of the key has expired (live ones matched above); release it
+        sqlx::query("update breadcrumbs set upsert_key = null where owner_id = $1 and schema_name = $2 and upsert_key = $3 and id <> $4")
+            .bind(owner_id)
+            .bind(&schema_name)
+            .bind(&key)
+            .bind(breadcrumb.id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("update breadcrumbs set upsert_key = $2 where id = $1 and upsert_key is distinct from $2")
+            .bind(breadcrumb.id)
+            .bind(&key)
+            .execute(&mut *tx)
+            .await?;
+        let superseded: Vec<Uuid> = matches.iter().skip(1).copied().collect();
+        if !superseded.is_empty() {
+            sqlx::query("update breadcrumbs set ttl = now(), ttl_source = 'upsert-duplicate' where id = any($1)")
+                .bind(&superseded)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(UpsertedBreadcrumb { breadcrumb, created, superseded })
+    }
+
+    pub async fn delete_breadcrumb(&self, owner_id: Uuid, agent_id: Uuid, id: Uuid) -> Result<i64> {
+        let deleted = self.delete_breadcrumb_returning_schema(owner_id, agent_id, id).await?;
+        Ok(deleted.map_or(0, |_| 1))
+    }
+
+    /// Delete a breadcrumb; `Some(schema_name)` if a row was removed, `None` if nothing matched
+    pub async fn delete_breadcrumb_returning_schema(&self, owner_id: Uuid, agent_id: Uuid, id: Uuid) -> Result<Option<Option<String>>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, Some(agent_id)).await?;
+        let row: Option<(Option<String>,)> = sqlx::query_as(r#"delete from breadcrumbs where id = $1 returning schema_name"#)
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await?;
+        Ok(row.map(|(schema_name,)| schema_name))
+    }
+
+    /// Delete a breadcrumb unless other breadcrumbs declare references to it; `force` deletes anyway and
+    /// returns the references that broke. The row is locked first, so a reference added meanwhile
+    /// either waits for the delete or is seen by it. References a breadcrumb makes to itself don't count
+    #[tracing::instrument(name = "db", skip_all, fields(query = "delete_breadcrumb_unless_referenced"))]
+    pub async fn delete_breadcrumb_unless_referenced(&self, owner_id: Uuid, agent_id: Uuid, id: Uuid, force: bool) -> Result<ReferencedDelete> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, Some(agent_id)).await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        let locked = sqlx::query_scalar::<_, Uuid>("select id from breadcrumbs where id = $1 for update")
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if locked.is_none() {
+            return Ok(ReferencedDelete::NotFound);
+        }
+        let incoming = sqlx::query_as::<_, (Uuid, String, Uuid, String, DateTime<Utc>)>(
+            r#"select source_id, field, target_id, relation, created_at from breadcrumb_references
+               where target_id = $1 and source_id <> $1 order by created_at, source_id, field"#
+        )
+        .bind(id)
+        .fetch_all(&mut *tx)
+        .await?;
+        if !incoming.is_empty() && !force {
+            return Ok(ReferencedDelete::Referenced(incoming.into_iter().map(|(source_id, field, target_id, relation, created_at)| {
+                BreadcrumbReference { source_id, field, target_id, relation, created_at }
+            }).collect()));
+        }
+        let broken = sqlx::query_as::<_, (Uuid, String, String, String, Vec<String>, Option<String>, String, String, Option<Uuid>)>(
+            r#"select b.id, r.field, r.relation, b.title, b.tags, b.schema_name, b.visibility::text, b.sensitivity::text, b.created_by
+               from breadcrumb_references r join breadcrumbs b on b.id = r.source_id
+               where r.target_id = $1 and r.source_id <> $1 order by r.created_at, b.id, r.field"#
+        )
+        .bind(id)
+        .fetch_all(&mut *tx)
+        .await?
+        .into_iter()
+        .map(|(source_id, field, relation, title, tags, schema_name, visibility, sensitivity, created_by)| {
+            BrokenReference { source_id, field, relation, title, tags, schema_name, visibility, sensitivity, created_by }
+        })
+        .collect();
+        let schema_name = sqlx::query_scalar::<_, Option<String>>("delete from breadcrumbs where id = $1 returning schema_name")
+            .bind(id)
+            .fetch_one(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(ReferencedDelete::Deleted { schema_name, broken })
+    }
+
+    /// Which of `ids` are the tenant's breadcrumbs the agent can read, under the same RLS as
+    /// `get_breadcrumb_context_for`; what declared references may point at
+    pub async fn readable_breadcrumb_ids(&self, owner_id: Uuid, agent_id: Option<Uuid>, ids: &[Uuid]) -> Result<Vec<Uuid>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, agent_id).await?;
+        let found = sqlx::query_scalar::<_, Uuid>("select id from breadcrumbs where id = any($1) and owner_id = $2")
+            .bind(ids)
+            .bind(owner_id)
+            .fetch_all(&mut *conn)
+            .await?;
+        Ok(found)
+    }
+
+    /// Distinct `session:` tags on those of `ids` the agent can read, in tag order
+    pub async fn session_tags_of(&self, owner_id: Uuid, agent_id: Option<Uuid>, ids: &[Uuid]) -> Result<Vec<String>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, agent_id).await?;
+        let tags = sqlx::query_scalar::<_, String>(
+            "select distinct t from breadcrumbs, unnest(tags) as t where id = any($1) and owner_id = $2 and t like 'session:%' order by t"
+        )
+        .bind(ids)
+        .bind(owner_id)
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(tags)
+    }
+
+    /// Make `refs` the references `source_id` declares, dropping any it declared before
+    pub async fn replace_breadcrumb_references(&self, owner_id: Uuid, agent_id: Uuid, source_id: Uuid, refs: &[NewBreadcrumbReference]) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, Some(agent_id)).await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        sqlx::query("delete from breadcrumb_references where source_id = $1")
+            .bind(source_id)
+            .execute(&mut *tx)
+            .await?;
+        if !refs.is_empty() {
+            let fields: Vec<String> = refs.iter().map(|r| r.field.clone()).collect();
+            let targets: Vec<Uuid> = refs.iter().map(|r| r.breadcrumb_id).collect();
+            let relations: Vec<String> = refs.iter().map(|r| r.relation.clone()).collect();
+            sqlx::query(
+                r#"insert into breadcrumb_references (owner_id, source_id, field, target_id, relation)
+                   select $1, $2, field, target_id, relation from unnest($3::text[], $4::uuid[], $5::text[]) as r(field, target_id, relation)
+                   on conflict (source_id, field, target_id) do nothing"#
+            )
+            .bind(owner_id)
+            .bind(source_id)
+            .bind(&fields)
+            .bind(&targets)
+            .bind(&relations)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// References `id` declares to breadcrumbs the agent can read; `None` if it can't read `id`
+    pub async fn list_breadcrumb_references(&self, owner_id: Uuid, agent_id: Option<Uuid>, id: Uuid) -> Result<Option<Vec<BreadcrumbReference>>> {
+        self.list_references(owner_id, agent_id, id, r#"
+            select r.source_id, r.field, r.target_id, r.relation, r.created_at
+            from breadcrumb_references r join breadcrumbs b on b.id = r.target_id
+            where r.source_id = $1 order by r.field, r.target_id"#).await
+    }
+
+    /// References to `id` from breadcrumbs the agent can read; `None` if it can't read `id`
+    pub async fn list_breadcrumb_referenced_by(&self, owner_id: Uuid, agent_id: Option<Uuid>, id: Uuid) -> Result<Option<Vec<BreadcrumbReference>>> {
+        self.list_references(owner_id, agent_id, id, r#"
+            select r.source_id, r.field, r.target_id, r.relation, r.created_at
+            from breadcrumb_references r join breadcrumbs b on b.id = r.source_id
+            where r.target_id = $1 order by r.created_at, r.source_id, r.field"#).await
+    }
+
+    // The join against breadcrumbs applies the agent's RLS to the other end too
+    async fn list_references(&self, owner_id: Uuid, agent_id: Option<Uuid>, id: Uuid, query: &str) -> Result<Option<Vec<BreadcrumbReference>>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, agent_id).await?;
+        let readable = sqlx::query_scalar::<_, Uuid>("select id from breadcrumbs where id = $1")
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await?;
+        if readable.is_none() {
+            return Ok(None);
+        }
+        let rows = sqlx::query_as::<_, (Uuid, String, Uuid, String, DateTime<Utc>)>(query)
+            .bind(id)
+            .fetch_all(&mut *conn)
+            .await?;
+        Ok(Some(rows.into_iter().map(|(source_id, field, target_id, relation, created_at)| {
+            BreadcrumbReference { source_id, field, target_id, relation, created_at }
+        }).collect()))
+    }
+
+    /// Store `att` for the tenant (once per sha256) and link it to the breadcrumb. Bytes the tenant
+    /// already holds dedupe and don't count against `quota_bytes`; quota checks are serialized per tenant
+    pub async fn attach_to_breadcrumb(&self, owner_id: Uuid, agent_id: Uuid, breadcrumb_id: Uuid, att: NewAttachment, quota_bytes: i64) -> Result<AttachOutcome> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, Some(agent_id)).await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        sqlx::query("select pg_advisory_xact_lock(hashtextextended($1::text, 0))")
+            .bind(owner_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let existing = sqlx::query_as::<_, (String, i64)>(
+            r#"update attachments set last_linked_at = now() where owner_id = $1 and sha256 = $2 returning content_type, size_bytes"#
+        )
+        .bind(owner_id)
+        .bind(&att.sha256)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let deduplicated = existing.is_some();
+        let (content_type, size_bytes) = match existing {
+            Some(stored) => stored,
+            None => {
+                let used_bytes = sqlx::query_scalar::<_, i64>(r#"select coalesce(sum(size_bytes), 0)::bigint from attachments where owner_id = $1"#)
+                    .bind(owner_id)
+                    .fetch_one(&mut *tx)
+                    .await?;
+                if used_bytes + att.size_bytes > quota_bytes {
+                    return Ok(AttachOutcome::QuotaExceeded { used_bytes, quota_bytes });
+                }
+                let (data, storage_path) = match att.body {
+                    AttachmentBody::Inline(bytes) => (Some(bytes), None),
+                    AttachmentBody::Stored(key) => (None, Some(key)),
+                };
+                sqlx::query(
+                    r#"insert into attachments (owner_id, sha256, content_type, size_bytes, data, storage_path) values ($1,$2,$3,$4,$5,$6)"#
+                )
+                .bind(owner_id)
+                .bind(&att.sha256)
+                .bind(&att.content_type)
+                .bind(att.size_bytes)
+                .bind(data)
+                .bind(storage_path)
+                .execute(&mut *tx)
+                .await?;
+                (att.content_type, att.size_bytes)
+            }
+        };
+
+        let (filename, created_by, created_at) = sqlx::query_as::<_, (Option<String>, Option<Uuid>, DateTime<Utc>)>(
+            r#"insert into breadcrumb_attachments (breadcrumb_id, owner_id, sha256, filename, created_by)
+               values ($1,$2,$3,$4,$5)
+               on conflict (breadcrumb_id, sha256) do update set filename = coalesce(excluded.filename, breadcrumb_attachments.filename)
+               returning filename, created_by, created_at"#
+        )
+        .bind(breadcrumb_id)
+        .bind(owner_id)
+        .bind(&att.sha256)
+        .bind(att.filename)
+        .bind(agent_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        let meta = AttachmentMeta { sha256: att.sha256, content_type, size_bytes, filename, created_by, created_at };
+        Ok(AttachOutcome::Linked { meta, deduplicated })
+    }
+
+    /// Attachments linked to a breadcrumb the agent can read, oldest first
+    pub async fn list_breadcrumb_attachments(&self, owner_id: Uuid, agent_id: Option<Uuid>, breadcrumb_id: Uuid) -> Result<Vec<AttachmentMeta>> {
         let mut conn = self.pool.acquire().await?;
-        set_rls(&mut conn, owner_id, Some(agent_id)).await?;
-        // Fetch current
-        let cur = sqlx::query_as::<_, DbBreadcrumb>(
-            r#"select id, owner_id, title, description, semantic_version, context, tags, schema_name, llm_hints, visibility::text as visibility, sensitivity::text as sensitivity, version, checksum, ttl, ttl_type, ttl_config, read_count, ttl_source, created_at, updated_at, created_by, updated_by, size_bytes, embedding from breadcrumbs where id = $1"#
+        set_rls(&mut conn, owner_id, agent_id).await?;
+        let rows = sqlx::query_as::<_, (String, String, i64, Option<String>, Option<Uuid>, DateTime<Utc>)>(
+            r#"select l.sha256, a.content_type, a.size_bytes, l.filename, l.created_by, l.created_at
+               from breadcrumb_attachments l
+               join attachments a on a.owner_id = l.owner_id and a.sha256 = l.sha256
+               where l.breadcrumb_id = $1
+               order by l.created_at"#
         )
-        .bind(id)
-        .fetch_one(&mut *conn)
+        .bind(breadcrumb_id)
+        .fetch_all(&mut *conn)
         .await?;
-        
-        tracing::info!("🔧 DB: Current breadcrumb version={}, context_preview={}", 
-            cur.version, 
-            serde_json::to_string(&cur.context).unwrap_or_default().chars().take(100).collect::<String>()
-        );
-        
-        if let Some(ev) = expected_version { 
-            if cur.version != ev { 
-                tracing::warn!("🔧 DB: Version mismatch! Expected: {}, Current: {}", ev, cur.version);
-                anyhow::bail!("version_mismatch"); 
-            } 
-        }
-
-        let new_title = u.title.unwrap_or(cur.title);
-        let new_description = u.description.or(cur.description);              // NEW
-        let new_semantic_version = u.semantic_version.or(cur.semantic_version); // NEW
-        let new_context = u.context.unwrap_or(cur.context);
-        let new_tags = u.tags.unwrap_or(cur.tags);
-        let new_schema = u.schema_name.or(cur.schema_name);
-        let new_llm_hints = u.llm_hints.or(cur.llm_hints);                    // NEW
-        let new_visibility = u.visibility.map(|v| visibility_to_db(&v)).unwrap_or(cur.visibility.as_str());
-        let new_sensitivity = u.sensitivity.map(|s| sensitivity_to_db(&s)).unwrap_or(cur.sensitivity.as_str());
-        let new_ttl = u.ttl.or(cur.ttl);
-        let new_ttl_type = u.ttl_type.or(cur.ttl_type);
-        let new_ttl_config = u.ttl_config.or(cur.ttl_config);
-        let new_ttl_source = u.ttl_source.or(cur.ttl_source);
-        let new_checksum = checksum_json(&new_context);
-        let new_size = serde_json::to_vec(&new_context)?.len() as i32;
-        let new_version = cur.version + 1;
-        
-        tracing::info!("🔧 DB: New values - version: {} -> {}, context_size: {} bytes, context_preview: {}", 
-            cur.version, new_version, new_size,
-            serde_json::to_string(&new_context).unwrap_or_default().chars().take(100).collect::<String>()
-        );
+        Ok(rows.into_iter().map(|(sha256, content_type, size_bytes, filename, created_by, created_at)| AttachmentMeta { sha256, content_type, size_bytes, filename, created_by, created_at }).collect())
+    }
 
-        let rec = sqlx::query_as::<_, DbBreadcrumb>(
-            r#"update breadcrumbs set title=$2, description=$3, semantic_version=$4, context=$5, tags=$6, schema_name=$7, llm_hints=$8,
-                 visibility=$9::visibility, sensitivity=$10::sensitivity, version=$11, checksum=$12,
-                 ttl=$13, ttl_type=$14, ttl_config=$15, ttl_source=$16, updated_at=now(), updated_by=$17, size_bytes=$18
-               where id=$1 returning id, owner_id, title, description, semantic_version, context, tags, schema_name, llm_hints, visibility::text as visibility, sensitivity::text as sensitivity, version, checksum, ttl, ttl_type, ttl_config, read_count, ttl_source, created_at, updated_at, created_by, updated_by, size_bytes, embedding"#
+    /// Content for `sha256` if it is linked to a breadcrumb the agent can read; the agent's own
+    /// tenant's copy wins when several tenants hold the same bytes
+    pub async fn get_attachment(&self, owner_id: Uuid, agent_id: Option<Uuid>, sha256: &str) -> Result<Option<Attachment>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, agent_id).await?;
+        let row = sqlx::query_as::<_, (Uuid, String, i64, Option<Vec<u8>>, Option<String>)>(
+            r#"select a.owner_id, a.content_type, a.size_bytes, a.data, a.storage_path
+               from attachments a
+               where a.sha256 = $1
+                 and exists (select 1 from breadcrumb_attachments l where l.owner_id = a.owner_id and l.sha256 = a.sha256)
+               order by (a.owner_id = $2) desc
+               limit 1"#
         )
-        .bind(id)
-        .bind(&new_title)
-        .bind(&new_description)             // NEW
-        .bind(&new_semantic_version)        // NEW
-        .bind(&new_context)
-        .bind(&new_tags)
-        .bind(&new_schema)
-        .bind(&new_llm_hints)               // NEW
-        .bind(new_visibility)
-        .bind(new_sensitivity)
-        .bind(new_version)
-        .bind(&new_checksum)
-        .bind(new_ttl)
-        .bind(&new_ttl_type)
-        .bind(&new_ttl_config)
-        .bind(&new_ttl_source)
-        .bind(agent_id)
-        .bind(new_size)
-        .fetch_one(&mut *conn)
+        .bind(sha256)
+        .bind(owner_id)
+        .fetch_optional(&mut *conn)
         .await?;
-        
-        tracing::info!("🔧 DB: SQL UPDATE completed successfully! Returned version={}, context_preview={}", 
-            rec.version,
-            serde_json::to_string(&rec.context).unwrap_or_default().chars().take(100).collect::<String>()
-        );
+        Ok(row.and_then(|(owner_id, content_type, size_bytes, data, storage_path)| {
+            let body = match (data, storage_path) {
+                (Some(bytes), _) => AttachmentBody::Inline(bytes),
+                (None, Some(key)) => AttachmentBody::Stored(key),
+                (None, None) => return None,
+            };
+            Some(Attachment { owner_id, content_type, size_bytes, body })
+        }))
+    }
 
-        // Append history
-        sqlx::query(r#"insert into breadcrumb_history (breadcrumb_id, version, context, updated_at, updated_by, checksum) values ($1,$2,$3, now(), $4, $5)"#)
-            .bind(id)
-            .bind(new_version)
-            .bind(&new_context)
-            .bind(agent_id)
-            .bind(&new_checksum)
-            .execute(&mut *conn)
+    /// Which of `shas` the tenant already stores
+    pub async fn held_attachments(&self, owner_id: Uuid, shas: &[String]) -> Result<Vec<String>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
+        let held = sqlx::query_scalar::<_, String>(r#"select sha256 from attachments where owner_id = $1 and sha256 = any($2)"#)
+            .bind(owner_id)
+            .bind(shas)
+            .fetch_all(&mut *conn)
             .await?;
-
-        Ok(rec.into())
+        Ok(held)
     }
 
-    pub async fn delete_breadcrumb(&self, owner_id: Uuid, agent_id: Uuid, id: Uuid) -> Result<i64> {
+    /// Link the values moved out of a breadcrumb's context to it: `values` are stored for the tenant
+    /// (bytes it already holds are left as they are) and `held` are shas it stores already. Like the
+    /// context they came from, they don't count against the attachment quota
+    pub async fn link_context_values(&self, owner_id: Uuid, agent_id: Uuid, breadcrumb_id: Uuid, values: Vec<NewAttachment>, held: &[String]) -> Result<()> {
         let mut conn = self.pool.acquire().await?;
         set_rls(&mut conn, owner_id, Some(agent_id)).await?;
-        let res = sqlx::query(r#"delete from breadcrumbs where id = $1"#)
-            .bind(id)
-            .execute(&mut *conn)
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        let mut shas: Vec<String> = held.to_vec();
+        for att in values {
+            let (data, storage_path) = match att.body {
+                AttachmentBody::Inline(bytes) => (Some(bytes), None),
+                AttachmentBody::Stored(key) => (None, Some(key)),
+            };
+            sqlx::query(
+                r#"insert into attachments (owner_id, sha256, content_type, size_bytes, data, storage_path) values ($1,$2,$3,$4,$5,$6)
+                   on conflict (owner_id, sha256) do nothing"#
+            )
+            .bind(owner_id)
+            .bind(&att.sha256)
+            .bind(&att.content_type)
+            .bind(att.size_bytes)
+            .bind(data)
+            .bind(storage_path)
+            .execute(&mut *tx)
             .await?;
-        Ok(res.rows_affected() as i64)
+            shas.push(att.sha256);
+        }
+        sqlx::query(r#"update attachments set last_linked_at = now() where owner_id = $1 and sha256 = any($2)"#)
+            .bind(owner_id)
+            .bind(&shas)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            r#"insert into breadcrumb_attachments (breadcrumb_id, owner_id, sha256, created_by)
+               select $1, $2, sha256, $3 from unnest($4::text[]) as sha256
+               on conflict (breadcrumb_id, sha256) do nothing"#
+        )
+        .bind(breadcrumb_id)
+        .bind(owner_id)
+        .bind(agent_id)
+        .bind(&shas)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
     }
 }
 
 impl Db {
     pub async fn list_secrets(&self, owner_id: Uuid, scope_type: Option<&str>, scope_id: Option<Uuid>) -> Result<Vec<(Uuid, String, String, Option<Uuid>, chrono::DateTime<chrono::Utc>)>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
         let mut query = String::from("select id, name, scope_type, scope_id, created_at from secrets where owner_id = $1");
         if scope_type.is_some() { query.push_str(" and scope_type = $2"); }
-        if scope_id.is_some() { query.push_str(" and scope_id = $3"); }
+        if scope_id.is_some() { query.push_str(if scope_type.is_some() { " and scope_id = $3" } else { " and scope_id = $2" }); }
         query.push_str(" order by created_at desc");
-        
-        let rows = if let Some(st) = scope_type {
-            if let Some(sid) = scope_id {
-                sqlx::query_as::<_, (Uuid, String, String, Option<Uuid>, chrono::DateTime<chrono::Utc>)>(&query)
-                    .bind(owner_id)
-                    .bind(st)
-                    .bind(sid)
-                    .fetch_all(&self.pool)
-                    .await?
-            } else {
-                sqlx::query_as::<_, (Uuid, String, String, Option<Uuid>, chrono::DateTime<chrono::Utc>)>(&query)
-                    .bind(owner_id)
-                    .bind(st)
-                    .fetch_all(&self.pool)
-                    .await?
-            }
-        } else {
-            sqlx::query_as::<_, (Uuid, String, String, Option<Uuid>, chrono::DateTime<chrono::Utc>)>(&query)
-                .bind(owner_id)
-                .fetch_all(&self.pool)
-                .await?
-        };
+
+        let mut q = sqlx::query_as::<_, (Uuid, String, String, Option<Uuid>, chrono::DateTime<chrono::Utc>)>(&query).bind(owner_id);
+        if let Some(st) = scope_type { q = q.bind(st); }
+        if let Some(sid) = scope_id { q = q.bind(sid); }
+        let rows = q.fetch_all(&mut *conn).await?;
         Ok(rows)
     }
 
     pub async fn update_secret(&self, owner_id: Uuid, secret_id: Uuid, enc_blob: &[u8], dek_encrypted: &[u8]) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
         let rows = sqlx::query(
             "update secrets set enc_blob = $1, dek_encrypted = $2, updated_at = now() where id = $3 and owner_id = $4"
         )
@@ -571,58 +2294,448 @@ impl Db {
         .bind(dek_encrypted)
         .bind(secret_id)
         .bind(owner_id)
-        .execute(&self.pool)
+        .execute(&mut *conn)
         .await?
         .rows_affected();
         
         if rows == 0 {
-            anyhow::bail!("secret not found or not owned");
+            return Err(DbError::NotFound("secret".to_string()));
         }
         Ok(())
     }
 
     pub async fn delete_secret(&self, owner_id: Uuid, secret_id: Uuid) -> Result<u64> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
         let result = sqlx::query(
             "delete from secrets where id = $1 and owner_id = $2"
         )
         .bind(secret_id)
         .bind(owner_id)
-        .execute(&self.pool)
+        .execute(&mut *conn)
         .await?;
         Ok(result.rows_affected())
     }
 
     // Agent CRUD operations
     pub async fn list_agents(&self, owner_id: Uuid) -> Result<Vec<(Uuid, Vec<String>, chrono::DateTime<chrono::Utc>)>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
         let rows = sqlx::query_as::<_, (Uuid, Vec<String>, chrono::DateTime<chrono::Utc>)>(
             "select id, roles, created_at from agents where owner_id = $1 order by created_at desc"
         )
         .bind(owner_id)
-        .fetch_all(&self.pool)
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(rows)
+    }
+    
+    pub async fn get_agent(&self, owner_id: Uuid, agent_id: Uuid) -> Result<Option<(Uuid, Vec<String>, chrono::DateTime<chrono::Utc>)>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, Some(agent_id)).await?;
+        let row = sqlx::query_as::<_, (Uuid, Vec<String>, chrono::DateTime<chrono::Utc>)>(
+            "select id, roles, created_at from agents where owner_id = $1 and id = $2"
+        )
+        .bind(owner_id)
+        .bind(agent_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+        Ok(row)
+    }
+    
+    /// What still points at the agent; None when it isn't registered under `owner_id`
+    pub async fn agent_dependents(&self, owner_id: Uuid, agent_id: Uuid) -> Result<Option<AgentDependents>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, Some(agent_id)).await?;
+        let exists = sqlx::query_scalar::<_, Uuid>("select id from agents where owner_id = $1 and id = $2")
+            .bind(owner_id)
+            .bind(agent_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+        if exists.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(agent_dependents_conn(&mut conn, owner_id, agent_id).await?))
+    }
+
+    /// Delete the agent and everything attached to it in one transaction: selector and breadcrumb
+    /// subscriptions, webhooks (and with them their deliveries), ACL grants to it, API keys, DLQ
+    /// entries and the agent row with its webhook secret. Breadcrumbs it wrote stay, with
+    /// created_by/updated_by cleared. Writes an agent_audit entry; None when the agent isn't registered
+    pub async fn offboard_agent(&self, owner_id: Uuid, agent_id: Uuid, actor_agent_id: Option<Uuid>, reason: &str) -> Result<Option<AgentOffboarding>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, Some(agent_id)).await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        let offboarded = offboard_agent_tx(&mut tx, owner_id, agent_id, actor_agent_id, reason).await?;
+        if offboarded.is_some() {
+            tx.commit().await?;
+        }
+        Ok(offboarded)
+    }
+
+    /// The owner's agents, unexpired selectors and active webhooks, in creation order
+    pub async fn export_topology(&self, owner_id: Uuid) -> Result<Topology> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
+        let agents = sqlx::query_as::<_, (Uuid, Vec<String>)>(
+            "select id, roles from agents where owner_id = $1 order by created_at, id"
+        )
+        .bind(owner_id)
+        .fetch_all(&mut *conn)
+        .await?;
+        let selectors = sqlx::query_as::<_, DbSelector>(
+            r#"select id, owner_id, agent_id, selector, expires_at from selector_subscriptions
+               where owner_id = $1 and (expires_at is null or expires_at > now()) order by created_at, id"#
+        )
+        .bind(owner_id)
+        .fetch_all(&mut *conn)
+        .await?;
+        let webhooks = sqlx::query_as::<_, (Uuid, String, Option<String>, Option<i16>, Option<Uuid>)>(
+            r#"select w.agent_id, w.url, w.payload_template, w.payload_version, w.selector_id
+               from agent_webhooks w join agents a on a.id = w.agent_id
+               where a.owner_id = $1 and w.active order by w.created_at, w.id"#
+        )
+        .bind(owner_id)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let mut topology = Topology {
+            agents: agents.into_iter().map(|(id, roles)| TopologyAgent { id, roles }).collect(),
+            selectors: Vec::with_capacity(selectors.len()),
+            webhooks: webhooks.into_iter().map(|(agent_id, url, payload_template, payload_version, selector_id)| TopologyWebhook {
+                agent_id, url, payload_template, payload_version: payload_version.map(|v| v as u16), selector_id,
+            }).collect(),
+        };
+        for row in selectors {
+            let sub: SelectorSubscription = row.try_into()?;
+            topology.selectors.push(TopologySelector {
+                id: sub.id, agent_id: sub.agent_id, selector: sub.selector, channels: sub.channels,
+                payload_version: sub.payload_version, expires_at: sub.expires_at,
+            });
+        }
+        Ok(topology)
+    }
+
+    /// Upsert a topology in one transaction: agents by id (roles replaced, webhook secrets kept),
+    /// selectors by id, webhooks by agent and URL (reactivating deactivated ones). With `prune`, the
+    /// owner's agents, selectors and webhooks missing from it are offboarded, deleted and deactivated,
+    /// except `keep_agent`, the importing agent. The caller validates the document first; an id taken by
+    /// another tenant is a Conflict
+    pub async fn import_topology(&self, owner_id: Uuid, topology: &Topology, prune: bool, keep_agent: Uuid) -> Result<TopologyImport> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        let mut out = TopologyImport::default();
+        let item = |kind, index, id, agent_id, url: Option<&str>, action| TopologyItem { kind, index, id, agent_id, url: url.map(String::from), action };
+
+        for (i, agent) in topology.agents.iter().enumerate() {
+            let roles = sqlx::query_scalar::<_, Vec<String>>("select roles from agents where owner_id = $1 and id = $2 for update")
+                .bind(owner_id)
+                .bind(agent.id)
+                .fetch_optional(&mut *tx)
+                .await?;
+            let action = match roles {
+                Some(roles) if roles == agent.roles => TopologyAction::Skipped,
+                Some(_) => {
+                    sqlx::query("update agents set roles = $3 where owner_id = $1 and id = $2")
+                        .bind(owner_id)
+                        .bind(agent.id)
+                        .bind(&agent.roles[..])
+                        .execute(&mut *tx)
+                        .await?;
+                    TopologyAction::Updated
+                }
+                None => {
+                    sqlx::query("insert into agents (id, owner_id, agent_key, roles) values ($1, $2, $3, $4)")
+                        .bind(agent.id)
+                        .bind(owner_id)
+                        .bind(agent.id.to_string())
+                        .bind(&agent.roles[..])
+                        .execute(&mut *tx)
+                        .await
+                        .map_err(|e| match DbError::from(e) {
+                            DbError::Conflict(_) => DbError::Conflict(format!("agent {} belongs to another tenant", agent.id)),
+                            e => e,
+                        })?;
+                    TopologyAction::Created
+                }
+            };
+            out.items.push(item(TopologyKind::Agent, Some(i), agent.id, agent.id, None, action));
+        }
+
+        for (i, sel) in topology.selectors.iter().enumerate() {
+            let stored = selector_to_db(&sel.selector, &sel.channels, sel.payload_version)?;
+            let existing = sqlx::query_as::<_, (Uuid, JsonValue, Option<DateTime<Utc>>)>(
+                "select agent_id, selector, expires_at from selector_subscriptions where owner_id = $1 and id = $2 for update"
+            )
+            .bind(owner_id)
+            .bind(sel.id)
+            .fetch_optional(&mut *tx)
+            .await?;
+            let action = match existing {
+                Some((agent_id, selector, expires_at)) if agent_id == sel.agent_id && selector == stored && expires_at == sel.expires_at => TopologyAction::Skipped,
+                Some(_) => {
+                    sqlx::query("update selector_subscriptions set agent_id = $3, selector = $4, expires_at = $5 where owner_id = $1 and id = $2")
+                        .bind(owner_id)
+                        .bind(sel.id)
+                        .bind(sel.agent_id)
+                        .bind(&stored)
+                        .bind(sel.expires_at)
+                        .execute(&mut *tx)
+                        .await?;
+                    TopologyAction::Updated
+                }
+                None => {
+                    sqlx::query("insert into selector_subscriptions (id, owner_id, agent_id, selector, expires_at) values ($1, $2, $3, $4, $5)")
+                        .bind(sel.id)
+                        .bind(owner_id)
+                        .bind(sel.agent_id)
+                        .bind(&stored)
+                        .bind(sel.expires_at)
+                        .execute(&mut *tx)
+                        .await
+                        .map_err(|e| match DbError::from(e) {
+                            DbError::Conflict(_) => DbError::Conflict(format!("selector {} belongs to another tenant", sel.id)),
+                            e => e,
+                        })?;
+                    TopologyAction::Created
+                }
+            };
+            out.items.push(item(TopologyKind::Selector, Some(i), sel.id, sel.agent_id, None, action));
+        }
+
+        for (i, hook) in topology.webhooks.iter().enumerate() {
+            let existing = sqlx::query_as::<_, (Uuid, bool, Option<String>, Option<i16>, Option<Uuid>)>(
+                "select id, active, payload_template, payload_version, selector_id from agent_webhooks where agent_id = $1 and url = $2 for update"
+            )
+            .bind(hook.agent_id)
+            .bind(&hook.url)
+            .fetch_optional(&mut *tx)
+            .await?;
+            let unchanged = existing.as_ref().is_some_and(|(_, active, template, version, selector_id)| {
+                *active && *template == hook.payload_template && version.map(|v| v as u16) == hook.payload_version && *selector_id == hook.selector_id
+            });
+            let id = match &existing {
+                Some((id, ..)) if unchanged => *id,
+                _ => sqlx::query_scalar::<_, Uuid>(
+                    r#"insert into agent_webhooks (agent_id, url, payload_template, payload_version, selector_id)
+                       values ($1, $2, $3, $4, $5)
+                       on conflict (agent_id, url) do update set active = true, deactivated_reason = null, consecutive_failures = 0, payload_template = excluded.payload_template,
+                           payload_version = excluded.payload_version, selector_id = excluded.selector_id
+                       returning id"#
+                )
+                .bind(hook.agent_id)
+                .bind(&hook.url)
+                .bind(hook.payload_template.as_deref())
+                .bind(hook.payload_version.map(|v| v as i16))
+                .bind(hook.selector_id)
+                .fetch_one(&mut *tx)
+                .await?,
+            };
+            let action = match (&existing, unchanged) {
+                (_, true) => TopologyAction::Skipped,
+                (Some(_), false) => TopologyAction::Updated,
+                (None, _) => TopologyAction::Created,
+            };
+            out.items.push(item(TopologyKind::Webhook, Some(i), id, hook.agent_id, Some(&hook.url), action));
+        }
+
+        if prune {
+            let agents: Vec<Uuid> = topology.agents.iter().map(|a| a.id).collect();
+            let selectors: Vec<Uuid> = topology.selectors.iter().map(|s| s.id).collect();
+            let (hook_agents, hook_urls): (Vec<Uuid>, Vec<String>) = topology.webhooks.iter().map(|w| (w.agent_id, w.url.clone())).unzip();
+
+            let hooks = sqlx::query_as::<_, (Uuid, Uuid, String)>(
+                r#"update agent_webhooks w set active = false, deactivated_reason = 'pruned by topology import'
+                   from agents a
+                   where a.id = w.agent_id and a.owner_id = $1 and w.active
+                     and (w.agent_id, w.url) not in (select * from unnest($2::uuid[], $3::text[]))
+                   returning w.id, w.agent_id, w.url"#
+            )
+            .bind(owner_id)
+            .bind(&hook_agents)
+            .bind(&hook_urls)
+            .fetch_all(&mut *tx)
+            .await?;
+            for (id, agent_id, url) in hooks {
+                out.items.push(item(TopologyKind::Webhook, None, id, agent_id, Some(&url), TopologyAction::Removed));
+            }
+
+            // Deleting a selector deactivates the webhooks bound to it (trigger from 0030)
+            let sels = sqlx::query_as::<_, (Uuid, Uuid)>(
+                "delete from selector_subscriptions where owner_id = $1 and id <> all($2) returning id, agent_id"
+            )
+            .bind(owner_id)
+            .bind(&selectors)
+            .fetch_all(&mut *tx)
+            .await?;
+            for (id, agent_id) in sels {
+                out.items.push(item(TopologyKind::Selector, None, id, agent_id, None, TopologyAction::Removed));
+            }
+
+            let gone = sqlx::query_scalar::<_, Uuid>("select id from agents where owner_id = $1 and id <> all($2) and id <> $3")
+                .bind(owner_id)
+                .bind(&agents)
+                .bind(keep_agent)
+                .fetch_all(&mut *tx)
+                .await?;
+            for agent_id in gone {
+                if let Some(offboarded) = offboard_agent_tx(&mut tx, owner_id, agent_id, Some(keep_agent), "topology import prune").await? {
+                    out.api_key_hashes.extend(offboarded.api_key_hashes);
+                    out.items.push(item(TopologyKind::Agent, None, agent_id, agent_id, None, TopologyAction::Removed));
+                }
+            }
+        }
+
+        tx.commit().await?;
+        Ok(out)
+    }
+
+    /// Store a new key for `agent_id`; the caller hashes it and keeps the plaintext to itself
+    pub async fn create_api_key(&self, owner_id: Uuid, agent_id: Uuid, name: Option<&str>, prefix: &str, hashed_key: &str, roles: &[String]) -> Result<ApiKey> {
+        let row = sqlx::query_as::<_, DbApiKey>(
+            r#"insert into api_keys (owner_id, agent_id, hashed_key, prefix, roles, name)
+               values ($1,$2,$3,$4,$5,$6)
+               returning id, owner_id, agent_id, name, prefix, roles, created_at, last_used_at, revoked_at"#
+        )
+        .bind(owner_id)
+        .bind(agent_id)
+        .bind(hashed_key)
+        .bind(prefix)
+        .bind(roles)
+        .bind(name)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(row.into())
+    }
+
+    /// All of the agent's keys, revoked ones included, newest first
+    pub async fn list_api_keys(&self, owner_id: Uuid, agent_id: Uuid) -> Result<Vec<ApiKey>> {
+        let rows = sqlx::query_as::<_, DbApiKey>(
+            r#"select id, owner_id, agent_id, name, prefix, roles, created_at, last_used_at, revoked_at
+               from api_keys where owner_id = $1 and agent_id = $2
+               order by created_at desc"#
+        )
+        .bind(owner_id)
+        .bind(agent_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    /// Returns the revoked key's hash, or None when no active key had this id
+    pub async fn revoke_api_key(&self, owner_id: Uuid, agent_id: Uuid, key_id: Uuid) -> Result<Option<String>> {
+        let hashed = sqlx::query_scalar::<_, String>(
+            r#"update api_keys set revoked_at = now()
+               where id = $1 and owner_id = $2 and agent_id = $3 and revoked_at is null
+               returning hashed_key"#
+        )
+        .bind(key_id)
+        .bind(owner_id)
+        .bind(agent_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(hashed)
+    }
+
+    /// The unrevoked key with this hash, stamping last_used_at; callers cache the result,
+    /// so last_used_at moves at most once per cache lifetime
+    pub async fn find_api_key(&self, hashed_key: &str) -> Result<Option<ApiKey>> {
+        let row = sqlx::query_as::<_, DbApiKey>(
+            r#"update api_keys set last_used_at = now()
+               where hashed_key = $1 and revoked_at is null
+               returning id, owner_id, agent_id, name, prefix, roles, created_at, last_used_at, revoked_at"#
+        )
+        .bind(hashed_key)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(Into::into))
+    }
+
+    /// Record a run in `running` state before its first stage starts
+    pub async fn create_agent_run(&self, owner_id: Uuid, agent_id: Uuid, input: &JsonValue) -> Result<AgentRun> {
+        let row = sqlx::query_as::<_, DbAgentRun>(
+            r#"insert into agent_runs (owner_id, agent_id, input) values ($1,$2,$3)
+               returning id, owner_id, agent_id, status, input, stages, result, error, created_at, updated_at, finished_at, expires_at"#
+        )
+        .bind(owner_id)
+        .bind(agent_id)
+        .bind(input)
+        .fetch_one(&self.pool)
+        .await?;
+        row.try_into()
+    }
+
+    pub async fn get_agent_run(&self, owner_id: Uuid, run_id: Uuid) -> Result<Option<AgentRun>> {
+        let row = sqlx::query_as::<_, DbAgentRun>(
+            r#"select id, owner_id, agent_id, status, input, stages, result, error, created_at, updated_at, finished_at, expires_at
+               from agent_runs where id = $1 and owner_id = $2"#
+        )
+        .bind(run_id)
+        .bind(owner_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        row.map(TryInto::try_into).transpose()
+    }
+
+    /// Replace a running run's stages; false once it has finished or been cancelled, so the
+    /// runner stops at the next stage even when the cancel was served by another instance
+    pub async fn save_agent_run_stages(&self, owner_id: Uuid, run_id: Uuid, stages: &[AgentRunStage]) -> Result<bool> {
+        let res = sqlx::query(
+            "update agent_runs set stages = $3, updated_at = now() where id = $1 and owner_id = $2 and status = 'running'"
+        )
+        .bind(run_id)
+        .bind(owner_id)
+        .bind(serde_json::to_value(stages)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() == 1)
+    }
+
+    /// Move a running run to `status` (succeeded, failed or cancelled), kept for `retention`;
+    /// false when it had already finished
+    pub async fn finish_agent_run(&self, owner_id: Uuid, run_id: Uuid, status: &str, result: Option<&JsonValue>, error: Option<&str>, retention: chrono::Duration) -> Result<bool> {
+        let res = sqlx::query(
+            r#"update agent_runs
+               set status = $3, result = $4, error = $5, updated_at = now(), finished_at = now(), expires_at = now() + $6 * interval '1 second'
+               where id = $1 and owner_id = $2 and status = 'running'"#
+        )
+        .bind(run_id)
+        .bind(owner_id)
+        .bind(status)
+        .bind(result)
+        .bind(error)
+        .bind(retention.num_seconds() as f64)
+        .execute(&self.pool)
         .await?;
-        Ok(rows)
+        Ok(res.rows_affected() == 1)
     }
-    
-    pub async fn get_agent(&self, owner_id: Uuid, agent_id: Uuid) -> Result<Option<(Uuid, Vec<String>, chrono::DateTime<chrono::Utc>)>> {
-        let row = sqlx::query_as::<_, (Uuid, Vec<String>, chrono::DateTime<chrono::Utc>)>(
-            "select id, roles, created_at from agents where owner_id = $1 and id = $2"
+
+    /// Fail every tenant's runs with no progress for `stale_after` (their server died mid-run),
+    /// except `live` ones this process is still executing
+    pub async fn fail_stale_agent_runs(&self, stale_after: chrono::Duration, retention: chrono::Duration, live: &[Uuid], reason: &str) -> Result<u64> {
+        let res = sqlx::query(
+            r#"update agent_runs
+               set status = 'failed', error = $4, updated_at = now(), finished_at = now(), expires_at = now() + $2 * interval '1 second'
+               where status = 'running' and updated_at < now() - $1 * interval '1 second' and not (id = any($3))"#
         )
-        .bind(owner_id)
-        .bind(agent_id)
-        .fetch_optional(&self.pool)
+        .bind(stale_after.num_seconds() as f64)
+        .bind(retention.num_seconds() as f64)
+        .bind(live)
+        .bind(reason)
+        .execute(&self.pool)
         .await?;
-        Ok(row)
+        Ok(res.rows_affected())
     }
-    
-    pub async fn delete_agent(&self, owner_id: Uuid, agent_id: Uuid) -> Result<()> {
-        sqlx::query("delete from agents where owner_id = $1 and id = $2")
-            .bind(owner_id)
-            .bind(agent_id)
+
+    /// Delete finished runs past their retention, across tenants
+    pub async fn delete_expired_agent_runs(&self) -> Result<u64> {
+        let res = sqlx::query("delete from agent_runs where expires_at < now()")
             .execute(&self.pool)
             .await?;
-        Ok(())
+        Ok(res.rows_affected())
     }
-    
+
     // Tenant CRUD operations
     pub async fn list_tenants(&self) -> Result<Vec<(Uuid, String, chrono::DateTime<chrono::Utc>)>> {
         let rows = sqlx::query_as::<_, (Uuid, String, chrono::DateTime<chrono::Utc>)>(
@@ -652,6 +2765,8 @@ impl Db {
         Ok(())
     }
     
+    /// Only the tenants row: fails on foreign keys while the owner has agents, breadcrumbs and the
+    /// like. Offboarding a tenant with data goes through `begin_tenant_deletion`
     pub async fn delete_tenant(&self, tenant_id: Uuid) -> Result<()> {
         sqlx::query("delete from tenants where id = $1")
             .bind(tenant_id)
@@ -659,38 +2774,218 @@ impl Db {
             .await?;
         Ok(())
     }
-    
+
+    /// Rows per table in `TENANT_TABLES` that a deletion of the tenant would remove
+    pub async fn count_tenant_rows(&self, tenant_id: Uuid) -> Result<Vec<(&'static str, i64)>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, tenant_id, None).await?;
+        let mut counts = Vec::with_capacity(TENANT_TABLES.len());
+        for (table, condition) in TENANT_TABLES {
+            let n = sqlx::query_scalar::<_, i64>(&format!("select count(*) from {} where {}", table, condition))
+                .bind(tenant_id)
+                .fetch_one(&mut *conn)
+                .await?;
+            counts.push((*table, n));
+        }
+        Ok(counts)
+    }
+
+    /// Every row of `table` (one of `TENANT_TABLES`) that belongs to the tenant, as JSON objects, for the
+    /// deletion's snapshot. Vectors come out as their text form
+    pub async fn export_tenant_table(&self, tenant_id: Uuid, table: &str) -> Result<Vec<JsonValue>> {
+        let condition = tenant_table_condition(table)?;
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, tenant_id, None).await?;
+        let rows = sqlx::query_scalar::<_, JsonValue>(&format!("select to_jsonb(t) from {} t where {}", table, condition))
+            .bind(tenant_id)
+            .fetch_all(&mut *conn)
+            .await?;
+        Ok(rows)
+    }
+
+    /// Attachment store keys of the tenant's blobs that aren't kept inline
+    pub async fn list_tenant_blob_keys(&self, tenant_id: Uuid) -> Result<Vec<String>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, tenant_id, None).await?;
+        let keys = sqlx::query_scalar::<_, String>("select storage_path from attachments where owner_id = $1 and storage_path is not null order by storage_path")
+            .bind(tenant_id)
+            .fetch_all(&mut *conn)
+            .await?;
+        Ok(keys)
+    }
+
+    /// Delete up to `batch_size` of the tenant's rows from `table` (one of `TENANT_TABLES`); returns how many went
+    pub async fn delete_tenant_rows(&self, tenant_id: Uuid, table: &str, batch_size: i64) -> Result<u64> {
+        let condition = tenant_table_condition(table)?;
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, tenant_id, None).await?;
+        let res = sqlx::query(&format!(
+            "delete from {t} where ctid = any(array(select ctid from {t} where {c} limit $2))", t = table, c = condition
+        ))
+        .bind(tenant_id)
+        .bind(batch_size)
+        .execute(&mut *conn)
+        .await?;
+        Ok(res.rows_affected())
+    }
+
+    /// Mark the tenant deleting and start (or resume) its deletion run. A failed run picks up where it
+    /// stopped; a completed one of a tenant created again with the same id starts over. None when there
+    /// is neither a tenant nor an unfinished run
+    pub async fn begin_tenant_deletion(&self, tenant_id: Uuid, export_to: Option<&str>) -> Result<Option<TenantDeletion>> {
+        let mut tx = self.pool.begin().await?;
+        let name = sqlx::query_scalar::<_, String>("update tenants set deleting_at = coalesce(deleting_at, now()) where id = $1 returning name")
+            .bind(tenant_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        let row = match name {
+            Some(name) => sqlx::query_as::<_, TenantDeletionRow>(&format!(
+                r#"insert into tenant_deletions (tenant_id, tenant_name, status, export_to) values ($1, $2, 'exporting', $3)
+                   on conflict (tenant_id) do update set
+                     tenant_name = excluded.tenant_name,
+                     status = case when tenant_deletions.status = 'completed' or tenant_deletions.export_location is null then 'exporting' else 'deleting' end,
+                     export_to = case when tenant_deletions.status = 'completed' or tenant_deletions.export_location is null
+                                   then coalesce(excluded.export_to, tenant_deletions.export_to) else tenant_deletions.export_to end,
+                     export_location = case when tenant_deletions.status = 'completed' then null else tenant_deletions.export_location end,
+                     deleted = case when tenant_deletions.status = 'completed' then '{{}}' else tenant_deletions.deleted end,
+                     started_at = case when tenant_deletions.status = 'completed' then now() else tenant_deletions.started_at end,
+                     finished_at = null, error = null, updated_at = now()
+                   returning {}"#, TENANT_DELETION_COLUMNS
+            ))
+            .bind(tenant_id)
+            .bind(name)
+            .bind(export_to)
+            .fetch_one(&mut *tx)
+            .await
+            .map(Some)?,
+            // The tenants row goes last, so a run without one only has that step left
+            None => sqlx::query_as::<_, TenantDeletionRow>(&format!(
+                r#"update tenant_deletions set status = 'deleting', error = null, updated_at = now()
+                   where tenant_id = $1 and status <> 'completed' and export_location is not null returning {}"#, TENANT_DELETION_COLUMNS
+            ))
+            .bind(tenant_id)
+            .fetch_optional(&mut *tx)
+            .await?,
+        };
+        tx.commit().await?;
+        row.map(tenant_deletion_from_row).transpose()
+    }
+
+    pub async fn get_tenant_deletion(&self, tenant_id: Uuid) -> Result<Option<TenantDeletion>> {
+        let row = sqlx::query_as::<_, TenantDeletionRow>(&format!("select {} from tenant_deletions where tenant_id = $1", TENANT_DELETION_COLUMNS))
+            .bind(tenant_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.map(tenant_deletion_from_row).transpose()
+    }
+
+    /// Tenants marked deleting, for the write gate of every replica
+    pub async fn list_deleting_tenants(&self) -> Result<Vec<Uuid>> {
+        let ids = sqlx::query_scalar::<_, Uuid>("select id from tenants where deleting_at is not null")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(ids)
+    }
+
+    pub async fn set_tenant_deletion_stage(&self, tenant_id: Uuid, stage: &str) -> Result<()> {
+        sqlx::query("update tenant_deletions set stage = $2, updated_at = now() where tenant_id = $1")
+            .bind(tenant_id)
+            .bind(stage)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Record the written snapshot and move on to deleting
+    pub async fn set_tenant_deletion_exported(&self, tenant_id: Uuid, location: &str) -> Result<()> {
+        sqlx::query("update tenant_deletions set export_location = $2, status = 'deleting', updated_at = now() where tenant_id = $1")
+            .bind(tenant_id)
+            .bind(location)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Add `n` to the rows deleted from `table`
+    pub async fn add_tenant_deletion_progress(&self, tenant_id: Uuid, table: &str, n: i64) -> Result<()> {
+        sqlx::query(
+            r#"update tenant_deletions
+               set deleted = jsonb_set(deleted, array[$2], to_jsonb(coalesce((deleted->>$2)::bigint, 0) + $3)), updated_at = now()
+               where tenant_id = $1"#
+        )
+        .bind(tenant_id)
+        .bind(table)
+        .bind(n)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Leave the run failed at its current stage, to be resumed by requesting the deletion again
+    pub async fn fail_tenant_deletion(&self, tenant_id: Uuid, error: &str) -> Result<()> {
+        sqlx::query("update tenant_deletions set status = 'failed', error = $2, updated_at = now() where tenant_id = $1")
+            .bind(tenant_id)
+            .bind(error)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Delete the tenants row and complete the run, in one transaction
+    pub async fn finish_tenant_deletion(&self, tenant_id: Uuid) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("delete from tenants where id = $1")
+            .bind(tenant_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            r#"update tenant_deletions set status = 'completed', stage = null, error = null, updated_at = now(), finished_at = now()
+               where tenant_id = $1"#
+        )
+        .bind(tenant_id)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
     // Selector CRUD operations
-    pub async fn update_selector(&self, owner_id: Uuid, agent_id: Uuid, selector_id: Uuid, selector: Selector) -> Result<()> {
+    pub async fn update_selector(&self, owner_id: Uuid, agent_id: Uuid, selector_id: Uuid, selector: Selector, channels: &[DeliveryChannel], payload_version: Option<u16>) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, Some(agent_id)).await?;
         sqlx::query(
-            "update selectors set selector = $4 where id = $1 and owner_id = $2 and agent_id = $3"
+            "update selector_subscriptions set selector = $4 where id = $1 and owner_id = $2 and agent_id = $3"
         )
         .bind(selector_id)
         .bind(owner_id)
         .bind(agent_id)
-        .bind(JsonValue::from(serde_json::to_value(selector)?))
-        .execute(&self.pool)
+        .bind(selector_to_db(&selector, channels, payload_version)?)
+        .execute(&mut *conn)
         .await?;
         Ok(())
     }
     
     pub async fn delete_selector(&self, owner_id: Uuid, agent_id: Uuid, selector_id: Uuid) -> Result<()> {
-        sqlx::query("delete from selectors where id = $1 and owner_id = $2 and agent_id = $3")
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, Some(agent_id)).await?;
+        sqlx::query("delete from selector_subscriptions where id = $1 and owner_id = $2 and agent_id = $3")
             .bind(selector_id)
             .bind(owner_id)
             .bind(agent_id)
-            .execute(&self.pool)
+            .execute(&mut *conn)
             .await?;
         Ok(())
     }
     
     // ACL operations
     pub async fn list_acls(&self, owner_id: Uuid) -> Result<Vec<(Uuid, Uuid, Option<Uuid>, Vec<String>, chrono::DateTime<chrono::Utc>)>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
         let rows = sqlx::query_as::<_, (Uuid, Uuid, Option<Uuid>, Vec<String>, chrono::DateTime<chrono::Utc>)>(
-            "select id, breadcrumb_id, grantee_agent_id, actions, created_at from acl_entries where owner_id = $1 order by created_at desc"
+            "select id, breadcrumb_id, grantee_agent_id, actions::text[], created_at from acl_entries where owner_id = $1 order by created_at desc"
         )
         .bind(owner_id)
-        .fetch_all(&self.pool)
+        .fetch_all(&mut *conn)
         .await?;
         Ok(rows)
     }
@@ -712,6 +3007,28 @@ impl Db {
         Ok(row > 0)
     }
 
+    /// Roles and ACL actions on `breadcrumb_id` for each of `agent_ids`, in one query for fanout;
+    /// agents that aren't registered are missing from the result
+    pub async fn agent_read_grants(&self, owner_id: Uuid, breadcrumb_id: Uuid, agent_ids: &[Uuid]) -> Result<Vec<(Uuid, Vec<String>, Vec<String>)>> {
+        let mut conn = self.pool.acquire().await?;
+        set_rls(&mut conn, owner_id, None).await?;
+        let rows = sqlx::query_as::<_, (Uuid, Vec<String>, Vec<String>)>(
+            r#"select ag.id, ag.roles,
+                      coalesce(array_agg(distinct act::text) filter (where act is not null), '{}') as actions
+               from agents ag
+               left join acl_entries a on a.breadcrumb_id = $2 and (a.grantee_agent_id = ag.id or a.grantee_owner_id = $1)
+               left join lateral unnest(a.actions) act on true
+               where ag.owner_id = $1 and ag.id = any($3)
+               group by ag.id, ag.roles"#
+        )
+        .bind(owner_id)
+        .bind(breadcrumb_id)
+        .bind(agent_ids)
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(rows)
+    }
+
     pub async fn grant_acl_agent(&self, owner_id: Uuid, breadcrumb_id: Uuid, grantee_agent_id: Uuid, action: &str) -> Result<Uuid> {
         let mut conn = self.pool.acquire().await?;
         set_rls(&mut conn, owner_id, Some(grantee_agent_id)).await?;
@@ -755,6 +3072,63 @@ impl Db {
         .await?;
         Ok(())
     }
+
+    /// How far this database's replay trails its primary: zero when it isn't a standby or has
+    /// replayed everything it received, None while a standby has replayed nothing yet
+    pub async fn replication_lag(&self) -> Result<Option<std::time::Duration>> {
+        let secs: Option<f64> = sqlx::query_scalar(
+            r#"select case when not pg_is_in_recovery() or pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() then 0::float8
+                      else extract(epoch from now() - pg_last_xact_replay_timestamp())::float8 end"#
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(secs.map(|s| std::time::Duration::from_secs_f64(s.max(0.0))))
+    }
+}
+
+/// id, url, payload_template, payload_version, selector_id, strict_ordering
+type WebhookRow = (Uuid, String, Option<String>, Option<i16>, Option<Uuid>, bool);
+
+/// WebhookRow, then active, deactivated_reason, created_at, last_success_at, last_failure_at,
+/// consecutive_failures, total_deliveries
+type WebhookStatusRow = (Uuid, String, Option<String>, Option<i16>, Option<Uuid>, bool, bool, Option<String>, DateTime<Utc>, Option<DateTime<Utc>>, Option<DateTime<Utc>>, i32, i64);
+
+fn webhook_from_row((id, url, payload_template, payload_version, selector_id, strict_ordering): WebhookRow) -> AgentWebhook {
+    AgentWebhook { id, url, payload_template, payload_version: payload_version.map(|v| v as u16), selector_id, strict_ordering }
+}
+
+/// id, url, secret, schema_name, any_tags, event_types, created_at, strict_ordering
+type OwnerWebhookRow = (Uuid, String, Option<String>, Option<String>, Vec<String>, Vec<String>, DateTime<Utc>, bool);
+
+fn owner_webhook_from_row((id, url, secret, schema_name, any_tags, event_types, created_at, strict_ordering): OwnerWebhookRow) -> OwnerWebhook {
+    OwnerWebhook { id, url, schema_name, any_tags, event_types, created_at, has_secret: secret.is_some(), secret, strict_ordering }
+}
+
+type BreadcrumbShareRow = (Uuid, Uuid, Option<Uuid>, String, DateTime<Utc>, DateTime<Utc>, Option<DateTime<Utc>>);
+
+fn breadcrumb_share_from_row((id, breadcrumb_id, created_by, view, expires_at, created_at, revoked_at): BreadcrumbShareRow) -> BreadcrumbShare {
+    BreadcrumbShare { id, breadcrumb_id, created_by, view, expires_at, created_at, revoked_at }
+}
+
+type InboxRow = (Uuid, Uuid, i32, JsonValue, DateTime<Utc>, DateTime<Utc>, i32);
+
+fn inbox_entry_from_row((id, breadcrumb_id, version, event, created_at, leased_until, deliveries): InboxRow) -> InboxEntry {
+    InboxEntry { id, breadcrumb_id, version, event, created_at, leased_until, deliveries }
+}
+
+type TenantDeletionRow = (Uuid, String, String, Option<String>, Option<String>, Option<String>, JsonValue, Option<String>, DateTime<Utc>, DateTime<Utc>, Option<DateTime<Utc>>);
+
+fn tenant_deletion_from_row((tenant_id, tenant_name, status, stage, export_to, export_location, deleted, error, started_at, updated_at, finished_at): TenantDeletionRow) -> Result<TenantDeletion> {
+    let deleted = serde_json::from_value(deleted)?;
+    Ok(TenantDeletion { tenant_id, tenant_name, status, stage, export_to, export_location, deleted, error, started_at, updated_at, finished_at })
+}
+
+/// The `TENANT_TABLES` clause for `table`; anything else is refused before it can reach the SQL
+fn tenant_table_condition(table: &str) -> Result<&'static str> {
+    TENANT_TABLES.iter()
+        .find(|(t, _)| *t == table)
+        .map(|(_, condition)| *condition)
+        .ok_or_else(|| DbError::Invalid(format!("not a tenant table: {}", table)))
 }
 
 fn visibility_to_db(v: &Visibility) -> &'static str {
@@ -765,11 +3139,193 @@ fn sensitivity_to_db(s: &Sensitivity) -> &'static str {
     match s { Sensitivity::Low => "low", Sensitivity::Pii => "pii", Sensitivity::Secret => "secret" }
 }
 
+/// Over the context with object keys sorted, so the same checksum comes out of the jsonb read back
 fn checksum_json(v: &JsonValue) -> String {
-    let mut hasher = Sha256::new();
-    let bytes = serde_json::to_vec(v).expect("json to bytes");
-    hasher.update(bytes);
-    format!("sha256:{}", hex::encode(hasher.finalize()))
+    sha256_prefixed(&serde_json::to_vec(&sorted_keys(v)).expect("json to bytes"))
+}
+
+/// How checksums were taken before they were canonical: in the key order at hand
+fn legacy_checksum_json(v: &JsonValue) -> String {
+    sha256_prefixed(&serde_json::to_vec(v).expect("json to bytes"))
+}
+
+fn sha256_prefixed(bytes: &[u8]) -> String {
+    format!("sha256:{}", hex::encode(Sha256::digest(bytes)))
+}
+
+fn sorted_keys(v: &JsonValue) -> JsonValue {
+    match v {
+        JsonValue::Object(map) => {
+            let mut entries: Vec<(&String, &JsonValue)> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            JsonValue::Object(entries.into_iter().map(|(k, v)| (k.clone(), sorted_keys(v))).collect())
+        }
+        JsonValue::Array(items) => JsonValue::Array(items.iter().map(sorted_keys).collect()),
+        other => other.clone(),
+    }
+}
+
+/// Checksum and size of what is stored: for an encrypted context the ciphertext, so both still
+/// change with every write
+fn stored_checksum_and_size(context: &JsonValue, sealed: Option<&EncryptedContext>) -> Result<(String, i32)> {
+    Ok(match sealed {
+        Some(sealed) => (format!("sha256:{}", hex::encode(Sha256::digest(&sealed.ciphertext))), sealed.ciphertext.len() as i32),
+        None => (checksum_json(context), serde_json::to_vec(context)?.len() as i32),
+    })
+}
+
+/// A stored row as verification reads it: ciphertext if the context is encrypted
+type StoredChecksumRow = (Uuid, i32, JsonValue, Option<Vec<u8>>, String, bool);
+
+fn check_checksum(source: ChecksumSource, (breadcrumb_id, version, context, ciphertext, stored, canonical): StoredChecksumRow) -> ChecksumCheck {
+    let computed = match &ciphertext {
+        Some(ciphertext) => sha256_prefixed(ciphertext),
+        None => checksum_json(&context),
+    };
+    let status = if computed == stored {
+        ChecksumStatus::Match
+    } else if ciphertext.is_some() || canonical {
+        ChecksumStatus::Mismatch
+    } else if legacy_checksum_json(&context) == stored {
+        ChecksumStatus::Match
+    } else {
+        ChecksumStatus::Unverifiable
+    };
+    ChecksumCheck { breadcrumb_id, version, source, status, stored_checksum: stored, computed_checksum: computed }
+}
+
+async fn load_encrypted_context(conn: &mut PgConnection, id: Uuid, version: Option<i32>) -> Result<Option<EncryptedContext>> {
+    let row = match version {
+        None => sqlx::query_as::<_, (Option<Vec<u8>>, Option<Vec<u8>>, Option<String>)>(
+            "select context_encrypted, context_dek_encrypted, context_kek_id from breadcrumbs where id = $1"
+        )
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await?,
+        Some(version) => sqlx::query_as::<_, (Option<Vec<u8>>, Option<Vec<u8>>, Option<String>)>(
+            "select context_encrypted, context_dek_encrypted, context_kek_id from breadcrumb_history where breadcrumb_id = $1 and version = $2"
+        )
+        .bind(id)
+        .bind(version)
+        .fetch_optional(&mut *conn)
+        .await?,
+    };
+    Ok(match row {
+        Some((Some(ciphertext), Some(dek_encrypted), Some(kek_id))) => Some(EncryptedContext { ciphertext, dek_encrypted, kek_id }),
+        _ => None,
+    })
+}
+
+/// The body of `Db::offboard_agent`, inside the caller's transaction
+async fn offboard_agent_tx(conn: &mut PgConnection, owner_id: Uuid, agent_id: Uuid, actor_agent_id: Option<Uuid>, reason: &str) -> Result<Option<AgentOffboarding>> {
+    let locked = sqlx::query_scalar::<_, Uuid>("select id from agents where owner_id = $1 and id = $2 for update")
+        .bind(owner_id)
+        .bind(agent_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    if locked.is_none() {
+        return Ok(None);
+    }
+    let removed = agent_dependents_conn(&mut *conn, owner_id, agent_id).await?;
+    let api_key_hashes = sqlx::query_scalar::<_, String>(
+        "select hashed_key from api_keys where owner_id = $1 and agent_id = $2 and revoked_at is null"
+    )
+    .bind(owner_id)
+    .bind(agent_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    for statement in [
+        "delete from selector_subscriptions where owner_id = $1 and agent_id = $2",
+        "delete from subscriptions where owner_id = $1 and agent_id = $2",
+        "delete from agent_webhooks where agent_id = $2 and exists (select 1 from agents a where a.id = $2 and a.owner_id = $1)",
+        "delete from acl_entries where owner_id = $1 and grantee_agent_id = $2",
+        "delete from api_keys where owner_id = $1 and agent_id = $2",
+        "delete from webhook_dlq where owner_id = $1 and agent_id = $2",
+        "update breadcrumbs set created_by = null where owner_id = $1 and created_by = $2",
+        "update breadcrumbs set updated_by = null where owner_id = $1 and updated_by = $2",
+        r#"update breadcrumb_history h set updated_by = null
+           from breadcrumbs b where b.id = h.breadcrumb_id and b.owner_id = $1 and h.updated_by = $2"#,
+        "delete from agents where owner_id = $1 and id = $2",
+    ] {
+        sqlx::query(statement)
+            .bind(owner_id)
+            .bind(agent_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    sqlx::query(
+        r#"insert into agent_audit (owner_id, agent_id, actor_agent_id, action, detail)
+           values ($1, $2, $3, 'offboard', $4)"#
+    )
+    .bind(owner_id)
+    .bind(agent_id)
+    .bind(actor_agent_id)
+    .bind(serde_json::json!({ "reason": reason, "removed": removed }))
+    .execute(&mut *conn)
+    .await?;
+    Ok(Some(AgentOffboarding { removed, api_key_hashes }))
+}
+
+async fn agent_dependents_conn(conn: &mut PgConnection, owner_id: Uuid, agent_id: Uuid) -> Result<AgentDependents> {
+    let row = sqlx::query_as::<_, (i64, i64, i64, i64, i64, i64, i64)>(
+        r#"select
+             (select count(*) from selector_subscriptions where owner_id = $1 and agent_id = $2),
+             (select count(*) from subscriptions where owner_id = $1 and agent_id = $2),
+             (select count(*) from agent_webhooks where agent_id = $2 and active),
+             (select count(*) from acl_entries where owner_id = $1 and grantee_agent_id = $2),
+             (select count(*) from api_keys where owner_id = $1 and agent_id = $2 and revoked_at is null),
+             (select count(*) from webhook_dlq where owner_id = $1 and agent_id = $2),
+             (select count(*) from breadcrumbs where owner_id = $1 and (created_by = $2 or updated_by = $2))
+               + (select count(*) from breadcrumb_history h join breadcrumbs b on b.id = h.breadcrumb_id
+                  where b.owner_id = $1 and h.updated_by = $2)"#
+    )
+    .bind(owner_id)
+    .bind(agent_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    let (selector_subscriptions, subscriptions, webhooks, acl_grants, api_keys, webhook_dlq, authored_breadcrumbs) = row;
+    Ok(AgentDependents { selector_subscriptions, subscriptions, webhooks, acl_grants, api_keys, webhook_dlq, authored_breadcrumbs })
+}
+
+// Outbox row for a breadcrumb write; call inside the write's transaction
+async fn record_breadcrumb_event(conn: &mut PgConnection, owner_id: Uuid, breadcrumb_id: Uuid, version: i32, event_type: &str) -> Result<()> {
+    sqlx::query("insert into breadcrumb_outbox (owner_id, breadcrumb_id, version, event_type) values ($1, $2, $3, $4)")
+        .bind(owner_id)
+        .bind(breadcrumb_id)
+        .bind(version)
+        .bind(event_type)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+fn pool_options(owner: Uuid, agent: Option<Uuid>) -> PgPoolOptions {
+    PgPoolOptions::new()
+        .max_connections(10)
+        .after_connect(move |conn, _meta| {
+            Box::pin(async move {
+                sqlx::query("select set_config('app.current_owner_id', $1, false)")
+                    .bind(owner.to_string())
+                    .execute(&mut *conn)
+                    .await?;
+                if let Some(agent_id) = agent {
+                    sqlx::query("select set_config('app.current_agent_id', $1, false)")
+                        .bind(agent_id.to_string())
+                        .execute(&mut *conn)
+                        .await?;
+                }
+                Ok(())
+            })
+        })
+}
+
+/// Stored key for `upsert_breadcrumb_by_key`: the key tags sorted, deduplicated and comma-joined
+pub fn upsert_key(key_tags: &[String]) -> String {
+    let mut tags: Vec<&str> = key_tags.iter().map(String::as_str).collect();
+    tags.sort_unstable();
+    tags.dedup();
+    tags.join(",")
 }
 
 async fn set_rls(conn: &mut PgConnection, owner_id: Uuid, agent_id: Option<Uuid>) -> Result<()> {
@@ -813,7 +3369,53 @@ struct DbBreadcrumb {
     created_by: Option<Uuid>,
     updated_by: Option<Uuid>,
     size_bytes: i32,
+    #[sqlx(default)]
     embedding: Option<Vector>,
+    /// Selected in place of `embedding` when the vector itself isn't wanted; see `embedding_columns`
+    #[sqlx(default)]
+    has_embedding: Option<bool>,
+    #[sqlx(default)]
+    embedding_dim: Option<i32>,
+}
+
+/// The embedding part of a full-record select: the vector, or only whether there is one and its
+/// length, so a read that leaves the vector out doesn't fetch and decode it
+fn embedding_columns(include_embedding: bool) -> &'static str {
+    if include_embedding { "embedding" } else { "embedding is not null as has_embedding, vector_dims(embedding) as embedding_dim" }
+}
+
+impl From<DbBreadcrumb> for BreadcrumbContextView {
+    fn from(r: DbBreadcrumb) -> Self {
+        BreadcrumbContextView {
+            id: r.id,
+            title: r.title,
+            description: r.description,
+            semantic_version: r.semantic_version,
+            context: r.context,
+            tags: r.tags,
+            schema_name: r.schema_name,
+            llm_hints: r.llm_hints,
+            version: r.version,
+            updated_at: r.updated_at,
+        }
+    }
+}
+
+impl From<DbBreadcrumb> for BreadcrumbFull {
+    fn from(r: DbBreadcrumb) -> Self {
+        BreadcrumbFull {
+            id: r.id, owner_id: r.owner_id, title: r.title, description: r.description, semantic_version: r.semantic_version,
+            context: r.context, tags: r.tags, schema_name: r.schema_name, llm_hints: r.llm_hints,
+            visibility: match r.visibility.as_str() {"public"=>Visibility::Public, "team"=>Visibility::Team, _=>Visibility::Private},
+            sensitivity: match r.sensitivity.as_str() {"pii"=>Sensitivity::Pii, "secret"=>Sensitivity::Secret, _=>Sensitivity::Low},
+            version: r.version, checksum: r.checksum, ttl: r.ttl, ttl_type: r.ttl_type, ttl_config: r.ttl_config,
+            read_count: r.read_count, ttl_source: r.ttl_source, created_at: r.created_at, updated_at: r.updated_at,
+            created_by: r.created_by, updated_by: r.updated_by, size_bytes: r.size_bytes,
+            has_embedding: r.has_embedding.unwrap_or(r.embedding.is_some()),
+            embedding_dim: r.embedding_dim.map(|d| d as usize).or_else(|| r.embedding.as_ref().map(|e| e.as_slice().len())),
+            embedding: r.embedding
+        }
+    }
 }
 
 impl From<DbBreadcrumb> for Breadcrumb {
@@ -847,6 +3449,95 @@ impl From<DbBreadcrumb> for Breadcrumb {
 }
 
 #[derive(sqlx::FromRow)]
-struct DbSelector { id: Uuid, owner_id: Uuid, agent_id: Uuid, selector: JsonValue }
+struct DbSelector { id: Uuid, owner_id: Uuid, agent_id: Uuid, selector: JsonValue, expires_at: Option<DateTime<Utc>> }
+
+/// Channels and the pinned payload version ride along in the selector JSONB so older rows need no migration
+fn selector_to_db(selector: &Selector, channels: &[DeliveryChannel], payload_version: Option<u16>) -> Result<JsonValue> {
+    let mut value = serde_json::to_value(selector)?;
+    value["channels"] = serde_json::to_value(channels)?;
+    if let Some(version) = payload_version {
+        value["payload_version"] = version.into();
+    }
+    Ok(value)
+}
+
+impl TryFrom<DbSelector> for SelectorSubscription {
+    type Error = DbError;
+
+    fn try_from(r: DbSelector) -> Result<Self> {
+        let channels = match r.selector.get("channels") {
+            Some(channels) => serde_json::from_value(channels.clone())?,
+            None => DeliveryChannel::all(),
+        };
+        let payload_version = r.selector.get("payload_version").and_then(|v| v.as_u64()).map(|v| v as u16);
+        Ok(SelectorSubscription { id: r.id, owner_id: r.owner_id, agent_id: r.agent_id, selector: serde_json::from_value(r.selector)?, channels, expires_at: r.expires_at, payload_version })
+    }
+}
+
+
+
+#[derive(sqlx::FromRow)]
+struct DbApiKey {
+    id: Uuid,
+    owner_id: Uuid,
+    agent_id: Uuid,
+    name: Option<String>,
+    prefix: String,
+    roles: Vec<String>,
+    created_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+    revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(sqlx::FromRow)]
+struct DbAgentRun {
+    id: Uuid,
+    owner_id: Uuid,
+    agent_id: Uuid,
+    status: String,
+    input: JsonValue,
+    stages: JsonValue,
+    result: Option<JsonValue>,
+    error: Option<String>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    finished_at: Option<DateTime<Utc>>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<DbAgentRun> for AgentRun {
+    type Error = DbError;
 
+    fn try_from(r: DbAgentRun) -> Result<Self> {
+        Ok(AgentRun {
+            id: r.id,
+            owner_id: r.owner_id,
+            agent_id: r.agent_id,
+            status: r.status,
+            input: r.input,
+            stages: serde_json::from_value(r.stages)?,
+            result: r.result,
+            error: r.error,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+            finished_at: r.finished_at,
+            expires_at: r.expires_at,
+        })
+    }
+}
 
+impl From<DbApiKey> for ApiKey {
+    fn from(r: DbApiKey) -> Self {
+        ApiKey {
+            id: r.id,
+            owner_id: r.owner_id,
+            agent_id: r.agent_id,
+            name: r.name,
+            prefix: r.prefix,
+            roles: r.roles,
+            created_at: r.created_at,
+            last_used_at: r.last_used_at,
+            revoked_at: r.revoked_at,
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! Text fed to the embedding model for a breadcrumb, shared by rcrt-server (ingest and `?q=` search)
+//! and anything else that embeds breadcrumbs, so vectors stay comparable
+//!
+//! Only string leaves of the context are kept: key names, numbers, ids, URLs, hashes and base64 blobs
+//! carry no meaning for the model and crowd real text out of its window. Token-length truncation is
+//! left to the tokenizer in the embedding backend.
+
+use serde_json::Value as JsonValue;
+
+/// Keys skipped by default; `*` matches a prefix or suffix (`*_id` skips `session_id`)
+pub const DEFAULT_SKIP_KEYS: &[&str] = &[
+    "id", "*_id", "*_ids", "uuid", "url", "*_url", "uri", "href", "src", "hash", "*_hash", "checksum",
+    "sha", "sha256", "signature", "token", "*_token", "embedding", "embeddings", "vector", "base64",
+    "data_url", "image", "favicon", "mime_type",
+];
+
+/// Strings shorter than this (after trimming) are dropped
+pub const DEFAULT_MIN_LEN: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct EmbeddingTextConfig {
+    pub min_len: usize,
+    /// Lowercased key patterns; see `DEFAULT_SKIP_KEYS`
+    pub skip_keys: Vec<String>,
+}
+
+impl Default for EmbeddingTextConfig {
+    fn default() -> Self {
+        EmbeddingTextConfig {
+            min_len: DEFAULT_MIN_LEN,
+            skip_keys: DEFAULT_SKIP_KEYS.iter().map(|k| k.to_string()).collect(),
+        }
+    }
+}
+
+impl EmbeddingTextConfig {
+    /// EMBED_MIN_STRING_LEN and EMBED_SKIP_KEYS (comma-separated, replaces the defaults)
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        if let Some(min_len) = std::env::var("EMBED_MIN_STRING_LEN").ok().and_then(|s| s.parse().ok()) {
+            config.min_len = min_len;
+        }
+        if let Ok(keys) = std::env::var("EMBED_SKIP_KEYS") {
+            config.skip_keys = keys.split(',').map(|k| k.trim().to_lowercase()).filter(|k| !k.is_empty()).collect();
+        }
+        config
+    }
+
+    fn skips(&self, key: &str) -> bool {
+        let key = key.to_lowercase();
+        self.skip_keys.iter().any(|pattern| match (pattern.strip_prefix('*'), pattern.strip_suffix('*')) {
+            (Some(suffix), _) => key.ends_with(suffix),
+            (_, Some(prefix)) => key.starts_with(prefix),
+            _ => key == *pattern,
+        })
+    }
+}
+
+/// `embed_fields` of an llm_hints value: dotted paths into the context to embed instead of all of it.
+/// Instance hints take precedence over the schema's
+pub fn embed_fields(llm_hints: &JsonValue) -> Option<Vec<String>> {
+    let fields = llm_hints.get("embed_fields")?.as_array()?;
+    Some(fields.iter().filter_map(|f| f.as_str().map(str::to_string)).collect())
+}
+
+/// Title plus the context's meaningful strings, space-joined. With `embed_fields`, only those paths
+/// are walked (the skip list still applies beneath them)
+pub fn embedding_text(title: &str, context: &JsonValue, embed_fields: Option<&[String]>, config: &EmbeddingTextConfig) -> String {
+    let mut parts = Vec::new();
+    if !title.trim().is_empty() {
+        parts.push(title.trim());
+    }
+    match embed_fields {
+        Some(fields) => {
+            for path in fields {
+                if let Some(value) = path.split('.').try_fold(context, |v, key| v.get(key)) {
+                    collect(value, config, &mut parts);
+                }
+            }
+        }
+        None => collect(context, config, &mut parts),
+    }
+    parts.join(" ")
+}
+
+fn collect<'a>(value: &'a JsonValue, config: &EmbeddingTextConfig, parts: &mut Vec<&'a str>) {
+    match value {
+        JsonValue::String(s) => push_text(parts, s, config),
+        JsonValue::Array(items) => items.iter().for_each(|item| collect(item, config, parts)),
+        JsonValue::Object(map) => {
+            for (key, item) in map {
+                if !config.skips(key) {
+                    collect(item, config, parts);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn push_text<'a>(parts: &mut Vec<&'a str>, text: &'a str, config: &EmbeddingTextConfig) {
+    let text = text.trim();
+    if text.chars().count() >= config.min_len && !is_opaque(text) {
+        parts.push(text);
+    }
+}
+
+/// UUIDs, URLs, hashes and base64 under keys the skip list doesn't know about
+fn is_opaque(text: &str) -> bool {
+    if uuid::Uuid::parse_str(text).is_ok() {
+        return true;
+    }
+    if !text.contains(char::is_whitespace) {
+        if text.starts_with("http://") || text.starts_with("https://") || text.starts_with("data:") {
+            return true;
+        }
+        // Long unbroken runs of base64/hex alphabet with digits; long identifiers rarely have them
+        if text.len() >= 32 && text.chars().any(|c| c.is_ascii_digit()) && text.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '=' | '-' | '_')) {
+            return true;
+        }
+    }
+    false
+}
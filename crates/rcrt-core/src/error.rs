@@ -0,0 +1,48 @@
+//! What `Db` methods fail with, typed so callers can tell a missing row or a stale version
+//! from a broken database without matching on message text
+
+use crate::models::VersionMismatch;
+
+pub type Result<T, E = DbError> = std::result::Result<T, E>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum DbError {
+    /// No such row, or row-level security hides it from the caller
+    #[error("{0} not found")]
+    NotFound(String),
+    /// Stale expected version; carries the row as it stands. Displays as "version_mismatch"
+    #[error(transparent)]
+    VersionMismatch(#[from] VersionMismatch),
+    /// Row-level security refused the write
+    #[error("forbidden: {0}")]
+    Forbidden(String),
+    /// Unique or foreign key violation
+    #[error("conflict: {0}")]
+    Conflict(String),
+    /// The request can't be stored as given
+    #[error("invalid: {0}")]
+    Invalid(String),
+    #[error(transparent)]
+    Sqlx(sqlx::Error),
+    #[error(transparent)]
+    Serde(#[from] serde_json::Error),
+}
+
+/// Postgres insufficient_privilege, raised when a write fails an RLS policy
+const INSUFFICIENT_PRIVILEGE: &str = "42501";
+
+impl From<sqlx::Error> for DbError {
+    fn from(e: sqlx::Error) -> Self {
+        match &e {
+            sqlx::Error::RowNotFound => DbError::NotFound("row".to_string()),
+            sqlx::Error::Database(db) if db.is_unique_violation() || db.is_foreign_key_violation() => {
+                DbError::Conflict(db.message().to_string())
+            }
+            sqlx::Error::Database(db) if db.code().as_deref() == Some(INSUFFICIENT_PRIVILEGE) => {
+                DbError::Forbidden(db.message().to_string())
+            }
+            _ => DbError::Sqlx(e),
+        }
+    }
+}
+
@@ -0,0 +1,180 @@
+//! Entity extraction shared by rcrt-server (`/extract/entities`) and the context-builder worker
+
+use anyhow::Result;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::{HashMap, HashSet};
+
+/// Entity extractor using regex-based keyword extraction for hybrid search
+/// Extracts relevant keywords and entities from text to improve search accuracy
+pub struct EntityExtractor {
+    /// Important RCRT domain terms
+    domain_terms: HashSet<String>,
+    /// Regex for extracting schemas (e.g., "tool.code.v1")
+    schema_pattern: Regex,
+    /// Regex for extracting code identifiers
+    identifier_pattern: Regex,
+}
+
+impl EntityExtractor {
+    /// Initialize entity extractor
+    pub fn new() -> Result<Self> {
+        // RCRT-specific domain terms to extract
+        let domain_terms = vec![
+            // Core concepts
+            "breadcrumb", "breadcrumbs", "agent", "agents", "tool", "tools",
+            "context", "embedding", "embeddings", "semantic", "vector",
+            "schema", "schemas", "secret", "secrets", "tag", "tags",
+            
+            // Actions
+            "create", "search", "execute", "configure", "update", "delete",
+            "publish", "subscribe", "trigger", "respond",
+            
+            // Technologies
+            "deno", "typescript", "rust", "postgresql", "onnx", "gliner",
+            "docker", "jwt", "api", "sse", "pgvector",
+            
+            // Features
+            "permission", "permissions", "ui_schema", "bootstrap", "schedule",
+            "workflow", "catalog", "config", "definition",
+            
+            // Components
+            "database", "frontend", "backend", "dashboard", "runner",
+        ]
+        .into_iter()
+        .map(|s| s.to_lowercase())
+        .collect();
+        
+        // Regex for schemas (e.g., "tool.code.v1", "user.message.v1")
+        let schema_pattern = Regex::new(r"\b[a-z_]+(?:\.[a-z_]+)+\.v\d+\b")?;
+        
+        // Regex for code identifiers (camelCase, snake_case, kebab-case)
+        let identifier_pattern = Regex::new(r"\b(?:[a-z][a-z0-9_-]*|[a-z][a-zA-Z0-9]+)\b")?;
+        
+        Ok(Self {
+            domain_terms,
+            schema_pattern,
+            identifier_pattern,
+        })
+    }
+    
+    /// Extract entities and keywords from text
+    /// Returns empty result for empty text
+    pub fn extract(&self, text: &str) -> Result<ExtractedEntities> {
+        if text.is_empty() {
+            return Ok(ExtractedEntities::default());
+        }
+        
+        let text_lower = text.to_lowercase();
+        let mut entities: HashMap<String, Vec<String>> = HashMap::new();
+        let mut keywords = Vec::new();
+        
+        // Extract schemas (high priority)
+        for cap in self.schema_pattern.captures_iter(&text_lower) {
+            let schema = cap.get(0).unwrap().as_str().to_string();
+            entities
+                .entry("schema".to_string())
+                .or_default()
+                .push(schema.clone());
+            keywords.push(schema);
+        }
+        
+        // Extract domain terms
+        for term in &self.domain_terms {
+            if text_lower.contains(term) {
+                entities
+                    .entry("concept".to_string())
+                    .or_default()
+                    .push(term.clone());
+                keywords.push(term.clone());
+            }
+        }
+        
+        // Extract identifiers (lower priority - only keep unique ones)
+        for cap in self.identifier_pattern.captures_iter(&text_lower) {
+            let identifier = cap.get(0).unwrap().as_str();
+            // Filter out common words and very short identifiers
+            if identifier.len() >= 4 && self.domain_terms.contains(identifier) {
+                keywords.push(identifier.to_string());
+            }
+        }
+        
+        // Deduplicate keywords
+        keywords.sort();
+        keywords.dedup();
+        
+        Ok(ExtractedEntities { entities, keywords })
+    }
+}
+
+/// Result of entity extraction
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ExtractedEntities {
+    /// Entities grouped by type (e.g., {"tool": ["openrouter", "calculator"], "action": ["create"]})
+    pub entities: HashMap<String, Vec<String>>,
+    /// High-confidence keywords for search (lowercased, deduplicated)
+    pub keywords: Vec<String>,
+}
+
+/// `entities.extracted_by` written by the context-builder worker
+pub const EXTRACTED_BY_WORKER: &str = "gliner";
+/// `entities.extracted_by` for rcrt-server's creation-time heuristics; provisional, the worker replaces it
+pub const EXTRACTED_BY_HEURISTIC: &str = "heuristic";
+/// `entities.extracted_by` when the client supplied entity_keywords on create
+pub const EXTRACTED_BY_CLIENT: &str = "client";
+/// `entities.extracted_by` for encrypted contexts: nothing is extracted and the worker leaves them be
+pub const EXTRACTED_BY_ENCRYPTED: &str = "encrypted";
+
+impl ExtractedEntities {
+    /// The `entities` column value: entities by type plus an `extracted_by` provenance marker
+    pub fn to_entities_json(&self, extracted_by: &str) -> JsonValue {
+        let mut map: serde_json::Map<String, JsonValue> = self.entities.iter()
+            .map(|(kind, values)| (kind.clone(), JsonValue::from(values.clone())))
+            .collect();
+        map.insert("extracted_by".to_string(), JsonValue::from(extracted_by));
+        JsonValue::Object(map)
+    }
+}
+
+/// Whether the worker should extract for a row: nothing stored yet, or only the provisional
+/// heuristic result. Rows without a marker were written by the worker before provenance existed
+pub fn needs_worker_extraction(entities: Option<&JsonValue>, has_keywords: bool) -> bool {
+    match entities {
+        Some(entities) if has_keywords => {
+            entities.get("extracted_by").and_then(|v| v.as_str()) == Some(EXTRACTED_BY_HEURISTIC)
+        }
+        _ => true,
+    }
+}
+
+
+/// Collect the text fields of a breadcrumb that are worth extracting from
+pub fn breadcrumb_text(title: Option<&str>, context: &JsonValue) -> String {
+    let mut parts = Vec::new();
+    
+    // Add title
+    if let Some(title) = title {
+        if !title.is_empty() {
+            parts.push(title.to_string());
+        }
+    }
+    
+    // Add common context fields
+    for field in ["content", "description", "summary"] {
+        if let Some(value) = context.get(field).and_then(|v| v.as_str()) {
+            if !value.is_empty() {
+                parts.push(value.to_string());
+            }
+        }
+    }
+    
+    // For code breadcrumbs, add code content
+    if let Some(source) = context.get("code").and_then(|c| c.get("source")).and_then(|v| v.as_str()) {
+        if !source.is_empty() {
+            parts.push(source.to_string());
+        }
+    }
+    
+    parts.join(" ")
+}
@@ -1,4 +1,10 @@
 pub mod models;
 pub mod db;
+pub mod error;
+pub mod extraction;
+pub mod embedding_text;
+pub mod trace_context;
+pub mod telemetry;
+pub mod roles;
 
 
@@ -1,15 +1,44 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde_json::Value as JsonValue;
 use pgvector::Vector;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Visibility { Public, Team, Private }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Ordered like the database enum, so `Pii < Secret` compares as in SQL
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Sensitivity { Low, Pii, Secret }
 
+impl Sensitivity {
+    /// The database/API spelling: `low`, `pii` or `secret`
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "low" => Some(Sensitivity::Low),
+            "pii" => Some(Sensitivity::Pii),
+            "secret" => Some(Sensitivity::Secret),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self { Sensitivity::Low => "low", Sensitivity::Pii => "pii", Sensitivity::Secret => "secret" }
+    }
+}
+
+/// If-Match failure from `Db::update_breadcrumb`, carrying the row as it stands so callers can
+/// rebase without another read. Displays as "version_mismatch"
+#[derive(Debug, Clone, Serialize, thiserror::Error)]
+#[error("version_mismatch")]
+pub struct VersionMismatch {
+    pub expected_version: i32,
+    pub current_version: i32,
+    pub updated_at: DateTime<Utc>,
+    pub updated_by: Option<Uuid>,
+    pub context: JsonValue,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BreadcrumbCreate {
     pub title: String,
@@ -25,6 +54,10 @@ pub struct BreadcrumbCreate {
     pub ttl_type: Option<String>,        // 'never', 'datetime', 'duration', 'usage', 'hybrid'
     pub ttl_config: Option<JsonValue>,   // Duration spec, max_reads, etc
     pub ttl_source: Option<String>,      // 'manual', 'schema-default', 'auto-applied', 'explicit'
+    #[serde(default)]
+    pub entity_keywords: Option<Vec<String>>, // NEW: Pre-computed keywords (skips entity worker)
+    #[serde(default)]
+    pub entities: Option<JsonValue>,          // Entities JSON with extracted_by; defaults to client provenance when keywords are set
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -110,13 +143,22 @@ pub struct BreadcrumbFull {
     pub created_by: Option<Uuid>,
     pub updated_by: Option<Uuid>,
     pub size_bytes: i32,
+    /// Only selected when asked for (`?include_embedding=true`), and left out of the JSON when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub embedding: Option<Vector>,
+    /// Whether the breadcrumb has an embedding, and its length, even when the vector is left out
+    #[serde(default)]
+    pub has_embedding: bool,
+    #[serde(default)]
+    pub embedding_dim: Option<usize>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Selector {
     pub any_tags: Option<Vec<String>>,   // match if overlap
     pub all_tags: Option<Vec<String>>,   // match if all contained
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub none_tags: Option<Vec<String>>,  // NEW: reject if any present (evaluated last)
     pub schema_name: Option<String>,
     pub context_match: Option<Vec<ContextMatch>>, // simple ops on JSON paths
 }
@@ -127,6 +169,47 @@ pub struct SelectorSubscription {
     pub owner_id: Uuid,
     pub agent_id: Uuid,
     pub selector: Selector,
+    /// Where matches are delivered; stored in the selector JSONB, all channels when absent
+    #[serde(default = "DeliveryChannel::all")]
+    pub channels: Vec<DeliveryChannel>,
+    /// After this the selector no longer matches and hygiene removes it; None never expires
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Event payload version pinned for this selector's deliveries; stored in the selector JSONB, None for the default
+    #[serde(default)]
+    pub payload_version: Option<u16>,
+}
+
+impl SelectorSubscription {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|at| at <= now)
+    }
+}
+
+/// How a selector match reaches its agent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryChannel {
+    /// The agent's SSE stream, fed from its agents.{id}.events subject
+    Sse,
+    /// Every webhook the agent registered
+    Webhook,
+    /// The agents.{id}.events NATS subject
+    Nats,
+    /// The agent's durable inbox, pulled and acknowledged over GET /agents/:id/inbox
+    Inbox,
+}
+
+impl DeliveryChannel {
+    /// Selectors created before channels existed deliver to every push channel; the inbox is opt-in
+    pub fn all() -> Vec<DeliveryChannel> {
+        vec![DeliveryChannel::Sse, DeliveryChannel::Webhook, DeliveryChannel::Nats]
+    }
+
+    /// Every channel a selector can name, in canonical order
+    pub fn known() -> Vec<DeliveryChannel> {
+        vec![DeliveryChannel::Sse, DeliveryChannel::Webhook, DeliveryChannel::Nats, DeliveryChannel::Inbox]
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -136,6 +219,622 @@ pub struct ContextMatch {
     pub value: serde_json::Value,  // comparison value
 }
 
+/// Per-schema usage, from `Db::list_schema_usage`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaUsage {
+    pub schema_name: String,
+    pub count: i64,
+    pub first_seen: DateTime<Utc>,
+    pub last_used: DateTime<Utc>,
+}
+
+/// An attachment as linked to a breadcrumb, from `Db::list_breadcrumb_attachments`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttachmentMeta {
+    pub sha256: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    pub filename: Option<String>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Where an attachment's bytes live: inline in Postgres, or under a key in the server's attachment store
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttachmentBody {
+    Inline(Vec<u8>),
+    Stored(String),
+}
+
+/// Upload for `Db::attach_to_breadcrumb`; `sha256` is the lowercase hex digest of the bytes
+#[derive(Debug, Clone)]
+pub struct NewAttachment {
+    pub sha256: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    pub filename: Option<String>,
+    pub body: AttachmentBody,
+}
+
+#[derive(Debug, Clone)]
+pub enum AttachOutcome {
+    /// `deduplicated`: the tenant already held these bytes, so `body` was not written
+    Linked { meta: AttachmentMeta, deduplicated: bool },
+    /// Storing the bytes would take the tenant past `quota_bytes`; nothing was written
+    QuotaExceeded { used_bytes: i64, quota_bytes: i64 },
+}
+
+/// Attachment content, from `Db::get_attachment`
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub owner_id: Uuid,
+    pub content_type: String,
+    pub size_bytes: i64,
+    pub body: AttachmentBody,
+}
+
+/// An envelope-encrypted context: `ciphertext` is the nonce-prefixed AES-GCM of the context JSON
+/// under a per-breadcrumb DEK, `dek_encrypted` that DEK wrapped by the KEK named `kek_id`
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncryptedContext {
+    pub ciphertext: Vec<u8>,
+    pub dek_encrypted: Vec<u8>,
+    pub kek_id: String,
+}
+
+impl EncryptedContext {
+    /// What the plaintext `context` column holds for an encrypted row
+    pub fn stub() -> JsonValue {
+        serde_json::json!({ "encrypted": true })
+    }
+}
+
+/// Whether a checksum was taken over the live row or a breadcrumb_history row
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumSource { Current, History }
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumStatus {
+    Match,
+    Mismatch,
+    /// Written before checksums were canonical and no longer matching; can't be told from corruption
+    Unverifiable,
+}
+
+/// A stored checksum next to the one recomputed from the stored context (or its ciphertext)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChecksumCheck {
+    pub breadcrumb_id: Uuid,
+    pub version: i32,
+    pub source: ChecksumSource,
+    pub status: ChecksumStatus,
+    pub stored_checksum: String,
+    pub computed_checksum: String,
+}
+
+/// One page of `Db::verify_checksums_batch`
+#[derive(Debug, Clone)]
+pub struct ChecksumScan {
+    /// Breadcrumbs looked at (each with its history, if asked)
+    pub scanned: i64,
+    /// Mismatches with the owner of each breadcrumb
+    pub mismatches: Vec<(Uuid, ChecksumCheck)>,
+    pub unverifiable: i64,
+    /// Cursor for the next page; the last breadcrumb id scanned
+    pub next_after: Option<Uuid>,
+}
+
+/// The history version current at a point in time, from `Db::get_breadcrumb_history_as_of`
+#[derive(Debug, Clone)]
+pub struct HistoryAsOf {
+    pub version: i32,
+    pub context: JsonValue,
+    pub updated_at: DateTime<Utc>,
+    /// The next retained version and when it was written; None while `version` is current
+    pub next_version: Option<i32>,
+    pub superseded_at: Option<DateTime<Utc>>,
+}
+
+/// Which of a tenant's breadcrumbs `Db::purge_matching` deletes; every set field must match.
+/// `created_after` is inclusive and `created_before` exclusive, like the list filters
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PurgeFilter {
+    pub schema_name: Option<String>,
+    #[serde(default)]
+    pub all_tags: Vec<String>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_by: Option<Uuid>,
+}
+
+impl PurgeFilter {
+    /// Whether anything narrows the match; an empty filter would select every breadcrumb
+    pub fn is_narrowing(&self) -> bool {
+        self.schema_name.is_some() || !self.all_tags.is_empty() || self.created_before.is_some()
+            || self.created_after.is_some() || self.created_by.is_some()
+    }
+}
+
+/// A breadcrumb removed by `Db::purge_matching`, with what its deletion event carries
+#[derive(Debug, Clone)]
+pub struct DeletedBreadcrumb {
+    pub id: Uuid,
+    pub title: String,
+    pub tags: Vec<String>,
+    pub schema_name: Option<String>,
+    pub visibility: String,
+    pub sensitivity: String,
+    pub created_by: Option<Uuid>,
+}
+
+/// Outcome of `Db::upsert_breadcrumb_by_key`
+#[derive(Debug, Clone)]
+pub struct UpsertedBreadcrumb {
+    pub breadcrumb: Breadcrumb,
+    pub created: bool,
+    /// Older rows with the same key, expired by this upsert
+    pub superseded: Vec<Uuid>,
+}
+
+/// Relation of a declared reference that doesn't name one
+pub const DEFAULT_RELATION: &str = "related";
+/// Relation the context-builder follows as a causal edge, from a breadcrumb to what triggered it
+pub const TRIGGERED_BY: &str = "triggered_by";
+
+fn default_relation() -> String {
+    DEFAULT_RELATION.to_string()
+}
+
+/// A reference declared on create/update, in the `references` array or the context's `$refs` block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NewBreadcrumbReference {
+    /// Where in the context the id lives, e.g. `trigger_event_id`
+    pub field: String,
+    pub breadcrumb_id: Uuid,
+    #[serde(default = "default_relation")]
+    pub relation: String,
+}
+
+/// A stored reference, from `Db::list_breadcrumb_references` and `Db::list_breadcrumb_referenced_by`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BreadcrumbReference {
+    pub source_id: Uuid,
+    pub field: String,
+    pub target_id: Uuid,
+    pub relation: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// An incoming reference a forced delete broke, with what the referencing breadcrumb's
+/// breadcrumb.reference_broken event carries
+#[derive(Debug, Clone)]
+pub struct BrokenReference {
+    pub source_id: Uuid,
+    pub field: String,
+    pub relation: String,
+    pub title: String,
+    pub tags: Vec<String>,
+    pub schema_name: Option<String>,
+    pub visibility: String,
+    pub sensitivity: String,
+    pub created_by: Option<Uuid>,
+}
+
+/// Outcome of `Db::delete_breadcrumb_unless_referenced`
+#[derive(Debug, Clone)]
+pub enum ReferencedDelete {
+    NotFound,
+    /// Other breadcrumbs reference it and the delete wasn't forced; nothing was removed
+    Referenced(Vec<BreadcrumbReference>),
+    Deleted { schema_name: Option<String>, broken: Vec<BrokenReference> },
+}
+
+/// An agent API key without its secret, from `Db::list_api_keys` and `Db::find_api_key`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub owner_id: Uuid,
+    pub agent_id: Uuid,
+    pub name: Option<String>,
+    /// First characters of the plaintext key, for telling keys apart
+    pub prefix: String,
+    pub roles: Vec<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub last_used_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// One stage of an agent run; `output` is set once it succeeds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentRunStage {
+    pub name: String,
+    /// running, succeeded or failed
+    pub status: String,
+    pub output: Option<String>,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// A POST /agents/run pipeline run, from `Db::get_agent_run`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentRun {
+    pub id: Uuid,
+    pub owner_id: Uuid,
+    pub agent_id: Uuid,
+    /// running, succeeded, failed or cancelled
+    pub status: String,
+    pub input: serde_json::Value,
+    pub stages: Vec<AgentRunStage>,
+    pub result: Option<serde_json::Value>,
+    pub error: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// When a finished run is deleted
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// A staged DELETE /tenants/:id run, from `Db::get_tenant_deletion`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TenantDeletion {
+    pub tenant_id: Uuid,
+    pub tenant_name: String,
+    /// exporting, deleting, completed or failed
+    pub status: String,
+    /// The step in progress, or where a failed run stopped: export, blobs, a table name or tenant
+    pub stage: Option<String>,
+    /// URL the snapshot is PUT to; None writes it to the attachment store
+    pub export_to: Option<String>,
+    /// Where the snapshot went, once it is written
+    pub export_location: Option<String>,
+    /// Rows deleted so far by table
+    pub deleted: std::collections::BTreeMap<String, i64>,
+    pub error: Option<String>,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// A share link to one breadcrumb, from `Db::list_breadcrumb_shares`. The signed token naming it is
+/// only returned when the link is created
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BreadcrumbShare {
+    /// Also the token's `jti`
+    pub id: Uuid,
+    pub breadcrumb_id: Uuid,
+    pub created_by: Option<Uuid>,
+    /// What the link shows; only `context` (the context view with llm_hints applied) so far
+    pub view: String,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// What still points at an agent, from `Db::agent_dependents`; `Db::offboard_agent` removes it all
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AgentDependents {
+    pub selector_subscriptions: i64,
+    pub subscriptions: i64,
+    pub webhooks: i64,
+    /// ACL entries of the owner's breadcrumbs granted to the agent
+    pub acl_grants: i64,
+    /// Unrevoked keys
+    pub api_keys: i64,
+    pub webhook_dlq: i64,
+    /// Breadcrumbs and history versions with the agent as created_by/updated_by; offboarding
+    /// keeps them and clears the reference
+    pub authored_breadcrumbs: i64,
+}
+
+impl AgentDependents {
+    pub fn is_empty(&self) -> bool {
+        *self == AgentDependents::default()
+    }
+}
+
+/// Outcome of `Db::offboard_agent`
+#[derive(Debug, Clone)]
+pub struct AgentOffboarding {
+    pub removed: AgentDependents,
+    /// Hashes of the keys that went with the agent, for evicting cached lookups
+    pub api_key_hashes: Vec<String>,
+}
+
+/// A webhook, from `Db::list_agent_webhooks` (active) or `Db::list_inactive_agent_webhooks`; with its
+/// state and delivery stats in a `WebhookStatus`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentWebhook {
+    pub id: Uuid,
+    pub url: String,
+    /// Handlebars template for the request body; None sends the raw event
+    pub payload_template: Option<String>,
+    /// Event payload version sent to this webhook; None defers to the matching selectors
+    pub payload_version: Option<u16>,
+    /// The one selector of its agent whose matches it receives; None receives every match
+    pub selector_id: Option<Uuid>,
+    /// Hold a breadcrumb's later versions back while an earlier delivery of it is still retrying
+    #[serde(default)]
+    pub strict_ordering: bool,
+}
+
+/// A webhook with its state and delivery stats, from `Db::list_agent_webhook_statuses`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookStatus {
+    #[serde(flatten)]
+    pub webhook: AgentWebhook,
+    pub active: bool,
+    /// Why it was deactivated, when that was recorded
+    pub deactivated_reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_success_at: Option<DateTime<Utc>>,
+    pub last_failure_at: Option<DateTime<Utc>>,
+    /// Failed deliveries since the last success, or since the URL was registered again
+    pub consecutive_failures: i32,
+    /// Deliveries that finished, delivered or failed; test deliveries and DLQ retries don't count
+    pub total_deliveries: i64,
+}
+
+/// Sort for `Db::list_agent_webhook_statuses`; all but `CreatedAt` put the largest or latest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookOrder {
+    /// Oldest first
+    CreatedAt,
+    LastSuccessAt,
+    LastFailureAt,
+    ConsecutiveFailures,
+    TotalDeliveries,
+}
+
+impl WebhookOrder {
+    /// The API spelling: `created_at`, `last_success_at`, `last_failure_at`, `consecutive_failures` or `total_deliveries`
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "created_at" => Some(WebhookOrder::CreatedAt),
+            "last_success_at" => Some(WebhookOrder::LastSuccessAt),
+            "last_failure_at" => Some(WebhookOrder::LastFailureAt),
+            "consecutive_failures" => Some(WebhookOrder::ConsecutiveFailures),
+            "total_deliveries" => Some(WebhookOrder::TotalDeliveries),
+            _ => None,
+        }
+    }
+}
+
+/// A queued event in an agent's inbox, from `Db::lease_inbox`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InboxEntry {
+    pub id: Uuid,
+    pub breadcrumb_id: Uuid,
+    pub version: i32,
+    pub event: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+    /// Hidden from other pollers until then; unacked, it is handed out again afterwards
+    pub leased_until: DateTime<Utc>,
+    /// How many times the entry has been leased, this one included
+    pub deliveries: i32,
+}
+
+/// A webhook the owner registered for an integration rather than an agent, from `Db::list_owner_webhooks`.
+/// It receives the breadcrumb events its schema, tags and event types match, signed with its own secret
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OwnerWebhook {
+    pub id: Uuid,
+    pub url: String,
+    /// Only breadcrumbs of this schema; None matches any
+    pub schema_name: Option<String>,
+    /// Only breadcrumbs carrying one of these tags (patterns as in selectors); empty matches any
+    pub any_tags: Vec<String>,
+    /// `breadcrumb.created` and/or `breadcrumb.updated`; empty matches both
+    pub event_types: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    /// Whether deliveries are signed; the secret itself is never returned
+    pub has_secret: bool,
+    /// Hold a breadcrumb's later versions back while an earlier delivery of it is still retrying
+    pub strict_ordering: bool,
+    #[serde(skip)]
+    pub secret: Option<String>,
+}
+
+impl OwnerWebhook {
+    /// Event types an owner webhook can filter on
+    pub const EVENT_TYPES: [&'static str; 2] = ["breadcrumb.created", "breadcrumb.updated"];
+
+    /// Its schema and tag filters as a selector, for the shared matcher
+    pub fn selector(&self) -> Selector {
+        Selector {
+            any_tags: (!self.any_tags.is_empty()).then(|| self.any_tags.clone()),
+            all_tags: None,
+            none_tags: None,
+            schema_name: self.schema_name.clone(),
+            context_match: None,
+        }
+    }
+
+    pub fn wants(&self, event_type: &str) -> bool {
+        self.event_types.is_empty() || self.event_types.iter().any(|t| t == event_type)
+    }
+}
+
+/// An owner's agents, selector subscriptions and active webhooks, from `Db::export_topology` and
+/// for `Db::import_topology`. Webhook secrets, API keys and breadcrumbs are not part of it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Topology {
+    #[serde(default)]
+    pub agents: Vec<TopologyAgent>,
+    #[serde(default)]
+    pub selectors: Vec<TopologySelector>,
+    #[serde(default)]
+    pub webhooks: Vec<TopologyWebhook>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TopologyAgent {
+    pub id: Uuid,
+    pub roles: Vec<String>,
+}
+
+/// A selector subscription under its own id, so webhooks in the same document can bind to it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopologySelector {
+    pub id: Uuid,
+    pub agent_id: Uuid,
+    pub selector: Selector,
+    #[serde(default = "DeliveryChannel::all")]
+    pub channels: Vec<DeliveryChannel>,
+    #[serde(default)]
+    pub payload_version: Option<u16>,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// A webhook, identified by its agent and URL as registration does
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TopologyWebhook {
+    pub agent_id: Uuid,
+    pub url: String,
+    #[serde(default)]
+    pub payload_template: Option<String>,
+    #[serde(default)]
+    pub payload_version: Option<u16>,
+    /// One of the document's selectors of the same agent
+    #[serde(default)]
+    pub selector_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TopologyKind {
+    Agent,
+    Selector,
+    Webhook,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TopologyAction {
+    Created,
+    Updated,
+    /// Already as the document has it
+    Skipped,
+    /// Not in the document and pruned: agents offboarded, selectors deleted, webhooks deactivated
+    Removed,
+}
+
+/// What an import did to one agent, selector or webhook
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TopologyItem {
+    pub kind: TopologyKind,
+    /// Position in the document's list of that kind; None for pruned items
+    pub index: Option<usize>,
+    /// The agent's, selector's or webhook's id
+    pub id: Uuid,
+    pub agent_id: Uuid,
+    /// Webhooks only
+    pub url: Option<String>,
+    pub action: TopologyAction,
+}
+
+/// From `Db::import_topology`
+#[derive(Debug, Clone, Default)]
+pub struct TopologyImport {
+    pub items: Vec<TopologyItem>,
+    /// Hashes of the API keys of pruned agents, for evicting cached lookups
+    pub api_key_hashes: Vec<String>,
+}
+
+/// A session's counters from the session_stats table, kept by triggers on breadcrumbs; from `Db::list_session_stats`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionStats {
+    pub session_tag: String,
+    pub breadcrumb_count: i64,
+    /// user.message.v1 and agent.response.v1 breadcrumbs
+    pub message_count: i64,
+    pub last_activity_at: DateTime<Utc>,
+    /// Creators of the session's breadcrumbs, most breadcrumbs first
+    pub agents: Vec<Uuid>,
+}
+
+/// Sort for `Db::list_session_stats`, always descending
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionOrder {
+    LastActivity,
+    BreadcrumbCount,
+    MessageCount,
+}
+
+impl SessionOrder {
+    /// The API spelling: `last_activity`, `breadcrumb_count` or `message_count`
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "last_activity" => Some(SessionOrder::LastActivity),
+            "breadcrumb_count" => Some(SessionOrder::BreadcrumbCount),
+            "message_count" => Some(SessionOrder::MessageCount),
+            _ => None,
+        }
+    }
+}
+
+/// What a breadcrumb_access counter counts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessType {
+    /// GET /breadcrumbs/:id, /full and bulk_get
+    ApiRead,
+    /// Included in an assembled context (bulk_get with `access: context_assembly`)
+    ContextAssembly,
+    /// Returned by GET /breadcrumbs/search
+    SearchHit,
+}
+
+impl AccessType {
+    /// The API and column spelling: `api_read`, `context_assembly` or `search_hit`
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "api_read" => Some(AccessType::ApiRead),
+            "context_assembly" => Some(AccessType::ContextAssembly),
+            "search_hit" => Some(AccessType::SearchHit),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccessType::ApiRead => "api_read",
+            AccessType::ContextAssembly => "context_assembly",
+            AccessType::SearchHit => "search_hit",
+        }
+    }
+
+    /// Whether it also bumps the breadcrumb's read_count; a search hit may never be looked at
+    pub fn is_read(&self) -> bool {
+        !matches!(self, AccessType::SearchHit)
+    }
+}
+
+/// One day's accesses of a breadcrumb by one agent; written by `Db::record_breadcrumb_access`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccessCount {
+    pub owner_id: Uuid,
+    pub breadcrumb_id: Uuid,
+    pub agent_id: Uuid,
+    pub access_type: AccessType,
+    pub day: NaiveDate,
+    pub count: i64,
+}
+
+/// A breadcrumb's accesses of one type over a window, from `Db::top_accessed_breadcrumbs`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TopBreadcrumb {
+    pub breadcrumb_id: Uuid,
+    pub title: String,
+    pub schema_name: Option<String>,
+    pub count: i64,
+    /// Distinct agents behind `count`
+    pub agents: i64,
+    pub last_day: NaiveDate,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AclGrantAgent {
     pub breadcrumb_id: Uuid,
@@ -144,3 +843,87 @@ pub struct AclGrantAgent {
 }
 
 
+
+/// Schema of an owner's embedding model switch; the newest one names the model search and the
+/// context-builder rank by
+pub const EMBEDDING_CONFIG_SCHEMA: &str = "system.embedding.config.v1";
+/// The model behind breadcrumbs.embedding (vector(384)) and the embedding_model default; the active
+/// model of owners without a system.embedding.config.v1
+pub const COLUMN_EMBEDDING_MODEL: &str = "all-MiniLM-L6-v2";
+
+/// Context of a system.embedding.config.v1 breadcrumb: `{"active_model": "bge-base-en-v1.5"}`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmbeddingModelConfig {
+    pub active_model: String,
+}
+
+impl Default for EmbeddingModelConfig {
+    fn default() -> Self {
+        EmbeddingModelConfig { active_model: COLUMN_EMBEDDING_MODEL.to_string() }
+    }
+}
+
+/// SQL over a `breadcrumbs` row for the active model's vector, from `EmbeddingModelConfig::vector_sql`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelVectorSql {
+    /// The row's vector, NULL where the model hasn't embedded it
+    pub vector: String,
+    /// True for rows the model has embedded
+    pub embedded: String,
+}
+
+impl EmbeddingModelConfig {
+    /// Model names end up in SQL, so they are limited to letters, digits and `.-_:/@`, up to 100 characters
+    pub fn valid_model_name(name: &str) -> bool {
+        !name.is_empty() && name.len() <= 100
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || ".-_:/@".contains(c))
+    }
+
+    /// The error is fit for a 422
+    pub fn from_context(context: &JsonValue) -> Result<Self, String> {
+        let fields = context.as_object().ok_or("context must be an object")?;
+        if let Some(unknown) = fields.keys().find(|k| *k != "active_model") {
+            return Err(format!("unknown field {}", unknown));
+        }
+        let model = fields.get("active_model").and_then(|m| m.as_str()).ok_or("active_model must be a string")?;
+        if !Self::valid_model_name(model) {
+            return Err(format!("active_model {:?} may only use letters, digits and .-_:/@ (up to 100)", model));
+        }
+        Ok(EmbeddingModelConfig { active_model: model.to_string() })
+    }
+
+    /// Whether the active model's vectors are in breadcrumbs.embedding rather than breadcrumb_embeddings
+    pub fn in_column(&self) -> bool {
+        self.active_model == COLUMN_EMBEDDING_MODEL
+    }
+
+    /// The column keeps its ivfflat index; another model's vectors are looked up per row
+    pub fn vector_sql(&self) -> ModelVectorSql {
+        // Validated names only: no quote can reach the literal
+        let model = if Self::valid_model_name(&self.active_model) { self.active_model.as_str() } else { COLUMN_EMBEDDING_MODEL };
+        if model == COLUMN_EMBEDDING_MODEL {
+            ModelVectorSql {
+                vector: "embedding".to_string(),
+                embedded: format!("(embedding is not null and embedding_model = '{}')", model),
+            }
+        } else {
+            let lookup = format!("from breadcrumb_embeddings me where me.breadcrumb_id = breadcrumbs.id and me.model = '{}'", model);
+            ModelVectorSql {
+                vector: format!("(select me.embedding {})", lookup),
+                embedded: format!("exists (select 1 {})", lookup),
+            }
+        }
+    }
+}
+
+/// How far a model's vectors cover the owner's embedded breadcrumbs, from `Db::embedding_coverage`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EmbeddingCoverage {
+    pub model: String,
+    /// Breadcrumbs with a vector from `model`
+    pub embedded: i64,
+    /// Breadcrumbs with a vector from any model
+    pub eligible: i64,
+    /// embedded / eligible; 1 when nothing is eligible
+    pub ratio: f64,
+}
@@ -0,0 +1,88 @@
+//! Roles
+//! The role vocabulary tokens, API keys and agent registrations draw from. Names are matched
+//! case-insensitively and stored lowercase; anything outside `Role::ALL` is rejected where it
+//! enters the system (token validation, registration, key creation) rather than quietly
+//! granting nothing.
+//!
+//! To add a role: add a variant, its name in `as_str`, and an entry in `ALL`. `FromStr`, serde
+//! and the error listing valid roles follow from those.
+
+use std::fmt;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase", try_from = "String")]
+pub enum Role {
+    /// Create, update and delete any breadcrumb
+    Curator,
+    /// Create breadcrumbs
+    Emitter,
+    /// Subscribe to events and read breadcrumbs
+    Subscriber,
+    /// The /admin routes
+    Admin,
+}
+
+impl Role {
+    /// Every role, in the order error messages list them
+    pub const ALL: &'static [Role] = &[Role::Curator, Role::Emitter, Role::Subscriber, Role::Admin];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Curator => "curator",
+            Role::Emitter => "emitter",
+            Role::Subscriber => "subscriber",
+            Role::Admin => "admin",
+        }
+    }
+
+    /// All of `names`, or the first one that isn't a role
+    pub fn parse_all<S: AsRef<str>>(names: &[S]) -> Result<Vec<Role>, UnknownRole> {
+        names.iter().map(|name| name.as_ref().parse()).collect()
+    }
+
+    /// Lowercase names, as stored in agents.roles and api_keys.roles
+    pub fn names(roles: &[Role]) -> Vec<String> {
+        roles.iter().map(|role| role.as_str().to_string()).collect()
+    }
+
+    /// Whether `names` (e.g. roles read back from the database) include this role
+    pub fn is_named_in<S: AsRef<str>>(self, names: &[S]) -> bool {
+        names.iter().any(|name| name.as_ref().parse::<Role>() == Ok(self))
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A name outside `Role::ALL`; displays with the valid roles
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("unknown role '{0}'; valid roles are {}", valid_roles())]
+pub struct UnknownRole(pub String);
+
+fn valid_roles() -> String {
+    Role::ALL.iter().map(Role::as_str).collect::<Vec<_>>().join(", ")
+}
+
+impl FromStr for Role {
+    type Err = UnknownRole;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim();
+        Role::ALL.iter().copied()
+            .find(|role| role.as_str().eq_ignore_ascii_case(name))
+            .ok_or_else(|| UnknownRole(s.to_string()))
+    }
+}
+
+impl TryFrom<String> for Role {
+    type Error = UnknownRole;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
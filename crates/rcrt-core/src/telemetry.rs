@@ -0,0 +1,326 @@
+//! Telemetry
+//! Traces shared by rcrt-server and the context-builder. Every span a service opens gets a trace and
+//! span id, continued from the caller's `traceparent` (a request header, or the field the server
+//! stamps on breadcrumb events), so one trace follows a user message across services. With the
+//! `otel` feature and OTEL_EXPORTER_OTLP_ENDPOINT set, finished spans are exported over OTLP/HTTP
+//! JSON. Log output stays with each service, which hands its log layer to `init`
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Level, Subscriber};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Registry;
+
+use crate::trace_context::{SpanRecord, TraceContext, TRACEPARENT};
+
+/// Receives each span as it closes
+pub trait SpanExporter: Send + Sync {
+    fn export(&self, span: SpanRecord);
+}
+
+/// Install `log` and tracing for `service`, whose own spans are the info spans of `own_crate` (e.g.
+/// "rcrt_server") and rcrt_core; call once, inside the runtime
+pub fn init<L>(service: &str, own_crate: &str, log: L)
+where
+    L: Layer<Registry> + Send + Sync + 'static,
+{
+    tracing_subscriber::registry()
+        .with(log)
+        .with(TraceLayer::new(exporter(service)).with_filter(own_spans(own_crate)))
+        .init();
+}
+
+/// RUST_LOG only decides what is logged; a service's info spans are always traced
+pub fn own_spans(own_crate: &str) -> Targets {
+    Targets::new().with_target(own_crate, Level::INFO).with_target("rcrt_core", Level::INFO)
+}
+
+#[cfg(feature = "otel")]
+fn exporter(service: &str) -> Option<Arc<dyn SpanExporter>> {
+    otlp::OtlpExporter::from_env(service).map(|e| Arc::new(e) as Arc<dyn SpanExporter>)
+}
+
+#[cfg(not(feature = "otel"))]
+fn exporter(_service: &str) -> Option<Arc<dyn SpanExporter>> {
+    if std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").is_ok() || std::env::var("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT").is_ok() {
+        eprintln!("OTEL_EXPORTER_OTLP_ENDPOINT is set, but this build has no `otel` feature; spans are not exported");
+    }
+    None
+}
+
+/// Keeps every finished span in memory; for tests asserting on spans
+#[derive(Default)]
+pub struct Capture(std::sync::Mutex<Vec<SpanRecord>>);
+
+impl SpanExporter for Capture {
+    fn export(&self, span: SpanRecord) {
+        self.0.lock().unwrap().push(span);
+    }
+}
+
+impl Capture {
+    /// A subscriber for this thread tracing `own_crate`'s spans into a new Capture
+    pub fn install(own_crate: &str) -> (Arc<Capture>, tracing::subscriber::DefaultGuard) {
+        let capture = Arc::new(Capture::default());
+        let guard = tracing_subscriber::registry()
+            .with(TraceLayer::new(Some(capture.clone() as Arc<dyn SpanExporter>)).with_filter(own_spans(own_crate)))
+            .set_default();
+        (capture, guard)
+    }
+
+    pub fn spans(&self) -> Vec<SpanRecord> {
+        self.0.lock().unwrap().clone()
+    }
+
+    pub fn named(&self, name: &str) -> Vec<SpanRecord> {
+        self.0.lock().unwrap().iter().filter(|s| s.name == name).cloned().collect()
+    }
+}
+
+/// The `traceparent` of the current span, for outgoing calls and events; None outside any traced span
+pub fn current_traceparent() -> Option<String> {
+    tracing::Span::current().with_subscriber(|(id, dispatch)| {
+        let registry = dispatch.downcast_ref::<Registry>()?;
+        let span = registry.span(id)?;
+        // The current span may be one the trace layer filters out; its nearest traced ancestor stands in
+        span.scope().find_map(|s| s.extensions().get::<SpanState>().map(|state| state.context.header()))
+    }).flatten()
+}
+
+struct SpanState {
+    context: TraceContext,
+    parent_span_id: Option<[u8; 8]>,
+    start: SystemTime,
+    attributes: Vec<(String, String)>,
+}
+
+/// Span fields as attributes; a `traceparent` field names a remote parent instead
+#[derive(Default)]
+struct Fields {
+    traceparent: Option<String>,
+    attributes: Vec<(String, String)>,
+}
+
+impl Fields {
+    fn push(&mut self, field: &Field, value: String) {
+        if field.name() == TRACEPARENT {
+            self.traceparent = Some(value);
+        } else {
+            self.attributes.push((field.name().to_string(), value));
+        }
+    }
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.push(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.push(field, format!("{:?}", value));
+    }
+}
+
+/// Assigns trace and span ids and hands closed spans to the exporter, if any
+pub struct TraceLayer {
+    exporter: Option<Arc<dyn SpanExporter>>,
+}
+
+impl TraceLayer {
+    pub fn new(exporter: Option<Arc<dyn SpanExporter>>) -> Self {
+        TraceLayer { exporter }
+    }
+}
+
+impl<S> Layer<S> for TraceLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        // A local parent wins; a root span continues the caller's trace when it names one
+        let parent = span.parent().and_then(|p| p.extensions().get::<SpanState>().map(|state| state.context))
+            .or_else(|| fields.traceparent.as_deref().and_then(TraceContext::parse));
+        let (context, parent_span_id) = match parent {
+            Some(parent) => (parent.child(), Some(parent.span_id)),
+            None => (TraceContext::new_root(), None),
+        };
+        span.extensions_mut().insert(SpanState { context, parent_span_id, start: SystemTime::now(), attributes: fields.attributes });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut fields = Fields::default();
+        values.record(&mut fields);
+        if let Some(state) = span.extensions_mut().get_mut::<SpanState>() {
+            state.attributes.extend(fields.attributes);
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(exporter) = &self.exporter else { return };
+        let Some(span) = ctx.span(&id) else { return };
+        let Some(state) = span.extensions_mut().remove::<SpanState>() else { return };
+        if !state.context.sampled {
+            return;
+        }
+        exporter.export(SpanRecord {
+            name: span.name().to_string(),
+            context: state.context,
+            parent_span_id: state.parent_span_id,
+            start: state.start,
+            end: SystemTime::now(),
+            attributes: state.attributes,
+        });
+    }
+}
+
+#[cfg(feature = "otel")]
+mod otlp {
+    use std::time::Duration;
+    use crate::trace_context::{otlp_json, SpanRecord};
+    use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+    use tokio::sync::mpsc;
+
+    use super::SpanExporter;
+
+    /// Spans queued for export; more are dropped until the queue drains
+    const QUEUE: usize = 4096;
+    const MAX_BATCH: usize = 512;
+
+    /// Batches spans to an OTLP/HTTP collector, configured by the standard variables:
+    /// OTEL_EXPORTER_OTLP_ENDPOINT (…/v1/traces is appended) or OTEL_EXPORTER_OTLP_TRACES_ENDPOINT (used as is),
+    /// OTEL_EXPORTER_OTLP_HEADERS (`k=v,k2=v2`), OTEL_SERVICE_NAME and OTEL_BSP_SCHEDULE_DELAY (ms, default 5000)
+    pub struct OtlpExporter {
+        tx: mpsc::Sender<SpanRecord>,
+    }
+
+    impl OtlpExporter {
+        pub fn from_env(service: &str) -> Option<Self> {
+            let var = |name: &str| std::env::var(name).ok().filter(|v| !v.trim().is_empty());
+            let url = var("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT")
+                .or_else(|| var("OTEL_EXPORTER_OTLP_ENDPOINT").map(|base| format!("{}/v1/traces", base.trim_end_matches('/'))))?;
+            if let Some(protocol) = var("OTEL_EXPORTER_OTLP_PROTOCOL").filter(|p| p != "http/json") {
+                eprintln!("OTEL_EXPORTER_OTLP_PROTOCOL={} is not supported; exporting http/json to {}", protocol, url);
+            }
+            let headers = var("OTEL_EXPORTER_OTLP_HEADERS").map(|h| parse_headers(&h)).unwrap_or_default();
+            let service = var("OTEL_SERVICE_NAME").unwrap_or_else(|| service.to_string());
+            let delay = Duration::from_millis(var("OTEL_BSP_SCHEDULE_DELAY").and_then(|s| s.parse().ok()).unwrap_or(5000));
+            let (tx, rx) = mpsc::channel(QUEUE);
+            tokio::spawn(run(rx, url, headers, service, delay));
+            Some(OtlpExporter { tx })
+        }
+    }
+
+    impl SpanExporter for OtlpExporter {
+        fn export(&self, span: SpanRecord) {
+            let _ = self.tx.try_send(span);
+        }
+    }
+
+    /// Values are percent-decoded, as the spec allows them to be encoded
+    fn parse_headers(raw: &str) -> HeaderMap {
+        raw.split(',').filter_map(|pair| {
+            let (name, value) = pair.split_once('=')?;
+            let value = percent_encoding::percent_decode_str(value.trim()).decode_utf8().ok()?;
+            Some((HeaderName::try_from(name.trim()).ok()?, HeaderValue::from_str(&value).ok()?))
+        }).collect()
+    }
+
+    async fn run(mut rx: mpsc::Receiver<SpanRecord>, url: String, headers: HeaderMap, service: String, delay: Duration) {
+        let client = reqwest::Client::new();
+        let mut tick = tokio::time::interval(delay);
+        let mut batch = Vec::new();
+        loop {
+            let open = tokio::select! {
+                span = rx.recv() => match span {
+                    Some(span) => {
+                        batch.push(span);
+                        if batch.len() < MAX_BATCH {
+                            continue;
+                        }
+                        true
+                    }
+                    None => false,
+                },
+                _ = tick.tick() => true,
+            };
+            if !batch.is_empty() {
+                let body = otlp_json(&service, &std::mem::take(&mut batch));
+                let sent = client.post(&url).headers(headers.clone()).json(&body).send().await.and_then(|r| r.error_for_status());
+                if let Err(e) = sent {
+                    tracing::warn!("OTLP export to {} failed: {}", url, e);
+                }
+            }
+            if !open {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::Instrument;
+
+    const REMOTE: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn test_traceparent_round_trip() {
+        let context = TraceContext::parse(REMOTE).unwrap();
+        assert_eq!(context.header(), REMOTE);
+        assert_eq!(context.child().trace_id, context.trace_id);
+        assert!(!TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00").unwrap().sampled);
+        // Later versions may append fields
+        assert!(TraceContext::parse("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra").is_some());
+        for bad in [
+            "", "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7", "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00F067AA0BA902B7-01", "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+        ] {
+            assert_eq!(TraceContext::parse(bad), None, "{}", bad);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spans_nest_under_the_callers_trace() {
+        let (capture, _guard) = Capture::install("rcrt_core");
+        let remote = TraceContext::parse(REMOTE).unwrap();
+
+        let request = tracing::info_span!("request", traceparent = REMOTE, path = "/breadcrumbs");
+        let sent = async {
+            async {}.instrument(tracing::info_span!("db", query = "create_breadcrumb")).await;
+            tracing::info_span!("embedding").in_scope(|| {});
+            current_traceparent()
+        }.instrument(request).await;
+        assert_eq!(current_traceparent(), None);
+
+        let request = &capture.named("request")[0];
+        assert_eq!(request.context.trace_id, remote.trace_id);
+        assert_eq!(request.parent_span_id, Some(remote.span_id));
+        assert_eq!(request.attributes, vec![("path".to_string(), "/breadcrumbs".to_string())]);
+        // Outgoing calls carry the request's own span
+        assert_eq!(sent, Some(request.context.header()));
+        for child in ["db", "embedding"] {
+            let child = &capture.named(child)[0];
+            assert_eq!(child.context.trace_id, remote.trace_id);
+            assert_eq!(child.parent_span_id, Some(request.context.span_id));
+        }
+        assert_eq!(capture.named("db")[0].attributes, vec![("query".to_string(), "create_breadcrumb".to_string())]);
+
+        // No traceparent starts a trace of its own
+        tracing::info_span!("request").in_scope(|| {});
+        let fresh = &capture.named("request")[1];
+        assert_eq!(fresh.parent_span_id, None);
+        assert_ne!(fresh.context.trace_id, remote.trace_id);
+    }
+}
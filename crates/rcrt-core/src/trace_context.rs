@@ -0,0 +1,114 @@
+//! W3C Trace Context
+//! The `traceparent` header rcrt-server, the context-builder and the dashboard pass along so one trace
+//! follows a request across services, and the OTLP/HTTP JSON encoding of finished spans their exporters send
+
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+/// Header (and breadcrumb event field) carrying the caller's span
+pub const TRACEPARENT: &str = "traceparent";
+
+/// A span's place in a trace: `version-trace_id-span_id-flags`, e.g.
+/// `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceContext {
+    pub trace_id: [u8; 16],
+    pub span_id: [u8; 8],
+    pub sampled: bool,
+}
+
+impl TraceContext {
+    /// The first span of a new trace
+    pub fn new_root() -> Self {
+        TraceContext { trace_id: *Uuid::new_v4().as_bytes(), span_id: new_span_id(), sampled: true }
+    }
+
+    /// A span under this one, in the same trace
+    pub fn child(&self) -> Self {
+        TraceContext { span_id: new_span_id(), ..*self }
+    }
+
+    /// None for anything but a well-formed header with non-zero ids. Versions after 00 are read by
+    /// their first four fields, as the spec asks
+    pub fn parse(header: &str) -> Option<Self> {
+        let mut parts = header.trim().split('-');
+        let (version, trace_id, span_id, flags) = (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+        if decode(version)?.len() != 1 || version == "ff" || (version == "00" && parts.next().is_some()) {
+            return None;
+        }
+        let trace_id: [u8; 16] = decode(trace_id)?.try_into().ok()?;
+        let span_id: [u8; 8] = decode(span_id)?.try_into().ok()?;
+        let flags: [u8; 1] = decode(flags)?.try_into().ok()?;
+        if trace_id == [0; 16] || span_id == [0; 8] {
+            return None;
+        }
+        Some(TraceContext { trace_id, span_id, sampled: flags[0] & 1 == 1 })
+    }
+
+    pub fn header(&self) -> String {
+        format!("00-{}-{}-{:02x}", self.trace_id_hex(), self.span_id_hex(), self.sampled as u8)
+    }
+
+    pub fn trace_id_hex(&self) -> String {
+        hex::encode(self.trace_id)
+    }
+
+    pub fn span_id_hex(&self) -> String {
+        hex::encode(self.span_id)
+    }
+}
+
+fn new_span_id() -> [u8; 8] {
+    let mut id = [0; 8];
+    id.copy_from_slice(&Uuid::new_v4().as_bytes()[..8]);
+    id
+}
+
+/// Lowercase hex only, as the header requires
+fn decode(s: &str) -> Option<Vec<u8>> {
+    if s.bytes().any(|b| b.is_ascii_uppercase()) {
+        return None;
+    }
+    hex::decode(s).ok()
+}
+
+/// A finished span, as handed to an exporter
+#[derive(Debug, Clone)]
+pub struct SpanRecord {
+    pub name: String,
+    pub context: TraceContext,
+    /// None for a root span
+    pub parent_span_id: Option<[u8; 8]>,
+    pub start: SystemTime,
+    pub end: SystemTime,
+    /// Span fields, rendered as strings
+    pub attributes: Vec<(String, String)>,
+}
+
+/// OTLP/HTTP JSON body for `spans`, all from `service` (the `service.name` resource attribute)
+pub fn otlp_json(service: &str, spans: &[SpanRecord]) -> Value {
+    let nanos = |t: SystemTime| t.duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0).to_string();
+    let spans: Vec<Value> = spans.iter().map(|span| {
+        let mut out = json!({
+            "traceId": span.context.trace_id_hex(),
+            "spanId": span.context.span_id_hex(),
+            "name": span.name,
+            "startTimeUnixNano": nanos(span.start),
+            "endTimeUnixNano": nanos(span.end),
+            "attributes": span.attributes.iter()
+                .map(|(key, value)| json!({ "key": key, "value": { "stringValue": value } }))
+                .collect::<Vec<_>>(),
+        });
+        if let Some(parent) = span.parent_span_id {
+            out["parentSpanId"] = json!(hex::encode(parent));
+        }
+        out
+    }).collect();
+    json!({
+        "resourceSpans": [{
+            "resource": { "attributes": [{ "key": "service.name", "value": { "stringValue": service } }] },
+            "scopeSpans": [{ "scope": { "name": service }, "spans": spans }]
+        }]
+    })
+}
@@ -0,0 +1,95 @@
+//! `embedding_text` over a fixture set of real breadcrumb shapes.
+//!
+//! Each fixture pairs a context with the text the embedding model should see; the old extractor
+//! (title + serialized JSON) is kept alongside for the noise comparison in SYSTEM_ARCHITECTURE.md.
+
+use rcrt_core::embedding_text::{embed_fields, embedding_text, EmbeddingTextConfig};
+use serde_json::{json, Value};
+
+/// What ingest embedded before: title plus the raw JSON
+fn old_text(title: &str, context: &Value) -> String {
+    format!("{} {}", title, serde_json::to_string(context).unwrap())
+}
+
+fn text(title: &str, context: &Value) -> String {
+    embedding_text(title, context, None, &EmbeddingTextConfig::default())
+}
+
+/// Object key order depends on serde_json's `preserve_order`, so compare the kept strings as a set
+fn assert_kept(text: &str, title: &str, kept: &[&str]) {
+    assert!(text.starts_with(title), "{text}");
+    let mut rest = text[title.len()..].trim().to_string();
+    for part in kept {
+        assert!(rest.contains(part), "missing {part:?} in {text:?}");
+        rest = rest.replacen(part, "", 1);
+    }
+    assert!(rest.trim().is_empty(), "unexpected {rest:?} in {text:?}");
+}
+
+#[test]
+fn test_user_message_keeps_only_prose() {
+    let context = json!({
+        "content": "How do I rotate the OpenAI key stored in secrets?",
+        "conversation_id": "5b0c3c7e-8d6e-4a9b-9c55-2f0f6d1a7e21",
+        "timestamp": "2025-01-12T09:30:00Z",
+        "turn": 4,
+    });
+    assert_kept(&text("User message", &context), "User message", &["How do I rotate the OpenAI key stored in secrets?", "2025-01-12T09:30:00Z"]);
+    assert!(old_text("User message", &context).contains("conversation_id"));
+}
+
+#[test]
+fn test_browser_tab_drops_urls_and_blobs() {
+    let context = json!({
+        "url": "https://docs.rs/sqlx/latest/sqlx/",
+        "title": "sqlx - Rust",
+        "favicon": "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAABAAAAAQCAYAAAAf8/9hAAAA",
+        "content": { "headings": ["Async SQL toolkit", "Compile-time checked queries"], "text": "SQLx is an async, pure Rust SQL crate." },
+        "tab_id": 918273,
+        "screenshot_hash": "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08",
+    });
+    assert_kept(&text("Browser tab", &context), "Browser tab", &[
+        "Async SQL toolkit Compile-time checked queries", "SQLx is an async, pure Rust SQL crate.", "sqlx - Rust",
+    ]);
+}
+
+#[test]
+fn test_tool_response_skips_ids_and_unlisted_opaque_values() {
+    let context = json!({
+        "request_id": "0f8fad5b-d9cb-469f-a165-70867728950e",
+        "tool": "openrouter",
+        "status": "ok",
+        "output": { "answer": "Paris is the capital of France.", "trace": "b2d7a51f9c3e4f6a8d0b1c2e3f4a5b6c7d8e9f0a" },
+        "ok": true,
+    });
+    assert_kept(&text("Tool response", &context), "Tool response", &["Paris is the capital of France.", "openrouter"]);
+}
+
+#[test]
+fn test_embed_fields_restricts_to_listed_paths() {
+    let hints = json!({ "embed_fields": ["description", "code.source"] });
+    let fields = embed_fields(&hints).unwrap();
+    let context = json!({
+        "description": "Parse CSV rows into records",
+        "code": { "source": "fn parse(row: &str) -> Record", "language": "rust", "source_url": "https://example.com/a.rs" },
+        "changelog": "noise that should not be embedded",
+    });
+    assert_eq!(
+        embedding_text("Snippet", &context, Some(&fields), &EmbeddingTextConfig::default()),
+        "Snippet Parse CSV rows into records fn parse(row: &str) -> Record"
+    );
+}
+
+#[test]
+fn test_embed_fields_absent_or_malformed() {
+    assert_eq!(embed_fields(&json!({ "mode": "merge" })), None);
+    assert_eq!(embed_fields(&json!({ "embed_fields": "content" })), None);
+    assert_eq!(embed_fields(&json!({ "embed_fields": ["content", 3] })), Some(vec!["content".to_string()]));
+}
+
+#[test]
+fn test_skip_key_patterns_and_min_len() {
+    let config = EmbeddingTextConfig { min_len: 5, skip_keys: vec!["internal*".into(), "*_note".into()] };
+    let context = json!({ "internal_state": "should be skipped", "debug_note": "also skipped", "short": "abc", "body": "kept text" });
+    assert_eq!(embedding_text("", &context, None, &config), "kept text");
+}
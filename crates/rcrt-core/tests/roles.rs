@@ -0,0 +1,25 @@
+//! The role vocabulary: case-insensitive parsing, the unknown-role message, and the serde spelling.
+
+use rcrt_core::roles::Role;
+
+#[test]
+fn test_roles_parse_case_insensitively_and_reject_unknown_names() {
+    assert_eq!("Emitter".parse::<Role>(), Ok(Role::Emitter));
+    assert_eq!(" CURATOR ".parse::<Role>(), Ok(Role::Curator));
+    let err = "emiter".parse::<Role>().unwrap_err();
+    assert_eq!(err.to_string(), "unknown role 'emiter'; valid roles are curator, emitter, subscriber, admin");
+    assert_eq!(Role::parse_all(&["admin", "Subscriber"]), Ok(vec![Role::Admin, Role::Subscriber]));
+    assert!(Role::parse_all(&["admin", "root"]).is_err());
+    assert!(Role::Curator.is_named_in(&["emitter".to_string(), "Curator".to_string()]));
+}
+
+#[test]
+fn test_roles_round_trip_through_serde_lowercase() {
+    for role in Role::ALL {
+        let json = serde_json::to_value(role).unwrap();
+        assert_eq!(json, role.as_str());
+        assert_eq!(serde_json::from_value::<Role>(json).unwrap(), *role);
+    }
+    assert_eq!(serde_json::from_str::<Role>("\"Admin\"").unwrap(), Role::Admin);
+    assert!(serde_json::from_str::<Role>("\"root\"").is_err());
+}
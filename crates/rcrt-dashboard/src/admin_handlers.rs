@@ -38,6 +38,37 @@ pub async fn get_subscriptions(State(state): State<AppState>) -> Result<Json<ser
     proxy_request(&state, "subscriptions/selectors", "GET", None).await
 }
 
+/// Overview counts from rcrt-server's /admin/stats (aggregated there in SQL), cached briefly
+pub async fn get_stats_overview(State(state): State<AppState>) -> Result<Json<serde_json::Value>, StatusCode> {
+    if let Some(stats) = state.overview_cache.get() {
+        return Ok(Json(stats));
+    }
+    let Json(stats) = proxy_request(&state, "admin/stats", "GET", None).await?;
+    state.overview_cache.put(stats.clone());
+    Ok(Json(stats))
+}
+
+/// Context dry run: POST /debug/assemble on the context-builder with its debug token; 404 unless
+/// configured. The context-builder's status and error text are passed through
+pub async fn debug_assemble(State(state): State<AppState>, Json(body): Json<serde_json::Value>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let Some(debug) = &state.context_builder_debug else {
+        return Err((StatusCode::NOT_FOUND, "context dry runs need CONTEXT_BUILDER_URL and DEBUG_TOKEN".to_string()));
+    };
+    let request = state.http_client.post(format!("{}/debug/assemble", debug.url))
+        .bearer_auth(&debug.token)
+        .json(&body);
+    let response = crate::request_id::forward(request).send().await.map_err(|e| {
+        tracing::error!("Failed to reach the context-builder for a dry run: {}", e);
+        (StatusCode::BAD_GATEWAY, "context-builder unreachable".to_string())
+    })?;
+    let status = StatusCode::from_u16(response.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+    if !status.is_success() {
+        let text = response.text().await.unwrap_or_default();
+        return Err((status, text));
+    }
+    response.json().await.map(Json).map_err(|e| (StatusCode::BAD_GATEWAY, format!("invalid dry run response: {}", e)))
+}
+
 async fn proxy_request(
     state: &AppState, 
     endpoint: &str, 
@@ -62,6 +93,7 @@ async fn proxy_request(
         if let Some(token) = token {
             request = request.header("Authorization", format!("Bearer {}", token));
         }
+        request = crate::request_id::forward(request);
         
         if let Some(json_body) = body {
             request = request.json(json_body);
@@ -63,6 +63,11 @@ impl AuthManager {
         self.acquire_token_with_retry().await
     }
 
+    /// Unix expiry of the token `get_valid_token` last handed out
+    pub async fn token_expires_at(&self) -> Option<u64> {
+        self.token_info.read().await.as_ref().map(|info| info.expires_at)
+    }
+
     /// Acquire JWT token with exponential backoff retry
     pub async fn acquire_token_with_retry(&self) -> Option<String> {
         let max_retries = 10;
@@ -124,7 +129,7 @@ impl AuthManager {
         let token_req = TokenRequest {
             owner_id: self.owner_id.to_string(),
             agent_id: self.agent_id.to_string(),
-            roles: Some(vec!["curator".to_string(), "emitter".to_string(), "subscriber".to_string()]),
+            roles: Some(vec!["admin".to_string(), "curator".to_string(), "emitter".to_string(), "subscriber".to_string()]),
             ttl_sec: Some(3600), // 1 hour
         };
 
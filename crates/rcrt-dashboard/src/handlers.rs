@@ -1,6 +1,6 @@
 use crate::models::*;
 use axum::{
-    extract::{Path, State},
+    extract::{Path, RawQuery, State},
     http::{HeaderMap, StatusCode},
     response::{Json, Redirect},
 };
@@ -54,8 +54,8 @@ where
     while retry_count < max_retries {
         let token = state.auth_manager.get_valid_token().await;
         
-        let mut request = state.http_client
-            .request(method.clone(), &format!("{}/{}", state.rcrt_base_url, endpoint));
+        let mut request = crate::request_id::forward(state.http_client
+            .request(method.clone(), &format!("{}/{}", state.rcrt_base_url, endpoint)));
         
         if let Some(token) = token {
             request = request.header("Authorization", format!("Bearer {}", token));
@@ -119,6 +119,34 @@ pub async fn get_breadcrumbs(State(state): State<AppState>) -> Result<Json<Vec<B
     }
 }
 
+/// Type-ahead for the breadcrumb browser; `q`, `kind` and `limit` pass through to /breadcrumbs/suggest
+pub async fn get_breadcrumb_suggestions(
+    State(state): State<AppState>,
+    RawQuery(query): RawQuery,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let endpoint = format!("breadcrumbs/suggest?{}", query.unwrap_or_default());
+    make_authenticated_request::<serde_json::Value>(&state, reqwest::Method::GET, &endpoint, None, None).await.map(Json)
+}
+
+/// The server's structured diff between two versions (`from`, `to`) of a breadcrumb's context
+pub async fn get_breadcrumb_diff(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    RawQuery(query): RawQuery,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let endpoint = format!("breadcrumbs/{}/diff?{}", id, query.unwrap_or_default());
+    make_authenticated_request::<serde_json::Value>(&state, reqwest::Method::GET, &endpoint, None, None).await.map(Json)
+}
+
+/// Sessions with their counters for the session picker; `order`, `limit` and `offset` pass through
+pub async fn get_sessions(
+    State(state): State<AppState>,
+    RawQuery(query): RawQuery,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let endpoint = format!("sessions?{}", query.unwrap_or_default());
+    make_authenticated_request::<serde_json::Value>(&state, reqwest::Method::GET, &endpoint, None, None).await.map(Json)
+}
+
 pub async fn get_breadcrumb_context(
     State(state): State<AppState>, 
     Path(id): Path<Uuid>
@@ -0,0 +1,425 @@
+//! Dashboard login
+//! Optional session-cookie login in front of the dashboard proxy, independent of the upstream RCRT JWT
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+    Json,
+};
+use serde::Deserialize;
+
+use crate::models::AppState;
+
+pub const SESSION_COOKIE: &str = "rcrt_dashboard_session";
+/// Readable by the page's JS, which echoes it back as `X-CSRF-Token`
+pub const CSRF_COOKIE: &str = "rcrt_dashboard_csrf";
+pub const CSRF_HEADER: &str = "x-csrf-token";
+pub const LOGIN_PAGE: &str = "/static/login.html";
+
+/// Reachable without a session: the login form itself, the login call and the health probe
+const PUBLIC_PATHS: &[&str] = &["/health", "/api/login", LOGIN_PAGE];
+
+/// Login settings from the environment.
+///
+/// `DASHBOARD_AUTH=disabled` turns the whole layer off; anything else requires at least one
+/// user from `DASHBOARD_USERS` (whitespace-separated `name:argon2-hash` pairs) or
+/// `DASHBOARD_USERS_FILE` (htpasswd-style, one pair per line, `#` comments)
+#[derive(Debug, Clone)]
+pub struct LoginConfig {
+    pub users: HashMap<String, String>,
+    pub session_ttl: Duration,
+    pub cookie_secure: bool,
+    pub max_failures: u32,
+    pub failure_window: Duration,
+}
+
+impl LoginConfig {
+    /// `Ok(None)` when DASHBOARD_AUTH=disabled
+    pub fn from_env() -> Result<Option<Self>> {
+        if std::env::var("DASHBOARD_AUTH").map(|v| v.eq_ignore_ascii_case("disabled")).unwrap_or(false) {
+            return Ok(None);
+        }
+
+        let mut users = HashMap::new();
+        if let Ok(inline) = std::env::var("DASHBOARD_USERS") {
+            users.extend(parse_users(&inline.split_whitespace().collect::<Vec<_>>().join("\n"))?);
+        }
+        if let Ok(path) = std::env::var("DASHBOARD_USERS_FILE") {
+            let contents = std::fs::read_to_string(&path).with_context(|| format!("reading DASHBOARD_USERS_FILE {}", path))?;
+            users.extend(parse_users(&contents)?);
+        }
+        if users.is_empty() {
+            anyhow::bail!("dashboard login is enabled but no users are configured; set DASHBOARD_USERS or DASHBOARD_USERS_FILE, or DASHBOARD_AUTH=disabled");
+        }
+
+        Ok(Some(Self {
+            users,
+            session_ttl: Duration::from_secs(env_or("DASHBOARD_SESSION_TTL_SECS", 8 * 3600)),
+            cookie_secure: std::env::var("DASHBOARD_COOKIE_SECURE").map(|v| v == "true" || v == "1").unwrap_or(false),
+            max_failures: env_or("DASHBOARD_LOGIN_MAX_FAILURES", 5),
+            failure_window: Duration::from_secs(env_or("DASHBOARD_LOGIN_WINDOW_SECS", 300)),
+        }))
+    }
+}
+
+fn env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+    std::env::var(key).ok().and_then(|s| s.parse().ok()).unwrap_or(default)
+}
+
+/// `name:hash` per line; blank lines and `#` comments are skipped. Hashes must be argon2 PHC strings
+pub fn parse_users(contents: &str) -> Result<HashMap<String, String>> {
+    let mut users = HashMap::new();
+    for (n, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (name, hash) = line.split_once(':').with_context(|| format!("dashboard user entry {} is not name:hash", n + 1))?;
+        let parsed = PasswordHash::new(hash).map_err(|e| anyhow::anyhow!("dashboard user {}: bad password hash: {}", name, e))?;
+        if !parsed.algorithm.as_str().starts_with("argon2") {
+            anyhow::bail!("dashboard user {}: password hash must be argon2, got {}", name, parsed.algorithm);
+        }
+        users.insert(name.to_string(), hash.to_string());
+    }
+    Ok(users)
+}
+
+/// PHC string for `password`, as printed by `rcrt-dashboard hash-password`
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|h| h.to_string())
+        .map_err(|e| anyhow::anyhow!("hashing password: {}", e))
+}
+
+#[derive(Debug, Clone)]
+struct Session {
+    username: String,
+    csrf_token: String,
+    expires_at: Instant,
+}
+
+/// Failed attempts for one key (client IP or username) inside the current window
+#[derive(Debug, Clone, Copy)]
+struct Failures {
+    window_start: Instant,
+    count: u32,
+}
+
+/// In-memory sessions and login throttling; sessions do not survive a restart
+pub struct LoginManager {
+    config: LoginConfig,
+    /// Verified against when the username is unknown so both paths cost one argon2 run
+    dummy_hash: String,
+    sessions: Mutex<HashMap<String, Session>>,
+    failures: Mutex<HashMap<String, Failures>>,
+}
+
+impl LoginManager {
+    pub fn new(config: LoginConfig) -> Result<Self> {
+        Ok(Self {
+            config,
+            dummy_hash: hash_password("rcrt-dashboard-unknown-user")?,
+            sessions: Mutex::new(HashMap::new()),
+            failures: Mutex::new(HashMap::new()),
+        })
+    }
+
+    async fn verify(&self, username: &str, password: &str) -> bool {
+        let known = self.config.users.get(username);
+        let hash = known.cloned().unwrap_or_else(|| self.dummy_hash.clone());
+        let password = password.to_string();
+        let matches = tokio::task::spawn_blocking(move || {
+            PasswordHash::new(&hash)
+                .map(|parsed| Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok())
+                .unwrap_or(false)
+        })
+        .await
+        .unwrap_or(false);
+        known.is_some() && matches
+    }
+
+    /// Seconds until `keys` may try again, if any of them is locked out
+    fn retry_after_at(&self, keys: &[String], now: Instant) -> Option<u64> {
+        let failures = self.failures.lock().ok()?;
+        keys.iter()
+            .filter_map(|key| failures.get(key))
+            .filter(|f| f.count >= self.config.max_failures)
+            .filter_map(|f| (f.window_start + self.config.failure_window).checked_duration_since(now))
+            .filter(|left| !left.is_zero())
+            .map(|left| left.as_secs().max(1))
+            .max()
+    }
+
+    fn record_failure_at(&self, keys: &[String], now: Instant) {
+        if let Ok(mut failures) = self.failures.lock() {
+            failures.retain(|_, f| now.saturating_duration_since(f.window_start) < self.config.failure_window);
+            for key in keys {
+                let entry = failures.entry(key.clone()).or_insert(Failures { window_start: now, count: 0 });
+                entry.count += 1;
+            }
+        }
+    }
+
+    fn clear_failures(&self, keys: &[String]) {
+        if let Ok(mut failures) = self.failures.lock() {
+            for key in keys {
+                failures.remove(key);
+            }
+        }
+    }
+
+    /// Returns (session id, csrf token)
+    fn create_session_at(&self, username: &str, now: Instant) -> (String, String) {
+        let id = random_token();
+        let csrf_token = random_token();
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.retain(|_, s| s.expires_at > now);
+            sessions.insert(id.clone(), Session {
+                username: username.to_string(),
+                csrf_token: csrf_token.clone(),
+                expires_at: now + self.config.session_ttl,
+            });
+        }
+        (id, csrf_token)
+    }
+
+    fn session_at(&self, id: &str, now: Instant) -> Option<Session> {
+        let mut sessions = self.sessions.lock().ok()?;
+        match sessions.get(id) {
+            Some(s) if s.expires_at > now => Some(s.clone()),
+            Some(_) => {
+                sessions.remove(id);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn end_session(&self, id: &str) -> Option<Session> {
+        self.sessions.lock().ok()?.remove(id)
+    }
+
+    fn cookies(&self, session_id: &str, csrf_token: &str, max_age: u64) -> [HeaderValue; 2] {
+        let secure = if self.config.cookie_secure { "; Secure" } else { "" };
+        [
+            format!("{}={}; Path=/; HttpOnly; SameSite=Strict; Max-Age={}{}", SESSION_COOKIE, session_id, max_age, secure),
+            format!("{}={}; Path=/; SameSite=Strict; Max-Age={}{}", CSRF_COOKIE, csrf_token, max_age, secure),
+        ]
+        .map(|c| HeaderValue::from_str(&c).expect("cookie values are hex"))
+    }
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(k, _)| *k == name)
+        .map(|(_, v)| v)
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn is_mutating(method: &Method) -> bool {
+    !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// Middleware: without a live session, `/api/*` gets 401 and everything else is sent to the
+/// login page; mutating requests must also echo the session's CSRF token in `X-CSRF-Token`
+pub async fn require_session(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let Some(login) = state.login.as_ref() else {
+        return next.run(request).await;
+    };
+    let path = request.uri().path();
+    if PUBLIC_PATHS.contains(&path) {
+        return next.run(request).await;
+    }
+
+    let session = cookie(request.headers(), SESSION_COOKIE).and_then(|id| login.session_at(id, Instant::now()));
+    let Some(session) = session else {
+        return if path.starts_with("/api/") {
+            (StatusCode::UNAUTHORIZED, "login required").into_response()
+        } else {
+            Redirect::to(LOGIN_PAGE).into_response()
+        };
+    };
+
+    if is_mutating(request.method()) {
+        let presented = request.headers().get(CSRF_HEADER).and_then(|v| v.to_str().ok()).unwrap_or("");
+        if !constant_time_eq(presented, &session.csrf_token) {
+            tracing::warn!(user = %session.username, path, "dashboard request rejected: missing or wrong CSRF token");
+            return (StatusCode::FORBIDDEN, "CSRF token missing or invalid").into_response();
+        }
+    }
+    next.run(request).await
+}
+
+#[derive(Deserialize)]
+pub struct LoginRequest {
+    pub username: String,
+    pub password: String,
+}
+
+pub async fn login(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(req): Json<LoginRequest>,
+) -> Response {
+    let Some(login) = state.login.clone() else {
+        return (StatusCode::NOT_FOUND, "dashboard login is disabled").into_response();
+    };
+    let ip = addr.ip();
+    let keys = [format!("ip:{}", ip), format!("user:{}", req.username)];
+
+    if let Some(retry_after) = login.retry_after_at(&keys, Instant::now()) {
+        tracing::warn!(user = %req.username, %ip, retry_after, "dashboard login throttled");
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, retry_after.to_string())],
+            "too many failed login attempts",
+        )
+            .into_response();
+    }
+
+    if !login.verify(&req.username, &req.password).await {
+        login.record_failure_at(&keys, Instant::now());
+        tracing::warn!(user = %req.username, %ip, "dashboard login failed");
+        return (StatusCode::UNAUTHORIZED, "invalid username or password").into_response();
+    }
+
+    login.clear_failures(&keys);
+    let (session_id, csrf_token) = login.create_session_at(&req.username, Instant::now());
+    tracing::info!(user = %req.username, %ip, "dashboard login succeeded");
+
+    let [session_cookie, csrf_cookie] = login.cookies(&session_id, &csrf_token, login.config.session_ttl.as_secs());
+    let mut response = Json(serde_json::json!({ "username": req.username, "csrf_token": csrf_token })).into_response();
+    response.headers_mut().append(header::SET_COOKIE, session_cookie);
+    response.headers_mut().append(header::SET_COOKIE, csrf_cookie);
+    response
+}
+
+pub async fn logout(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    let Some(login) = state.login.clone() else {
+        return StatusCode::NO_CONTENT.into_response();
+    };
+    if let Some(session) = cookie(&headers, SESSION_COOKIE).and_then(|id| login.end_session(id)) {
+        tracing::info!(user = %session.username, "dashboard logout");
+    }
+    let mut response = StatusCode::NO_CONTENT.into_response();
+    for expired in login.cookies("", "", 0) {
+        response.headers_mut().append(header::SET_COOKIE, expired);
+    }
+    response
+}
+
+/// The logged-in user, or `{"auth": "disabled"}`; lets the UI recover its CSRF token after a reload
+pub async fn whoami(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    let Some(login) = state.login.clone() else {
+        return Json(serde_json::json!({ "auth": "disabled" })).into_response();
+    };
+    match cookie(&headers, SESSION_COOKIE).and_then(|id| login.session_at(id, Instant::now())) {
+        Some(session) => Json(serde_json::json!({ "username": session.username, "csrf_token": session.csrf_token })).into_response(),
+        None => (StatusCode::UNAUTHORIZED, "login required").into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager(users: &str) -> LoginManager {
+        LoginManager::new(LoginConfig {
+            users: parse_users(users).unwrap(),
+            session_ttl: Duration::from_secs(60),
+            cookie_secure: false,
+            max_failures: 3,
+            failure_window: Duration::from_secs(300),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_users_skips_comments_and_rejects_plaintext() {
+        let hash = hash_password("hunter2").unwrap();
+        let users = parse_users(&format!("# ops\n\nalice:{}\n", hash)).unwrap();
+        assert_eq!(users.get("alice"), Some(&hash));
+        assert!(parse_users("bob:hunter2").is_err());
+        assert!(parse_users("no-separator").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_verify_checks_password_and_unknown_users() {
+        let login = manager(&format!("alice:{}", hash_password("hunter2").unwrap()));
+        assert!(login.verify("alice", "hunter2").await);
+        assert!(!login.verify("alice", "wrong").await);
+        assert!(!login.verify("mallory", "hunter2").await);
+    }
+
+    #[test]
+    fn test_failures_lock_out_until_window_ends() {
+        let login = manager("");
+        let keys = ["ip:10.0.0.1".to_string(), "user:alice".to_string()];
+        let start = Instant::now();
+        for _ in 0..2 {
+            login.record_failure_at(&keys, start);
+        }
+        assert_eq!(login.retry_after_at(&keys, start), None);
+        login.record_failure_at(&keys, start);
+        assert_eq!(login.retry_after_at(&keys, start + Duration::from_secs(100)), Some(200));
+        // A different IP guessing the same user is still throttled
+        assert!(login.retry_after_at(&["ip:10.0.0.2".to_string(), "user:alice".to_string()], start).is_some());
+        assert_eq!(login.retry_after_at(&keys, start + Duration::from_secs(300)), None);
+        login.clear_failures(&keys);
+        assert_eq!(login.retry_after_at(&keys, start), None);
+    }
+
+    #[test]
+    fn test_sessions_expire_and_end() {
+        let login = manager("");
+        let start = Instant::now();
+        let (id, csrf) = login.create_session_at("alice", start);
+        assert_ne!(id, csrf);
+        assert_eq!(login.session_at(&id, start + Duration::from_secs(59)).unwrap().csrf_token, csrf);
+        assert!(login.session_at(&id, start + Duration::from_secs(60)).is_none());
+
+        let (id, _) = login.create_session_at("alice", start);
+        assert_eq!(login.end_session(&id).unwrap().username, "alice");
+        assert!(login.session_at(&id, start).is_none());
+    }
+
+    #[test]
+    fn test_cookie_lookup_and_csrf_compare() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, HeaderValue::from_static("theme=dark; rcrt_dashboard_session=abc123; rcrt_dashboard_csrf=def"));
+        assert_eq!(cookie(&headers, SESSION_COOKIE), Some("abc123"));
+        assert_eq!(cookie(&headers, CSRF_COOKIE), Some("def"));
+        assert_eq!(cookie(&headers, "missing"), None);
+        assert!(constant_time_eq("abc", "abc"));
+        assert!(!constant_time_eq("abc", "abd"));
+        assert!(!constant_time_eq("abc", "abcd"));
+        assert!(is_mutating(&Method::POST) && is_mutating(&Method::DELETE) && !is_mutating(&Method::GET));
+    }
+}
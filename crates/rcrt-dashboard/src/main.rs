@@ -1,9 +1,11 @@
 use std::net::SocketAddr;
 use axum::{
+    middleware,
     routing::{get, post, put},
     Router,
 };
 use tower_http::{services::ServeDir, cors::CorsLayer};
+use tower_http::compression::{CompressionLayer, predicate::{NotForContentType, Predicate, SizeAbove}};
 use tracing_subscriber::{EnvFilter, fmt};
 use uuid::Uuid;
 use anyhow::Result;
@@ -13,18 +15,47 @@ mod handlers;
 mod admin_handlers;
 mod sse_handlers;
 mod auth;
+mod overview;
+mod login;
+mod request_id;
 
-use models::AppState;
+use models::{AppState, ContextBuilderDebug};
 use handlers::*;
 use admin_handlers::*;
 use sse_handlers::*;
 use auth::AuthManager;
+use overview::OverviewCache;
+use login::{LoginConfig, LoginManager};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenvy::dotenv().ok();
     let filter = EnvFilter::from_default_env();
-    fmt().with_env_filter(filter).init();
+    // LOG_FORMAT=json: one object per line, with span fields (request_id) as keys
+    if std::env::var("LOG_FORMAT").as_deref() == Ok("json") {
+        fmt().json().with_env_filter(filter).with_current_span(true).with_span_list(true).init();
+    } else {
+        fmt().with_env_filter(filter).init();
+    }
+
+    // `rcrt-dashboard hash-password` reads a password from stdin and prints the argon2 hash for DASHBOARD_USERS
+    if std::env::args().nth(1).as_deref() == Some("hash-password") {
+        let mut password = String::new();
+        std::io::stdin().read_line(&mut password)?;
+        println!("{}", login::hash_password(password.trim_end_matches(['\r', '\n']))?);
+        return Ok(());
+    }
+
+    let login_manager = match LoginConfig::from_env()? {
+        Some(config) => {
+            tracing::info!("Dashboard login enabled for {} user(s)", config.users.len());
+            Some(std::sync::Arc::new(LoginManager::new(config)?))
+        }
+        None => {
+            tracing::warn!("DASHBOARD_AUTH=disabled: anyone who can reach the dashboard gets its RCRT access");
+            None
+        }
+    };
 
     let rcrt_base_url = std::env::var("RCRT_URL")
         .unwrap_or_else(|_| "http://localhost:8080".to_string());
@@ -39,6 +70,8 @@ async fn main() -> Result<()> {
         .and_then(|s| Uuid::parse_str(&s).ok())
         .unwrap_or_else(|| Uuid::new_v4());
 
+    // gzip/br are negotiated with rcrt-server and decoded transparently; handlers re-serialize
+    // the JSON, so no upstream Content-Encoding or Content-Length is ever forwarded
     let http_client = reqwest::Client::new();
     
     // Create AuthManager for robust JWT handling
@@ -86,6 +119,18 @@ async fn main() -> Result<()> {
         None => tracing::warn!("Could not obtain initial JWT token, will retry in background"),
     }
 
+    let overview_cache_secs = std::env::var("OVERVIEW_CACHE_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(10u64);
+
+    let sse_max_streams_per_ip = std::env::var("SSE_MAX_STREAMS_PER_IP").ok().and_then(|s| s.parse().ok()).unwrap_or(6usize);
+
+    // Context dry runs, proxied to the context-builder's metrics listener (e.g. http://context-builder:9091)
+    let context_builder_debug = match (std::env::var("CONTEXT_BUILDER_URL"), std::env::var("DEBUG_TOKEN")) {
+        (Ok(url), Ok(token)) if !url.is_empty() && !token.is_empty() => {
+            Some(ContextBuilderDebug { url: url.trim_end_matches('/').to_string(), token })
+        }
+        _ => None,
+    };
+
     let state = AppState {
         http_client,
         rcrt_base_url,
@@ -93,13 +138,39 @@ async fn main() -> Result<()> {
         agent_id,
         jwt_token,
         auth_manager,
+        overview_cache: std::sync::Arc::new(OverviewCache::new(std::time::Duration::from_secs(overview_cache_secs))),
+        login: login_manager,
+        sse_streams: std::sync::Arc::new(StreamLimiter::new(sse_max_streams_per_ip)),
+        context_builder_debug,
     };
 
-    let app = Router::new()
+    let compression_min_bytes = std::env::var("COMPRESSION_MIN_BYTES").ok().and_then(|s| s.parse().ok()).unwrap_or(1024u16);
+
+    let app = router(state, compression_min_bytes);
+
+    let addr: SocketAddr = "0.0.0.0:8082".parse().unwrap();
+    tracing::info!("Dashboard listening on {}", addr);
+    // Peer addresses key the login throttle
+    axum::serve(
+        tokio::net::TcpListener::bind(addr).await?,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
+    Ok(())
+}
+
+fn router(state: AppState, compression_min_bytes: u16) -> Router {
+    Router::new()
         .route("/", get(dashboard_page))
         .route("/api/breadcrumbs", get(get_breadcrumbs).post(create_breadcrumb))
+        .route("/api/breadcrumbs/suggest", get(get_breadcrumb_suggestions))
         .route("/api/breadcrumbs/:id", get(get_breadcrumb_context).patch(update_breadcrumb).delete(delete_breadcrumb))
+        .route("/api/breadcrumbs/:id/diff", get(get_breadcrumb_diff))
         .route("/api/events/stream", get(proxy_sse_stream))
+        .route("/api/login", post(login::login))
+        .route("/api/logout", post(login::logout))
+        .route("/api/session", get(login::whoami))
+        .route("/api/sessions", get(get_sessions))
         .route("/api/auth/token", get(get_jwt_token)) // 🎯 NEW: Direct JWT access for frontend
         .route("/api/agents", get(get_agents))
         .route("/api/agents/:id", get(get_agent))
@@ -111,13 +182,18 @@ async fn main() -> Result<()> {
         .route("/api/acl", get(get_acl))
         .route("/api/agents/:id/webhooks", get(get_agent_webhooks))
         .route("/api/subscriptions", get(get_subscriptions))
+        .route("/api/stats/overview", get(get_stats_overview))
+        .route("/api/debug/assemble", post(debug_assemble))
         .route("/health", get(health))
         .nest_service("/static", ServeDir::new("static"))
+        // Outermost of the routes so the static UI and every /api/* proxy sit behind the login
+        .layer(middleware::from_fn_with_state(state.clone(), login::require_session))
+        // The SSE proxy streams text/event-stream and must never be buffered by an encoder
+        .layer(CompressionLayer::new().compress_when(
+            SizeAbove::new(compression_min_bytes).and(NotForContentType::IMAGES).and(NotForContentType::SSE),
+        ))
         .layer(CorsLayer::permissive())
-        .with_state(state);
-
-    let addr: SocketAddr = "0.0.0.0:8082".parse().unwrap();
-    tracing::info!("Dashboard listening on {}", addr);
-    axum::serve(tokio::net::TcpListener::bind(addr).await?, app).await?;
-    Ok(())
-}
\ No newline at end of file
+        // Outermost, so the login check and every proxied call run under the request id
+        .layer(middleware::from_fn(request_id::request_id_middleware))
+        .with_state(state)
+}
@@ -65,6 +65,9 @@ pub struct UpdateBreadcrumbRequest {
 }
 
 use crate::auth::AuthManager;
+use crate::login::LoginManager;
+use crate::overview::OverviewCache;
+use crate::sse_handlers::StreamLimiter;
 
 #[derive(Clone)]
 pub struct AppState {
@@ -76,4 +79,19 @@ pub struct AppState {
     pub agent_id: Uuid,
     pub jwt_token: Option<String>,
     pub auth_manager: AuthManager,
+    pub overview_cache: std::sync::Arc<OverviewCache>,
+    /// None when DASHBOARD_AUTH=disabled
+    pub login: Option<std::sync::Arc<LoginManager>>,
+    /// Open /api/events/stream connections per client IP
+    pub sse_streams: std::sync::Arc<StreamLimiter>,
+    /// None unless CONTEXT_BUILDER_URL and DEBUG_TOKEN are set
+    pub context_builder_debug: Option<ContextBuilderDebug>,
+}
+
+/// Where /api/debug/assemble goes: the context-builder's metrics listener, and the
+/// DEBUG_TOKEN it was started with (kept here, never sent to the browser)
+#[derive(Clone)]
+pub struct ContextBuilderDebug {
+    pub url: String,
+    pub token: String,
 }
@@ -0,0 +1,68 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Last `/admin/stats` document, reused for `ttl` so overview polling from many
+/// browser tabs costs one upstream call per window.
+///
+/// Failures are never cached: the next request goes upstream again.
+#[derive(Debug)]
+pub struct OverviewCache {
+    ttl: Duration,
+    entry: Mutex<Option<(Instant, serde_json::Value)>>,
+}
+
+impl OverviewCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl, entry: Mutex::new(None) }
+    }
+
+    pub fn get(&self) -> Option<serde_json::Value> {
+        self.get_at(Instant::now())
+    }
+
+    pub fn put(&self, value: serde_json::Value) {
+        self.put_at(Instant::now(), value)
+    }
+
+    fn get_at(&self, now: Instant) -> Option<serde_json::Value> {
+        let entry = self.entry.lock().ok()?;
+        entry.as_ref()
+            .filter(|(at, _)| now.saturating_duration_since(*at) < self.ttl)
+            .map(|(_, value)| value.clone())
+    }
+
+    fn put_at(&self, now: Instant, value: serde_json::Value) {
+        if let Ok(mut entry) = self.entry.lock() {
+            *entry = Some((now, value));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_empty_cache_misses() {
+        assert!(OverviewCache::new(Duration::from_secs(10)).get().is_none());
+    }
+
+    #[test]
+    fn test_entry_served_until_ttl() {
+        let cache = OverviewCache::new(Duration::from_secs(10));
+        let start = Instant::now();
+        cache.put_at(start, json!({ "dlq": { "depth": 1 } }));
+        assert_eq!(cache.get_at(start + Duration::from_secs(9)), Some(json!({ "dlq": { "depth": 1 } })));
+        assert!(cache.get_at(start + Duration::from_secs(10)).is_none());
+    }
+
+    #[test]
+    fn test_put_replaces_entry() {
+        let cache = OverviewCache::new(Duration::from_secs(10));
+        let start = Instant::now();
+        cache.put_at(start, json!(1));
+        cache.put_at(start + Duration::from_secs(5), json!(2));
+        assert_eq!(cache.get_at(start + Duration::from_secs(12)), Some(json!(2)));
+    }
+}
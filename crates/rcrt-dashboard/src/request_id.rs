@@ -0,0 +1,167 @@
+//! X-Request-Id for the dashboard proxy: a browser-supplied id (or a fresh one) is
+//! forwarded on every rcrt-server call made for the request and echoed back, so the
+//! dashboard's and the server's log lines for one click share an id. The browser's
+//! `traceparent` (or a new trace) is forwarded the same way, so rcrt-server's spans for
+//! every call one click makes land in one trace.
+
+use axum::{extract::Request, http::HeaderValue, middleware::Next, response::Response};
+use tracing::Instrument;
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+/// W3C trace context header, as rcrt-server reads it
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+/// Longer incoming ids are replaced, same limit as rcrt-server
+const MAX_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+    static TRACEPARENT: String;
+}
+
+fn accept_or_generate(value: Option<&HeaderValue>) -> String {
+    value.and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && id.len() <= MAX_LEN && id.bytes().all(|b| b.is_ascii_graphic()))
+        .map(String::from)
+        .unwrap_or_else(|| Uuid::new_v4().to_string())
+}
+
+/// A well-formed version 00 traceparent as sent, or a new sampled trace. The dashboard records no
+/// spans of its own, so upstream calls hang directly off the browser's span
+fn accept_or_start_trace(value: Option<&HeaderValue>) -> String {
+    let is_hex = |s: &str, len: usize| s.len() == len && s.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b));
+    let valid = |tp: &&str| {
+        let parts: Vec<&str> = tp.split('-').collect();
+        matches!(parts[..], [version, trace_id, span_id, flags]
+            if version == "00" && is_hex(trace_id, 32) && is_hex(span_id, 16) && is_hex(flags, 2)
+                && trace_id.bytes().any(|b| b != b'0') && span_id.bytes().any(|b| b != b'0'))
+    };
+    value.and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(valid)
+        .map(String::from)
+        .unwrap_or_else(|| format!("00-{}-{}-01", Uuid::new_v4().simple(), &Uuid::new_v4().simple().to_string()[..16]))
+}
+
+/// Add the current request's id and traceparent to an upstream call; unchanged outside a request
+pub fn forward(mut request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    if let Ok(id) = REQUEST_ID.try_with(|id| id.clone()) {
+        request = request.header(REQUEST_ID_HEADER, id);
+    }
+    if let Ok(traceparent) = TRACEPARENT.try_with(|tp| tp.clone()) {
+        request = request.header(TRACEPARENT_HEADER, traceparent);
+    }
+    request
+}
+
+pub async fn request_id_middleware(req: Request, next: Next) -> Response {
+    let id = accept_or_generate(req.headers().get(REQUEST_ID_HEADER));
+    let traceparent = accept_or_start_trace(req.headers().get(TRACEPARENT_HEADER));
+    let trace_id = &traceparent[3..35];
+    let span = tracing::info_span!("request", request_id = %id, trace_id = %trace_id, method = %req.method(), path = %req.uri().path());
+    let handled = TRACEPARENT.scope(traceparent.clone(), next.run(req).instrument(span));
+    let mut resp = REQUEST_ID.scope(id.clone(), handled).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        resp.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{auth::AuthManager, models::AppState, overview::OverviewCache};
+    use axum::{body::Body, http::HeaderMap, routing::{get, post}, Json, Router};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    /// Stands in for rcrt-server, recording the headers each GET /breadcrumbs arrives with
+    async fn upstream(seen: Arc<Mutex<Vec<HeaderMap>>>) -> String {
+        let app = Router::new()
+            .route("/health", get(|| async { "ok" }))
+            .route("/auth/token", post(|| async {
+                Json(serde_json::json!({ "token": "t", "owner_id": "o", "agent_id": "a", "roles": [], "exp": i64::MAX }))
+            }))
+            .route("/breadcrumbs", get(move |headers: HeaderMap| async move {
+                seen.lock().unwrap().push(headers);
+                Json(serde_json::json!([]))
+            }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    fn dashboard(base_url: String) -> Router {
+        let http_client = reqwest::Client::new();
+        let state = AppState {
+            http_client: http_client.clone(),
+            rcrt_base_url: base_url.clone(),
+            owner_id: Uuid::new_v4(),
+            agent_id: Uuid::new_v4(),
+            jwt_token: None,
+            auth_manager: AuthManager::new(http_client, base_url, Uuid::new_v4(), Uuid::new_v4()),
+            overview_cache: Arc::new(OverviewCache::new(Duration::from_secs(10))),
+            login: None,
+            sse_streams: Arc::new(crate::sse_handlers::StreamLimiter::new(0)),
+            context_builder_debug: None,
+        };
+        crate::router(state, 1024)
+    }
+
+    async fn get_breadcrumbs(app: &Router, request_id: Option<&str>) -> Option<String> {
+        get_breadcrumbs_with(app, request_id.map(|id| (REQUEST_ID_HEADER, id))).await
+    }
+
+    async fn get_breadcrumbs_with(app: &Router, header: Option<(&str, &str)>) -> Option<String> {
+        let mut req = axum::http::Request::get("/api/breadcrumbs");
+        if let Some((name, value)) = header {
+            req = req.header(name, value);
+        }
+        let res = app.clone().oneshot(req.body(Body::empty()).unwrap()).await.unwrap();
+        assert!(res.status().is_success(), "{}", res.status());
+        res.headers().get(REQUEST_ID_HEADER).and_then(|v| v.to_str().ok()).map(String::from)
+    }
+
+    #[tokio::test]
+    async fn test_request_id_round_trips_through_the_proxy() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let app = dashboard(upstream(seen.clone()).await);
+
+        // The browser's id reaches rcrt-server and comes back
+        assert_eq!(get_breadcrumbs(&app, Some("click-42")).await.as_deref(), Some("click-42"));
+        // Without one the dashboard makes one, and forwards the same id it returns
+        let generated = get_breadcrumbs(&app, None).await.expect("generated id");
+        assert!(Uuid::parse_str(&generated).is_ok());
+        // An unusable id is replaced
+        let replaced = get_breadcrumbs(&app, Some("has space")).await.expect("replacement id");
+        assert_ne!(replaced, "has space");
+
+        let forwarded: Vec<Option<String>> = seen.lock().unwrap().iter()
+            .map(|h| h.get(REQUEST_ID_HEADER).and_then(|v| v.to_str().ok()).map(String::from))
+            .collect();
+        assert_eq!(forwarded, vec![Some("click-42".to_string()), Some(generated), Some(replaced)]);
+    }
+
+    #[tokio::test]
+    async fn test_traceparent_is_forwarded_or_started() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let app = dashboard(upstream(seen.clone()).await);
+        let browser = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+        get_breadcrumbs_with(&app, Some((TRACEPARENT_HEADER, browser))).await;
+        get_breadcrumbs_with(&app, None).await;
+        get_breadcrumbs_with(&app, Some((TRACEPARENT_HEADER, "00-not-a-trace-01"))).await;
+
+        let sent: Vec<String> = seen.lock().unwrap().iter()
+            .map(|h| h.get(TRACEPARENT_HEADER).expect("traceparent forwarded").to_str().unwrap().to_string())
+            .collect();
+        assert_eq!(sent[0], browser);
+        for started in &sent[1..] {
+            assert_eq!(accept_or_start_trace(Some(&HeaderValue::from_str(started).unwrap())), *started);
+            assert_ne!(started, browser);
+        }
+    }
+}
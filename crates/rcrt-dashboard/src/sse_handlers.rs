@@ -1,247 +1,491 @@
+//! SSE proxy: relays rcrt-server's /events/stream to the browser, one upstream connection per
+//! browser connection, so each reconnect is renegotiated with that request's filters and
+//! Last-Event-ID. Every stream ends with a `stream.closed` event saying why.
+
 use crate::models::AppState;
 use axum::{
-    extract::State,
-    http::StatusCode,
+    extract::{ConnectInfo, Query, State},
+    http::HeaderMap,
     response::sse::{Event, Sse},
 };
 use futures_core::Stream;
-use futures_util::StreamExt; // For bytes_stream().next()
+use std::collections::HashMap;
 use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const LAST_EVENT_ID: &str = "last-event-id";
+
+/// Why a proxied stream ended, sent as the `reason` of the final `stream.closed` event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseReason {
+    /// This client IP already has SSE_MAX_STREAMS_PER_IP streams open
+    TooManyStreams,
+    /// rcrt-server refused the dashboard's token, or the token the stream was opened with ran out
+    AuthExpired,
+    /// The dashboard couldn't get a token at all
+    AuthUnavailable,
+    /// rcrt-server couldn't be reached or didn't accept the stream
+    UpstreamUnavailable,
+    /// rcrt-server ended the stream, e.g. on a restart
+    UpstreamClosed,
+}
+
+impl CloseReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CloseReason::TooManyStreams => "too_many_streams",
+            CloseReason::AuthExpired => "auth_expired",
+            CloseReason::AuthUnavailable => "auth_unavailable",
+            CloseReason::UpstreamUnavailable => "upstream_unavailable",
+            CloseReason::UpstreamClosed => "upstream_closed",
+        }
+    }
+}
+
+fn closed_event(reason: CloseReason, detail: &str) -> Event {
+    let closed = serde_json::json!({
+        "type": "stream.closed",
+        "reason": reason.as_str(),
+        "detail": detail,
+        "timestamp": chrono::Utc::now().to_rfc3339()
+    });
+    Event::default().data(closed.to_string())
+}
+
+/// Open proxy streams per client IP, capped at SSE_MAX_STREAMS_PER_IP (0 = no cap)
+pub struct StreamLimiter {
+    max_per_ip: usize,
+    open: Mutex<HashMap<IpAddr, usize>>,
+}
+
+/// One open stream; dropping it (the browser went away) frees the slot
+struct StreamPermit {
+    limiter: Arc<StreamLimiter>,
+    ip: IpAddr,
+}
+
+impl StreamLimiter {
+    pub fn new(max_per_ip: usize) -> Self {
+        Self { max_per_ip, open: Mutex::new(HashMap::new()) }
+    }
+
+    fn try_acquire(self: &Arc<Self>, ip: IpAddr) -> Option<StreamPermit> {
+        let mut open = self.open.lock().ok()?;
+        let count = open.entry(ip).or_insert(0);
+        if self.max_per_ip > 0 && *count >= self.max_per_ip {
+            return None;
+        }
+        *count += 1;
+        Some(StreamPermit { limiter: self.clone(), ip })
+    }
+}
+
+impl Drop for StreamPermit {
+    fn drop(&mut self) {
+        if let Ok(mut open) = self.limiter.open.lock() {
+            if let Some(count) = open.get_mut(&self.ip) {
+                *count -= 1;
+                if *count == 0 {
+                    open.remove(&self.ip);
+                }
+            }
+        }
+    }
+}
+
+/// Map the browser's filters onto /events/stream's: `schema` → `schema_name`, and repeatable
+/// `any_tag`/`all_tag`/`none_tag` (or their comma-separated `*_tags` forms) → `any_tags`/`all_tags`/`none_tags`.
+/// `payload_version` passes through; anything else is dropped
+fn upstream_filters(params: &[(String, String)]) -> Vec<(&'static str, String)> {
+    let mut schema_name = None;
+    let mut payload_version = None;
+    let mut tags: [(&'static str, Vec<&str>); 3] = [("any_tags", Vec::new()), ("all_tags", Vec::new()), ("none_tags", Vec::new())];
+    for (key, value) in params {
+        let list = match key.as_str() {
+            "schema" | "schema_name" => {
+                schema_name = Some(value.clone());
+                continue;
+            }
+            "payload_version" => {
+                payload_version = Some(value.clone());
+                continue;
+            }
+            "any_tag" | "any_tags" => &mut tags[0].1,
+            "all_tag" | "all_tags" => &mut tags[1].1,
+            "none_tag" | "none_tags" => &mut tags[2].1,
+            _ => continue,
+        };
+        list.extend(value.split(',').map(str::trim).filter(|t| !t.is_empty()));
+    }
+
+    let mut query: Vec<(&'static str, String)> = tags
+        .into_iter()
+        .filter(|(_, list)| !list.is_empty())
+        .map(|(name, list)| (name, list.join(",")))
+        .collect();
+    query.extend(schema_name.map(|s| ("schema_name", s)));
+    query.extend(payload_version.map(|v| ("payload_version", v)));
+    query
+}
+
+/// Where to resume from: the header a reconnecting EventSource sends, else `?last_event_id=`
+/// for a fresh EventSource opened with new filters (which can't set headers)
+fn last_event_id(headers: &HeaderMap, params: &[(String, String)]) -> Option<String> {
+    headers
+        .get(LAST_EVENT_ID)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from)
+        .or_else(|| params.iter().find(|(k, _)| k == "last_event_id").map(|(_, v)| v.clone()))
+        .filter(|id| !id.is_empty())
+}
+
+struct Upstream {
+    response: reqwest::Response,
+    /// How long the token the stream was opened with stays valid
+    token_left: Duration,
+}
 
-pub async fn proxy_sse_stream(State(state): State<AppState>) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+async fn open_upstream(state: &AppState, filters: &[(&'static str, String)], last_event_id: Option<&str>) -> Result<Upstream, (CloseReason, String)> {
     use reqwest::header::{ACCEPT, AUTHORIZATION, CACHE_CONTROL};
-    
-    tracing::info!("🔌 Dashboard connecting to real RCRT SSE stream");
-    
-    // Connect to real RCRT SSE stream
-    let sse_url = format!("{}/events/stream", state.rcrt_base_url);
-    
-    let mut request = state.http_client
-        .get(&sse_url)
+
+    let Some(token) = state.auth_manager.get_valid_token().await else {
+        return Err((CloseReason::AuthUnavailable, "no RCRT token available".to_string()));
+    };
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let token_left = state.auth_manager.token_expires_at().await
+        .map(|exp| Duration::from_secs(exp.saturating_sub(now)))
+        .unwrap_or(Duration::MAX);
+
+    let mut request = crate::request_id::forward(state.http_client
+        .get(format!("{}/events/stream", state.rcrt_base_url))
+        .query(filters)
         .header(ACCEPT, "text/event-stream")
-        .header(CACHE_CONTROL, "no-cache");
-        
-    // Add auth header if available
-    if let Some(token) = &state.jwt_token {
-        request = request.header(AUTHORIZATION, format!("Bearer {}", token));
-    }
-    
-    let response = match request.send().await {
-        Ok(resp) => {
-            if resp.status().is_success() {
-                tracing::info!("✅ Connected to real RCRT SSE stream");
-                resp
+        .header(CACHE_CONTROL, "no-cache")
+        .header(AUTHORIZATION, format!("Bearer {}", token)));
+    if let Some(id) = last_event_id {
+        request = request.header(LAST_EVENT_ID, id);
+    }
+
+    match request.send().await {
+        Ok(response) if response.status().is_success() => Ok(Upstream { response, token_left }),
+        Ok(response) => {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            let reason = if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+                CloseReason::AuthExpired
             } else {
-                tracing::error!("❌ RCRT SSE connection failed: {}", resp.status());
-                return Err(StatusCode::BAD_GATEWAY);
-            }
-        },
-        Err(e) => {
-            tracing::error!("❌ Failed to connect to RCRT SSE: {}", e);
-            return Err(StatusCode::SERVICE_UNAVAILABLE);
+                CloseReason::UpstreamUnavailable
+            };
+            Err((reason, format!("rcrt-server answered {}: {}", status, body)))
         }
+        Err(e) => Err((CloseReason::UpstreamUnavailable, e.to_string())),
+    }
+}
+
+pub async fn proxy_sse_stream(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(params): Query<Vec<(String, String)>>,
+    headers: HeaderMap,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let ip = addr.ip();
+    let filters = upstream_filters(&params);
+    let last_event_id = last_event_id(&headers, &params);
+
+    // Connected here rather than in the stream so the upstream call carries this request's X-Request-Id
+    let opened = match state.sse_streams.try_acquire(ip) {
+        Some(permit) => open_upstream(&state, &filters, last_event_id.as_deref()).await.map(|upstream| (permit, upstream)),
+        None => Err((CloseReason::TooManyStreams, format!("{} streams already open from {}", state.sse_streams.max_per_ip, ip))),
     };
-    
-    // 🔐 SECURE HIGH-PERFORMANCE PROXY: 2-second polling for near real-time (CORS compliant)
-    // Note: True streaming proxy requires complex reqwest stream handling (bytes_stream() doesn't exist)
+    match &opened {
+        Ok(_) => tracing::info!(%ip, ?filters, ?last_event_id, "🔌 Proxying RCRT SSE stream"),
+        Err((reason, detail)) => tracing::warn!(%ip, reason = reason.as_str(), %detail, "❌ RCRT SSE stream not proxied"),
+    }
+
     let stream = async_stream::stream! {
-        // Send initial connection success event
-        let connect_event = serde_json::json!({
-            "type": "system",
-            "message": "🔐 Secure high-performance RCRT proxy (2s polling - limited by CORS & reqwest API)",
-            "timestamp": chrono::Utc::now().to_rfc3339()
-        });
-        yield Ok(Event::default().data(connect_event.to_string()));
-        
-        // 🚀 HIGH-PERFORMANCE POLLING: 2-second updates for near real-time
-        let mut interval = tokio::time::interval(std::time::Duration::from_secs(2));
-        let mut last_seen_response_id: Option<String> = None;
-        let mut last_seen_request_id: Option<String> = None;
-        let mut counter = 0;
-        
-        loop {
-            interval.tick().await;
-            counter += 1;
-            
-            if let Some(token) = &state.jwt_token {
-                // Check for NEW tool requests (show immediately)
-                match state.http_client
-                    .get(&format!("{}/breadcrumbs?tag=tool:request", state.rcrt_base_url))
-                    .header("Authorization", format!("Bearer {}", token))
-                    .send()
-                    .await
-                {
-                    Ok(resp) if resp.status().is_success() => {
-                        if let Ok(requests) = resp.json::<Vec<serde_json::Value>>().await {
-                            if let Some(latest) = requests.first() {
-                                let id = latest.get("id").and_then(|v| v.as_str()).map(String::from);
-                                if id.is_some() && id != last_seen_request_id {
-                                    last_seen_request_id = id.clone();
-                                    
-                                    // 🎯 FETCH FULL CONTEXT for tool events to show payloads
-                                    let mut event = serde_json::json!({
-                                        "type": "breadcrumb.updated",
-                                        "schema_name": "tool.request.v1", 
-                                        "breadcrumb_id": id,
-                                        "title": latest.get("title"),
-                                        "tags": latest.get("tags"),
-                                        "version": latest.get("version"),
-                                        "updated_at": latest.get("updated_at"),
-                                        "timestamp": chrono::Utc::now().to_rfc3339()
-                                    });
-                                    
-                                    // Get full context for tool request details
-                                    if let Some(id_str) = &id {
-                                        match state.http_client
-                                            .get(&format!("{}/breadcrumbs/{}", state.rcrt_base_url, id_str))
-                                            .header("Authorization", format!("Bearer {}", token))
-                                            .send()
-                                            .await
-                                        {
-                                            Ok(context_resp) if context_resp.status().is_success() => {
-                                                if let Ok(context_data) = context_resp.json::<serde_json::Value>().await {
-                                                    if let Some(context) = context_data.get("context") {
-                                                        event["context"] = context.clone();
-                                                    }
-                                                }
-                                            },
-                                            _ => {} // Ignore context fetch failures
-                                        }
-                                    }
-                                    
-                                    yield Ok(Event::default().data(event.to_string()));
-                                }
-                            }
-                        }
-                    },
-                    _ => {} 
-                }
-                
-                // Check for NEW user messages and responses (for chat)
-                match state.http_client
-                    .get(&format!("{}/breadcrumbs?tag=user:message", state.rcrt_base_url))
-                    .header("Authorization", format!("Bearer {}", token))
-                    .send()
-                    .await
-                {
-                    Ok(resp) if resp.status().is_success() => {
-                        if let Ok(messages) = resp.json::<Vec<serde_json::Value>>().await {
-                            if let Some(latest) = messages.first() {
-                                let id = latest.get("id").and_then(|v| v.as_str()).map(String::from);
-                                if id.is_some() {
-                                    let event = serde_json::json!({
-                                        "type": "breadcrumb.updated",
-                                        "schema_name": "user.message.v1", 
-                                        "breadcrumb_id": id,
-                                        "title": latest.get("title"),
-                                        "tags": latest.get("tags"),
-                                        "version": latest.get("version"),
-                                        "updated_at": latest.get("updated_at"),
-                                        "timestamp": chrono::Utc::now().to_rfc3339()
-                                    });
-                                    yield Ok(Event::default().data(event.to_string()));
-                                }
-                            }
-                        }
-                    },
-                    _ => {}
-                }
-                
-                // Check for NEW user responses (for chat)
-                match state.http_client
-                    .get(&format!("{}/breadcrumbs?tag=user:response", state.rcrt_base_url))
-                    .header("Authorization", format!("Bearer {}", token))
-                    .send()
-                    .await
-                {
-                    Ok(resp) if resp.status().is_success() => {
-                        if let Ok(responses) = resp.json::<Vec<serde_json::Value>>().await {
-                            if let Some(latest) = responses.first() {
-                                let id = latest.get("id").and_then(|v| v.as_str()).map(String::from);
-                                if id.is_some() {
-                                    let event = serde_json::json!({
-                                        "type": "breadcrumb.updated",
-                                        "schema_name": "user.response.v1", 
-                                        "breadcrumb_id": id,
-                                        "title": latest.get("title"),
-                                        "tags": latest.get("tags"),
-                                        "version": latest.get("version"),
-                                        "updated_at": latest.get("updated_at"),
-                                        "timestamp": chrono::Utc::now().to_rfc3339()
-                                    });
-                                    yield Ok(Event::default().data(event.to_string()));
-                                }
-                            }
-                        }
-                    },
-                    _ => {}
-                }
-                
-                // Check for NEW tool responses (show immediately)  
-                match state.http_client
-                    .get(&format!("{}/breadcrumbs?tag=tool:response", state.rcrt_base_url))
-                    .header("Authorization", format!("Bearer {}", token))
-                    .send()
-                    .await
-                {
-                    Ok(resp) if resp.status().is_success() => {
-                        if let Ok(responses) = resp.json::<Vec<serde_json::Value>>().await {
-                            if let Some(latest) = responses.first() {
-                                let id = latest.get("id").and_then(|v| v.as_str()).map(String::from);
-                                if id.is_some() && id != last_seen_response_id {
-                                    last_seen_response_id = id.clone();
-                                    
-                                    // 🎯 FETCH FULL CONTEXT for tool response details  
-                                    let mut event = serde_json::json!({
-                                        "type": "breadcrumb.updated", 
-                                        "schema_name": "tool.response.v1",
-                                        "breadcrumb_id": id,
-                                        "title": latest.get("title"),
-                                        "tags": latest.get("tags"),
-                                        "version": latest.get("version"),
-                                        "updated_at": latest.get("updated_at"),
-                                        "timestamp": chrono::Utc::now().to_rfc3339()
-                                    });
-                                    
-                                    // Get full context for tool response details (LLM output, costs, etc)
-                                    if let Some(id_str) = &id {
-                                        match state.http_client
-                                            .get(&format!("{}/breadcrumbs/{}", state.rcrt_base_url, id_str))
-                                            .header("Authorization", format!("Bearer {}", token))
-                                            .send()
-                                            .await
-                                        {
-                                            Ok(context_resp) if context_resp.status().is_success() => {
-                                                if let Ok(context_data) = context_resp.json::<serde_json::Value>().await {
-                                                    if let Some(context) = context_data.get("context") {
-                                                        event["context"] = context.clone();
-                                                    }
-                                                }
-                                            },
-                                            _ => {} // Ignore context fetch failures
-                                        }
-                                    }
-                                    
-                                    yield Ok(Event::default().data(event.to_string()));
-                                }
+        let (reason, detail) = match opened {
+            Err(closed) => closed,
+            Ok((_permit, Upstream { mut response, token_left })) => {
+                let expiry = tokio::time::sleep(token_left);
+                tokio::pin!(expiry);
+                let mut parser = SseParser::default();
+                loop {
+                    let chunk = tokio::select! {
+                        chunk = response.chunk() => chunk,
+                        _ = &mut expiry => break (CloseReason::AuthExpired, "the stream's RCRT token expired; reconnect for a fresh one".to_string()),
+                    };
+                    match chunk {
+                        Ok(Some(bytes)) => {
+                            for frame in parser.push(&bytes) {
+                                yield Ok(frame.into_event());
                             }
                         }
-                    },
-                    _ => {}
+                        Ok(None) => break (CloseReason::UpstreamClosed, "rcrt-server ended the stream".to_string()),
+                        Err(e) => break (CloseReason::UpstreamClosed, e.to_string()),
+                    }
                 }
             }
-            
-            // Heartbeat every 15 cycles (30 seconds)
-            if counter % 15 == 0 {
-                let ping = serde_json::json!({
-                    "type": "ping",
-                    "ts": chrono::Utc::now().to_rfc3339(),
-                    "source": "secure_proxy"
-                });
-                yield Ok(Event::default().data(ping.to_string()));
+        };
+        tracing::info!(%ip, reason = reason.as_str(), "🔌 RCRT SSE proxy stream closed");
+        yield Ok(closed_event(reason, &detail));
+    };
+
+    Sse::new(stream)
+}
+
+/// One upstream event, ready to re-emit with its id and type intact
+#[derive(Debug, PartialEq)]
+struct Frame {
+    id: Option<String>,
+    event: Option<String>,
+    data: String,
+}
+
+impl Frame {
+    fn into_event(self) -> Event {
+        let mut event = Event::default().data(self.data);
+        if let Some(id) = self.id {
+            event = event.id(id);
+        }
+        if let Some(name) = self.event {
+            event = event.event(name);
+        }
+        event
+    }
+}
+
+/// Incremental text/event-stream parser; chunks may split lines (and UTF-8 sequences) anywhere
+#[derive(Default)]
+struct SseParser {
+    buffer: Vec<u8>,
+    id: Option<String>,
+    event: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    fn push(&mut self, chunk: &[u8]) -> Vec<Frame> {
+        self.buffer.extend_from_slice(chunk);
+        let mut frames = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|b| *b == b'\n' || *b == b'\r') {
+            // A trailing \r may be the first half of \r\n
+            if self.buffer[end] == b'\r' && end + 1 == self.buffer.len() {
+                break;
+            }
+            let terminator = if self.buffer[end] == b'\r' && self.buffer[end + 1] == b'\n' { 2 } else { 1 };
+            let line: Vec<u8> = self.buffer.drain(..end + terminator).take(end).collect();
+            if let Some(frame) = self.line(&String::from_utf8_lossy(&line)) {
+                frames.push(frame);
             }
         }
-    };
-    
-    Ok(Sse::new(stream))
+        frames
+    }
+
+    fn line(&mut self, line: &str) -> Option<Frame> {
+        if line.is_empty() {
+            let data = std::mem::take(&mut self.data);
+            let (id, event) = (self.id.take(), self.event.take());
+            return (!data.is_empty()).then(|| Frame { id, event, data: data.join("\n") });
+        }
+        // Comments, e.g. keep-alives
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "data" => self.data.push(value.to_string()),
+            "id" if !value.contains('\0') => self.id = Some(value.to_string()),
+            "event" => self.event = Some(value.to_string()),
+            _ => {}
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{auth::AuthManager, overview::OverviewCache};
+    use axum::{body::Body, extract::connect_info::MockConnectInfo, http::StatusCode, response::IntoResponse, routing::{get, post}, Json, Router};
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    fn pairs(params: &[(&str, &str)]) -> Vec<(String, String)> {
+        params.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_filters_map_onto_the_upstream_names() {
+        let params = pairs(&[
+            ("schema", "tool.request.v1"),
+            ("any_tag", "session:abc"),
+            ("any_tag", "session:def"),
+            ("all_tags", "a, b"),
+            ("none_tag", "archived"),
+            ("payload_version", "2"),
+            ("access_token", "dropped"),
+        ]);
+        assert_eq!(upstream_filters(&params), vec![
+            ("any_tags", "session:abc,session:def".to_string()),
+            ("all_tags", "a,b".to_string()),
+            ("none_tags", "archived".to_string()),
+            ("schema_name", "tool.request.v1".to_string()),
+            ("payload_version", "2".to_string()),
+        ]);
+        assert!(upstream_filters(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_last_event_id_prefers_the_header() {
+        let params = pairs(&[("last_event_id", "from-query")]);
+        let mut headers = HeaderMap::new();
+        assert_eq!(last_event_id(&headers, &params).as_deref(), Some("from-query"));
+        headers.insert(LAST_EVENT_ID, "from-header".parse().unwrap());
+        assert_eq!(last_event_id(&headers, &params).as_deref(), Some("from-header"));
+        assert_eq!(last_event_id(&HeaderMap::new(), &[]), None);
+    }
+
+    #[test]
+    fn test_parser_handles_split_chunks_and_line_endings() {
+        let mut parser = SseParser::default();
+        assert!(parser.push(b": keep-alive\n\nid: 7\r").is_empty());
+        assert!(parser.push(b"\nevent: update\ndata: {\"a\":").is_empty());
+        let frames = parser.push(b"1}\ndata: more\n\ndata: bare\r\rdata:x\n");
+        assert_eq!(frames, vec![
+            Frame { id: Some("7".into()), event: Some("update".into()), data: "{\"a\":1}\nmore".into() },
+            Frame { id: None, event: None, data: "bare".into() },
+        ]);
+        // A UTF-8 character split across chunks survives
+        let snowman = "data: ☃\n\n".as_bytes();
+        assert!(parser.push(&snowman[..7]).is_empty());
+        assert_eq!(parser.push(&snowman[7..])[0].data, "x\n☃");
+    }
+
+    #[test]
+    fn test_limiter_frees_slots_on_drop() {
+        let limiter = Arc::new(StreamLimiter::new(2));
+        let ip: IpAddr = [10, 0, 0, 1].into();
+        let (a, _b) = (limiter.try_acquire(ip).unwrap(), limiter.try_acquire(ip).unwrap());
+        assert!(limiter.try_acquire(ip).is_none());
+        assert!(limiter.try_acquire([10, 0, 0, 2].into()).is_some(), "the cap is per IP");
+        drop(a);
+        assert!(limiter.try_acquire(ip).is_some());
+        assert!(Arc::new(StreamLimiter::new(0)).try_acquire(ip).is_some(), "0 means no cap");
+    }
+
+    /// What the mock rcrt-server saw on each /events/stream connection
+    #[derive(Debug, Clone)]
+    struct Seen {
+        query: String,
+        authorization: Option<String>,
+        last_event_id: Option<String>,
+    }
+
+    /// Stands in for rcrt-server. `schema_name=hold` keeps the stream open, `schema_name=denied` is a 401,
+    /// anything else gets two events and ends
+    async fn upstream(seen: Arc<Mutex<Vec<Seen>>>) -> String {
+        let app = Router::new()
+            .route("/health", get(|| async { "ok" }))
+            .route("/auth/token", post(|| async {
+                Json(serde_json::json!({ "token": "t", "owner_id": "o", "agent_id": "a", "roles": [], "exp": i64::MAX }))
+            }))
+            .route("/events/stream", get(move |uri: axum::http::Uri, headers: HeaderMap| async move {
+                let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(String::from);
+                let query = uri.query().unwrap_or("").to_string();
+                seen.lock().unwrap().push(Seen { query: query.clone(), authorization: header("authorization"), last_event_id: header(LAST_EVENT_ID) });
+                if query.contains("schema_name=denied") {
+                    return (StatusCode::UNAUTHORIZED, "token expired").into_response();
+                }
+                let events = futures_util::stream::iter([
+                    Ok::<_, Infallible>(Event::default().id("41").data(r#"{"type":"breadcrumb.created"}"#)),
+                    Ok(Event::default().id("42").event("update").data(r#"{"type":"breadcrumb.updated"}"#)),
+                ]);
+                if query.contains("schema_name=hold") {
+                    Sse::new(futures_util::StreamExt::chain(events, futures_util::stream::pending())).into_response()
+                } else {
+                    Sse::new(events).into_response()
+                }
+            }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    fn dashboard(base_url: String, max_per_ip: usize) -> Router {
+        let http_client = reqwest::Client::new();
+        let state = AppState {
+            http_client: http_client.clone(),
+            rcrt_base_url: base_url.clone(),
+            owner_id: Uuid::new_v4(),
+            agent_id: Uuid::new_v4(),
+            jwt_token: None,
+            auth_manager: AuthManager::new(http_client, base_url, Uuid::new_v4(), Uuid::new_v4()),
+            overview_cache: Arc::new(OverviewCache::new(Duration::from_secs(10))),
+            login: None,
+            sse_streams: Arc::new(StreamLimiter::new(max_per_ip)),
+        };
+        crate::router(state, 1024).layer(MockConnectInfo(SocketAddr::from(([10, 0, 0, 1], 50000))))
+    }
+
+    async fn open(app: &Router, uri: &str, last_event_id: Option<&str>) -> axum::response::Response {
+        let mut req = axum::http::Request::get(uri);
+        if let Some(id) = last_event_id {
+            req = req.header(LAST_EVENT_ID, id);
+        }
+        let res = app.clone().oneshot(req.body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        res
+    }
+
+    async fn body(res: axum::response::Response) -> String {
+        String::from_utf8(axum::body::to_bytes(res.into_body(), usize::MAX).await.unwrap().to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_filters_and_last_event_id_reach_the_upstream() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let app = dashboard(upstream(seen.clone()).await, 4);
+
+        let first = body(open(&app, "/api/events/stream?schema=tool.request.v1&any_tag=session:abc", None).await).await;
+        // Upstream ids and event types come through for the browser to resume from
+        assert!(first.contains("id: 41\n"), "{}", first);
+        assert!(first.contains("event: update\n"), "{}", first);
+        assert!(first.contains(r#""type":"breadcrumb.updated""#), "{}", first);
+        assert!(first.contains(r#""type":"stream.closed""#), "{}", first);
+        assert!(first.contains(r#""reason":"upstream_closed""#), "{}", first);
+
+        // Reconnecting with other filters opens a new upstream stream with those, resuming from the header
+        body(open(&app, "/api/events/stream?none_tag=noise&payload_version=2", Some("42")).await).await;
+
+        let seen = seen.lock().unwrap().clone();
+        assert_eq!(seen.len(), 2);
+        assert_eq!(seen[0].query, "any_tags=session%3Aabc&schema_name=tool.request.v1");
+        assert_eq!(seen[0].authorization.as_deref(), Some("Bearer t"));
+        assert_eq!(seen[0].last_event_id, None);
+        assert_eq!(seen[1].query, "none_tags=noise&payload_version=2");
+        assert_eq!(seen[1].last_event_id.as_deref(), Some("42"));
+    }
+
+    #[tokio::test]
+    async fn test_streams_end_with_the_reason() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let app = dashboard(upstream(seen.clone()).await, 1);
+
+        let denied = body(open(&app, "/api/events/stream?schema=denied", None).await).await;
+        assert!(denied.contains(r#""reason":"auth_expired""#), "{}", denied);
+
+        // One stream per IP: a second is refused without reaching rcrt-server, until the first goes away
+        let held = open(&app, "/api/events/stream?schema=hold", None).await;
+        let refused = body(open(&app, "/api/events/stream", None).await).await;
+        assert!(refused.contains(r#""reason":"too_many_streams""#), "{}", refused);
+        assert_eq!(seen.lock().unwrap().len(), 2);
+        drop(held);
+        let after = body(open(&app, "/api/events/stream", None).await).await;
+        assert!(after.contains(r#""reason":"upstream_closed""#), "{}", after);
+        assert_eq!(seen.lock().unwrap().len(), 3);
+    }
 }
@@ -312,7 +312,7 @@ ${agent.roles.includes('subscriber') ? '✅ Subscribe to events (subscriber)' :
     // ============ HYGIENE MANAGEMENT ============
     
     async triggerHygieneCleanup() {
-        if (!confirm('🧹 Run hygiene cleanup?\n\nThis will remove expired breadcrumbs including:\n- Health checks older than 5 minutes\n- System pings older than 10 minutes\n- Agent thinking data older than 6 hours\n- Other expired temporary data\n\nProceed?')) {
+        if (!confirm('🧹 Run hygiene cleanup?\n\nThis will remove expired breadcrumbs including:\n- Breadcrumbs past their TTL\n- Breadcrumbs older than their TTL policy (health checks, pings, agent thinking, ...)\n\nProceed?')) {
             return;
         }
         
@@ -320,9 +320,9 @@ ${agent.roles.includes('subscriber') ? '✅ Subscribe to events (subscriber)' :
             console.log('Triggering hygiene cleanup...');
             
             const result = await apiClient.triggerHygiene();
-            const totalCleaned = result.ttl_purged + result.health_checks_purged + result.expired_purged;
+            const totalCleaned = result.ttl_purged + result.policy_purged + result.expired_purged;
             
-            alert(`✅ Hygiene cleanup completed!\n\nCleaned up:\n- TTL expired: ${result.ttl_purged}\n- Health checks: ${result.health_checks_purged}\n- Other expired: ${result.expired_purged}\n\nTotal: ${totalCleaned} breadcrumbs removed`);
+            alert(`✅ Hygiene cleanup completed!\n\nCleaned up:\n- TTL expired: ${result.ttl_purged}\n- TTL policies: ${result.policy_purged}\n- Other expired: ${result.expired_purged}\n\nTotal: ${totalCleaned} breadcrumbs removed`);
             
             // Notify the main dashboard to refresh
             if (window.dashboard && window.dashboard.refreshData) {
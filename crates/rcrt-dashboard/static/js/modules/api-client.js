@@ -3,6 +3,17 @@
  * Handles all HTTP requests to the RCRT API
  */
 
+/**
+ * X-CSRF-Token for mutating requests, echoed from the cookie the dashboard sets at login
+ */
+function csrfHeader(method = 'GET') {
+    if (['GET', 'HEAD', 'OPTIONS'].includes(method.toUpperCase())) {
+        return {};
+    }
+    const match = document.cookie.match(/(?:^|;\s*)rcrt_dashboard_csrf=([^;]+)/);
+    return match ? { 'X-CSRF-Token': match[1] } : {};
+}
+
 export class ApiClient {
     constructor(baseUrl = '') {
         this.baseUrl = baseUrl;
@@ -13,11 +24,12 @@ export class ApiClient {
      */
     async request(url, options = {}) {
         const config = {
+            ...options,
             headers: {
                 'Content-Type': 'application/json',
+                ...csrfHeader(options.method),
                 ...options.headers
-            },
-            ...options
+            }
         };
         
         // Add timeout to prevent hanging requests
@@ -34,6 +46,11 @@ export class ApiClient {
             const response = await fetch(this.baseUrl + url, config);
             clearTimeout(timeoutId);
             
+            // Dashboard login is on and the session is gone: back to the login form
+            if (response.status === 401 && url.startsWith('/api/')) {
+                window.location.href = '/static/login.html';
+            }
+            
             if (!response.ok) {
                 throw new Error(`HTTP ${response.status}: ${response.statusText}`);
             }
@@ -276,6 +293,14 @@ export class ApiClient {
         });
     }
     
+    /**
+     * Load overview counts in one call (cached server-side for ~10s).
+     * Fields that failed upstream come back as { error: "..." }
+     */
+    async loadStatsOverview() {
+        return await this.request('/api/stats/overview');
+    }
+
     /**
      * Load admin entities (tenants, secrets, acl, etc.)
      * @param {string} entityType - Type of entity to load
@@ -83,6 +83,11 @@ export class EventStreamManager {
             const data = JSON.parse(event.data);
             console.log('Parsed event data:', data);
             
+            // The proxy's last word on a stream: auth_expired, upstream_closed (e.g. a server restart), too_many_streams, ...
+            if (data.type === 'stream.closed') {
+                this.updateStreamStatus(false, 'Stream closed: ' + data.reason);
+            }
+            
             // Filter out ping events if hiding is enabled
             if (dashboardState.hidePings && data.type === 'ping') {
                 console.log('Ping event filtered out');
@@ -132,7 +132,7 @@ export interface BulkDeleteResult {
 
 export interface HygieneResult {
   ttl_purged: number;
-  health_checks_purged: number;
+  policy_purged: number;
   expired_purged: number;
 }
 
@@ -0,0 +1,51 @@
+//! Embedded Breadcrumbs
+//! The breadcrumb service in-process, without the HTTP server: same policies, history, TTLs and
+//! events as the API, against the same Postgres.
+//!
+//!     DATABASE_URL=postgres://... cargo run -p rcrt-server --example embedded --no-default-features
+//!
+//! With the default `nats` feature it also needs NATS_URL, and events are published there.
+
+use rcrt_core::db::Db;
+use rcrt_core::roles::Role;
+use rcrt_server::auth::{AuthConfig, AuthContext, AuthMode};
+use rcrt_server::service::{BreadcrumbService, CreateReq, UpdateReq};
+use rcrt_server::AppState;
+use serde_json::json;
+use uuid::Uuid;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let (owner_id, agent_id) = (Uuid::new_v4(), Uuid::new_v4());
+    let db = Db::connect(&std::env::var("DATABASE_URL")?, owner_id, None).await?;
+    rcrt_server::migrate(&db).await?;
+    db.ensure_tenant(owner_id, "Embedded").await?;
+    db.upsert_agent(owner_id, agent_id, vec!["emitter".into(), "subscriber".into()]).await?;
+
+    // Nothing is authenticated in-process; the AuthContext says who each call acts as
+    let auth_config = AuthConfig::new(AuthMode::Disabled { owner_id, agent_id }, None, None, None, None)?;
+    #[cfg(feature = "nats")]
+    let state = AppState::new(db, auth_config, async_nats::connect(std::env::var("NATS_URL")?.as_str()).await?, 120)?;
+    #[cfg(not(feature = "nats"))]
+    let state = AppState::new(db, auth_config, 120)?;
+    let service = BreadcrumbService::new(state);
+    let me = AuthContext { owner_id, agent_id, roles: vec![Role::Emitter, Role::Subscriber] };
+
+    let created = service.create(&me, CreateReq {
+        title: "Embedded note".into(),
+        context: json!({ "step": 1 }),
+        tags: vec!["embedded".into()],
+        ..CreateReq::default()
+    }, None).await?;
+    let id = created.breadcrumb.id;
+
+    let update = UpdateReq { context: Some(json!({ "step": 2 })), ..UpdateReq::default() };
+    let updated = service.update(&me, id, Some(created.breadcrumb.version), false, update).await?;
+    println!("{} is at version {}", id, updated.version);
+
+    let view = service.get_context(&me, id, false).await?;
+    println!("context: {}", view.context);
+
+    service.delete(&me, id, false).await?;
+    Ok(())
+}
@@ -0,0 +1,154 @@
+//! Access Log
+//! Per-day access counters behind the breadcrumb analytics. Reads, context assemblies and search hits
+//! are added up in memory by breadcrumb, agent, kind and day, then flushed every ACCESS_LOG_FLUSH_SECS
+//! as one batched upsert that also bumps read_count. A failed flush keeps its counts for the next one;
+//! a crash loses at most one interval's worth
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use chrono::{NaiveDate, Utc};
+use rcrt_core::db::Db;
+use rcrt_core::error::DbError;
+use rcrt_core::models::{AccessCount, AccessType};
+use uuid::Uuid;
+
+use crate::AppState;
+
+type Key = (Uuid, Uuid, Uuid, AccessType, NaiveDate);
+
+/// Counts not yet flushed, keyed by (owner, breadcrumb, agent, access type, day)
+#[derive(Default)]
+pub struct AccessLog {
+    pending: Mutex<HashMap<Key, i64>>,
+}
+
+impl AccessLog {
+    /// Count one access of each of `ids` by `agent_id` of `owner_id` today
+    pub fn record(&self, owner_id: Uuid, agent_id: Uuid, access_type: AccessType, ids: &[Uuid]) {
+        self.record_on(Utc::now().date_naive(), owner_id, agent_id, access_type, ids);
+    }
+
+    fn record_on(&self, day: NaiveDate, owner_id: Uuid, agent_id: Uuid, access_type: AccessType, ids: &[Uuid]) {
+        if ids.is_empty() {
+            return;
+        }
+        if let Ok(mut pending) = self.pending.lock() {
+            for id in ids {
+                *pending.entry((owner_id, *id, agent_id, access_type, day)).or_default() += 1;
+            }
+        }
+    }
+
+    fn drain(&self) -> Vec<AccessCount> {
+        let pending = match self.pending.lock() {
+            Ok(mut pending) => std::mem::take(&mut *pending),
+            Err(_) => return Vec::new(),
+        };
+        pending.into_iter().map(|((owner_id, breadcrumb_id, agent_id, access_type, day), count)| AccessCount {
+            owner_id, breadcrumb_id, agent_id, access_type, day, count,
+        }).collect()
+    }
+
+    /// Write everything counted so far; on failure the counts go back to be retried with the next batch
+    pub async fn flush(&self, db: &Db) -> Result<usize, DbError> {
+        let counts = self.drain();
+        if counts.is_empty() {
+            return Ok(0);
+        }
+        match db.record_breadcrumb_access(&counts).await {
+            Ok(_) => Ok(counts.len()),
+            Err(e) => {
+                if let Ok(mut pending) = self.pending.lock() {
+                    for c in counts {
+                        *pending.entry((c.owner_id, c.breadcrumb_id, c.agent_id, c.access_type, c.day)).or_default() += c.count;
+                    }
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Flush the access log every ACCESS_LOG_FLUSH_SECS (default 10)
+pub fn start_flusher(state: AppState) -> tokio::task::JoinHandle<()> {
+    let secs = std::env::var("ACCESS_LOG_FLUSH_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(10u64);
+    tracing::info!("📈 Access log flushing every {}s", secs.max(1));
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(secs.max(1)));
+        loop {
+            interval.tick().await;
+            if let Err(e) = state.access_log.flush(&state.db).await {
+                tracing::warn!("📈 Access log flush failed, keeping the counts: {}", e);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accesses_add_up_per_agent_kind_and_day() {
+        let log = AccessLog::default();
+        let (owner, a, b, agent) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let today = NaiveDate::from_ymd_opt(2026, 3, 2).unwrap();
+        let yesterday = today.pred_opt().unwrap();
+        log.record_on(today, owner, agent, AccessType::ApiRead, &[a, b]);
+        log.record_on(today, owner, agent, AccessType::ApiRead, &[a]);
+        log.record_on(today, owner, agent, AccessType::SearchHit, &[a]);
+        log.record_on(yesterday, owner, agent, AccessType::ApiRead, &[a]);
+        log.record_on(today, owner, agent, AccessType::ContextAssembly, &[]);
+
+        let mut counts = log.drain();
+        counts.sort_by_key(|c| (c.breadcrumb_id == b, c.day, c.access_type.as_str()));
+        let summary: Vec<_> = counts.iter().map(|c| (c.breadcrumb_id, c.access_type, c.day, c.count)).collect();
+        assert_eq!(summary, vec![
+            (a, AccessType::ApiRead, yesterday, 1),
+            (a, AccessType::ApiRead, today, 2),
+            (a, AccessType::SearchHit, today, 1),
+            (b, AccessType::ApiRead, today, 1),
+        ]);
+        assert!(log.drain().is_empty());
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_flush_upserts_counts_and_bumps_read_count(pool: sqlx::PgPool) {
+        use crate::test_support::crumb;
+
+        let db = Db { pool: pool.clone() };
+        let (owner_id, agent_id, other_agent) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        db.ensure_tenant(owner_id, "Access Log Test").await.unwrap();
+        let create = |title: &str| rcrt_core::models::BreadcrumbCreate { title: title.into(), ..crumb("knowledge.v1", &[]) };
+        let kept = db.create_breadcrumb_for(owner_id, Some(agent_id), Some(agent_id), create("kept")).await.unwrap();
+        let deleted = db.create_breadcrumb_for(owner_id, Some(agent_id), Some(agent_id), create("deleted")).await.unwrap();
+
+        let log = AccessLog::default();
+        log.record(owner_id, agent_id, AccessType::ApiRead, &[kept.id, deleted.id]);
+        log.record(owner_id, other_agent, AccessType::ContextAssembly, &[kept.id]);
+        log.record(owner_id, agent_id, AccessType::SearchHit, &[kept.id]);
+        db.delete_breadcrumb(owner_id, agent_id, deleted.id).await.unwrap();
+        assert_eq!(log.flush(&db).await.unwrap(), 4);
+        assert_eq!(log.flush(&db).await.unwrap(), 0);
+
+        // A second batch lands on the same day's rows
+        log.record(owner_id, agent_id, AccessType::ApiRead, &[kept.id]);
+        log.flush(&db).await.unwrap();
+
+        let today = Utc::now().date_naive();
+        let mut counts: Vec<_> = db.breadcrumb_access_for(owner_id, kept.id, today).await.unwrap()
+            .into_iter().map(|c| (c.access_type, c.agent_id == agent_id, c.count)).collect();
+        counts.sort_by_key(|(t, _, _)| t.as_str());
+        assert_eq!(counts, vec![(AccessType::ApiRead, true, 2), (AccessType::ContextAssembly, false, 1), (AccessType::SearchHit, true, 1)]);
+        // Search hits aren't reads
+        let read_count: Option<i32> = sqlx::query_scalar("select read_count from breadcrumbs where id = $1")
+            .bind(kept.id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(read_count, Some(3));
+        assert!(db.breadcrumb_access_for(owner_id, deleted.id, today).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,39 @@
+//! ACL Handlers
+//! Per-breadcrumb grants to other agents; curators grant and revoke
+
+use axum::{extract::State, http::StatusCode, Json};
+use rcrt_core::models::AclGrantAgent;
+use rcrt_core::roles::Role;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{auth::AuthContext, db_errors::db_error, AppState};
+
+pub async fn grant_acl(State(state): State<AppState>, auth: AuthContext, Json(req): Json<AclGrantAgent>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "curator role required".into())); }
+    let id = state.db.grant_acl_agent(auth.owner_id, req.breadcrumb_id, req.grantee_agent_id, &req.action).await.map_err(db_error)?;
+    Ok(Json(json!({"id": id})))
+}
+
+#[derive(Deserialize)]
+pub struct AclRevokeReq { breadcrumb_id: Uuid, grantee_agent_id: Uuid, action: String }
+pub async fn revoke_acl(State(state): State<AppState>, auth: AuthContext, Json(req): Json<AclRevokeReq>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "curator role required".into())); }
+    let rows = state.db.revoke_acl_agent(auth.owner_id, req.breadcrumb_id, req.grantee_agent_id, &req.action).await.map_err(db_error)?;
+    Ok(Json(json!({"rows": rows})))
+}
+
+pub async fn list_acls(State(state): State<AppState>, auth: AuthContext) -> Result<Json<Vec<serde_json::Value>>, (StatusCode, String)> {
+    let acls = state.db.list_acls(auth.owner_id).await.map_err(db_error)?;
+    let out = acls.into_iter().map(|(id, breadcrumb_id, grantee_agent_id, actions, created_at)| {
+        json!({
+            "id": id,
+            "breadcrumb_id": breadcrumb_id,
+            "grantee_agent_id": grantee_agent_id,
+            "actions": actions,
+            "created_at": created_at
+        })
+    }).collect();
+    Ok(Json(out))
+}
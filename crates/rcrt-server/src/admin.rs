@@ -0,0 +1,286 @@
+//! Admin Handlers
+//! Manual purge and hygiene runs, hygiene stats, session close, and embedding backfill
+
+use axum::{extract::{Query, State}, http::StatusCode, Json};
+use rcrt_core::models::{BreadcrumbCreate, COLUMN_EMBEDDING_MODEL};
+use rcrt_core::roles::Role;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{auth::{require_admin, AuthContext}, breadcrumbs::embedding_input, db_errors::db_error, domain_metrics, embedding, embedding_policy, events::publish_breadcrumb_created, history_retention, hygiene, hygiene_config::HygieneConfigs, internal_error, ttl_policy, AppState};
+
+pub async fn admin_purge(State(state): State<AppState>, auth: AuthContext) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    require_admin(&state, &auth)?;
+    
+    tracing::info!("Admin purge triggered by agent: {}", auth.agent_id);
+    
+    // Run comprehensive cleanup
+    let ttl_purged = state.db.purge_expired_for_owner(auth.owner_id).await.map_err(db_error)?;
+    
+    let expired_purged = hygiene::cleanup_expired_breadcrumbs(&state.db)
+        .await
+        .map_err(internal_error)?;
+    
+    let policy_purged = hygiene::cleanup_policy_expired(&state.db, &hygiene::HygieneConfig::default())
+        .await
+        .map_err(internal_error)?;
+    
+    let total_purged = ttl_purged + (policy_purged as i64) + (expired_purged as i64);
+    
+    tracing::info!("Admin purge completed: {} breadcrumbs purged", total_purged);
+    
+    Ok(Json(json!({
+        "purged": total_purged,
+        "ttl_purged": ttl_purged,
+        "policy_purged": policy_purged,
+        "expired_purged": expired_purged
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct SessionCloseQuery { purge: Option<bool>, ttl_hours: Option<i64> }
+
+// Close a session: stamp a TTL on (or purge) everything tagged with it, record
+// a session.closed.v1 breadcrumb, and emit its events so caches can evict the session.
+pub async fn close_session(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(session_tag): axum::extract::Path<String>, Query(q): Query<SessionCloseQuery>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if !session_tag.starts_with("session:") {
+        return Err((StatusCode::BAD_REQUEST, "session tag must start with 'session:'".into()));
+    }
+    let is_curator = auth.has_role(Role::Curator);
+    let purge = q.purge.unwrap_or(false);
+    if purge && !is_curator {
+        return Err((StatusCode::FORBIDDEN, "curator role required for purge".into()));
+    }
+    if !is_curator {
+        if !auth.has_role(Role::Emitter) {
+            return Err((StatusCode::FORBIDDEN, "emitter role required".into()));
+        }
+        if !state.db.is_session_emitter(auth.owner_id, auth.agent_id, &session_tag).await.map_err(db_error)? {
+            return Err((StatusCode::FORBIDDEN, "only the session's emitter or a curator can close it".into()));
+        }
+    }
+
+    let batch_size = state.session_close_batch_size;
+    let ttl_hours = q.ttl_hours.unwrap_or(state.session_close_ttl_hours).max(0);
+    let ttl = chrono::Utc::now() + chrono::Duration::hours(ttl_hours);
+
+    let affected = if purge {
+        state.db.purge_session(auth.owner_id, &session_tag, batch_size).await.map_err(db_error)?
+    } else {
+        state.db.stamp_session_ttl(auth.owner_id, &session_tag, ttl, batch_size).await.map_err(db_error)?
+    };
+    tracing::info!("🧹 Session {} closed by {}: {} breadcrumbs {}", session_tag, auth.agent_id, affected, if purge { "purged" } else { "ttl-stamped" });
+
+    let closed = BreadcrumbCreate {
+        title: format!("Session closed: {}", session_tag),
+        description: None,
+        semantic_version: None,
+        context: json!({
+            "session_tag": session_tag,
+            "closed_by": auth.agent_id,
+            "closed_at": chrono::Utc::now(),
+            "purged": purge,
+            "breadcrumbs_affected": affected,
+            "ttl": if purge { None } else { Some(ttl) },
+        }),
+        tags: vec![session_tag.clone(), "system:session-closed".to_string()],
+        schema_name: Some("session.closed.v1".to_string()),
+        llm_hints: None,
+        visibility: None,
+        sensitivity: None,
+        ttl: Some(ttl),
+        ttl_type: Some("datetime".to_string()),
+        ttl_config: None,
+        ttl_source: Some("session-closed".to_string()),
+        entity_keywords: None,
+        entities: None,
+    };
+    let bc = state.db.create_breadcrumb_for(auth.owner_id, Some(auth.agent_id), Some(auth.agent_id), closed).await.map_err(db_error)?;
+    publish_breadcrumb_created(&state, auth.owner_id, &bc).await;
+
+    Ok(Json(json!({
+        "ok": true,
+        "session_tag": session_tag,
+        "purged": purge,
+        "breadcrumbs_affected": affected,
+        "ttl": if purge { None } else { Some(ttl) },
+        "closed_breadcrumb_id": bc.id,
+    })))
+}
+
+// Hygiene management endpoints
+pub async fn get_hygiene_stats(State(state): State<AppState>, auth: AuthContext) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    // Only curators can view hygiene stats
+    if !auth.has_role(Role::Curator) { 
+        return Err((StatusCode::FORBIDDEN, "curator role required".into())); 
+    }
+    
+    let stats = match state.hygiene_stats.lock() {
+        Ok(stats) => stats.clone(),
+        Err(_) => hygiene::HygieneStats::default(),
+    };
+    let history = &state.history_retention;
+    let tenant_config = HygieneConfigs::load(&state.db, Some(auth.owner_id)).await.map_err(db_error)?;
+    
+    Ok(Json(json!({
+        "runs_completed": stats.runs_completed,
+        "total_breadcrumbs_purged": stats.total_breadcrumbs_purged,
+        "total_agents_cleaned": stats.total_agents_cleaned,
+        "total_history_pruned": stats.total_history_pruned,
+        "keep_latest_purged": stats.keep_latest_purged.values().filter(|d| d.owner_id == auth.owner_id).collect::<Vec<_>>(),
+        "last_run_duration_ms": stats.last_run_duration_ms,
+        "last_run_at": stats.last_run_at,
+        "last_run_errors": stats.last_run_errors,
+        "history_retention": {
+            "default": history.default,
+            "keep_latest": history.keep_latest,
+            "batch_size": history.batch_size,
+            "max_per_run": history.max_per_run
+        },
+        // source is the system.hygiene.config.v1 breadcrumb in force, null for the env values
+        "tenant_config": tenant_config.for_owner(auth.owner_id),
+        "hygiene_enabled": true,
+        "last_updated": chrono::Utc::now().to_rfc3339()
+    })))
+}
+
+pub async fn trigger_hygiene_run(State(state): State<AppState>, auth: AuthContext) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    // Only curators can trigger manual hygiene runs
+    if !auth.has_role(Role::Curator) {
+        return Err((StatusCode::FORBIDDEN, "curator role required".into()));
+    }
+    
+    tracing::info!("Manual hygiene run triggered by agent: {}", auth.agent_id);
+    
+    // The same per-tenant pass the runner makes, on freshly loaded tenant configs
+    let policies = ttl_policy::TtlPolicies::load(&state.db, None)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let tenants = hygiene::run_tenant_cleanup(&state, &hygiene::HygieneConfig::default(), &policies)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let (expired_purged, policy_purged, keep_latest) = (tenants.expired, tenants.policy, tenants.keep_latest);
+    
+    let history_pruned = history_retention::prune_breadcrumb_history(&state.db, &state.history_retention)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    
+    let selectors_removed = hygiene::cleanup_expired_selectors(&state)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    
+    let total_cleaned = policy_purged + expired_purged + keep_latest.iter().map(|d| d.deleted).sum::<u64>();
+    
+    tracing::info!("Manual hygiene completed: {} breadcrumbs cleaned, {} history versions pruned", total_cleaned, history_pruned);
+    
+    Ok(Json(json!({
+        "triggered": true,
+        "policy_purged": policy_purged,
+        "expired_breadcrumbs_purged": expired_purged,
+        // The run covers every tenant; only the caller's policies are named
+        "keep_latest_purged": keep_latest.iter().filter(|d| d.owner_id == auth.owner_id).collect::<Vec<_>>(),
+        "history_versions_pruned": history_pruned,
+        "expired_selectors_removed": selectors_removed,
+        "agents_cleaned": tenants.agents,
+        "tenants_failed": tenants.failed,
+        "total_cleaned": total_cleaned,
+        "message": "Manual hygiene run completed successfully"
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct BackfillQuery { which: Option<String>, model: Option<String>, after: Option<Uuid>, limit: Option<i64>, per_sec: Option<u32> }
+
+/// Embed up to `limit` of the owner's breadcrumbs missing a content (`which=content`, the default) or
+/// title (`which=title`) vector. Rows whose schema isn't embedded, that are above EMBED_SENSITIVITY_MAX,
+/// or whose embedding fails, are skipped and stay null; keep calling with `after=next_after` while `has_more`.
+/// `model=` names EMBED_TARGET_MODEL_NAME to fill breadcrumb_embeddings for a model migration (content
+/// only). At most `per_sec` rows a second (EMBED_BACKFILL_PER_SEC when absent, 0 for no pacing), so a
+/// re-embed leaves CPU and connections to live traffic
+pub async fn backfill_embeddings(State(state): State<AppState>, auth: AuthContext, Query(q): Query<BackfillQuery>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    require_admin(&state, &auth)?;
+    let title = match q.which.as_deref() {
+        None | Some("content") => false,
+        Some("title") => true,
+        Some(other) => return Err((StatusCode::BAD_REQUEST, format!("which must be content or title, not {}", other))),
+    };
+    // None for the column model, whose vectors live in breadcrumbs.embedding
+    let target = match q.model.as_deref().filter(|m| *m != COLUMN_EMBEDDING_MODEL) {
+        None => None,
+        Some(model) => match state.target_model.as_ref().filter(|t| t.name == model) {
+            Some(_) if title => return Err((StatusCode::BAD_REQUEST, "which=title only applies to the column model".into())),
+            Some(target) => Some(target.clone()),
+            None => return Err((StatusCode::BAD_REQUEST, format!("{} is neither the column model nor EMBED_TARGET_MODEL_NAME", model))),
+        },
+    };
+    let limit = q.limit.unwrap_or(200).clamp(1, 1000);
+    let per_sec = q.per_sec.unwrap_or(state.embed_backfill_per_sec);
+
+    let rows = match &target {
+        Some(target) => state.db.list_breadcrumbs_missing_model_embedding(auth.owner_id, Some(auth.agent_id), &target.name, q.after, limit).await,
+        None => state.db.list_breadcrumbs_missing_embedding(auth.owner_id, Some(auth.agent_id), title, q.after, limit).await,
+    }.map_err(db_error)?;
+    let (mut embedded, mut skipped, mut failed) = (0, 0, 0);
+    let started = std::time::Instant::now();
+    for bc in &rows {
+        if !embedding_policy::should_embed_schema(bc.schema_name.as_deref())
+            || !embedding_policy::within_sensitivity(Some(&bc.sensitivity), &state.embed_sensitivity_max) {
+            skipped += 1;
+            continue;
+        }
+        if per_sec > 0 {
+            // Embedding n starts no earlier than n / per_sec seconds in
+            let due = started + std::time::Duration::from_secs_f64((embedded + failed) as f64 / per_sec as f64);
+            tokio::time::sleep_until(due.into()).await;
+        }
+        let text = if title {
+            embedding::standalone_text(&bc.title)
+        } else {
+            embedding_input(&state, &bc.title, &bc.context, bc.llm_hints.as_ref(), bc.schema_name.as_deref()).await
+        };
+        let vector = {
+            let _timer = domain_metrics::embedding_timer("backfill");
+            match &target {
+                Some(target) => target.embedder.embed(text),
+                None => embedding::embed_text(text),
+            }
+        };
+        let stored = match vector {
+            Ok(v) if target.is_some() => state.db.set_breadcrumb_model_embedding(auth.owner_id, Some(auth.agent_id), bc.id, &target.as_ref().unwrap().name, v).await,
+            Ok(v) if title => state.db.set_breadcrumb_title_embedding(auth.owner_id, Some(auth.agent_id), bc.id, v).await,
+            Ok(v) => state.db.set_breadcrumb_embedding(auth.owner_id, Some(auth.agent_id), bc.id, v).await,
+            Err(e) => {
+                tracing::warn!("Backfill embedding failed for {}: {}", bc.id, e);
+                failed += 1;
+                continue;
+            }
+        };
+        stored.map_err(db_error)?;
+        embedded += 1;
+    }
+    let model = target.as_ref().map_or(COLUMN_EMBEDDING_MODEL, |t| t.name.as_str());
+    tracing::info!("Embedding backfill ({}, {}) by {}: {} embedded, {} skipped, {} failed", if title { "title" } else { "content" }, model, auth.agent_id, embedded, skipped, failed);
+
+    Ok(Json(json!({
+        "which": if title { "title" } else { "content" },
+        "model": model,
+        "embedded": embedded,
+        "skipped": skipped,
+        "failed": failed,
+        "next_after": rows.last().map(|bc| bc.id),
+        "has_more": rows.len() as i64 == limit
+    })))
+}
+
+/// Null the embeddings of the owner's breadcrumbs above EMBED_SENSITIVITY_MAX, e.g. after lowering it;
+/// rows embedded before the limit existed otherwise keep surfacing through similarity
+pub async fn clear_sensitive_embeddings(State(state): State<AppState>, auth: AuthContext) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    require_admin(&state, &auth)?;
+    let cleared = state.db.clear_embeddings_above_sensitivity(auth.owner_id, &state.embed_sensitivity_max, 500).await.map_err(db_error)?;
+    tracing::info!("Cleared embeddings of {} breadcrumbs above {} for {} (by {})", cleared, state.embed_sensitivity_max.as_str(), auth.owner_id, auth.agent_id);
+    Ok(Json(json!({
+        "cleared": cleared,
+        "embed_sensitivity_max": state.embed_sensitivity_max.as_str()
+    })))
+}
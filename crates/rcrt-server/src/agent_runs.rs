@@ -0,0 +1,396 @@
+//! Agent Runs
+//! The OpenRouter-backed /agents/run demo pipeline as a persisted run: stages execute in a background
+//! task and are saved as they finish, runs can be polled and cancelled, and runs orphaned by a restart are failed
+
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+use axum::{extract::{Query, State}, http::StatusCode, response::{IntoResponse, Response}, Json};
+use chrono::Utc;
+use rcrt_core::models::{AgentRun, AgentRunStage};
+use rcrt_core::roles::Role;
+use reqwest::Client as HttpClient;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::task::AbortHandle;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::{auth::AuthContext, db_errors::db_error, internal_error, AppState};
+
+const OPENROUTER_CHAT_URL: &str = "https://openrouter.ai/api/v1/chat/completions";
+/// One stage's LLM call gives up after this, so a live run never goes this long without a write
+const STAGE_TIMEOUT: Duration = Duration::from_secs(300);
+/// How often the sweeper fails orphaned runs and deletes expired ones; the first sweep is at startup
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Run retention and staleness, plus the runs this process is executing
+pub struct AgentRuns {
+    retention: chrono::Duration,
+    stale_after: chrono::Duration,
+    chat_url: String,
+    /// Background runs by id, for cancel
+    tasks: Mutex<HashMap<Uuid, AbortHandle>>,
+}
+
+impl AgentRuns {
+    /// `stale_after` is raised to twice the stage timeout so a slow stage is never taken for a dead server
+    pub fn new(retention: Duration, stale_after: Duration) -> Self {
+        let seconds = |d: Duration| chrono::Duration::seconds(d.as_secs().min(i64::MAX as u64) as i64);
+        AgentRuns {
+            retention: seconds(retention),
+            stale_after: seconds(stale_after.max(STAGE_TIMEOUT * 2)),
+            chat_url: OPENROUTER_CHAT_URL.to_string(),
+            tasks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn live(&self) -> Vec<Uuid> {
+        self.tasks.lock().unwrap_or_else(PoisonError::into_inner).keys().copied().collect()
+    }
+
+    fn forget(&self, run_id: Uuid) {
+        self.tasks.lock().unwrap_or_else(PoisonError::into_inner).remove(&run_id);
+    }
+
+    /// Stop the run's task if it executes here; another instance's run stops at its next stage
+    fn abort(&self, run_id: Uuid) {
+        if let Some(task) = self.tasks.lock().unwrap_or_else(PoisonError::into_inner).remove(&run_id) {
+            task.abort();
+        }
+    }
+}
+
+async fn openrouter_chat(
+    client: &HttpClient,
+    base_url: &str,
+    api_key: &str,
+    referer: Option<&str>,
+    site_title: Option<&str>,
+    model: &str,
+    role_system: String,
+    user_messages: serde_json::Value,
+) -> Result<String, (StatusCode, String)> {
+    let sys_msg = serde_json::json!({"role":"system","content": role_system});
+    let merged: serde_json::Value = match user_messages {
+        serde_json::Value::Array(arr) => {
+            let mut msgs = vec![sys_msg];
+            msgs.extend(arr);
+            serde_json::Value::Array(msgs)
+        }
+        other => serde_json::json!([sys_msg, {"role":"user","content": other}])
+    };
+    let mut req = client.post(base_url)
+        .header("Authorization", format!("Bearer {}", api_key))
+        .header("Content-Type", "application/json");
+    if let Some(r) = referer { req = req.header("HTTP-Referer", r); }
+    if let Some(t) = site_title { req = req.header("X-Title", t); }
+    let payload = serde_json::json!({
+        "model": model,
+        "messages": merged,
+        "stream": false
+    });
+    let resp = req.json(&payload).send().await.map_err(internal_error)?;
+    let status = resp.status();
+    if !status.is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        return Err((StatusCode::BAD_GATEWAY, format!("openrouter {}: {}", status, body)));
+    }
+    let v: serde_json::Value = resp.json().await.map_err(internal_error)?;
+    let content = v.get("choices")
+        .and_then(|c| c.get(0))
+        .and_then(|c| c.get("message"))
+        .and_then(|m| m.get("content"))
+        .and_then(|c| c.as_str())
+        .ok_or((StatusCode::BAD_GATEWAY, "invalid openrouter response".into()))?;
+    Ok(content.to_string())
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct AgentRunInput {
+    model: String,
+    messages: serde_json::Value,
+    referer: Option<String>,
+    site_title: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct AgentRunOutput {
+    agent1_plan: String,
+    agent2_execution: String,
+    agent3_summary: String,
+    final_answer: String,
+}
+
+/// What every stage's OpenRouter call needs
+struct Pipeline {
+    client: HttpClient,
+    chat_url: String,
+    api_key: String,
+    referer: Option<String>,
+    site_title: Option<String>,
+    input: AgentRunInput,
+}
+
+impl Pipeline {
+    async fn chat(&self, role_system: String) -> Result<String, (StatusCode, String)> {
+        openrouter_chat(
+            &self.client,
+            &self.chat_url,
+            &self.api_key,
+            self.referer.as_deref(),
+            self.site_title.as_deref(),
+            &self.input.model,
+            role_system,
+            self.input.messages.clone(),
+        ).await
+    }
+}
+
+/// A run's stages as saved so far
+struct StageLog<'a> {
+    state: &'a AppState,
+    owner_id: Uuid,
+    run_id: Uuid,
+    stages: Vec<AgentRunStage>,
+}
+
+impl StageLog<'_> {
+    /// One stage, saved when it starts and when it ends; fails if the call does or the run was cancelled
+    async fn run(&mut self, pipeline: &Pipeline, name: &str, role_system: String) -> Result<String, (StatusCode, String)> {
+        self.stages.push(AgentRunStage { name: name.to_string(), status: "running".into(), output: None, started_at: Utc::now(), finished_at: None });
+        self.save().await?;
+        let outcome = pipeline.chat(role_system).await;
+        if let Some(stage) = self.stages.last_mut() {
+            stage.finished_at = Some(Utc::now());
+            match &outcome {
+                Ok(output) => {
+                    stage.status = "succeeded".into();
+                    stage.output = Some(output.clone());
+                }
+                Err(_) => stage.status = "failed".into(),
+            }
+        }
+        self.save().await?;
+        outcome
+    }
+
+    async fn save(&self) -> Result<(), (StatusCode, String)> {
+        if self.state.db.save_agent_run_stages(self.owner_id, self.run_id, &self.stages).await.map_err(db_error)? {
+            Ok(())
+        } else {
+            Err((StatusCode::CONFLICT, "run is no longer running".into()))
+        }
+    }
+}
+
+/// Planner, researcher, synthesizer
+async fn run_stages(log: &mut StageLog<'_>, pipeline: &Pipeline) -> Result<AgentRunOutput, (StatusCode, String)> {
+    let agent1_plan = log.run(pipeline, "planner", "You are Planner. Draft a concise plan. Do not execute, only plan.".to_string()).await?;
+    let agent2_execution = log.run(pipeline, "researcher", format!("You are Researcher. Execute the plan strictly and produce findings. Plan:\n{}", agent1_plan)).await?;
+    let agent3_summary = log.run(pipeline, "synthesizer", format!("You are Synthesizer. Summarize findings into a direct answer. Findings:\n{}", agent2_execution)).await?;
+    let final_answer = agent3_summary.clone();
+    Ok(AgentRunOutput { agent1_plan, agent2_execution, agent3_summary, final_answer })
+}
+
+/// Run the stages, then finish the run with the output or the first error
+async fn execute(state: &AppState, owner_id: Uuid, run_id: Uuid, pipeline: &Pipeline) -> Result<AgentRunOutput, (StatusCode, String)> {
+    let mut log = StageLog { state, owner_id, run_id, stages: Vec::new() };
+    let outcome = run_stages(&mut log, pipeline).await;
+
+    // Only moves a run that is still running, so a cancel is never overwritten
+    let retention = state.agent_runs.retention;
+    let finished = match &outcome {
+        Ok(output) => state.db.finish_agent_run(owner_id, run_id, "succeeded", Some(&json!(output)), None, retention).await,
+        Err((_, error)) => state.db.finish_agent_run(owner_id, run_id, "failed", None, Some(error), retention).await,
+    };
+    if let Err(e) = finished {
+        tracing::warn!("Failed to record the end of agent run {}: {}", run_id, e);
+    }
+    outcome
+}
+
+#[derive(Deserialize)]
+pub struct RunQuery { wait: Option<bool> }
+
+/// 202 with a run id to poll, or with `?wait=true` the finished output as before runs were persisted
+pub async fn run_agents(State(state): State<AppState>, auth: AuthContext, Query(q): Query<RunQuery>, Json(body): Json<AgentRunInput>) -> Result<Response, (StatusCode, String)> {
+    // Require curator or emitter to invoke multi-agent orchestration
+    if !(auth.has_role(Role::Curator) || auth.has_role(Role::Emitter)) { return Err((StatusCode::FORBIDDEN, "forbidden".into())); }
+
+    let api_key = std::env::var("OPENROUTER_API_KEY").map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "OPENROUTER_API_KEY missing".into()))?;
+    let pipeline = Pipeline {
+        client: HttpClient::builder().timeout(STAGE_TIMEOUT).build().map_err(internal_error)?,
+        chat_url: state.agent_runs.chat_url.clone(),
+        api_key,
+        referer: body.referer.clone().or_else(|| std::env::var("OPENROUTER_REFERER").ok()),
+        site_title: body.site_title.clone().or_else(|| std::env::var("OPENROUTER_SITE_TITLE").ok()),
+        input: body,
+    };
+    let input = serde_json::to_value(&pipeline.input).map_err(internal_error)?;
+    let run = state.db.create_agent_run(auth.owner_id, auth.agent_id, &input).await.map_err(db_error)?;
+
+    if q.wait.unwrap_or(false) {
+        let output = execute(&state, auth.owner_id, run.id, &pipeline).await?;
+        return Ok(Json(output).into_response());
+    }
+
+    // Registered under the lock the task's own cleanup takes, so a fast run can't finish first
+    let runs = state.agent_runs.clone();
+    let (owner_id, run_id) = (auth.owner_id, run.id);
+    {
+        let mut tasks = runs.tasks.lock().unwrap_or_else(PoisonError::into_inner);
+        let task = tokio::spawn(async move {
+            let _ = execute(&state, owner_id, run_id, &pipeline).await;
+            state.agent_runs.forget(run_id);
+        }.in_current_span());
+        tasks.insert(run_id, task.abort_handle());
+    }
+    Ok((StatusCode::ACCEPTED, Json(json!({ "run_id": run.id, "status": run.status }))).into_response())
+}
+
+/// The caller's own runs, or any of the tenant's for curators; others read as missing
+async fn visible_run(state: &AppState, auth: &AuthContext, run_id: Uuid) -> Result<AgentRun, (StatusCode, String)> {
+    match state.db.get_agent_run(auth.owner_id, run_id).await.map_err(db_error)? {
+        Some(run) if run.agent_id == auth.agent_id || auth.has_role(Role::Curator) => Ok(run),
+        _ => Err((StatusCode::NOT_FOUND, "run not found".into())),
+    }
+}
+
+/// Status, every stage saved so far (outputs of completed ones) and, once succeeded, the result
+pub async fn get_run(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(run_id): axum::extract::Path<Uuid>) -> Result<Json<AgentRun>, (StatusCode, String)> {
+    Ok(Json(visible_run(&state, &auth, run_id).await?))
+}
+
+pub async fn cancel_run(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(run_id): axum::extract::Path<Uuid>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let run = visible_run(&state, &auth, run_id).await?;
+    let reason = format!("cancelled by agent {}", auth.agent_id);
+    let cancelled = run.status == "running"
+        && state.db.finish_agent_run(auth.owner_id, run_id, "cancelled", None, Some(&reason), state.agent_runs.retention).await.map_err(db_error)?;
+    if !cancelled {
+        let status = state.db.get_agent_run(auth.owner_id, run_id).await.map_err(db_error)?.map(|r| r.status).unwrap_or(run.status);
+        return Err((StatusCode::CONFLICT, format!("run already {}", status)));
+    }
+    state.agent_runs.abort(run_id);
+    Ok(Json(json!({ "ok": true, "run_id": run_id, "status": "cancelled" })))
+}
+
+/// Fail runs nobody is executing any more and delete finished runs past retention
+async fn sweep(state: &AppState) {
+    let runs = &state.agent_runs;
+    let reason = format!("no progress for {}s: the server executing this run stopped", runs.stale_after.num_seconds());
+    match state.db.fail_stale_agent_runs(runs.stale_after, runs.retention, &runs.live(), &reason).await {
+        Ok(0) => {}
+        Ok(n) => tracing::warn!("Marked {} orphaned agent runs failed", n),
+        Err(e) => tracing::warn!("Failed to sweep stale agent runs: {}", e),
+    }
+    match state.db.delete_expired_agent_runs().await {
+        Ok(0) => {}
+        Ok(n) => tracing::info!("Deleted {} expired agent runs", n),
+        Err(e) => tracing::warn!("Failed to delete expired agent runs: {}", e),
+    }
+}
+
+pub fn start_sweeper(state: AppState) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            ticker.tick().await;
+            sweep(&state).await;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stale_threshold_outlasts_a_stage() {
+        let runs = AgentRuns::new(Duration::from_secs(3600), Duration::from_secs(1));
+        assert_eq!(runs.stale_after, chrono::Duration::seconds(600));
+        assert_eq!(runs.retention, chrono::Duration::hours(1));
+        assert_eq!(AgentRuns::new(Duration::ZERO, Duration::from_secs(900)).stale_after, chrono::Duration::seconds(900));
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_runs_are_polled_and_cancelled(pool: sqlx::PgPool) {
+        use crate::auth::{AuthConfig, AuthMode};
+        use crate::test_support::{request, send, state};
+        use axum::{routing::post, Router};
+        use rcrt_core::db::Db;
+        use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+
+        // OpenRouter stand-in: answers "stage N" while `open`, otherwise never answers
+        let calls = Arc::new(AtomicUsize::new(0));
+        let open = Arc::new(AtomicUsize::new(usize::MAX));
+        let (counter, limit) = (calls.clone(), open.clone());
+        let llm = Router::new().route("/chat", post(move || {
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+            let answer = n < limit.load(Ordering::SeqCst);
+            async move {
+                if !answer {
+                    std::future::pending::<()>().await;
+                }
+                Json(json!({ "choices": [{ "message": { "content": format!("stage {}", n) } }] }))
+            }
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let chat_url = format!("http://{}/chat", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, llm).await.unwrap() });
+        std::env::set_var("OPENROUTER_API_KEY", "test-key");
+
+        let db = Db { pool };
+        let (owner_id, agent_id) = (Uuid::new_v4(), Uuid::new_v4());
+        db.ensure_tenant(owner_id, "Agent Run Test").await.unwrap();
+        let auth = AuthConfig::new(AuthMode::Disabled { owner_id, agent_id }, None, None, None, None).unwrap();
+        let base = state(db, auth).await;
+        let mut runs = AgentRuns::new(Duration::from_secs(3600), Duration::from_secs(900));
+        runs.chat_url = chat_url;
+        let app = crate::build_app(AppState { agent_runs: Arc::new(runs), ..base });
+        let input = json!({ "model": "test/model", "messages": "What is RCRT?" });
+        let get = |run_id: &str| send(&app, request("GET", &format!("/agents/run/{}", run_id), None, None));
+
+        // Background run: 202 at once, then every stage's output
+        let (status, started) = send(&app, request("POST", "/agents/run", None, Some(input.clone()))).await;
+        assert_eq!(status, StatusCode::ACCEPTED, "{}", started);
+        let run_id = started["run_id"].as_str().unwrap().to_string();
+        let mut run = json!(null);
+        for _ in 0..100 {
+            run = get(&run_id).await.1;
+            if run["status"] != "running" { break; }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(run["status"], "succeeded", "{}", run);
+        let outputs: Vec<&str> = run["stages"].as_array().unwrap().iter().map(|s| s["output"].as_str().unwrap()).collect();
+        assert_eq!(outputs, ["stage 0", "stage 1", "stage 2"]);
+        assert_eq!(run["result"]["final_answer"], "stage 2");
+        assert!(run["expires_at"].is_string());
+
+        // Synchronous mode still returns the output itself
+        let (status, output) = send(&app, request("POST", "/agents/run?wait=true", None, Some(input.clone()))).await;
+        assert_eq!(status, StatusCode::OK, "{}", output);
+        assert_eq!(output["agent1_plan"], "stage 3");
+
+        // The second stage hangs; cancel keeps the first stage's output
+        open.store(calls.load(Ordering::SeqCst) + 1, Ordering::SeqCst);
+        let (_, started) = send(&app, request("POST", "/agents/run", None, Some(input))).await;
+        let run_id = started["run_id"].as_str().unwrap().to_string();
+        for _ in 0..100 {
+            if get(&run_id).await.1["stages"].as_array().is_some_and(|stages| stages.len() == 2) { break; }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        let (status, body) = send(&app, request("POST", &format!("/agents/run/{}/cancel", run_id), None, None)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let (status, _) = send(&app, request("POST", &format!("/agents/run/{}/cancel", run_id), None, None)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let (_, run) = get(&run_id).await;
+        assert_eq!(run["status"], "cancelled");
+        assert_eq!(run["stages"][0]["output"], "stage 6");
+        assert_eq!(run["stages"][1]["status"], "running");
+
+        let (status, _) = get(&Uuid::new_v4().to_string()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,103 @@
+//! Agent Handlers
+//! Agent registration and lookup; the /agents/run pipeline lives in agent_runs
+
+use axum::{extract::{Query, State}, http::StatusCode, response::{IntoResponse, Response}, Json};
+use rcrt_core::models::AgentOffboarding;
+use rcrt_core::roles::Role;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{auth::{unknown_role, AuthContext}, breadcrumb_filter::session_tag, db_errors::db_error, service::MAX_TAG_BYTES, AppState};
+
+#[derive(Deserialize)]
+pub struct AgentRegReq { roles: Vec<String> }
+pub async fn register_agent(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(agent_id): axum::extract::Path<Uuid>, Json(req): Json<AgentRegReq>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if auth.agent_id != agent_id && !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "forbidden".into())); }
+    let roles = Role::parse_all(&req.roles).map_err(unknown_role)?;
+    state.db.upsert_agent(auth.owner_id, agent_id, Role::names(&roles)).await.map_err(db_error)?;
+    Ok(Json(json!({"ok": true})))
+}
+
+/// The owner's agents, each with its count of unacknowledged inbox entries
+pub async fn list_agents(State(state): State<AppState>, auth: AuthContext) -> Result<Json<Vec<serde_json::Value>>, (StatusCode, String)> {
+    let agents = state.db.list_agents(auth.owner_id).await.map_err(db_error)?;
+    let depths = state.db.inbox_depths(auth.owner_id).await.map_err(db_error)?;
+    let out = agents.into_iter().map(|(id, roles, created_at)| {
+        json!({
+            "id": id,
+            "roles": roles,
+            "created_at": created_at,
+            "inbox_depth": depths.get(&id).copied().unwrap_or(0)
+        })
+    }).collect();
+    Ok(Json(out))
+}
+
+pub async fn get_agent(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(agent_id): axum::extract::Path<Uuid>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let agent = state.db.get_agent(auth.owner_id, agent_id).await.map_err(db_error)?;
+    match agent {
+        Some((id, roles, created_at)) => Ok(Json(json!({
+            "id": id,
+            "roles": roles,
+            "created_at": created_at
+        }))),
+        None => Err((StatusCode::NOT_FOUND, "agent not found".into()))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AgentSessionReq { session: Option<String> }
+
+/// Set the session the agent works in (`abc` or `session:abc`), or clear it with null; with
+/// SESSION_TAG_INFERENCE at agent or both, its creates without a session tag get this one
+pub async fn set_agent_session(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(agent_id): axum::extract::Path<Uuid>, Json(req): Json<AgentSessionReq>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if auth.agent_id != agent_id && !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "forbidden".into())); }
+    let session = req.session.as_deref().map(|s| session_tag(s.trim())).transpose()?;
+    if session.as_ref().is_some_and(|s| s.len() > MAX_TAG_BYTES) {
+        return Err((StatusCode::BAD_REQUEST, format!("tags are at most {} bytes", MAX_TAG_BYTES)));
+    }
+    if !state.db.set_agent_session(auth.owner_id, agent_id, session.as_deref()).await.map_err(db_error)? {
+        return Err((StatusCode::NOT_FOUND, "agent not found".into()));
+    }
+    Ok(Json(json!({"agent_id": agent_id, "session": session})))
+}
+
+#[derive(Deserialize)]
+pub struct DeleteAgentQuery {
+    /// Remove what's attached to the agent too, instead of refusing with 409
+    #[serde(default)]
+    cascade: bool,
+}
+
+/// Offboard an agent. Anything still attached (selectors, webhooks, grants, keys, authored
+/// breadcrumbs) gets a 409 listing it, unless `?cascade=true`
+pub async fn delete_agent(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(agent_id): axum::extract::Path<Uuid>, Query(q): Query<DeleteAgentQuery>) -> Result<Json<serde_json::Value>, Response> {
+    if !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "curator role required").into_response()); }
+    if !q.cascade {
+        let Some(dependents) = state.db.agent_dependents(auth.owner_id, agent_id).await.map_err(|e| db_error(e).into_response())? else {
+            return Err((StatusCode::NOT_FOUND, "agent not found").into_response());
+        };
+        if !dependents.is_empty() {
+            return Err((StatusCode::CONFLICT, Json(json!({
+                "error": "agent_has_dependents",
+                "dependents": dependents,
+                "hint": "retry with ?cascade=true to remove them along with the agent"
+            }))).into_response());
+        }
+    }
+    let Some(offboarded) = state.db.offboard_agent(auth.owner_id, agent_id, Some(auth.agent_id), "api").await.map_err(|e| db_error(e).into_response())? else {
+        return Err((StatusCode::NOT_FOUND, "agent not found").into_response());
+    };
+    finish_offboarding(&state, auth.owner_id, &offboarded);
+    tracing::info!("👋 Agent {} offboarded by {}: {:?}", agent_id, auth.agent_id, offboarded.removed);
+    Ok(Json(json!({"ok": true, "removed": offboarded.removed})))
+}
+
+/// Drop cached state of an offboarded agent: its selectors and API key lookups
+pub(crate) fn finish_offboarding(state: &AppState, owner_id: Uuid, offboarded: &AgentOffboarding) {
+    state.selector_index.invalidate(owner_id);
+    for hashed in &offboarded.api_key_hashes {
+        state.api_keys.evict(hashed);
+    }
+}
@@ -0,0 +1,118 @@
+//! Breadcrumb Analytics
+//! Curator views over the access log: one breadcrumb's accesses by kind, day and agent, and the tenant's
+//! most accessed breadcrumbs. Counts still waiting for the access log flush aren't included yet
+
+use std::collections::BTreeMap;
+use axum::{extract::{Path, Query, State}, http::StatusCode, Json};
+use chrono::{Duration, Utc};
+use rcrt_core::models::AccessType;
+use rcrt_core::roles::Role;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{auth::AuthContext, db_errors::db_error, AppState};
+
+const DEFAULT_WINDOW_DAYS: i64 = 7;
+const MAX_WINDOW_DAYS: i64 = 366;
+const DEFAULT_LIMIT: i64 = 20;
+const MAX_LIMIT: i64 = 100;
+
+/// `"7d"` or a number of days; counts are kept per day, so a window is whole days ending today
+fn parse_window(window: Option<&str>) -> Result<i64, (StatusCode, String)> {
+    let Some(window) = window else { return Ok(DEFAULT_WINDOW_DAYS) };
+    let trimmed = window.trim();
+    let days = trimmed.strip_suffix('d').unwrap_or(trimmed).parse::<i64>().ok();
+    match days {
+        Some(days) if (1..=MAX_WINDOW_DAYS).contains(&days) => Ok(days),
+        _ => Err((StatusCode::BAD_REQUEST, format!("window must be 1d to {}d, not {}", MAX_WINDOW_DAYS, window))),
+    }
+}
+
+fn require_curator(auth: &AuthContext) -> Result<(), (StatusCode, String)> {
+    if !auth.has_role(Role::Curator) {
+        return Err((StatusCode::FORBIDDEN, "curator role required".into()));
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct AnalyticsQuery {
+    window: Option<String>,
+}
+
+/// Accesses of one breadcrumb over the window (default 7d): totals per kind, per day and per agent,
+/// with its read_count
+pub async fn get_breadcrumb_analytics(State(state): State<AppState>, auth: AuthContext, Path(id): Path<Uuid>, Query(q): Query<AnalyticsQuery>) -> Result<Json<Value>, (StatusCode, String)> {
+    require_curator(&auth)?;
+    let days = parse_window(q.window.as_deref())?;
+    let Some(full) = state.db.get_breadcrumb_full_for(auth.owner_id, Some(auth.agent_id), id, false).await.map_err(db_error)? else {
+        return Err((StatusCode::NOT_FOUND, "not found".into()));
+    };
+    let since = Utc::now().date_naive() - Duration::days(days - 1);
+    let counts = state.db.breadcrumb_access_for(auth.owner_id, id, since).await.map_err(db_error)?;
+
+    let mut totals: BTreeMap<&str, i64> = [AccessType::ApiRead, AccessType::ContextAssembly, AccessType::SearchHit]
+        .iter().map(|t| (t.as_str(), 0)).collect();
+    let mut by_day: BTreeMap<_, BTreeMap<&str, i64>> = BTreeMap::new();
+    let mut by_agent: BTreeMap<Uuid, BTreeMap<&str, i64>> = BTreeMap::new();
+    for c in &counts {
+        let kind = c.access_type.as_str();
+        *totals.entry(kind).or_default() += c.count;
+        *by_day.entry(c.day).or_default().entry(kind).or_default() += c.count;
+        *by_agent.entry(c.agent_id).or_default().entry(kind).or_default() += c.count;
+    }
+    Ok(Json(json!({
+        "breadcrumb_id": full.id,
+        "title": full.title,
+        "schema_name": full.schema_name,
+        "read_count": full.read_count,
+        "window_days": days,
+        "since": since,
+        "totals": totals,
+        "by_day": by_day.into_iter().rev().map(|(day, counts)| json!({ "day": day, "counts": counts })).collect::<Vec<_>>(),
+        "by_agent": by_agent.into_iter().map(|(agent_id, counts)| json!({ "agent_id": agent_id, "counts": counts })).collect::<Vec<_>>(),
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct TopQuery {
+    window: Option<String>,
+    by: Option<String>,
+    limit: Option<i64>,
+}
+
+/// The tenant's most accessed breadcrumbs of one kind (`by`, default context_assembly) over the window
+pub async fn top_breadcrumbs(State(state): State<AppState>, auth: AuthContext, Query(q): Query<TopQuery>) -> Result<Json<Value>, (StatusCode, String)> {
+    require_curator(&auth)?;
+    let days = parse_window(q.window.as_deref())?;
+    let by = match q.by.as_deref() {
+        None => AccessType::ContextAssembly,
+        Some(s) => AccessType::parse(s)
+            .ok_or((StatusCode::BAD_REQUEST, format!("by must be api_read, context_assembly or search_hit, not {}", s)))?,
+    };
+    let limit = q.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let since = Utc::now().date_naive() - Duration::days(days - 1);
+    let top = state.db.top_accessed_breadcrumbs(auth.owner_id, by, since, limit).await.map_err(db_error)?;
+    Ok(Json(json!({
+        "by": by,
+        "window_days": days,
+        "since": since,
+        "breadcrumbs": top,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_is_whole_days() {
+        assert_eq!(parse_window(None).unwrap(), 7);
+        assert_eq!(parse_window(Some("30d")).unwrap(), 30);
+        assert_eq!(parse_window(Some("1")).unwrap(), 1);
+        for bad in ["0d", "12h", "d", "400d", "-3d"] {
+            assert_eq!(parse_window(Some(bad)).unwrap_err().0, StatusCode::BAD_REQUEST, "{}", bad);
+        }
+    }
+}
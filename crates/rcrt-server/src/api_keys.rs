@@ -0,0 +1,169 @@
+//! API Keys
+//! Long-lived, revocable per-agent keys (`Authorization: ApiKey <key>`) for server-to-server agents,
+//! the curator endpoints that issue and revoke them, and the lookup cache behind the AuthContext extractor
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use axum::{extract::State, http::StatusCode, Json};
+use rand::RngCore;
+use rcrt_core::db::Db;
+use rcrt_core::models::ApiKey;
+use rcrt_core::roles::Role;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{auth::{unknown_role, AuthContext}, db_errors::db_error, AppState};
+
+/// Every key starts with this, so leaked keys are easy to grep for
+const KEY_PREFIX: &str = "rcrt_";
+/// Characters of the plaintext kept as `ApiKey::prefix`: "rcrt_" plus 8 hex digits
+const DISPLAY_PREFIX_LEN: usize = 13;
+
+/// A fresh key: "rcrt_" and 32 random bytes in hex
+pub fn generate_key() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("{}{}", KEY_PREFIX, hex::encode(bytes))
+}
+
+/// Keys carry 256 random bits, so an unsalted BLAKE3 is enough and keeps lookups a single index probe
+pub fn hash_key(key: &str) -> String {
+    blake3::hash(key.as_bytes()).to_hex().to_string()
+}
+
+/// Resolved keys by hash. A revoke evicts here at once; other server instances stop accepting
+/// the key within `ttl`. Misses aren't cached, so a new key works immediately.
+pub struct ApiKeyCache {
+    ttl: Duration,
+    entries: RwLock<HashMap<String, (Instant, AuthContext)>>,
+}
+
+impl ApiKeyCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl, entries: RwLock::new(HashMap::new()) }
+    }
+
+    /// The identity behind `key`, or None when it is unknown or revoked
+    pub async fn resolve(&self, db: &Db, key: &str) -> anyhow::Result<Option<AuthContext>> {
+        let hashed = hash_key(key);
+        if let Ok(entries) = self.entries.read() {
+            if let Some((loaded, auth)) = entries.get(&hashed) {
+                if loaded.elapsed() < self.ttl { return Ok(Some(auth.clone())); }
+            }
+        }
+        // find_api_key stamps last_used_at, so it moves at most once per ttl per instance
+        let Some(found) = db.find_api_key(&hashed).await? else {
+            self.evict(&hashed);
+            return Ok(None);
+        };
+        // Keys minted before roles were checked may name one that no longer parses; it grants nothing
+        let roles = found.roles.iter().filter_map(|name| match name.parse::<Role>() {
+            Ok(role) => Some(role),
+            Err(e) => {
+                tracing::warn!("API key {} for agent {}: {}", found.prefix, found.agent_id, e);
+                None
+            }
+        }).collect();
+        let auth = AuthContext { owner_id: found.owner_id, agent_id: found.agent_id, roles };
+        if let Ok(mut entries) = self.entries.write() {
+            if entries.len() > 10_000 {
+                let ttl = self.ttl;
+                entries.retain(|_, (loaded, _)| loaded.elapsed() < ttl);
+            }
+            entries.insert(hashed, (Instant::now(), auth.clone()));
+        }
+        Ok(Some(auth))
+    }
+
+    pub fn evict(&self, hashed_key: &str) {
+        if let Ok(mut entries) = self.entries.write() {
+            entries.remove(hashed_key);
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CreateApiKeyReq {
+    name: Option<String>,
+    /// Defaults to the agent's registered roles
+    roles: Option<Vec<String>>,
+}
+
+/// The first of `roles` the caller doesn't hold itself; a key can't carry more than whoever minted it,
+/// so admin keys come only from admins
+fn ungrantable(auth: &AuthContext, roles: &[Role]) -> Option<Role> {
+    roles.iter().copied().find(|role| !auth.has_role(*role))
+}
+
+pub async fn create_api_key(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(agent_id): axum::extract::Path<Uuid>, Json(req): Json<CreateApiKeyReq>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "curator role required".into())); }
+    let Some((_, agent_roles, _)) = state.db.get_agent(auth.owner_id, agent_id).await.map_err(db_error)? else {
+        return Err((StatusCode::NOT_FOUND, "agent not found".into()));
+    };
+    let roles = Role::parse_all(&req.roles.unwrap_or(agent_roles)).map_err(unknown_role)?;
+    if let Some(role) = ungrantable(&auth, &roles) {
+        return Err((StatusCode::FORBIDDEN, format!("cannot grant the {} role without holding it", role)));
+    }
+    let roles = Role::names(&roles);
+    let key = generate_key();
+    let created = state.db.create_api_key(auth.owner_id, agent_id, req.name.as_deref(), &key[..DISPLAY_PREFIX_LEN], &hash_key(&key), &roles)
+        .await.map_err(db_error)?;
+    // The only time the plaintext leaves the server
+    Ok(Json(json!({
+        "id": created.id,
+        "key": key,
+        "prefix": created.prefix,
+        "name": created.name,
+        "roles": created.roles,
+        "created_at": created.created_at,
+    })))
+}
+
+pub async fn list_api_keys(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(agent_id): axum::extract::Path<Uuid>) -> Result<Json<Vec<ApiKey>>, (StatusCode, String)> {
+    if !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "curator role required".into())); }
+    let keys = state.db.list_api_keys(auth.owner_id, agent_id).await.map_err(db_error)?;
+    Ok(Json(keys))
+}
+
+pub async fn revoke_api_key(State(state): State<AppState>, auth: AuthContext, axum::extract::Path((agent_id, key_id)): axum::extract::Path<(Uuid, Uuid)>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "curator role required".into())); }
+    let Some(hashed) = state.db.revoke_api_key(auth.owner_id, agent_id, key_id).await.map_err(db_error)? else {
+        return Err((StatusCode::NOT_FOUND, "api key not found".into()));
+    };
+    state.api_keys.evict(&hashed);
+    Ok(Json(json!({"ok": true})))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_keys_are_unique_and_prefixed() {
+        let a = generate_key();
+        let b = generate_key();
+        assert_ne!(a, b);
+        assert!(a.starts_with(KEY_PREFIX));
+        assert_eq!(a.len(), KEY_PREFIX.len() + 64);
+        assert!(a[..DISPLAY_PREFIX_LEN].starts_with(KEY_PREFIX));
+    }
+
+    #[test]
+    fn test_keys_carry_only_roles_the_caller_holds() {
+        let curator = AuthContext { owner_id: Uuid::new_v4(), agent_id: Uuid::new_v4(), roles: vec![Role::Curator, Role::Subscriber] };
+        assert_eq!(ungrantable(&curator, &[Role::Subscriber]), None);
+        assert_eq!(ungrantable(&curator, &[Role::Subscriber, Role::Admin]), Some(Role::Admin));
+        let admin = AuthContext { roles: vec![Role::Curator, Role::Admin], ..curator };
+        assert_eq!(ungrantable(&admin, &[Role::Admin]), None);
+    }
+
+    #[test]
+    fn test_hash_is_stable_and_hides_the_key() {
+        let key = generate_key();
+        assert_eq!(hash_key(&key), hash_key(&key));
+        assert_ne!(hash_key(&key), hash_key(&generate_key()));
+        assert!(!hash_key(&key).contains(&key[KEY_PREFIX.len()..]));
+    }
+}
@@ -0,0 +1,278 @@
+//! Attachment Handlers
+//! Content-addressed binary attachments on breadcrumbs: upload, list, download, and the store large blobs live in
+
+use std::path::PathBuf;
+use std::pin::Pin;
+use axum::{body::{Body, Bytes}, extract::{FromRequest, Multipart, Query, Request, State}, http::{header, StatusCode}, response::{IntoResponse, Response}, Json};
+use rcrt_core::models::{AttachmentBody, AttachmentMeta, AttachOutcome, NewAttachment};
+use rcrt_core::roles::Role;
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncRead;
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
+
+use crate::{auth::AuthContext, db_errors::db_error, internal_error, AppState};
+
+/// Headroom over the attachment size limit for multipart boundaries and part headers
+const MULTIPART_OVERHEAD: usize = 64 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct AttachmentLimits {
+    /// Uploads up to this size are kept inline in Postgres, larger ones go to the AttachmentStore
+    pub inline_max_bytes: usize,
+    pub max_bytes: usize,
+    /// Distinct bytes stored per tenant; deduplicated uploads don't count twice
+    pub tenant_quota_bytes: i64,
+}
+
+impl AttachmentLimits {
+    /// Request body limit for the upload route
+    pub fn upload_body_limit(&self) -> usize {
+        self.max_bytes + MULTIPART_OVERHEAD
+    }
+}
+
+/// Where attachments too large to keep inline are written. Keys are `{owner_id}/{sha256[..2]}/{sha256}`,
+/// so a put of an existing key writes the same bytes again
+#[axum::async_trait]
+pub trait AttachmentStore: Send + Sync {
+    async fn put(&self, key: &str, bytes: &[u8]) -> anyhow::Result<()>;
+    async fn open(&self, key: &str) -> anyhow::Result<Pin<Box<dyn AsyncRead + Send>>>;
+    /// Missing keys are not an error
+    async fn delete(&self, key: &str) -> anyhow::Result<()>;
+}
+
+/// AttachmentStore on a local (or mounted) directory
+pub struct FsStore {
+    root: PathBuf,
+}
+
+impl FsStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FsStore { root: root.into() }
+    }
+}
+
+#[axum::async_trait]
+impl AttachmentStore for FsStore {
+    async fn put(&self, key: &str, bytes: &[u8]) -> anyhow::Result<()> {
+        let path = self.root.join(key);
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        // Write aside and rename so readers never see a partial file
+        let tmp = path.with_extension(format!("{}.tmp", Uuid::new_v4()));
+        tokio::fs::write(&tmp, bytes).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        Ok(())
+    }
+
+    async fn open(&self, key: &str) -> anyhow::Result<Pin<Box<dyn AsyncRead + Send>>> {
+        Ok(Box::pin(tokio::fs::File::open(self.root.join(key)).await?))
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        match tokio::fs::remove_file(self.root.join(key)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+pub(crate) fn store_key(owner_id: Uuid, sha256: &str) -> String {
+    format!("{}/{}/{}", owner_id, &sha256[..2], sha256)
+}
+
+#[derive(Deserialize)]
+pub struct UploadQuery { filename: Option<String> }
+
+/// POST /breadcrumbs/:id/attachments: a multipart form (first file part) or the raw body with its Content-Type.
+/// Context JSON references the result by `sha256`
+pub async fn upload_attachment(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(id): axum::extract::Path<Uuid>, Query(q): Query<UploadQuery>, request: Request) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if !(auth.has_role(Role::Emitter) || auth.has_role(Role::Curator)) {
+        return Err((StatusCode::FORBIDDEN, "emitter role required".into()));
+    }
+    let Some(bc) = state.db.get_breadcrumb_full_for(auth.owner_id, Some(auth.agent_id), id, false).await.map_err(db_error)? else {
+        return Err((StatusCode::NOT_FOUND, "not found".into()));
+    };
+    // Blobs are stored and counted against the owning tenant
+    if bc.owner_id != auth.owner_id {
+        return Err((StatusCode::FORBIDDEN, "only the owning tenant can attach to a breadcrumb".into()));
+    }
+
+    let (bytes, content_type, filename) = read_upload(&state, request, q.filename).await?;
+    let limits = &state.attachment_limits;
+    if bytes.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "empty attachment".into()));
+    }
+    if bytes.len() > limits.max_bytes {
+        return Err((StatusCode::PAYLOAD_TOO_LARGE, format!("attachment exceeds {} bytes", limits.max_bytes)));
+    }
+
+    let sha256 = hex::encode(Sha256::digest(&bytes));
+    let body = if bytes.len() <= limits.inline_max_bytes {
+        AttachmentBody::Inline(bytes.to_vec())
+    } else {
+        let key = store_key(auth.owner_id, &sha256);
+        state.attachment_store.put(&key, &bytes).await.map_err(internal_error)?;
+        AttachmentBody::Stored(key)
+    };
+    let stored_key = match &body { AttachmentBody::Stored(key) => Some(key.clone()), AttachmentBody::Inline(_) => None };
+    let att = NewAttachment { sha256, content_type, size_bytes: bytes.len() as i64, filename, body };
+
+    match state.db.attach_to_breadcrumb(auth.owner_id, auth.agent_id, id, att, limits.tenant_quota_bytes).await.map_err(db_error)? {
+        AttachOutcome::Linked { meta, deduplicated } => Ok(Json(json!({
+            "sha256": meta.sha256,
+            "content_type": meta.content_type,
+            "size_bytes": meta.size_bytes,
+            "filename": meta.filename,
+            "deduplicated": deduplicated,
+            "url": format!("/attachments/{}", meta.sha256),
+        }))),
+        AttachOutcome::QuotaExceeded { used_bytes, quota_bytes } => {
+            // No row was written, so nothing else points at these bytes
+            if let Some(key) = stored_key {
+                if let Err(e) = state.attachment_store.delete(&key).await {
+                    tracing::warn!("Failed to remove unstored attachment {}: {}", key, e);
+                }
+            }
+            Err((StatusCode::INSUFFICIENT_STORAGE, format!("attachment quota exceeded: {} of {} bytes used", used_bytes, quota_bytes)))
+        }
+    }
+}
+
+/// Bytes, content type and filename from a multipart form or a raw body
+async fn read_upload(state: &AppState, request: Request, filename: Option<String>) -> Result<(Bytes, String, Option<String>), (StatusCode, String)> {
+    let content_type = request.headers().get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()).unwrap_or("application/octet-stream").to_string();
+    if !content_type.starts_with("multipart/form-data") {
+        let bytes = Bytes::from_request(request, state).await.map_err(|e| (e.status(), e.body_text()))?;
+        return Ok((bytes, content_type, filename));
+    }
+    let mut multipart = Multipart::from_request(request, state).await.map_err(|e| (e.status(), e.body_text()))?;
+    while let Some(field) = multipart.next_field().await.map_err(|e| (e.status(), e.body_text()))? {
+        if field.file_name().is_none() && field.name() != Some("file") {
+            continue;
+        }
+        let content_type = field.content_type().unwrap_or("application/octet-stream").to_string();
+        let filename = filename.or_else(|| field.file_name().map(str::to_string));
+        let bytes = field.bytes().await.map_err(|e| (e.status(), e.body_text()))?;
+        return Ok((bytes, content_type, filename));
+    }
+    Err((StatusCode::BAD_REQUEST, "multipart body has no file part".into()))
+}
+
+pub async fn list_attachments(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(id): axum::extract::Path<Uuid>) -> Result<Json<Vec<AttachmentMeta>>, (StatusCode, String)> {
+    // Unreadable breadcrumbs are 404, not an empty list
+    if state.db.get_breadcrumb_context_for(auth.owner_id, Some(auth.agent_id), id).await.map_err(db_error)?.is_none() {
+        return Err((StatusCode::NOT_FOUND, "not found".into()));
+    }
+    let items = state.db.list_breadcrumb_attachments(auth.owner_id, Some(auth.agent_id), id).await.map_err(db_error)?;
+    Ok(Json(items))
+}
+
+/// GET /attachments/:sha256: readable when a breadcrumb the agent can read links to it
+pub async fn get_attachment(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(sha256): axum::extract::Path<String>) -> Result<Response, (StatusCode, String)> {
+    let sha256 = sha256.to_ascii_lowercase();
+    if sha256.len() != 64 || !sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err((StatusCode::BAD_REQUEST, "sha256 must be 64 hex characters".into()));
+    }
+    let Some(att) = state.db.get_attachment(auth.owner_id, Some(auth.agent_id), &sha256).await.map_err(db_error)? else {
+        return Err((StatusCode::NOT_FOUND, "not found".into()));
+    };
+    let body = match att.body {
+        AttachmentBody::Inline(bytes) => Body::from(bytes),
+        AttachmentBody::Stored(key) => Body::from_stream(ReaderStream::new(state.attachment_store.open(&key).await.map_err(internal_error)?)),
+    };
+    let headers = [
+        (header::CONTENT_TYPE, att.content_type),
+        (header::CONTENT_LENGTH, att.size_bytes.to_string()),
+        (header::ETAG, format!("\"{}\"", sha256)),
+        // Content-addressed, so never stale
+        (header::CACHE_CONTROL, "private, max-age=31536000, immutable".to_string()),
+        // Uploaded HTML/SVG must not run as this origin
+        (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+        (header::CONTENT_SECURITY_POLICY, "sandbox".to_string()),
+    ];
+    Ok((headers, body).into_response())
+}
+
+/// Delete blobs no breadcrumb links to and that haven't been (re)linked for `grace_hours`, with their stored
+/// bytes. Raw pool like the rest of the hygiene runner: orphans of every tenant are swept
+pub async fn cleanup_orphaned_attachments(state: &AppState, grace_hours: i64) -> Result<u64, sqlx::Error> {
+    let keys: Vec<Option<String>> = sqlx::query_scalar(
+        r#"delete from attachments a
+           where a.last_linked_at < now() - make_interval(hours => $1::int)
+             and not exists (select 1 from breadcrumb_attachments l where l.owner_id = a.owner_id and l.sha256 = a.sha256)
+           returning a.storage_path"#
+    )
+    .bind(grace_hours as i32)
+    .fetch_all(&state.db.pool)
+    .await?;
+    for key in keys.iter().flatten() {
+        if let Err(e) = state.attachment_store.delete(key).await {
+            tracing::warn!("Failed to delete stored attachment {}: {}", key, e);
+        }
+    }
+    if !keys.is_empty() {
+        tracing::info!("Removed {} orphaned attachments", keys.len());
+    }
+    Ok(keys.len() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fs_store_round_trip() {
+        let store = FsStore::new(std::env::temp_dir().join(format!("rcrt-attachments-{}", Uuid::new_v4())));
+        let key = store_key(Uuid::new_v4(), &hex::encode(Sha256::digest(b"pdf bytes")));
+        store.put(&key, b"pdf bytes").await.unwrap();
+        store.put(&key, b"pdf bytes").await.unwrap();
+
+        let mut out = Vec::new();
+        tokio::io::AsyncReadExt::read_to_end(&mut store.open(&key).await.unwrap(), &mut out).await.unwrap();
+        assert_eq!(out, b"pdf bytes");
+
+        store.delete(&key).await.unwrap();
+        assert!(store.open(&key).await.is_err());
+        store.delete(&key).await.unwrap();
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_orphans_are_removed_after_breadcrumb_delete(pool: sqlx::PgPool) {
+        use crate::auth::{AuthConfig, AuthMode};
+        use crate::test_support::{crumb, state};
+        use rcrt_core::db::Db;
+
+        let db = Db { pool };
+        let (owner_id, agent_id) = (Uuid::new_v4(), Uuid::new_v4());
+        db.ensure_tenant(owner_id, "Attachment Test").await.unwrap();
+        let create = |title: &str| rcrt_core::models::BreadcrumbCreate { title: title.into(), ..crumb("note.v1", &[]) };
+        let first = db.create_breadcrumb_for(owner_id, Some(agent_id), Some(agent_id), create("first")).await.unwrap();
+        let second = db.create_breadcrumb_for(owner_id, Some(agent_id), Some(agent_id), create("second")).await.unwrap();
+        let att = || NewAttachment {
+            sha256: hex::encode(Sha256::digest(b"screenshot")), content_type: "image/png".into(), size_bytes: 10,
+            filename: Some("shot.png".into()), body: AttachmentBody::Inline(b"screenshot".to_vec()),
+        };
+
+        let AttachOutcome::Linked { deduplicated, .. } = db.attach_to_breadcrumb(owner_id, agent_id, first.id, att(), 1024).await.unwrap() else { panic!("quota") };
+        assert!(!deduplicated);
+        let AttachOutcome::Linked { deduplicated, .. } = db.attach_to_breadcrumb(owner_id, agent_id, second.id, att(), 1024).await.unwrap() else { panic!("quota") };
+        assert!(deduplicated);
+        let other = NewAttachment { sha256: hex::encode(Sha256::digest(b"other")), ..att() };
+        assert!(matches!(db.attach_to_breadcrumb(owner_id, agent_id, first.id, other, 15).await.unwrap(), AttachOutcome::QuotaExceeded { used_bytes: 10, quota_bytes: 15 }));
+
+        let auth = AuthConfig::new(AuthMode::Disabled { owner_id, agent_id }, None, None, None, None).unwrap();
+        let state = state(db.clone(), auth).await;
+        // Still linked from `second`
+        db.delete_breadcrumb(owner_id, agent_id, first.id).await.unwrap();
+        assert_eq!(cleanup_orphaned_attachments(&state, 0).await.unwrap(), 0);
+        db.delete_breadcrumb(owner_id, agent_id, second.id).await.unwrap();
+        assert_eq!(cleanup_orphaned_attachments(&state, 1).await.unwrap(), 0, "inside the grace period");
+        assert_eq!(cleanup_orphaned_attachments(&state, 0).await.unwrap(), 1);
+    }
+}
@@ -0,0 +1,321 @@
+//! Auth
+//! Auth configuration parsed once at startup, the AuthContext extractor that reads it from AppState
+//! (JWT bearer tokens or `ApiKey` keys), and token issuance
+
+use anyhow::Context;
+use axum::extract::{FromRequestParts, State};
+use axum::http::{header, request::Parts, StatusCode};
+use axum::Json;
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use rcrt_core::roles::{Role, UnknownRole};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{internal_error, tenant_deletion::reject_writes, AppState};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AuthMode {
+    /// Verify RS256 bearer tokens (default)
+    Jwt,
+    /// AUTH_MODE=disabled: every request acts as this owner/agent with all roles
+    Disabled { owner_id: Uuid, agent_id: Uuid },
+}
+
+pub struct AuthConfig {
+    pub mode: AuthMode,
+    pub decoding_key: Option<DecodingKey>,
+    pub encoding_key: Option<EncodingKey>,
+    pub validation: Validation,
+    /// Stamped into issued tokens and required on incoming ones when set
+    pub issuer: Option<String>,
+    pub audience: Option<String>,
+    /// GET /metrics requires `Authorization: Bearer <token>` when set; open otherwise
+    pub metrics_token: Option<String>,
+    /// Let curator tokens through the /admin routes until operators have issued admin ones
+    pub admin_accepts_curator: bool,
+}
+
+impl AuthConfig {
+    pub fn new(mode: AuthMode, public_pem: Option<&str>, private_pem: Option<&str>, issuer: Option<String>, audience: Option<String>) -> anyhow::Result<Self> {
+        let decoding_key = public_pem.map(|pem| DecodingKey::from_rsa_pem(pem.as_bytes()))
+            .transpose().context("invalid RSA public key")?;
+        let encoding_key = private_pem.map(|pem| EncodingKey::from_rsa_pem(pem.as_bytes()))
+            .transpose().context("invalid RSA private key")?;
+        let mut validation = Validation::new(Algorithm::RS256);
+        if let Some(iss) = &issuer {
+            validation.set_issuer(&[iss]);
+        }
+        if let Some(aud) = &audience {
+            validation.set_audience(&[aud]);
+        }
+        match &mode {
+            AuthMode::Disabled { owner_id, .. } => tracing::warn!("🔓 AUTH_MODE=disabled: all requests act as owner {}", owner_id),
+            AuthMode::Jwt if decoding_key.is_none() => tracing::warn!("🔒 JWT_PUBLIC_KEY_PEM not set: authenticated routes will reject every request"),
+            AuthMode::Jwt => {}
+        }
+        Ok(AuthConfig { mode, decoding_key, encoding_key, validation, issuer, audience, metrics_token: None, admin_accepts_curator: true })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AuthContext { pub owner_id: Uuid, pub agent_id: Uuid, pub roles: Vec<Role> }
+
+impl AuthContext {
+    pub fn has_role(&self, role: Role) -> bool {
+        self.roles.contains(&role)
+    }
+}
+
+/// 422 naming the valid roles, for a token, registration or key that asks for one outside `Role::ALL`
+pub fn unknown_role(e: UnknownRole) -> (StatusCode, String) {
+    (StatusCode::UNPROCESSABLE_ENTITY, e.to_string())
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims { sub: String, owner_id: String, roles: Option<Vec<String>> }
+
+enum Credential {
+    Bearer(String),
+    ApiKey(String),
+}
+
+/// Bearer or ApiKey header, or access_token/token query (a JWT) for SSE/browser clients
+fn credential(parts: &Parts) -> Result<Credential, (StatusCode, String)> {
+    if let Some(hv) = parts.headers.get(header::AUTHORIZATION).and_then(|h| h.to_str().ok()) {
+        if let Some(key) = hv.strip_prefix("ApiKey ") {
+            return Ok(Credential::ApiKey(key.trim().to_string()));
+        }
+        return hv.strip_prefix("Bearer ")
+            .map(|token| Credential::Bearer(token.to_string()))
+            .ok_or((StatusCode::UNAUTHORIZED, "invalid Authorization header".into()));
+    }
+    parts.uri.query().unwrap_or("")
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(k, _)| *k == "access_token" || *k == "token")
+        .map(|(_, v)| Credential::Bearer(percent_encoding::percent_decode_str(v).decode_utf8_lossy().to_string()))
+        .ok_or((StatusCode::UNAUTHORIZED, "missing Authorization".into()))
+}
+
+#[axum::async_trait]
+impl FromRequestParts<AppState> for AuthContext {
+    type Rejection = (StatusCode, String);
+    #[tracing::instrument(name = "auth", skip_all)]
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let auth = &state.auth;
+        // A tenant being deleted keeps reading but can't write
+        let writable = |owner_id| reject_writes(&state.deleting_tenants, &parts.method, parts.uri.path(), owner_id);
+        if let AuthMode::Disabled { owner_id, agent_id } = auth.mode {
+            writable(owner_id)?;
+            return Ok(AuthContext { owner_id, agent_id, roles: Role::ALL.to_vec() });
+        }
+
+        let token = match credential(parts)? {
+            // Same AuthContext a JWT with the key's owner, agent and roles would produce
+            Credential::ApiKey(key) => {
+                let auth = state.api_keys.resolve(&state.db, &key).await
+                    .map_err(internal_error)?
+                    .ok_or((StatusCode::UNAUTHORIZED, "invalid or revoked api key".into()))?;
+                writable(auth.owner_id)?;
+                return Ok(auth);
+            }
+            Credential::Bearer(token) => token,
+        };
+        let Some(dec_key) = &auth.decoding_key else {
+            return Err((StatusCode::UNAUTHORIZED, "JWT required; set JWT_PUBLIC_KEY_PEM or use AUTH_MODE=disabled explicitly".into()));
+        };
+        let data = decode::<Claims>(&token, dec_key, &auth.validation)
+            .map_err(|e| (StatusCode::UNAUTHORIZED, format!("invalid token: {}", e)))?;
+        let owner = Uuid::parse_str(&data.claims.owner_id)
+            .map_err(|_| (StatusCode::UNAUTHORIZED, "invalid owner_id in token".into()))?;
+        let agent = Uuid::parse_str(&data.claims.sub)
+            .map_err(|_| (StatusCode::UNAUTHORIZED, "invalid sub in token".into()))?;
+        // A misspelt role would otherwise just grant nothing
+        let roles = Role::parse_all(&data.claims.roles.unwrap_or_default()).map_err(unknown_role)?;
+        // Before the upsert, which would otherwise recreate agents the deletion removed
+        writable(owner)?;
+        // Ensure agent row exists with roles so FK on created_by/updated_by succeeds
+        if let Err(e) = state.db.upsert_agent(owner, agent, Role::names(&roles)).await {
+            return Err(internal_error(e));
+        }
+        Ok(AuthContext { owner_id: owner, agent_id: agent, roles })
+    }
+}
+
+/// Gate for the /admin routes: the admin role, or curator while AuthConfig::admin_accepts_curator is on
+pub fn require_admin(state: &AppState, auth: &AuthContext) -> Result<(), (StatusCode, String)> {
+    let accepts_curator = state.auth.admin_accepts_curator;
+    if auth.has_role(Role::Admin) || (accepts_curator && auth.has_role(Role::Curator)) {
+        return Ok(());
+    }
+    let message = if accepts_curator { "admin or curator role required" } else { "admin role required" };
+    Err((StatusCode::FORBIDDEN, message.into()))
+}
+
+#[derive(Deserialize)]
+pub struct TokenRequest {
+    owner_id: String,
+    agent_id: String, 
+    roles: Option<Vec<String>>,
+    ttl_sec: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct TokenResponse {
+    token: String,
+    owner_id: String,
+    agent_id: String,
+    roles: Vec<Role>,
+    exp: i64,
+}
+
+pub async fn generate_jwt_token(State(state): State<AppState>, Json(req): Json<TokenRequest>) -> Result<Json<TokenResponse>, (StatusCode, String)> {
+    let Some(encoding_key) = &state.auth.encoding_key else {
+        return Err((StatusCode::SERVICE_UNAVAILABLE, "JWT signing not configured (missing JWT_PRIVATE_KEY_PEM)".into()));
+    };
+    
+    // Require explicit values in request - no environment fallbacks
+    let owner_id = req.owner_id;
+    let agent_id = req.agent_id;
+    let roles = match req.roles {
+        Some(roles) => Role::parse_all(&roles).map_err(unknown_role)?,
+        None => vec![Role::Curator, Role::Emitter, Role::Subscriber],
+    };
+    let ttl_sec = req.ttl_sec.unwrap_or(3600); // 1 hour default
+    
+    // Validate UUIDs
+    let _owner_uuid = Uuid::parse_str(&owner_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, "invalid owner_id format".into()))?;
+    let agent_uuid = Uuid::parse_str(&agent_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, "invalid agent_id format".into()))?;
+    
+    let now = chrono::Utc::now().timestamp();
+    let exp = now + ttl_sec;
+    
+    let mut claims = json!({
+        "sub": agent_id,
+        "owner_id": owner_id,
+        "roles": roles,
+        "iat": now,
+        "exp": exp
+    });
+    
+    // Add optional issuer/audience if configured
+    if let Some(iss) = &state.auth.issuer {
+        claims["iss"] = json!(iss);
+    }
+    if let Some(aud) = &state.auth.audience {
+        claims["aud"] = json!(aud);
+    }
+    
+    let header = Header::new(Algorithm::RS256);
+    let token = encode(&header, &claims, encoding_key)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("JWT encoding failed: {}", e)))?;
+    
+    // Ensure agent exists in database with these roles
+    if let Err(e) = state.db.upsert_agent(Uuid::parse_str(&owner_id).unwrap(), agent_uuid, Role::names(&roles)).await {
+        tracing::warn!("Failed to upsert agent during token generation: {}", e);
+    }
+    
+    Ok(Json(TokenResponse {
+        token,
+        owner_id,
+        agent_id,
+        roles,
+        exp,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request, routing::get, Router};
+    use crate::test_support::{offline_db, state};
+    use tower::ServiceExt;
+
+    const PUBLIC_PEM: &str = include_str!("../testdata/jwt_rs256_public.pem");
+    const PRIVATE_PEM: &str = include_str!("../testdata/jwt_rs256_private.pem");
+
+    fn jwt_config() -> AuthConfig {
+        AuthConfig::new(AuthMode::Jwt, Some(PUBLIC_PEM), Some(PRIVATE_PEM), None, None).unwrap()
+    }
+
+    fn token(config: &AuthConfig, owner_id: Uuid, agent_id: Uuid, roles: &[&str], exp: i64) -> String {
+        let claims = json!({ "sub": agent_id, "owner_id": owner_id, "roles": roles, "exp": exp });
+        encode(&Header::new(Algorithm::RS256), &claims, config.encoding_key.as_ref().unwrap()).unwrap()
+    }
+
+    async fn whoami(state: AppState, authorization: Option<String>) -> (StatusCode, serde_json::Value) {
+        let app = Router::new()
+            .route("/whoami", get(|auth: AuthContext| async move {
+                Json(json!({ "owner_id": auth.owner_id, "agent_id": auth.agent_id, "roles": auth.roles }))
+            }))
+            .with_state(state);
+        let mut req = Request::builder().uri("/whoami");
+        if let Some(value) = authorization {
+            req = req.header(header::AUTHORIZATION, value);
+        }
+        let res = app.oneshot(req.body(Body::empty()).unwrap()).await.unwrap();
+        let status = res.status();
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_disabled_mode_uses_configured_identity() {
+        let owner_id = Uuid::new_v4();
+        let agent_id = Uuid::new_v4();
+        let config = AuthConfig::new(AuthMode::Disabled { owner_id, agent_id }, None, None, None, None).unwrap();
+        let (status, body) = whoami(state(offline_db(), config).await, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["owner_id"], owner_id.to_string());
+        assert_eq!(body["agent_id"], agent_id.to_string());
+        assert_eq!(body["roles"].as_array().unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_missing_header_is_unauthorized() {
+        let (status, _) = whoami(state(offline_db(), jwt_config()).await, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_expired_token_is_unauthorized() {
+        let config = jwt_config();
+        let expired = token(&config, Uuid::new_v4(), Uuid::new_v4(), &["emitter"], chrono::Utc::now().timestamp() - 3600);
+        let (status, _) = whoami(state(offline_db(), config).await, Some(format!("Bearer {}", expired))).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_token_with_unknown_role_is_rejected() {
+        let config = jwt_config();
+        let typo = token(&config, Uuid::new_v4(), Uuid::new_v4(), &["emitter", "curatr"], chrono::Utc::now().timestamp() + 3600);
+        let app = Router::new()
+            .route("/whoami", get(|_: AuthContext| async { "ok" }))
+            .with_state(state(offline_db(), config).await);
+        let req = Request::builder().uri("/whoami").header(header::AUTHORIZATION, format!("Bearer {}", typo));
+        let res = app.oneshot(req.body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(String::from_utf8_lossy(&bytes), "unknown role 'curatr'; valid roles are curator, emitter, subscriber, admin");
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_valid_token_authenticates(pool: sqlx::PgPool) {
+        let db = rcrt_core::db::Db { pool };
+        let owner_id = Uuid::new_v4();
+        let agent_id = Uuid::new_v4();
+        db.ensure_tenant(owner_id, "Auth Test").await.unwrap();
+        let config = jwt_config();
+        // Role names are case-insensitive and come back lowercase
+        let valid = token(&config, owner_id, agent_id, &["Emitter"], chrono::Utc::now().timestamp() + 3600);
+        let (status, body) = whoami(state(db, config).await, Some(format!("Bearer {}", valid))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["owner_id"], owner_id.to_string());
+        assert_eq!(body["agent_id"], agent_id.to_string());
+        assert_eq!(body["roles"], json!(["emitter"]));
+    }
+}
@@ -0,0 +1,152 @@
+//! Breadcrumb Filter
+//! Query-string filters shared by GET /breadcrumbs and /breadcrumbs/search, applied as bound SQL parameters
+
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use sqlx::{Postgres, QueryBuilder};
+
+/// Lower time bounds are inclusive and upper bounds exclusive, so `created_after=2025-06-01T00:00:00Z`
+/// with `created_before=2025-07-01T00:00:00Z` is exactly June
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BreadcrumbFilter {
+    pub tag: Option<String>,
+    /// `session=abc` or `session=session:abc`; held as the full `session:abc` tag and ANDed with `tag`
+    pub session: Option<String>,
+    /// `exclude_tag`, repeatable: drop breadcrumbs carrying any of these
+    pub exclude_tags: Vec<String>,
+    pub schema_name: Option<String>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub updated_after: Option<DateTime<Utc>>,
+    pub updated_before: Option<DateTime<Utc>>,
+}
+
+impl BreadcrumbFilter {
+    /// From the decoded query pairs; keys that aren't filters are left to the endpoint's own query struct
+    pub fn from_query(pairs: &[(String, String)]) -> Result<Self, (StatusCode, String)> {
+        let mut filter = BreadcrumbFilter::default();
+        for (key, value) in pairs {
+            match key.as_str() {
+                "tag" => filter.tag = Some(value.clone()),
+                "session" => filter.session = Some(session_tag(value)?),
+                "exclude_tag" => filter.exclude_tags.push(value.clone()),
+                "schema_name" => filter.schema_name = Some(value.clone()),
+                "created_after" => filter.created_after = Some(timestamp(key, value)?),
+                "created_before" => filter.created_before = Some(timestamp(key, value)?),
+                // `since` is the list endpoint's older name for the same inclusive bound
+                "updated_after" | "since" => filter.updated_after = Some(timestamp(key, value)?),
+                "updated_before" => filter.updated_before = Some(timestamp(key, value)?),
+                _ => {}
+            }
+        }
+        Ok(filter)
+    }
+
+    /// Append ` and <condition>` for each set field; `qb` must already be inside a where clause
+    pub fn push_conditions(&self, qb: &mut QueryBuilder<'_, Postgres>) {
+        if let Some(tag) = &self.tag {
+            qb.push(" and ").push_bind(tag.clone()).push(" = any(tags)");
+        }
+        if let Some(session) = &self.session {
+            qb.push(" and ").push_bind(session.clone()).push(" = any(tags)");
+        }
+        if !self.exclude_tags.is_empty() {
+            qb.push(" and not (tags && ").push_bind(self.exclude_tags.clone()).push(")");
+        }
+        if let Some(schema_name) = &self.schema_name {
+            qb.push(" and schema_name = ").push_bind(schema_name.clone());
+        }
+        let bounds = [
+            ("created_at >= ", self.created_after),
+            ("created_at < ", self.created_before),
+            ("updated_at >= ", self.updated_after),
+            ("updated_at < ", self.updated_before),
+        ];
+        for (condition, bound) in bounds {
+            if let Some(bound) = bound {
+                qb.push(" and ").push(condition).push_bind(bound);
+            }
+        }
+    }
+}
+
+/// `abc` or `session:abc` as the `session:abc` tag
+pub(crate) fn session_tag(value: &str) -> Result<String, (StatusCode, String)> {
+    let id = value.strip_prefix("session:").unwrap_or(value);
+    if id.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "session must not be empty".into()));
+    }
+    Ok(format!("session:{}", id))
+}
+
+pub fn timestamp(key: &str, value: &str) -> Result<DateTime<Utc>, (StatusCode, String)> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|_| (StatusCode::BAD_REQUEST, format!("{} must be an RFC 3339 timestamp", key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(query: &[(&str, &str)]) -> Vec<(String, String)> {
+        query.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_from_query_collects_repeated_exclude_tags_and_ranges() {
+        let filter = BreadcrumbFilter::from_query(&pairs(&[
+            ("tag", "knowledge"), ("exclude_tag", "archived"), ("exclude_tag", "draft"), ("nn", "5"),
+            ("created_after", "2025-06-01T00:00:00Z"), ("since", "2025-06-15T12:00:00+02:00"),
+        ]))
+        .unwrap();
+        assert_eq!(filter.tag.as_deref(), Some("knowledge"));
+        assert_eq!(filter.exclude_tags, vec!["archived", "draft"]);
+        assert_eq!(filter.created_after.unwrap().to_rfc3339(), "2025-06-01T00:00:00+00:00");
+        assert_eq!(filter.updated_after.unwrap().to_rfc3339(), "2025-06-15T10:00:00+00:00");
+        assert_eq!(filter.created_before, None);
+    }
+
+    #[test]
+    fn test_session_is_stored_as_its_tag() {
+        let filter = BreadcrumbFilter::from_query(&pairs(&[("session", "abc")])).unwrap();
+        assert_eq!(filter.session.as_deref(), Some("session:abc"));
+        let filter = BreadcrumbFilter::from_query(&pairs(&[("session", "session:abc")])).unwrap();
+        assert_eq!(filter.session.as_deref(), Some("session:abc"));
+        assert_eq!(BreadcrumbFilter::from_query(&pairs(&[("session", "session:")])).unwrap_err().0, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_from_query_rejects_bad_timestamps() {
+        let (status, message) = BreadcrumbFilter::from_query(&pairs(&[("created_before", "last week")])).unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(message.contains("created_before"));
+    }
+
+    #[test]
+    fn test_push_conditions_binds_every_value() {
+        let filter = BreadcrumbFilter::from_query(&pairs(&[
+            ("tag", "k8s"), ("session", "s1"), ("exclude_tag", "archived"), ("schema_name", "knowledge.v1"),
+            ("created_after", "2025-06-01T00:00:00Z"), ("updated_before", "2025-07-01T00:00:00Z"),
+        ]))
+        .unwrap();
+        let mut qb = QueryBuilder::<Postgres>::new("select id from breadcrumbs where owner_id = ");
+        qb.push_bind(uuid::Uuid::nil());
+        filter.push_conditions(&mut qb);
+        assert_eq!(
+            qb.sql(),
+            "select id from breadcrumbs where owner_id = $1 and $2 = any(tags) and $3 = any(tags) and not (tags && $4) and schema_name = $5 and created_at >= $6 and updated_at < $7"
+        );
+
+        // Without a tag the session takes the first placeholder
+        let filter = BreadcrumbFilter::from_query(&pairs(&[("session", "s1"), ("schema_name", "knowledge.v1")])).unwrap();
+        let mut qb = QueryBuilder::<Postgres>::new("select id from breadcrumbs where owner_id = ");
+        qb.push_bind(uuid::Uuid::nil());
+        filter.push_conditions(&mut qb);
+        assert_eq!(qb.sql(), "select id from breadcrumbs where owner_id = $1 and $2 = any(tags) and schema_name = $3");
+
+        let mut qb = QueryBuilder::<Postgres>::new("select id from breadcrumbs where true");
+        BreadcrumbFilter::default().push_conditions(&mut qb);
+        assert_eq!(qb.sql(), "select id from breadcrumbs where true");
+    }
+}
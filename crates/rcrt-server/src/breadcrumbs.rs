@@ -0,0 +1,976 @@
+//! Breadcrumb Handlers
+//! Create, read (context view, full, bulk), update, tag changes, delete, history, retention, rollback, list, vector search and entity extraction.
+//! Create, validate, upsert, update, tag changes, delete and the context view are adapters over service::BreadcrumbService
+
+use axum::{extract::{Query, State}, http::StatusCode, Json};
+use rcrt_core::embedding_text::{embed_fields, embedding_text};
+use rcrt_core::error::DbError;
+use rcrt_core::models::{AccessType, BreadcrumbContextView, BreadcrumbFull, EncryptedContext};
+use rcrt_core::roles::Role;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{Postgres, QueryBuilder};
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::auth::AuthContext;
+use crate::breadcrumb_filter::BreadcrumbFilter;
+use crate::db_errors::{db_error, db_error_response};
+use crate::embedding::{self, Embedder};
+use crate::events::publish_breadcrumb_updated;
+use crate::replica::ReadPreference;
+use crate::service::{apply_view_hints, record_access, BreadcrumbService, CreateReq, ServiceError, TagsReq, UpdateReq, Validation};
+use crate::{domain_metrics, envelope, history_retention, internal_error, large_values, request_id, schema_registry, search_cache, ttl_policy, AppState};
+
+#[derive(Deserialize)]
+pub struct SearchQuery { qvec: Option<String>, q: Option<String>, nn: Option<i64>, include_context: Option<bool>, target: Option<String>, preview: Option<usize> }
+
+/// Which vector `target=` ranks by; `Both` mixes the two distances by AppState::search_title_weight
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SearchTarget { Content, Title, Both }
+
+impl SearchTarget {
+    fn parse(target: Option<&str>) -> Result<Self, (StatusCode, String)> {
+        match target {
+            None | Some("content") => Ok(SearchTarget::Content),
+            Some("title") => Ok(SearchTarget::Title),
+            Some("both") => Ok(SearchTarget::Both),
+            Some(other) => Err((StatusCode::BAD_REQUEST, format!("target must be content, title or both, not {}", other))),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum SearchResult {
+    List(Vec<ListItem>),
+    Context(Vec<BreadcrumbContextView>),
+}
+
+/// Filters (see BreadcrumbFilter) are WHERE clauses ahead of the ORDER BY, so the ivfflat index still
+/// drives the scan; pgvector filters the rows the probed lists return, so selective filters can yield
+/// fewer than `nn` results. `target=title` only sees rows with a title_embedding; `target=both` ranks
+/// by an expression no index covers, so it scans every row the filters leave. The `q` embedding and the
+/// ranked ids are cached briefly (see search_cache); a cached ranking only re-reads its rows
+pub async fn vector_search(State(state): State<AppState>, auth: AuthContext, Query(q): Query<SearchQuery>, Query(pairs): Query<Vec<(String, String)>>) -> Result<Json<SearchResult>, (StatusCode, String)> {
+    let filter = BreadcrumbFilter::from_query(&pairs)?;
+    let target = SearchTarget::parse(q.target.as_deref())?;
+    // Rank by the owner's active model; only the column model has title vectors
+    let model = state.active_models.active(auth.owner_id).await;
+    if !model.in_column() && target != SearchTarget::Content {
+        return Err((StatusCode::BAD_REQUEST, format!("target must be content while {} is the active embedding model", model.active_model)));
+    }
+    // if qvec not provided, attempt to embed ?q=title/context
+    let qvec: Vec<f32> = if let Some(qv) = q.qvec {
+        qv.split(',').filter_map(|s| s.parse::<f32>().ok()).collect()
+    } else if let Some(text) = q.q.as_deref().filter(|_| !model.in_column()) {
+        let Some(active) = state.target_model.as_ref().filter(|t| t.name == model.active_model) else {
+            return Err((StatusCode::SERVICE_UNAVAILABLE, format!("{} is active but not loaded here (EMBED_TARGET_MODEL_NAME)", model.active_model)));
+        };
+        match active.embedder.embed(embedding::standalone_text(text)) {
+            Ok(v) => v,
+            Err(e) => {
+                tracing::warn!("vector_search embedding with {} failed: {}", active.name, e);
+                return Ok(Json(SearchResult::List(vec![])));
+            }
+        }
+    } else if let Some(text) = q.q {
+        match state.search_cache.embeddings.embed(embedding::standalone_text(&text)).await {
+            Ok(v) => v,
+            Err(e) => {
+                tracing::warn!("vector_search embedding failed: {}", e);
+                return Ok(Json(SearchResult::List(vec![])));
+            }
+        }
+    } else { return Ok(Json(SearchResult::List(vec![]))); };
+    let limit = q.nn.unwrap_or(5).max(1);
+    let include_context = q.include_context.unwrap_or(false);
+    let preview = q.preview.map(|n| n.clamp(1, MAX_PREVIEW_CHARS));
+    let reader = (!auth.has_role(Role::Curator)).then_some(auth.agent_id);
+    let key = search_cache::search_key(auth.owner_id, reader, &qvec, &format!("{:?}:{}", target, model.active_model), &filter, limit);
+    let cached = state.search_cache.results.get(&key);
+    let _search_timer = cached.is_none().then(domain_metrics::vector_search_timer);
+
+    // Built again if the replica can't serve it and the primary is asked instead
+    let build = || {
+        let mut qb = QueryBuilder::<Postgres>::new(if include_context {
+            "select id, title, context, tags, schema_name, version, updated_at from breadcrumbs where owner_id = "
+        } else {
+            "select id, title, tags, version, updated_at, "
+        });
+        if !include_context {
+            push_preview_column(&mut qb, &auth, preview);
+            qb.push(" from breadcrumbs where owner_id = ");
+        }
+        qb.push_bind(auth.owner_id);
+        push_full_read_condition(&mut qb, &auth);
+        if let Some(ids) = &cached {
+            qb.push(" and id = any(").push_bind(ids.to_vec()).push(") order by array_position(").push_bind(ids.to_vec()).push(", id)");
+        } else {
+            filter.push_conditions(&mut qb);
+            // Cosine distance, the ivfflat index's operator class; embeddings are L2-normalized, so the
+            // ranking is the same as inner product
+            match target {
+                SearchTarget::Content if !model.in_column() => {
+                    let sql = model.vector_sql();
+                    qb.push(format!(" and {} order by {} <=> ", sql.embedded, sql.vector)).push_bind(qvec.clone()).push("::vector");
+                }
+                SearchTarget::Content => {
+                    qb.push(" order by embedding <=> ").push_bind(qvec.clone()).push("::vector");
+                }
+                SearchTarget::Title => {
+                    qb.push(" and title_embedding is not null order by title_embedding <=> ").push_bind(qvec.clone()).push("::vector");
+                }
+                SearchTarget::Both => {
+                    // Rows without a title vector fall back to their content distance for the title share
+                    let w = state.search_title_weight as f64;
+                    qb.push(" order by (").push_bind(1.0 - w).push(" * (embedding <=> ").push_bind(qvec.clone())
+                        .push("::vector) + ").push_bind(w).push(" * coalesce(title_embedding <=> ").push_bind(qvec.clone())
+                        .push("::vector, embedding <=> ").push_bind(qvec.clone()).push("::vector))");
+                }
+            }
+            qb.push(" limit ").push_bind(limit);
+        }
+        qb
+    };
+    // Every row returned is a search hit, from a cached ranking or not
+    let remember = |ids: Vec<Uuid>| {
+        record_access(&state, &auth, AccessType::SearchHit, &ids);
+        if cached.is_none() { state.search_cache.results.insert(key, ids) }
+    };
+
+    if include_context {
+        let rows = state.reads.read(ReadPreference::Replica, |db| {
+            let mut qb = build();
+            async move {
+                qb.build_query_as::<(Uuid,String,serde_json::Value,Vec<String>,Option<String>,i32,chrono::DateTime<chrono::Utc>)>()
+                    .fetch_all(&db.pool)
+                    .instrument(tracing::info_span!("db", query = "vector_search"))
+                    .await
+                    .map_err(DbError::from)
+            }
+        }).await.map_err(db_error)?;
+        remember(rows.iter().map(|r| r.0).collect());
+        let items = rows.into_iter().map(|(id,title,context,tags,schema_name,version,updated_at)| {
+            BreadcrumbContextView {
+                id, title, description: None, semantic_version: None, context, tags, schema_name, llm_hints: None, version, updated_at
+            }
+        }).collect();
+        Ok(Json(SearchResult::Context(items)))
+    } else {
+        let rows = state.reads.read(ReadPreference::Replica, |db| {
+            let mut qb = build();
+            async move {
+                qb.build_query_as::<(Uuid,String,Vec<String>,i32,chrono::DateTime<chrono::Utc>,Option<serde_json::Value>)>()
+                    .fetch_all(&db.pool)
+                    .instrument(tracing::info_span!("db", query = "vector_search"))
+                    .await
+                    .map_err(DbError::from)
+            }
+        }).await.map_err(db_error)?;
+        remember(rows.iter().map(|r| r.0).collect());
+        let mut budget = PreviewBudget::new();
+        let items = rows.into_iter().map(|(id,title,tags,version,updated_at,context)| {
+            let context_preview = preview.zip(context).and_then(|(n, context)| budget.take(&context, n));
+            ListItem{ id, title, tags, schema_name: None, version, updated_at, context_preview }
+        }).collect();
+        Ok(Json(SearchResult::List(items)))
+    }
+}
+
+/// ` and <caller may read the row in full>`. A pii/secret title is what fanout redacts, so search
+/// and suggest only find such rows for curators, their creator and read_full grantees
+pub(crate) fn push_full_read_condition(qb: &mut QueryBuilder<'_, Postgres>, auth: &AuthContext) {
+    if !auth.has_role(Role::Curator) {
+        qb.push(" and ");
+        push_full_read_expr(qb, auth);
+    }
+}
+
+fn push_full_read_expr(qb: &mut QueryBuilder<'_, Postgres>, auth: &AuthContext) {
+    if auth.has_role(Role::Curator) {
+        qb.push("true");
+    } else {
+        qb.push("(sensitivity = 'low' or created_by = ").push_bind(auth.agent_id)
+            .push(" or exists (select 1 from acl_entries a where a.breadcrumb_id = breadcrumbs.id and (a.grantee_agent_id = ").push_bind(auth.agent_id)
+            .push(" or a.grantee_owner_id = ").push_bind(auth.owner_id)
+            .push(") and 'read_full' = any(a.actions)))");
+    }
+}
+
+/// Longest `preview=` honoured, in characters
+pub const MAX_PREVIEW_CHARS: usize = 1000;
+/// Preview bytes one list or search response carries; rows past it get no preview
+pub const MAX_PREVIEW_BYTES_PER_REQUEST: usize = 64 * 1024;
+
+/// The context column for `preview=`: null without one, and for rows the caller can't read in full,
+/// so their context never leaves the database
+fn push_preview_column(qb: &mut QueryBuilder<'_, Postgres>, auth: &AuthContext, preview: Option<usize>) {
+    if preview.is_some() {
+        qb.push("case when ");
+        push_full_read_expr(qb, auth);
+        qb.push(" then context end");
+    } else {
+        qb.push("null::jsonb");
+    }
+}
+
+/// The context's meaningful strings (the embedding text, without the title) cut to `chars`
+/// characters, so the cut always falls on a char boundary
+pub fn context_preview(context: &serde_json::Value, chars: usize) -> String {
+    let text = embedding_text("", context, None, embedding::text_config());
+    truncate_chars(&text, chars).to_string()
+}
+
+fn truncate_chars(text: &str, chars: usize) -> &str {
+    match text.char_indices().nth(chars) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    }
+}
+
+/// What is left of MAX_PREVIEW_BYTES_PER_REQUEST
+struct PreviewBudget { remaining: usize }
+
+impl PreviewBudget {
+    fn new() -> Self {
+        PreviewBudget { remaining: MAX_PREVIEW_BYTES_PER_REQUEST }
+    }
+
+    /// A preview of at most `chars` characters, shortened to the bytes left; None once they're spent
+    fn take(&mut self, context: &serde_json::Value, chars: usize) -> Option<String> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let mut preview = context_preview(context, chars);
+        if preview.len() > self.remaining {
+            let mut end = self.remaining;
+            while !preview.is_char_boundary(end) { end -= 1; }
+            preview.truncate(end);
+            // Less than a char left: stop rather than hand out empty previews
+            if end == 0 {
+                self.remaining = 0;
+                return None;
+            }
+        }
+        self.remaining -= preview.len();
+        Some(preview)
+    }
+}
+
+/// Text to embed for a breadcrumb: the instance's `llm_hints.embed_fields`, else the schema's,
+/// else every meaningful string in the context
+pub async fn embedding_input(state: &AppState, title: &str, context: &serde_json::Value, llm_hints: Option<&serde_json::Value>, schema_name: Option<&str>) -> String {
+    let mut fields = llm_hints.and_then(embed_fields);
+    if fields.is_none() {
+        if let Some(schema) = schema_name {
+            fields = state.schema_cache.load_schema_hints(schema).await.and_then(|h| h.embed_fields);
+        }
+    }
+    embedding_text(title, context, fields.as_deref(), embedding::text_config())
+}
+
+#[derive(Serialize)]
+pub struct CreateResp {
+    id: Uuid,
+    /// When the breadcrumb was created (an idempotent replay answers the original's)
+    created_at: chrono::DateTime<chrono::Utc>,
+    /// When the server took this request; with `created_at`, the start of an end-to-end latency
+    #[serde(skip_serializing_if = "Option::is_none")]
+    received_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Tags the server added, e.g. an inferred session tag
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags_inferred: Vec<String>,
+}
+
+#[tracing::instrument(skip_all, fields(breadcrumb_id = tracing::field::Empty))]
+pub async fn create_breadcrumb(State(state): State<AppState>, auth: AuthContext, headers: axum::http::HeaderMap, Json(req): Json<CreateReq>) -> Result<(axum::http::HeaderMap, Json<CreateResp>), ServiceError> {
+    let idempotency_key = headers.get("Idempotency-Key").and_then(|h| h.to_str().ok());
+    let created = BreadcrumbService::new(state).create(&auth, req, idempotency_key).await?;
+    // Writes to a deprecated schema still succeed, but the caller is told
+    let mut resp_headers = axum::http::HeaderMap::new();
+    if created.deprecated {
+        resp_headers.insert("Deprecation", axum::http::HeaderValue::from_static("true"));
+    }
+    Ok((resp_headers, Json(CreateResp {
+        id: created.breadcrumb.id,
+        created_at: created.breadcrumb.created_at,
+        received_at: request_id::received_at(),
+        tags_inferred: created.tags_inferred,
+    })))
+}
+
+/// Dry-run a create: the same body as POST /breadcrumbs, answered with a report instead of a write;
+/// see BreadcrumbService::validate
+pub async fn validate_breadcrumb(State(state): State<AppState>, auth: AuthContext, Json(req): Json<CreateReq>) -> Result<Json<Validation>, ServiceError> {
+    Ok(Json(BreadcrumbService::new(state).validate(&auth, req).await?))
+}
+
+#[derive(Deserialize)]
+pub struct UpsertQuery {
+    schema: String,
+    /// Comma-separated tags that identify the breadcrumb within the schema
+    key_tags: String,
+}
+
+/// Create or update the one live breadcrumb of `schema` carrying all of `key_tags`; see BreadcrumbService::upsert
+#[tracing::instrument(skip_all, fields(breadcrumb_id = tracing::field::Empty))]
+pub async fn upsert_breadcrumb(State(state): State<AppState>, auth: AuthContext, Query(q): Query<UpsertQuery>, Json(req): Json<CreateReq>) -> Result<Json<serde_json::Value>, ServiceError> {
+    let key_tags: Vec<String> = q.key_tags.split(',').map(str::trim).filter(|t| !t.is_empty()).map(String::from).collect();
+    let up = BreadcrumbService::new(state).upsert(&auth, &q.schema, &key_tags, req).await?;
+    let bc = &up.breadcrumb;
+    Ok(Json(json!({"id": bc.id, "version": bc.version, "created": up.created, "superseded": up.superseded})))
+}
+
+#[derive(Deserialize)]
+pub struct ExtractBreadcrumb { title: Option<String>, context: serde_json::Value }
+
+#[derive(Deserialize)]
+pub struct ExtractReq { text: Option<String>, breadcrumb: Option<ExtractBreadcrumb> }
+
+pub async fn extract_entities(State(state): State<AppState>, auth: AuthContext, Json(req): Json<ExtractReq>) -> Result<Json<rcrt_core::extraction::ExtractedEntities>, (StatusCode, String)> {
+    if !state.extract_limiter.check(&auth.agent_id) {
+        return Err((StatusCode::TOO_MANY_REQUESTS, "rate limit exceeded".into()));
+    }
+    let text = match (req.text, req.breadcrumb) {
+        (Some(text), None) => text,
+        (None, Some(bc)) => rcrt_core::extraction::breadcrumb_text(bc.title.as_deref(), &bc.context),
+        _ => return Err((StatusCode::BAD_REQUEST, "provide exactly one of text or breadcrumb".into())),
+    };
+    if text.len() > state.extract_max_input_bytes {
+        return Err((StatusCode::PAYLOAD_TOO_LARGE, format!("input exceeds {} bytes", state.extract_max_input_bytes)));
+    }
+    let extracted = state.entity_extractor.extract(&text).map_err(internal_error)?;
+    Ok(Json(extracted))
+}
+
+#[derive(Deserialize, Default)]
+pub struct InlineQuery {
+    /// Replace `$rcrt_ref` references to large values with their text; on by default for /full only
+    inline: Option<bool>,
+}
+
+pub async fn get_breadcrumb_context(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(id): axum::extract::Path<Uuid>, Query(q): Query<InlineQuery>) -> Result<Json<BreadcrumbContextView>, ServiceError> {
+    BreadcrumbService::new(state).get_context(&auth, id, q.inline.unwrap_or(false)).await.map(Json)
+}
+
+#[derive(Deserialize)]
+pub struct AsOfQuery {
+    ts: chrono::DateTime<chrono::Utc>,
+    inline: Option<bool>,
+}
+
+/// The context view as it was at `ts`, from the history version current then. Only the context is
+/// versioned: title, tags and llm_hints are today's. Not counted as a read
+pub async fn get_breadcrumb_as_of(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(id): axum::extract::Path<Uuid>, Query(q): Query<AsOfQuery>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let (owner_id, agent_id, ts) = (auth.owner_id, Some(auth.agent_id), q.ts);
+    let Some(mut view) = state.reads.read(ReadPreference::Replica, |db| async move { db.get_breadcrumb_context_for(owner_id, agent_id, id).await }).await.map_err(db_error)? else {
+        return Err((StatusCode::NOT_FOUND, "not found".into()));
+    };
+    let Some(past) = state.reads.read(ReadPreference::Replica, |db| async move { db.get_breadcrumb_history_as_of(owner_id, agent_id, id, ts).await }).await.map_err(db_error)? else {
+        return Err((StatusCode::NOT_FOUND, format!("no version of {} at {}", id, q.ts.to_rfc3339())));
+    };
+    // With versions pruned in between, the retained one may not be what was current at ts
+    if past.next_version.is_some_and(|next| next != past.version + 1) {
+        return Err((StatusCode::GONE, format!("the version of {} current at {} has been pruned", id, q.ts.to_rfc3339())));
+    }
+    view.context = past.context;
+    view.version = past.version;
+    view.updated_at = past.updated_at;
+    if q.inline.unwrap_or(false) {
+        large_values::inline(&state, auth.owner_id, auth.agent_id, &mut view.context).await?;
+    }
+    apply_view_hints(&state, &mut view).await;
+    let mut body = serde_json::to_value(view).map_err(internal_error)?;
+    body["as_of"] = json!(q.ts);
+    body["superseded_at"] = json!(past.superseded_at);
+    body["llm_hints_version"] = json!("current");
+    Ok(Json(body))
+}
+
+#[derive(Deserialize, Default)]
+pub struct FullQuery {
+    /// As on GET /breadcrumbs/:id, but on by default
+    inline: Option<bool>,
+    /// Return the embedding vector itself; otherwise only `has_embedding` and `embedding_dim`
+    #[serde(default)]
+    include_embedding: bool,
+}
+
+pub async fn get_breadcrumb_full(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(id): axum::extract::Path<Uuid>, Query(q): Query<FullQuery>) -> Result<Json<BreadcrumbFull>, (StatusCode, String)> {
+    // Access control handled by get_breadcrumb_full_for (checks visibility and ACLs)
+    let (owner_id, agent_id, include_embedding) = (auth.owner_id, auth.agent_id, q.include_embedding);
+    let Some(mut full) = state.reads.read(ReadPreference::Replica, |db| async move { db.get_breadcrumb_full_for(owner_id, Some(agent_id), id, include_embedding).await }).await.map_err(db_error)? else {
+        return Err((StatusCode::NOT_FOUND, "not found".into()));
+    };
+    if full.context == EncryptedContext::stub() {
+        if let Some(sealed) = state.reads.read(ReadPreference::Replica, |db| async move { db.get_encrypted_context(owner_id, Some(agent_id), id, None).await }).await.map_err(db_error)? {
+            // Reading a breadcrumb doesn't imply reading its encrypted context: the writer, curators and read_full grants only
+            let may_decrypt = full.created_by == Some(auth.agent_id)
+                || auth.has_role(Role::Curator)
+                || state.reads.read(ReadPreference::Replica, |db| async move { db.has_acl_action(owner_id, agent_id, id, "read_full").await }).await.map_err(db_error)?;
+            if !may_decrypt {
+                return Err((StatusCode::FORBIDDEN, "read_full required to decrypt this context".into()));
+            }
+            full.context = envelope::open_context(&envelope::local_kek()?, &sealed)?;
+        }
+    }
+    if q.inline.unwrap_or(true) {
+        large_values::inline(&state, auth.owner_id, auth.agent_id, &mut full.context).await?;
+    }
+    record_access(&state, &auth, AccessType::ApiRead, &[full.id]);
+    Ok(Json(full))
+}
+
+const BULK_GET_MAX_IDS: usize = 100;
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum BulkView { #[default] Context, Full }
+
+#[derive(Deserialize)]
+pub struct BulkGetRequest {
+    ids: Vec<Uuid>,
+    #[serde(default)]
+    view: BulkView,
+    /// As GET's `?inline`: defaults to on for the full view, off for the context view
+    inline: Option<bool>,
+    /// As /full's `?include_embedding`, for the full view
+    #[serde(default)]
+    include_embedding: bool,
+    /// How the returned breadcrumbs are counted in the analytics: `api_read` (default),
+    /// `context_assembly`, which context builders send for what they assemble, or `none` for
+    /// reads that shouldn't count (context dry runs)
+    access: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum BulkItems {
+    Context(Vec<BreadcrumbContextView>),
+    Full(Vec<BreadcrumbFull>),
+}
+
+#[derive(Serialize)]
+pub struct BulkGetResponse {
+    breadcrumbs: BulkItems,
+    /// Requested ids that don't exist or aren't visible to the caller (not distinguished, like a single-item 404)
+    missing: Vec<Uuid>,
+}
+
+/// Put `found` in request order and list the requested ids that weren't found; duplicate ids are returned once
+fn order_by_request<T>(ids: &[Uuid], found: Vec<T>, id_of: impl Fn(&T) -> Uuid) -> (Vec<T>, Vec<Uuid>) {
+    let mut by_id: std::collections::HashMap<Uuid, T> = found.into_iter().map(|item| (id_of(&item), item)).collect();
+    let mut seen = std::collections::HashSet::new();
+    let mut ordered = Vec::with_capacity(by_id.len());
+    let mut missing = Vec::new();
+    for id in ids {
+        if !seen.insert(*id) { continue; }
+        match by_id.remove(id) {
+            Some(item) => ordered.push(item),
+            None => missing.push(*id),
+        }
+    }
+    (ordered, missing)
+}
+
+pub async fn bulk_get_breadcrumbs(State(state): State<AppState>, auth: AuthContext, Json(req): Json<BulkGetRequest>) -> Result<Json<BulkGetResponse>, (StatusCode, String)> {
+    if req.ids.len() > BULK_GET_MAX_IDS {
+        return Err((StatusCode::BAD_REQUEST, format!("at most {} ids per request", BULK_GET_MAX_IDS)));
+    }
+    let access = match req.access.as_deref() {
+        None => Some(AccessType::ApiRead),
+        Some("none") => None,
+        Some(s) => Some(AccessType::parse(s).filter(AccessType::is_read)
+            .ok_or((StatusCode::BAD_REQUEST, format!("access must be api_read, context_assembly or none, not {}", s)))?),
+    };
+    // Same RLS-scoped reads as GET /breadcrumbs/:id and /full, so visibility, ACLs and sensitivity apply per id
+    let (owner_id, agent_id, ids, include_embedding) = (auth.owner_id, Some(auth.agent_id), req.ids.as_slice(), req.include_embedding);
+    let (breadcrumbs, missing) = match req.view {
+        BulkView::Context => {
+            let found = state.reads.read(ReadPreference::Replica, |db| async move { db.get_breadcrumbs_context_for(owner_id, agent_id, ids).await }).await.map_err(db_error)?;
+            let (mut views, missing) = order_by_request(&req.ids, found, |v| v.id);
+            if let Some(access) = access {
+                let found_ids: Vec<Uuid> = views.iter().map(|v| v.id).collect();
+                record_access(&state, &auth, access, &found_ids);
+            }
+            for view in &mut views {
+                if req.inline.unwrap_or(false) {
+                    large_values::inline(&state, auth.owner_id, auth.agent_id, &mut view.context).await?;
+                }
+                apply_view_hints(&state, view).await;
+            }
+            (BulkItems::Context(views), missing)
+        }
+        BulkView::Full => {
+            let found = state.reads.read(ReadPreference::Replica, |db| async move { db.get_breadcrumbs_full_for(owner_id, agent_id, ids, include_embedding).await }).await.map_err(db_error)?;
+            let (mut full, missing) = order_by_request(&req.ids, found, |f| f.id);
+            if let Some(access) = access {
+                let found_ids: Vec<Uuid> = full.iter().map(|f| f.id).collect();
+                record_access(&state, &auth, access, &found_ids);
+            }
+            if req.inline.unwrap_or(true) {
+                for item in &mut full {
+                    large_values::inline(&state, auth.owner_id, auth.agent_id, &mut item.context).await?;
+                }
+            }
+            (BulkItems::Full(full), missing)
+        }
+    };
+    Ok(Json(BulkGetResponse { breadcrumbs, missing }))
+}
+
+#[derive(Deserialize, Default)]
+pub struct UpdateQuery {
+    /// Include the current context in a 412 body
+    #[serde(default)]
+    return_current: bool,
+    /// Curators only: write even if If-Match is stale
+    #[serde(default)]
+    force: bool,
+}
+
+#[tracing::instrument(skip_all, fields(breadcrumb_id = %id))]
+pub async fn update_breadcrumb(State(state): State<AppState>, auth: AuthContext, headers: axum::http::HeaderMap, axum::extract::Path(id): axum::extract::Path<Uuid>, Query(q): Query<UpdateQuery>, Json(req): Json<UpdateReq>) -> Result<Json<serde_json::Value>, axum::response::Response> {
+    use axum::response::IntoResponse;
+    let expected_version = headers.get(axum::http::header::IF_MATCH).and_then(|h| h.to_str().ok()).and_then(|s| s.trim_matches('"').parse::<i32>().ok());
+    BreadcrumbService::new(state).update(&auth, id, expected_version, q.force, req).await.map_err(|e| match e {
+        ServiceError::Db(e) => db_error_response(e, q.return_current),
+        e => e.into_response(),
+    })?;
+    Ok(Json(json!({"ok": true})))
+}
+
+/// Add and remove tags against the stored ones; see BreadcrumbService::update_tags
+#[tracing::instrument(skip_all, fields(breadcrumb_id = %id))]
+pub async fn update_breadcrumb_tags(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(id): axum::extract::Path<Uuid>, Json(req): Json<TagsReq>) -> Result<Json<serde_json::Value>, ServiceError> {
+    let bc = BreadcrumbService::new(state).update_tags(&auth, id, req).await?;
+    Ok(Json(json!({"id": bc.id, "tags": bc.tags, "version": bc.version})))
+}
+
+#[derive(Deserialize, Default)]
+pub struct DeleteQuery {
+    /// Delete even though other breadcrumbs reference it; each of them gets a breadcrumb.reference_broken event
+    #[serde(default)]
+    force: bool,
+}
+
+/// Delete a breadcrumb. One that other breadcrumbs declare references to gets a 409 listing them, unless `?force=true`
+pub async fn delete_breadcrumb(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(id): axum::extract::Path<Uuid>, Query(q): Query<DeleteQuery>) -> Result<Json<serde_json::Value>, ServiceError> {
+    let broken = BreadcrumbService::new(state).delete(&auth, id, q.force).await?;
+    Ok(Json(json!({"ok": true, "broken_references": broken.len()})))
+}
+
+pub async fn get_breadcrumb_history(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(id): axum::extract::Path<Uuid>) -> Result<Json<Vec<serde_json::Value>>, (StatusCode, String)> {
+    let (owner_id, agent_id) = (auth.owner_id, Some(auth.agent_id));
+    let rows = state.reads.read(ReadPreference::Replica, |db| async move { db.list_breadcrumb_history(owner_id, agent_id, id).await }).await.map_err(db_error)?;
+    let out = rows.into_iter().map(|(v,c,u,b)| json!({"version": v, "context": c, "updated_at": u, "updated_by": b})).collect();
+    Ok(Json(out))
+}
+
+/// TTL settings, read count and retained history size, with the history policy that applies
+pub async fn get_breadcrumb_retention(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(id): axum::extract::Path<Uuid>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let Some(full) = state.db.get_breadcrumb_full_for(auth.owner_id, Some(auth.agent_id), id, false).await.map_err(db_error)? else {
+        return Err((StatusCode::NOT_FOUND, "not found".into()));
+    };
+    let (versions, bytes, oldest_version) = state.db.breadcrumb_history_size(auth.owner_id, Some(auth.agent_id), id).await.map_err(db_error)?;
+    let config = &state.history_retention;
+    let policies = history_retention::HistoryPolicies::load(&state.db, config.default).await.map_err(|e| db_error(e.into()))?;
+    Ok(Json(json!({
+        "id": full.id,
+        "schema_name": full.schema_name,
+        "version": full.version,
+        "ttl": full.ttl,
+        "ttl_type": full.ttl_type,
+        "ttl_config": full.ttl_config,
+        "ttl_source": full.ttl_source,
+        "read_count": full.read_count,
+        "history": {
+            "versions": versions,
+            "bytes": bytes,
+            "oldest_version": oldest_version,
+            "keep_latest": config.keep_latest,
+            "policy": policies.effective(full.owner_id, full.schema_name.as_deref())
+        }
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct RollbackReq { version: i32 }
+
+/// Restore the context of an earlier version as a new version; If-Match applies as on PATCH
+pub async fn rollback_breadcrumb(State(state): State<AppState>, auth: AuthContext, headers: axum::http::HeaderMap, axum::extract::Path(id): axum::extract::Path<Uuid>, Json(req): Json<RollbackReq>) -> Result<Json<serde_json::Value>, axum::response::Response> {
+    use axum::response::IntoResponse;
+    let Some(full) = state.db.get_breadcrumb_full_for(auth.owner_id, Some(auth.agent_id), id, false).await.map_err(|e| db_error(e).into_response())? else {
+        return Err((StatusCode::NOT_FOUND, "not found").into_response());
+    };
+    if req.version < 1 || req.version > full.version {
+        return Err((StatusCode::NOT_FOUND, format!("version {} does not exist (current is {})", req.version, full.version)).into_response());
+    }
+    if req.version == full.version {
+        return Err((StatusCode::BAD_REQUEST, format!("version {} is already current", req.version)).into_response());
+    }
+    let Some(context) = state.db.get_breadcrumb_history_version(auth.owner_id, Some(auth.agent_id), id, req.version).await.map_err(|e| db_error(e).into_response())? else {
+        let config = &state.history_retention;
+        let policies = history_retention::HistoryPolicies::load(&state.db, config.default).await.map_err(|e| db_error(e.into()).into_response())?;
+        let policy = policies.effective(full.owner_id, full.schema_name.as_deref());
+        return Err((StatusCode::GONE, format!(
+            "version {} was pruned by history retention (keep_versions: {:?}, keep_days: {:?}, latest {} always kept); see /breadcrumbs/{}/history for retained versions",
+            req.version, policy.keep_versions, policy.keep_days, config.keep_latest, id
+        )).into_response());
+    };
+
+    // Older versions may predate the policy checks
+    if let Some(schema_name) = full.schema_name.as_deref() {
+        ttl_policy::check_write(&auth, schema_name, &context).map_err(IntoResponse::into_response)?;
+    }
+
+    // An encrypted version is restored from its own ciphertext; a plaintext one is sealed if the breadcrumb is encrypted now
+    let sealed = match state.db.get_encrypted_context(auth.owner_id, Some(auth.agent_id), id, Some(req.version)).await.map_err(|e| db_error(e).into_response())? {
+        Some(sealed) => Some(sealed),
+        None if full.context == EncryptedContext::stub() && state.db.get_encrypted_context(auth.owner_id, Some(auth.agent_id), id, None).await.map_err(|e| db_error(e).into_response())?.is_some() => {
+            let kek = envelope::local_kek().map_err(IntoResponse::into_response)?;
+            Some(envelope::seal_context(&kek, &context).map_err(IntoResponse::into_response)?)
+        }
+        None => None,
+    };
+
+    let expected_version = headers.get(axum::http::header::IF_MATCH).and_then(|h| h.to_str().ok()).and_then(|s| s.trim_matches('"').parse::<i32>().ok()).unwrap_or(full.version);
+    let upd = rcrt_core::models::BreadcrumbUpdate {
+        title: None,
+        description: None,
+        semantic_version: None,
+        context: if sealed.is_some() { None } else { Some(context) },
+        tags: None,
+        schema_name: None,
+        llm_hints: None,
+        visibility: None,
+        sensitivity: None,
+        ttl: None,
+        ttl_type: None,
+        ttl_config: None,
+        ttl_source: None,
+    };
+    let started = std::time::Instant::now();
+    let updated = match sealed {
+        Some(sealed) => state.db.update_encrypted_breadcrumb(auth.owner_id, auth.agent_id, id, Some(expected_version), upd, sealed).await,
+        None => state.db.update_breadcrumb(auth.owner_id, auth.agent_id, id, Some(expected_version), upd).await,
+    };
+    let bc = updated.map_err(|e| db_error_response(e, false))?;
+    domain_metrics::record_op("update", bc.schema_name.as_deref(), auth.owner_id, started, Some(bc.size_bytes));
+    tracing::info!("⏪ Rolled back breadcrumb {} to version {} as version {}", id, req.version, bc.version);
+
+    if bc.schema_name.as_deref() == Some(schema_registry::SCHEMA_DEF) {
+        state.schema_registry.invalidate().await;
+    } else if bc.schema_name.as_deref().is_some_and(ttl_policy::is_policy_schema) {
+        state.ttl_policies.invalidate().await;
+    }
+    publish_breadcrumb_updated(&state, auth.owner_id, &bc).await;
+
+    Ok(Json(json!({"ok": true, "version": bc.version, "restored_from": req.version})))
+}
+
+#[derive(Deserialize)]
+pub struct ListQuery { limit: Option<i64>, offset: Option<i64>, include_context: Option<bool>, preview: Option<usize> }
+
+/// `context_preview` only with `preview=N`, and never for rows the caller can't read in full
+#[derive(Serialize)]
+pub struct ListItem {
+    id: Uuid, title: String, tags: Vec<String>, schema_name: Option<String>, version: i32, updated_at: chrono::DateTime<chrono::Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    context_preview: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum ListResult {
+    List(Vec<ListItem>),
+    Context(Vec<BreadcrumbContextView>),
+}
+
+pub async fn list_breadcrumbs(State(state): State<AppState>, auth: AuthContext, Query(q): Query<ListQuery>, Query(pairs): Query<Vec<(String, String)>>) -> Result<Json<ListResult>, (axum::http::StatusCode, String)> {
+    let include_context = q.include_context.unwrap_or(false);
+    let preview = q.preview.map(|n| n.clamp(1, MAX_PREVIEW_CHARS));
+    let filter = BreadcrumbFilter::from_query(&pairs)?;
+
+    let build = || {
+        let mut qb = QueryBuilder::<Postgres>::new(if include_context {
+            "select id, title, context, tags, schema_name, version, updated_at from breadcrumbs where true"
+        } else {
+            "select id, title, tags, schema_name, version, updated_at, "
+        });
+        if !include_context {
+            push_preview_column(&mut qb, &auth, preview);
+            qb.push(" from breadcrumbs where true");
+        }
+        // updated_after (or since) is inclusive so pollers can dedupe items sharing the cursor timestamp
+        filter.push_conditions(&mut qb);
+        qb.push(" order by updated_at desc");
+        if let Some(limit) = q.limit { qb.push(" limit ").push_bind(limit.max(1)); }
+        if let Some(offset) = q.offset { qb.push(" offset ").push_bind(offset.max(0)); }
+        qb
+    };
+
+    if include_context {
+        let rows = state.reads.read(ReadPreference::Replica, |db| {
+            let mut qb = build();
+            async move {
+                qb.build_query_as::<(Uuid,String,serde_json::Value,Vec<String>,Option<String>,i32,chrono::DateTime<chrono::Utc>)>()
+                    .fetch_all(&db.pool)
+                    .instrument(tracing::info_span!("db", query = "list_breadcrumbs"))
+                    .await
+                    .map_err(DbError::from)
+            }
+        }).await.map_err(db_error)?;
+        let items = rows.into_iter().map(|(id,title,context,tags,schema_name,version,updated_at)| {
+            BreadcrumbContextView { 
+                id, title, description: None, semantic_version: None, context, tags, schema_name, llm_hints: None, version, updated_at 
+            }
+        }).collect();
+        Ok(Json(ListResult::Context(items)))
+    } else {
+        let rows = state.reads.read(ReadPreference::Replica, |db| {
+            let mut qb = build();
+            async move {
+                qb.build_query_as::<(Uuid,String,Vec<String>,Option<String>,i32,chrono::DateTime<chrono::Utc>,Option<serde_json::Value>)>()
+                    .fetch_all(&db.pool)
+                    .instrument(tracing::info_span!("db", query = "list_breadcrumbs"))
+                    .await
+                    .map_err(DbError::from)
+            }
+        }).await.map_err(db_error)?;
+        let mut budget = PreviewBudget::new();
+        let items = rows.into_iter().map(|(id,title,tags,schema_name,version,updated_at,context)| {
+            let context_preview = preview.zip(context).and_then(|(n, context)| budget.take(&context, n));
+            ListItem{ id, title, tags, schema_name, version, updated_at, context_preview }
+        }).collect();
+        Ok(Json(ListResult::List(items)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{AuthConfig, AuthMode};
+    use crate::test_support::{offline_db, request, send, state};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_order_by_request_keeps_request_order() {
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let found = vec![(c, "c"), (a, "a")];
+        let (ordered, missing) = order_by_request(&[c, b, a, c], found, |(id, _)| *id);
+        assert_eq!(ordered.iter().map(|(_, name)| *name).collect::<Vec<_>>(), vec!["c", "a"]);
+        assert_eq!(missing, vec![b]);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_get_rejects_over_cap() {
+        let auth = AuthConfig::new(AuthMode::Disabled { owner_id: Uuid::new_v4(), agent_id: Uuid::nil() }, None, None, None, None).unwrap();
+        let ids: Vec<Uuid> = (0..=BULK_GET_MAX_IDS).map(|_| Uuid::new_v4()).collect();
+        let app = crate::build_app(state(offline_db(), auth).await);
+        let (status, _) = send(&app, request("POST", "/breadcrumbs/bulk_get", None, Some(json!({ "ids": ids })))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_context_preview_cuts_on_char_boundaries() {
+        let context = json!({ "note": "héllo wörld 🎉🎉 done", "session_id": "abc-123", "count": 3 });
+        assert_eq!(context_preview(&context, 8), "héllo wö");
+        assert_eq!(context_preview(&context, 13), "héllo wörld 🎉");
+        assert_eq!(context_preview(&context, 500), "héllo wörld 🎉🎉 done");
+        assert_eq!(context_preview(&json!({ "encrypted": true }), 10), "");
+    }
+
+    #[test]
+    fn test_preview_budget_stops_at_the_request_cap() {
+        // 3-byte chars, so the cap lands mid-char and the last preview backs off to a boundary
+        let context = json!({ "text": "€".repeat(MAX_PREVIEW_CHARS) });
+        let mut budget = PreviewBudget::new();
+        let mut total = 0;
+        while let Some(preview) = budget.take(&context, MAX_PREVIEW_CHARS) {
+            assert!(!preview.is_empty() && preview.chars().all(|c| c == '€'));
+            total += preview.len();
+        }
+        assert!(total <= MAX_PREVIEW_BYTES_PER_REQUEST && total > MAX_PREVIEW_BYTES_PER_REQUEST - 3, "{}", total);
+        assert_eq!(budget.take(&json!({ "text": "a" }), 1), None);
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_embed_sensitivity_max_keeps_vectors_off_sensitive_rows(pool: sqlx::PgPool) {
+        let db = rcrt_core::db::Db { pool };
+        let (owner_id, agent_id) = (Uuid::new_v4(), Uuid::new_v4());
+        db.ensure_tenant(owner_id, "Sensitivity Test").await.unwrap();
+        let auth = AuthConfig::new(AuthMode::Disabled { owner_id, agent_id }, None, None, None, None).unwrap();
+        let base = state(db.clone(), auth).await;
+        let app = crate::build_app(AppState { embed_sensitivity_max: rcrt_core::models::Sensitivity::Low, ..base });
+        let embedded = |id: Uuid| {
+            let db = db.clone();
+            async move { db.get_breadcrumb_full_for(owner_id, None, id, false).await.unwrap().unwrap().has_embedding }
+        };
+        let create = |title: &str, sensitivity: &str| send(&app, request("POST", "/breadcrumbs", None, Some(json!({
+            "title": title, "context": { "text": title }, "tags": [], "sensitivity": sensitivity
+        }))));
+
+        let (status, body) = create("open", "low").await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let open: Uuid = body["id"].as_str().unwrap().parse().unwrap();
+        let (_, body) = create("classified", "secret").await;
+        let secret: Uuid = body["id"].as_str().unwrap().parse().unwrap();
+        assert!(embedded(open).await);
+        assert!(!embedded(secret).await);
+
+        // Raising the sensitivity drops the vector computed while it was low
+        let (status, body) = send(&app, request("PATCH", &format!("/breadcrumbs/{}", open), None, Some(json!({ "sensitivity": "pii" })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert!(!embedded(open).await);
+
+        // Rows embedded before the limit are cleared by the admin action
+        db.set_breadcrumb_embedding(owner_id, None, secret, vec![0.5; 384]).await.unwrap();
+        let (status, body) = send(&app, request("POST", "/admin/embeddings/clear-sensitive", None, Some(json!({})))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["cleared"], 1);
+        assert_eq!(body["embed_sensitivity_max"], "low");
+        assert!(!embedded(secret).await);
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_full_leaves_out_the_embedding_unless_asked(pool: sqlx::PgPool) {
+        let db = rcrt_core::db::Db { pool };
+        let (owner_id, agent_id) = (Uuid::new_v4(), Uuid::new_v4());
+        db.ensure_tenant(owner_id, "Embedding Omission Test").await.unwrap();
+        let auth = AuthConfig::new(AuthMode::Disabled { owner_id, agent_id }, None, None, None, None).unwrap();
+        let app = crate::build_app(state(db.clone(), auth).await);
+        let (status, body) = send(&app, request("POST", "/breadcrumbs", None, Some(json!({ "title": "Runbook", "context": { "text": "restart it" }, "tags": [] })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let id: Uuid = body["id"].as_str().unwrap().parse().unwrap();
+        db.set_breadcrumb_embedding(owner_id, None, id, vec![0.5; 384]).await.unwrap();
+
+        let (status, full) = send(&app, request("GET", &format!("/breadcrumbs/{}/full", id), None, None)).await;
+        assert_eq!(status, StatusCode::OK, "{}", full);
+        assert!(full.get("embedding").is_none(), "{}", full);
+        assert_eq!((full["has_embedding"].clone(), full["embedding_dim"].clone()), (json!(true), json!(384)));
+        let (_, full) = send(&app, request("GET", &format!("/breadcrumbs/{}/full?include_embedding=true", id), None, None)).await;
+        assert_eq!(full["embedding"].as_array().map(Vec::len), Some(384));
+
+        let bulk = |include_embedding: bool| send(&app, request("POST", "/breadcrumbs/bulk_get", None, Some(json!({ "ids": [id], "view": "full", "include_embedding": include_embedding }))));
+        let (_, body) = bulk(false).await;
+        assert!(body["breadcrumbs"][0].get("embedding").is_none());
+        assert_eq!(body["breadcrumbs"][0]["has_embedding"], json!(true));
+        let (_, body) = bulk(true).await;
+        assert_eq!(body["breadcrumbs"][0]["embedding"].as_array().map(Vec::len), Some(384));
+
+        // History never carries vectors
+        let (_, history) = send(&app, request("GET", &format!("/breadcrumbs/{}/history", id), None, None)).await;
+        assert!(history.as_array().unwrap().iter().all(|v| v.get("embedding").is_none()));
+
+        // Without an embedding there's nothing to include
+        sqlx::query("update breadcrumbs set embedding = null where id = $1").bind(id).execute(&db.pool).await.unwrap();
+        let (_, full) = send(&app, request("GET", &format!("/breadcrumbs/{}/full?include_embedding=true", id), None, None)).await;
+        assert!(full.get("embedding").is_none());
+        assert_eq!((full["has_embedding"].clone(), full["embedding_dim"].clone()), (json!(false), serde_json::Value::Null));
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_context_view_cache_follows_patch_and_delete(pool: sqlx::PgPool) {
+        let db = rcrt_core::db::Db { pool };
+        let (owner_id, agent_id) = (Uuid::new_v4(), Uuid::new_v4());
+        db.ensure_tenant(owner_id, "View Cache Test").await.unwrap();
+        let auth = AuthConfig::new(AuthMode::Disabled { owner_id, agent_id }, None, None, None, None).unwrap();
+        let cache = Arc::new(crate::view_cache::ContextViewCache::new(16));
+        let base = state(db.clone(), auth).await;
+        let app = crate::build_app(AppState { view_cache: Some(cache.clone()), ..base });
+
+        // The cached view is the transformed one
+        let (status, body) = send(&app, request("POST", "/breadcrumbs", None, Some(json!({
+            "title": "Tools", "context": { "tools": ["search", "fetch"], "internal": "not for agents" }, "tags": ["catalog"],
+            "llm_hints": { "exclude": ["internal"] }
+        })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let id: Uuid = body["id"].as_str().unwrap().parse().unwrap();
+        let (status, first) = send(&app, request("GET", &format!("/breadcrumbs/{}", id), None, None)).await;
+        assert_eq!(status, StatusCode::OK, "{}", first);
+        assert_eq!(first["context"], json!({ "tools": ["search", "fetch"] }));
+        assert_eq!(cache.get(id, 1).expect("view cached on first read").context, first["context"]);
+        let (_, again) = send(&app, request("GET", &format!("/breadcrumbs/{}", id), None, None)).await;
+        assert_eq!(again, first);
+
+        // A PATCH drops the entry, and the next read caches the new version
+        let (status, body) = send(&app, request("PATCH", &format!("/breadcrumbs/{}", id), None, Some(json!({ "context": { "tools": ["search"] } })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert!(cache.get(id, 1).is_none());
+        let (_, after) = send(&app, request("GET", &format!("/breadcrumbs/{}", id), None, None)).await;
+        assert_eq!(after["version"], 2);
+        assert_eq!(after["context"], json!({ "tools": ["search"] }));
+        assert!(cache.get(id, 2).is_some());
+
+        // A change the process didn't see is still caught by the version check
+        sqlx::query("update breadcrumbs set context = '{\"tools\": []}', version = version + 1 where id = $1").bind(id).execute(&db.pool).await.unwrap();
+        let (_, outside) = send(&app, request("GET", &format!("/breadcrumbs/{}", id), None, None)).await;
+        assert_eq!((outside["version"].as_i64(), &outside["context"]), (Some(3), &json!({ "tools": [] })));
+
+        let (status, _) = send(&app, request("DELETE", &format!("/breadcrumbs/{}", id), None, None)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(cache.len(), 0);
+        let (status, _) = send(&app, request("GET", &format!("/breadcrumbs/{}", id), None, None)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_repeated_search_reuses_embedding_and_ranking(pool: sqlx::PgPool) {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct CountingEmbedder(AtomicUsize);
+        impl embedding::Embedder for CountingEmbedder {
+            fn embed(&self, _text: String) -> Result<Vec<f32>, String> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Ok(vec![0.5; 384])
+            }
+        }
+
+        let db = rcrt_core::db::Db { pool };
+        let (owner_id, agent_id) = (Uuid::new_v4(), Uuid::new_v4());
+        db.ensure_tenant(owner_id, "Search Cache Test").await.unwrap();
+        let auth = AuthConfig::new(AuthMode::Disabled { owner_id, agent_id }, None, None, None, None).unwrap();
+        let embedder = Arc::new(CountingEmbedder(AtomicUsize::new(0)));
+        let cache = crate::search_cache::SearchCache::new(embedder.clone(), std::time::Duration::from_secs(60), 16, std::time::Duration::from_secs(60), 16);
+        let base = state(db.clone(), auth).await;
+        let app = crate::build_app(AppState { search_cache: Arc::new(cache), ..base });
+        let create = |title: &'static str| {
+            let (app, db) = (&app, db.clone());
+            async move {
+                let (status, body) = send(app, request("POST", "/breadcrumbs", None, Some(json!({ "title": title, "context": {}, "tags": ["faq"] })))).await;
+                assert_eq!(status, StatusCode::OK, "{}", body);
+                let id: Uuid = body["id"].as_str().unwrap().parse().unwrap();
+                db.set_breadcrumb_embedding(owner_id, None, id, vec![0.5; 384]).await.unwrap();
+                id
+            }
+        };
+        let search = || async {
+            let (status, body) = send(&app, request("GET", "/breadcrumbs/search?q=refund%20policy&tag=faq&nn=5", None, None)).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+            let mut titles: Vec<String> = body.as_array().unwrap().iter().map(|b| b["title"].as_str().unwrap().to_string()).collect();
+            titles.sort();
+            titles
+        };
+
+        let refunds = create("Refunds").await;
+        let returns = create("Returns").await;
+        assert_eq!(search().await, vec!["Refunds", "Returns"]);
+        assert_eq!(search().await, vec!["Refunds", "Returns"]);
+        assert_eq!(embedder.0.load(Ordering::SeqCst), 1);
+
+        // A cached ranking re-reads its rows, so edits and deletes show at once; new rows wait out the TTL
+        let (status, body) = send(&app, request("PATCH", &format!("/breadcrumbs/{}", refunds), None, Some(json!({ "title": "Refunds (updated)" })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let (status, _) = send(&app, request("DELETE", &format!("/breadcrumbs/{}", returns), None, None)).await;
+        assert_eq!(status, StatusCode::OK);
+        create("Exchanges").await;
+        assert_eq!(search().await, vec!["Refunds (updated)"]);
+        assert_eq!(embedder.0.load(Ordering::SeqCst), 1);
+    }
+}
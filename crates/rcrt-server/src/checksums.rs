@@ -0,0 +1,159 @@
+//! Checksum Verification
+//! GET /breadcrumbs/:id/verify, the curator batch scan, and the hygiene sample; mismatches found in a
+//! batch are recorded as a system.checksum.mismatch.v1 breadcrumb per tenant
+
+use std::collections::BTreeMap;
+use axum::{extract::{Query, State}, http::StatusCode, Json};
+use chrono::Utc;
+use rcrt_core::db::Db;
+use rcrt_core::models::{BreadcrumbCreate, ChecksumCheck, ChecksumScan, ChecksumStatus};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::auth::{require_admin, AuthContext};
+use crate::db_errors::db_error;
+use crate::events::publish_breadcrumb_created;
+use crate::AppState;
+
+pub const MISMATCH_SCHEMA: &str = "system.checksum.mismatch.v1";
+
+#[derive(Deserialize)]
+pub struct VerifyQuery {
+    /// Also recompute each retained history version
+    #[serde(default)]
+    history: bool,
+}
+
+pub async fn verify_breadcrumb(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(id): axum::extract::Path<Uuid>, Query(q): Query<VerifyQuery>) -> Result<Json<Value>, (StatusCode, String)> {
+    let Some(checks) = state.db.verify_breadcrumb_checksums(auth.owner_id, Some(auth.agent_id), id, q.history).await.map_err(db_error)? else {
+        return Err((StatusCode::NOT_FOUND, "not found".into()));
+    };
+    let mismatches: Vec<&ChecksumCheck> = checks.iter().filter(|c| c.status == ChecksumStatus::Mismatch).collect();
+    if !mismatches.is_empty() {
+        tracing::warn!("🧾 Checksum mismatch on breadcrumb {}: {} of {} versions", id, mismatches.len(), checks.len());
+    }
+    Ok(Json(json!({
+        "id": id,
+        "ok": mismatches.is_empty(),
+        "mismatches": mismatches,
+        "checks": checks
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct BatchQuery { after: Option<Uuid>, limit: Option<i64>, #[serde(default)] sample: bool, #[serde(default)] history: bool }
+
+/// Recompute checksums of up to `limit` of the owner's breadcrumbs, in id order after `after` or a
+/// random `sample`; keep calling with `after=next_after` while `has_more` for a full scan
+pub async fn verify_checksums_batch(State(state): State<AppState>, auth: AuthContext, Query(q): Query<BatchQuery>) -> Result<Json<Value>, (StatusCode, String)> {
+    require_admin(&state, &auth)?;
+    let limit = q.limit.unwrap_or(200).clamp(1, 1000);
+    let scan = state.db.verify_checksums_batch(Some(auth.owner_id), q.after, limit, q.sample, q.history).await.map_err(db_error)?;
+    let report = record_reports(&state, &scan, "admin").await.into_iter().next();
+    tracing::info!("🧾 Checksum batch by {}: {} scanned, {} mismatches, {} unverifiable", auth.agent_id, scan.scanned, scan.mismatches.len(), scan.unverifiable);
+    Ok(Json(json!({
+        "scanned": scan.scanned,
+        "mismatches": scan.mismatches.iter().map(|(_, c)| c).collect::<Vec<_>>(),
+        "unverifiable": scan.unverifiable,
+        "report_id": report,
+        "next_after": scan.next_after,
+        "has_more": !q.sample && scan.scanned == limit
+    })))
+}
+
+/// One hygiene pass: `limit` breadcrumbs of every tenant after `cursor`, wrapping around at the end.
+/// Returns the cursor for the next pass
+pub async fn run_hygiene_sample(state: &AppState, cursor: Option<Uuid>, limit: i64) -> Result<Option<Uuid>, rcrt_core::error::DbError> {
+    let scan = state.db.verify_checksums_batch(None, cursor, limit, false, false).await?;
+    if !scan.mismatches.is_empty() {
+        tracing::warn!("🧾 Hygiene found {} checksum mismatches in {} breadcrumbs", scan.mismatches.len(), scan.scanned);
+        record_reports(state, &scan, "hygiene").await;
+    }
+    Ok(if scan.scanned < limit { None } else { scan.next_after })
+}
+
+/// Report breadcrumb ids, one per tenant with mismatches; a failed report is logged, not returned
+async fn record_reports(state: &AppState, scan: &ChecksumScan, found_by: &str) -> Vec<Uuid> {
+    let mut ids = Vec::new();
+    for (owner_id, context) in reports_by_owner(scan, found_by) {
+        match create_report(&state.db, owner_id, context).await {
+            Ok(bc) => {
+                publish_breadcrumb_created(state, owner_id, &bc).await;
+                ids.push(bc.id);
+            }
+            Err(e) => tracing::error!("🧾 Failed to record checksum mismatch report for owner {}: {}", owner_id, e),
+        }
+    }
+    ids
+}
+
+fn reports_by_owner(scan: &ChecksumScan, found_by: &str) -> Vec<(Uuid, Value)> {
+    let mut by_owner: BTreeMap<Uuid, Vec<&ChecksumCheck>> = BTreeMap::new();
+    for (owner_id, check) in &scan.mismatches {
+        by_owner.entry(*owner_id).or_default().push(check);
+    }
+    by_owner.into_iter()
+        .map(|(owner_id, mismatches)| (owner_id, json!({
+            "found_by": found_by,
+            "checked_at": Utc::now(),
+            "scanned": scan.scanned,
+            "mismatches": mismatches
+        })))
+        .collect()
+}
+
+async fn create_report(db: &Db, owner_id: Uuid, context: Value) -> rcrt_core::error::Result<rcrt_core::models::Breadcrumb> {
+    let count = context["mismatches"].as_array().map_or(0, Vec::len);
+    db.create_breadcrumb_for(owner_id, None, None, BreadcrumbCreate {
+        title: format!("Checksum mismatch in {} breadcrumb versions", count),
+        description: Some("Stored checksums that no longer match the stored context; the row was changed outside the API".to_string()),
+        semantic_version: None,
+        context,
+        tags: vec!["system:checksum-mismatch".to_string()],
+        schema_name: Some(MISMATCH_SCHEMA.to_string()),
+        llm_hints: None,
+        visibility: None,
+        sensitivity: None,
+        ttl: Some(Utc::now() + chrono::Duration::days(30)),
+        ttl_type: None,
+        ttl_config: None,
+        ttl_source: None,
+        entity_keywords: None,
+        entities: None,
+    }).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rcrt_core::models::ChecksumSource;
+
+    fn mismatch(id: Uuid, version: i32) -> ChecksumCheck {
+        ChecksumCheck {
+            breadcrumb_id: id,
+            version,
+            source: ChecksumSource::History,
+            status: ChecksumStatus::Mismatch,
+            stored_checksum: "sha256:aa".into(),
+            computed_checksum: "sha256:bb".into(),
+        }
+    }
+
+    #[test]
+    fn test_one_report_per_owner() {
+        let (a, b, bc) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let scan = ChecksumScan {
+            scanned: 10,
+            mismatches: vec![(a, mismatch(bc, 1)), (b, mismatch(Uuid::new_v4(), 4)), (a, mismatch(bc, 2))],
+            unverifiable: 0,
+            next_after: None,
+        };
+        let reports: BTreeMap<Uuid, Value> = reports_by_owner(&scan, "hygiene").into_iter().collect();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[&a]["mismatches"].as_array().unwrap().len(), 2);
+        assert_eq!(reports[&a]["mismatches"][1]["version"], 2);
+        assert_eq!(reports[&a]["mismatches"][0]["stored_checksum"], "sha256:aa");
+        assert_eq!(reports[&b]["found_by"], "hygiene");
+    }
+}
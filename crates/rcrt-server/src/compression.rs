@@ -0,0 +1,81 @@
+//! Response Compression
+//! gzip/br for JSON responses above a size threshold; SSE and /metrics are routed around the layer
+
+use tower_http::compression::predicate::{NotForContentType, Predicate, SizeAbove};
+use tower_http::compression::CompressionLayer;
+
+/// Negotiates gzip or br from Accept-Encoding; bodies under COMPRESSION_MIN_BYTES (default 1024) go out as-is
+pub fn layer() -> CompressionLayer<impl Predicate> {
+    let min_bytes = std::env::var("COMPRESSION_MIN_BYTES").ok().and_then(|s| s.parse().ok()).unwrap_or(1024u16);
+    CompressionLayer::new().compress_when(
+        SizeAbove::new(min_bytes)
+            .and(NotForContentType::GRPC)
+            .and(NotForContentType::IMAGES)
+            // Routing already keeps /events/stream out; this guards any other stream
+            .and(NotForContentType::SSE),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::auth::{AuthConfig, AuthMode};
+    use crate::test_support::{offline_db, state};
+    use axum::{body::Body, http::{header, Request, StatusCode}};
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    fn dev_auth(owner_id: Uuid) -> AuthConfig {
+        AuthConfig::new(AuthMode::Disabled { owner_id, agent_id: Uuid::nil() }, None, None, None, None).unwrap()
+    }
+
+    fn get(uri: &str) -> Request<Body> {
+        Request::builder().uri(uri).header(header::ACCEPT_ENCODING, "gzip, br").body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_metrics_and_small_bodies_are_not_compressed() {
+        let app = crate::build_app(state(offline_db(), dev_auth(Uuid::new_v4())).await);
+        for uri in ["/metrics", "/health"] {
+            let res = app.clone().oneshot(get(uri)).await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+            assert!(res.headers().get(header::CONTENT_ENCODING).is_none(), "{} was compressed", uri);
+        }
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_large_breadcrumb_is_gzipped(pool: sqlx::PgPool) {
+        use crate::test_support::crumb;
+        use rcrt_core::models::BreadcrumbCreate;
+        use std::io::Read;
+
+        let db = rcrt_core::db::Db { pool };
+        let owner_id = Uuid::new_v4();
+        db.ensure_tenant(owner_id, "Compression Test").await.unwrap();
+        let chunks: Vec<String> = (0..2000).map(|i| format!("context line {} with some repeated padding", i)).collect();
+        let bc = db.create_breadcrumb_for(owner_id, None, None, BreadcrumbCreate {
+            title: "Large context".into(),
+            context: serde_json::json!({ "chunks": chunks }),
+            ..crumb("agent.context.v1", &["test:compression"])
+        }).await.unwrap();
+
+        let app = crate::build_app(state(db, dev_auth(owner_id)).await);
+        let uri = format!("/breadcrumbs/{}", bc.id);
+        let plain = app.clone().oneshot(Request::builder().uri(&uri).body(Body::empty()).unwrap()).await.unwrap();
+        assert!(plain.headers().get(header::CONTENT_ENCODING).is_none());
+        let plain = axum::body::to_bytes(plain.into_body(), usize::MAX).await.unwrap();
+
+        let res = app.oneshot(Request::builder().uri(&uri).header(header::ACCEPT_ENCODING, "gzip").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get(header::CONTENT_ENCODING).unwrap(), "gzip");
+        let compressed = axum::body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        assert!(compressed.len() < plain.len() / 4, "{} compressed vs {} plain", compressed.len(), plain.len());
+
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(&compressed[..]).read_to_end(&mut decoded).unwrap();
+        let decoded: serde_json::Value = serde_json::from_slice(&decoded).unwrap();
+        let expected: serde_json::Value = serde_json::from_slice(&plain).unwrap();
+        assert_eq!(decoded, expected);
+        assert_eq!(decoded["context"]["chunks"].as_array().unwrap().len(), 2000);
+    }
+}
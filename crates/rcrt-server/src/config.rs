@@ -0,0 +1,245 @@
+//! Config
+//! Startup settings, read from the environment once and handed to AppState::from_config
+
+use std::path::PathBuf;
+use anyhow::Context;
+use rcrt_core::models::Sensitivity;
+use uuid::Uuid;
+
+use crate::auth::AuthMode;
+use crate::coordination::MigrationMode;
+use crate::history_retention::{self, HistoryRetentionConfig};
+use crate::session_inference::SessionInference;
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub db_url: String,
+    /// Read replica that pure reads prefer; unset reads everything from db_url
+    pub db_replica_url: Option<String>,
+    /// Reads leave the replica while its replication lag is above this
+    pub db_replica_max_staleness_ms: u64,
+    /// Seconds between replica lag probes (at least 1)
+    pub db_replica_lag_check_secs: u64,
+    /// Whether this replica applies migrations, skips them, or waits for another replica to
+    pub migrations: MigrationMode,
+    /// How long startup waits for the migration lock or, with MIGRATIONS=require, for the schema
+    pub migration_wait_secs: u64,
+    /// Default tenant, created on startup
+    pub owner_id: Uuid,
+    pub auth: AuthSettings,
+    /// Required when built with the `nats` feature
+    pub nats_url: Option<String>,
+    /// POST /extract/entities calls allowed per agent per minute
+    pub extract_rate_per_min: u32,
+    /// Fill entity_keywords heuristically on create when the client sent none
+    pub extract_keywords_on_create: bool,
+    /// Directory for attachments too large to keep inline in Postgres
+    pub attachment_dir: PathBuf,
+    /// Also embed the title alone into title_embedding on create
+    pub embed_title_separately: bool,
+    /// Title share of the combined distance for /breadcrumbs/search?target=both, 0..=1
+    pub search_title_weight: f32,
+    /// Most sensitive breadcrumbs that get embeddings; rows above it are never embedded
+    pub embed_sensitivity_max: Sensitivity,
+    /// Rows POST /admin/embeddings/backfill embeds per second when the call doesn't say; 0 doesn't pace it
+    pub embed_backfill_per_sec: u32,
+    /// Share of the embedded breadcrumbs a model must cover before POST /admin/embeddings/cutover makes it active
+    pub embed_cutover_min_coverage: f64,
+    /// Rebuild an owner's fanout selector index at least this often, even without selector CRUD
+    pub selector_index_max_age_secs: u64,
+    /// How long a resolved API key is trusted before re-checking it (and its revocation) in Postgres
+    pub api_key_cache_ttl_secs: u64,
+    /// Finished /agents/run runs stay readable this long
+    pub agent_run_retention_hours: u64,
+    /// A running run with no progress for this long is failed as orphaned (at least 600)
+    pub agent_run_stale_secs: u64,
+    /// Seconds between SSE heartbeat pings (at least 1)
+    pub sse_ping_interval_secs: u64,
+    /// Envelope-encrypt the context of every sensitivity=secret breadcrumb, not just those sent with encrypt: true
+    pub encrypt_secret_contexts: bool,
+    /// Context string values longer than this are stored as attachments and referenced; 0 keeps everything inline
+    pub context_externalize_min_bytes: usize,
+    /// Cache transformed GET /breadcrumbs/:id context views in process
+    pub cache_context_views: bool,
+    /// Most context views the cache holds before evicting the least recently used
+    pub context_view_cache_max_entries: usize,
+    /// Seconds a search's ranked ids are reused for the same query; 0 turns the cache off
+    pub search_cache_ttl_secs: u64,
+    /// Most searches whose ids are kept before dropping the oldest
+    pub search_cache_max_entries: usize,
+    /// Seconds a query text's embedding is reused; 0 turns the cache off
+    pub query_embedding_cache_ttl_secs: u64,
+    /// Most query embeddings kept before dropping the oldest
+    pub query_embedding_cache_max_entries: usize,
+    /// Concurrent interactive requests (breadcrumb creates, context view reads)
+    pub priority_interactive_concurrency: usize,
+    /// Concurrent batch requests (imports, backfills, hygiene runs)
+    pub priority_batch_concurrency: usize,
+    /// Batch requests still waiting for a slot or a pooled connection after this many ms are shed with 503; 0 never sheds
+    pub load_shed_wait_ms: u64,
+    /// Retry-After on a shed request, and how long batch requests are shed outright after a long wait
+    pub load_shed_retry_after_secs: u64,
+    /// Deactivate a webhook after this many failed deliveries in a row; 0 never does
+    pub webhook_auto_disable_after_failures: u32,
+    /// Deliveries a strict-ordering webhook holds back per breadcrumb; the next one goes to the DLQ
+    pub webhook_ordered_queue_max: usize,
+    /// Where a create without a session: tag may get one; off by default
+    pub session_tag_inference: SessionInference,
+    /// Key share link tokens are signed with; unset uses a random one, so links break on restart and across replicas
+    pub share_link_secret: Option<String>,
+    /// GET /shared/:token requests allowed per token per minute
+    pub share_link_rate_per_min: u32,
+    /// How long GET /agents/:id/inbox hides the entries it hands out when the poller names no lease
+    pub inbox_lease_secs: u64,
+    /// Most breadcrumbs one POST /admin/breadcrumbs/purge deletes; the rest wait for the next call with the returned token
+    pub purge_max_per_request: i64,
+    /// Breadcrumbs a purge deletes per transaction (at least 1)
+    pub purge_batch_size: i64,
+    /// Delivery attempts per webhook dispatch, the first included
+    pub webhook_max_retries: usize,
+    /// Longest Retry-After a webhook delivery waits out before its next attempt
+    pub webhook_retry_after_max_secs: u64,
+    /// Uploads up to this size stay inline in Postgres; larger ones go to the AttachmentStore
+    pub attachment_inline_max_bytes: usize,
+    /// Largest attachment upload
+    pub attachment_max_bytes: usize,
+    /// Distinct attachment bytes stored per tenant
+    pub attachment_tenant_quota_bytes: i64,
+    /// Operations a version diff carries before it is truncated
+    pub diff_max_ops: usize,
+    /// Serialized size of the values a version diff carries before it is truncated
+    pub diff_max_bytes: usize,
+    /// Arrays longer than this are listed in a diff summary rather than diffed element by element
+    pub diff_max_array_len: usize,
+    /// Events an SSE connection may fall behind before SSE_OVERFLOW_POLICY applies
+    pub sse_channel_capacity: usize,
+    /// SSE_OVERFLOW_POLICY=drop_oldest; otherwise an overflowing connection is closed
+    pub sse_overflow_drop_oldest: bool,
+    /// Version and age limits for breadcrumb history, and how pruning batches; 0 disables a limit
+    pub history_retention: HistoryRetentionConfig,
+    /// Largest text POST /extract takes
+    pub extract_max_input_bytes: usize,
+    /// Rows a session close stamps or purges per statement
+    pub session_close_batch_size: i64,
+    /// TTL a session close stamps on its breadcrumbs unless the request sets ttl_hours
+    pub session_close_ttl_hours: i64,
+    /// How long one NATS publish may take before the event is counted as failed
+    pub nats_publish_timeout_ms: u64,
+}
+
+/// Auth mode and JWT keys; turned into an `auth::AuthConfig` by AppState::from_config
+#[derive(Clone, Debug)]
+pub struct AuthSettings {
+    pub mode: AuthMode,
+    pub public_key_pem: Option<String>,
+    pub private_key_pem: Option<String>,
+    pub issuer: Option<String>,
+    pub audience: Option<String>,
+    /// METRICS_TOKEN: bearer token GET /metrics requires; unset leaves it open
+    pub metrics_token: Option<String>,
+    /// ADMIN_ACCEPTS_CURATOR (default true): curator tokens still pass the /admin routes, which otherwise
+    /// need the admin role; turn off once admin tokens are issued
+    pub admin_accepts_curator: bool,
+}
+
+impl Config {
+    /// Read DB_URL, DB_REPLICA_URL, DB_REPLICA_MAX_STALENESS_MS, DB_REPLICA_LAG_CHECK_SECS, MIGRATIONS, MIGRATION_WAIT_SECS, OWNER_ID, AUTH_MODE, AGENT_ID (disabled mode), JWT_PUBLIC_KEY_PEM, JWT_PRIVATE_KEY_PEM,
+    /// JWT_ISSUER, JWT_AUDIENCE, METRICS_TOKEN, ADMIN_ACCEPTS_CURATOR, NATS_URL, EXTRACT_RATE_LIMIT_PER_MIN, EXTRACT_KEYWORDS_ON_CREATE, ATTACHMENT_DIR,
+    /// EMBED_TITLE_SEPARATELY, SEARCH_TITLE_WEIGHT, EMBED_BACKFILL_PER_SEC, EMBED_CUTOVER_MIN_COVERAGE, ENCRYPT_SECRET_CONTEXTS, CONTEXT_EXTERNALIZE_MIN_BYTES, CACHE_CONTEXT_VIEWS,
+    /// CACHE_CONTEXT_VIEWS_MAX_ENTRIES, SEARCH_CACHE_TTL_SECS, SEARCH_CACHE_MAX_ENTRIES, QUERY_EMBEDDING_CACHE_TTL_SECS,
+    /// QUERY_EMBEDDING_CACHE_MAX_ENTRIES, PRIORITY_INTERACTIVE_CONCURRENCY, PRIORITY_BATCH_CONCURRENCY, LOAD_SHED_WAIT_MS,
+    /// LOAD_SHED_RETRY_AFTER_SECS, WEBHOOK_AUTO_DISABLE_AFTER_FAILURES, WEBHOOK_ORDERED_QUEUE_MAX, SESSION_TAG_INFERENCE, SHARE_LINK_SECRET, SHARE_LINK_RATE_LIMIT_PER_MIN,
+    /// INBOX_LEASE_SECS, PURGE_MAX_PER_REQUEST, PURGE_BATCH_SIZE, WEBHOOK_MAX_RETRIES, WEBHOOK_RETRY_AFTER_MAX_SECS, ATTACHMENT_INLINE_MAX_BYTES, ATTACHMENT_MAX_BYTES, ATTACHMENT_TENANT_QUOTA_BYTES, DIFF_MAX_OPS, DIFF_MAX_BYTES, DIFF_MAX_ARRAY_LEN, SSE_CHANNEL_CAPACITY, SSE_OVERFLOW_POLICY, HISTORY_KEEP_VERSIONS, HISTORY_KEEP_DAYS, HISTORY_KEEP_LATEST, HISTORY_PRUNE_BATCH, HISTORY_PRUNE_MAX_PER_RUN, EXTRACT_MAX_INPUT_BYTES, SESSION_CLOSE_BATCH_SIZE, SESSION_CLOSE_TTL_HOURS and NATS_PUBLISH_TIMEOUT_MS
+    pub fn from_env() -> anyhow::Result<Self> {
+        let db_url = std::env::var("DB_URL").context("DB_URL not set")?;
+        let env_owner_id = std::env::var("OWNER_ID").ok().and_then(|s| Uuid::parse_str(&s).ok());
+        let mode = match std::env::var("AUTH_MODE").as_deref() {
+            Ok("disabled") => {
+                let owner_id = env_owner_id.context("OWNER_ID required in disabled auth mode")?;
+                let agent_id = std::env::var("AGENT_ID").ok().and_then(|s| Uuid::parse_str(&s).ok())
+                    .unwrap_or_else(Uuid::nil);
+                AuthMode::Disabled { owner_id, agent_id }
+            }
+            _ => AuthMode::Jwt,
+        };
+        let embed_sensitivity_max = match std::env::var("EMBED_SENSITIVITY_MAX") {
+            Ok(s) => Sensitivity::parse(&s).with_context(|| format!("EMBED_SENSITIVITY_MAX must be low, pii or secret, not {}", s))?,
+            Err(_) => Sensitivity::Secret,
+        };
+        let migrations = match std::env::var("MIGRATIONS") {
+            Ok(s) => MigrationMode::parse(&s).with_context(|| format!("MIGRATIONS must be run, skip or require, not {}", s))?,
+            Err(_) => MigrationMode::Run,
+        };
+        let session_tag_inference = match std::env::var("SESSION_TAG_INFERENCE") {
+            Ok(s) => SessionInference::parse(&s).with_context(|| format!("SESSION_TAG_INFERENCE must be off, trigger, agent or both, not {}", s))?,
+            Err(_) => SessionInference::Off,
+        };
+        Ok(Config {
+            db_url,
+            db_replica_url: std::env::var("DB_REPLICA_URL").ok().filter(|s| !s.is_empty()),
+            db_replica_max_staleness_ms: std::env::var("DB_REPLICA_MAX_STALENESS_MS").ok().and_then(|s| s.parse().ok()).unwrap_or(5000),
+            db_replica_lag_check_secs: std::env::var("DB_REPLICA_LAG_CHECK_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(5),
+            migrations,
+            migration_wait_secs: std::env::var("MIGRATION_WAIT_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(300),
+            owner_id: env_owner_id.unwrap_or_else(Uuid::new_v4),
+            auth: AuthSettings {
+                mode,
+                public_key_pem: std::env::var("JWT_PUBLIC_KEY_PEM").ok(),
+                private_key_pem: std::env::var("JWT_PRIVATE_KEY_PEM").ok(),
+                issuer: std::env::var("JWT_ISSUER").ok(),
+                audience: std::env::var("JWT_AUDIENCE").ok(),
+                metrics_token: std::env::var("METRICS_TOKEN").ok().filter(|s| !s.is_empty()),
+                admin_accepts_curator: std::env::var("ADMIN_ACCEPTS_CURATOR").ok().and_then(|s| s.parse().ok()).unwrap_or(true),
+            },
+            nats_url: std::env::var("NATS_URL").ok(),
+            extract_rate_per_min: std::env::var("EXTRACT_RATE_LIMIT_PER_MIN").ok().and_then(|s| s.parse().ok()).unwrap_or(120),
+            extract_keywords_on_create: std::env::var("EXTRACT_KEYWORDS_ON_CREATE").ok().and_then(|s| s.parse().ok()).unwrap_or(false),
+            attachment_dir: std::env::var("ATTACHMENT_DIR").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("data/attachments")),
+            embed_title_separately: std::env::var("EMBED_TITLE_SEPARATELY").ok().and_then(|s| s.parse().ok()).unwrap_or(false),
+            search_title_weight: std::env::var("SEARCH_TITLE_WEIGHT").ok().and_then(|s| s.parse::<f32>().ok()).unwrap_or(0.5).clamp(0.0, 1.0),
+            embed_sensitivity_max,
+            embed_backfill_per_sec: std::env::var("EMBED_BACKFILL_PER_SEC").ok().and_then(|s| s.parse().ok()).unwrap_or(0),
+            embed_cutover_min_coverage: std::env::var("EMBED_CUTOVER_MIN_COVERAGE").ok().and_then(|s| s.parse::<f64>().ok()).unwrap_or(0.99).clamp(0.0, 1.0),
+            selector_index_max_age_secs: std::env::var("SELECTOR_INDEX_MAX_AGE_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(60),
+            api_key_cache_ttl_secs: std::env::var("API_KEY_CACHE_TTL_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(30),
+            agent_run_retention_hours: std::env::var("AGENT_RUN_RETENTION_HOURS").ok().and_then(|s| s.parse().ok()).unwrap_or(24),
+            agent_run_stale_secs: std::env::var("AGENT_RUN_STALE_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(900),
+            sse_ping_interval_secs: std::env::var("SSE_PING_INTERVAL_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(5),
+            encrypt_secret_contexts: std::env::var("ENCRYPT_SECRET_CONTEXTS").ok().and_then(|s| s.parse().ok()).unwrap_or(false),
+            context_externalize_min_bytes: std::env::var("CONTEXT_EXTERNALIZE_MIN_BYTES").ok().and_then(|s| s.parse().ok()).unwrap_or(32 * 1024),
+            cache_context_views: std::env::var("CACHE_CONTEXT_VIEWS").ok().and_then(|s| s.parse().ok()).unwrap_or(false),
+            context_view_cache_max_entries: std::env::var("CACHE_CONTEXT_VIEWS_MAX_ENTRIES").ok().and_then(|s| s.parse().ok()).unwrap_or(1000),
+            search_cache_ttl_secs: std::env::var("SEARCH_CACHE_TTL_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(10),
+            search_cache_max_entries: std::env::var("SEARCH_CACHE_MAX_ENTRIES").ok().and_then(|s| s.parse().ok()).unwrap_or(1000),
+            query_embedding_cache_ttl_secs: std::env::var("QUERY_EMBEDDING_CACHE_TTL_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(3600),
+            query_embedding_cache_max_entries: std::env::var("QUERY_EMBEDDING_CACHE_MAX_ENTRIES").ok().and_then(|s| s.parse().ok()).unwrap_or(1000),
+            priority_interactive_concurrency: std::env::var("PRIORITY_INTERACTIVE_CONCURRENCY").ok().and_then(|s| s.parse().ok()).unwrap_or(64),
+            priority_batch_concurrency: std::env::var("PRIORITY_BATCH_CONCURRENCY").ok().and_then(|s| s.parse().ok()).unwrap_or(2),
+            load_shed_wait_ms: std::env::var("LOAD_SHED_WAIT_MS").ok().and_then(|s| s.parse().ok()).unwrap_or(250),
+            load_shed_retry_after_secs: std::env::var("LOAD_SHED_RETRY_AFTER_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(5),
+            webhook_auto_disable_after_failures: std::env::var("WEBHOOK_AUTO_DISABLE_AFTER_FAILURES").ok().and_then(|s| s.parse().ok()).unwrap_or(0),
+            webhook_ordered_queue_max: std::env::var("WEBHOOK_ORDERED_QUEUE_MAX").ok().and_then(|s| s.parse().ok()).unwrap_or(100),
+            session_tag_inference,
+            share_link_secret: std::env::var("SHARE_LINK_SECRET").ok().filter(|s| !s.is_empty()),
+            share_link_rate_per_min: std::env::var("SHARE_LINK_RATE_LIMIT_PER_MIN").ok().and_then(|s| s.parse().ok()).unwrap_or(60),
+            inbox_lease_secs: std::env::var("INBOX_LEASE_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(30),
+            purge_max_per_request: std::env::var("PURGE_MAX_PER_REQUEST").ok().and_then(|s| s.parse().ok()).unwrap_or(10_000),
+            purge_batch_size: std::env::var("PURGE_BATCH_SIZE").ok().and_then(|s| s.parse::<i64>().ok()).unwrap_or(500).max(1),
+            webhook_max_retries: std::env::var("WEBHOOK_MAX_RETRIES").ok().and_then(|s| s.parse::<usize>().ok()).unwrap_or(8).max(1),
+            webhook_retry_after_max_secs: std::env::var("WEBHOOK_RETRY_AFTER_MAX_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(300),
+            attachment_inline_max_bytes: std::env::var("ATTACHMENT_INLINE_MAX_BYTES").ok().and_then(|s| s.parse().ok()).unwrap_or(256 * 1024),
+            attachment_max_bytes: std::env::var("ATTACHMENT_MAX_BYTES").ok().and_then(|s| s.parse().ok()).unwrap_or(25 * 1024 * 1024),
+            attachment_tenant_quota_bytes: std::env::var("ATTACHMENT_TENANT_QUOTA_BYTES").ok().and_then(|s| s.parse().ok()).unwrap_or(1024 * 1024 * 1024),
+            diff_max_ops: std::env::var("DIFF_MAX_OPS").ok().and_then(|s| s.parse().ok()).filter(|n| *n > 0).unwrap_or(1000),
+            diff_max_bytes: std::env::var("DIFF_MAX_BYTES").ok().and_then(|s| s.parse().ok()).filter(|n| *n > 0).unwrap_or(256 * 1024),
+            diff_max_array_len: std::env::var("DIFF_MAX_ARRAY_LEN").ok().and_then(|s| s.parse().ok()).filter(|n| *n > 0).unwrap_or(1000),
+            sse_channel_capacity: std::env::var("SSE_CHANNEL_CAPACITY").ok().and_then(|s| s.parse().ok()).unwrap_or(1000),
+            sse_overflow_drop_oldest: matches!(std::env::var("SSE_OVERFLOW_POLICY").as_deref(), Ok("drop_oldest") | Ok("drop-oldest")),
+            history_retention: history_retention::load_history_retention_config(),
+            extract_max_input_bytes: std::env::var("EXTRACT_MAX_INPUT_BYTES").ok().and_then(|s| s.parse().ok()).unwrap_or(64 * 1024),
+            session_close_batch_size: std::env::var("SESSION_CLOSE_BATCH_SIZE").ok().and_then(|s| s.parse().ok()).unwrap_or(500),
+            session_close_ttl_hours: std::env::var("SESSION_CLOSE_TTL_HOURS").ok().and_then(|s| s.parse().ok()).unwrap_or(168),
+            nats_publish_timeout_ms: std::env::var("NATS_PUBLISH_TIMEOUT_MS").ok().and_then(|s| s.parse().ok()).unwrap_or(2000),
+        })
+    }
+}
@@ -0,0 +1,254 @@
+//! Replica Coordination
+//! Postgres advisory locks for replicas sharing one database: one replica migrates while the others
+//! wait, and singleton background tasks (hygiene runner, outbox dispatcher) run on one instance at a time
+
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use prometheus::{IntGaugeVec, register_int_gauge_vec};
+use rcrt_core::db::Db;
+use sqlx::{Connection, PgConnection};
+use tokio::sync::Mutex;
+
+use crate::MIGRATOR;
+
+/// Advisory lock keys; arbitrary, but fixed across releases so old and new replicas agree
+const MIGRATION_LOCK: i64 = 0x7263_7274_0001;
+pub const HYGIENE_LOCK: i64 = 0x7263_7274_0002;
+pub const OUTBOX_LOCK: i64 = 0x7263_7274_0003;
+
+const POLL: Duration = Duration::from_secs(1);
+/// How often a waiting replica says it is still waiting
+const LOG_EVERY: Duration = Duration::from_secs(30);
+
+static LEADER: OnceLock<IntGaugeVec> = OnceLock::new();
+
+fn leader() -> &'static IntGaugeVec {
+    LEADER.get_or_init(|| register_int_gauge_vec!("singleton_task_leader", "1 while this instance runs the singleton task", &["task"]).unwrap())
+}
+
+/// What a replica does about migrations on startup (MIGRATIONS)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationMode {
+    /// Apply pending migrations under the migration lock (default)
+    Run,
+    /// Neither migrate nor check; for schemas managed out of band
+    Skip,
+    /// Don't migrate; wait until another replica has brought the schema up to this binary's version
+    Require,
+}
+
+impl MigrationMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "run" => Some(MigrationMode::Run),
+            "skip" => Some(MigrationMode::Skip),
+            "require" => Some(MigrationMode::Require),
+            _ => None,
+        }
+    }
+}
+
+/// Newest migration bundled into this binary
+pub fn expected_schema_version() -> i64 {
+    MIGRATOR.iter().map(|m| m.version).max().unwrap_or(0)
+}
+
+/// Newest migration applied to the database; None before any has run
+pub async fn schema_version(db: &Db) -> Result<Option<i64>, sqlx::Error> {
+    match sqlx::query_scalar::<_, Option<i64>>("select max(version) from _sqlx_migrations where success").fetch_one(&db.pool).await {
+        Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some("42P01") => Ok(None),
+        result => result,
+    }
+}
+
+/// Bring the schema to where `mode` wants it, waiting at most `timeout` for other replicas
+pub async fn prepare_schema(db: &Db, mode: MigrationMode, timeout: Duration) -> anyhow::Result<()> {
+    match mode {
+        MigrationMode::Run => migrate_with_lock(db, timeout).await,
+        MigrationMode::Skip => {
+            tracing::info!("MIGRATIONS=skip: not checking the schema version");
+            Ok(())
+        }
+        MigrationMode::Require => wait_for_schema(db, timeout).await,
+    }
+}
+
+/// Run the bundled migrations while holding the migration lock. A replica that finds the lock taken
+/// waits for it, then finds nothing left to apply
+pub async fn migrate_with_lock(db: &Db, timeout: Duration) -> anyhow::Result<()> {
+    // Session-level lock on a connection of its own, so it's released however this ends
+    let mut conn = PgConnection::connect_with(&db.pool.connect_options()).await?;
+    let started = Instant::now();
+    let mut next_log = started;
+    while !try_lock(&mut conn, MIGRATION_LOCK).await? {
+        if started.elapsed() >= timeout {
+            anyhow::bail!("another replica held the migration lock for over {}s (MIGRATION_WAIT_SECS); not starting", timeout.as_secs());
+        }
+        if Instant::now() >= next_log {
+            tracing::info!("⏳ Another replica is migrating; waiting for the migration lock ({}s so far)", started.elapsed().as_secs());
+            next_log = Instant::now() + LOG_EVERY;
+        }
+        tokio::time::sleep(POLL).await;
+    }
+    let before = schema_version(db).await?;
+    let result = MIGRATOR.run(&db.pool).await;
+    // Closing the session releases the lock
+    let _ = conn.close().await;
+    result?;
+    let after = schema_version(db).await?;
+    if before != after {
+        tracing::info!("✅ Migrated schema from {:?} to {:?}", before, after);
+    }
+    Ok(())
+}
+
+/// Wait until the applied schema is at least the version this binary was built for
+async fn wait_for_schema(db: &Db, timeout: Duration) -> anyhow::Result<()> {
+    let expected = expected_schema_version();
+    let started = Instant::now();
+    let mut next_log = started;
+    loop {
+        let current = schema_version(db).await?;
+        if current.is_some_and(|v| v >= expected) {
+            tracing::info!("✅ Schema at {:?}, this binary needs {}", current, expected);
+            return Ok(());
+        }
+        if started.elapsed() >= timeout {
+            anyhow::bail!("schema still at {:?} after {}s, this binary needs {} (MIGRATIONS=require waits for another replica to migrate)", current, timeout.as_secs(), expected);
+        }
+        if Instant::now() >= next_log {
+            tracing::info!("⏳ MIGRATIONS=require: schema at {:?}, waiting for {}", current, expected);
+            next_log = Instant::now() + LOG_EVERY;
+        }
+        tokio::time::sleep(POLL).await;
+    }
+}
+
+async fn try_lock(conn: &mut PgConnection, key: i64) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar("select pg_try_advisory_lock($1)").bind(key).fetch_one(conn).await
+}
+
+/// One instance's claim on a singleton task. The lock lives on a connection of its own: when that
+/// connection drops (or Postgres restarts) the lock goes with it and another instance can take over.
+/// A takeover can overlap one cycle on the instance that hasn't noticed yet, so the tasks stay safe to
+/// run twice
+pub struct Leadership {
+    task: &'static str,
+    key: i64,
+    conn: Mutex<Option<PgConnection>>,
+}
+
+impl Leadership {
+    pub fn new(task: &'static str, key: i64) -> Self {
+        leader().with_label_values(&[task]).set(0);
+        Self { task, key, conn: Mutex::new(None) }
+    }
+
+    /// Whether this instance should run the task now; takes the lock when it's free. Call before each cycle
+    pub async fn acquire(&self, db: &Db) -> bool {
+        let mut held = self.conn.lock().await;
+        if let Some(conn) = held.as_mut() {
+            if conn.ping().await.is_ok() {
+                return true;
+            }
+            tracing::warn!("🗳️ Lost the connection holding the {} lock; another instance may take over", self.task);
+            *held = None;
+            leader().with_label_values(&[self.task]).set(0);
+        }
+        let mut conn = match PgConnection::connect_with(&db.pool.connect_options()).await {
+            Ok(conn) => conn,
+            Err(e) => {
+                tracing::warn!("🗳️ Can't connect to claim the {} lock: {}", self.task, e);
+                return false;
+            }
+        };
+        match try_lock(&mut conn, self.key).await {
+            Ok(true) => {
+                tracing::info!("🗳️ This instance now runs the {}", self.task);
+                leader().with_label_values(&[self.task]).set(1);
+                *held = Some(conn);
+                true
+            }
+            Ok(false) => false,
+            Err(e) => {
+                tracing::warn!("🗳️ Claiming the {} lock failed: {}", self.task, e);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migration_modes() {
+        assert_eq!(MigrationMode::parse("run"), Some(MigrationMode::Run));
+        assert_eq!(MigrationMode::parse("skip"), Some(MigrationMode::Skip));
+        assert_eq!(MigrationMode::parse("require"), Some(MigrationMode::Require));
+        assert_eq!(MigrationMode::parse("always"), None);
+        assert!(expected_schema_version() >= 31);
+    }
+
+    /// A second pool against the same database, standing in for another replica
+    #[cfg(feature = "db-tests")]
+    async fn replica(pool: &sqlx::PgPool) -> Db {
+        Db { pool: sqlx::postgres::PgPoolOptions::new().max_connections(4).connect_with((*pool.connect_options()).clone()).await.unwrap() }
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = false)]
+    async fn test_concurrent_replicas_migrate_once(pool: sqlx::PgPool) {
+        let (a, b, c) = (replica(&pool).await, replica(&pool).await, replica(&pool).await);
+        assert_eq!(schema_version(&a).await.unwrap(), None);
+
+        // Two replicas migrate at once while a third waits for the schema without migrating
+        let timeout = Duration::from_secs(120);
+        let (ra, rb, rc) = tokio::join!(
+            prepare_schema(&a, MigrationMode::Run, timeout),
+            prepare_schema(&b, MigrationMode::Run, timeout),
+            prepare_schema(&c, MigrationMode::Require, timeout),
+        );
+        ra.unwrap();
+        rb.unwrap();
+        rc.unwrap();
+        assert_eq!(schema_version(&a).await.unwrap(), Some(expected_schema_version()));
+        let applied: i64 = sqlx::query_scalar("select count(*) from _sqlx_migrations").fetch_one(&pool).await.unwrap();
+        assert_eq!(applied as usize, MIGRATOR.iter().count());
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = false)]
+    async fn test_waiting_replicas_give_up_after_the_timeout(pool: sqlx::PgPool) {
+        let db = replica(&pool).await;
+        // Someone else is mid-migration and stays there
+        let mut holder = PgConnection::connect_with(&pool.connect_options()).await.unwrap();
+        assert!(try_lock(&mut holder, MIGRATION_LOCK).await.unwrap());
+
+        let err = migrate_with_lock(&db, Duration::from_secs(1)).await.unwrap_err();
+        assert!(err.to_string().contains("migration lock"), "{}", err);
+        let err = prepare_schema(&db, MigrationMode::Require, Duration::from_secs(1)).await.unwrap_err();
+        assert!(err.to_string().contains("schema still at None"), "{}", err);
+        prepare_schema(&db, MigrationMode::Skip, Duration::ZERO).await.unwrap();
+
+        holder.close().await.unwrap();
+        migrate_with_lock(&db, Duration::from_secs(30)).await.unwrap();
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = false)]
+    async fn test_one_replica_leads_and_another_takes_over(pool: sqlx::PgPool) {
+        let (a, b) = (replica(&pool).await, replica(&pool).await);
+        let (lead_a, lead_b) = (Leadership::new("test task", 42), Leadership::new("test task", 42));
+        let (first, second) = tokio::join!(lead_a.acquire(&a), lead_b.acquire(&b));
+        assert!(first ^ second, "exactly one replica leads");
+        let (leader, standby, standby_db) = if first { (lead_a, lead_b, b) } else { (lead_b, lead_a, a) };
+        assert!(!standby.acquire(&standby_db).await);
+
+        // The leader going away frees the lock for the next cycle elsewhere
+        drop(leader);
+        assert!(standby.acquire(&standby_db).await);
+        assert!(standby.acquire(&standby_db).await, "a held lock stays held");
+    }
+}
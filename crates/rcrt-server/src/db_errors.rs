@@ -0,0 +1,92 @@
+//! DbError to HTTP
+//! The one place a `rcrt_core::error::DbError` becomes a status, so handlers never match on message text
+
+use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use rcrt_core::error::DbError;
+use rcrt_core::models::VersionMismatch;
+use serde_json::{json, Value};
+
+fn status(e: &DbError) -> StatusCode {
+    match e {
+        DbError::NotFound(_) => StatusCode::NOT_FOUND,
+        DbError::VersionMismatch(_) => StatusCode::PRECONDITION_FAILED,
+        DbError::Forbidden(_) => StatusCode::FORBIDDEN,
+        DbError::Conflict(_) => StatusCode::CONFLICT,
+        DbError::Invalid(_) => StatusCode::BAD_REQUEST,
+        DbError::Sqlx(_) | DbError::Serde(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// For handlers with `(StatusCode, String)` errors
+pub(crate) fn db_error(e: DbError) -> (StatusCode, String) {
+    (status(&e), e.to_string())
+}
+
+/// Like `db_error`, but a stale If-Match gets the 412 body with the current version (and
+/// context if asked) so the client can rebase without another GET
+pub(crate) fn db_error_response(e: DbError, return_current: bool) -> Response {
+    match e {
+        DbError::VersionMismatch(mismatch) => {
+            (StatusCode::PRECONDITION_FAILED, Json(version_mismatch_body(&mismatch, return_current))).into_response()
+        }
+        e => db_error(e).into_response(),
+    }
+}
+
+fn version_mismatch_body(mismatch: &VersionMismatch, return_current: bool) -> Value {
+    let mut body = json!({
+        "error": "version_mismatch",
+        "expected_version": mismatch.expected_version,
+        "current_version": mismatch.current_version,
+        "updated_at": mismatch.updated_at,
+        "updated_by": mismatch.updated_by,
+    });
+    if return_current {
+        body["context"] = mismatch.context.clone();
+    }
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mismatch() -> DbError {
+        DbError::VersionMismatch(VersionMismatch {
+            expected_version: 3,
+            current_version: 5,
+            updated_at: chrono::Utc::now(),
+            updated_by: None,
+            context: json!({ "step": 5 }),
+        })
+    }
+
+    async fn json_body(res: Response) -> Value {
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_version_mismatch_is_412_with_versions() {
+        let res = db_error_response(mismatch(), false);
+        assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+        let body = json_body(res).await;
+        assert_eq!(body["error"], "version_mismatch");
+        assert_eq!((body["expected_version"].as_i64(), body["current_version"].as_i64()), (Some(3), Some(5)));
+        assert!(body.get("context").is_none());
+
+        let body = json_body(db_error_response(mismatch(), true)).await;
+        assert_eq!(body["context"]["step"], 5);
+        assert_eq!(db_error(mismatch()), (StatusCode::PRECONDITION_FAILED, "version_mismatch".to_string()));
+    }
+
+    #[test]
+    fn test_statuses() {
+        assert_eq!(db_error(DbError::NotFound("breadcrumb".into())).0, StatusCode::NOT_FOUND);
+        assert_eq!(db_error(DbError::Conflict("duplicate key".into())).0, StatusCode::CONFLICT);
+        assert_eq!(db_error(DbError::Forbidden("rls".into())).0, StatusCode::FORBIDDEN);
+        assert_eq!(db_error(DbError::Invalid("no schema".into())).0, StatusCode::BAD_REQUEST);
+        assert_eq!(db_error(sqlx::Error::PoolTimedOut.into()).0, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(db_error(sqlx::Error::RowNotFound.into()).0, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,53 @@
+//! API Docs
+//! The OpenAPI spec and the Redoc/Swagger pages that render it
+
+use axum::response::{Html, IntoResponse};
+
+pub async fn openapi_spec() -> impl IntoResponse {
+    let spec = include_str!("../../../docs/openapi.json");
+    (
+        axum::http::StatusCode::OK,
+        [("content-type", "application/json")],
+        spec,
+    )
+}
+
+pub async fn docs_page() -> Html<&'static str> {
+    Html(r#"<!doctype html>
+<html>
+  <head>
+    <meta charset="utf-8"/>
+    <title>RCRT API Docs</title>
+    <meta name="viewport" content="width=device-width, initial-scale=1">
+    <style>body { margin: 0; padding: 0; }</style>
+    <script src="https://cdn.redoc.ly/redoc/latest/bundles/redoc.standalone.js"></script>
+  </head>
+  <body>
+    <redoc spec-url="/openapi.json"></redoc>
+  </body>
+</html>"#)
+}
+
+pub async fn swagger_page() -> Html<&'static str> {
+    Html(r#"<!doctype html>
+<html>
+  <head>
+    <meta charset="utf-8"/>
+    <title>RCRT Swagger</title>
+    <meta name="viewport" content="width=device-width, initial-scale=1">
+    <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css" />
+  </head>
+  <body>
+    <div id="swagger"></div>
+    <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+    <script>
+      window.ui = SwaggerUIBundle({
+        url: '/openapi.json',
+        dom_id: '#swagger',
+        presets: [SwaggerUIBundle.presets.apis],
+        layout: 'BaseLayout'
+      });
+    </script>
+  </body>
+</html>"#)
+}
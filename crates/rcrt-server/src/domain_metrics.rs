@@ -0,0 +1,200 @@
+//! Domain Metrics
+//! Breadcrumb operation, embedding and vector search metrics, labeled by schema and (optionally) owner
+
+use std::collections::HashSet;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use prometheus::{Histogram, HistogramTimer, HistogramVec, IntCounterVec, IntGaugeVec, register_histogram, register_histogram_vec, register_int_counter_vec, register_int_gauge_vec};
+use rcrt_core::db::Db;
+use uuid::Uuid;
+
+/// Schemas tracked by name when METRICS_SCHEMA_ALLOWLIST is unset; anything else is "other"
+const DEFAULT_SCHEMAS: &[&str] = &[
+    "user.message.v1",
+    "agent.response.v1",
+    "agent.context.v1",
+    "agent.def.v1",
+    "tool.request.v1",
+    "tool.response.v1",
+    "tool.catalog.v1",
+    "context.config.v1",
+    "browser.tab.context.v1",
+    "session.closed.v1",
+    "system.hygiene.v1",
+];
+
+static SCHEMA_ALLOWLIST: OnceLock<HashSet<String>> = OnceLock::new();
+static OWNER_LABELS: OnceLock<bool> = OnceLock::new();
+static BC_OPS: OnceLock<IntCounterVec> = OnceLock::new();
+static BC_OP_DURATION: OnceLock<HistogramVec> = OnceLock::new();
+static BC_SIZE: OnceLock<HistogramVec> = OnceLock::new();
+static EMBED_DURATION: OnceLock<HistogramVec> = OnceLock::new();
+static SEARCH_DURATION: OnceLock<Histogram> = OnceLock::new();
+static STORED_COUNT: OnceLock<IntGaugeVec> = OnceLock::new();
+static STORED_BYTES: OnceLock<IntGaugeVec> = OnceLock::new();
+static INBOX_DEPTH: OnceLock<IntGaugeVec> = OnceLock::new();
+
+fn parse_allowlist(raw: &str) -> HashSet<String> {
+    raw.split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string).collect()
+}
+
+fn schema_allowlist() -> &'static HashSet<String> {
+    SCHEMA_ALLOWLIST.get_or_init(|| match std::env::var("METRICS_SCHEMA_ALLOWLIST") {
+        Ok(raw) => parse_allowlist(&raw),
+        Err(_) => DEFAULT_SCHEMAS.iter().map(|s| s.to_string()).collect(),
+    })
+}
+
+fn schema_label_in<'a>(allowlist: &HashSet<String>, schema: Option<&'a str>) -> &'a str {
+    match schema {
+        None => "none",
+        Some(s) if allowlist.contains(s) => s,
+        Some(_) => "other",
+    }
+}
+
+/// Schema label with a cardinality guard: only allowlisted schemas keep their name
+pub fn schema_label(schema: Option<&str>) -> &str {
+    schema_label_in(schema_allowlist(), schema)
+}
+
+fn owner_labels_enabled() -> bool {
+    *OWNER_LABELS.get_or_init(|| {
+        std::env::var("METRICS_OWNER_LABELS").map(|v| v == "true" || v == "1").unwrap_or(false)
+    })
+}
+
+/// Owner label; collapsed to "all" unless METRICS_OWNER_LABELS is set, since tenants are unbounded
+pub fn owner_label(owner_id: Uuid) -> String {
+    if owner_labels_enabled() { owner_id.to_string() } else { "all".to_string() }
+}
+
+fn ops() -> &'static IntCounterVec {
+    BC_OPS.get_or_init(|| register_int_counter_vec!("breadcrumb_ops_total", "Breadcrumb writes by operation, schema and owner", &["op", "schema", "owner"]).unwrap())
+}
+
+fn op_duration() -> &'static HistogramVec {
+    BC_OP_DURATION.get_or_init(|| register_histogram_vec!(
+        "breadcrumb_op_duration_seconds", "Database time for breadcrumb writes", &["op", "schema"],
+        vec![0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5]
+    ).unwrap())
+}
+
+fn size() -> &'static HistogramVec {
+    BC_SIZE.get_or_init(|| register_histogram_vec!(
+        "breadcrumb_size_bytes", "Context size of created and updated breadcrumbs", &["schema"],
+        prometheus::exponential_buckets(256.0, 4.0, 8).unwrap()
+    ).unwrap())
+}
+
+fn embed_duration() -> &'static HistogramVec {
+    EMBED_DURATION.get_or_init(|| register_histogram_vec!(
+        "embedding_duration_seconds", "Time to embed text, by caller", &["source"],
+        vec![0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5]
+    ).unwrap())
+}
+
+fn search_duration() -> &'static Histogram {
+    SEARCH_DURATION.get_or_init(|| register_histogram!(
+        "vector_search_duration_seconds", "Database time for pgvector nearest-neighbour queries",
+        vec![0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5]
+    ).unwrap())
+}
+
+fn stored_count() -> &'static IntGaugeVec {
+    STORED_COUNT.get_or_init(|| register_int_gauge_vec!("breadcrumbs_stored", "Stored breadcrumbs per owner (sampled)", &["owner"]).unwrap())
+}
+
+fn stored_bytes() -> &'static IntGaugeVec {
+    STORED_BYTES.get_or_init(|| register_int_gauge_vec!("breadcrumbs_stored_bytes", "Stored breadcrumb context bytes per owner (sampled)", &["owner"]).unwrap())
+}
+
+fn inbox_depth() -> &'static IntGaugeVec {
+    INBOX_DEPTH.get_or_init(|| register_int_gauge_vec!("agent_inbox_depth", "Unacknowledged inbox entries per agent (sampled)", &["agent"]).unwrap())
+}
+
+/// Record a successful create/update/delete; `size_bytes` is None for deletes
+pub fn record_op(op: &str, schema: Option<&str>, owner_id: Uuid, started: Instant, size_bytes: Option<i32>) {
+    let schema = schema_label(schema);
+    ops().with_label_values(&[op, schema, &owner_label(owner_id)]).inc();
+    op_duration().with_label_values(&[op, schema]).observe(started.elapsed().as_secs_f64());
+    if let Some(bytes) = size_bytes {
+        size().with_label_values(&[schema]).observe(bytes as f64);
+    }
+}
+
+/// Observes on drop; `source` is "ingest", "query" or "backfill"
+pub fn embedding_timer(source: &str) -> HistogramTimer {
+    embed_duration().with_label_values(&[source]).start_timer()
+}
+
+/// Observes on drop
+pub fn vector_search_timer() -> HistogramTimer {
+    search_duration().start_timer()
+}
+
+/// Periodically refresh the per-owner stored count/size gauges and the per-agent inbox depths
+pub fn start_sampler(db: Db) -> tokio::task::JoinHandle<()> {
+    let secs = std::env::var("METRICS_SAMPLE_INTERVAL_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(60u64);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(secs.max(1)));
+        loop {
+            interval.tick().await;
+            if let Err(e) = sample(&db).await {
+                tracing::warn!("📊 Metrics sampler failed: {}", e);
+            }
+        }
+    })
+}
+
+async fn sample(db: &Db) -> Result<(), sqlx::Error> {
+    let rows: Vec<(Option<Uuid>, i64, i64)> = if owner_labels_enabled() {
+        sqlx::query_as("select owner_id, count(*), coalesce(sum(size_bytes), 0)::bigint from breadcrumbs group by owner_id")
+            .fetch_all(&db.pool)
+            .await?
+    } else {
+        sqlx::query_as("select null::uuid, count(*), coalesce(sum(size_bytes), 0)::bigint from breadcrumbs")
+            .fetch_all(&db.pool)
+            .await?
+    };
+    // Reset so owners whose breadcrumbs are all gone stop reporting
+    stored_count().reset();
+    stored_bytes().reset();
+    for (owner, count, bytes) in rows {
+        let label = owner.map(|o| o.to_string()).unwrap_or_else(|| "all".to_string());
+        stored_count().with_label_values(&[&label]).set(count);
+        stored_bytes().with_label_values(&[&label]).set(bytes);
+    }
+
+    // Only agents with something pending, so drained inboxes drop out
+    let depths: Vec<(Uuid, i64)> = sqlx::query_as("select agent_id, count(*) from agent_inbox where acked_at is null group by agent_id")
+        .fetch_all(&db.pool)
+        .await?;
+    inbox_depth().reset();
+    for (agent, depth) in depths {
+        inbox_depth().with_label_values(&[&agent.to_string()]).set(depth);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unlisted_schemas_collapse_to_other() {
+        let allow = parse_allowlist("user.message.v1, browser.tab.context.v1,,");
+        assert_eq!(allow.len(), 2);
+        assert_eq!(schema_label_in(&allow, Some("browser.tab.context.v1")), "browser.tab.context.v1");
+        assert_eq!(schema_label_in(&allow, Some("custom.thing.v7")), "other");
+        assert_eq!(schema_label_in(&allow, None), "none");
+    }
+
+    #[test]
+    fn test_record_op_uses_guarded_labels() {
+        let owner = Uuid::new_v4();
+        record_op("create", Some("never.listed.v1"), owner, Instant::now(), Some(512));
+        let count = ops().with_label_values(&["create", "other", &owner_label(owner)]).get();
+        assert!(count >= 1);
+    }
+}
@@ -0,0 +1,182 @@
+//! Embedding
+//! ONNX sentence embeddings for ingest and vector search (feature `embed-onnx`), from the column model
+//! and, during an embedding model migration, the target model
+
+use std::sync::OnceLock;
+#[cfg(feature = "embed-onnx")]
+use std::sync::Mutex;
+#[cfg(feature = "embed-onnx")]
+use ort::{session::Session, value::Value, inputs};
+use rcrt_core::embedding_text::{embedding_text, EmbeddingTextConfig};
+use rcrt_core::models::COLUMN_EMBEDDING_MODEL;
+#[cfg(feature = "embed-onnx")]
+use tokenizers::{Tokenizer, TruncationParams};
+// no ndarray tensors needed in embed path
+
+/// Which context strings get embedded (EMBED_MIN_STRING_LEN, EMBED_SKIP_KEYS), read once
+pub fn text_config() -> &'static EmbeddingTextConfig {
+    static CONFIG: OnceLock<EmbeddingTextConfig> = OnceLock::new();
+    CONFIG.get_or_init(EmbeddingTextConfig::from_env)
+}
+
+/// Text to embed for a search query or a title on its own, under the same string rules as ingest
+pub fn standalone_text(text: &str) -> String {
+    embedding_text(text, &serde_json::Value::Null, None, text_config())
+}
+
+/// Turns text into a vector; search embeds its queries through this, so tests can count or fake the calls
+pub trait Embedder: Send + Sync {
+    fn embed(&self, text: String) -> Result<Vec<f32>, String>;
+}
+
+/// The ONNX model behind `embed_text`; always errors without `embed-onnx`
+pub struct OnnxEmbedder;
+
+impl Embedder for OnnxEmbedder {
+    fn embed(&self, text: String) -> Result<Vec<f32>, String> {
+        embed_text(text)
+    }
+}
+
+/// Load the tokenizer and model with a throwaway input, so the first search doesn't pay for it
+pub fn warm_up() {
+    match embed_text(standalone_text("warm-up")) {
+        Ok(_) => tracing::info!("Embedding model loaded"),
+        Err(e) => tracing::debug!("Embedding warm-up skipped: {}", e),
+    }
+}
+
+/// One ONNX model and its tokenizer, loaded on first use
+#[cfg_attr(not(feature = "embed-onnx"), allow(dead_code))]
+pub struct OnnxModel {
+    /// What breadcrumb_embeddings.model and system.embedding.config.v1 call it
+    pub name: String,
+    model_path: String,
+    tokenizer_path: String,
+    dim: usize,
+    max_tokens: usize,
+    #[cfg(feature = "embed-onnx")]
+    tokenizer: OnceLock<Tokenizer>,
+    #[cfg(feature = "embed-onnx")]
+    session: OnceLock<Mutex<Session>>,
+}
+
+impl OnnxModel {
+    /// `{prefix}_MODEL`, `{prefix}_TOKENIZER`, `{prefix}_DIM` and `{prefix}_MAX_TOKENS`
+    fn from_env(name: String, prefix: &str, model_path: &str, tokenizer_path: &str, dim: usize) -> Self {
+        let var = |suffix: &str| std::env::var(format!("{}_{}", prefix, suffix)).ok();
+        OnnxModel {
+            name,
+            model_path: var("MODEL").unwrap_or_else(|| model_path.into()),
+            tokenizer_path: var("TOKENIZER").unwrap_or_else(|| tokenizer_path.into()),
+            dim: var("DIM").and_then(|s| s.parse().ok()).unwrap_or(dim),
+            max_tokens: var("MAX_TOKENS").and_then(|s| s.parse().ok()).unwrap_or(256),
+            #[cfg(feature = "embed-onnx")]
+            tokenizer: OnceLock::new(),
+            #[cfg(feature = "embed-onnx")]
+            session: OnceLock::new(),
+        }
+    }
+}
+
+impl Embedder for &'static OnnxModel {
+    fn embed(&self, text: String) -> Result<Vec<f32>, String> {
+        tracing::info_span!("embedding", model = %self.name).in_scope(|| self.embed_text(text))
+    }
+}
+
+/// The model behind breadcrumbs.embedding: EMBED_MODEL, EMBED_TOKENIZER, EMBED_DIM (384) and EMBED_MAX_TOKENS
+fn column_model() -> &'static OnnxModel {
+    static MODEL: OnceLock<OnnxModel> = OnceLock::new();
+    MODEL.get_or_init(|| OnnxModel::from_env(COLUMN_EMBEDDING_MODEL.into(), "EMBED", "models/model.onnx", "models/tokenizer.json", 384))
+}
+
+/// The model being migrated to, when EMBED_TARGET_MODEL_NAME is set: EMBED_TARGET_MODEL,
+/// EMBED_TARGET_TOKENIZER, EMBED_TARGET_DIM (768) and EMBED_TARGET_MAX_TOKENS
+pub fn target_model() -> Option<&'static OnnxModel> {
+    static MODEL: OnceLock<Option<OnnxModel>> = OnceLock::new();
+    MODEL.get_or_init(|| {
+        let name = std::env::var("EMBED_TARGET_MODEL_NAME").ok().filter(|n| !n.is_empty())?;
+        Some(OnnxModel::from_env(name, "EMBED_TARGET", "models/target/model.onnx", "models/target/tokenizer.json", 768))
+    }).as_ref()
+}
+
+pub fn embed_text(text: String) -> Result<Vec<f32>, String> {
+    column_model().embed(text)
+}
+
+#[cfg(feature = "embed-onnx")]
+impl OnnxModel {
+    fn embed_text(&self, text: String) -> Result<Vec<f32>, String> {
+        let tok = self.tokenizer.get_or_init(|| {
+            let mut tok = Tokenizer::from_file(&self.tokenizer_path).expect("load tokenizer");
+            // Cut at the model's max sequence length in tokens; longer inputs skew or break the pooled vector
+            tok.with_truncation(Some(TruncationParams { max_length: self.max_tokens, ..Default::default() })).expect("tokenizer truncation");
+            tok
+        });
+        let session = self.session.get_or_init(|| {
+            Mutex::new(Session::builder().unwrap().commit_from_file(&self.model_path).unwrap())
+        });
+        let encoding = tok.encode(text, true).map_err(|e| e.to_string())?;
+        if !encoding.get_overflowing().is_empty() {
+            tracing::debug!("embed_text input truncated to {} tokens", encoding.get_ids().len());
+        }
+        let ids = encoding.get_ids();
+        let ids_vec: Vec<i64> = ids.iter().map(|&x| x as i64).collect();
+        let shape: Vec<usize> = vec![1, ids.len()];
+        let mask_vec: Vec<i64> = vec![1i64; ids.len()];
+        let seg_vec: Vec<i64> = vec![0i64; ids.len()];
+
+        // Try with common BERT-style inputs first; fall back to input_ids only if model rejects extra inputs
+        let try_run = |with_all: bool| -> Result<Vec<f32>, String> {
+            let mut guard = session.lock().unwrap();
+            let outputs = if with_all {
+                let inp = inputs!{
+                    "input_ids" => Value::from_array((shape.clone(), ids_vec.clone())).map_err(|e| e.to_string())?,
+                    "attention_mask" => Value::from_array((shape.clone(), mask_vec.clone())).map_err(|e| e.to_string())?,
+                    "token_type_ids" => Value::from_array((shape.clone(), seg_vec.clone())).map_err(|e| e.to_string())?
+                };
+                guard.run(inp).map_err(|e| e.to_string())?
+            } else {
+                let inp = inputs!{
+                    "input_ids" => Value::from_array((shape.clone(), ids_vec.clone())).map_err(|e| e.to_string())?
+                };
+                guard.run(inp).map_err(|e| e.to_string())?
+            };
+            let (_shape, data) = outputs[0]
+                .try_extract_tensor::<f32>()
+                .map_err(|_| "embedding output not a float tensor".to_string())?;
+            let hidden = self.dim;
+            if data.len() == hidden {
+                Ok(data.to_vec())
+            } else {
+                let mut acc = vec![0f32; hidden];
+                let mut count: usize = 0;
+                for chunk in data.chunks_exact(hidden) {
+                    for i in 0..hidden { acc[i] += chunk[i]; }
+                    count += 1;
+                }
+                if count > 0 { for i in 0..hidden { acc[i] /= count as f32; } }
+                Ok(acc)
+            }
+        };
+        let vec = match try_run(true) {
+            Ok(v) => v,
+            Err(e) => {
+                // Retry with minimal inputs for models that don't expect mask/segment
+                tracing::warn!("embed_text run with all inputs failed, retrying with input_ids only: {}", e);
+                try_run(false)?
+            }
+        };
+        // L2 normalize
+        let norm: f32 = vec.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 { Ok(vec.into_iter().map(|x| x / norm).collect()) } else { Ok(vec) }
+    }
+}
+
+#[cfg(not(feature = "embed-onnx"))]
+impl OnnxModel {
+    fn embed_text(&self, _text: String) -> Result<Vec<f32>, String> {
+        Err("embedding disabled".into())
+    }
+}
@@ -0,0 +1,188 @@
+//! Embedding Model Migration
+//! Which model an owner's search ranks by (its newest system.embedding.config.v1), target model vectors
+//! stored next to the column model's on ingest, and GET /admin/embeddings/models and
+//! POST /admin/embeddings/cutover, which switches the active model once the target covers enough rows
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use axum::{extract::State, http::StatusCode, response::{IntoResponse, Response}, Json};
+use rcrt_core::db::Db;
+use rcrt_core::models::{BreadcrumbCreate, EmbeddingModelConfig, Sensitivity, Visibility, COLUMN_EMBEDDING_MODEL, EMBEDDING_CONFIG_SCHEMA};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::auth::{require_admin, AuthContext};
+use crate::db_errors::db_error;
+use crate::embedding::{self, Embedder};
+use crate::events::publish_breadcrumb_created;
+use crate::{domain_metrics, AppState};
+
+/// How long an owner's active model is served from cache without a cutover on this instance
+const CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// The model being migrated to; everything the column model embeds is also embedded with it
+pub struct TargetModel {
+    pub name: String,
+    pub embedder: Arc<dyn Embedder>,
+}
+
+impl TargetModel {
+    /// EMBED_TARGET_MODEL_NAME and its model files, see embedding::target_model
+    pub fn from_env() -> Option<Self> {
+        embedding::target_model().map(|model| TargetModel { name: model.name.clone(), embedder: Arc::new(model) })
+    }
+}
+
+/// Each owner's active model
+pub struct ActiveModels {
+    db: Db,
+    tenants: RwLock<HashMap<Uuid, (Arc<EmbeddingModelConfig>, Instant)>>,
+}
+
+impl ActiveModels {
+    pub fn new(db: Db) -> Self {
+        Self { db, tenants: RwLock::new(HashMap::new()) }
+    }
+
+    /// Falls back to the column model if the owner's config can't be read
+    pub async fn active(&self, owner_id: Uuid) -> Arc<EmbeddingModelConfig> {
+        if let Some((config, at)) = self.tenants.read().await.get(&owner_id) {
+            if at.elapsed() < CACHE_TTL {
+                return config.clone();
+            }
+        }
+        let config = match load(&self.db, owner_id).await {
+            Ok(config) => Arc::new(config),
+            Err(e) => {
+                // Don't cache failures; the next search retries
+                tracing::warn!("Failed to load the embedding model config of {}: {}", owner_id, e);
+                return Arc::new(EmbeddingModelConfig::default());
+            }
+        };
+        self.tenants.write().await.insert(owner_id, (config.clone(), Instant::now()));
+        config
+    }
+
+    pub async fn invalidate(&self, owner_id: Uuid) {
+        self.tenants.write().await.remove(&owner_id);
+    }
+}
+
+/// The newest valid system.embedding.config.v1 of the owner, or the column model
+async fn load(db: &Db, owner_id: Uuid) -> Result<EmbeddingModelConfig, sqlx::Error> {
+    // Raw pool like the hygiene configs; the query filters on owner_id itself
+    let rows = sqlx::query_as::<_, (Uuid, Value)>(
+        "select id, context from breadcrumbs where owner_id = $1 and schema_name = $2 order by updated_at desc"
+    )
+    .bind(owner_id)
+    .bind(EMBEDDING_CONFIG_SCHEMA)
+    .fetch_all(&db.pool)
+    .await?;
+    for (id, context) in rows {
+        match EmbeddingModelConfig::from_context(&context) {
+            Ok(config) => return Ok(config),
+            Err(e) => tracing::warn!("Ignoring invalid {} breadcrumb {}: {}", EMBEDDING_CONFIG_SCHEMA, id, e),
+        }
+    }
+    Ok(EmbeddingModelConfig::default())
+}
+
+/// After a create or upsert stored the column model's vector of `input`: embed it with the target model
+/// too, so new rows don't widen the gap the backfill closes. A failure leaves the row to the backfill
+pub async fn embed_target(state: &AppState, auth: &AuthContext, id: Uuid, input: String) {
+    let Some(target) = state.target_model.as_ref() else { return };
+    let vector = {
+        let _timer = domain_metrics::embedding_timer("ingest");
+        target.embedder.embed(input)
+    };
+    let stored = match vector {
+        Ok(v) => state.db.set_breadcrumb_model_embedding(auth.owner_id, Some(auth.agent_id), id, &target.name, v).await.map_err(|e| e.to_string()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = stored {
+        tracing::warn!("Embedding {} with target model {} failed: {}", id, target.name, e);
+    }
+}
+
+/// GET /admin/embeddings/models: the active and target models, and how far each model's vectors
+/// cover the owner's embedded breadcrumbs
+pub async fn list_models(State(state): State<AppState>, auth: AuthContext) -> Result<Json<Value>, (StatusCode, String)> {
+    require_admin(&state, &auth)?;
+    let active = state.active_models.active(auth.owner_id).await;
+    let mut names: BTreeSet<String> = state.db.list_embedding_models(auth.owner_id).await.map_err(db_error)?.into_iter().collect();
+    names.insert(active.active_model.clone());
+    names.extend(state.target_model.as_ref().map(|t| t.name.clone()));
+    let mut models = Vec::new();
+    for name in &names {
+        models.push(state.db.embedding_coverage(auth.owner_id, name).await.map_err(db_error)?);
+    }
+    Ok(Json(json!({
+        "active_model": active.active_model,
+        "column_model": COLUMN_EMBEDDING_MODEL,
+        "target_model": state.target_model.as_ref().map(|t| &t.name),
+        "min_coverage": state.embed_cutover_min_coverage,
+        "models": models,
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct CutoverReq {
+    model: String,
+    /// Overrides EMBED_CUTOVER_MIN_COVERAGE for this cutover, 0..=1
+    min_coverage: Option<f64>,
+}
+
+/// POST /admin/embeddings/cutover: make `model` the owner's active model by writing a new
+/// system.embedding.config.v1, which the context-builder follows too. Only the column model and the
+/// configured target can be made active, since search must embed its queries with it; 409 while the
+/// model's coverage is below the threshold
+pub async fn cutover(State(state): State<AppState>, auth: AuthContext, Json(req): Json<CutoverReq>) -> Result<Json<Value>, Response> {
+    require_admin(&state, &auth).map_err(IntoResponse::into_response)?;
+    let reject = |status: StatusCode, message: String| (status, message).into_response();
+    if !EmbeddingModelConfig::valid_model_name(&req.model) {
+        return Err(reject(StatusCode::BAD_REQUEST, format!("model {:?} may only use letters, digits and .-_:/@ (up to 100)", req.model)));
+    }
+    if req.model != COLUMN_EMBEDDING_MODEL && state.target_model.as_ref().map(|t| t.name.as_str()) != Some(req.model.as_str()) {
+        return Err(reject(StatusCode::CONFLICT, format!("{} is neither the column model nor EMBED_TARGET_MODEL_NAME; search couldn't embed queries with it", req.model)));
+    }
+    let min_coverage = req.min_coverage.unwrap_or(state.embed_cutover_min_coverage);
+    if !(0.0..=1.0).contains(&min_coverage) {
+        return Err(reject(StatusCode::BAD_REQUEST, "min_coverage must be between 0 and 1".into()));
+    }
+    let previous = state.active_models.active(auth.owner_id).await;
+    let coverage = state.db.embedding_coverage(auth.owner_id, &req.model).await.map_err(|e| db_error(e).into_response())?;
+    if coverage.ratio < min_coverage {
+        return Err((StatusCode::CONFLICT, Json(json!({
+            "error": "insufficient_coverage",
+            "min_coverage": min_coverage,
+            "coverage": coverage,
+        }))).into_response());
+    }
+
+    let config = EmbeddingModelConfig { active_model: req.model };
+    let bc = state.db.create_breadcrumb_for(auth.owner_id, Some(auth.agent_id), Some(auth.agent_id), BreadcrumbCreate {
+        title: format!("Embedding model: {}", config.active_model),
+        description: None, semantic_version: None,
+        context: json!(config),
+        tags: vec!["system:embedding-config".into()],
+        schema_name: Some(EMBEDDING_CONFIG_SCHEMA.into()),
+        llm_hints: None,
+        visibility: Some(Visibility::Team), sensitivity: Some(Sensitivity::Low),
+        ttl: None, ttl_type: None, ttl_config: None, ttl_source: None, entity_keywords: None, entities: None,
+    }).await.map_err(|e| db_error(e).into_response())?;
+    state.active_models.invalidate(auth.owner_id).await;
+    // The context-builder reloads its model when it sees the new config
+    publish_breadcrumb_created(&state, auth.owner_id, &bc).await;
+    tracing::info!("🔀 Embedding model of {} switched from {} to {} by {} (coverage {:.3})", auth.owner_id, previous.active_model, config.active_model, auth.agent_id, coverage.ratio);
+
+    Ok(Json(json!({
+        "ok": true,
+        "active_model": config.active_model,
+        "previous_model": previous.active_model,
+        "coverage": coverage,
+        "config_id": bc.id,
+    })))
+}
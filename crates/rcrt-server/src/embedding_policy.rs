@@ -1,5 +1,7 @@
 //! Embedding Policy
-//! Determines which breadcrumb schemas should have embeddings
+//! Determines which breadcrumb schemas, and how sensitive a breadcrumb, should have embeddings
+
+use rcrt_core::models::Sensitivity;
 
 /// Check if a schema should have embeddings for vector search
 pub fn should_embed_schema(schema: Option<&str>) -> bool {
@@ -28,6 +30,12 @@ pub fn should_embed_schema(schema: Option<&str>) -> bool {
     }
 }
 
+/// EMBED_SENSITIVITY_MAX: rows above it are never embedded, so a vector (or a title surfaced by
+/// similarity) can't leak them; no sensitivity means low
+pub fn within_sensitivity(sensitivity: Option<&Sensitivity>, max: &Sensitivity) -> bool {
+    sensitivity.unwrap_or(&Sensitivity::Low) <= max
+}
+
 /// Get embedding or fallback
 pub fn get_or_fallback_embedding(
     text: String,
@@ -39,7 +47,8 @@ pub fn get_or_fallback_embedding(
     }
     
     // Try to embed
-    match super::embed_text(text) {
+    let _timer = crate::domain_metrics::embedding_timer("ingest");
+    match crate::embedding::embed_text(text) {
         Ok(vec) => Some(vec),
         Err(e) => {
             tracing::warn!("Embedding failed for schema {:?}: {}. Using zero vector.", schema, e);
@@ -66,6 +75,14 @@ mod tests {
         assert!(!should_embed_schema(Some("system.metrics.v1")));
     }
     
+    #[test]
+    fn test_sensitivity_limit() {
+        assert!(within_sensitivity(None, &Sensitivity::Low));
+        assert!(within_sensitivity(Some(&Sensitivity::Pii), &Sensitivity::Pii));
+        assert!(!within_sensitivity(Some(&Sensitivity::Secret), &Sensitivity::Pii));
+        assert!(within_sensitivity(Some(&Sensitivity::Secret), &Sensitivity::Secret));
+    }
+    
     #[test]
     fn test_unknown_schemas_default_to_embed() {
         assert!(should_embed_schema(Some("custom.schema.v1")));
@@ -0,0 +1,94 @@
+//! Envelope Encryption
+//! AES-256-GCM under a fresh DEK, the DEK wrapped by the local KEK (LOCAL_KEK_BASE64) with
+//! XChaCha20-Poly1305. Both blobs carry their nonce as a prefix. Used by secrets and by
+//! encrypted breadcrumb contexts
+
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use aes_gcm::aead::{Aead, KeyInit, OsRng, rand_core::RngCore};
+use axum::http::StatusCode;
+use base64::Engine;
+use chacha20poly1305::{XChaCha20Poly1305, Key as XKey, XNonce};
+use rcrt_core::models::EncryptedContext;
+use serde_json::Value;
+
+use crate::internal_error;
+
+/// `kek_id` recorded with encrypted contexts, so a KEK rotation knows which DEKs to re-wrap
+pub const LOCAL_KEK_ID: &str = "local-kek";
+
+const AES_NONCE_LEN: usize = 12;
+const XCHACHA_NONCE_LEN: usize = 24;
+
+/// The 32-byte KEK from LOCAL_KEK_BASE64
+pub fn local_kek() -> Result<Vec<u8>, (StatusCode, String)> {
+    let kek_b64 = std::env::var("LOCAL_KEK_BASE64").map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "LOCAL_KEK_BASE64 missing".to_string()))?;
+    let kek = base64::engine::general_purpose::STANDARD.decode(kek_b64).map_err(internal_error)?;
+    if kek.len() != 32 {
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("LOCAL_KEK_BASE64 must decode to 32 bytes, got {}", kek.len())));
+    }
+    Ok(kek)
+}
+
+/// `(enc_blob, dek_encrypted)` for `plaintext` under a new DEK
+pub fn seal(kek: &[u8], plaintext: &[u8]) -> Result<(Vec<u8>, Vec<u8>), (StatusCode, String)> {
+    let dek = rand::random::<[u8; 32]>();
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&dek));
+    let mut nonce_bytes = [0u8; AES_NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce_bytes);
+    let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce_bytes), plaintext).map_err(internal_error)?;
+    let mut enc_blob = Vec::with_capacity(AES_NONCE_LEN + ciphertext.len());
+    enc_blob.extend_from_slice(&nonce_bytes);
+    enc_blob.extend_from_slice(&ciphertext);
+
+    let x = XChaCha20Poly1305::new(XKey::from_slice(kek));
+    let mut xnonce_bytes = [0u8; XCHACHA_NONCE_LEN];
+    OsRng.fill_bytes(&mut xnonce_bytes);
+    let dek_ct = x.encrypt(XNonce::from_slice(&xnonce_bytes), dek.as_slice()).map_err(internal_error)?;
+    let mut dek_encrypted = Vec::with_capacity(XCHACHA_NONCE_LEN + dek_ct.len());
+    dek_encrypted.extend_from_slice(&xnonce_bytes);
+    dek_encrypted.extend_from_slice(&dek_ct);
+    Ok((enc_blob, dek_encrypted))
+}
+
+/// The plaintext behind `seal`'s output
+pub fn open(kek: &[u8], enc_blob: &[u8], dek_encrypted: &[u8]) -> Result<Vec<u8>, (StatusCode, String)> {
+    if enc_blob.len() < AES_NONCE_LEN || dek_encrypted.len() < XCHACHA_NONCE_LEN {
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "truncated ciphertext".to_string()));
+    }
+    let x = XChaCha20Poly1305::new(XKey::from_slice(kek));
+    let (xnonce, dek_ct) = dek_encrypted.split_at(XCHACHA_NONCE_LEN);
+    let dek = x.decrypt(XNonce::from_slice(xnonce), dek_ct).map_err(internal_error)?;
+    let (nonce_bytes, ct) = enc_blob.split_at(AES_NONCE_LEN);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&dek));
+    cipher.decrypt(Nonce::from_slice(nonce_bytes), ct).map_err(internal_error)
+}
+
+pub fn seal_context(kek: &[u8], context: &Value) -> Result<EncryptedContext, (StatusCode, String)> {
+    let plaintext = serde_json::to_vec(context).map_err(internal_error)?;
+    let (ciphertext, dek_encrypted) = seal(kek, &plaintext)?;
+    Ok(EncryptedContext { ciphertext, dek_encrypted, kek_id: LOCAL_KEK_ID.to_string() })
+}
+
+pub fn open_context(kek: &[u8], sealed: &EncryptedContext) -> Result<Value, (StatusCode, String)> {
+    let plaintext = open(kek, &sealed.ciphertext, &sealed.dek_encrypted)?;
+    serde_json::from_slice(&plaintext).map_err(internal_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_context_round_trip() {
+        let kek = rand::random::<[u8; 32]>();
+        let context = json!({ "api_key": "sk-live-123", "notes": ["a", "b"] });
+        let sealed = seal_context(&kek, &context).unwrap();
+        assert!(!String::from_utf8_lossy(&sealed.ciphertext).contains("sk-live-123"));
+        assert_eq!(sealed.kek_id, LOCAL_KEK_ID);
+        assert_eq!(open_context(&kek, &sealed).unwrap(), context);
+
+        let other = rand::random::<[u8; 32]>();
+        assert!(open_context(&other, &sealed).is_err());
+    }
+}
@@ -0,0 +1,416 @@
+//! Event Bus
+//! NATS publishing that buffers events during an outage and replays them in order once it recovers
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use prometheus::{IntCounterVec, IntGauge, register_int_counter_vec, register_int_gauge};
+use rcrt_core::db::Db;
+use rcrt_core::models::BreadcrumbCreate;
+use tokio::sync::Notify;
+use uuid::Uuid;
+use crate::events;
+
+const GAP_SCHEMA: &str = "system.events.gap.v1";
+const OUTBOX_BATCH: i64 = 100;
+const MIN_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+// Outbox rows can be left by another replica or a previous process
+const OUTBOX_POLL: Duration = Duration::from_secs(5);
+
+static BUFFER_EVENTS: OnceLock<IntCounterVec> = OnceLock::new();
+static BUFFER_DEPTH: OnceLock<IntGauge> = OnceLock::new();
+
+fn buffer_events() -> &'static IntCounterVec {
+    BUFFER_EVENTS.get_or_init(|| register_int_counter_vec!("nats_event_buffer_total", "Events buffered, replayed or dropped during NATS outages", &["outcome"]).unwrap())
+}
+
+fn buffer_depth() -> &'static IntGauge {
+    BUFFER_DEPTH.get_or_init(|| register_int_gauge!("nats_event_buffer_depth", "Events waiting to be replayed to NATS").unwrap())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferMode {
+    /// Bounded in-process queue; lost on restart
+    Memory,
+    /// `event_outbox` table; survives restarts and is shared by replicas
+    Outbox,
+}
+
+impl BufferMode {
+    pub fn from_env() -> Self {
+        match std::env::var("EVENT_BUFFER").as_deref() {
+            Ok("outbox") => BufferMode::Outbox,
+            _ => BufferMode::Memory,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Pending {
+    seq: u64,
+    owner_id: Uuid,
+    subject: String,
+    payload: String,
+    failed_at: DateTime<Utc>,
+}
+
+struct MemoryBuffer {
+    items: VecDeque<Pending>,
+    capacity: usize,
+    next_seq: u64,
+}
+
+impl MemoryBuffer {
+    fn new(capacity: usize) -> Self {
+        MemoryBuffer { items: VecDeque::new(), capacity: capacity.max(1), next_seq: 0 }
+    }
+
+    /// Append, evicting and returning the oldest event when full
+    fn push(&mut self, owner_id: Uuid, subject: String, payload: String, failed_at: DateTime<Utc>) -> Option<Pending> {
+        let evicted = if self.items.len() >= self.capacity { self.items.pop_front() } else { None };
+        self.items.push_back(Pending { seq: self.next_seq, owner_id, subject, payload, failed_at });
+        self.next_seq += 1;
+        evicted
+    }
+
+    fn front(&self) -> Option<Pending> {
+        self.items.front().cloned()
+    }
+
+    /// Remove `seq` if it is still at the front (an overflow may have evicted it mid-replay)
+    fn ack(&mut self, seq: u64) -> bool {
+        if self.items.front().is_some_and(|p| p.seq == seq) {
+            self.items.pop_front();
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Window of dropped events for one owner
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Gap {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    dropped: u64,
+}
+
+fn record_gap(gaps: &mut HashMap<Uuid, Gap>, owner_id: Uuid, at: DateTime<Utc>) {
+    gaps.entry(owner_id)
+        .and_modify(|g| {
+            g.start = g.start.min(at);
+            g.end = g.end.max(at);
+            g.dropped += 1;
+        })
+        .or_insert(Gap { start: at, end: at, dropped: 1 });
+}
+
+enum Flush {
+    Drained,
+    Progress,
+    Stalled,
+}
+
+pub struct EventBus {
+    client: async_nats::Client,
+    db: Db,
+    mode: BufferMode,
+    capacity: usize,
+    /// Config::nats_publish_timeout_ms
+    publish_timeout: Duration,
+    memory: Mutex<MemoryBuffer>,
+    /// Outbox rows not yet replayed, as of the last insert or flush
+    outbox_depth: AtomicI64,
+    gaps: Mutex<HashMap<Uuid, Gap>>,
+    wake: Notify,
+}
+
+impl EventBus {
+    pub fn new(client: async_nats::Client, db: Db, publish_timeout: Duration) -> Arc<Self> {
+        let capacity = std::env::var("EVENT_BUFFER_CAPACITY").ok().and_then(|s| s.parse().ok()).unwrap_or(10_000usize);
+        let mode = BufferMode::from_env();
+        Arc::new(EventBus {
+            client,
+            db,
+            mode,
+            capacity,
+            publish_timeout,
+            memory: Mutex::new(MemoryBuffer::new(capacity)),
+            outbox_depth: AtomicI64::new(0),
+            gaps: Mutex::new(HashMap::new()),
+            wake: Notify::new(),
+        })
+    }
+
+    fn connected(&self) -> bool {
+        self.client.connection_state() == async_nats::connection::State::Connected
+    }
+
+    fn has_backlog(&self) -> bool {
+        match self.mode {
+            BufferMode::Memory => self.memory.lock().map(|m| !m.items.is_empty()).unwrap_or(false),
+            BufferMode::Outbox => self.outbox_depth.load(Ordering::SeqCst) > 0,
+        }
+    }
+
+    /// Publish now if possible, otherwise buffer for replay; returns whether it went out immediately
+    pub async fn publish(&self, owner_id: Uuid, subject: String, payload: String) -> bool {
+        // While a backlog exists new events queue behind it so consumers see them in order
+        if self.connected() && !self.has_backlog() && events::publish(&self.client, subject.clone(), payload.clone(), self.publish_timeout).await {
+            return true;
+        }
+        self.buffer(owner_id, subject, payload).await;
+        false
+    }
+
+    async fn buffer(&self, owner_id: Uuid, subject: String, payload: String) {
+        let now = Utc::now();
+        buffer_events().with_label_values(&["buffered"]).inc();
+        match self.mode {
+            BufferMode::Memory => {
+                let (evicted, depth) = {
+                    let Ok(mut memory) = self.memory.lock() else { return; };
+                    let evicted = memory.push(owner_id, subject, payload, now);
+                    (evicted, memory.items.len())
+                };
+                buffer_depth().set(depth as i64);
+                if let Some(dropped) = evicted {
+                    self.dropped(dropped.owner_id, dropped.failed_at);
+                }
+            }
+            BufferMode::Outbox => {
+                if let Err(e) = self.outbox_insert(owner_id, &subject, &payload).await {
+                    tracing::error!("🔧 NATS: ❌ Failed to write {} to event outbox: {}", subject, e);
+                    self.dropped(owner_id, now);
+                }
+            }
+        }
+        self.wake.notify_one();
+    }
+
+    fn dropped(&self, owner_id: Uuid, at: DateTime<Utc>) {
+        buffer_events().with_label_values(&["dropped"]).inc();
+        if let Ok(mut gaps) = self.gaps.lock() {
+            record_gap(&mut gaps, owner_id, at);
+        }
+        tracing::warn!("🔧 NATS: ⚠️ Event buffer full, dropped an event for owner {}", owner_id);
+    }
+
+    async fn outbox_insert(&self, owner_id: Uuid, subject: &str, payload: &str) -> Result<(), sqlx::Error> {
+        let mut tx = self.db.pool.begin().await?;
+        sqlx::query("insert into event_outbox (owner_id, subject, payload) values ($1, $2, $3)")
+            .bind(owner_id)
+            .bind(subject)
+            .bind(payload)
+            .execute(&mut *tx)
+            .await?;
+        // Trim the oldest rows past capacity; they become a gap for their owners
+        let evicted: Vec<(Uuid, DateTime<Utc>)> = sqlx::query_as(
+            r#"delete from event_outbox where id in (
+                 select id from event_outbox order by id
+                 limit greatest((select count(*) from event_outbox) - $1, 0)
+                 for update skip locked
+               ) returning owner_id, failed_at"#
+        )
+        .bind(self.capacity as i64)
+        .fetch_all(&mut *tx)
+        .await?;
+        let depth: i64 = sqlx::query_scalar("select count(*) from event_outbox").fetch_one(&mut *tx).await?;
+        tx.commit().await?;
+        for (owner_id, failed_at) in evicted {
+            self.dropped(owner_id, failed_at);
+        }
+        self.outbox_depth.store(depth, Ordering::SeqCst);
+        buffer_depth().set(depth);
+        Ok(())
+    }
+
+    /// Replay buffered events with backoff, then report any gaps once the backlog is drained
+    pub fn start_replay(self: &Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tracing::info!("🔧 NATS: Event buffer mode {:?}, capacity {}, publish timeout {:?}", self.mode, self.capacity, self.publish_timeout);
+        let bus = self.clone();
+        tokio::spawn(async move {
+            let mut backoff = MIN_BACKOFF;
+            loop {
+                let result = match bus.mode {
+                    BufferMode::Memory => Ok(bus.flush_memory().await),
+                    BufferMode::Outbox => bus.flush_outbox().await,
+                };
+                match result {
+                    Ok(Flush::Progress) => backoff = MIN_BACKOFF,
+                    Ok(Flush::Drained) => {
+                        backoff = MIN_BACKOFF;
+                        bus.emit_gaps().await;
+                        tokio::select! {
+                            _ = bus.wake.notified() => {}
+                            _ = tokio::time::sleep(OUTBOX_POLL), if bus.mode == BufferMode::Outbox => {}
+                        }
+                    }
+                    Ok(Flush::Stalled) => {
+                        tokio::time::sleep(backoff).await;
+                        backoff = (backoff * 2).min(MAX_BACKOFF);
+                    }
+                    Err(e) => {
+                        tracing::warn!("🔧 NATS: ⚠️ Event outbox flush failed: {}", e);
+                        tokio::time::sleep(backoff).await;
+                        backoff = (backoff * 2).min(MAX_BACKOFF);
+                    }
+                }
+            }
+        })
+    }
+
+    async fn flush_memory(&self) -> Flush {
+        let mut replayed = 0;
+        loop {
+            let Some(next) = self.memory.lock().ok().and_then(|m| m.front()) else {
+                buffer_depth().set(0);
+                return if replayed > 0 { Flush::Progress } else { Flush::Drained };
+            };
+            if !self.connected() || !events::publish(&self.client, next.subject.clone(), next.payload.clone(), self.publish_timeout).await {
+                return Flush::Stalled;
+            }
+            if let Ok(mut memory) = self.memory.lock() {
+                if memory.ack(next.seq) {
+                    buffer_depth().set(memory.items.len() as i64);
+                }
+            }
+            replayed += 1;
+            buffer_events().with_label_values(&["replayed"]).inc();
+        }
+    }
+
+    async fn flush_outbox(&self) -> Result<Flush, sqlx::Error> {
+        if !self.connected() {
+            return Ok(Flush::Stalled);
+        }
+        let mut tx = self.db.pool.begin().await?;
+        let rows: Vec<(i64, String, String)> = sqlx::query_as(
+            "select id, subject, payload from event_outbox order by id limit $1 for update skip locked"
+        )
+        .bind(OUTBOX_BATCH)
+        .fetch_all(&mut *tx)
+        .await?;
+        if rows.is_empty() {
+            tx.commit().await?;
+            self.outbox_depth.store(0, Ordering::SeqCst);
+            buffer_depth().set(0);
+            return Ok(Flush::Drained);
+        }
+
+        // Stop at the first failure so the remainder keeps its order
+        let total = rows.len();
+        let mut sent: Vec<i64> = Vec::with_capacity(total);
+        for (id, subject, payload) in rows {
+            if !events::publish(&self.client, subject, payload, self.publish_timeout).await {
+                break;
+            }
+            sent.push(id);
+        }
+        if !sent.is_empty() {
+            sqlx::query("delete from event_outbox where id = any($1)")
+                .bind(&sent)
+                .execute(&mut *tx)
+                .await?;
+        }
+        let depth: i64 = sqlx::query_scalar("select count(*) from event_outbox").fetch_one(&mut *tx).await?;
+        tx.commit().await?;
+        buffer_events().with_label_values(&["replayed"]).inc_by(sent.len() as u64);
+        self.outbox_depth.store(depth, Ordering::SeqCst);
+        buffer_depth().set(depth);
+        Ok(if sent.len() < total { Flush::Stalled } else { Flush::Progress })
+    }
+
+    /// Tell each affected owner which window lost events, so consumers can backfill it
+    async fn emit_gaps(&self) {
+        let gaps: Vec<(Uuid, Gap)> = match self.gaps.lock() {
+            Ok(mut gaps) => gaps.drain().collect(),
+            Err(_) => return,
+        };
+        for (owner_id, gap) in gaps {
+            let create = BreadcrumbCreate {
+                title: "Event gap".to_string(),
+                description: None,
+                semantic_version: None,
+                context: serde_json::json!({
+                    "gap_start": gap.start,
+                    "gap_end": gap.end,
+                    "dropped_events": gap.dropped,
+                    "reason": "nats_buffer_overflow",
+                }),
+                tags: vec!["system:events-gap".to_string()],
+                schema_name: Some(GAP_SCHEMA.to_string()),
+                llm_hints: None,
+                visibility: None,
+                sensitivity: None,
+                ttl: Some(Utc::now() + chrono::Duration::days(7)),
+                ttl_type: None,
+                ttl_config: None,
+                ttl_source: None,
+                entity_keywords: None,
+                entities: None,
+            };
+            match self.db.create_breadcrumb_for(owner_id, None, None, create).await {
+                Ok(bc) => {
+                    tracing::warn!("🔧 NATS: ⚠️ Reported gap of {} events for owner {} ({} .. {})", gap.dropped, owner_id, gap.start, gap.end);
+                    let created = events::breadcrumb_event("breadcrumb.created", owner_id, &bc).to_string();
+                    let updated = events::breadcrumb_event("breadcrumb.updated", owner_id, &bc).to_string();
+                    events::publish(&self.client, format!("bc.{}.created", bc.id), created, self.publish_timeout).await;
+                    events::publish(&self.client, format!("bc.{}.updated", bc.id), updated, self.publish_timeout).await;
+                    if let Err(e) = self.db.mark_breadcrumb_event_published(bc.id, bc.version).await {
+                        tracing::warn!("🔧 NATS: ⚠️ Failed to mark gap breadcrumb {} published: {}", bc.id, e);
+                    }
+                }
+                Err(e) => {
+                    tracing::error!("🔧 NATS: ❌ Failed to record event gap for owner {}: {}", owner_id, e);
+                    if let Ok(mut gaps) = self.gaps.lock() {
+                        gaps.insert(owner_id, gap);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_buffer_evicts_oldest() {
+        let mut buffer = MemoryBuffer::new(2);
+        let owner = Uuid::new_v4();
+        assert!(buffer.push(owner, "a".into(), "1".into(), Utc::now()).is_none());
+        assert!(buffer.push(owner, "b".into(), "2".into(), Utc::now()).is_none());
+        let evicted = buffer.push(owner, "c".into(), "3".into(), Utc::now()).unwrap();
+        assert_eq!(evicted.subject, "a");
+        assert_eq!(buffer.front().unwrap().subject, "b");
+    }
+
+    #[test]
+    fn test_ack_ignores_evicted_front() {
+        let mut buffer = MemoryBuffer::new(1);
+        let owner = Uuid::new_v4();
+        buffer.push(owner, "a".into(), "1".into(), Utc::now());
+        let in_flight = buffer.front().unwrap();
+        // Overflow while "a" is being replayed must not drop "b" on ack
+        buffer.push(owner, "b".into(), "2".into(), Utc::now());
+        assert!(!buffer.ack(in_flight.seq));
+        assert_eq!(buffer.front().unwrap().subject, "b");
+    }
+
+    #[test]
+    fn test_gap_window_spans_dropped_events() {
+        let mut gaps = HashMap::new();
+        let owner = Uuid::new_v4();
+        let t0 = Utc::now();
+        let t1 = t0 + chrono::Duration::seconds(30);
+        record_gap(&mut gaps, owner, t1);
+        record_gap(&mut gaps, owner, t0);
+        assert_eq!(gaps[&owner], Gap { start: t0, end: t1, dropped: 2 });
+    }
+}
@@ -0,0 +1,581 @@
+//! Events
+//! NATS connection and best-effort publishing, breadcrumb event fanout (marking the outbox), the SSE stream and missed-event catch-up
+
+#[cfg(feature = "nats")]
+use std::future::Future;
+#[cfg(feature = "nats")]
+use std::sync::OnceLock;
+use std::time::Duration;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use axum::{extract::{Query, State}, http::StatusCode, Json};
+use chrono::{DateTime, Utc};
+#[cfg(feature = "nats")]
+use prometheus::{IntCounterVec, register_int_counter_vec};
+#[cfg(feature = "nats")]
+use tracing::Instrument;
+use rcrt_core::models::Selector;
+#[cfg(feature = "nats")]
+use rcrt_core::roles::Role;
+use rcrt_core::trace_context::TRACEPARENT;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{auth::AuthContext, breadcrumb_filter, db_errors::db_error, fanout_access, payload_versions::{self, PayloadVersion}, request_id, selector_match, telemetry, AppState};
+#[cfg(feature = "nats")]
+use crate::{sse_queue, webhooks::fanout_events_and_webhooks};
+
+#[cfg(feature = "nats")]
+static PUBLISH_FAILURES: OnceLock<IntCounterVec> = OnceLock::new();
+
+#[cfg(feature = "nats")]
+fn publish_failures() -> &'static IntCounterVec {
+    PUBLISH_FAILURES.get_or_init(|| register_int_counter_vec!("nats_publish_failures_total", "NATS publishes that failed or timed out", &["reason"]).unwrap())
+}
+
+/// Connect to NATS, reconnecting forever once the first connection succeeds
+#[cfg(feature = "nats")]
+pub async fn connect(url: &str) -> anyhow::Result<async_nats::Client> {
+    let client = async_nats::ConnectOptions::new()
+        .max_reconnects(None)
+        .event_callback(|event| async move {
+            match event {
+                async_nats::Event::Connected => tracing::info!("✅ NATS connection (re)established"),
+                async_nats::Event::Disconnected => tracing::warn!("⚠️ NATS disconnected, reconnecting..."),
+                other => tracing::warn!("⚠️ NATS event: {}", other),
+            }
+        })
+        .connect(url)
+        .await?;
+    Ok(client)
+}
+
+/// Event payload for a breadcrumb change, in the latest payload version; NATS carries this shape
+/// and SSE, /events/missed and webhooks render it through payload_versions for their consumer.
+/// visibility/sensitivity/created_by let SSE apply fanout_access rules without a lookup.
+/// Events raised while serving a request carry its `request_id`, so consumers can log under it
+pub fn breadcrumb_event(event_type: &str, owner_id: Uuid, bc: &rcrt_core::models::Breadcrumb) -> serde_json::Value {
+    use rcrt_core::models::{Sensitivity, Visibility};
+    let mut event = serde_json::json!({
+        "type": event_type,
+        "breadcrumb_id": bc.id,
+        "owner_id": owner_id,
+        "title": bc.title,
+        "version": bc.version,
+        "tags": bc.tags,
+        "schema_name": bc.schema_name,
+        "updated_at": bc.updated_at,
+        "visibility": match bc.visibility { Visibility::Public => "public", Visibility::Team => "team", Visibility::Private => "private" },
+        "sensitivity": match bc.sensitivity { Sensitivity::Low => "low", Sensitivity::Pii => "pii", Sensitivity::Secret => "secret" },
+        "created_by": bc.created_by,
+        "context": bc.context
+    });
+    event["created_at"] = json!(bc.created_at);
+    stamp_latest(&mut event, bc.updated_by.or(bc.created_by));
+    event
+}
+
+/// request_id and traceparent plus the fields payload versions after v1 added; `agent_id` is whoever caused the change
+fn stamp_latest(event: &mut serde_json::Value, agent_id: Option<Uuid>) {
+    let request_id = request_id::current();
+    if let Some(request_id) = &request_id {
+        event["request_id"] = json!(request_id);
+    }
+    // The context-builder continues the trace from here
+    if let Some(traceparent) = telemetry::current_traceparent() {
+        event[TRACEPARENT] = json!(traceparent);
+    }
+    event["emitted_at"] = json!(Utc::now());
+    event["provenance"] = json!({ "agent_id": agent_id, "request_id": request_id });
+    if let Some(received_at) = request_id::received_at() {
+        event["received_at"] = json!(received_at);
+    }
+    event["payload_version"] = json!(PayloadVersion::LATEST.number());
+}
+
+/// Publish an event without failing the caller, giving up after `timeout`; returns whether it was accepted
+#[cfg(feature = "nats")]
+pub async fn publish(client: &async_nats::Client, subject: String, payload: String, timeout: Duration) -> bool {
+    let span = tracing::info_span!("nats.publish", subject = %subject);
+    best_effort(&subject, timeout, client.publish(subject.clone(), payload.into())).instrument(span).await
+}
+
+// Errors and timeouts are logged and counted, never propagated: events are a
+// side channel and must not turn a committed write into an HTTP error
+#[cfg(feature = "nats")]
+async fn best_effort<F, E>(subject: &str, timeout: Duration, publish: F) -> bool
+where
+    F: Future<Output = Result<(), E>>,
+    E: std::fmt::Display,
+{
+    match tokio::time::timeout(timeout, publish).await {
+        Ok(Ok(())) => true,
+        Ok(Err(e)) => {
+            tracing::error!("🔧 NATS: ❌ Failed to publish to {}: {}", subject, e);
+            publish_failures().with_label_values(&["error"]).inc();
+            false
+        }
+        Err(_) => {
+            tracing::error!("🔧 NATS: ❌ Publish to {} timed out after {:?}", subject, timeout);
+            publish_failures().with_label_values(&["timeout"]).inc();
+            false
+        }
+    }
+}
+
+// Publish created + updated events for a new breadcrumb and fan out to selectors/webhooks,
+// then mark its outbox row so the dispatcher does not repeat it
+pub async fn publish_breadcrumb_created(state: &AppState, owner_id: Uuid, bc: &rcrt_core::models::Breadcrumb) {
+    announce_breadcrumb_created(state, owner_id, bc).await;
+    mark_published(state, bc).await;
+}
+
+// Publish the updated event for a changed breadcrumb and fan out to selectors/webhooks,
+// then mark its outbox row so the dispatcher does not repeat it
+pub async fn publish_breadcrumb_updated(state: &AppState, owner_id: Uuid, bc: &rcrt_core::models::Breadcrumb) {
+    announce_breadcrumb_updated(state, owner_id, bc).await;
+    mark_published(state, bc).await;
+}
+
+// Publish a breadcrumb.deleted event. It carries no context, and selectors and webhooks aren't
+// consulted since there is nothing left to match against. SSE bridges bc.*.updated, so the event
+// rides that subject with its own type
+pub async fn publish_breadcrumb_deleted(state: &AppState, owner_id: Uuid, bc: &rcrt_core::models::DeletedBreadcrumb) {
+    if let Some(cache) = &state.view_cache {
+        cache.invalidate(bc.id);
+    }
+    #[cfg(feature = "nats")]
+    {
+        let mut event = json!({
+            "type": "breadcrumb.deleted",
+            "breadcrumb_id": bc.id,
+            "owner_id": owner_id,
+            "title": bc.title,
+            "tags": bc.tags,
+            "schema_name": bc.schema_name,
+            "visibility": bc.visibility,
+            "sensitivity": bc.sensitivity,
+            "created_by": bc.created_by,
+            "deleted_at": Utc::now()
+        });
+        stamp_latest(&mut event, None);
+        state.event_bus.publish(owner_id, format!("bc.{}.updated", bc.id), event.to_string()).await;
+    }
+    #[cfg(not(feature = "nats"))]
+    let _ = (state, owner_id, bc);
+}
+
+// Publish a breadcrumb.reference_broken event for each breadcrumb whose declared reference a forced
+// delete broke. It describes the referencing breadcrumb (so SSE applies that one's access rules) and
+// names the deleted one in referenced_id; like deletes, it rides bc.*.updated without fanout
+pub async fn publish_references_broken(state: &AppState, owner_id: Uuid, agent_id: Uuid, deleted_id: Uuid, broken: &[rcrt_core::models::BrokenReference]) {
+    #[cfg(feature = "nats")]
+    for r in broken {
+        let mut event = json!({
+            "type": "breadcrumb.reference_broken",
+            "breadcrumb_id": r.source_id,
+            "owner_id": owner_id,
+            "title": r.title,
+            "tags": r.tags,
+            "schema_name": r.schema_name,
+            "visibility": r.visibility,
+            "sensitivity": r.sensitivity,
+            "created_by": r.created_by,
+            "field": r.field,
+            "relation": r.relation,
+            "referenced_id": deleted_id,
+        });
+        stamp_latest(&mut event, Some(agent_id));
+        state.event_bus.publish(owner_id, format!("bc.{}.updated", r.source_id), event.to_string()).await;
+    }
+    #[cfg(not(feature = "nats"))]
+    let _ = (state, owner_id, agent_id, deleted_id, broken);
+}
+
+// Tell the agent on its own channel (its SSE stream) that WEBHOOK_AUTO_DISABLE_AFTER_FAILURES
+// deactivated one of its webhooks; registering the URL again reactivates it
+pub async fn publish_webhook_deactivated(state: &AppState, owner_id: Uuid, agent_id: Uuid, webhook_id: Uuid, url: &str, consecutive_failures: i32) {
+    #[cfg(feature = "nats")]
+    {
+        let mut event = json!({
+            "type": "webhook.deactivated",
+            "owner_id": owner_id,
+            "agent_id": agent_id,
+            "webhook_id": webhook_id,
+            "url": url,
+            "consecutive_failures": consecutive_failures,
+            "deactivated_at": Utc::now(),
+        });
+        stamp_latest(&mut event, None);
+        state.event_bus.publish(owner_id, format!("agents.{}.events", agent_id), event.to_string()).await;
+    }
+    #[cfg(not(feature = "nats"))]
+    let _ = (state, owner_id, agent_id, webhook_id, url, consecutive_failures);
+}
+
+// Failing here only costs a duplicate event once the dispatcher's grace period passes
+async fn mark_published(state: &AppState, bc: &rcrt_core::models::Breadcrumb) {
+    if let Err(e) = state.db.mark_breadcrumb_event_published(bc.id, bc.version).await {
+        tracing::warn!("📮 Outbox: ⚠️ Failed to mark {} v{} published: {}", bc.id, bc.version, e);
+    }
+}
+
+// Created + updated events and fanout, without touching the outbox (the dispatcher marks its own rows)
+pub(crate) async fn announce_breadcrumb_created(state: &AppState, owner_id: Uuid, bc: &rcrt_core::models::Breadcrumb) {
+    #[cfg(feature = "nats")]
+    {
+        tracing::info!("🔧 NATS: Publishing breadcrumb events for {}", bc.id);
+        
+        // Send both created and updated for immediate consumers that expect either
+        let created = breadcrumb_event("breadcrumb.created", owner_id, bc).to_string();
+        let updated = breadcrumb_event("breadcrumb.updated", owner_id, bc).to_string();
+        let subj_created = format!("bc.{}.created", bc.id);
+        let subj_updated = format!("bc.{}.updated", bc.id);
+        
+        tracing::info!("🔧 NATS: Publishing to {} and {}", subj_created, subj_updated);
+        
+        // Failed publishes are buffered and replayed by the event bus
+        if state.event_bus.publish(owner_id, subj_created, created).await {
+            tracing::info!("🔧 NATS: ✅ Published created event");
+        }
+        if state.event_bus.publish(owner_id, subj_updated, updated.clone()).await {
+            tracing::info!("🔧 NATS: ✅ Published updated event");
+        }
+        
+        fanout_events_and_webhooks(state, owner_id, bc, &updated).await;
+    }
+    #[cfg(not(feature = "nats"))]
+    let _ = (state, owner_id, bc);
+}
+
+// Updated event and fanout, without touching the outbox. Also drops the breadcrumb's cached context view
+pub(crate) async fn announce_breadcrumb_updated(state: &AppState, owner_id: Uuid, bc: &rcrt_core::models::Breadcrumb) {
+    if let Some(cache) = &state.view_cache {
+        cache.invalidate(bc.id);
+    }
+    #[cfg(feature = "nats")]
+    {
+        let updated = breadcrumb_event("breadcrumb.updated", owner_id, bc).to_string();
+        let subj_updated = format!("bc.{}.updated", bc.id);
+        
+        tracing::info!("🔧 NATS: Publishing update event for {}", bc.id);
+        state.event_bus.publish(owner_id, subj_updated, updated.clone()).await;
+        
+        fanout_events_and_webhooks(state, owner_id, bc, &updated).await;
+    }
+    #[cfg(not(feature = "nats"))]
+    let _ = (state, owner_id, bc);
+}
+
+// Optional SSE filter: comma-separated tag patterns (same glob rules as selectors)
+#[derive(Deserialize)]
+pub struct SseFilterQuery { any_tags: Option<String>, all_tags: Option<String>, none_tags: Option<String>, schema_name: Option<String> }
+
+impl SseFilterQuery {
+    fn to_selector(&self) -> Option<Selector> {
+        let split = |s: &Option<String>| s.as_ref().map(|v| v.split(',').map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect::<Vec<_>>());
+        if self.any_tags.is_none() && self.all_tags.is_none() && self.none_tags.is_none() && self.schema_name.is_none() { return None; }
+        Some(Selector { any_tags: split(&self.any_tags), all_tags: split(&self.all_tags), none_tags: split(&self.none_tags), schema_name: self.schema_name.clone(), context_match: None })
+    }
+}
+
+/// `?payload_version=N` on /events/stream and /events/missed; overrides selector pins, v1 when nothing asks
+#[derive(Deserialize)]
+pub struct PayloadVersionQuery { payload_version: Option<u16> }
+
+impl PayloadVersionQuery {
+    fn requested(&self) -> Result<Option<PayloadVersion>, (StatusCode, String)> {
+        self.payload_version.map(PayloadVersion::requested).transpose()
+    }
+}
+
+// SSE stream (NATS-backed when feature enabled)
+#[cfg(feature = "nats")]
+pub async fn sse_stream(State(state): State<AppState>, auth: AuthContext, Query(filter): Query<SseFilterQuery>, Query(version): Query<PayloadVersionQuery>) -> Result<axum::response::Sse<impl futures_core::Stream<Item = Result<axum::response::sse::Event, std::convert::Infallible>>>, (StatusCode, String)> {
+    use axum::response::Sse;
+    use axum::response::sse::Event;
+    use tokio_stream::StreamExt;
+    use std::time::Duration;
+    let requested_version = version.requested()?;
+    if state.nats_conn.is_none() { 
+        tracing::error!("🔧 SSE: ❌ No NATS connection available for SSE stream!");
+        return Err((StatusCode::SERVICE_UNAVAILABLE, "event stream unavailable".into())); 
+    }
+    
+    let conn = state.nats_conn.as_ref().unwrap().clone();
+    tracing::info!("🔧 SSE: 📡 NEW SSE CONNECTION from agent {} (owner: {})", auth.agent_id, auth.owner_id);
+    
+    // Subscribe to all breadcrumb update events  
+    tracing::info!("🔧 SSE: Subscribing to NATS bc.*.updated...");
+    let sub_bc = conn.subscribe("bc.*.updated".to_string()).await.map_err(|e| {
+        tracing::error!("🔧 SSE: ❌ Failed to subscribe to bc.*.updated: {}", e);
+        (StatusCode::SERVICE_UNAVAILABLE, "event stream unavailable".to_string())
+    })?;
+    
+    tracing::info!("🔧 SSE: Subscribing to NATS agents.{}.events...", auth.agent_id);
+    let sub_agent = conn.subscribe(format!("agents.{}.events", auth.agent_id)).await.map_err(|e| {
+        tracing::error!("🔧 SSE: ❌ Failed to subscribe to agent events: {}", e);
+        (StatusCode::SERVICE_UNAVAILABLE, "event stream unavailable".to_string())
+    })?;
+    
+    let connection_id = format!("{}/{}", auth.agent_id, &Uuid::new_v4().simple().to_string()[..8]);
+    let queue = sse_queue::SseQueue::new(connection_id, state.sse_capacity, state.sse_overflow);
+    tracing::info!("🔧 SSE: ✅ NATS subscriptions established, spawning bridge task...");
+
+    // Spawn bridge tasks; each ends (and unsubscribes) when the client goes away
+    let owner = auth.owner_id;
+    let (agent_id, roles) = (auth.agent_id, Role::names(&auth.roles));
+    let matcher = filter.to_selector().map(|sel| selector_match::CompiledSelector::compile(&sel));
+    let queue_bc = queue.clone();
+    tokio::spawn(async move {
+        tracing::info!("🔧 SSE: Bridge task started, listening for NATS bc.*.updated events...");
+        bridge_subscription(sub_bc, &queue_bc, |txt| {
+            tracing::info!("🔧 SSE: 📡 NATS event received: {}", &txt[..std::cmp::min(100, txt.len())]);
+            
+            let parsed = serde_json::from_str::<serde_json::Value>(txt).ok();
+            let pass = parsed.as_ref()
+                .and_then(|v| v.get("owner_id").cloned())
+                .and_then(|v| v.as_str().map(|s| s.to_string()))
+                .and_then(|s| Uuid::parse_str(&s).ok())
+                .map(|oid| {
+                    let matches = oid == owner;
+                    tracing::info!("🔧 SSE: Owner filter - event owner: {}, my owner: {}, matches: {}", oid, owner, matches);
+                    matches
+                })
+                .unwrap_or(false);
+            let pass = pass && match (&matcher, &parsed) {
+                (Some(m), Some(v)) => m.matches_event(v),
+                _ => true,
+            };
+            
+            if pass { 
+                tracing::info!("🔧 SSE: ✅ Owner filter passed, forwarding event to SSE client");
+                // Broadcast events skip the ACL lookup: grantees get full context on their agent channel
+                let version = requested_version.unwrap_or(PayloadVersion::DEFAULT);
+                match parsed.as_ref().map(|v| (fanout_access::ReadScope::from_event(v).delivery(agent_id, &roles, &[]), v)) {
+                    Some((fanout_access::Delivery::Skip, _)) => None,
+                    Some((fanout_access::Delivery::Metadata, v)) => Some(payload_versions::render(&fanout_access::metadata_event(v), version).to_string()),
+                    Some((fanout_access::Delivery::Full, v)) => Some(payload_versions::render(v, version).to_string()),
+                    None => Some(txt.to_string()),
+                }
+            } else {
+                tracing::info!("🔧 SSE: ⏭️ Owner/selector filter failed, skipping event");
+                None
+            }
+        }).await;
+    });
+
+    let queue_agent = queue.clone();
+    tokio::spawn(async move {
+        // The fanout already applied read access; the query's version wins over the selectors' pin
+        bridge_subscription(sub_agent, &queue_agent, |txt| {
+            let Ok(event) = serde_json::from_str::<serde_json::Value>(txt) else { return Some(txt.to_string()) };
+            let version = requested_version.or(PayloadVersion::pinned(&event)).unwrap_or(PayloadVersion::DEFAULT);
+            Some(payload_versions::render_str(txt, version))
+        }).await;
+    });
+
+    // Heartbeat pings so clients know the stream is alive
+    let queue_ping = queue.clone();
+    let heartbeat = state.sse_heartbeat.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(heartbeat.interval);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = queue_ping.closed() => break,
+            }
+            if queue_ping.push(heartbeat.ping()) == sse_queue::Push::Closed { break; }
+        }
+    });
+
+    let stream = sse_queue::into_stream(queue)
+        .map(|data| Ok(Event::default().data(data)));
+    Ok(Sse::new(stream))
+}
+
+// Forward messages from a NATS subscription into an SSE queue until the client
+// goes away, then unsubscribe
+#[cfg(feature = "nats")]
+async fn bridge_subscription(mut sub: async_nats::Subscriber, queue: &sse_queue::SseQueue, mut forward: impl FnMut(&str) -> Option<String>) {
+    use tokio_stream::StreamExt;
+    loop {
+        let msg = tokio::select! {
+            msg = sub.next() => msg,
+            _ = queue.closed() => break,
+        };
+        let Some(msg) = msg else {
+            tracing::warn!("🔧 SSE: ⚠️ NATS subscription ended");
+            break;
+        };
+        let Ok(txt) = std::str::from_utf8(&msg.payload) else {
+            tracing::warn!("🔧 SSE: ⚠️ Failed to decode NATS message as UTF-8");
+            continue;
+        };
+        if let Some(out) = forward(txt) {
+            if queue.push(out) == sse_queue::Push::Closed { break; }
+        }
+    }
+    if let Err(e) = sub.unsubscribe().await {
+        tracing::warn!("🔧 SSE: ⚠️ Failed to unsubscribe: {}", e);
+    }
+}
+
+/// The SSE keepalive contract: every `interval` each stream gets
+/// `{"type":"ping","ts","seq","interval_secs"}`. `seq` counts pings across every stream this process
+/// serves, so it only grows while the process lives; a client that sees it go down is talking to a
+/// restarted server and should catch up via /events/missed. A client should treat a stream with
+/// nothing (events or pings) for several intervals as dead and reconnect.
+pub struct Heartbeat {
+    pub interval: Duration,
+    seq: AtomicU64,
+}
+
+impl Heartbeat {
+    pub fn new(interval: Duration) -> Self {
+        Heartbeat { interval: interval.max(Duration::from_secs(1)), seq: AtomicU64::new(0) }
+    }
+
+    /// The next ping's data
+    pub fn ping(&self) -> String {
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed) + 1;
+        json!({
+            "type": "ping",
+            "ts": Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            "seq": seq,
+            "interval_secs": self.interval.as_secs(),
+        }).to_string()
+    }
+}
+
+// SSE endpoint unavailable when NATS feature is disabled
+#[cfg(not(feature = "nats"))]
+pub async fn sse_stream(_: State<AppState>, _: AuthContext, _: Query<SseFilterQuery>, _: Query<PayloadVersionQuery>) -> Result<axum::response::Sse<impl futures_core::Stream<Item = Result<axum::response::sse::Event, std::convert::Infallible>>>, (StatusCode, String)> {
+    Err((StatusCode::SERVICE_UNAVAILABLE, "event stream unavailable".into()))
+}
+
+/// Oldest `since` GET /events/missed serves; a longer outage needs a full resync
+const MISSED_WINDOW_DAYS: i64 = 7;
+const MISSED_DEFAULT_LIMIT: i64 = 100;
+const MISSED_MAX_LIMIT: i64 = 500;
+const MISSED_SCAN_BATCH: i64 = 500;
+/// Breadcrumbs examined per request, so a narrow selector can't turn one call into a scan of the whole window
+const MISSED_SCAN_BUDGET: usize = 5000;
+
+#[derive(Deserialize)]
+pub struct MissedQuery { since: Option<String>, cursor: Option<String>, limit: Option<i64> }
+
+/// Position after the last breadcrumb a page examined: `<updated_at in µs>_<id>`
+fn encode_cursor(updated_at: DateTime<Utc>, id: Uuid) -> String {
+    format!("{}_{}", updated_at.timestamp_micros(), id)
+}
+
+fn decode_cursor(cursor: &str) -> Option<(DateTime<Utc>, Uuid)> {
+    let (micros, id) = cursor.split_once('_')?;
+    Some((DateTime::from_timestamp_micros(micros.parse().ok()?)?, Uuid::parse_str(id).ok()?))
+}
+
+/// Pull-based catch-up: breadcrumbs updated since `since` (or after `cursor`) that match the caller's
+/// selector subscriptions, oldest first, as the events fanout would have sent (latest version only).
+/// The /events/stream filter params replace the selectors for the request; with neither, every
+/// breadcrumb matches, like an unfiltered stream. Events come in `?payload_version`, else the
+/// matching selectors' pin, else v1. Keep calling with `next_cursor` while `has_more`
+pub async fn missed_events(State(state): State<AppState>, auth: AuthContext, Query(q): Query<MissedQuery>, Query(filter): Query<SseFilterQuery>, Query(version): Query<PayloadVersionQuery>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let requested_version = version.requested()?;
+    let (since, after_id) = match (q.cursor.as_deref(), q.since.as_deref()) {
+        (Some(cursor), _) => decode_cursor(cursor).map(|(t, id)| (t, Some(id))).ok_or((StatusCode::BAD_REQUEST, "invalid cursor".to_string()))?,
+        (None, Some(since)) => (breadcrumb_filter::timestamp("since", since)?, None),
+        (None, None) => return Err((StatusCode::BAD_REQUEST, "since or cursor is required".into())),
+    };
+    if since < Utc::now() - chrono::Duration::days(MISSED_WINDOW_DAYS) {
+        return Err((StatusCode::BAD_REQUEST, format!("missed events are only kept for {} days; resync with GET /breadcrumbs", MISSED_WINDOW_DAYS)));
+    }
+    let limit = q.limit.unwrap_or(MISSED_DEFAULT_LIMIT).clamp(1, MISSED_MAX_LIMIT) as usize;
+
+    let matchers: Vec<(Arc<selector_match::CompiledSelector>, Option<PayloadVersion>)> = match filter.to_selector() {
+        Some(selector) => vec![(Arc::new(selector_match::CompiledSelector::compile(&selector)), None)],
+        None => state.db.list_selector_subscriptions(auth.owner_id, auth.agent_id, false).await.map_err(db_error)?
+            .iter()
+            .map(|s| (state.selector_cache.get_or_compile(s.id, &s.selector), s.payload_version.and_then(|v| PayloadVersion::from_number(v.into()))))
+            .collect(),
+    };
+
+    let mut events = Vec::new();
+    let (mut position, mut scanned, mut has_more) = ((since, after_id), 0usize, true);
+    'scan: while scanned < MISSED_SCAN_BUDGET {
+        let batch = state.db.list_breadcrumbs_updated_since(auth.owner_id, Some(auth.agent_id), position.0, position.1, MISSED_SCAN_BATCH).await.map_err(db_error)?;
+        for bc in &batch {
+            position = (bc.updated_at, Some(bc.id));
+            scanned += 1;
+            let matched: Vec<_> = matchers.iter().filter(|(m, _)| m.matches(&bc.tags, bc.schema_name.as_deref(), &bc.context)).collect();
+            if !matchers.is_empty() && matched.is_empty() {
+                continue;
+            }
+            let version = requested_version.or(matched.iter().filter_map(|(_, pin)| *pin).max()).unwrap_or(PayloadVersion::DEFAULT);
+            // Same access rules as selector fanout to the agent channel
+            let access = fanout_access::FanoutAccess::load(&state.db, auth.owner_id, bc, &[auth.agent_id]).await;
+            let event = breadcrumb_event("breadcrumb.updated", auth.owner_id, bc);
+            match access.delivery(auth.agent_id) {
+                fanout_access::Delivery::Full => events.push(payload_versions::render(&event, version)),
+                fanout_access::Delivery::Metadata => events.push(payload_versions::render(&fanout_access::metadata_event(&event), version)),
+                fanout_access::Delivery::Skip => {}
+            }
+            if events.len() == limit {
+                break 'scan;
+            }
+        }
+        if (batch.len() as i64) < MISSED_SCAN_BATCH {
+            has_more = false;
+            break;
+        }
+    }
+
+    Ok(Json(json!({
+        "events": events,
+        "next_cursor": position.1.map(|id| encode_cursor(position.0, id)),
+        "has_more": has_more
+    })))
+}
+
+#[cfg(all(test, feature = "nats"))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_publish_ok() {
+        let ok = best_effort("bc.x.updated", Duration::from_millis(50), async { Ok::<(), String>(()) }).await;
+        assert!(ok);
+    }
+
+    #[tokio::test]
+    async fn test_publish_error_is_swallowed() {
+        let ok = best_effort("bc.x.updated", Duration::from_millis(50), async { Err::<(), _>("connection closed") }).await;
+        assert!(!ok);
+    }
+
+    #[test]
+    fn test_missed_cursor_round_trip() {
+        let (at, id) = (DateTime::from_timestamp_micros(1_750_000_000_123_456).unwrap(), Uuid::new_v4());
+        assert_eq!(decode_cursor(&encode_cursor(at, id)), Some((at, id)));
+        assert_eq!(decode_cursor("yesterday"), None);
+        assert_eq!(decode_cursor(&format!("abc_{}", id)), None);
+    }
+
+    #[test]
+    fn test_heartbeat_seq_grows_across_pings() {
+        let heartbeat = Heartbeat::new(Duration::from_secs(7));
+        let first: serde_json::Value = serde_json::from_str(&heartbeat.ping()).unwrap();
+        let second: serde_json::Value = serde_json::from_str(&heartbeat.ping()).unwrap();
+        assert_eq!((first["type"].as_str(), first["seq"].as_u64(), first["interval_secs"].as_u64()), (Some("ping"), Some(1), Some(7)));
+        assert_eq!(second["seq"], 2);
+        assert_eq!(Heartbeat::new(Duration::ZERO).interval, Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_publish_timeout_is_swallowed() {
+        let hung = std::future::pending::<Result<(), String>>();
+        let ok = best_effort("bc.x.updated", Duration::from_millis(10), hung).await;
+        assert!(!ok);
+    }
+}
@@ -0,0 +1,187 @@
+//! Fanout Access
+//! What a selector match may push to an agent: the full event, metadata only, or nothing
+
+use std::collections::HashMap;
+use rcrt_core::db::Db;
+use rcrt_core::models::{Breadcrumb, Sensitivity, Visibility};
+use rcrt_core::roles::Role;
+use serde::Serialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+/// Fields that survive redaction
+const METADATA_FIELDS: &[&str] = &["type", "breadcrumb_id", "owner_id", "version", "tags", "schema_name", "updated_at", "delivery_id", "payload_version", "emitted_at", "created_at", "received_at", "pinned_payload_version"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Delivery {
+    Full,
+    /// id, schema, tags and version; the agent knows something changed but not what
+    Metadata,
+    Skip,
+}
+
+/// The parts of a breadcrumb that decide who reads it
+#[derive(Debug, Clone, Copy)]
+pub struct ReadScope {
+    pub private: bool,
+    /// pii or secret
+    pub sensitive: bool,
+    pub created_by: Option<Uuid>,
+}
+
+impl ReadScope {
+    pub fn of(bc: &Breadcrumb) -> Self {
+        ReadScope {
+            private: matches!(bc.visibility, Visibility::Private),
+            sensitive: matches!(bc.sensitivity, Sensitivity::Pii | Sensitivity::Secret),
+            created_by: bc.created_by,
+        }
+    }
+
+    /// From the `visibility`/`sensitivity`/`created_by` fields of a breadcrumb event
+    pub fn from_event(event: &Value) -> Self {
+        let field = |name: &str| event.get(name).and_then(|v| v.as_str());
+        ReadScope {
+            private: field("visibility") == Some("private"),
+            sensitive: matches!(field("sensitivity"), Some("pii" | "secret")),
+            created_by: field("created_by").and_then(|s| Uuid::parse_str(s).ok()),
+        }
+    }
+
+    fn restricted(&self) -> bool {
+        self.private || self.sensitive
+    }
+
+    /// Private: creator, curators and read grantees only. Pii/secret: full context for the creator,
+    /// curators and read_full grantees, metadata for everyone else
+    pub fn delivery(&self, agent_id: Uuid, roles: &[String], actions: &[String]) -> Delivery {
+        let has = |list: &[String], item: &str| list.iter().any(|x| x == item);
+        let privileged = self.created_by == Some(agent_id) || Role::Curator.is_named_in(roles);
+        if self.private && !privileged && !has(actions, "read_context") && !has(actions, "read_full") {
+            return Delivery::Skip;
+        }
+        if self.sensitive && !privileged && !has(actions, "read_full") {
+            return Delivery::Metadata;
+        }
+        Delivery::Full
+    }
+}
+
+/// Deliveries for every agent a breadcrumb matched, from one grants query (none for unrestricted breadcrumbs)
+pub struct FanoutAccess {
+    scope: ReadScope,
+    grants: HashMap<Uuid, (Vec<String>, Vec<String>)>,
+}
+
+impl FanoutAccess {
+    pub async fn load(db: &Db, owner_id: Uuid, bc: &Breadcrumb, agent_ids: &[Uuid]) -> Self {
+        Self::load_scope(db, owner_id, bc.id, ReadScope::of(bc), agent_ids).await
+    }
+
+    /// As `load`, for a breadcrumb described by its id and scope; one not written yet has no grants of
+    /// its own, so a nil id leaves only the agents' roles
+    pub async fn load_scope(db: &Db, owner_id: Uuid, breadcrumb_id: Uuid, scope: ReadScope, agent_ids: &[Uuid]) -> Self {
+        let mut grants = HashMap::new();
+        if scope.restricted() && !agent_ids.is_empty() {
+            match db.agent_read_grants(owner_id, breadcrumb_id, agent_ids).await {
+                Ok(rows) => grants.extend(rows.into_iter().map(|(agent_id, roles, actions)| (agent_id, (roles, actions)))),
+                // Fail closed: with no grants only the creator gets more than metadata
+                Err(e) => tracing::warn!("Failed to load read grants for {}: {}", breadcrumb_id, e),
+            }
+        }
+        FanoutAccess { scope, grants }
+    }
+
+    pub fn delivery(&self, agent_id: Uuid) -> Delivery {
+        if !self.scope.restricted() {
+            return Delivery::Full;
+        }
+        let (roles, actions) = self.grants.get(&agent_id).map(|(r, a)| (r.as_slice(), a.as_slice())).unwrap_or((&[], &[]));
+        self.scope.delivery(agent_id, roles, actions)
+    }
+}
+
+/// The event with everything but `METADATA_FIELDS` removed, marked `redacted`
+pub fn metadata_event(event: &Value) -> Value {
+    let mut out = serde_json::Map::new();
+    for field in METADATA_FIELDS {
+        if let Some(v) = event.get(*field) {
+            out.insert(field.to_string(), v.clone());
+        }
+    }
+    out.insert("redacted".into(), json!(true));
+    Value::Object(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roles(r: &[&str]) -> Vec<String> {
+        r.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_secret_is_metadata_unless_privileged() {
+        let creator = Uuid::new_v4();
+        let scope = ReadScope { private: false, sensitive: true, created_by: Some(creator) };
+        let agent = Uuid::new_v4();
+        assert_eq!(scope.delivery(agent, &roles(&["subscriber"]), &[]), Delivery::Metadata);
+        assert_eq!(scope.delivery(agent, &roles(&["subscriber"]), &roles(&["read_context"])), Delivery::Metadata);
+        assert_eq!(scope.delivery(agent, &roles(&["subscriber"]), &roles(&["read_full"])), Delivery::Full);
+        assert_eq!(scope.delivery(agent, &roles(&["curator"]), &[]), Delivery::Full);
+        assert_eq!(scope.delivery(creator, &[], &[]), Delivery::Full);
+    }
+
+    #[test]
+    fn test_private_skips_agents_without_a_grant() {
+        let scope = ReadScope { private: true, sensitive: false, created_by: None };
+        let agent = Uuid::new_v4();
+        assert_eq!(scope.delivery(agent, &roles(&["subscriber"]), &[]), Delivery::Skip);
+        assert_eq!(scope.delivery(agent, &roles(&["subscriber"]), &roles(&["read_context"])), Delivery::Full);
+        assert_eq!(scope.delivery(agent, &roles(&["curator"]), &[]), Delivery::Full);
+    }
+
+    #[test]
+    fn test_scope_from_event_and_metadata_payload() {
+        let creator = Uuid::new_v4();
+        let event = json!({
+            "type": "breadcrumb.updated", "breadcrumb_id": Uuid::nil(), "owner_id": Uuid::nil(), "version": 3,
+            "tags": ["pii"], "schema_name": "user.profile.v1", "updated_at": "2025-01-01T00:00:00Z",
+            "visibility": "team", "sensitivity": "secret", "created_by": creator, "context": { "ssn": "123" },
+        });
+        let scope = ReadScope::from_event(&event);
+        assert!(scope.sensitive && !scope.private);
+        assert_eq!(scope.created_by, Some(creator));
+
+        let meta = metadata_event(&event);
+        assert!(meta.get("context").is_none() && meta.get("created_by").is_none());
+        assert_eq!(meta["version"], 3);
+        assert_eq!(meta["redacted"], true);
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_secret_match_is_metadata_for_subscriber_and_full_for_curator(pool: sqlx::PgPool) {
+        use crate::test_support::crumb;
+
+        let db = Db { pool };
+        let (owner_id, emitter, subscriber, curator) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        db.ensure_tenant(owner_id, "Fanout Access Test").await.unwrap();
+        for (agent, role) in [(emitter, "emitter"), (subscriber, "subscriber"), (curator, "curator")] {
+            db.upsert_agent(owner_id, agent, vec![role.into()]).await.unwrap();
+        }
+        let bc = db.create_breadcrumb_for(owner_id, Some(emitter), Some(emitter), rcrt_core::models::BreadcrumbCreate {
+            title: "Card".into(), context: json!({ "card": "4111" }), sensitivity: Some(Sensitivity::Secret), ..crumb("note.v1", &["billing"])
+        }).await.unwrap();
+
+        let access = FanoutAccess::load(&db, owner_id, &bc, &[subscriber, curator]).await;
+        assert_eq!(access.delivery(subscriber), Delivery::Metadata);
+        assert_eq!(access.delivery(curator), Delivery::Full);
+        assert_eq!(access.delivery(emitter), Delivery::Full);
+
+        db.grant_acl_agent(owner_id, bc.id, subscriber, "read_full").await.unwrap();
+        assert_eq!(FanoutAccess::load(&db, owner_id, &bc, &[subscriber]).await.delivery(subscriber), Delivery::Full);
+    }
+}
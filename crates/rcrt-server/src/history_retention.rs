@@ -0,0 +1,337 @@
+//! History Retention
+//! How many breadcrumb_history versions to keep per schema and tenant, and the batched prune the hygiene runner applies
+
+use std::collections::{BTreeSet, HashMap};
+use rcrt_core::db::Db;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::schema_registry::{defined_schema, SCHEMA_DEF};
+
+/// Tenant-wide overrides: `{"default": {..}, "schemas": {"<schema>": {..}}}`, newest one per owner wins
+pub const HISTORY_POLICY: &str = "system.history_policy.v1";
+
+/// A version is pruned once it falls outside either limit; None means no limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct HistoryPolicy {
+    pub keep_versions: Option<i32>,
+    pub keep_days: Option<i32>,
+}
+
+impl HistoryPolicy {
+    /// Fields present in `overrides` replace ours; null or a non-positive number lifts that limit
+    pub fn overlay(self, overrides: &Value) -> Self {
+        let field = |key: &str, current: Option<i32>| match overrides.get(key) {
+            Some(v) => v.as_i64().filter(|n| *n > 0).map(|n| n.min(i32::MAX as i64) as i32),
+            None => current,
+        };
+        HistoryPolicy {
+            keep_versions: field("keep_versions", self.keep_versions),
+            keep_days: field("keep_days", self.keep_days),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HistoryRetentionConfig {
+    pub default: HistoryPolicy,
+    /// Latest versions kept whatever the policy says; at least 1, so the current version always survives
+    pub keep_latest: i32,
+    pub batch_size: i64,
+    pub max_per_run: i64,
+}
+
+impl Default for HistoryRetentionConfig {
+    fn default() -> Self {
+        Self {
+            default: HistoryPolicy { keep_versions: Some(100), keep_days: None },
+            keep_latest: 5,
+            batch_size: 1000,
+            max_per_run: 10_000,
+        }
+    }
+}
+
+/// HISTORY_* environment variables; 0 disables the version or age limit
+pub fn load_history_retention_config() -> HistoryRetentionConfig {
+    let defaults = HistoryRetentionConfig::default();
+    let var = |name: &str| std::env::var(name).ok().and_then(|s| s.parse::<i64>().ok());
+    let limit = |name: &str, default: Option<i32>| match var(name) {
+        Some(n) => Some(n).filter(|n| *n > 0).map(|n| n.min(i32::MAX as i64) as i32),
+        None => default,
+    };
+    HistoryRetentionConfig {
+        default: HistoryPolicy {
+            keep_versions: limit("HISTORY_KEEP_VERSIONS", defaults.default.keep_versions),
+            keep_days: limit("HISTORY_KEEP_DAYS", defaults.default.keep_days),
+        },
+        keep_latest: var("HISTORY_KEEP_LATEST").map(|n| n.clamp(1, i32::MAX as i64) as i32).unwrap_or(defaults.keep_latest),
+        batch_size: var("HISTORY_PRUNE_BATCH").filter(|n| *n > 0).unwrap_or(defaults.batch_size),
+        max_per_run: var("HISTORY_PRUNE_MAX_PER_RUN").filter(|n| *n > 0).unwrap_or(defaults.max_per_run),
+    }
+}
+
+/// One row of the prune query's rule table; the first matching rule (lowest `ord`) applies
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct PruneRule {
+    ord: i32,
+    owner_id: Option<Uuid>,
+    schema_name: Option<String>,
+    keep_versions: Option<i32>,
+    keep_days: Option<i32>,
+}
+
+/// The global default plus overrides from schema.def.v1 `history_retention` (every tenant, like
+/// the llm_hints cache) and each tenant's system.history_policy.v1
+#[derive(Debug, Clone, Default)]
+pub struct HistoryPolicies {
+    default: HistoryPolicy,
+    schemas: HashMap<String, Value>,
+    tenants: HashMap<Uuid, Value>,
+}
+
+impl HistoryPolicies {
+    /// `rows` are (owner_id, schema_name, tags, context), newest first
+    pub fn from_breadcrumbs(default: HistoryPolicy, rows: &[(Uuid, Option<String>, Vec<String>, Value)]) -> Self {
+        let mut policies = HistoryPolicies { default, ..Default::default() };
+        for (owner_id, schema_name, tags, context) in rows {
+            match schema_name.as_deref() {
+                Some(SCHEMA_DEF) => {
+                    if let (Some(schema), Some(overrides)) = (defined_schema(tags, context), context.get("history_retention")) {
+                        policies.schemas.entry(schema).or_insert_with(|| overrides.clone());
+                    }
+                }
+                Some(HISTORY_POLICY) => {
+                    policies.tenants.entry(*owner_id).or_insert_with(|| context.clone());
+                }
+                _ => {}
+            }
+        }
+        policies
+    }
+
+    pub async fn load(db: &Db, default: HistoryPolicy) -> Result<Self, sqlx::Error> {
+        // Raw pool like the hygiene runner: tenant policies for every owner are needed to prune
+        let rows = sqlx::query_as::<_, (Uuid, Option<String>, Vec<String>, Value)>(
+            r#"SELECT owner_id, schema_name, tags, context FROM breadcrumbs
+               WHERE (schema_name = $1 AND context ? 'history_retention') OR schema_name = $2
+               ORDER BY updated_at DESC"#
+        )
+        .bind(SCHEMA_DEF)
+        .bind(HISTORY_POLICY)
+        .fetch_all(&db.pool)
+        .await?;
+        Ok(Self::from_breadcrumbs(default, &rows))
+    }
+
+    /// Global default, then the tenant default, the schema definition and the tenant's schema entry
+    pub fn effective(&self, owner_id: Uuid, schema_name: Option<&str>) -> HistoryPolicy {
+        self.resolve(self.tenants.get(&owner_id), schema_name)
+    }
+
+    fn resolve(&self, tenant: Option<&Value>, schema_name: Option<&str>) -> HistoryPolicy {
+        let mut policy = self.default;
+        if let Some(overrides) = tenant.and_then(|t| t.get("default")) {
+            policy = policy.overlay(overrides);
+        }
+        if let Some(schema) = schema_name {
+            if let Some(overrides) = self.schemas.get(schema) {
+                policy = policy.overlay(overrides);
+            }
+            if let Some(overrides) = tenant.and_then(|t| t.get("schemas")).and_then(|s| s.get(schema)) {
+                policy = policy.overlay(overrides);
+            }
+        }
+        policy
+    }
+
+    /// Every (tenant, schema) pair with an override gets its own fully merged rule, most specific
+    /// first, so the first match in SQL is exactly `effective()`
+    fn rules(&self) -> Vec<PruneRule> {
+        let mut scopes: Vec<(Option<Uuid>, Option<String>)> = Vec::new();
+        let mut owners: Vec<&Uuid> = self.tenants.keys().collect();
+        owners.sort();
+        let global_schemas: BTreeSet<&String> = self.schemas.keys().collect();
+        for owner_id in &owners {
+            let mut schemas = global_schemas.clone();
+            if let Some(tenant_schemas) = self.tenants[*owner_id].get("schemas").and_then(|s| s.as_object()) {
+                schemas.extend(tenant_schemas.keys());
+            }
+            scopes.extend(schemas.into_iter().map(|s| (Some(**owner_id), Some(s.clone()))));
+        }
+        scopes.extend(global_schemas.into_iter().map(|s| (None, Some(s.clone()))));
+        scopes.extend(owners.into_iter().map(|o| (Some(*o), None)));
+        scopes.push((None, None));
+
+        scopes.into_iter().enumerate().map(|(ord, (owner_id, schema_name))| {
+            let policy = self.resolve(owner_id.and_then(|o| self.tenants.get(&o)), schema_name.as_deref());
+            PruneRule { ord: ord as i32, owner_id, schema_name, keep_versions: policy.keep_versions, keep_days: policy.keep_days }
+        }).collect()
+    }
+}
+
+/// Delete up to `limit` history rows outside their breadcrumb's policy. Version 1 and the latest
+/// `keep_latest` versions (so always the current one) are never candidates
+async fn prune_history_batch(db: &Db, rules: &Value, keep_latest: i32, limit: i64) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        r#"WITH rules AS (
+               SELECT * FROM jsonb_to_recordset($1::jsonb)
+               AS r(ord int, owner_id uuid, schema_name text, keep_versions int, keep_days int)
+           ),
+           doomed AS (
+               SELECT h.breadcrumb_id, h.version
+               FROM breadcrumb_history h
+               JOIN breadcrumbs b ON b.id = h.breadcrumb_id
+               CROSS JOIN LATERAL (
+                   SELECT r.keep_versions, r.keep_days FROM rules r
+                   WHERE (r.owner_id IS NULL OR r.owner_id = b.owner_id)
+                   AND (r.schema_name IS NULL OR r.schema_name = b.schema_name)
+                   ORDER BY r.ord
+                   LIMIT 1
+               ) p
+               WHERE h.version > 1
+               AND h.version <= b.version - $2
+               AND (
+                   (p.keep_versions IS NOT NULL AND h.version <= b.version - p.keep_versions)
+                   OR (p.keep_days IS NOT NULL AND h.updated_at < NOW() - make_interval(days => p.keep_days))
+               )
+               LIMIT $3
+           )
+           DELETE FROM breadcrumb_history h
+           USING doomed d
+           WHERE h.breadcrumb_id = d.breadcrumb_id AND h.version = d.version"#
+    )
+    .bind(rules)
+    .bind(keep_latest.max(1))
+    .bind(limit)
+    .execute(&db.pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Prune history across all tenants in batches of `batch_size`, stopping at `max_per_run` rows
+pub async fn prune_breadcrumb_history(db: &Db, config: &HistoryRetentionConfig) -> Result<u64, sqlx::Error> {
+    let policies = HistoryPolicies::load(db, config.default).await?;
+    let rules = json!(policies.rules());
+    let mut total = 0u64;
+    while (total as i64) < config.max_per_run {
+        let limit = config.batch_size.min(config.max_per_run - total as i64);
+        let deleted = prune_history_batch(db, &rules, config.keep_latest, limit).await?;
+        total += deleted;
+        if (deleted as i64) < limit {
+            break;
+        }
+    }
+
+    if total > 0 {
+        info!("Pruned {} breadcrumb history versions", total);
+    }
+
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn def(schema: &str, retention: Value) -> (Uuid, Option<String>, Vec<String>, Value) {
+        (Uuid::new_v4(), Some(SCHEMA_DEF.into()), vec![format!("defines:{}", schema)], json!({ "history_retention": retention }))
+    }
+
+    fn tenant(owner_id: Uuid, policy: Value) -> (Uuid, Option<String>, Vec<String>, Value) {
+        (owner_id, Some(HISTORY_POLICY.into()), vec![], policy)
+    }
+
+    const DEFAULT: HistoryPolicy = HistoryPolicy { keep_versions: Some(100), keep_days: Some(30) };
+
+    #[test]
+    fn test_overlay_replaces_only_present_fields() {
+        assert_eq!(DEFAULT.overlay(&json!({ "keep_versions": 10 })), HistoryPolicy { keep_versions: Some(10), keep_days: Some(30) });
+        assert_eq!(DEFAULT.overlay(&json!({ "keep_days": null })), HistoryPolicy { keep_versions: Some(100), keep_days: None });
+        assert_eq!(DEFAULT.overlay(&json!({ "keep_versions": 0 })).keep_versions, None);
+        assert_eq!(DEFAULT.overlay(&json!({})), DEFAULT);
+    }
+
+    #[test]
+    fn test_effective_policy_precedence() {
+        let (mine, other) = (Uuid::new_v4(), Uuid::new_v4());
+        let policies = HistoryPolicies::from_breadcrumbs(DEFAULT, &[
+            def("agent.context.v1", json!({ "keep_versions": 20 })),
+            // Older definition of the same schema is ignored
+            def("agent.context.v1", json!({ "keep_versions": 500 })),
+            tenant(mine, json!({ "default": { "keep_days": 7 }, "schemas": { "agent.context.v1": { "keep_days": 1 } } })),
+        ]);
+
+        assert_eq!(policies.effective(other, None), DEFAULT);
+        assert_eq!(policies.effective(other, Some("agent.context.v1")), HistoryPolicy { keep_versions: Some(20), keep_days: Some(30) });
+        assert_eq!(policies.effective(mine, Some("note.v1")), HistoryPolicy { keep_versions: Some(100), keep_days: Some(7) });
+        assert_eq!(policies.effective(mine, Some("agent.context.v1")), HistoryPolicy { keep_versions: Some(20), keep_days: Some(1) });
+    }
+
+    #[test]
+    fn test_rules_list_most_specific_scope_first() {
+        let owner_id = Uuid::new_v4();
+        let policies = HistoryPolicies::from_breadcrumbs(DEFAULT, &[
+            def("agent.context.v1", json!({ "keep_versions": 20 })),
+            tenant(owner_id, json!({ "default": { "keep_days": 7 } })),
+        ]);
+        let rules = policies.rules();
+        let scopes: Vec<(Option<Uuid>, Option<&str>)> = rules.iter().map(|r| (r.owner_id, r.schema_name.as_deref())).collect();
+        assert_eq!(scopes, vec![
+            (Some(owner_id), Some("agent.context.v1")),
+            (None, Some("agent.context.v1")),
+            (Some(owner_id), None),
+            (None, None),
+        ]);
+        assert_eq!((rules[0].keep_versions, rules[0].keep_days), (Some(20), Some(7)));
+        assert_eq!(rules.iter().map(|r| r.ord).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_prune_keeps_first_and_latest_versions(pool: sqlx::PgPool) {
+        use crate::test_support::crumb;
+        use rcrt_core::models::{BreadcrumbCreate, BreadcrumbUpdate};
+
+        let db = Db { pool };
+        let (owner_id, agent_id) = (Uuid::new_v4(), Uuid::new_v4());
+        db.ensure_tenant(owner_id, "History Retention Test").await.unwrap();
+        db.upsert_agent(owner_id, agent_id, vec!["emitter".into(), "curator".into()]).await.unwrap();
+        let create = |title: &str, schema: &str, context: Value, tags: &[&str]| BreadcrumbCreate { title: title.into(), context, ..crumb(schema, tags) };
+        let bump = |step: i32| BreadcrumbUpdate {
+            title: None, description: None, semantic_version: None, context: Some(json!({ "step": step })),
+            tags: None, schema_name: None, llm_hints: None, visibility: None, sensitivity: None,
+            ttl: None, ttl_type: None, ttl_config: None, ttl_source: None,
+        };
+
+        db.create_breadcrumb_for(owner_id, Some(agent_id), Some(agent_id), create(
+            "Chatty schema", SCHEMA_DEF, json!({ "history_retention": { "keep_versions": 3 } }), &["defines:agent.context.v1"],
+        )).await.unwrap();
+        let chatty = db.create_breadcrumb_for(owner_id, Some(agent_id), Some(agent_id), create("Context", "agent.context.v1", json!({ "step": 1 }), &[])).await.unwrap();
+        let quiet = db.create_breadcrumb_for(owner_id, Some(agent_id), Some(agent_id), create("Note", "note.v1", json!({ "step": 1 }), &[])).await.unwrap();
+        for step in 2..=12 {
+            db.update_breadcrumb(owner_id, agent_id, chatty.id, None, bump(step)).await.unwrap();
+            db.update_breadcrumb(owner_id, agent_id, quiet.id, None, bump(step)).await.unwrap();
+        }
+
+        let config = HistoryRetentionConfig { keep_latest: 2, batch_size: 2, ..Default::default() };
+        // 12 versions, keep v1 and the last 3: v2..=v9 go, two rows per batch
+        assert_eq!(prune_breadcrumb_history(&db, &config).await.unwrap(), 8);
+        let versions = |id| {
+            let db = db.clone();
+            async move { db.list_breadcrumb_history(owner_id, Some(agent_id), id).await.unwrap().into_iter().map(|h| h.0).collect::<Vec<_>>() }
+        };
+        assert_eq!(versions(chatty.id).await, vec![12, 11, 10, 1]);
+        // The default keeps 100 versions, so the other schema is untouched
+        assert_eq!(versions(quiet.id).await.len(), 12);
+        assert_eq!(prune_breadcrumb_history(&db, &config).await.unwrap(), 0);
+
+        // keep_latest wins over a tighter policy and the current version always stays
+        let config = HistoryRetentionConfig { default: HistoryPolicy { keep_versions: Some(1), keep_days: None }, keep_latest: 4, ..Default::default() };
+        prune_breadcrumb_history(&db, &config).await.unwrap();
+        assert_eq!(versions(quiet.id).await, vec![12, 11, 10, 9, 1]);
+        assert_eq!(versions(chatty.id).await, vec![12, 11, 10, 1]);
+    }
+}
@@ -3,82 +3,184 @@
  * Automatic cleanup of expired breadcrumbs, agents, and system resources
  */
 
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 use uuid::Uuid;
 use tokio::time::{interval, Instant};
-use tracing::{info, warn, error};
+use tracing::{debug, info, warn, error};
 use serde_json::json;
-use crate::AppState;
+use crate::{attachments, checksums, history_retention, ttl_policy, AppState};
 
 // Helper function for error handling
 fn internal_error<E: std::fmt::Display>(e: E) -> Box<dyn std::error::Error> {
     Box::new(std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))
 }
 
-/// Simple direct cleanup function that can be called from API endpoints
-pub async fn cleanup_health_checks(db: &rcrt_core::db::Db) -> Result<u64, sqlx::Error> {
-    info!("Running direct health check cleanup...");
+/// Breadcrumbs without an explicit TTL that have outlived the first TTL policy they match
+/// (tenant ttl.policy.v1 breadcrumbs, then the built-ins); callable from API endpoints
+pub async fn cleanup_policy_expired(db: &rcrt_core::db::Db, config: &HygieneConfig) -> Result<u64, sqlx::Error> {
+    info!("Running direct TTL policy cleanup...");
+    ttl_policy::cleanup_implicit_ttl(db, config.batch_size, config.max_delete_per_run).await
+}
+
+/// Simple cleanup for all expired breadcrumbs
+pub async fn cleanup_expired_breadcrumbs(db: &rcrt_core::db::Db) -> Result<u64, sqlx::Error> {
+    info!("Running direct expired breadcrumb cleanup...");
+    
+    let ttl_query = "DELETE FROM breadcrumbs WHERE ttl IS NOT NULL AND ttl < NOW()";
+    let total_deleted = sqlx::query(ttl_query).execute(&db.pool).await?.rows_affected();
     
-    // Clean up health check breadcrumbs older than 5 minutes
-    let query = "DELETE FROM breadcrumbs 
-                 WHERE schema_name = 'tool.request.v1' 
-                 AND tags @> ARRAY['health:check'] 
-                 AND created_at < NOW() - INTERVAL '5 minutes'";
+    if total_deleted > 0 {
+        info!("Cleaned up {} total expired breadcrumbs", total_deleted);
+    }
     
-    let result = sqlx::query(query)
+    Ok(total_deleted)
+}
+
+/// Prune webhook delivery records older than the retention window
+pub async fn cleanup_webhook_deliveries(db: &rcrt_core::db::Db, retention_days: i64) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM webhook_deliveries WHERE created_at < NOW() - make_interval(days => $1::int)")
+        .bind(retention_days as i32)
         .execute(&db.pool)
         .await?;
     
     let deleted = result.rows_affected();
     
     if deleted > 0 {
-        info!("Cleaned up {} health check breadcrumbs", deleted);
+        info!("Pruned {} webhook delivery records", deleted);
     }
     
     Ok(deleted)
 }
 
-/// Simple cleanup for all expired breadcrumbs
-pub async fn cleanup_expired_breadcrumbs(db: &rcrt_core::db::Db) -> Result<u64, sqlx::Error> {
-    info!("Running direct expired breadcrumb cleanup...");
-    
-    let mut total_deleted = 0u64;
+/// Drop inbox entries acknowledged before the retention window; unacked ones stay however old
+pub async fn cleanup_agent_inbox(db: &rcrt_core::db::Db, retention_days: i64) -> Result<u64, rcrt_core::error::DbError> {
+    let deleted = db.prune_inbox(chrono::Utc::now() - chrono::Duration::days(retention_days)).await?;
     
-    // 1. Explicit TTL breadcrumbs
-    let ttl_query = "DELETE FROM breadcrumbs WHERE ttl IS NOT NULL AND ttl < NOW()";
-    let ttl_result = sqlx::query(ttl_query).execute(&db.pool).await?;
-    total_deleted += ttl_result.rows_affected();
-    
-    // 2. Health checks (5 minutes)
-    let health_query = "DELETE FROM breadcrumbs 
-                       WHERE schema_name = 'tool.request.v1' 
-                       AND tags @> ARRAY['health:check']
-                       AND created_at < NOW() - INTERVAL '5 minutes'
-                       AND ttl IS NULL";
-    let health_result = sqlx::query(health_query).execute(&db.pool).await?;
-    total_deleted += health_result.rows_affected();
-    
-    // 3. System pings (10 minutes)
-    let ping_query = "DELETE FROM breadcrumbs 
-                     WHERE schema_name = 'system.ping.v1' 
-                     AND created_at < NOW() - INTERVAL '10 minutes'
-                     AND ttl IS NULL";
-    let ping_result = sqlx::query(ping_query).execute(&db.pool).await?;
-    total_deleted += ping_result.rows_affected();
+    if deleted > 0 {
+        info!("Pruned {} acknowledged inbox entries", deleted);
+    }
     
-    // 4. Agent thinking breadcrumbs (6 hours)
-    let thinking_query = "DELETE FROM breadcrumbs 
-                         WHERE schema_name IN ('agent.thinking.v1', 'agent.analysis.v1') 
-                         AND created_at < NOW() - INTERVAL '6 hours'
-                         AND ttl IS NULL";
-    let thinking_result = sqlx::query(thinking_query).execute(&db.pool).await?;
-    total_deleted += thinking_result.rows_affected();
+    Ok(deleted)
+}
+
+/// Drop breadcrumb access counters of days past the retention window
+pub async fn cleanup_access_log(db: &rcrt_core::db::Db, retention_days: i64) -> Result<u64, rcrt_core::error::DbError> {
+    let deleted = db.prune_breadcrumb_access(chrono::Utc::now().date_naive() - chrono::Duration::days(retention_days)).await?;
     
-    if total_deleted > 0 {
-        info!("Cleaned up {} total expired breadcrumbs", total_deleted);
+    if deleted > 0 {
+        info!("Pruned {} breadcrumb access counters", deleted);
     }
     
-    Ok(total_deleted)
+    Ok(deleted)
+}
+
+/// What run_tenant_cleanup removed, summed over tenants
+#[derive(Debug, Clone, Default)]
+pub struct TenantCleanup {
+    pub expired: u64,
+    pub policy: u64,
+    pub keep_latest: Vec<ttl_policy::KeepLatestDeletion>,
+    pub agents: u64,
+    /// Tenants whose cleanup failed part way; the rest still ran
+    pub failed: u32,
+}
+
+/// The tenant-scoped cleanups (explicit TTLs, TTL policies, keep_latest, idle agents) one tenant at a
+/// time, each as its system.hygiene.config.v1 says; `max_delete_per_run` applies per tenant
+pub async fn run_tenant_cleanup(state: &AppState, config: &HygieneConfig, policies: &ttl_policy::TtlPolicies) -> Result<TenantCleanup, sqlx::Error> {
+    let owners: Vec<Uuid> = sqlx::query_scalar("SELECT id FROM tenants ORDER BY id")
+        .fetch_all(&state.db.pool)
+        .await?;
+    let mut cleanup = TenantCleanup::default();
+    for owner_id in owners {
+        if let Err(e) = cleanup_tenant(state, config, policies, owner_id, &mut cleanup).await {
+            warn!("Hygiene cleanup for tenant {} failed: {}", owner_id, e);
+            cleanup.failed += 1;
+        }
+    }
+    Ok(cleanup)
+}
+
+async fn cleanup_tenant(state: &AppState, config: &HygieneConfig, policies: &ttl_policy::TtlPolicies, owner_id: Uuid, cleanup: &mut TenantCleanup) -> Result<(), sqlx::Error> {
+    let tenant = policies.hygiene.for_owner(owner_id);
+    if tenant.policies.expired_ttl {
+        cleanup.expired += sqlx::query("DELETE FROM breadcrumbs WHERE owner_id = $1 AND ttl IS NOT NULL AND ttl < NOW()")
+            .bind(owner_id)
+            .execute(&state.db.pool)
+            .await?
+            .rows_affected();
+    }
+    if tenant.policies.ttl_policies {
+        cleanup.policy += ttl_policy::cleanup_implicit_ttl_for(&state.db, policies, owner_id, config.batch_size, config.max_delete_per_run).await?;
+    }
+    if tenant.policies.keep_latest {
+        cleanup.keep_latest.extend(ttl_policy::cleanup_keep_latest_for(&state.db, policies, owner_id, config.batch_size, config.max_delete_per_run).await?);
+    }
+    if tenant.policies.idle_agents {
+        cleanup.agents += cleanup_idle_agents(state, owner_id, tenant.agent_max_idle_hours, config.agent_cleanup_on_exit).await?;
+    }
+    Ok(())
+}
+
+/// Offboard the tenant's agents that were created and wrote nothing in the last `max_idle_hours`;
+/// agents with selector subscriptions and curators are kept
+pub async fn cleanup_idle_agents(state: &AppState, owner_id: Uuid, max_idle_hours: i64, cleanup_on_exit: bool) -> Result<u64, sqlx::Error> {
+    let idle: Vec<Uuid> = sqlx::query_scalar(
+        r#"SELECT a.id FROM agents a
+           WHERE a.owner_id = $1
+           AND a.created_at < NOW() - make_interval(hours => $2::int)
+           AND NOT 'curator' = ANY(a.roles)
+           AND NOT EXISTS (SELECT 1 FROM selector_subscriptions s WHERE s.owner_id = a.owner_id AND s.agent_id = a.id)
+           AND NOT EXISTS (
+               SELECT 1 FROM breadcrumbs b
+               WHERE b.owner_id = a.owner_id
+               AND (b.created_by = a.id OR b.updated_by = a.id)
+               AND b.updated_at >= NOW() - make_interval(hours => $2::int)
+           )"#
+    )
+    .bind(owner_id)
+    .bind(max_idle_hours.min(i32::MAX as i64) as i32)
+    .fetch_all(&state.db.pool)
+    .await?;
+
+    let mut cleaned = 0u64;
+    for agent_id in idle {
+        if cleanup_on_exit {
+            sqlx::query("DELETE FROM breadcrumbs WHERE owner_id = $1 AND (created_by = $2 OR updated_by = $2) AND tags @> ARRAY['agent:memory', 'temp:data']")
+                .bind(owner_id)
+                .bind(agent_id)
+                .execute(&state.db.pool)
+                .await?;
+        }
+        // Everything else goes the way DELETE /agents/:id?cascade=true does it
+        if let Some(offboarded) = state.db.offboard_agent(owner_id, agent_id, None, "hygiene: idle agent").await? {
+            crate::agents::finish_offboarding(state, owner_id, &offboarded);
+            cleaned += 1;
+            info!("Cleaned up idle agent: {}", agent_id);
+        }
+    }
+    Ok(cleaned)
+}
+
+/// Delete selector subscriptions past their expires_at, dropping them from the fanout caches
+pub async fn cleanup_expired_selectors(state: &AppState) -> Result<u64, sqlx::Error> {
+    let removed: Vec<(Uuid, Uuid)> = sqlx::query_as("DELETE FROM selector_subscriptions WHERE expires_at IS NOT NULL AND expires_at <= NOW() RETURNING id, owner_id")
+        .fetch_all(&state.db.pool)
+        .await?;
+    let mut owners = std::collections::HashSet::new();
+    for (id, owner_id) in &removed {
+        state.selector_cache.invalidate(*id);
+        owners.insert(*owner_id);
+    }
+    for owner_id in owners {
+        state.selector_index.invalidate(owner_id);
+    }
+    if !removed.is_empty() {
+        info!("Removed {} expired selector subscriptions", removed.len());
+    }
+    Ok(removed.len() as u64)
 }
 
 #[derive(Debug, Clone)]
@@ -93,9 +195,24 @@ pub struct HygieneConfig {
     pub healthcheck_ttl_minutes: i64,
     pub temp_data_ttl_hours: i64,
     pub log_retention_days: i64,
+    pub webhook_delivery_retention_days: i64,
+    /// Days of breadcrumb_access counters kept for the analytics
+    pub access_log_retention_days: i64,
+    /// Days acknowledged inbox entries are kept after their ack
+    pub inbox_retention_days: i64,
+    /// Unlinked attachments are kept this long before their bytes are removed
+    pub attachment_orphan_grace_hours: i64,
+    pub history_retention: history_retention::HistoryRetentionConfig,
+    
+    // Integrity
+    /// Recompute the stored checksums of `checksum_sample_size` breadcrumbs per run, cycling through all of them
+    pub verify_checksums: bool,
+    pub checksum_sample_size: i64,
     
     // Agent expiry policies  
     pub agent_max_idle_hours: i64,
+    /// Whether tenants without a system.hygiene.config.v1 saying otherwise get idle agents removed
+    pub idle_agent_cleanup: bool,
     pub agent_cleanup_on_exit: bool,
     
     // Performance tuning
@@ -114,11 +231,21 @@ impl Default for HygieneConfig {
             // Breadcrumb defaults
             default_breadcrumb_ttl_hours: None, // No default expiry
             healthcheck_ttl_minutes: 5,         // Health checks expire quickly
-            temp_data_ttl_hours: 24,            // Temporary data lasts 1 day
-            log_retention_days: 30,             // Logs kept for 30 days
+            temp_data_ttl_hours: 1,             // Temporary agent state lasts 1 hour
+            log_retention_days: 7,              // Tool logs kept for 7 days
+            webhook_delivery_retention_days: 7, // Delivery dedupe window
+            access_log_retention_days: 90,      // A quarter of usage analytics
+            inbox_retention_days: 7,            // Acked inbox entries, for debugging a consumer
+            attachment_orphan_grace_hours: 1,   // Covers an upload racing a delete of its last link
+            history_retention: Default::default(),
+            
+            // Integrity
+            verify_checksums: false,
+            checksum_sample_size: 100,
             
             // Agent defaults
             agent_max_idle_hours: 48,           // Idle agents cleaned after 2 days
+            idle_agent_cleanup: false,
             agent_cleanup_on_exit: true,
             
             // Performance
@@ -131,6 +258,12 @@ impl Default for HygieneConfig {
 pub struct HygieneRunner {
     config: HygieneConfig,
     state: AppState,
+    /// TTL policies and tenant configs as of the last cycle, kept when a reload fails
+    policies: std::sync::Mutex<Option<Arc<ttl_policy::TtlPolicies>>>,
+    /// Last breadcrumb id the checksum sample reached; None starts over
+    checksum_cursor: std::sync::Mutex<Option<Uuid>>,
+    /// Only the replica holding the hygiene lock runs cycles
+    leadership: crate::coordination::Leadership,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -138,7 +271,11 @@ pub struct HygieneStats {
     pub runs_completed: u64,
     pub total_breadcrumbs_purged: u64,
     pub total_agents_cleaned: u64,
+    pub total_history_pruned: u64,
+    /// Deletions per keep_latest policy since start, keyed by policy id
+    pub keep_latest_purged: HashMap<Uuid, ttl_policy::KeepLatestDeletion>,
     pub last_run_duration_ms: u64,
+    pub last_run_at: Option<chrono::DateTime<chrono::Utc>>,
     pub last_run_errors: u32,
 }
 
@@ -155,6 +292,9 @@ impl HygieneRunner {
         Self {
             config,
             state,
+            policies: std::sync::Mutex::new(None),
+            checksum_cursor: std::sync::Mutex::new(None),
+            leadership: crate::coordination::Leadership::new("hygiene runner", crate::coordination::HYGIENE_LOCK),
         }
     }
     
@@ -180,6 +320,10 @@ impl HygieneRunner {
         
         loop {
             interval.tick().await;
+            if !self.leadership.acquire(&self.state.db).await {
+                debug!("🧹 Another replica runs hygiene; skipping this cycle");
+                continue;
+            }
             info!("🧹 Hygiene cycle starting...");
             
             let run_start = Instant::now();
@@ -200,6 +344,7 @@ impl HygieneRunner {
             let should_emit_stats = if let Ok(mut stats) = self.state.hygiene_stats.lock() {
                 stats.runs_completed += 1;
                 stats.last_run_duration_ms = run_start.elapsed().as_millis() as u64;
+                stats.last_run_at = Some(chrono::Utc::now());
                 
                 // Check if we should emit stats (every 10 runs)
                 stats.runs_completed % 10 == 0
@@ -225,16 +370,44 @@ impl HygieneRunner {
         };
         info!("🧹 Starting hygiene cycle #{}", current_run);
         
-        // Use the working direct cleanup functions
-        let health_checks_cleaned = cleanup_health_checks(&self.state.db).await.map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
+        // Tenant configs are re-read every cycle, so a system.hygiene.config.v1 write applies from the next one
+        let policies = self.refresh_policies().await?;
+        let tenants = run_tenant_cleanup(&self.state, &self.config, &policies).await.map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
+        let keep_latest = tenants.keep_latest;
+        
+        let total_cleaned = tenants.expired + tenants.policy + keep_latest.iter().map(|d| d.deleted).sum::<u64>();
+        
+        cleanup_webhook_deliveries(&self.state.db, self.config.webhook_delivery_retention_days).await.map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
         
-        let expired_cleaned = cleanup_expired_breadcrumbs(&self.state.db).await.map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
+        cleanup_access_log(&self.state.db, self.config.access_log_retention_days).await.map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
         
-        let total_cleaned = health_checks_cleaned + expired_cleaned;
+        cleanup_agent_inbox(&self.state.db, self.config.inbox_retention_days).await.map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
+        
+        cleanup_expired_selectors(&self.state).await.map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
+        
+        attachments::cleanup_orphaned_attachments(&self.state, self.config.attachment_orphan_grace_hours).await.map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
+        
+        let history_pruned = history_retention::prune_breadcrumb_history(&self.state.db, &self.config.history_retention).await.map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
+        
+        if self.config.verify_checksums {
+            let cursor = self.checksum_cursor.lock().ok().and_then(|c| *c);
+            let next = checksums::run_hygiene_sample(&self.state, cursor, self.config.checksum_sample_size).await.map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
+            if let Ok(mut c) = self.checksum_cursor.lock() {
+                *c = next;
+            }
+        }
         
         // Update shared stats
         if let Ok(mut stats) = self.state.hygiene_stats.lock() {
             stats.total_breadcrumbs_purged += total_cleaned;
+            stats.total_agents_cleaned += tenants.agents;
+            stats.total_history_pruned += history_pruned;
+            stats.last_run_errors += tenants.failed;
+            for deletion in keep_latest {
+                stats.keep_latest_purged.entry(deletion.policy_id)
+                    .and_modify(|d| d.deleted += deletion.deleted)
+                    .or_insert(deletion);
+            }
         }
         
         let duration = cycle_start.elapsed();
@@ -248,6 +421,26 @@ impl HygieneRunner {
         Ok(())
     }
     
+    /// Load every tenant's TTL policies and hygiene config, falling back to the last good set
+    async fn refresh_policies(&self) -> Result<Arc<ttl_policy::TtlPolicies>, Box<dyn std::error::Error>> {
+        match ttl_policy::TtlPolicies::load(&self.state.db, None).await {
+            Ok(loaded) => {
+                let loaded = Arc::new(loaded);
+                if let Ok(mut policies) = self.policies.lock() {
+                    *policies = Some(loaded.clone());
+                }
+                Ok(loaded)
+            }
+            Err(e) => match self.policies.lock().ok().and_then(|p| p.clone()) {
+                Some(last) => {
+                    warn!("Failed to reload hygiene policies, using the previous cycle's: {}", e);
+                    Ok(last)
+                }
+                None => Err(Box::new(e)),
+            },
+        }
+    }
+    
     async fn cleanup_expired_breadcrumbs(&self) -> Result<u64, Box<dyn std::error::Error>> {
         info!("Cleaning up explicitly expired breadcrumbs...");
         
@@ -324,157 +517,7 @@ impl HygieneRunner {
     }
     
     async fn apply_implicit_ttl_policies(&self) -> Result<u64, Box<dyn std::error::Error>> {
-        let mut total_deleted = 0u64;
-        
-        // Health check breadcrumbs - expire after configured minutes
-        let healthcheck_query = format!(
-            "DELETE FROM breadcrumbs 
-             WHERE schema_name = 'tool.request.v1' 
-             AND tags @> ARRAY['health:check']
-             AND created_at < NOW() - INTERVAL '{} minutes'
-             AND ttl IS NULL",
-            self.config.healthcheck_ttl_minutes
-        );
-        
-        let result = sqlx::query(&healthcheck_query)
-            .execute(&self.state.db.pool)
-            .await?;
-        
-        total_deleted += result.rows_affected();
-        
-        // Temporary agent memory - expire after configured hours
-        let temp_memory_query = format!(
-            "DELETE FROM breadcrumbs 
-             WHERE schema_name IN ('agent.memory.v1', 'agent.temp_state.v1') 
-             AND created_at < NOW() - INTERVAL '{} hours'
-             AND ttl IS NULL",
-            self.config.temp_data_ttl_hours
-        );
-        
-        let result = sqlx::query(&temp_memory_query)
-            .execute(&self.state.db.pool)
-            .await?;
-        
-        total_deleted += result.rows_affected();
-        
-        // Tool execution logs - expire after configured days
-        let tool_logs_query = format!(
-            "DELETE FROM breadcrumbs 
-             WHERE schema_name IN ('tool.response.v1', 'tool.error.v1') 
-             AND tags @> ARRAY['log:execution']
-             AND created_at < NOW() - INTERVAL '{} days'
-             AND ttl IS NULL",
-            self.config.log_retention_days
-        );
-        
-        let result = sqlx::query(&tool_logs_query)
-            .execute(&self.state.db.pool)
-            .await?;
-        
-        total_deleted += result.rows_affected();
-        
-        // Performance metrics - keep only recent ones
-        let metrics_query = format!(
-            "DELETE FROM breadcrumbs 
-             WHERE schema_name IN ('agent.metrics.v1', 'tool.performance.v1') 
-             AND created_at < NOW() - INTERVAL '7 days'
-             AND ttl IS NULL"
-        );
-        
-        let result = sqlx::query(&metrics_query)
-            .execute(&self.state.db.pool)
-            .await?;
-        
-        total_deleted += result.rows_affected();
-        
-        Ok(total_deleted)
-    }
-    
-    async fn cleanup_expired_agents(&self) -> Result<u64, Box<dyn std::error::Error>> {
-        info!("Cleaning up expired/idle agents...");
-        
-        // Find agents that haven't been active recently
-        let idle_agents_query = format!(
-            "SELECT DISTINCT a.id, a.owner_id 
-             FROM agents a 
-             LEFT JOIN breadcrumbs b ON a.id = b.created_by OR a.id = b.updated_by
-             WHERE a.created_at < NOW() - INTERVAL '{} hours'
-             AND (b.updated_at IS NULL OR b.updated_at < NOW() - INTERVAL '{} hours')",
-            self.config.agent_max_idle_hours,
-            self.config.agent_max_idle_hours
-        );
-        
-        let idle_agents: Vec<(Uuid, Uuid)> = sqlx::query_as(&idle_agents_query)
-            .fetch_all(&self.state.db.pool)
-            .await?;
-        
-        let mut cleaned_count = 0u64;
-        
-        for (agent_id, owner_id) in idle_agents {
-            // Check if agent has any active subscriptions before deleting
-            let subscription_count: (i64,) = sqlx::query_as(
-                "SELECT COUNT(*) FROM selector_subscriptions WHERE owner_id = $1 AND agent_id = $2"
-            )
-            .bind(owner_id)
-            .bind(agent_id)
-            .fetch_one(&self.state.db.pool)
-            .await?;
-            
-            if subscription_count.0 == 0 {
-                // Safe to delete - no active subscriptions
-                match self.cleanup_agent(owner_id, agent_id).await {
-                    Ok(()) => {
-                        cleaned_count += 1;
-                        info!("Cleaned up idle agent: {}", agent_id);
-                    }
-                    Err(e) => {
-                        warn!("Failed to cleanup agent {}: {}", agent_id, e);
-                    }
-                }
-            }
-        }
-        
-        Ok(cleaned_count)
-    }
-    
-    async fn cleanup_agent(&self, owner_id: Uuid, agent_id: Uuid) -> Result<(), Box<dyn std::error::Error>> {
-        // 1. Clean up agent's breadcrumbs (if configured)
-        if self.config.agent_cleanup_on_exit {
-            let agent_breadcrumbs_query = 
-                "DELETE FROM breadcrumbs 
-                 WHERE owner_id = $1 
-                 AND (created_by = $2 OR updated_by = $2)
-                 AND tags @> ARRAY['agent:memory', 'temp:data']";
-            
-            sqlx::query(agent_breadcrumbs_query)
-                .bind(owner_id)
-                .bind(agent_id)
-                .execute(&self.state.db.pool)
-                .await?;
-        }
-        
-        // 2. Clean up agent's subscriptions
-        sqlx::query("DELETE FROM selector_subscriptions WHERE owner_id = $1 AND agent_id = $2")
-            .bind(owner_id)
-            .bind(agent_id)
-            .execute(&self.state.db.pool)
-            .await?;
-        
-        // 3. Clean up agent's webhooks
-        sqlx::query("UPDATE agent_webhooks SET active = false WHERE owner_id = $1 AND agent_id = $2")
-            .bind(owner_id)
-            .bind(agent_id)
-            .execute(&self.state.db.pool)
-            .await?;
-        
-        // 4. Delete the agent record
-        sqlx::query("DELETE FROM agents WHERE owner_id = $1 AND id = $2")
-            .bind(owner_id)
-            .bind(agent_id)
-            .execute(&self.state.db.pool)
-            .await?;
-        
-        Ok(())
+        Ok(cleanup_policy_expired(&self.state.db, &self.config).await?)
     }
     
     async fn cleanup_orphaned_subscriptions(&self) -> Result<u64, Box<dyn std::error::Error>> {
@@ -517,69 +560,6 @@ impl HygieneRunner {
         Ok(deleted)
     }
     
-    async fn apply_schema_ttl_policies(&self) -> Result<u64, Box<dyn std::error::Error>> {
-        let policies = vec![
-            // Health checks - very short lived
-            (
-                "health_checks",
-                format!("schema_name = 'tool.request.v1' AND tags @> ARRAY['health:check'] AND created_at < NOW() - INTERVAL '{} minutes'", self.config.healthcheck_ttl_minutes)
-            ),
-            
-            // Ping events - short lived
-            (
-                "ping_events", 
-                "schema_name = 'system.ping.v1' AND created_at < NOW() - INTERVAL '10 minutes'".to_string()
-            ),
-            
-            // Temporary tool responses - medium lived
-            (
-                "temp_tool_responses",
-                "schema_name = 'tool.response.v1' AND tags @> ARRAY['temp:result'] AND created_at < NOW() - INTERVAL '1 hour'".to_string()
-            ),
-            
-            // Agent thinking/analysis breadcrumbs - medium lived  
-            (
-                "agent_thinking",
-                "schema_name IN ('agent.thinking.v1', 'agent.analysis.v1') AND created_at < NOW() - INTERVAL '6 hours'".to_string()
-            ),
-            
-            // Old performance metrics - long lived but limited
-            (
-                "old_metrics",
-                "schema_name LIKE '%.metrics.v1' AND created_at < NOW() - INTERVAL '30 days'".to_string()
-            ),
-        ];
-        
-        let mut total_deleted = 0u64;
-        
-        for (policy_name, condition) in policies {
-            let query = format!(
-                "DELETE FROM breadcrumbs 
-                 WHERE {} 
-                 AND ttl IS NULL 
-                 LIMIT {}",
-                condition,
-                self.config.batch_size
-            );
-            
-            match sqlx::query(&query).execute(&self.state.db.pool).await {
-                Ok(result) => {
-                    let deleted = result.rows_affected();
-                    if deleted > 0 {
-                        info!("Policy '{}' cleaned up {} breadcrumbs", policy_name, deleted);
-                        total_deleted += deleted;
-                    }
-                }
-                Err(e) => {
-                    warn!("Policy '{}' failed: {}", policy_name, e);
-                    // Error already handled above
-                }
-            }
-        }
-        
-        Ok(total_deleted)
-    }
-    
     async fn emit_hygiene_stats(&self) -> Result<(), Box<dyn std::error::Error>> {
         // Get current stats (avoid holding mutex across await)
         let current_stats = if let Ok(stats) = self.state.hygiene_stats.lock() {
@@ -587,6 +567,14 @@ impl HygieneRunner {
         } else {
             return Ok(()); // Skip if can't get stats
         };
+        // Which tenants ran on their own system.hygiene.config.v1 rather than the env values
+        let mut tenant_configs: Vec<serde_json::Value> = self.policies.lock().ok().and_then(|p| p.clone())
+            .map(|policies| policies.hygiene.overridden().map(|owner_id| {
+                let config = policies.hygiene.for_owner(*owner_id);
+                json!({ "owner_id": owner_id, "config_id": config.source, "policies": config.policies })
+            }).collect())
+            .unwrap_or_default();
+        tenant_configs.sort_by_key(|c| c["owner_id"].as_str().map(String::from));
         
         // Create a hygiene stats breadcrumb for monitoring
         let stats_breadcrumb = json!({
@@ -595,14 +583,20 @@ impl HygieneRunner {
                 "runs_completed": current_stats.runs_completed,
                 "total_breadcrumbs_purged": current_stats.total_breadcrumbs_purged,
                 "total_agents_cleaned": current_stats.total_agents_cleaned,
+                "total_history_pruned": current_stats.total_history_pruned,
+                "keep_latest_purged": current_stats.keep_latest_purged.values().collect::<Vec<_>>(),
                 "last_run_duration_ms": current_stats.last_run_duration_ms,
                 "last_run_errors": current_stats.last_run_errors,
                 "next_run_in_seconds": self.config.run_interval_seconds,
                 "config": {
+                    "source": "env",
                     "healthcheck_ttl_minutes": self.config.healthcheck_ttl_minutes,
                     "temp_data_ttl_hours": self.config.temp_data_ttl_hours,
-                    "agent_max_idle_hours": self.config.agent_max_idle_hours
-                }
+                    "log_retention_days": self.config.log_retention_days,
+                    "agent_max_idle_hours": self.config.agent_max_idle_hours,
+                    "idle_agent_cleanup": self.config.idle_agent_cleanup
+                },
+                "tenant_configs": tenant_configs
             },
             "tags": ["system:hygiene", "system:stats", "internal:monitoring"],
             "schema_name": "system.hygiene.v1",
@@ -650,6 +644,8 @@ impl HygieneRunner {
                 ttl_type: Some("datetime".to_string()),
                 ttl_config: None,
                 ttl_source: Some("auto-applied".to_string()),
+                entity_keywords: None,
+                entities: None,
             },
             None // No embedding needed for stats
         ).await?;
@@ -658,54 +654,21 @@ impl HygieneRunner {
     }
 }
 
-/// Enhanced TTL middleware for breadcrumb creation
+/// Enhanced TTL middleware for breadcrumb creation: the first of `policies` (see
+/// TtlPolicyCache::policies) matching the schema and tags sets the TTL
 pub fn apply_auto_ttl(
     create_req: &mut rcrt_core::models::BreadcrumbCreate,
     schema_name: Option<&str>,
-    tags: &[String]
+    tags: &[String],
+    policies: &[ttl_policy::TtlPolicy]
 ) {
     // Don't override explicit TTL
     if create_req.ttl.is_some() {
         return;
     }
     
-    // Apply automatic TTL based on schema and tags
-    let auto_ttl = match schema_name {
-        // Health checks expire very quickly
-        Some("tool.request.v1") if tags.iter().any(|t| t.contains("health:check")) => {
-            Some(chrono::Utc::now() + chrono::Duration::minutes(5))
-        },
-        
-        // System pings expire quickly
-        Some("system.ping.v1") => {
-            Some(chrono::Utc::now() + chrono::Duration::minutes(10))
-        },
-        
-        // Temporary agent state
-        Some(schema) if schema.contains("agent.temp") => {
-            Some(chrono::Utc::now() + chrono::Duration::hours(1))
-        },
-        
-        // Agent thinking/analysis - medium term
-        Some("agent.thinking.v1") | Some("agent.analysis.v1") => {
-            Some(chrono::Utc::now() + chrono::Duration::hours(6))
-        },
-        
-        // Tool execution logs - keep for debugging but not forever
-        Some("tool.response.v1") | Some("tool.error.v1") if tags.iter().any(|t| t.contains("log:")) => {
-            Some(chrono::Utc::now() + chrono::Duration::days(7))
-        },
-        
-        // Performance metrics - keep for trends but expire eventually
-        Some(schema) if schema.contains("metrics.v1") => {
-            Some(chrono::Utc::now() + chrono::Duration::days(30))
-        },
-        
-        _ => None
-    };
-    
-    if auto_ttl.is_some() {
-        create_req.ttl = auto_ttl;
+    if let Some(policy) = policies.iter().find(|p| p.matches(schema_name, tags)) {
+        policy.apply(create_req);
     }
 }
 
@@ -735,13 +698,56 @@ pub fn load_hygiene_config() -> HygieneConfig {
         temp_data_ttl_hours: std::env::var("HYGIENE_TEMP_DATA_TTL_HOURS")
             .ok()
             .and_then(|s| s.parse().ok())
-            .unwrap_or(24), // 24 hours default
+            .unwrap_or(1), // 1 hour default
+        
+        log_retention_days: std::env::var("HYGIENE_LOG_RETENTION_DAYS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(7), // 7 days default
         
         agent_max_idle_hours: std::env::var("HYGIENE_AGENT_IDLE_HOURS")
             .ok()
             .and_then(|s| s.parse().ok())
             .unwrap_or(48), // 48 hours default
         
+        idle_agent_cleanup: std::env::var("HYGIENE_IDLE_AGENT_CLEANUP")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(false),
+        
+        webhook_delivery_retention_days: std::env::var("HYGIENE_WEBHOOK_DELIVERY_RETENTION_DAYS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(7), // 7 days default
+        
+        access_log_retention_days: std::env::var("HYGIENE_ACCESS_LOG_RETENTION_DAYS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(90), // 90 days default
+        
+        inbox_retention_days: std::env::var("HYGIENE_INBOX_RETENTION_DAYS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(7), // 7 days default
+        
+        attachment_orphan_grace_hours: std::env::var("HYGIENE_ATTACHMENT_ORPHAN_GRACE_HOURS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(1), // 1 hour default
+        
+        // Config::history_retention, which AppState::start_background_tasks puts in
+        history_retention: Default::default(),
+        
+        verify_checksums: std::env::var("HYGIENE_VERIFY_CHECKSUMS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(false),
+        
+        checksum_sample_size: std::env::var("HYGIENE_CHECKSUM_SAMPLE_SIZE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(100),
+        
         ..Default::default()
     }
 }
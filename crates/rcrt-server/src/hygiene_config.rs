@@ -0,0 +1,207 @@
+//! Tenant Hygiene Config
+//! Per-tenant overrides of the hygiene runner's retention settings from system.hygiene.config.v1 breadcrumbs;
+//! the run interval and batch limits stay global
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use rcrt_core::db::Db;
+use serde::Serialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::hygiene::{load_hygiene_config, HygieneConfig};
+
+/// `{"healthcheck_ttl_minutes": 15, "temp_data_ttl_hours": 6, "log_retention_days": 14, "agent_max_idle_hours": 72,
+/// "policies": {"keep_latest": false, "idle_agents": true}}`; every field is optional and falls back to the env value.
+/// The newest one per owner wins
+pub const HYGIENE_CONFIG: &str = "system.hygiene.config.v1";
+
+const DURATIONS: [&str; 4] = ["healthcheck_ttl_minutes", "temp_data_ttl_hours", "log_retention_days", "agent_max_idle_hours"];
+
+/// Which cleanups the runner applies to a tenant
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct HygienePolicies {
+    /// Breadcrumbs past their explicit `ttl`
+    pub expired_ttl: bool,
+    /// Breadcrumbs without a TTL that outlived their ttl.policy.v1 or built-in policy
+    pub ttl_policies: bool,
+    pub keep_latest: bool,
+    /// Agents without writes for `agent_max_idle_hours` and no selector subscriptions
+    pub idle_agents: bool,
+}
+
+/// A tenant's effective hygiene settings
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TenantHygiene {
+    /// The built-in `health checks` TTL policy
+    pub healthcheck_ttl_minutes: i64,
+    /// The built-in `temporary agent state` TTL policy
+    pub temp_data_ttl_hours: i64,
+    /// The built-in tool response and error log TTL policies
+    pub log_retention_days: i64,
+    pub agent_max_idle_hours: i64,
+    pub policies: HygienePolicies,
+    /// The system.hygiene.config.v1 breadcrumb these came from; None for the env defaults
+    pub source: Option<Uuid>,
+}
+
+impl TenantHygiene {
+    pub fn from_env(config: &HygieneConfig) -> Self {
+        TenantHygiene {
+            healthcheck_ttl_minutes: config.healthcheck_ttl_minutes,
+            temp_data_ttl_hours: config.temp_data_ttl_hours,
+            log_retention_days: config.log_retention_days,
+            agent_max_idle_hours: config.agent_max_idle_hours,
+            policies: HygienePolicies { expired_ttl: true, ttl_policies: true, keep_latest: true, idle_agents: config.idle_agent_cleanup },
+            source: None,
+        }
+    }
+
+    /// Validate a system.hygiene.config.v1 context over `defaults`; the error is fit for a 422
+    pub fn from_context(defaults: &TenantHygiene, id: Option<Uuid>, context: &Value) -> Result<Self, String> {
+        let fields = context.as_object().ok_or("context must be an object")?;
+        if let Some(unknown) = fields.keys().find(|k| *k != "policies" && !DURATIONS.contains(&k.as_str())) {
+            return Err(format!("unknown field {} (run intervals and batch sizes can't be set per tenant)", unknown));
+        }
+        let duration = |key: &str, default: i64| match fields.get(key) {
+            None | Some(Value::Null) => Ok(default),
+            Some(v) => v.as_i64().filter(|n| *n > 0).ok_or(format!("{} must be a positive integer", key)),
+        };
+
+        let mut policies = defaults.policies;
+        match fields.get("policies") {
+            None | Some(Value::Null) => {}
+            Some(Value::Object(flags)) => {
+                for (name, enabled) in flags {
+                    let enabled = enabled.as_bool().ok_or(format!("policies.{} must be true or false", name))?;
+                    match name.as_str() {
+                        "expired_ttl" => policies.expired_ttl = enabled,
+                        "ttl_policies" => policies.ttl_policies = enabled,
+                        "keep_latest" => policies.keep_latest = enabled,
+                        "idle_agents" => policies.idle_agents = enabled,
+                        _ => return Err(format!("unknown policy {} (expected expired_ttl, ttl_policies, keep_latest or idle_agents)", name)),
+                    }
+                }
+            }
+            Some(_) => return Err("policies must be an object".into()),
+        }
+
+        Ok(TenantHygiene {
+            healthcheck_ttl_minutes: duration("healthcheck_ttl_minutes", defaults.healthcheck_ttl_minutes)?,
+            temp_data_ttl_hours: duration("temp_data_ttl_hours", defaults.temp_data_ttl_hours)?,
+            log_retention_days: duration("log_retention_days", defaults.log_retention_days)?,
+            agent_max_idle_hours: duration("agent_max_idle_hours", defaults.agent_max_idle_hours)?,
+            policies,
+            source: id,
+        })
+    }
+}
+
+/// The HYGIENE_* environment values every tenant without a config gets; read once
+pub fn env_defaults() -> &'static TenantHygiene {
+    static DEFAULTS: OnceLock<TenantHygiene> = OnceLock::new();
+    DEFAULTS.get_or_init(|| TenantHygiene::from_env(&load_hygiene_config()))
+}
+
+/// Each tenant's system.hygiene.config.v1 over shared defaults
+#[derive(Debug, Clone)]
+pub struct HygieneConfigs {
+    defaults: TenantHygiene,
+    tenants: HashMap<Uuid, TenantHygiene>,
+}
+
+impl Default for HygieneConfigs {
+    fn default() -> Self {
+        Self { defaults: env_defaults().clone(), tenants: HashMap::new() }
+    }
+}
+
+impl HygieneConfigs {
+    /// `rows` are (owner_id, id, context), newest first; invalid configs are skipped, so an older
+    /// valid one (or the defaults) stays in force
+    pub fn from_breadcrumbs(defaults: TenantHygiene, rows: &[(Uuid, Uuid, Value)]) -> Self {
+        let mut tenants = HashMap::new();
+        for (owner_id, id, context) in rows {
+            if tenants.contains_key(owner_id) {
+                continue;
+            }
+            match TenantHygiene::from_context(&defaults, Some(*id), context) {
+                Ok(config) => { tenants.insert(*owner_id, config); }
+                Err(e) => tracing::warn!("Ignoring invalid {} breadcrumb {}: {}", HYGIENE_CONFIG, id, e),
+            }
+        }
+        HygieneConfigs { defaults, tenants }
+    }
+
+    pub async fn load(db: &Db, owner_id: Option<Uuid>) -> Result<Self, sqlx::Error> {
+        // Raw pool like the hygiene runner: it needs every tenant's config
+        let rows = sqlx::query_as::<_, (Uuid, Uuid, Value)>(
+            r#"SELECT owner_id, id, context FROM breadcrumbs
+               WHERE schema_name = $1 AND ($2::uuid IS NULL OR owner_id = $2)
+               ORDER BY updated_at DESC"#
+        )
+        .bind(HYGIENE_CONFIG)
+        .bind(owner_id)
+        .fetch_all(&db.pool)
+        .await?;
+        Ok(Self::from_breadcrumbs(env_defaults().clone(), &rows))
+    }
+
+    pub fn for_owner(&self, owner_id: Uuid) -> &TenantHygiene {
+        self.tenants.get(&owner_id).unwrap_or(&self.defaults)
+    }
+
+    /// Owners with a valid config of their own
+    pub fn overridden(&self) -> impl Iterator<Item = &Uuid> {
+        self.tenants.keys()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn defaults() -> TenantHygiene {
+        TenantHygiene::from_env(&HygieneConfig::default())
+    }
+
+    #[test]
+    fn test_config_overrides_only_what_it_sets() {
+        let id = Uuid::new_v4();
+        let config = TenantHygiene::from_context(&defaults(), Some(id), &json!({ "log_retention_days": 14, "policies": { "keep_latest": false } })).unwrap();
+        assert_eq!(config.log_retention_days, 14);
+        assert_eq!(config.healthcheck_ttl_minutes, defaults().healthcheck_ttl_minutes);
+        assert!(!config.policies.keep_latest && config.policies.expired_ttl);
+        assert_eq!(config.source, Some(id));
+        assert_eq!(TenantHygiene::from_context(&defaults(), None, &json!({})).unwrap(), defaults());
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let parse = |context: Value| TenantHygiene::from_context(&defaults(), None, &context);
+        assert!(parse(json!({ "healthcheck_ttl_minutes": 0 })).is_err());
+        assert!(parse(json!({ "temp_data_ttl_hours": -1 })).is_err());
+        assert!(parse(json!({ "log_retention_days": "7d" })).is_err());
+        assert!(parse(json!({ "run_interval_seconds": 60 })).is_err());
+        assert!(parse(json!({ "policies": { "idle_agents": "yes" } })).is_err());
+        assert!(parse(json!({ "policies": { "history": false } })).is_err());
+        assert!(parse(json!({ "policies": [] })).is_err());
+        assert!(parse(json!([])).is_err());
+    }
+
+    #[test]
+    fn test_newest_valid_config_per_owner_wins() {
+        let (owner, other) = (Uuid::new_v4(), Uuid::new_v4());
+        let (broken, newer, older) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let configs = HygieneConfigs::from_breadcrumbs(defaults(), &[
+            (owner, broken, json!({ "agent_max_idle_hours": 0 })),
+            (owner, newer, json!({ "agent_max_idle_hours": 72 })),
+            (owner, older, json!({ "agent_max_idle_hours": 24 })),
+        ]);
+        assert_eq!(configs.for_owner(owner).agent_max_idle_hours, 72);
+        assert_eq!(configs.for_owner(owner).source, Some(newer));
+        assert_eq!(configs.for_owner(other), &defaults());
+        assert_eq!(configs.overridden().collect::<Vec<_>>(), vec![&owner]);
+    }
+}
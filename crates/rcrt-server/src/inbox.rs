@@ -0,0 +1,72 @@
+//! Inbox
+//! Pull delivery for selectors with the inbox channel: fanout queues events in agent_inbox, agents
+//! lease the oldest unacknowledged ones and acknowledge them when done; an unacked lease that runs
+//! out hands the entry out again
+
+use axum::{extract::{Path, Query, State}, http::StatusCode, Json};
+use rcrt_core::roles::Role;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{auth::AuthContext, db_errors::db_error, AppState};
+
+const DEFAULT_LIMIT: i64 = 10;
+const MAX_LIMIT: i64 = 100;
+/// Longest lease a poller may ask for
+const MAX_LEASE_SECS: u64 = 3600;
+/// Ids one ack may carry
+const MAX_ACK_IDS: usize = 1000;
+
+#[derive(Deserialize)]
+pub struct InboxQuery {
+    limit: Option<i64>,
+    /// Seconds the entries stay hidden from other pollers; INBOX_LEASE_SECS when omitted
+    lease_secs: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct AckReq { ids: Vec<Uuid> }
+
+/// The agent itself, or a curator on its behalf
+fn authorize(auth: &AuthContext, agent_id: Uuid) -> Result<(), (StatusCode, String)> {
+    if auth.agent_id != agent_id && !auth.has_role(Role::Curator) {
+        return Err((StatusCode::FORBIDDEN, "forbidden".into()));
+    }
+    Ok(())
+}
+
+/// Lease up to `limit` of the agent's oldest unacknowledged entries, oldest first
+pub async fn lease(State(state): State<AppState>, auth: AuthContext, Path(agent_id): Path<Uuid>, Query(q): Query<InboxQuery>) -> Result<Json<Value>, (StatusCode, String)> {
+    authorize(&auth, agent_id)?;
+    let limit = q.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let lease_secs = q.lease_secs.unwrap_or(state.inbox_lease_secs).clamp(1, MAX_LEASE_SECS);
+    let entries = state.db.lease_inbox(auth.owner_id, agent_id, limit, lease_secs as i64).await.map_err(db_error)?;
+    Ok(Json(json!({ "entries": entries, "lease_secs": lease_secs })))
+}
+
+/// Acknowledge entries so they are never handed out again; `acked` counts the ones that were pending
+pub async fn ack(State(state): State<AppState>, auth: AuthContext, Path(agent_id): Path<Uuid>, Json(req): Json<AckReq>) -> Result<Json<Value>, (StatusCode, String)> {
+    authorize(&auth, agent_id)?;
+    if req.ids.is_empty() || req.ids.len() > MAX_ACK_IDS {
+        return Err((StatusCode::BAD_REQUEST, format!("ids must name between 1 and {} entries", MAX_ACK_IDS)));
+    }
+    let acked = state.db.ack_inbox(auth.owner_id, agent_id, &req.ids).await.map_err(db_error)?;
+    Ok(Json(json!({ "acked": acked })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_the_agent_or_a_curator_reaches_an_inbox() {
+        let agent_id = Uuid::new_v4();
+        let me = AuthContext { owner_id: Uuid::new_v4(), agent_id, roles: vec![Role::Subscriber] };
+        assert!(authorize(&me, agent_id).is_ok());
+        let other = AuthContext { agent_id: Uuid::new_v4(), ..me.clone() };
+        assert_eq!(authorize(&other, agent_id).unwrap_err().0, StatusCode::FORBIDDEN);
+        let curator = AuthContext { roles: vec![Role::Curator], ..other };
+        assert!(authorize(&curator, agent_id).is_ok());
+    }
+}
@@ -0,0 +1,111 @@
+//! Keyword Extraction
+//! Creation-time entity_keywords from cheap regex heuristics (EXTRACT_KEYWORDS_ON_CREATE); marked provisional so the context-builder still extracts
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+use rcrt_core::extraction::{breadcrumb_text, EntityExtractor, ExtractedEntities, EXTRACTED_BY_HEURISTIC};
+use serde_json::Value;
+
+/// Hybrid search only needs a handful of terms per breadcrumb
+const MAX_KEYWORDS: usize = 24;
+
+/// Capitalized words that start sentences rather than name anything
+const STOPWORDS: &[&str] = &[
+    "the", "this", "that", "these", "those", "there", "then", "when", "what", "which", "where", "who",
+    "why", "how", "and", "but", "for", "not", "you", "your", "our", "its", "his", "her", "they", "we",
+    "please", "thanks", "hello", "yes", "also", "after", "before", "with", "from", "into", "about",
+];
+
+fn name_pattern() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\b[A-Z][a-zA-Z0-9]{2,}\b").unwrap())
+}
+
+/// snake_case, kebab-case and camelCase identifiers
+fn identifier_pattern() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\b(?:[a-z][a-z0-9]*(?:[_-][a-z0-9]+)+|[a-z]+(?:[A-Z][a-z0-9]+)+)\b").unwrap())
+}
+
+fn hashtag_pattern() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"#([A-Za-z][A-Za-z0-9_-]+)").unwrap())
+}
+
+/// Keywords and the `entities` value (with `extracted_by: "heuristic"`) for a new breadcrumb,
+/// or None when nothing worth indexing was found
+pub fn extract_keywords(extractor: &EntityExtractor, title: &str, context: &Value) -> Option<(Vec<String>, Value)> {
+    let text = breadcrumb_text(Some(title), context);
+    // The core extractor's vocabulary comes first: query-side keywords are drawn from it
+    let mut extracted = extractor.extract(&text).unwrap_or_default();
+
+    let mut add = |kind: &str, value: &str| {
+        let value = value.to_lowercase();
+        let values = extracted.entities.entry(kind.to_string()).or_default();
+        if !values.contains(&value) {
+            values.push(value.clone());
+        }
+        extracted.keywords.push(value);
+    };
+    for cap in hashtag_pattern().captures_iter(&text) {
+        add("tag", &cap[1]);
+    }
+    for m in identifier_pattern().find_iter(&text) {
+        add("identifier", m.as_str());
+    }
+    for m in name_pattern().find_iter(&text) {
+        if !STOPWORDS.contains(&m.as_str().to_lowercase().as_str()) {
+            add("name", m.as_str());
+        }
+    }
+
+    let ExtractedEntities { entities, keywords } = extracted;
+    let mut seen = std::collections::HashSet::new();
+    let keywords: Vec<String> = keywords.into_iter()
+        .filter(|k| seen.insert(k.clone()))
+        .take(MAX_KEYWORDS)
+        .collect();
+    if keywords.is_empty() {
+        return None;
+    }
+    let entities = ExtractedEntities { entities, keywords: Vec::new() }.to_entities_json(EXTRACTED_BY_HEURISTIC);
+    Some((keywords, entities))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn extract(title: &str, context: Value) -> Option<(Vec<String>, Value)> {
+        extract_keywords(&EntityExtractor::new().unwrap(), title, &context)
+    }
+
+    #[test]
+    fn test_names_identifiers_and_hashtags() {
+        let (keywords, entities) = extract(
+            "Deploy notes",
+            json!({ "content": "The Kubernetes rollout of user_service and fetchOrders is done #release-42" }),
+        ).unwrap();
+        for expected in ["kubernetes", "user_service", "fetchorders", "release-42", "deploy"] {
+            assert!(keywords.contains(&expected.to_string()), "missing {expected} in {keywords:?}");
+        }
+        assert!(!keywords.contains(&"the".to_string()));
+        assert_eq!(entities["extracted_by"], "heuristic");
+        assert_eq!(entities["tag"], json!(["release-42"]));
+    }
+
+    #[test]
+    fn test_keywords_deduplicated_and_capped() {
+        let content = (0..40).map(|i| format!("Widget{i} Widget{i}")).collect::<Vec<_>>().join(" ");
+        let (keywords, _) = extract("t", json!({ "content": content })).unwrap();
+        assert_eq!(keywords.len(), MAX_KEYWORDS);
+        assert_eq!(keywords.iter().collect::<std::collections::HashSet<_>>().len(), MAX_KEYWORDS);
+    }
+
+    #[test]
+    fn test_nothing_found_is_none() {
+        assert!(extract("ok", json!({ "content": "all good here" })).is_none());
+    }
+}
@@ -0,0 +1,207 @@
+//! Large Context Values
+//! String values over CONTEXT_EXTERNALIZE_MIN_BYTES are moved into the tenant's attachments and
+//! replaced by `{"$rcrt_ref": sha256, "bytes": N, "content_type": "text/plain"}`, so history rows,
+//! events and embeddings only carry the reference. Reads put the text back on request. Updates
+//! never unlink a value, so older history versions keep resolving
+
+use std::collections::{BTreeMap, HashMap};
+use axum::http::StatusCode;
+use rcrt_core::models::{AttachmentBody, NewAttachment};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncReadExt;
+use uuid::Uuid;
+
+use crate::{attachments, db_errors::db_error, internal_error, schema_registry, ttl_policy, AppState};
+
+pub const REF_KEY: &str = "$rcrt_ref";
+const CONTENT_TYPE: &str = "text/plain";
+
+/// Values moved out of a context, linked to the breadcrumb once it is written
+#[derive(Default)]
+pub struct Externalized {
+    /// Bytes the tenant didn't store yet
+    new: Vec<NewAttachment>,
+    /// Referenced shas the tenant already stores, including references sent back unchanged
+    held: Vec<String>,
+}
+
+/// Move `context`'s large string values out, storing bytes the tenant doesn't hold yet. Schema
+/// definitions, TTL policies and hygiene configs are read by the server itself and stay whole
+pub async fn externalize(state: &AppState, owner_id: Uuid, schema_name: Option<&str>, context: &mut Value) -> Result<Externalized, (StatusCode, String)> {
+    if kept_whole(state, schema_name) {
+        return Ok(Externalized::default());
+    }
+    let mut values = BTreeMap::new();
+    extract(context, state.externalize_min_bytes, &mut values);
+    let mut shas = references(context);
+    if shas.is_empty() {
+        return Ok(Externalized::default());
+    }
+    shas.sort();
+    shas.dedup();
+    let held = state.db.held_attachments(owner_id, &shas).await.map_err(db_error)?;
+
+    let inline_max_bytes = state.attachment_limits.inline_max_bytes;
+    let mut new = Vec::new();
+    for (sha256, text) in values {
+        if held.contains(&sha256) {
+            continue;
+        }
+        let bytes = text.into_bytes();
+        let size_bytes = bytes.len() as i64;
+        let body = if bytes.len() <= inline_max_bytes {
+            AttachmentBody::Inline(bytes)
+        } else {
+            let key = attachments::store_key(owner_id, &sha256);
+            state.attachment_store.put(&key, &bytes).await.map_err(internal_error)?;
+            AttachmentBody::Stored(key)
+        };
+        new.push(NewAttachment { sha256, content_type: CONTENT_TYPE.to_string(), size_bytes, filename: None, body });
+    }
+    Ok(Externalized { new, held })
+}
+
+/// A value `externalize_preview` found; sha256 and size in bytes
+#[derive(Debug, Clone, Serialize)]
+pub struct ExternalValue {
+    pub sha256: String,
+    pub bytes: usize,
+}
+
+/// Replace `context`'s large string values with references as `externalize` would, storing nothing
+pub fn externalize_preview(state: &AppState, schema_name: Option<&str>, context: &mut Value) -> Vec<ExternalValue> {
+    if kept_whole(state, schema_name) {
+        return Vec::new();
+    }
+    let mut values = BTreeMap::new();
+    extract(context, state.externalize_min_bytes, &mut values);
+    values.into_iter().map(|(sha256, text)| ExternalValue { sha256, bytes: text.len() }).collect()
+}
+
+fn kept_whole(state: &AppState, schema_name: Option<&str>) -> bool {
+    state.externalize_min_bytes == 0 || schema_name.is_some_and(|s| s == schema_registry::SCHEMA_DEF || ttl_policy::is_policy_schema(s))
+}
+
+/// Link what `externalize` moved out to the written breadcrumb, before its event goes out
+pub async fn link(state: &AppState, owner_id: Uuid, agent_id: Uuid, breadcrumb_id: Uuid, externalized: Externalized) -> Result<(), (StatusCode, String)> {
+    if externalized.new.is_empty() && externalized.held.is_empty() {
+        return Ok(());
+    }
+    state.db.link_context_values(owner_id, agent_id, breadcrumb_id, externalized.new, &externalized.held).await.map_err(db_error)
+}
+
+/// Put the text back in place of each reference the reader can see; references that don't resolve stay
+pub async fn inline(state: &AppState, owner_id: Uuid, agent_id: Uuid, context: &mut Value) -> Result<(), (StatusCode, String)> {
+    let mut texts = HashMap::new();
+    for sha256 in references(context) {
+        if texts.contains_key(&sha256) {
+            continue;
+        }
+        let Some(att) = state.db.get_attachment(owner_id, Some(agent_id), &sha256).await.map_err(db_error)? else {
+            tracing::warn!("Context reference {} doesn't resolve for agent {}, leaving it in place", sha256, agent_id);
+            continue;
+        };
+        let bytes = match att.body {
+            AttachmentBody::Inline(bytes) => bytes,
+            AttachmentBody::Stored(key) => {
+                let mut bytes = Vec::new();
+                state.attachment_store.open(&key).await.map_err(internal_error)?.read_to_end(&mut bytes).await.map_err(internal_error)?;
+                bytes
+            }
+        };
+        texts.insert(sha256, String::from_utf8_lossy(&bytes).into_owned());
+    }
+    if !texts.is_empty() {
+        replace(context, &texts);
+    }
+    Ok(())
+}
+
+/// Replace string values of more than `min_bytes` with references, collecting them by sha256
+fn extract(value: &mut Value, min_bytes: usize, values: &mut BTreeMap<String, String>) {
+    match value {
+        Value::String(text) if text.len() > min_bytes => {
+            let sha256 = hex::encode(Sha256::digest(text.as_bytes()));
+            let reference = json!({ REF_KEY: sha256, "bytes": text.len(), "content_type": CONTENT_TYPE });
+            values.insert(sha256, std::mem::take(text));
+            *value = reference;
+        }
+        Value::Object(map) => map.values_mut().for_each(|v| extract(v, min_bytes, values)),
+        Value::Array(items) => items.iter_mut().for_each(|v| extract(v, min_bytes, values)),
+        _ => {}
+    }
+}
+
+/// The sha256 of a reference object
+fn reference(value: &Value) -> Option<&str> {
+    let sha256 = value.as_object()?.get(REF_KEY)?.as_str()?;
+    (sha256.len() == 64 && sha256.bytes().all(|b| b.is_ascii_hexdigit())).then_some(sha256)
+}
+
+fn references(value: &Value) -> Vec<String> {
+    let mut shas = Vec::new();
+    let mut stack = vec![value];
+    while let Some(value) = stack.pop() {
+        if let Some(sha256) = reference(value) {
+            shas.push(sha256.to_ascii_lowercase());
+            continue;
+        }
+        match value {
+            Value::Object(map) => stack.extend(map.values()),
+            Value::Array(items) => stack.extend(items),
+            _ => {}
+        }
+    }
+    shas
+}
+
+fn replace(value: &mut Value, texts: &HashMap<String, String>) {
+    if let Some(text) = reference(value).and_then(|sha256| texts.get(&sha256.to_ascii_lowercase())).cloned() {
+        *value = Value::String(text);
+        return;
+    }
+    match value {
+        Value::Object(map) => map.values_mut().for_each(|v| replace(v, texts)),
+        Value::Array(items) => items.iter_mut().for_each(|v| replace(v, texts)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_and_replace_round_trip() {
+        let page = "x".repeat(100);
+        let original = json!({ "url": "https://example.com", "page": page, "turns": [page, "hi"], "n": 3 });
+        let mut context = original.clone();
+        let mut values = BTreeMap::new();
+        extract(&mut context, 32, &mut values);
+
+        // One stored value for the two copies
+        assert_eq!(values.len(), 1);
+        let sha256 = values.keys().next().unwrap().clone();
+        assert_eq!(context["page"], json!({ REF_KEY: sha256, "bytes": 100, "content_type": "text/plain" }));
+        assert_eq!(context["turns"][0], context["page"]);
+        assert_eq!(context["turns"][1], "hi");
+        assert_eq!(context["url"], "https://example.com");
+        assert_eq!(references(&context).len(), 2);
+
+        // A reference is never externalized again
+        let before = context.clone();
+        extract(&mut context, 32, &mut BTreeMap::new());
+        assert_eq!(context, before);
+
+        replace(&mut context, &values.into_iter().collect());
+        assert_eq!(context, original);
+    }
+
+    #[test]
+    fn test_malformed_references_are_ignored() {
+        let context = json!({ "a": { REF_KEY: "not-a-sha" }, "b": { REF_KEY: 7 } });
+        assert!(references(&context).is_empty());
+    }
+}
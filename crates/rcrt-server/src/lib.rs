@@ -0,0 +1,506 @@
+//! RCRT Server
+//! AppState, its construction from Config, and the HTTP router; main.rs only reads the environment and serves
+
+use std::sync::{Arc, Mutex};
+use axum::{extract::DefaultBodyLimit, routing::{get, post, put, delete}, Router};
+use rcrt_core::db::Db;
+use sqlx::migrate::Migrator;
+use tower_http::cors::{CorsLayer, Any};
+use uuid::Uuid;
+
+pub mod auth;
+pub mod config;
+pub mod replica;
+pub mod service;
+pub mod telemetry;
+mod access_log;
+mod acl;
+mod admin;
+mod agent_runs;
+mod agents;
+mod analytics;
+mod api_keys;
+mod attachments;
+mod breadcrumb_filter;
+mod breadcrumbs;
+mod checksums;
+mod compression;
+mod coordination;
+mod db_errors;
+mod docs;
+mod domain_metrics;
+mod embedding;
+mod embedding_models;
+mod embedding_policy;
+mod envelope;
+mod events;
+mod fanout_access;
+mod history_retention;
+mod hygiene;
+mod hygiene_config;
+mod inbox;
+mod keywords;
+mod large_values;
+mod observability;
+mod outbox;
+mod payload_versions;
+mod priority;
+mod purge;
+mod rate_limit;
+mod references;
+mod request_id;
+mod schema_registry;
+mod search_cache;
+mod secrets;
+mod selector_match;
+mod selectors;
+mod session_inference;
+mod session_stats;
+mod share_links;
+mod stats;
+mod suggest;
+mod templates;
+mod tenant_deletion;
+mod tenants;
+mod topology;
+mod transforms;
+mod ttl_policy;
+mod version_diff;
+mod view_cache;
+mod webhooks;
+#[cfg(feature = "nats")]
+mod sse_queue;
+#[cfg(feature = "nats")]
+mod event_bus;
+#[cfg(test)]
+mod test_support;
+
+pub use config::Config;
+
+static MIGRATOR: Migrator = sqlx::migrate!("../../migrations");
+
+#[derive(Clone)]
+pub struct AppState {
+    /// The primary; writes and reads that must see them
+    db: Db,
+    /// Pure reads; Config::db_replica_*, the primary alone in `new`
+    reads: Arc<replica::ReadRouter>,
+    /// Config::db_replica_lag_check_secs; 5s in `new`
+    replica_lag_check: std::time::Duration,
+    auth: Arc<auth::AuthConfig>,
+    #[cfg(feature = "nats")]
+    nats_conn: Option<async_nats::Client>,
+    #[cfg(feature = "nats")]
+    event_bus: Arc<event_bus::EventBus>,
+    hygiene_stats: Arc<Mutex<hygiene::HygieneStats>>,
+    schema_cache: Arc<transforms::SchemaDefinitionCache>,
+    selector_cache: Arc<selector_match::SelectorMatcherCache>,
+    /// Per-owner fanout index; Config::selector_index_max_age_secs, 60s in `new`
+    selector_index: Arc<selector_match::SelectorIndexCache>,
+    schema_registry: Arc<schema_registry::SchemaRegistry>,
+    ttl_policies: Arc<ttl_policy::TtlPolicyCache>,
+    entity_extractor: Arc<rcrt_core::extraction::EntityExtractor>,
+    extract_limiter: Arc<rate_limit::RateLimiter<Uuid>>,
+    /// Attachments above the inline size; Config::attachment_dir, a temp directory in `new`
+    attachment_store: Arc<dyn attachments::AttachmentStore>,
+    /// Config::extract_keywords_on_create; off in `new`
+    extract_keywords_on_create: bool,
+    /// Config::embed_title_separately; off in `new`
+    embed_title_separately: bool,
+    /// Config::search_title_weight; 0.5 in `new`
+    search_title_weight: f32,
+    /// Config::embed_sensitivity_max; secret (embed everything) in `new`
+    embed_sensitivity_max: rcrt_core::models::Sensitivity,
+    /// Each owner's system.embedding.config.v1, which search ranks by
+    active_models: Arc<embedding_models::ActiveModels>,
+    /// EMBED_TARGET_MODEL_NAME's model, embedded with alongside the column model; read from the environment in `new` too
+    target_model: Option<Arc<embedding_models::TargetModel>>,
+    /// Config::embed_backfill_per_sec; unpaced in `new`
+    embed_backfill_per_sec: u32,
+    /// Config::embed_cutover_min_coverage; 0.99 in `new`
+    embed_cutover_min_coverage: f64,
+    /// Config::api_key_cache_ttl_secs; 30s in `new`
+    api_keys: Arc<api_keys::ApiKeyCache>,
+    /// Config::agent_run_retention_hours and agent_run_stale_secs; 24h and 15min in `new`
+    agent_runs: Arc<agent_runs::AgentRuns>,
+    /// Config::sse_ping_interval_secs; 5s in `new`
+    sse_heartbeat: Arc<events::Heartbeat>,
+    /// Config::encrypt_secret_contexts; off in `new`
+    encrypt_secret_contexts: bool,
+    /// Config::context_externalize_min_bytes; 32 KiB in `new`
+    externalize_min_bytes: usize,
+    /// Config::cache_context_views and context_view_cache_max_entries; off in `new`
+    view_cache: Option<Arc<view_cache::ContextViewCache>>,
+    /// Config::search_cache_* and query_embedding_cache_*; the ONNX embedder with 10s/1000 and 1h/1000 in `new`
+    search_cache: Arc<search_cache::SearchCache>,
+    /// Access counts waiting for the next flush
+    access_log: Arc<access_log::AccessLog>,
+    /// Config::priority_* and load_shed_*; 64 interactive and 2 batch slots, shedding after 250ms for 5s, in `new`
+    priority: Arc<priority::PriorityGate>,
+    /// Config::webhook_auto_disable_after_failures; never in `new`
+    webhook_auto_disable_after_failures: u32,
+    /// Config::webhook_ordered_queue_max; 100 in `new`
+    webhook_queues: Arc<webhooks::OrderedQueues>,
+    /// Config::session_tag_inference; off in `new`
+    session_tag_inference: session_inference::SessionInference,
+    /// Tenants whose writes are refused while DELETE /tenants/:id runs
+    deleting_tenants: Arc<tenant_deletion::DeletingTenants>,
+    /// Config::share_link_secret and share_link_rate_per_min; a random secret and 60 a minute in `new`
+    share_links: Arc<share_links::ShareLinks>,
+    /// Config::inbox_lease_secs; 30 in `new`
+    inbox_lease_secs: u64,
+    /// Config::purge_max_per_request; 10000 in `new`
+    purge_max_per_request: i64,
+    /// Config::purge_batch_size; 500 in `new`
+    purge_batch_size: i64,
+    /// Config::webhook_max_retries and webhook_retry_after_max_secs; 8 attempts and 300s in `new`
+    webhook_retry: webhooks::RetryPolicy,
+    /// Config::attachment_*; 256 KiB inline, 25 MiB max and a 1 GiB quota in `new`
+    attachment_limits: attachments::AttachmentLimits,
+    /// Config::diff_*; 1000 ops, 256 KiB and 1000 elements in `new`
+    diff_limits: version_diff::Limits,
+    /// Config::sse_channel_capacity; 1000 in `new`
+    #[cfg(feature = "nats")]
+    sse_capacity: usize,
+    /// Config::sse_overflow_drop_oldest; Disconnect in `new`
+    #[cfg(feature = "nats")]
+    sse_overflow: sse_queue::OverflowPolicy,
+    /// Config::history_retention, which the hygiene runner prunes by too; the defaults in `new`
+    history_retention: history_retention::HistoryRetentionConfig,
+    /// Config::extract_max_input_bytes; 64 KiB in `new`
+    extract_max_input_bytes: usize,
+    /// Config::session_close_batch_size; 500 in `new`
+    session_close_batch_size: i64,
+    /// Config::session_close_ttl_hours; 168 (7 days) in `new`
+    session_close_ttl_hours: i64,
+}
+
+impl AppState {
+    /// Connect to Postgres (migrating as Config::migrations says and creating the default tenant) and,
+    /// with the `nats` feature, to NATS; fails fast if either is unreachable
+    pub async fn from_config(config: Config) -> anyhow::Result<Self> {
+        let db = Db::connect(&config.db_url, config.owner_id, None).await?;
+        coordination::prepare_schema(&db, config.migrations, std::time::Duration::from_secs(config.migration_wait_secs)).await?;
+        // Ensure default tenant exists (prevents FK violations on first boot)
+        db.ensure_tenant(config.owner_id, "Default Tenant").await?;
+        let settings = config.auth;
+        let mut auth = auth::AuthConfig::new(
+            settings.mode,
+            settings.public_key_pem.as_deref(),
+            settings.private_key_pem.as_deref(),
+            settings.issuer,
+            settings.audience,
+        )?;
+        auth.metrics_token = settings.metrics_token;
+        auth.admin_accepts_curator = settings.admin_accepts_curator;
+        if auth.admin_accepts_curator {
+            tracing::warn!("ADMIN_ACCEPTS_CURATOR is on: curator tokens can use the /admin routes");
+        }
+
+        #[cfg(feature = "nats")]
+        let state = {
+            use anyhow::Context;
+            let nats_url = config.nats_url.context("NATS_URL not set")?;
+            let conn = events::connect(&nats_url).await.context("failed to connect to NATS")?;
+            tracing::info!("✅ Connected to NATS at {}", nats_url);
+            // `new` gives the bus the default publish timeout
+            let publish_timeout = std::time::Duration::from_millis(config.nats_publish_timeout_ms);
+            Self::new(db, auth, conn.clone(), config.extract_rate_per_min)
+                .map(|s| Self { event_bus: event_bus::EventBus::new(conn, s.db.clone(), publish_timeout), ..s })
+        };
+        #[cfg(not(feature = "nats"))]
+        let state = Self::new(db, auth, config.extract_rate_per_min);
+        // Refuse to start encrypting secret contexts that could never be read back
+        if config.encrypt_secret_contexts {
+            envelope::local_kek().map_err(|(_, e)| anyhow::anyhow!("ENCRYPT_SECRET_CONTEXTS needs a KEK: {}", e))?;
+        }
+        let share_link_secret = match config.share_link_secret {
+            Some(secret) => secret.into_bytes(),
+            None => {
+                tracing::warn!("SHARE_LINK_SECRET is not set: share links only work on this replica until it restarts");
+                rand::random::<[u8; 32]>().to_vec()
+            }
+        };
+        // Lazily connected, so a replica that is down at startup only means reading from the primary
+        let reads = match &config.db_replica_url {
+            Some(url) => {
+                let replica = Db::connect_lazy(url, config.owner_id, None)?;
+                tracing::info!("📚 Read replica configured; reads prefer it within {}ms of the primary", config.db_replica_max_staleness_ms);
+                replica::ReadRouter::new(db.clone(), replica, std::time::Duration::from_millis(config.db_replica_max_staleness_ms))
+            }
+            None => replica::ReadRouter::primary_only(db.clone()),
+        };
+        state.map(|s| Self {
+            reads: Arc::new(reads),
+            replica_lag_check: std::time::Duration::from_secs(config.db_replica_lag_check_secs),
+            extract_keywords_on_create: config.extract_keywords_on_create,
+            embed_title_separately: config.embed_title_separately,
+            search_title_weight: config.search_title_weight,
+            embed_sensitivity_max: config.embed_sensitivity_max,
+            embed_backfill_per_sec: config.embed_backfill_per_sec,
+            embed_cutover_min_coverage: config.embed_cutover_min_coverage,
+            webhook_auto_disable_after_failures: config.webhook_auto_disable_after_failures,
+            webhook_queues: Arc::new(webhooks::OrderedQueues::new(config.webhook_ordered_queue_max)),
+            session_tag_inference: config.session_tag_inference,
+            share_links: Arc::new(share_links::ShareLinks::new(share_link_secret, config.share_link_rate_per_min)),
+            inbox_lease_secs: config.inbox_lease_secs,
+            selector_index: Arc::new(selector_match::SelectorIndexCache::new(std::time::Duration::from_secs(config.selector_index_max_age_secs))),
+            api_keys: Arc::new(api_keys::ApiKeyCache::new(std::time::Duration::from_secs(config.api_key_cache_ttl_secs))),
+            agent_runs: Arc::new(agent_runs::AgentRuns::new(
+                std::time::Duration::from_secs(config.agent_run_retention_hours.saturating_mul(3600)),
+                std::time::Duration::from_secs(config.agent_run_stale_secs),
+            )),
+            attachment_store: Arc::new(attachments::FsStore::new(config.attachment_dir)),
+            sse_heartbeat: Arc::new(events::Heartbeat::new(std::time::Duration::from_secs(config.sse_ping_interval_secs))),
+            encrypt_secret_contexts: config.encrypt_secret_contexts,
+            externalize_min_bytes: config.context_externalize_min_bytes,
+            view_cache: config.cache_context_views.then(|| Arc::new(view_cache::ContextViewCache::new(config.context_view_cache_max_entries))),
+            search_cache: Arc::new(search_cache::SearchCache::new(
+                Arc::new(embedding::OnnxEmbedder),
+                std::time::Duration::from_secs(config.search_cache_ttl_secs),
+                config.search_cache_max_entries,
+                std::time::Duration::from_secs(config.query_embedding_cache_ttl_secs),
+                config.query_embedding_cache_max_entries,
+            )),
+            priority: Arc::new(priority::PriorityGate::new(
+                config.priority_interactive_concurrency,
+                config.priority_batch_concurrency,
+                std::time::Duration::from_millis(config.load_shed_wait_ms),
+                std::time::Duration::from_secs(config.load_shed_retry_after_secs),
+            )),
+            purge_max_per_request: config.purge_max_per_request,
+            purge_batch_size: config.purge_batch_size,
+            webhook_retry: webhooks::RetryPolicy::new(config.webhook_max_retries, std::time::Duration::from_secs(config.webhook_retry_after_max_secs)),
+            attachment_limits: attachments::AttachmentLimits { inline_max_bytes: config.attachment_inline_max_bytes, max_bytes: config.attachment_max_bytes, tenant_quota_bytes: config.attachment_tenant_quota_bytes },
+            diff_limits: version_diff::Limits { max_ops: config.diff_max_ops, max_bytes: config.diff_max_bytes, max_array_len: config.diff_max_array_len },
+            #[cfg(feature = "nats")]
+            sse_capacity: config.sse_channel_capacity,
+            #[cfg(feature = "nats")]
+            sse_overflow: if config.sse_overflow_drop_oldest { sse_queue::OverflowPolicy::DropOldest } else { sse_queue::OverflowPolicy::Disconnect },
+            history_retention: config.history_retention,
+            extract_max_input_bytes: config.extract_max_input_bytes,
+            session_close_batch_size: config.session_close_batch_size,
+            session_close_ttl_hours: config.session_close_ttl_hours,
+            ..s
+        })
+    }
+
+    /// State over an already-migrated database; no startup checks, so tests can pass a lazy pool and NATS client
+    pub fn new(db: Db, auth: auth::AuthConfig, #[cfg(feature = "nats")] nats_conn: async_nats::Client, extract_rate_per_min: u32) -> anyhow::Result<Self> {
+        // Initialize schema definition cache for llm_hints
+        let schema_cache = Arc::new(transforms::SchemaDefinitionCache::new(Arc::new(db.clone())));
+        // Entity extractor (same pipeline as the context-builder worker)
+        let entity_extractor = Arc::new(rcrt_core::extraction::EntityExtractor::new()?);
+        Ok(AppState {
+            #[cfg(feature = "nats")]
+            event_bus: event_bus::EventBus::new(nats_conn.clone(), db.clone(), std::time::Duration::from_secs(2)),
+            #[cfg(feature = "nats")]
+            nats_conn: Some(nats_conn),
+            auth: Arc::new(auth),
+            hygiene_stats: Arc::new(Mutex::new(hygiene::HygieneStats::default())),
+            schema_cache,
+            selector_cache: Arc::new(selector_match::SelectorMatcherCache::new()),
+            selector_index: Arc::new(selector_match::SelectorIndexCache::new(std::time::Duration::from_secs(60))),
+            schema_registry: Arc::new(schema_registry::SchemaRegistry::new(db.clone())),
+            ttl_policies: Arc::new(ttl_policy::TtlPolicyCache::new(db.clone())),
+            entity_extractor,
+            extract_limiter: Arc::new(rate_limit::RateLimiter::new(extract_rate_per_min, std::time::Duration::from_secs(60))),
+            extract_keywords_on_create: false,
+            embed_title_separately: false,
+            search_title_weight: 0.5,
+            embed_sensitivity_max: rcrt_core::models::Sensitivity::Secret,
+            active_models: Arc::new(embedding_models::ActiveModels::new(db.clone())),
+            target_model: embedding_models::TargetModel::from_env().map(Arc::new),
+            embed_backfill_per_sec: 0,
+            embed_cutover_min_coverage: 0.99,
+            api_keys: Arc::new(api_keys::ApiKeyCache::new(std::time::Duration::from_secs(30))),
+            agent_runs: Arc::new(agent_runs::AgentRuns::new(std::time::Duration::from_secs(24 * 3600), std::time::Duration::from_secs(15 * 60))),
+            attachment_store: Arc::new(attachments::FsStore::new(std::env::temp_dir().join("rcrt-attachments"))),
+            sse_heartbeat: Arc::new(events::Heartbeat::new(std::time::Duration::from_secs(5))),
+            encrypt_secret_contexts: false,
+            externalize_min_bytes: 32 * 1024,
+            view_cache: None,
+            search_cache: Arc::new(search_cache::SearchCache::new(
+                Arc::new(embedding::OnnxEmbedder),
+                std::time::Duration::from_secs(10),
+                1000,
+                std::time::Duration::from_secs(3600),
+                1000,
+            )),
+            access_log: Arc::new(access_log::AccessLog::default()),
+            priority: Arc::new(priority::PriorityGate::new(64, 2, std::time::Duration::from_millis(250), std::time::Duration::from_secs(5))),
+            webhook_auto_disable_after_failures: 0,
+            webhook_queues: Arc::new(webhooks::OrderedQueues::new(100)),
+            session_tag_inference: session_inference::SessionInference::Off,
+            reads: Arc::new(replica::ReadRouter::primary_only(db.clone())),
+            replica_lag_check: std::time::Duration::from_secs(5),
+            deleting_tenants: Arc::new(tenant_deletion::DeletingTenants::default()),
+            share_links: Arc::new(share_links::ShareLinks::new(rand::random::<[u8; 32]>().to_vec(), 60)),
+            inbox_lease_secs: 30,
+            purge_max_per_request: 10_000,
+            purge_batch_size: 500,
+            webhook_retry: webhooks::RetryPolicy::new(8, std::time::Duration::from_secs(300)),
+            attachment_limits: attachments::AttachmentLimits { inline_max_bytes: 256 * 1024, max_bytes: 25 * 1024 * 1024, tenant_quota_bytes: 1024 * 1024 * 1024 },
+            diff_limits: version_diff::Limits { max_ops: 1000, max_bytes: 256 * 1024, max_array_len: 1000 },
+            #[cfg(feature = "nats")]
+            sse_capacity: 1000,
+            #[cfg(feature = "nats")]
+            sse_overflow: sse_queue::OverflowPolicy::Disconnect,
+            history_retention: history_retention::HistoryRetentionConfig::default(),
+            extract_max_input_bytes: 64 * 1024,
+            session_close_batch_size: 500,
+            session_close_ttl_hours: 168,
+            db,
+        })
+    }
+
+    /// Hygiene runner, outbox dispatcher, NATS event replay, the domain metrics sampler, the agent run
+    /// sweeper, the access log flusher, the replica lag monitor, the deleting tenants refresher and the embedding model warm-up; keep the handles alive
+    pub fn start_background_tasks(&self) -> Vec<tokio::task::JoinHandle<()>> {
+        let mut tasks = Vec::new();
+
+        // Start hygiene runner for automatic cleanup
+        let hygiene_config = hygiene::HygieneConfig { history_retention: self.history_retention.clone(), ..hygiene::load_hygiene_config() };
+        tracing::info!("Hygiene config loaded: enabled={}, interval={}s", hygiene_config.enabled, hygiene_config.run_interval_seconds);
+        tasks.push(hygiene::HygieneRunner::new(self.clone(), Some(hygiene_config)).start());
+        tracing::info!("Hygiene runner started for automatic cleanup");
+
+        // Publish breadcrumb events committed but never announced (crash between commit and fanout)
+        tasks.push(outbox::start_dispatcher(self.clone()));
+
+        // Replay events that failed to publish while NATS was unavailable
+        #[cfg(feature = "nats")]
+        tasks.push(self.event_bus.start_replay());
+
+        // Sample stored breadcrumb count/size for the per-owner gauges, and inbox depths per agent
+        tasks.push(domain_metrics::start_sampler(self.db.clone()));
+
+        // Fail runs a previous process left running, then expire finished ones
+        tasks.push(agent_runs::start_sweeper(self.clone()));
+
+        // Write the batched access counts behind the breadcrumb analytics
+        tasks.push(access_log::start_flusher(self.clone()));
+
+        // Decide whether reads may use the replica, if there is one
+        tasks.push(replica::start_monitor(self.reads.clone(), self.replica_lag_check));
+
+        // Learn of tenant deletions other replicas started
+        tasks.push(tenant_deletion::start_refresher(self.clone()));
+
+        // Load the embedding model now rather than on the first search
+        tasks.push(tokio::task::spawn_blocking(embedding::warm_up));
+        tasks
+    }
+}
+
+/// Apply the bundled migrations under the migration lock, waiting up to 5 minutes for another replica
+/// that holds it. `AppState::from_config` runs them itself; embedded callers of `AppState::new` run this first
+pub async fn migrate(db: &Db) -> anyhow::Result<()> {
+    coordination::migrate_with_lock(db, std::time::Duration::from_secs(300)).await
+}
+
+pub fn build_app(state: AppState) -> Router {
+    Router::new()
+        .route("/health", get(observability::health))
+        .route("/ready", get(observability::ready))
+        .route("/", get(docs::docs_page))
+        .route("/docs", get(docs::docs_page))
+        .route("/swagger", get(docs::swagger_page))
+        .route("/openapi.json", get(docs::openapi_spec))
+        .route("/auth/token", post(auth::generate_jwt_token))
+        .route("/admin/purge", post(admin::admin_purge))
+        .route("/admin/breadcrumbs/purge", post(purge::purge_breadcrumbs))
+        .route("/admin/stats", get(stats::admin_stats))
+        .route("/admin/embeddings/backfill", post(admin::backfill_embeddings))
+        .route("/admin/embeddings/clear-sensitive", post(admin::clear_sensitive_embeddings))
+        .route("/admin/embeddings/models", get(embedding_models::list_models))
+        .route("/admin/embeddings/cutover", post(embedding_models::cutover))
+        .route("/admin/checksums/verify", post(checksums::verify_checksums_batch))
+        .route("/admin/sessions/rebuild", post(session_stats::rebuild_sessions))
+        .route("/admin/topology/export", get(topology::export_topology))
+        .route("/admin/topology/import", post(topology::import_topology))
+        .route("/agents/run", post(agent_runs::run_agents))
+        .route("/agents/run/:id", get(agent_runs::get_run))
+        .route("/agents/run/:id/cancel", post(agent_runs::cancel_run))
+        .route("/breadcrumbs", post(breadcrumbs::create_breadcrumb).get(breadcrumbs::list_breadcrumbs))
+        .route("/breadcrumbs/:id", get(breadcrumbs::get_breadcrumb_context).patch(breadcrumbs::update_breadcrumb).delete(breadcrumbs::delete_breadcrumb))
+        .route("/breadcrumbs/:id/full", get(breadcrumbs::get_breadcrumb_full))
+        .route("/breadcrumbs/:id/as_of", get(breadcrumbs::get_breadcrumb_as_of))
+        .route("/breadcrumbs/bulk_get", post(breadcrumbs::bulk_get_breadcrumbs))
+        .route("/breadcrumbs/validate", post(breadcrumbs::validate_breadcrumb))
+        .route("/breadcrumbs/upsert", put(breadcrumbs::upsert_breadcrumb))
+        .route("/breadcrumbs/from_template/:template_name", post(templates::create_from_template))
+        .route("/breadcrumbs/:id/history", get(breadcrumbs::get_breadcrumb_history))
+        .route("/breadcrumbs/:id/diff", get(version_diff::get_breadcrumb_diff))
+        .route("/breadcrumbs/:id/references", get(references::list_references))
+        .route("/breadcrumbs/:id/referenced_by", get(references::list_referenced_by))
+        .route("/breadcrumbs/:id/retention", get(breadcrumbs::get_breadcrumb_retention))
+        .route("/breadcrumbs/:id/analytics", get(analytics::get_breadcrumb_analytics))
+        .route("/breadcrumbs/:id/rollback", post(breadcrumbs::rollback_breadcrumb))
+        .route("/breadcrumbs/:id/tags", post(breadcrumbs::update_breadcrumb_tags))
+        .route("/breadcrumbs/:id/verify", get(checksums::verify_breadcrumb))
+        .route("/breadcrumbs/:id/share", post(share_links::create_share))
+        .route("/breadcrumbs/:id/shares", get(share_links::list_shares))
+        .route("/breadcrumbs/:id/shares/:share_id", delete(share_links::revoke_share))
+        .route("/shared/:token", get(share_links::get_shared))
+        .route("/breadcrumbs/:id/attachments", post(attachments::upload_attachment).layer(DefaultBodyLimit::max(state.attachment_limits.upload_body_limit())).get(attachments::list_attachments))
+        .route("/attachments/:sha256", get(attachments::get_attachment))
+        .route("/breadcrumbs/search", get(breadcrumbs::vector_search))
+        .route("/breadcrumbs/suggest", get(suggest::suggest))
+        .route("/schemas", get(schema_registry::list_schemas))
+        .route("/schemas/:name", get(schema_registry::get_schema).put(schema_registry::update_schema_status))
+        .route("/extract/entities", post(breadcrumbs::extract_entities))
+        .route("/subscriptions/selectors", post(selectors::create_selector).get(selectors::list_selectors).delete(selectors::delete_selectors_by_tag))
+        .route("/subscriptions/selectors/:id", put(selectors::update_selector).delete(selectors::delete_selector))
+        .route("/acl", get(acl::list_acls))
+        .route("/acl/grant", post(acl::grant_acl))
+        .route("/acl/revoke", post(acl::revoke_acl))
+        .route("/agents", get(agents::list_agents))
+        .route("/agents/:id/webhooks", post(webhooks::register_webhook).get(webhooks::list_webhooks))
+        .route("/agents/:id/webhooks/:wid", delete(webhooks::deactivate_webhook))
+        .route("/agents/:id/webhooks/:wid/test", post(webhooks::test_webhook))
+        .route("/agents/:id", post(agents::register_agent).get(agents::get_agent).delete(agents::delete_agent))
+        .route("/agents/:id/secret", post(webhooks::set_agent_secret))
+        .route("/agents/:id/session", put(agents::set_agent_session))
+        .route("/agents/:id/inbox", get(inbox::lease))
+        .route("/agents/:id/inbox/ack", post(inbox::ack))
+        .route("/agents/:id/api-keys", post(api_keys::create_api_key).get(api_keys::list_api_keys))
+        .route("/agents/:id/api-keys/:key_id", delete(api_keys::revoke_api_key))
+        .route("/tenants", get(tenants::list_tenants))
+        .route("/tenants/:id", post(tenants::ensure_tenant).get(tenants::get_tenant).put(tenants::update_tenant).delete(tenants::delete_tenant))
+        .route("/tenants/:id/deletion", get(tenants::get_tenant_deletion))
+        .route("/secrets", post(secrets::create_secret).get(secrets::list_secrets))
+        .route("/secrets/:id", put(secrets::update_secret).delete(secrets::delete_secret))
+        .route("/secrets/:id/decrypt", post(secrets::decrypt_secret))
+        .route("/webhooks", post(webhooks::register_owner_webhook).get(webhooks::list_owner_webhooks))
+        .route("/webhooks/:id", get(webhooks::get_owner_webhook).delete(webhooks::delete_owner_webhook))
+        .route("/dlq", get(webhooks::list_dlq))
+        .route("/dlq/:id", delete(webhooks::delete_dlq))
+        .route("/dlq/:id/retry", post(webhooks::retry_dlq))
+        .route("/hygiene/stats", get(admin::get_hygiene_stats))
+        .route("/hygiene/run", post(admin::trigger_hygiene_run))
+        .route("/sessions", get(session_stats::list_sessions))
+        .route("/analytics/top_breadcrumbs", get(analytics::top_breadcrumbs))
+        .route("/sessions/:session_tag/close", post(admin::close_session))
+        .layer(compression::layer())
+        // Streaming and scrape endpoints bypass compression
+        .route("/metrics", get(observability::metrics))
+        .route("/events/stream", get(events::sse_stream))
+        .route("/events/missed", get(events::missed_events))
+        .with_state(state.clone())
+        // Inside CORS, so a shed 503 still carries its headers
+        .layer(axum::middleware::from_fn_with_state(state, priority::priority_middleware))
+        .layer(
+            CorsLayer::new()
+                .allow_origin(Any)
+                .allow_methods(Any)
+                .allow_headers(Any)
+        )
+        .layer(axum::middleware::from_fn(observability::http_metrics_middleware))
+        // Outermost, so every log line of a request carries its id
+        .layer(axum::middleware::from_fn(request_id::request_id_middleware))
+}
+
+fn internal_error<E: std::fmt::Display>(e: E) -> (axum::http::StatusCode, String) {
+    (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
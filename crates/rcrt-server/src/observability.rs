@@ -0,0 +1,101 @@
+//! Observability
+//! Health and readiness checks, Prometheus scrape endpoint and per-request HTTP metrics
+
+use std::sync::OnceLock;
+use axum::extract::State;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Json, Response};
+use prometheus::{Encoder, TextEncoder, IntCounterVec, HistogramVec, register_int_counter_vec, register_histogram_vec};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::{coordination, AppState};
+
+pub async fn health() -> &'static str { "ok" }
+
+/// Ready once Postgres answers and its schema is at least the version this binary was built for; a replica
+/// serving ahead of a migration another one is still running reports 503 until it lands
+pub async fn ready(State(state): State<AppState>) -> Response {
+    let expected = coordination::expected_schema_version();
+    let (dependency, error) = match coordination::schema_version(&state.db).await {
+        Ok(Some(version)) if version >= expected => {
+            return Json(json!({ "status": "ready", "schema_version": version, "expected_schema_version": expected })).into_response();
+        }
+        Ok(version) => ("schema", format!("schema at {:?}, this binary needs {}", version, expected)),
+        Err(e) => ("database", e.to_string()),
+    };
+    (StatusCode::SERVICE_UNAVAILABLE, Json(json!({
+        "status": "not_ready",
+        "expected_schema_version": expected,
+        "failing": [{ "dependency": dependency, "error": error }],
+    }))).into_response()
+}
+
+/// Checked in the handler rather than a layer: the 401 then goes through http_metrics_middleware like any
+/// other response, and nothing here records metrics of its own
+fn scrape_allowed(expected: Option<&str>, headers: &HeaderMap) -> bool {
+    let Some(expected) = expected else { return true };
+    let given = headers.get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+    // Compare digests so the time taken doesn't depend on how much of the token matched
+    given.is_some_and(|given| Sha256::digest(given.as_bytes()) == Sha256::digest(expected.as_bytes()))
+}
+
+pub async fn metrics(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if !scrape_allowed(state.auth.metrics_token.as_deref(), &headers) {
+        return (StatusCode::UNAUTHORIZED, [(header::WWW_AUTHENTICATE, "Bearer")], "metrics token required").into_response();
+    }
+    let encoder = TextEncoder::new();
+    let mf = prometheus::gather();
+    let mut buf = Vec::new();
+    let _ = encoder.encode(&mf, &mut buf);
+    ([("content-type", "text/plain; version=0.0.4")], buf).into_response()
+}
+
+static HTTP_REQ_TOTAL: OnceLock<IntCounterVec> = OnceLock::new();
+static HTTP_REQ_HISTO: OnceLock<HistogramVec> = OnceLock::new();
+
+pub async fn http_metrics_middleware(
+    req: axum::http::Request<axum::body::Body>,
+    next: axum::middleware::Next,
+) -> impl IntoResponse {
+    let method = req.method().as_str().to_string();
+    // Avoid exploding labels: keep path template-ish by trimming IDs
+    let path_owned = req.uri().path().to_string();
+    let path_label: String = if path_owned.len() > 64 { path_owned[..64].to_string() } else { path_owned.clone() };
+    let start = std::time::Instant::now();
+    let resp = next.run(req).await;
+    let status = resp.status().as_u16().to_string();
+    let dur = start.elapsed().as_secs_f64();
+    let counter = HTTP_REQ_TOTAL.get_or_init(|| register_int_counter_vec!(
+        "http_requests_total","HTTP requests total", &["method","path","status"]
+    ).unwrap());
+    let histo = HTTP_REQ_HISTO.get_or_init(|| register_histogram_vec!(
+        "http_request_duration_seconds","HTTP request duration seconds", &["method","path","status"],
+        vec![0.005,0.01,0.025,0.05,0.1,0.25,0.5,1.0,2.5,5.0]
+    ).unwrap());
+    counter.with_label_values(&[&method, &path_label, &status]).inc();
+    histo.with_label_values(&[&method, &path_label, &status]).observe(dur);
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bearer(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_scrape_allowed() {
+        assert!(scrape_allowed(None, &HeaderMap::new()));
+        assert!(scrape_allowed(Some("s3cret"), &bearer("s3cret")));
+        assert!(!scrape_allowed(Some("s3cret"), &HeaderMap::new()));
+        assert!(!scrape_allowed(Some("s3cret"), &bearer("s3cre")));
+        assert!(!scrape_allowed(Some("s3cret"), &bearer("s3cret ")));
+    }
+}
@@ -0,0 +1,155 @@
+//! Breadcrumb Outbox
+//! Publishes breadcrumb events the request path never got to, e.g. when the process died between commit and fanout
+
+use std::sync::OnceLock;
+use std::time::Duration;
+use prometheus::{IntCounterVec, register_int_counter_vec};
+use uuid::Uuid;
+
+use crate::{events, AppState};
+
+const BATCH: i64 = 100;
+const POLL: Duration = Duration::from_secs(5);
+/// Published rows are kept this long for inspection, then pruned
+const KEEP_PUBLISHED_HOURS: i64 = 24;
+
+static OUTBOX_EVENTS: OnceLock<IntCounterVec> = OnceLock::new();
+
+fn outbox_events() -> &'static IntCounterVec {
+    OUTBOX_EVENTS.get_or_init(|| register_int_counter_vec!("breadcrumb_outbox_events_total", "Outbox rows published or skipped by the dispatcher", &["outcome"]).unwrap())
+}
+
+/// Seconds a row may stay unpublished before the dispatcher takes over from the request path
+fn grace_secs() -> f64 {
+    std::env::var("OUTBOX_GRACE_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(10.0)
+}
+
+/// Poll for stale outbox rows on the replica holding the outbox lock; FOR UPDATE SKIP LOCKED still keeps
+/// a takeover from publishing a row twice at once
+pub fn start_dispatcher(state: AppState) -> tokio::task::JoinHandle<()> {
+    let grace = grace_secs();
+    tracing::info!("📮 Outbox dispatcher started (grace {}s)", grace);
+    let leadership = crate::coordination::Leadership::new("outbox dispatcher", crate::coordination::OUTBOX_LOCK);
+    tokio::spawn(async move {
+        loop {
+            if !leadership.acquire(&state.db).await {
+                tokio::time::sleep(POLL).await;
+                continue;
+            }
+            match dispatch_batch(&state, grace).await {
+                // A full batch means more are waiting
+                Ok(n) if n as i64 == BATCH => continue,
+                Ok(_) => {}
+                Err(e) => tracing::warn!("📮 Outbox: ⚠️ Dispatch failed: {}", e),
+            }
+            if let Err(e) = prune_published(&state).await {
+                tracing::warn!("📮 Outbox: ⚠️ Prune failed: {}", e);
+            }
+            tokio::time::sleep(POLL).await;
+        }
+    })
+}
+
+/// Publish up to BATCH rows older than `grace` seconds and mark them done; returns how many were handled.
+/// At-least-once: a row the request path is still publishing may go out twice, webhooks dedupe on delivery_id.
+async fn dispatch_batch(state: &AppState, grace: f64) -> anyhow::Result<usize> {
+    let mut tx = state.db.pool.begin().await?;
+    let rows: Vec<(Uuid, Uuid, Uuid, i32, String)> = sqlx::query_as(
+        r#"select id, owner_id, breadcrumb_id, version, event_type from breadcrumb_outbox
+           where published_at is null and created_at < now() - make_interval(secs => $1)
+           order by created_at
+           limit $2
+           for update skip locked"#
+    )
+    .bind(grace)
+    .bind(BATCH)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut done = Vec::with_capacity(rows.len());
+    for (id, owner_id, breadcrumb_id, version, event_type) in rows {
+        let bc = match state.db.get_breadcrumb_for(owner_id, breadcrumb_id).await {
+            Ok(bc) => bc,
+            Err(e) => {
+                // Left unmarked for the next pass
+                tracing::warn!("📮 Outbox: ⚠️ Failed to load {} for replay: {}", breadcrumb_id, e);
+                continue;
+            }
+        };
+        match bc {
+            // Consumers must hear a breadcrumb exists even if it has moved on since
+            Some(bc) if event_type == "created" => {
+                events::announce_breadcrumb_created(state, owner_id, &bc).await;
+                outbox_events().with_label_values(&["published"]).inc();
+            }
+            Some(bc) if bc.version == version => {
+                events::announce_breadcrumb_updated(state, owner_id, &bc).await;
+                outbox_events().with_label_values(&["published"]).inc();
+            }
+            // Deleted, or a later version has its own row carrying newer state
+            _ => outbox_events().with_label_values(&["superseded"]).inc(),
+        }
+        done.push(id);
+    }
+
+    if !done.is_empty() {
+        tracing::info!("📮 Outbox: Replayed {} breadcrumb event(s) the request path missed", done.len());
+        sqlx::query("update breadcrumb_outbox set published_at = now() where id = any($1)")
+            .bind(&done)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(done.len())
+}
+
+async fn prune_published(state: &AppState) -> Result<u64, sqlx::Error> {
+    let res = sqlx::query("delete from breadcrumb_outbox where published_at < now() - make_interval(hours => $1::int)")
+        .bind(KEEP_PUBLISHED_HOURS as i32)
+        .execute(&state.db.pool)
+        .await?;
+    Ok(res.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_dispatcher_publishes_unmarked_rows(pool: sqlx::PgPool) {
+        use super::*;
+        use crate::auth::{AuthConfig, AuthMode};
+        use crate::test_support::{crumb, state};
+        use rcrt_core::db::Db;
+        use rcrt_core::models::BreadcrumbCreate;
+
+        let db = Db { pool: pool.clone() };
+        let (owner_id, agent_id) = (Uuid::new_v4(), Uuid::new_v4());
+        db.ensure_tenant(owner_id, "Outbox Test").await.unwrap();
+        let create = |title: &str| BreadcrumbCreate { title: title.into(), ..crumb("note.v1", &[]) };
+        // Written but never announced, as if the process died right after commit
+        let lost = db.create_breadcrumb_for(owner_id, Some(agent_id), Some(agent_id), create("lost")).await.unwrap();
+        let announced = db.create_breadcrumb_for(owner_id, Some(agent_id), Some(agent_id), create("announced")).await.unwrap();
+        db.mark_breadcrumb_event_published(announced.id, 1).await.unwrap();
+        let deleted = db.create_breadcrumb_for(owner_id, Some(agent_id), Some(agent_id), create("deleted")).await.unwrap();
+        db.delete_breadcrumb(owner_id, agent_id, deleted.id).await.unwrap();
+
+        let auth = AuthConfig::new(AuthMode::Disabled { owner_id, agent_id }, None, None, None, None).unwrap();
+        let state = state(db, auth).await;
+        // Rows inside the grace period are left to the request path
+        assert_eq!(dispatch_batch(&state, 3600.0).await.unwrap(), 0);
+        assert_eq!(dispatch_batch(&state, 0.0).await.unwrap(), 2);
+        assert_eq!(dispatch_batch(&state, 0.0).await.unwrap(), 0);
+
+        let pending: i64 = sqlx::query_scalar("select count(*) from breadcrumb_outbox where published_at is null")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(pending, 0);
+        let (published_at,): (Option<chrono::DateTime<chrono::Utc>>,) = sqlx::query_as("select published_at from breadcrumb_outbox where breadcrumb_id = $1")
+            .bind(lost.id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(published_at.is_some());
+    }
+}
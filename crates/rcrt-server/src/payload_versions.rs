@@ -0,0 +1,202 @@
+//! Payload Versions
+//! The event payload versions SSE, /events/missed and webhooks can deliver, and the converters between them.
+//! Events are built in the latest shape and travel NATS that way; each delivery renders the version its
+//! consumer asked for (SSE `?payload_version`, a webhook's or selector's pin), v1 by default. A version
+//! only ever adds fields, so rendering an older one drops what the later ones added
+
+use axum::http::StatusCode;
+use serde_json::{Map, Value};
+
+/// Set by the fanout on agents.{id}.events messages when a matching selector pins a version, so the
+/// agent's SSE stream renders it; never delivered
+pub const PINNED_FIELD: &str = "pinned_payload_version";
+
+/// Header carrying the version of a webhook body
+pub const HEADER: &str = "X-RCRT-Payload-Version";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PayloadVersion {
+    /// type, breadcrumb_id, owner_id, title, version, tags, schema_name, updated_at, visibility,
+    /// sensitivity, created_by, context, and request_id and traceparent when the write had them (deletes:
+    /// deleted_at instead of version and context)
+    V1 = 1,
+    /// v1 plus `emitted_at` and `provenance: {agent_id, request_id}`
+    V2 = 2,
+    /// v2 plus the breadcrumb's `created_at` (not on deletes) and `received_at`, when the server took the
+    /// request that raised the event (events raised outside a request have none)
+    V3 = 3,
+}
+
+impl PayloadVersion {
+    /// What consumers get unless they pin a version; stays v1 until its deprecation window passes
+    pub const DEFAULT: PayloadVersion = PayloadVersion::V1;
+    /// The shape events are built in
+    pub const LATEST: PayloadVersion = PayloadVersion::V3;
+
+    pub fn from_number(n: u64) -> Option<Self> {
+        match n {
+            1 => Some(PayloadVersion::V1),
+            2 => Some(PayloadVersion::V2),
+            3 => Some(PayloadVersion::V3),
+            _ => None,
+        }
+    }
+
+    pub fn number(self) -> u16 {
+        self as u16
+    }
+
+    /// A requested version, as a 400 when the server doesn't know it
+    pub fn requested(n: u16) -> Result<Self, (StatusCode, String)> {
+        Self::from_number(n.into()).ok_or((StatusCode::BAD_REQUEST, format!("unknown payload_version {} (supported: 1 to {})", n, Self::LATEST.number())))
+    }
+
+    /// The version an event says it is in; events from before versioning are v1
+    pub fn of(event: &Value) -> Self {
+        event.get("payload_version").and_then(Value::as_u64).and_then(Self::from_number).unwrap_or(PayloadVersion::V1)
+    }
+
+    /// The pin the fanout stamped on an agent channel message
+    pub fn pinned(event: &Value) -> Option<Self> {
+        event.get(PINNED_FIELD).and_then(Value::as_u64).and_then(Self::from_number)
+    }
+}
+
+// One converter per version, each taking the next version's shape down to its own
+
+fn to_v1(event: &mut Map<String, Value>) {
+    to_v2(event);
+    event.remove("emitted_at");
+    event.remove("provenance");
+}
+
+fn to_v2(event: &mut Map<String, Value>) {
+    to_v3(event);
+    event.remove("created_at");
+    event.remove("received_at");
+}
+
+fn to_v3(_event: &mut Map<String, Value>) {}
+
+/// `event` (in the latest shape) as `version`, with `payload_version` set and the internal pin removed.
+/// Non-object values pass through untouched
+pub fn render(event: &Value, version: PayloadVersion) -> Value {
+    let mut out = event.clone();
+    if let Some(obj) = out.as_object_mut() {
+        obj.remove(PINNED_FIELD);
+        match version {
+            PayloadVersion::V1 => to_v1(obj),
+            PayloadVersion::V2 => to_v2(obj),
+            PayloadVersion::V3 => to_v3(obj),
+        }
+        obj.insert("payload_version".to_string(), version.number().into());
+    }
+    out
+}
+
+/// `render` for a serialized event; breadcrumb events only, anything else (or unparseable) is returned as is
+pub fn render_str(event: &str, version: PayloadVersion) -> String {
+    match serde_json::from_str::<Value>(event) {
+        Ok(v) if v.get("type").and_then(Value::as_str).is_some_and(|t| t.starts_with("breadcrumb.")) => render(&v, version).to_string(),
+        _ => event.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn latest() -> Value {
+        json!({
+            "type": "breadcrumb.updated", "breadcrumb_id": "b", "owner_id": "o", "title": "t", "version": 3,
+            "tags": ["x"], "schema_name": null, "updated_at": "2025-01-01T00:00:00Z", "visibility": "team",
+            "sensitivity": "low", "created_by": "a", "context": { "k": 1 }, "request_id": "r",
+            "emitted_at": "2025-01-01T00:00:01Z", "provenance": { "agent_id": "a", "request_id": "r" },
+            "created_at": "2024-12-31T23:59:59.123Z", "received_at": "2025-01-01T00:00:00.500Z",
+            "payload_version": 3, "pinned_payload_version": 3,
+        })
+    }
+
+    #[test]
+    fn test_version_numbers_round_trip() {
+        for version in [PayloadVersion::V1, PayloadVersion::V2, PayloadVersion::V3] {
+            assert_eq!(PayloadVersion::from_number(version.number().into()), Some(version));
+            assert_eq!(PayloadVersion::requested(version.number()).unwrap(), version);
+        }
+        assert_eq!(PayloadVersion::from_number(0), None);
+        assert_eq!(PayloadVersion::from_number(4), None);
+        assert_eq!(PayloadVersion::requested(4).unwrap_err().0, StatusCode::BAD_REQUEST);
+        assert_eq!(PayloadVersion::DEFAULT, PayloadVersion::V1);
+        assert_eq!(PayloadVersion::LATEST, PayloadVersion::V3);
+    }
+
+    #[test]
+    fn test_v1_drops_everything_later_versions_added() {
+        let mut expected = latest();
+        for field in ["emitted_at", "provenance", "created_at", "received_at", "pinned_payload_version"] {
+            expected.as_object_mut().unwrap().remove(field);
+        }
+        expected["payload_version"] = json!(1);
+        assert_eq!(render(&latest(), PayloadVersion::V1), expected);
+    }
+
+    #[test]
+    fn test_v2_drops_the_timestamps_v3_added() {
+        let mut expected = latest();
+        for field in ["created_at", "received_at", "pinned_payload_version"] {
+            expected.as_object_mut().unwrap().remove(field);
+        }
+        expected["payload_version"] = json!(2);
+        assert_eq!(render(&latest(), PayloadVersion::V2), expected);
+    }
+
+    #[test]
+    fn test_v3_is_the_latest_shape() {
+        let mut expected = latest();
+        expected.as_object_mut().unwrap().remove(PINNED_FIELD);
+        assert_eq!(render(&latest(), PayloadVersion::V3), expected);
+    }
+
+    #[test]
+    fn test_every_version_only_adds_fields() {
+        let fields = |v: PayloadVersion| render(&latest(), v).as_object().unwrap().keys().cloned().collect::<Vec<_>>();
+        let versions = [fields(PayloadVersion::V1), fields(PayloadVersion::V2), fields(PayloadVersion::V3)];
+        for pair in versions.windows(2) {
+            let (older, newer) = (&pair[0], &pair[1]);
+            assert!(older.iter().all(|f| newer.contains(f)), "lost {:?}", older.iter().filter(|f| !newer.contains(f)).collect::<Vec<_>>());
+            assert!(newer.len() > older.len());
+        }
+    }
+
+    #[test]
+    fn test_render_is_idempotent_and_tolerates_older_events() {
+        let v1 = render(&latest(), PayloadVersion::V1);
+        assert_eq!(render(&v1, PayloadVersion::V1), v1);
+        // A v1 event asked for as v2 can't grow the fields it never had, but says which version it claims
+        let up = render(&v1, PayloadVersion::V2);
+        assert_eq!(up["payload_version"], 2);
+        assert!(up.get("provenance").is_none());
+        // Redacted events keep only some fields; rendering must not put any back
+        let meta = json!({ "type": "breadcrumb.updated", "breadcrumb_id": "b", "redacted": true, "emitted_at": "2025-01-01T00:00:01Z" });
+        assert_eq!(render(&meta, PayloadVersion::V1), json!({ "type": "breadcrumb.updated", "breadcrumb_id": "b", "redacted": true, "payload_version": 1 }));
+        assert_eq!(render(&json!("not an event"), PayloadVersion::V1), json!("not an event"));
+    }
+
+    #[test]
+    fn test_version_of_event_and_pin() {
+        assert_eq!(PayloadVersion::of(&latest()), PayloadVersion::V3);
+        assert_eq!(PayloadVersion::of(&json!({ "type": "breadcrumb.updated" })), PayloadVersion::V1);
+        assert_eq!(PayloadVersion::pinned(&latest()), Some(PayloadVersion::V3));
+        assert_eq!(PayloadVersion::pinned(&json!({ "pinned_payload_version": 9 })), None);
+    }
+
+    #[test]
+    fn test_render_str_only_touches_breadcrumb_events() {
+        let rendered: Value = serde_json::from_str(&render_str(&latest().to_string(), PayloadVersion::V1)).unwrap();
+        assert_eq!(rendered, render(&latest(), PayloadVersion::V1));
+        let ping = r#"{"type":"ping","seq":1}"#;
+        assert_eq!(render_str(ping, PayloadVersion::V1), ping);
+        assert_eq!(render_str("not json", PayloadVersion::V2), "not json");
+    }
+}
@@ -0,0 +1,264 @@
+//! Priority Classes
+//! Interactive requests (breadcrumb creates, context view reads) and batch ones (imports, backfills,
+//! hygiene) each get their own concurrency partition in front of the shared connection pool. When waits
+//! pass the shed threshold, batch requests are refused with 503 + Retry-After so interactive traffic
+//! keeps flowing. Unclassified requests pass straight through
+
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use axum::{extract::{Request, State}, http::{header, HeaderMap, Method, StatusCode}, middleware::Next, response::{IntoResponse, Response}, Json};
+use prometheus::{register_histogram_vec, register_int_counter_vec, HistogramVec, IntCounterVec};
+use serde_json::json;
+use tokio::sync::{Semaphore, SemaphorePermit};
+use uuid::Uuid;
+
+use crate::AppState;
+
+/// Picks the class on routes that aren't batch by definition, e.g. a loader creating breadcrumbs one
+/// at a time sends `batch`
+pub const HEADER: &str = "X-RCRT-Priority";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Priority {
+    Interactive,
+    Batch,
+}
+
+impl Priority {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Priority::Interactive => "interactive",
+            Priority::Batch => "batch",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "interactive" => Some(Priority::Interactive),
+            "batch" => Some(Priority::Batch),
+            _ => None,
+        }
+    }
+}
+
+/// POST routes that are batch work whatever the header says
+const BATCH_ROUTES: &[&str] = &[
+    "/admin/purge",
+    "/admin/breadcrumbs/purge",
+    "/admin/embeddings/backfill",
+    "/admin/embeddings/clear-sensitive",
+    "/admin/checksums/verify",
+    "/admin/sessions/rebuild",
+    "/admin/topology/import",
+    "/hygiene/run",
+];
+
+/// Batch routes first, then the header, then breadcrumb creates and context view reads as interactive.
+/// Streams never take a slot: a permit would be held for as long as the client stays connected
+pub fn classify(method: &Method, path: &str, headers: &HeaderMap) -> Option<Priority> {
+    if method == Method::POST && BATCH_ROUTES.contains(&path) {
+        return Some(Priority::Batch);
+    }
+    if path.starts_with("/events/") {
+        return None;
+    }
+    if let Some(class) = headers.get(HEADER).and_then(|v| v.to_str().ok()).and_then(Priority::parse) {
+        return Some(class);
+    }
+    let interactive = match (method, path.strip_prefix("/breadcrumbs")) {
+        (&Method::POST, Some("")) => true,
+        (&Method::GET, Some(rest)) => rest.strip_prefix('/').is_some_and(|id| Uuid::parse_str(id).is_ok()),
+        _ => false,
+    };
+    interactive.then_some(Priority::Interactive)
+}
+
+pub struct PriorityGate {
+    interactive: Semaphore,
+    batch: Semaphore,
+    /// Longest a batch request waits for its slot and a pooled connection before it is shed; zero never sheds
+    shed_after: Duration,
+    /// Sent as Retry-After, and how long batch requests are shed outright once a wait ran long
+    retry_after: Duration,
+    shedding_until: Mutex<Option<Instant>>,
+}
+
+impl PriorityGate {
+    /// Partition sizes are at least 1
+    pub fn new(interactive: usize, batch: usize, shed_after: Duration, retry_after: Duration) -> Self {
+        Self {
+            interactive: Semaphore::new(interactive.max(1)),
+            batch: Semaphore::new(batch.max(1)),
+            shed_after,
+            retry_after,
+            shedding_until: Mutex::new(None),
+        }
+    }
+
+    /// Wait for a slot in `class`'s partition, held until the response is ready; None means shed
+    pub async fn admit(&self, class: Priority, pool: &sqlx::PgPool) -> Option<SemaphorePermit<'_>> {
+        let start = Instant::now();
+        let permit = match class {
+            Priority::Interactive => {
+                let permit = self.interactive.acquire().await.ok();
+                // Interactive requests queueing is the pressure batch work gives way to
+                if !self.shed_after.is_zero() && start.elapsed() > self.shed_after {
+                    self.start_shedding();
+                }
+                permit
+            }
+            Priority::Batch => self.admit_batch(pool, start).await,
+        };
+        pool_wait_histogram().with_label_values(&[class.as_str()]).observe(start.elapsed().as_secs_f64());
+        permit
+    }
+
+    async fn admit_batch(&self, pool: &sqlx::PgPool, start: Instant) -> Option<SemaphorePermit<'_>> {
+        if self.shed_after.is_zero() {
+            return self.batch.acquire().await.ok();
+        }
+        if self.shedding() {
+            return None;
+        }
+        // A full batch partition only means batch work is backed up; shed this one without shedding the rest
+        let permit = tokio::time::timeout(self.shed_after, self.batch.acquire()).await.ok()?.ok()?;
+        // Probe the pool itself: no connection within what's left of the threshold means everyone is queueing
+        let left = self.shed_after.saturating_sub(start.elapsed());
+        match tokio::time::timeout(left, pool.acquire()).await {
+            Err(_) => {
+                self.start_shedding();
+                None
+            }
+            // A connection error is the handler's to report
+            Ok(_) => Some(permit),
+        }
+    }
+
+    fn shedding(&self) -> bool {
+        self.shedding_until.lock().map(|until| until.is_some_and(|t| Instant::now() < t)).unwrap_or(false)
+    }
+
+    fn start_shedding(&self) {
+        if let Ok(mut until) = self.shedding_until.lock() {
+            *until = Some(Instant::now() + self.retry_after);
+        }
+    }
+
+    fn shed_response(&self, class: Priority) -> Response {
+        let secs = self.retry_after.as_secs().max(1);
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::RETRY_AFTER, secs.to_string())],
+            Json(json!({ "error": "overloaded", "priority": class.as_str(), "retry_after_secs": secs })),
+        ).into_response()
+    }
+}
+
+static POOL_WAIT: OnceLock<HistogramVec> = OnceLock::new();
+static SHED_TOTAL: OnceLock<IntCounterVec> = OnceLock::new();
+
+fn pool_wait_histogram() -> &'static HistogramVec {
+    POOL_WAIT.get_or_init(|| register_histogram_vec!(
+        "db_pool_wait_seconds", "Wait for a priority slot (and, for batch, a pooled connection) by class", &["class"],
+        vec![0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0]
+    ).unwrap())
+}
+
+fn shed_counter() -> &'static IntCounterVec {
+    SHED_TOTAL.get_or_init(|| register_int_counter_vec!(
+        "requests_shed_total", "Requests refused with 503 under database pressure, by class", &["class"]
+    ).unwrap())
+}
+
+pub async fn priority_middleware(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let Some(class) = classify(req.method(), req.uri().path(), req.headers()) else {
+        return next.run(req).await;
+    };
+    let gate = state.priority.clone();
+    match gate.admit(class, &state.db.pool).await {
+        Some(permit) => {
+            let response = next.run(req).await;
+            drop(permit);
+            response
+        }
+        None => {
+            shed_counter().with_label_values(&[class.as_str()]).inc();
+            tracing::warn!("🚦 Shedding {} {} ({} priority) under database pressure", req.method(), req.uri().path(), class.as_str());
+            gate.shed_response(class)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_classify() {
+        let none = HeaderMap::new();
+        let mut batch = HeaderMap::new();
+        batch.insert(HEADER, HeaderValue::from_static("Batch"));
+        let id = format!("/breadcrumbs/{}", Uuid::new_v4());
+
+        assert_eq!(classify(&Method::POST, "/breadcrumbs", &none), Some(Priority::Interactive));
+        assert_eq!(classify(&Method::GET, &id, &none), Some(Priority::Interactive));
+        assert_eq!(classify(&Method::GET, "/breadcrumbs/search", &none), None);
+        assert_eq!(classify(&Method::GET, &format!("{}/history", id), &none), None);
+        assert_eq!(classify(&Method::POST, "/admin/topology/import", &none), Some(Priority::Batch));
+        assert_eq!(classify(&Method::POST, "/breadcrumbs", &batch), Some(Priority::Batch));
+        assert_eq!(classify(&Method::GET, "/agents", &batch), Some(Priority::Batch));
+
+        // Batch routes can't promote themselves, and streams never take a slot
+        let mut interactive = HeaderMap::new();
+        interactive.insert(HEADER, HeaderValue::from_static("interactive"));
+        assert_eq!(classify(&Method::POST, "/admin/embeddings/backfill", &interactive), Some(Priority::Batch));
+        assert_eq!(classify(&Method::GET, "/events/stream", &interactive), None);
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_batch_is_shed_while_interactive_completes(pool: sqlx::PgPool) {
+        use std::sync::Arc;
+        use crate::auth::{AuthConfig, AuthMode};
+        use crate::test_support::state;
+        use axum::body::Body;
+        use rcrt_core::db::Db;
+        use tower::ServiceExt;
+
+        let (owner_id, agent_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let setup = Db { pool: pool.clone() };
+        setup.ensure_tenant(owner_id, "Priority Test").await.unwrap();
+        setup.upsert_agent(owner_id, agent_id, vec!["curator".into(), "emitter".into(), "subscriber".into()]).await.unwrap();
+
+        // One connection, so holding it is a saturated pool
+        let small = sqlx::postgres::PgPoolOptions::new().max_connections(1).connect_with((*pool.connect_options()).clone()).await.unwrap();
+        let auth = AuthConfig::new(AuthMode::Disabled { owner_id, agent_id }, None, None, None, None).unwrap();
+        let base = state(Db { pool: small.clone() }, auth).await;
+        let app = crate::build_app(AppState {
+            priority: Arc::new(PriorityGate::new(4, 1, Duration::from_millis(100), Duration::from_secs(2))),
+            ..base
+        });
+        let batch = || axum::http::Request::post("/admin/sessions/rebuild").body(Body::empty()).unwrap();
+        let create = || axum::http::Request::post("/breadcrumbs")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(json!({ "title": "hi", "schema_name": "user.message.v1", "context": { "message": "hi" }, "tags": [] }).to_string()))
+            .unwrap();
+
+        let held = small.acquire().await.unwrap();
+        let res = app.clone().oneshot(batch()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(res.headers()[header::RETRY_AFTER], "2");
+
+        // The interactive request waits for the connection instead of being refused
+        let interactive = tokio::spawn(app.clone().oneshot(create()));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        drop(held);
+        assert_eq!(interactive.await.unwrap().unwrap().status(), StatusCode::OK);
+
+        // The pool is free again, but batch stays shed until Retry-After has passed
+        assert_eq!(app.clone().oneshot(batch()).await.unwrap().status(), StatusCode::SERVICE_UNAVAILABLE);
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert_ne!(app.oneshot(batch()).await.unwrap().status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
@@ -0,0 +1,139 @@
+//! Filtered Purge
+//! POST /admin/breadcrumbs/purge: delete a tenant's breadcrumbs by schema, tags, creation time or
+//! creator. A dry run (the default) counts the matches and returns a confirmation token; the real
+//! run must echo it, and only goes ahead while the filter still matches the same number of rows
+
+use axum::{extract::State, http::StatusCode, Json};
+use chrono::Utc;
+use rcrt_core::models::{BreadcrumbCreate, DeletedBreadcrumb, PurgeFilter};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::auth::{require_admin, AuthContext};
+use crate::db_errors::db_error;
+use crate::events::{publish_breadcrumb_created, publish_breadcrumb_deleted};
+use crate::AppState;
+
+pub const PURGE_SCHEMA: &str = "system.purge.v1";
+/// Titles a dry run returns so the caller can eyeball what matched
+const SAMPLE_SIZE: i64 = 10;
+
+#[derive(Deserialize)]
+pub struct PurgeReq {
+    #[serde(flatten)]
+    filter: PurgeFilter,
+    #[serde(default = "default_dry_run")]
+    dry_run: bool,
+    confirmation_token: Option<String>,
+}
+
+fn default_dry_run() -> bool { true }
+
+pub async fn purge_breadcrumbs(State(state): State<AppState>, auth: AuthContext, Json(req): Json<PurgeReq>) -> Result<Json<Value>, (StatusCode, String)> {
+    require_admin(&state, &auth)?;
+    if !req.filter.is_narrowing() {
+        return Err((StatusCode::BAD_REQUEST, "at least one of schema_name, all_tags, created_before, created_after or created_by is required".into()));
+    }
+    let max_per_request = state.purge_max_per_request;
+
+    let (matched, sample) = state.db.count_purge_matches(auth.owner_id, &req.filter, SAMPLE_SIZE).await.map_err(db_error)?;
+    let token = confirmation_token(auth.owner_id, &req.filter, matched);
+    if req.dry_run {
+        return Ok(Json(json!({
+            "dry_run": true,
+            "matched": matched,
+            "would_delete": matched.min(max_per_request),
+            "sample": sample.into_iter().map(|(id, title)| json!({ "id": id, "title": title })).collect::<Vec<_>>(),
+            "confirmation_token": token
+        })));
+    }
+    match req.confirmation_token {
+        None => return Err((StatusCode::BAD_REQUEST, "confirmation_token from a dry run with the same filter is required".into())),
+        Some(echoed) if echoed != token => return Err((StatusCode::CONFLICT, "confirmation_token doesn't match: the filter or what it matches changed since the dry run".into())),
+        Some(_) => {}
+    }
+
+    let deleted = state.db.purge_matching(auth.owner_id, &req.filter, state.purge_batch_size, max_per_request).await.map_err(db_error)?;
+    for bc in &deleted {
+        publish_breadcrumb_deleted(&state, auth.owner_id, bc).await;
+    }
+    let remaining = (matched - deleted.len() as i64).max(0);
+    let audit_id = record_audit(&state, &auth, &req.filter, &deleted, remaining).await;
+    tracing::info!("🧹 Purge by {}: {} breadcrumbs deleted, {} left matching {:?}", auth.agent_id, deleted.len(), remaining, req.filter);
+
+    // Past the cap the caller continues with a token for what is left
+    Ok(Json(json!({
+        "dry_run": false,
+        "deleted": deleted.len(),
+        "remaining": remaining,
+        "audit_id": audit_id,
+        "confirmation_token": (remaining > 0).then(|| confirmation_token(auth.owner_id, &req.filter, remaining))
+    })))
+}
+
+/// Binds the token to the tenant, the exact filter and how many rows it matched; a checksum of
+/// what the dry run saw, not a secret
+fn confirmation_token(owner_id: Uuid, filter: &PurgeFilter, matched: i64) -> String {
+    let canonical = json!({ "owner_id": owner_id, "filter": filter, "matched": matched }).to_string();
+    hex::encode(&Sha256::digest(canonical.as_bytes())[..16])
+}
+
+/// A system.purge.v1 breadcrumb summarizing the run; a failure is logged, not returned, since the rows are gone either way
+async fn record_audit(state: &AppState, auth: &AuthContext, filter: &PurgeFilter, deleted: &[DeletedBreadcrumb], remaining: i64) -> Option<Uuid> {
+    let mut schemas: Vec<&str> = deleted.iter().map(|bc| bc.schema_name.as_deref().unwrap_or("none")).collect();
+    schemas.sort_unstable();
+    schemas.dedup();
+    let audit = BreadcrumbCreate {
+        title: format!("Purged {} breadcrumbs", deleted.len()),
+        description: None,
+        semantic_version: None,
+        context: json!({
+            "purged_by": auth.agent_id,
+            "purged_at": Utc::now(),
+            "filter": filter,
+            "deleted": deleted.len(),
+            "remaining": remaining,
+            "schemas": schemas
+        }),
+        tags: vec!["system:purge".to_string()],
+        schema_name: Some(PURGE_SCHEMA.to_string()),
+        llm_hints: None,
+        visibility: None,
+        sensitivity: None,
+        ttl: Some(Utc::now() + chrono::Duration::days(90)),
+        ttl_type: None,
+        ttl_config: None,
+        ttl_source: None,
+        entity_keywords: None,
+        entities: None,
+    };
+    match state.db.create_breadcrumb_for(auth.owner_id, Some(auth.agent_id), Some(auth.agent_id), audit).await {
+        Ok(bc) => {
+            publish_breadcrumb_created(state, auth.owner_id, &bc).await;
+            Some(bc.id)
+        }
+        Err(e) => {
+            tracing::error!("🧹 Failed to record purge audit for owner {}: {}", auth.owner_id, e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_changes_with_filter_and_match_count() {
+        let owner = Uuid::new_v4();
+        let filter = PurgeFilter { all_tags: vec!["import:batch-7".into()], ..Default::default() };
+        let token = confirmation_token(owner, &filter, 3000);
+        assert_eq!(token, confirmation_token(owner, &filter, 3000));
+        assert_ne!(token, confirmation_token(owner, &filter, 2999));
+        assert_ne!(token, confirmation_token(Uuid::new_v4(), &filter, 3000));
+        let narrower = PurgeFilter { schema_name: Some("note.v1".into()), ..filter };
+        assert_ne!(token, confirmation_token(owner, &narrower, 3000));
+    }
+}
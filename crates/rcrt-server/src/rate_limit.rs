@@ -0,0 +1,67 @@
+//! Rate Limiting
+//! Fixed-window request limiter keyed by agent (or any hashable key)
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub struct RateLimiter<K> {
+    max_requests: u32,
+    window: Duration,
+    windows: Mutex<HashMap<K, (Instant, u32)>>,
+}
+
+impl<K: Eq + Hash + Clone> RateLimiter<K> {
+    pub fn new(max_requests: u32, window: Duration) -> Self {
+        Self { max_requests, window, windows: Mutex::new(HashMap::new()) }
+    }
+
+    /// Record a request for `key`; returns false when the key is over its limit
+    pub fn check(&self, key: &K) -> bool {
+        self.check_at(key, Instant::now())
+    }
+
+    fn check_at(&self, key: &K, now: Instant) -> bool {
+        let Ok(mut windows) = self.windows.lock() else { return true; };
+        // Drop expired windows so idle keys don't accumulate
+        if windows.len() > 10_000 {
+            let window = self.window;
+            windows.retain(|_, (start, _)| now.duration_since(*start) < window);
+        }
+        let entry = windows.entry(key.clone()).or_insert((now, 0));
+        if now.duration_since(entry.0) >= self.window {
+            *entry = (now, 0);
+        }
+        if entry.1 >= self.max_requests {
+            return false;
+        }
+        entry.1 += 1;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_within_window() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        let now = Instant::now();
+        assert!(limiter.check_at(&"a", now));
+        assert!(limiter.check_at(&"a", now));
+        assert!(!limiter.check_at(&"a", now));
+        // Other keys are independent
+        assert!(limiter.check_at(&"b", now));
+    }
+
+    #[test]
+    fn test_window_resets() {
+        let limiter = RateLimiter::new(1, Duration::from_secs(1));
+        let now = Instant::now();
+        assert!(limiter.check_at(&"a", now));
+        assert!(!limiter.check_at(&"a", now));
+        assert!(limiter.check_at(&"a", now + Duration::from_secs(1)));
+    }
+}
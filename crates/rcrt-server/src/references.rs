@@ -0,0 +1,132 @@
+//! Breadcrumb References
+//! Declared references from a breadcrumb to others of its tenant: the `references` array on
+//! create/update, or a `$refs` block in the context holding the same entries. They are checked before
+//! the write (422 for ids the writer can't read), stored after it, listed by GET
+//! /breadcrumbs/:id/references and /referenced_by, and make deleting their target need ?force=true
+
+use axum::{extract::{Path, State}, http::StatusCode, Json};
+use rcrt_core::models::{BreadcrumbReference, NewBreadcrumbReference};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{auth::AuthContext, db_errors::db_error, AppState};
+
+/// Context key whose value is an array of `{field, breadcrumb_id, relation}`, like `references`
+pub const REFS_KEY: &str = "$refs";
+
+/// Most references one breadcrumb may declare
+const MAX_REFERENCES: usize = 100;
+
+/// `explicit` plus the context's `$refs`, trimmed and deduplicated by field and target. `None` when the
+/// write mentions neither, so an update leaves the stored references alone
+fn declared(explicit: Option<Vec<NewBreadcrumbReference>>, context: Option<&Value>) -> Result<Option<Vec<NewBreadcrumbReference>>, (StatusCode, String)> {
+    let in_context = context.and_then(|c| c.get(REFS_KEY));
+    if explicit.is_none() && in_context.is_none() {
+        return Ok(None);
+    }
+    let mut refs = explicit.unwrap_or_default();
+    if let Some(value) = in_context {
+        let parsed: Vec<NewBreadcrumbReference> = serde_json::from_value(value.clone())
+            .map_err(|e| (StatusCode::BAD_REQUEST, format!("{} must be an array of {{field, breadcrumb_id, relation}}: {}", REFS_KEY, e)))?;
+        refs.extend(parsed);
+    }
+    let mut out: Vec<NewBreadcrumbReference> = Vec::with_capacity(refs.len());
+    for r in refs {
+        let (field, relation) = (r.field.trim(), r.relation.trim());
+        if field.is_empty() || relation.is_empty() {
+            return Err((StatusCode::BAD_REQUEST, "references need a non-empty field and relation".into()));
+        }
+        if !out.iter().any(|o| o.field == field && o.breadcrumb_id == r.breadcrumb_id) {
+            out.push(NewBreadcrumbReference { field: field.to_string(), breadcrumb_id: r.breadcrumb_id, relation: relation.to_string() });
+        }
+    }
+    if out.len() > MAX_REFERENCES {
+        return Err((StatusCode::BAD_REQUEST, format!("at most {} references per breadcrumb", MAX_REFERENCES)));
+    }
+    Ok(Some(out))
+}
+
+/// The references a write declares, each pointing at a breadcrumb of the tenant the writer can read.
+/// Call before writing; hand the result to `store` once the breadcrumb is written
+pub async fn declare(state: &AppState, auth: &AuthContext, explicit: Option<Vec<NewBreadcrumbReference>>, context: Option<&Value>) -> Result<Option<Vec<NewBreadcrumbReference>>, (StatusCode, String)> {
+    let Some(refs) = declared(explicit, context)? else { return Ok(None) };
+    let mut ids: Vec<Uuid> = refs.iter().map(|r| r.breadcrumb_id).collect();
+    ids.sort();
+    ids.dedup();
+    if !ids.is_empty() {
+        let found = state.db.readable_breadcrumb_ids(auth.owner_id, Some(auth.agent_id), &ids).await.map_err(db_error)?;
+        let missing: Vec<String> = ids.iter().filter(|id| !found.contains(id)).map(Uuid::to_string).collect();
+        if !missing.is_empty() {
+            return Err((StatusCode::UNPROCESSABLE_ENTITY, format!("references to missing or unreadable breadcrumbs: {}", missing.join(", "))));
+        }
+    }
+    Ok(Some(refs))
+}
+
+/// Replace the written breadcrumb's references with what `declare` returned; `None` keeps them.
+/// A target deleted since `declare` fails this with a 409, after the write itself
+pub async fn store(state: &AppState, auth: &AuthContext, source_id: Uuid, refs: Option<Vec<NewBreadcrumbReference>>) -> Result<(), (StatusCode, String)> {
+    let Some(refs) = refs else { return Ok(()) };
+    state.db.replace_breadcrumb_references(auth.owner_id, auth.agent_id, source_id, &refs).await.map_err(db_error)
+}
+
+/// References the breadcrumb declares, to breadcrumbs the caller can read
+pub async fn list_references(State(state): State<AppState>, auth: AuthContext, Path(id): Path<Uuid>) -> Result<Json<Vec<BreadcrumbReference>>, (StatusCode, String)> {
+    state.db.list_breadcrumb_references(auth.owner_id, Some(auth.agent_id), id).await.map_err(db_error)?
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, "not found".into()))
+}
+
+/// References to the breadcrumb from breadcrumbs the caller can read
+pub async fn list_referenced_by(State(state): State<AppState>, auth: AuthContext, Path(id): Path<Uuid>) -> Result<Json<Vec<BreadcrumbReference>>, (StatusCode, String)> {
+    state.db.list_breadcrumb_referenced_by(auth.owner_id, Some(auth.agent_id), id).await.map_err(db_error)?
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, "not found".into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rcrt_core::models::DEFAULT_RELATION;
+    use serde_json::json;
+
+    fn reference(field: &str, id: Uuid, relation: &str) -> NewBreadcrumbReference {
+        NewBreadcrumbReference { field: field.into(), breadcrumb_id: id, relation: relation.into() }
+    }
+
+    #[test]
+    fn test_nothing_declared_leaves_references_alone() {
+        assert_eq!(declared(None, None).unwrap(), None);
+        assert_eq!(declared(None, Some(&json!({ "trigger_event_id": Uuid::new_v4() }))).unwrap(), None);
+        // An empty array is a declaration: it clears them
+        assert_eq!(declared(Some(vec![]), None).unwrap(), Some(vec![]));
+        assert_eq!(declared(None, Some(&json!({ "$refs": [] }))).unwrap(), Some(vec![]));
+    }
+
+    #[test]
+    fn test_explicit_and_context_refs_merge_and_dedupe() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let context = json!({
+            "trigger_event_id": a,
+            "$refs": [
+                { "field": "trigger_event_id", "breadcrumb_id": a, "relation": "triggered_by" },
+                { "field": "related_ids", "breadcrumb_id": b },
+            ],
+        });
+        let refs = declared(Some(vec![reference(" trigger_event_id ", a, "caused_by")]), Some(&context)).unwrap().unwrap();
+        assert_eq!(refs, vec![
+            reference("trigger_event_id", a, "caused_by"),
+            reference("related_ids", b, DEFAULT_RELATION),
+        ]);
+    }
+
+    #[test]
+    fn test_malformed_declarations_are_rejected() {
+        let bad_refs = declared(None, Some(&json!({ "$refs": { "parent": Uuid::new_v4() } }))).unwrap_err();
+        assert_eq!(bad_refs.0, StatusCode::BAD_REQUEST);
+        let blank = declared(Some(vec![reference(" ", Uuid::new_v4(), "related")]), None).unwrap_err();
+        assert_eq!(blank.0, StatusCode::BAD_REQUEST);
+        let too_many = (0..=MAX_REFERENCES).map(|_| reference("related_ids", Uuid::new_v4(), "related")).collect();
+        assert_eq!(declared(Some(too_many), None).unwrap_err().0, StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,296 @@
+//! Read Replicas
+//! With DB_REPLICA_URL set, pure reads (context views, /full, bulk get, history, lists, search and
+//! suggest) go to a replica pool through `ReadRouter`. Everything on a write path, and reads that must
+//! see a write just made (idempotency checks, the fanout after a write), keep using `AppState::db`, the
+//! primary. A monitor probes the replica's replication lag every DB_REPLICA_LAG_CHECK_SECS; while the
+//! replica is unreachable or trails by more than DB_REPLICA_MAX_STALENESS_MS, reads go to the primary.
+//! A read the replica can't serve is retried on the primary and takes the replica out until the next
+//! probe finds it healthy
+
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use prometheus::{register_gauge, register_int_counter_vec, register_int_gauge_vec, Gauge, IntCounterVec, IntGaugeVec};
+use rcrt_core::db::Db;
+use rcrt_core::error::DbError;
+
+/// Where a read may go
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadPreference {
+    /// Always the primary
+    Primary,
+    /// The replica while it is reachable and fresh enough, else the primary
+    Replica,
+}
+
+/// The pool that served a read
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pool {
+    Primary,
+    Replica,
+}
+
+impl Pool {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Pool::Primary => "primary",
+            Pool::Replica => "replica",
+        }
+    }
+}
+
+// Replica health, as last seen by the monitor or a failed read
+const HEALTHY: u8 = 0;
+const LAGGING: u8 = 1;
+const UNREACHABLE: u8 = 2;
+
+struct Replica {
+    db: Db,
+    max_staleness: Duration,
+    health: AtomicU8,
+}
+
+/// Picks the pool for each read; see the module docs
+pub struct ReadRouter {
+    primary: Db,
+    replica: Option<Replica>,
+    /// Reads served, by Pool
+    served: [AtomicU64; 2],
+}
+
+impl ReadRouter {
+    /// Every read on the primary
+    pub fn primary_only(primary: Db) -> Self {
+        Self { primary, replica: None, served: Default::default() }
+    }
+
+    /// Reads preferring the replica go to `replica` once a probe has found it within `max_staleness`
+    pub fn new(primary: Db, replica: Db, max_staleness: Duration) -> Self {
+        Self {
+            primary,
+            replica: Some(Replica { db: replica, max_staleness, health: AtomicU8::new(UNREACHABLE) }),
+            served: Default::default(),
+        }
+    }
+
+    pub fn has_replica(&self) -> bool {
+        self.replica.is_some()
+    }
+
+    /// Reads `pool` has served through this router
+    pub fn served(&self, pool: Pool) -> u64 {
+        self.served[pool as usize].load(Ordering::Relaxed)
+    }
+
+    fn usable_replica(&self, preference: ReadPreference) -> Option<&Replica> {
+        let replica = self.replica.as_ref().filter(|_| preference == ReadPreference::Replica)?;
+        match replica.health.load(Ordering::Relaxed) {
+            HEALTHY => Some(replica),
+            LAGGING => {
+                fallbacks().with_label_values(&["lagging"]).inc();
+                None
+            }
+            _ => {
+                fallbacks().with_label_values(&["unreachable"]).inc();
+                None
+            }
+        }
+    }
+
+    fn count(&self, pool: Pool) {
+        self.served[pool as usize].fetch_add(1, Ordering::Relaxed);
+        reads().with_label_values(&[pool.as_str()]).inc();
+    }
+
+    /// Run `query` against the pool `preference` allows. A replica read that fails because the replica
+    /// can't serve it is run again on the primary
+    pub async fn read<T, F, Fut>(&self, preference: ReadPreference, query: F) -> Result<T, DbError>
+    where
+        F: Fn(Db) -> Fut,
+        Fut: Future<Output = Result<T, DbError>>,
+    {
+        if let Some(replica) = self.usable_replica(preference) {
+            match query(replica.db.clone()).await {
+                Err(e) if replica_cannot_serve(&e) => {
+                    tracing::warn!("📚 Replica read failed, retrying on the primary: {}", e);
+                    fallbacks().with_label_values(&["error"]).inc();
+                    if unreachable(&e) {
+                        replica.health.store(UNREACHABLE, Ordering::Relaxed);
+                    }
+                }
+                result => {
+                    self.count(Pool::Replica);
+                    return result;
+                }
+            }
+        }
+        self.count(Pool::Primary);
+        query(self.primary.clone()).await
+    }
+
+    /// Probe the replica's lag and decide whether reads may use it
+    pub async fn check_replica(&self) {
+        let Some(replica) = &self.replica else { return };
+        let lag = replica.db.replication_lag().await;
+        self.observe(lag);
+        for (pool, db) in [(Pool::Primary, &self.primary), (Pool::Replica, &replica.db)] {
+            connections().with_label_values(&[pool.as_str(), "open"]).set(db.pool.size() as i64);
+            connections().with_label_values(&[pool.as_str(), "idle"]).set(db.pool.num_idle() as i64);
+        }
+    }
+
+    fn observe(&self, lag: Result<Option<Duration>, DbError>) {
+        let Some(replica) = &self.replica else { return };
+        let health = match lag {
+            Ok(Some(lag)) => {
+                lag_seconds().set(lag.as_secs_f64());
+                if lag <= replica.max_staleness { HEALTHY } else { LAGGING }
+            }
+            // A standby that hasn't replayed anything yet can't say how far behind it is
+            Ok(None) => LAGGING,
+            Err(e) => {
+                tracing::warn!("📚 Replica lag probe failed: {}", e);
+                UNREACHABLE
+            }
+        };
+        if replica.health.swap(health, Ordering::Relaxed) != health {
+            match health {
+                HEALTHY => tracing::info!("📚 Read replica in use"),
+                LAGGING => tracing::warn!("📚 Read replica trails by more than {:?}; reading from the primary", replica.max_staleness),
+                _ => tracing::warn!("📚 Read replica unreachable; reading from the primary"),
+            }
+        }
+    }
+}
+
+/// Can't reach the replica, or it is shutting down or recovering
+fn unreachable(e: &DbError) -> bool {
+    let DbError::Sqlx(e) = e else { return false };
+    matches!(e, sqlx::Error::Io(_) | sqlx::Error::Tls(_) | sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed | sqlx::Error::WorkerCrashed)
+        || e.as_database_error().and_then(|d| d.code()).is_some_and(|code| code.starts_with("57P"))
+}
+
+/// Worth retrying on the primary: an unreachable replica, or a "read" that tried to write
+/// (read_only_sql_transaction)
+fn replica_cannot_serve(e: &DbError) -> bool {
+    unreachable(e) || matches!(e, DbError::Sqlx(e) if e.as_database_error().and_then(|d| d.code()).as_deref() == Some("25006"))
+}
+
+/// Probe the replica every `interval`; nothing to do without one
+pub fn start_monitor(router: Arc<ReadRouter>, interval: Duration) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        if !router.has_replica() {
+            return;
+        }
+        let mut tick = tokio::time::interval(interval.max(Duration::from_secs(1)));
+        loop {
+            tick.tick().await;
+            router.check_replica().await;
+        }
+    })
+}
+
+static READS: OnceLock<IntCounterVec> = OnceLock::new();
+static FALLBACKS: OnceLock<IntCounterVec> = OnceLock::new();
+static LAG: OnceLock<Gauge> = OnceLock::new();
+static CONNECTIONS: OnceLock<IntGaugeVec> = OnceLock::new();
+
+fn reads() -> &'static IntCounterVec {
+    READS.get_or_init(|| register_int_counter_vec!("db_reads_total", "Routed reads by the pool that served them", &["pool"]).unwrap())
+}
+
+fn fallbacks() -> &'static IntCounterVec {
+    FALLBACKS.get_or_init(|| register_int_counter_vec!(
+        "db_replica_fallbacks_total", "Reads that preferred the replica but went to the primary: lagging, unreachable or error", &["reason"]
+    ).unwrap())
+}
+
+fn lag_seconds() -> &'static Gauge {
+    LAG.get_or_init(|| register_gauge!("db_replica_lag_seconds", "Replication lag at the last probe").unwrap())
+}
+
+fn connections() -> &'static IntGaugeVec {
+    CONNECTIONS.get_or_init(|| register_int_gauge_vec!("db_pool_connections", "Pooled connections by pool, open and idle", &["pool", "state"]).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::offline_db;
+
+    async fn ok(_db: Db) -> Result<u8, DbError> {
+        Ok(1)
+    }
+
+    #[tokio::test]
+    async fn test_reads_follow_preference_and_replica_health() {
+        let router = ReadRouter::new(offline_db(), offline_db(), Duration::from_secs(1));
+        // Not probed yet, so not trusted
+        router.read(ReadPreference::Replica, ok).await.unwrap();
+        assert_eq!((router.served(Pool::Primary), router.served(Pool::Replica)), (1, 0));
+
+        router.observe(Ok(Some(Duration::from_millis(200))));
+        router.read(ReadPreference::Replica, ok).await.unwrap();
+        router.read(ReadPreference::Primary, ok).await.unwrap();
+        assert_eq!((router.served(Pool::Primary), router.served(Pool::Replica)), (2, 1));
+
+        router.observe(Ok(Some(Duration::from_secs(5))));
+        router.read(ReadPreference::Replica, ok).await.unwrap();
+        router.observe(Ok(None));
+        router.read(ReadPreference::Replica, ok).await.unwrap();
+        assert_eq!((router.served(Pool::Primary), router.served(Pool::Replica)), (4, 1));
+
+        let primary_only = ReadRouter::primary_only(offline_db());
+        primary_only.observe(Ok(Some(Duration::ZERO)));
+        primary_only.read(ReadPreference::Replica, ok).await.unwrap();
+        assert_eq!(primary_only.served(Pool::Primary), 1);
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_replica_reads_fall_back_to_the_primary(pool: sqlx::PgPool) {
+        use crate::auth::{AuthConfig, AuthMode};
+        use crate::test_support::{request, send, state};
+        use crate::AppState;
+        use axum::http::StatusCode;
+        use serde_json::json;
+        use uuid::Uuid;
+
+        // Two pools on the same database; the test database isn't a standby, so its lag is zero
+        let primary = Db { pool: pool.clone() };
+        let replica = Db { pool: sqlx::postgres::PgPoolOptions::new().max_connections(2).connect_with((*pool.connect_options()).clone()).await.unwrap() };
+        let router = Arc::new(ReadRouter::new(primary.clone(), replica.clone(), Duration::from_secs(1)));
+        router.check_replica().await;
+
+        let (owner_id, agent_id) = (Uuid::new_v4(), Uuid::new_v4());
+        primary.ensure_tenant(owner_id, "Replica Test").await.unwrap();
+        let auth = AuthConfig::new(AuthMode::Disabled { owner_id, agent_id }, None, None, None, None).unwrap();
+        let app = crate::build_app(AppState { reads: router.clone(), ..state(primary, auth).await });
+
+        // The create and its fanout use the primary directly, not the router
+        let (status, body) = send(&app, request("POST", "/breadcrumbs", None, Some(json!({ "title": "Replicated", "context": { "n": 1 }, "tags": ["replica"] })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let id = body["id"].as_str().unwrap().to_string();
+        assert_eq!(router.served(Pool::Replica) + router.served(Pool::Primary), 0);
+
+        for uri in [format!("/breadcrumbs/{}", id), format!("/breadcrumbs/{}/full", id), format!("/breadcrumbs/{}/history", id), "/breadcrumbs?tag=replica".to_string()] {
+            let (status, body) = send(&app, request("GET", &uri, None, None)).await;
+            assert_eq!(status, StatusCode::OK, "{}: {}", uri, body);
+        }
+        let replica_reads = router.served(Pool::Replica);
+        assert!(replica_reads >= 4, "{}", replica_reads);
+        assert_eq!(router.served(Pool::Primary), 0);
+
+        // The replica goes away: the read is retried on the primary, and later ones go there directly
+        replica.pool.close().await;
+        let (status, body) = send(&app, request("GET", &format!("/breadcrumbs/{}", id), None, None)).await;
+        assert_eq!((status, &body["title"]), (StatusCode::OK, &json!("Replicated")));
+        assert_eq!(router.served(Pool::Replica), replica_reads);
+        assert!(router.served(Pool::Primary) >= 1);
+        router.check_replica().await;
+        let before = router.served(Pool::Primary);
+        send(&app, request("GET", &format!("/breadcrumbs/{}/full", id), None, None)).await;
+        assert_eq!(router.served(Pool::Primary), before + 1);
+    }
+}
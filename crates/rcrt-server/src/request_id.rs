@@ -0,0 +1,76 @@
+//! Request IDs
+//! Accepts the caller's X-Request-Id (or makes one), runs the request inside a span carrying it,
+//! echoes it on the response and stamps it on the breadcrumb events the request produces. The span
+//! also continues the caller's `traceparent`, see telemetry. The time the request arrived is kept
+//! alongside, for the events' and create responses' `received_at`
+
+use axum::{extract::Request, http::HeaderValue, middleware::Next, response::Response};
+use chrono::{DateTime, Utc};
+use rcrt_core::trace_context::TRACEPARENT;
+use tracing::Instrument;
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+/// Longer incoming ids are replaced rather than logged
+const MAX_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+    static RECEIVED_AT: DateTime<Utc>;
+}
+
+/// The id of the request this task is serving; None in background tasks
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// When the request this task is serving reached the middleware; None in background tasks
+pub fn received_at() -> Option<DateTime<Utc>> {
+    RECEIVED_AT.try_with(|at| *at).ok()
+}
+
+/// A caller's id is kept when it is short printable ASCII; anything else gets a fresh UUID
+pub fn accept_or_generate(value: Option<&HeaderValue>) -> String {
+    value.and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && id.len() <= MAX_LEN && id.bytes().all(|b| b.is_ascii_graphic()))
+        .map(String::from)
+        .unwrap_or_else(|| Uuid::new_v4().to_string())
+}
+
+pub async fn request_id_middleware(req: Request, next: Next) -> Response {
+    let received_at = Utc::now();
+    let id = accept_or_generate(req.headers().get(REQUEST_ID_HEADER));
+    let traceparent = req.headers().get(TRACEPARENT).and_then(|v| v.to_str().ok());
+    let span = tracing::info_span!("request", request_id = %id, method = %req.method(), path = %req.uri().path(), traceparent);
+    let mut resp = REQUEST_ID.scope(id.clone(), RECEIVED_AT.scope(received_at, next.run(req).instrument(span))).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        resp.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accepts_sane_ids_and_replaces_the_rest() {
+        assert_eq!(accept_or_generate(Some(&HeaderValue::from_static("dash-42"))), "dash-42");
+        assert_eq!(accept_or_generate(Some(&HeaderValue::from_static(" abc "))), "abc");
+        for bad in [HeaderValue::from_static(""), HeaderValue::from_static("has space"), HeaderValue::from_str(&"x".repeat(MAX_LEN + 1)).unwrap()] {
+            assert!(Uuid::parse_str(&accept_or_generate(Some(&bad))).is_ok());
+        }
+        assert!(Uuid::parse_str(&accept_or_generate(None)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_current_is_scoped_to_the_request() {
+        assert_eq!(current(), None);
+        let inside = REQUEST_ID.scope("r1".to_string(), async { current() }).await;
+        assert_eq!(inside.as_deref(), Some("r1"));
+        let at = Utc::now();
+        assert_eq!(received_at(), None);
+        assert_eq!(RECEIVED_AT.scope(at, async { received_at() }).await, Some(at));
+    }
+}
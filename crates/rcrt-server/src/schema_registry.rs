@@ -0,0 +1,349 @@
+//! Schema Registry
+//! Schema names in use with their schema.def.v1 metadata, and definition lookups for writes
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use axum::{extract::State, http::StatusCode, Json};
+use chrono::{DateTime, Utc};
+use rcrt_core::db::Db;
+use rcrt_core::error::DbError;
+use rcrt_core::models::{Breadcrumb, SchemaUsage};
+use rcrt_core::roles::Role;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::{auth::AuthContext, db_errors::db_error, events::publish_breadcrumb_updated, AppState};
+
+pub const SCHEMA_DEF: &str = "schema.def.v1";
+
+/// How long a definition lookup (hit or miss) is reused before re-reading it
+const DEFINITION_TTL: Duration = Duration::from_secs(60);
+
+/// Registered metadata from a schema.def.v1 breadcrumb
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SchemaDefMeta {
+    pub definition_id: Uuid,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub strict: bool,
+    pub deprecated: bool,
+    pub deprecation_message: Option<String>,
+    pub replaced_by: Option<String>,
+}
+
+impl SchemaDefMeta {
+    pub fn from_parts(definition_id: Uuid, title: Option<String>, description: Option<String>, context: &Value) -> Self {
+        let text = |key: &str| context.get(key).and_then(|v| v.as_str()).map(str::to_string);
+        let flag = |key: &str| context.get(key).and_then(|v| v.as_bool()).unwrap_or(false);
+        SchemaDefMeta {
+            definition_id,
+            title,
+            description: description.or_else(|| text("description")).or_else(|| text("purpose")),
+            strict: flag("strict"),
+            deprecated: flag("deprecated"),
+            deprecation_message: text("deprecation_message"),
+            replaced_by: text("replaced_by"),
+        }
+    }
+
+    pub fn from_definition(bc: &Breadcrumb) -> Self {
+        Self::from_parts(bc.id, Some(bc.title.clone()), bc.description.clone(), &bc.context)
+    }
+}
+
+/// The schema a schema.def.v1 breadcrumb defines. Definitions in the wild name it with a
+/// `defines:` tag (what the llm_hints cache reads), `context.defines_schema`,
+/// `context.schema_name` or `context.target_schema`; checked in that order
+pub fn defined_schema(tags: &[String], context: &Value) -> Option<String> {
+    tags.iter()
+        .find_map(|t| t.strip_prefix("defines:"))
+        .map(str::to_string)
+        .or_else(|| {
+            ["defines_schema", "schema_name", "target_schema"].iter()
+                .find_map(|key| context.get(*key).and_then(|v| v.as_str()))
+                .map(str::to_string)
+        })
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SchemaEntry {
+    pub schema_name: String,
+    pub count: i64,
+    /// None for schemas that are defined but have no breadcrumbs yet
+    pub first_seen: Option<DateTime<Utc>>,
+    pub last_used: Option<DateTime<Utc>>,
+    pub definition: Option<SchemaDefMeta>,
+}
+
+/// One entry per schema that is either used or defined, sorted by name. `definitions` is
+/// newest first, so the most recently updated definition of a schema wins
+pub fn merge_entries(usage: Vec<SchemaUsage>, definitions: &[Breadcrumb]) -> Vec<SchemaEntry> {
+    let mut defs: HashMap<String, SchemaDefMeta> = HashMap::new();
+    for bc in definitions {
+        if let Some(name) = defined_schema(&bc.tags, &bc.context) {
+            defs.entry(name).or_insert_with(|| SchemaDefMeta::from_definition(bc));
+        }
+    }
+    let mut entries: Vec<SchemaEntry> = usage.into_iter().map(|u| SchemaEntry {
+        definition: defs.remove(&u.schema_name),
+        schema_name: u.schema_name,
+        count: u.count,
+        first_seen: Some(u.first_seen),
+        last_used: Some(u.last_used),
+    }).collect();
+    entries.extend(defs.into_iter().map(|(schema_name, definition)| SchemaEntry {
+        schema_name,
+        count: 0,
+        first_seen: None,
+        last_used: None,
+        definition: Some(definition),
+    }));
+    entries.sort_by(|a, b| a.schema_name.cmp(&b.schema_name));
+    entries
+}
+
+/// Cached schema definition lookups for the create path: deprecations, and the definition
+/// POST /breadcrumbs/validate reports
+pub struct SchemaRegistry {
+    db: Db,
+    definitions: RwLock<HashMap<String, (Option<SchemaDefMeta>, Instant)>>,
+}
+
+impl SchemaRegistry {
+    pub fn new(db: Db) -> Self {
+        Self { db, definitions: RwLock::new(HashMap::new()) }
+    }
+
+    /// The definition of `schema_name` if it is marked deprecated
+    pub async fn deprecation(&self, schema_name: &str) -> Option<SchemaDefMeta> {
+        self.definition(schema_name).await.filter(|meta| meta.deprecated)
+    }
+
+    /// The newest schema.def.v1 defining `schema_name`, if any
+    pub async fn definition(&self, schema_name: &str) -> Option<SchemaDefMeta> {
+        {
+            let cache = self.definitions.read().await;
+            if let Some((meta, at)) = cache.get(schema_name) {
+                if at.elapsed() < DEFINITION_TTL {
+                    return meta.clone();
+                }
+            }
+        }
+
+        // Read like the llm_hints cache: definitions apply to every tenant
+        let row = sqlx::query_as::<_, (Uuid, Option<String>, Option<String>, Value)>(
+            r#"SELECT id, title, description, context FROM breadcrumbs
+               WHERE schema_name = 'schema.def.v1'
+               AND ($1 = ANY(tags) OR context->>'defines_schema' = $2 OR context->>'schema_name' = $2 OR context->>'target_schema' = $2)
+               ORDER BY updated_at DESC
+               LIMIT 1"#
+        )
+        .bind(format!("defines:{}", schema_name))
+        .bind(schema_name)
+        .fetch_optional(&self.db.pool)
+        .await;
+        let meta = match row {
+            Ok(row) => row.map(|(id, title, description, context)| SchemaDefMeta::from_parts(id, title, description, &context)),
+            Err(e) => {
+                // Don't cache failures; the write itself already succeeded
+                tracing::warn!("Failed to look up schema definition for {}: {}", schema_name, e);
+                return None;
+            }
+        };
+
+        self.definitions.write().await.insert(schema_name.to_string(), (meta.clone(), Instant::now()));
+        meta
+    }
+
+    /// Drop cached lookups after a schema.def.v1 write
+    pub async fn invalidate(&self) {
+        self.definitions.write().await.clear();
+    }
+}
+
+pub async fn list_schemas(State(state): State<AppState>, auth: AuthContext) -> Result<Json<Vec<SchemaEntry>>, (StatusCode, String)> {
+    let usage = state.db.list_schema_usage(auth.owner_id, Some(auth.agent_id), None).await.map_err(db_error)?;
+    let definitions = state.db.list_schema_definitions(auth.owner_id, Some(auth.agent_id)).await.map_err(db_error)?;
+    Ok(Json(merge_entries(usage, &definitions)))
+}
+
+#[derive(Serialize)]
+pub struct SchemaDetail {
+    #[serde(flatten)]
+    entry: SchemaEntry,
+    /// Most recently updated breadcrumbs with this schema
+    sample_ids: Vec<Uuid>,
+}
+
+pub async fn get_schema(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(name): axum::extract::Path<String>) -> Result<Json<SchemaDetail>, (StatusCode, String)> {
+    let usage = state.db.list_schema_usage(auth.owner_id, Some(auth.agent_id), Some(&name)).await.map_err(db_error)?;
+    let definitions: Vec<_> = state.db.list_schema_definitions(auth.owner_id, Some(auth.agent_id)).await.map_err(db_error)?
+        .into_iter()
+        .filter(|bc| defined_schema(&bc.tags, &bc.context).as_deref() == Some(name.as_str()))
+        .collect();
+    let Some(entry) = merge_entries(usage, &definitions).into_iter().next() else {
+        return Err((StatusCode::NOT_FOUND, "schema not found".into()));
+    };
+    let sample_ids = state.db.sample_breadcrumb_ids(auth.owner_id, Some(auth.agent_id), &name, 10).await.map_err(db_error)?;
+    Ok(Json(SchemaDetail { entry, sample_ids }))
+}
+
+#[derive(Deserialize)]
+pub struct SchemaStatusReq { deprecated: bool, message: Option<String>, replaced_by: Option<String> }
+
+pub async fn update_schema_status(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(name): axum::extract::Path<String>, Json(req): Json<SchemaStatusReq>) -> Result<Json<SchemaDefMeta>, (StatusCode, String)> {
+    if !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "curator role required".into())); }
+    let Some(def) = state.db.list_schema_definitions(auth.owner_id, Some(auth.agent_id)).await.map_err(db_error)?
+        .into_iter()
+        .find(|bc| defined_schema(&bc.tags, &bc.context).as_deref() == Some(name.as_str())) else {
+        return Err((StatusCode::NOT_FOUND, format!("no {} breadcrumb defines {}", SCHEMA_DEF, name)));
+    };
+
+    let mut context = def.context.clone();
+    let Some(fields) = context.as_object_mut() else {
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, "schema definition context is not an object".into()));
+    };
+    fields.insert("deprecated".into(), json!(req.deprecated));
+    if req.deprecated {
+        if let Some(message) = req.message { fields.insert("deprecation_message".into(), json!(message)); }
+        if let Some(replaced_by) = req.replaced_by { fields.insert("replaced_by".into(), json!(replaced_by)); }
+    } else {
+        fields.remove("deprecation_message");
+        fields.remove("replaced_by");
+    }
+
+    let upd = rcrt_core::models::BreadcrumbUpdate {
+        title: None,
+        description: None,
+        semantic_version: None,
+        context: Some(context),
+        tags: None,
+        schema_name: None,
+        llm_hints: None,
+        visibility: None,
+        sensitivity: None,
+        ttl: None,
+        ttl_type: None,
+        ttl_config: None,
+        ttl_source: None,
+    };
+    let bc = state.db.update_breadcrumb(auth.owner_id, auth.agent_id, def.id, Some(def.version), upd).await.map_err(|e| {
+        // The caller sent no If-Match, so losing a race with another writer is a conflict, not a 412
+        match e {
+            DbError::VersionMismatch(_) => (StatusCode::CONFLICT, e.to_string()),
+            e => db_error(e),
+        }
+    })?;
+    state.schema_registry.invalidate().await;
+    tracing::info!("📐 Schema {} deprecated={} by agent {}", name, req.deprecated, auth.agent_id);
+    publish_breadcrumb_updated(&state, auth.owner_id, &bc).await;
+    Ok(Json(SchemaDefMeta::from_definition(&bc)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(title: &str, tags: &[&str], context: Value) -> Breadcrumb {
+        Breadcrumb {
+            id: Uuid::new_v4(),
+            owner_id: Uuid::new_v4(),
+            title: title.to_string(),
+            description: None,
+            semantic_version: None,
+            context,
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            schema_name: Some(SCHEMA_DEF.to_string()),
+            llm_hints: None,
+            visibility: rcrt_core::models::Visibility::Team,
+            sensitivity: rcrt_core::models::Sensitivity::Low,
+            version: 1,
+            checksum: String::new(),
+            ttl: None,
+            ttl_type: None,
+            ttl_config: None,
+            read_count: None,
+            ttl_source: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            created_by: None,
+            updated_by: None,
+            size_bytes: 0,
+        }
+    }
+
+    #[test]
+    fn test_defined_schema_conventions() {
+        let tags = |t: &[&str]| t.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(defined_schema(&tags(&["schema", "defines:user.message.v1"]), &json!({})).as_deref(), Some("user.message.v1"));
+        assert_eq!(defined_schema(&tags(&["schema:definition"]), &json!({ "defines_schema": "tool.code.v1" })).as_deref(), Some("tool.code.v1"));
+        assert_eq!(defined_schema(&tags(&["schema:ui.page.v1"]), &json!({ "schema_name": "ui.page.v1" })).as_deref(), Some("ui.page.v1"));
+        assert_eq!(defined_schema(&tags(&["schema"]), &json!({ "target_schema": "note.v1" })).as_deref(), Some("note.v1"));
+        assert_eq!(defined_schema(&tags(&["schema"]), &json!({})), None);
+    }
+
+    #[test]
+    fn test_merge_includes_defined_but_unused_schemas() {
+        let now = Utc::now();
+        let usage = vec![SchemaUsage { schema_name: "user.message.v1".into(), count: 3, first_seen: now, last_used: now }];
+        let defs = vec![
+            definition("Old Tool", &["defines:tool.old.v1"], json!({ "deprecated": true, "replaced_by": "tool.new.v1", "strict": true })),
+            definition("User Message", &["defines:user.message.v1"], json!({ "purpose": "Chat input" })),
+        ];
+
+        let entries = merge_entries(usage, &defs);
+
+        assert_eq!(entries.iter().map(|e| e.schema_name.as_str()).collect::<Vec<_>>(), vec!["tool.old.v1", "user.message.v1"]);
+        let old = entries[0].definition.as_ref().unwrap();
+        assert!(old.deprecated && old.strict);
+        assert_eq!(old.replaced_by.as_deref(), Some("tool.new.v1"));
+        assert_eq!(entries[0].count, 0);
+        assert!(entries[0].first_seen.is_none());
+        assert_eq!(entries[1].count, 3);
+        assert_eq!(entries[1].definition.as_ref().unwrap().description.as_deref(), Some("Chat input"));
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_deprecated_schema_sets_header(pool: sqlx::PgPool) {
+        use crate::auth::{AuthConfig, AuthMode};
+        use crate::test_support::state;
+        use axum::{body::Body, http::header};
+        use tower::ServiceExt;
+
+        let db = Db { pool };
+        let (owner_id, agent_id) = (Uuid::new_v4(), Uuid::new_v4());
+        db.ensure_tenant(owner_id, "Schema Registry Test").await.unwrap();
+        db.upsert_agent(owner_id, agent_id, vec!["curator".into()]).await.unwrap();
+        let auth = AuthConfig::new(AuthMode::Disabled { owner_id, agent_id }, None, None, None, None).unwrap();
+        let app = crate::build_app(state(db, auth).await);
+        let send = |method: &str, uri: &str, body: serde_json::Value| {
+            axum::http::Request::builder().method(method).uri(uri)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let create = |schema: &str| send("POST", "/breadcrumbs", json!({
+            "title": "Schema test", "context": {}, "tags": ["defines:tool.old.v1"], "schema_name": schema
+        }));
+
+        assert_eq!(app.clone().oneshot(create(SCHEMA_DEF)).await.unwrap().status(), StatusCode::OK);
+        let res = app.clone().oneshot(create("tool.old.v1")).await.unwrap();
+        assert!(res.headers().get("Deprecation").is_none());
+
+        let res = app.clone().oneshot(send("PUT", "/schemas/tool.old.v1", json!({ "deprecated": true, "replaced_by": "tool.new.v1" }))).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = app.clone().oneshot(create("tool.old.v1")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("Deprecation").unwrap(), "true");
+
+        let res = app.clone().oneshot(axum::http::Request::get("/schemas/tool.old.v1").body(Body::empty()).unwrap()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&axum::body::to_bytes(res.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(body["count"], 2);
+        assert_eq!(body["definition"]["deprecated"], true);
+        assert_eq!(body["definition"]["replaced_by"], "tool.new.v1");
+        assert_eq!(body["sample_ids"].as_array().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,270 @@
+//! Search Cache
+//! Short-lived caches for GET /breadcrumbs/search, where agents repeat a query within seconds (retries,
+//! several agents handed the same question). Query embeddings are kept by text for longer, since the
+//! model is deterministic, and concurrent misses for one text share a single embedder call. Ranked
+//! result ids are kept for a few seconds by owner, reader, vector, target, filters and limit. Writes
+//! invalidate neither: within the TTL a repeated search can miss breadcrumbs created or re-embedded
+//! since, or rank a changed one where it was. The rows themselves are re-read on every hit, so edits,
+//! deletes and access changes show right away
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use prometheus::{IntCounterVec, IntGaugeVec, register_int_counter_vec, register_int_gauge_vec};
+use sha2::{Digest, Sha256};
+use tokio::sync::OnceCell;
+use uuid::Uuid;
+
+use crate::breadcrumb_filter::BreadcrumbFilter;
+use crate::domain_metrics;
+use crate::embedding::Embedder;
+
+static LOOKUPS: OnceLock<IntCounterVec> = OnceLock::new();
+static ENTRIES: OnceLock<IntGaugeVec> = OnceLock::new();
+
+fn lookups() -> &'static IntCounterVec {
+    LOOKUPS.get_or_init(|| register_int_counter_vec!("search_cache_total", "Search cache lookups", &["cache", "result"]).unwrap())
+}
+
+fn entries() -> &'static IntGaugeVec {
+    ENTRIES.get_or_init(|| register_int_gauge_vec!("search_cache_entries", "Entries held by the search caches", &["cache"]).unwrap())
+}
+
+/// SHA-256 of what decides a cached value
+pub type Key = [u8; 32];
+
+struct Entry<V> {
+    inserted: Instant,
+    /// Matches the `TtlMap::order` item that inserted it
+    tick: u64,
+    value: V,
+}
+
+/// At most `max_entries` values, each gone `ttl` after it was inserted; the oldest go first when full
+struct TtlMap<V> {
+    ttl: Duration,
+    max_entries: usize,
+    entries: HashMap<Key, Entry<V>>,
+    /// Insertion order, oldest first; items whose key was re-inserted since are skipped
+    order: VecDeque<(u64, Key)>,
+    tick: u64,
+}
+
+impl<V: Clone> TtlMap<V> {
+    fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self { ttl, max_entries: max_entries.max(1), entries: HashMap::new(), order: VecDeque::new(), tick: 0 }
+    }
+
+    fn get(&self, key: &Key, now: Instant) -> Option<V> {
+        self.entries.get(key).filter(|e| now.duration_since(e.inserted) < self.ttl).map(|e| e.value.clone())
+    }
+
+    fn insert(&mut self, key: Key, value: V, now: Instant) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        self.entries.remove(&key);
+        // Insertion order is also expiry order, so expired entries are all at the front
+        while let Some(&(tick, oldest)) = self.order.front() {
+            let evict = match self.entries.get(&oldest) {
+                Some(e) if e.tick == tick => now.duration_since(e.inserted) >= self.ttl || self.entries.len() >= self.max_entries,
+                _ => true,
+            };
+            if !evict {
+                break;
+            }
+            self.order.pop_front();
+            if self.entries.get(&oldest).is_some_and(|e| e.tick == tick) {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.tick += 1;
+        self.order.push_back((self.tick, key));
+        self.entries.insert(key, Entry { inserted: now, tick: self.tick, value });
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+fn count(cache: &str, hit: bool) {
+    lookups().with_label_values(&[cache, if hit { "hit" } else { "miss" }]).inc();
+}
+
+/// Query embeddings by text; an entry is claimed before the embedder runs, so requests arriving while
+/// it does wait for that result. A failed embedding is not kept and the next request tries again
+pub struct QueryEmbeddings {
+    embedder: Arc<dyn Embedder>,
+    cells: Mutex<TtlMap<Arc<OnceCell<Vec<f32>>>>>,
+}
+
+impl QueryEmbeddings {
+    fn new(embedder: Arc<dyn Embedder>, ttl: Duration, max_entries: usize) -> Self {
+        Self { embedder, cells: Mutex::new(TtlMap::new(ttl, max_entries)) }
+    }
+
+    /// The embedding of `text`, already passed through `embedding::standalone_text`
+    pub async fn embed(&self, text: String) -> Result<Vec<f32>, String> {
+        let key: Key = Sha256::digest(text.as_bytes()).into();
+        let cell = match self.cells.lock() {
+            Ok(mut cells) => {
+                let now = Instant::now();
+                let cached = cells.get(&key, now);
+                count("embedding", cached.is_some());
+                cached.unwrap_or_else(|| {
+                    let cell = Arc::new(OnceCell::new());
+                    cells.insert(key, cell.clone(), now);
+                    entries().with_label_values(&["embedding"]).set(cells.len() as i64);
+                    cell
+                })
+            }
+            Err(_) => Arc::new(OnceCell::new()),
+        };
+        let embedder = self.embedder.clone();
+        cell.get_or_try_init(|| async move {
+            let _timer = domain_metrics::embedding_timer("query");
+            embedder.embed(text)
+        }).await.cloned()
+    }
+}
+
+/// Ranked breadcrumb ids of recent searches
+pub struct SearchResults {
+    ids: Mutex<TtlMap<Arc<Vec<Uuid>>>>,
+}
+
+impl SearchResults {
+    fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self { ids: Mutex::new(TtlMap::new(ttl, max_entries)) }
+    }
+
+    pub fn get(&self, key: &Key) -> Option<Arc<Vec<Uuid>>> {
+        let found = self.ids.lock().ok().and_then(|ids| ids.get(key, Instant::now()));
+        count("results", found.is_some());
+        found
+    }
+
+    pub fn insert(&self, key: Key, ids: Vec<Uuid>) {
+        if let Ok(mut cached) = self.ids.lock() {
+            cached.insert(key, Arc::new(ids), Instant::now());
+            entries().with_label_values(&["results"]).set(cached.len() as i64);
+        }
+    }
+}
+
+/// Both caches; a TTL of 0 turns that one off
+pub struct SearchCache {
+    pub embeddings: QueryEmbeddings,
+    pub results: SearchResults,
+}
+
+impl SearchCache {
+    pub fn new(embedder: Arc<dyn Embedder>, results_ttl: Duration, results_max_entries: usize, embedding_ttl: Duration, embedding_max_entries: usize) -> Self {
+        Self {
+            embeddings: QueryEmbeddings::new(embedder, embedding_ttl, embedding_max_entries),
+            results: SearchResults::new(results_ttl, results_max_entries),
+        }
+    }
+}
+
+/// Key of one search's ranked ids. `reader` is the agent whose read rules filtered the ranking, `None`
+/// for curators, who read every row; `target` names the ranked vector
+pub fn search_key(owner_id: Uuid, reader: Option<Uuid>, qvec: &[f32], target: &str, filter: &BreadcrumbFilter, limit: i64) -> Key {
+    let mut hasher = Sha256::new();
+    hasher.update(owner_id.as_bytes());
+    hasher.update(reader.unwrap_or_else(Uuid::nil).as_bytes());
+    for x in qvec {
+        hasher.update(x.to_le_bytes());
+    }
+    hasher.update(target.as_bytes());
+    hasher.update(format!("{:?}", filter).as_bytes());
+    hasher.update(limit.to_le_bytes());
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Counts calls; slow enough that concurrent callers overlap
+    #[derive(Default)]
+    struct CountingEmbedder(AtomicUsize);
+
+    impl Embedder for CountingEmbedder {
+        fn embed(&self, text: String) -> Result<Vec<f32>, String> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(50));
+            Ok(vec![text.len() as f32])
+        }
+    }
+
+    fn key(n: u8) -> Key {
+        [n; 32]
+    }
+
+    #[test]
+    fn test_entries_expire_and_the_oldest_are_evicted() {
+        let start = Instant::now();
+        let mut map = TtlMap::new(Duration::from_secs(10), 2);
+        map.insert(key(1), 1, start);
+        map.insert(key(2), 2, start + Duration::from_secs(1));
+        assert_eq!(map.get(&key(1), start + Duration::from_secs(9)), Some(1));
+        assert_eq!(map.get(&key(1), start + Duration::from_secs(10)), None);
+        // Re-inserting 1 makes 2 the oldest
+        map.insert(key(1), 11, start + Duration::from_secs(2));
+        map.insert(key(3), 3, start + Duration::from_secs(3));
+        assert_eq!(map.len(), 2);
+        let now = start + Duration::from_secs(3);
+        assert_eq!((map.get(&key(1), now), map.get(&key(2), now), map.get(&key(3), now)), (Some(11), None, Some(3)));
+
+        let mut off = TtlMap::new(Duration::ZERO, 2);
+        off.insert(key(1), 1, start);
+        assert_eq!(off.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_query_skips_the_embedder() {
+        let embedder = Arc::new(CountingEmbedder::default());
+        let cache = SearchCache::new(embedder.clone(), Duration::from_secs(10), 10, Duration::from_secs(60), 10);
+        assert_eq!(cache.embeddings.embed("refund policy".into()).await.unwrap(), vec![13.0]);
+        assert_eq!(cache.embeddings.embed("refund policy".into()).await.unwrap(), vec![13.0]);
+        assert_eq!(embedder.0.load(Ordering::SeqCst), 1);
+        cache.embeddings.embed("shipping".into()).await.unwrap();
+        assert_eq!(embedder.0.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_misses_share_one_embedding() {
+        let embedder = Arc::new(CountingEmbedder::default());
+        let cache = Arc::new(SearchCache::new(embedder.clone(), Duration::from_secs(10), 10, Duration::from_secs(60), 10));
+        let calls: Vec<_> = (0..8).map(|_| {
+            let cache = cache.clone();
+            tokio::spawn(async move { cache.embeddings.embed("same question".into()).await })
+        }).collect();
+        for call in calls {
+            assert_eq!(call.await.unwrap().unwrap(), vec![13.0]);
+        }
+        assert_eq!(embedder.0.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_search_key_covers_every_input() {
+        let owner = Uuid::new_v4();
+        let filter = BreadcrumbFilter::default();
+        let base = search_key(owner, None, &[0.5, 0.5], "Content", &filter, 5);
+        assert_eq!(base, search_key(owner, None, &[0.5, 0.5], "Content", &filter, 5));
+        let tagged = BreadcrumbFilter { tag: Some("kb".into()), ..BreadcrumbFilter::default() };
+        for other in [
+            search_key(Uuid::new_v4(), None, &[0.5, 0.5], "Content", &filter, 5),
+            search_key(owner, Some(Uuid::new_v4()), &[0.5, 0.5], "Content", &filter, 5),
+            search_key(owner, None, &[0.5, 0.25], "Content", &filter, 5),
+            search_key(owner, None, &[0.5, 0.5], "Title", &filter, 5),
+            search_key(owner, None, &[0.5, 0.5], "Content", &tagged, 5),
+            search_key(owner, None, &[0.5, 0.5], "Content", &filter, 6),
+        ] {
+            assert_ne!(base, other);
+        }
+    }
+}
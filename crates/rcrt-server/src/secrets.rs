@@ -0,0 +1,92 @@
+//! Secret Handlers
+//! Envelope-encrypted secrets: AES-GCM values with DEKs wrapped by the local KEK (LOCAL_KEK_BASE64), see `envelope`
+
+use axum::{extract::{Query, State}, http::StatusCode, Json};
+use rcrt_core::roles::Role;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{auth::AuthContext, db_errors::db_error, envelope, AppState};
+
+#[derive(Deserialize)]
+pub struct SecretCreateReq { name: String, scope_type: String, scope_id: Option<Uuid>, value: String }
+pub async fn create_secret(State(state): State<AppState>, auth: AuthContext, Json(req): Json<SecretCreateReq>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "curator role required".into())); }
+    let kek = envelope::local_kek()?;
+    let (enc_blob, dek_encrypted) = envelope::seal(&kek, req.value.as_bytes())?;
+    let secret_id = state.db.create_secret(auth.owner_id, &req.name, &req.scope_type, req.scope_id, &enc_blob, &dek_encrypted, "local-keK").await.map_err(db_error)?;
+    Ok(Json(json!({"id": secret_id})))
+}
+
+#[derive(Deserialize)]
+pub struct SecretDecryptReq { reason: Option<String> }
+pub async fn decrypt_secret(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(secret_id): axum::extract::Path<Uuid>, Json(req): Json<SecretDecryptReq>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    // Fetch secret materials
+    let Some((enc_blob, dek_wrapped, _kek_id)) = state.db.get_secret_material(auth.owner_id, secret_id).await.map_err(db_error)? else {
+        return Err((StatusCode::NOT_FOUND, "not found".into()));
+    };
+    let kek = envelope::local_kek()?;
+    let plaintext = envelope::open(&kek, &enc_blob, &dek_wrapped)?;
+    state.db.audit_secret(secret_id, Some(auth.agent_id), "decrypt", req.reason.as_deref()).await.map_err(db_error)?;
+    Ok(Json(json!({"value": String::from_utf8_lossy(&plaintext)})))
+}
+
+pub async fn list_secrets(State(state): State<AppState>, auth: AuthContext, Query(q): Query<ListSecretsQuery>) -> Result<Json<Vec<serde_json::Value>>, (StatusCode, String)> {
+    // List secrets for the authenticated owner, optionally filtered by scope
+    let rows = state.db.list_secrets(auth.owner_id, q.scope_type.as_deref(), q.scope_id).await.map_err(db_error)?;
+    let out = rows.into_iter().map(|(id, name, scope_type, scope_id, created_at)| {
+        json!({
+            "id": id,
+            "name": name,
+            "scope_type": scope_type,
+            "scope_id": scope_id,
+            "created_at": created_at
+        })
+    }).collect();
+    Ok(Json(out))
+}
+
+#[derive(Deserialize)]
+pub struct ListSecretsQuery { 
+    scope_type: Option<String>, 
+    scope_id: Option<Uuid> 
+}
+
+pub async fn update_secret(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(secret_id): axum::extract::Path<Uuid>, Json(req): Json<SecretUpdateReq>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if !auth.has_role(Role::Curator) { 
+        return Err((StatusCode::FORBIDDEN, "curator role required".into())); 
+    }
+    
+    // Re-encrypt under a new DEK
+    let kek = envelope::local_kek()?;
+    let (enc_blob, dek_encrypted) = envelope::seal(&kek, req.value.as_bytes())?;
+    
+    // Update in database
+    state.db.update_secret(auth.owner_id, secret_id, &enc_blob, &dek_encrypted).await.map_err(db_error)?;
+    state.db.audit_secret(secret_id, Some(auth.agent_id), "update", Some("value updated")).await.map_err(db_error)?;
+    
+    Ok(Json(json!({"ok": true})))
+}
+
+#[derive(Deserialize)]
+pub struct SecretUpdateReq { 
+    value: String 
+}
+
+pub async fn delete_secret(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(secret_id): axum::extract::Path<Uuid>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if !auth.has_role(Role::Curator) { 
+        return Err((StatusCode::FORBIDDEN, "curator role required".into())); 
+    }
+    
+    // Audit before deletion
+    state.db.audit_secret(secret_id, Some(auth.agent_id), "delete", Some("secret deleted")).await.map_err(db_error)?;
+    
+    // Delete the secret
+    let rows = state.db.delete_secret(auth.owner_id, secret_id).await.map_err(db_error)?;
+    if rows == 0 { 
+        return Err((StatusCode::NOT_FOUND, "secret not found".into())); 
+    }
+    
+    Ok(Json(json!({"ok": true})))
+}
@@ -0,0 +1,554 @@
+//! Selector Matching
+//! Shared matcher for selector subscriptions (webhook/agent fanout) and SSE filters
+//!
+//! Tag patterns support glob-style wildcards at either end:
+//! `session:*` (prefix), `*:error` (suffix), `*debug*` (contains), `*` (any tag).
+//! A `*` anywhere else is matched literally.
+//!
+//! Evaluation order:
+//! 1. `schema_name` must equal the event schema (if set)
+//! 2. `any_tags`: at least one pattern matches at least one event tag
+//! 3. `all_tags`: every pattern matches at least one event tag
+//! 4. `context_match` rules on top-level `$.key` paths
+//! 5. `none_tags` last: the event is rejected if any event tag matches any pattern
+//!
+//! Wildcards are expanded against the event's tags, so `none_tags` always wins:
+//! a tag that satisfies an `all_tags` glob and a `none_tags` glob rejects the event.
+//!
+//! Fanout doesn't run every owner selector per event: `SelectorIndex` narrows them to
+//! candidates by exact tag, tag prefix or schema first, and only those see the full matcher.
+
+use rcrt_core::db::Db;
+use rcrt_core::models::{ContextMatch, Selector, SelectorSubscription};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq)]
+enum TagPattern {
+    Exact(String),
+    Prefix(String),
+    Suffix(String),
+    Contains(String),
+    Any,
+}
+
+impl TagPattern {
+    fn compile(pattern: &str) -> Self {
+        if pattern == "*" {
+            return TagPattern::Any;
+        }
+        let starts = pattern.starts_with('*');
+        let ends = pattern.ends_with('*');
+        match (starts, ends) {
+            (true, true) => TagPattern::Contains(pattern[1..pattern.len() - 1].to_string()),
+            (false, true) => TagPattern::Prefix(pattern[..pattern.len() - 1].to_string()),
+            (true, false) => TagPattern::Suffix(pattern[1..].to_string()),
+            (false, false) => TagPattern::Exact(pattern.to_string()),
+        }
+    }
+
+    fn matches(&self, tag: &str) -> bool {
+        match self {
+            TagPattern::Exact(s) => tag == s,
+            TagPattern::Prefix(p) => tag.starts_with(p.as_str()),
+            TagPattern::Suffix(s) => tag.ends_with(s.as_str()),
+            TagPattern::Contains(s) => tag.contains(s.as_str()),
+            TagPattern::Any => true,
+        }
+    }
+
+    fn matches_any(&self, tags: &[String]) -> bool {
+        tags.iter().any(|t| self.matches(t))
+    }
+}
+
+/// A selector with its tag patterns pre-compiled
+#[derive(Debug, Clone)]
+pub struct CompiledSelector {
+    any_tags: Option<Vec<TagPattern>>,
+    all_tags: Option<Vec<TagPattern>>,
+    none_tags: Option<Vec<TagPattern>>,
+    schema_name: Option<String>,
+    context_match: Option<Vec<ContextMatch>>,
+}
+
+fn compile_patterns(patterns: &Option<Vec<String>>) -> Option<Vec<TagPattern>> {
+    patterns.as_ref().map(|v| v.iter().map(|p| TagPattern::compile(p)).collect())
+}
+
+impl CompiledSelector {
+    pub fn compile(selector: &Selector) -> Self {
+        Self {
+            any_tags: compile_patterns(&selector.any_tags),
+            all_tags: compile_patterns(&selector.all_tags),
+            none_tags: compile_patterns(&selector.none_tags),
+            schema_name: selector.schema_name.clone(),
+            context_match: selector.context_match.clone(),
+        }
+    }
+
+    /// Check whether an event with these tags/schema/context matches
+    pub fn matches(&self, tags: &[String], schema_name: Option<&str>, context: &Value) -> bool {
+        if let Some(sn) = &self.schema_name {
+            if schema_name != Some(sn.as_str()) { return false; }
+        }
+        if let Some(any) = &self.any_tags {
+            if !any.iter().any(|p| p.matches_any(tags)) { return false; }
+        }
+        if let Some(all) = &self.all_tags {
+            if !all.iter().all(|p| p.matches_any(tags)) { return false; }
+        }
+        if let Some(cm) = &self.context_match {
+            if !cm.iter().all(|rule| context_rule_matches(rule, context)) { return false; }
+        }
+        // none_tags evaluated last: exclusions override every positive match
+        if let Some(none) = &self.none_tags {
+            if none.iter().any(|p| p.matches_any(tags)) { return false; }
+        }
+        true
+    }
+
+    /// Match against an event payload (`tags`, `schema_name`, `context` fields)
+    pub fn matches_event(&self, event: &Value) -> bool {
+        let tags: Vec<String> = event.get("tags")
+            .and_then(|v| v.as_array())
+            .map(|arr| arr.iter().filter_map(|t| t.as_str().map(|s| s.to_string())).collect())
+            .unwrap_or_default();
+        let schema_name = event.get("schema_name").and_then(|v| v.as_str());
+        let context = event.get("context").unwrap_or(&Value::Null);
+        self.matches(&tags, schema_name, context)
+    }
+}
+
+/// simple context_match: only `$.key` paths on top-level keys for now
+fn context_rule_matches(rule: &ContextMatch, context: &Value) -> bool {
+    // support $.key format only
+    if !rule.path.starts_with("$.") { return true; }
+    let key = &rule.path[2..];
+    let val = context.get(key);
+    match rule.op.as_str() {
+        "eq" => val == Some(&rule.value),
+        "contains_any" => {
+            if let (Some(Value::Array(arr)), Value::Array(needles)) = (val, &rule.value) {
+                needles.iter().any(|n| arr.contains(n))
+            } else { true }
+        }
+        _ => true
+    }
+}
+
+/// Cache of compiled selectors keyed by subscription id
+///
+/// Entries are keyed on the selector's JSON so updated selectors recompile on next use.
+#[derive(Default)]
+pub struct SelectorMatcherCache {
+    compiled: RwLock<HashMap<Uuid, (String, Arc<CompiledSelector>)>>,
+}
+
+impl SelectorMatcherCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the compiled matcher for a subscription, compiling if missing or stale
+    pub fn get_or_compile(&self, id: Uuid, selector: &Selector) -> Arc<CompiledSelector> {
+        let fingerprint = serde_json::to_string(selector).unwrap_or_default();
+        if let Ok(cache) = self.compiled.read() {
+            if let Some((fp, compiled)) = cache.get(&id) {
+                if *fp == fingerprint { return compiled.clone(); }
+            }
+        }
+        let compiled = Arc::new(CompiledSelector::compile(selector));
+        if let Ok(mut cache) = self.compiled.write() {
+            cache.insert(id, (fingerprint, compiled.clone()));
+        }
+        compiled
+    }
+
+    /// Drop a cached matcher (selector updated or deleted)
+    pub fn invalidate(&self, id: Uuid) {
+        if let Ok(mut cache) = self.compiled.write() {
+            cache.remove(&id);
+        }
+    }
+}
+
+/// What an event must carry for a selector to have any chance of matching
+enum IndexKey {
+    Tag(String),
+    TagPrefix(String),
+    Schema(String),
+}
+
+/// One owner's selector subscriptions, bucketed so an event only meets the selectors it could match.
+///
+/// Each selector is filed under the keys one of which every matching event must hit:
+/// an exact or prefix `all_tags` pattern, else every `any_tags` pattern if they're all exact or
+/// prefix, else `schema_name`. The rest (suffix/contains globs, `context_match` or `none_tags`
+/// only) go in the `always` bucket. An empty `any_tags` can never match and isn't filed at all.
+pub struct SelectorIndex {
+    subscriptions: Vec<(SelectorSubscription, Arc<CompiledSelector>)>,
+    by_tag: HashMap<String, Vec<usize>>,
+    by_tag_prefix: HashMap<String, Vec<usize>>,
+    by_schema: HashMap<String, Vec<usize>>,
+    always: Vec<usize>,
+}
+
+impl SelectorIndex {
+    pub fn build(subscriptions: Vec<SelectorSubscription>, matchers: &SelectorMatcherCache) -> Self {
+        let mut index = SelectorIndex {
+            subscriptions: Vec::with_capacity(subscriptions.len()),
+            by_tag: HashMap::new(),
+            by_tag_prefix: HashMap::new(),
+            by_schema: HashMap::new(),
+            always: Vec::new(),
+        };
+        for (i, sub) in subscriptions.into_iter().enumerate() {
+            let matcher = matchers.get_or_compile(sub.id, &sub.selector);
+            match index_keys(&matcher) {
+                Some(keys) => {
+                    for key in keys {
+                        let bucket = match key {
+                            IndexKey::Tag(tag) => index.by_tag.entry(tag),
+                            IndexKey::TagPrefix(prefix) => index.by_tag_prefix.entry(prefix),
+                            IndexKey::Schema(schema) => index.by_schema.entry(schema),
+                        };
+                        bucket.or_default().push(i);
+                    }
+                }
+                None if matcher.any_tags.as_ref().is_some_and(|any| any.is_empty()) => {}
+                None => index.always.push(i),
+            }
+            index.subscriptions.push((sub, matcher));
+        }
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.subscriptions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.subscriptions.is_empty()
+    }
+
+    /// Positions of the selectors worth running the full matcher on, in load order
+    pub fn candidates(&self, tags: &[String], schema_name: Option<&str>) -> Vec<usize> {
+        let mut out = self.always.clone();
+        for tag in tags {
+            if let Some(ids) = self.by_tag.get(tag) {
+                out.extend(ids);
+            }
+            if !self.by_tag_prefix.is_empty() {
+                // Every prefix of the tag, including the whole tag ("session:*" matches "session:")
+                for (end, c) in tag.char_indices() {
+                    if let Some(ids) = self.by_tag_prefix.get(&tag[..end + c.len_utf8()]) {
+                        out.extend(ids);
+                    }
+                }
+            }
+        }
+        if let Some(ids) = schema_name.and_then(|s| self.by_schema.get(s)) {
+            out.extend(ids);
+        }
+        out.sort_unstable();
+        out.dedup();
+        out
+    }
+
+    /// Subscriptions whose selector matches, in load order; the same result as running every matcher.
+    /// Selectors that expired since the index was built never match
+    pub fn matching(&self, tags: &[String], schema_name: Option<&str>, context: &Value) -> Vec<&SelectorSubscription> {
+        let now = chrono::Utc::now();
+        self.candidates(tags, schema_name)
+            .into_iter()
+            .map(|i| &self.subscriptions[i])
+            .filter(|(sub, matcher)| !sub.is_expired(now) && matcher.matches(tags, schema_name, context))
+            .map(|(sub, _)| sub)
+            .collect()
+    }
+}
+
+fn indexable(pattern: &TagPattern) -> Option<IndexKey> {
+    match pattern {
+        TagPattern::Exact(tag) => Some(IndexKey::Tag(tag.clone())),
+        TagPattern::Prefix(prefix) => Some(IndexKey::TagPrefix(prefix.clone())),
+        _ => None,
+    }
+}
+
+fn index_keys(matcher: &CompiledSelector) -> Option<Vec<IndexKey>> {
+    // One all_tags pattern is enough: a matching event must satisfy each of them
+    if let Some(key) = matcher.all_tags.iter().flatten().find_map(indexable) {
+        return Some(vec![key]);
+    }
+    if let Some(any) = matcher.any_tags.as_ref().filter(|any| !any.is_empty()) {
+        if let Some(keys) = any.iter().map(indexable).collect::<Option<Vec<_>>>() {
+            return Some(keys);
+        }
+    }
+    matcher.schema_name.clone().map(|schema| vec![IndexKey::Schema(schema)])
+}
+
+/// Per-owner SelectorIndex, rebuilt on first use after selector CRUD invalidates it, and after
+/// `max_age` as a safety net for changes that bypass the handlers (agent cleanup, direct SQL)
+pub struct SelectorIndexCache {
+    indexes: RwLock<HashMap<Uuid, (Instant, Arc<SelectorIndex>)>>,
+    /// Bumped by every invalidation; a rebuild that raced one is used once but not cached
+    generation: AtomicU64,
+    max_age: Duration,
+}
+
+impl SelectorIndexCache {
+    pub fn new(max_age: Duration) -> Self {
+        Self { indexes: RwLock::new(HashMap::new()), generation: AtomicU64::new(0), max_age }
+    }
+
+    pub async fn get(&self, db: &Db, owner_id: Uuid, matchers: &SelectorMatcherCache) -> anyhow::Result<Arc<SelectorIndex>> {
+        if let Ok(indexes) = self.indexes.read() {
+            if let Some((built, index)) = indexes.get(&owner_id) {
+                if built.elapsed() < self.max_age { return Ok(index.clone()); }
+            }
+        }
+        let generation = self.generation.load(Ordering::SeqCst);
+        let subs = db.list_selector_subscriptions_for_owner(owner_id).await?;
+        let index = Arc::new(SelectorIndex::build(subs, matchers));
+        if let Ok(mut indexes) = self.indexes.write() {
+            if self.generation.load(Ordering::SeqCst) == generation {
+                indexes.insert(owner_id, (Instant::now(), index.clone()));
+            }
+        }
+        Ok(index)
+    }
+
+    /// Drop the owner's index (selector created, updated or deleted, or agent removed)
+    pub fn invalidate(&self, owner_id: Uuid) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        if let Ok(mut indexes) = self.indexes.write() {
+            indexes.remove(&owner_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rcrt_core::models::DeliveryChannel;
+    use serde_json::json;
+
+    fn selector(any: Option<&[&str]>, all: Option<&[&str]>, none: Option<&[&str]>) -> Selector {
+        let owned = |v: Option<&[&str]>| v.map(|v| v.iter().map(|s| s.to_string()).collect());
+        Selector {
+            any_tags: owned(any),
+            all_tags: owned(all),
+            none_tags: owned(none),
+            schema_name: None,
+            context_match: None,
+        }
+    }
+
+    fn tags(t: &[&str]) -> Vec<String> {
+        t.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_pattern_compile() {
+        assert_eq!(TagPattern::compile("session:*"), TagPattern::Prefix("session:".into()));
+        assert_eq!(TagPattern::compile("*:error"), TagPattern::Suffix(":error".into()));
+        assert_eq!(TagPattern::compile("*debug*"), TagPattern::Contains("debug".into()));
+        assert_eq!(TagPattern::compile("*"), TagPattern::Any);
+        assert_eq!(TagPattern::compile("tool:request"), TagPattern::Exact("tool:request".into()));
+        // Inner '*' is literal
+        assert_eq!(TagPattern::compile("a*b"), TagPattern::Exact("a*b".into()));
+    }
+
+    #[test]
+    fn test_exact_tags_unchanged() {
+        let s = CompiledSelector::compile(&selector(Some(&["a", "b"]), Some(&["c"]), None));
+        assert!(s.matches(&tags(&["b", "c"]), None, &Value::Null));
+        assert!(!s.matches(&tags(&["a"]), None, &Value::Null));
+        assert!(!s.matches(&tags(&["c"]), None, &Value::Null));
+    }
+
+    #[test]
+    fn test_any_tags_prefix_glob() {
+        let s = CompiledSelector::compile(&selector(Some(&["session:*"]), None, None));
+        assert!(s.matches(&tags(&["session:abc"]), None, &Value::Null));
+        assert!(s.matches(&tags(&["x", "session:"]), None, &Value::Null));
+        assert!(!s.matches(&tags(&["sessions"]), None, &Value::Null));
+        assert!(!s.matches(&[], None, &Value::Null));
+    }
+
+    #[test]
+    fn test_all_tags_suffix_glob() {
+        let s = CompiledSelector::compile(&selector(None, Some(&["*:error", "agent:*"]), None));
+        assert!(s.matches(&tags(&["tool:error", "agent:bob"]), None, &Value::Null));
+        assert!(!s.matches(&tags(&["tool:error"]), None, &Value::Null));
+        assert!(!s.matches(&tags(&["agent:bob", "tool:ok"]), None, &Value::Null));
+    }
+
+    #[test]
+    fn test_none_tags_excludes() {
+        let s = CompiledSelector::compile(&selector(Some(&["tool:request"]), None, Some(&["health:check"])));
+        assert!(s.matches(&tags(&["tool:request"]), None, &Value::Null));
+        assert!(!s.matches(&tags(&["tool:request", "health:check"]), None, &Value::Null));
+    }
+
+    #[test]
+    fn test_none_tags_alone_matches_everything_else() {
+        let s = CompiledSelector::compile(&selector(None, None, Some(&["debug:*"])));
+        assert!(s.matches(&[], None, &Value::Null));
+        assert!(s.matches(&tags(&["a"]), None, &Value::Null));
+        assert!(!s.matches(&tags(&["a", "debug:verbose"]), None, &Value::Null));
+    }
+
+    #[test]
+    fn test_tag_matching_all_glob_and_none_glob_is_rejected() {
+        // "session:test-1" satisfies the all_tags glob AND the none_tags glob: none wins
+        let s = CompiledSelector::compile(&selector(None, Some(&["session:*"]), Some(&["*test-1"])));
+        assert!(!s.matches(&tags(&["session:test-1"]), None, &Value::Null));
+        assert!(s.matches(&tags(&["session:prod-1"]), None, &Value::Null));
+    }
+
+    #[test]
+    fn test_empty_any_tags_never_matches() {
+        let s = CompiledSelector::compile(&selector(Some(&[]), None, None));
+        assert!(!s.matches(&tags(&["a"]), None, &Value::Null));
+    }
+
+    #[test]
+    fn test_schema_and_context_match() {
+        let mut sel = selector(Some(&["*"]), None, None);
+        sel.schema_name = Some("user.message.v1".into());
+        sel.context_match = Some(vec![ContextMatch { path: "$.lang".into(), op: "eq".into(), value: json!("en") }]);
+        let s = CompiledSelector::compile(&sel);
+        assert!(s.matches(&tags(&["x"]), Some("user.message.v1"), &json!({"lang": "en"})));
+        assert!(!s.matches(&tags(&["x"]), Some("user.message.v1"), &json!({"lang": "de"})));
+        assert!(!s.matches(&tags(&["x"]), Some("tool.request.v1"), &json!({"lang": "en"})));
+        assert!(!s.matches(&tags(&["x"]), None, &json!({"lang": "en"})));
+    }
+
+    #[test]
+    fn test_matches_event_payload() {
+        let s = CompiledSelector::compile(&selector(Some(&["session:*"]), None, Some(&["health:check"])));
+        let event = json!({"type": "breadcrumb.updated", "tags": ["session:1"], "schema_name": "user.message.v1"});
+        assert!(s.matches_event(&event));
+        let event = json!({"type": "breadcrumb.updated", "tags": ["session:1", "health:check"]});
+        assert!(!s.matches_event(&event));
+        assert!(!s.matches_event(&json!({"type": "ping"})));
+    }
+
+    #[test]
+    fn test_legacy_selector_json_deserializes() {
+        let sel: Selector = serde_json::from_value(json!({"any_tags": ["a"]})).unwrap();
+        assert!(sel.none_tags.is_none());
+    }
+
+    #[test]
+    fn test_cache_recompiles_on_change() {
+        let cache = SelectorMatcherCache::new();
+        let id = Uuid::new_v4();
+        let first = cache.get_or_compile(id, &selector(Some(&["a"]), None, None));
+        let again = cache.get_or_compile(id, &selector(Some(&["a"]), None, None));
+        assert!(Arc::ptr_eq(&first, &again));
+
+        let changed = cache.get_or_compile(id, &selector(Some(&["b"]), None, None));
+        assert!(!Arc::ptr_eq(&first, &changed));
+        assert!(changed.matches(&tags(&["b"]), None, &Value::Null));
+
+        cache.invalidate(id);
+        let fresh = cache.get_or_compile(id, &selector(Some(&["b"]), None, None));
+        assert!(!Arc::ptr_eq(&changed, &fresh));
+    }
+
+    fn subscription(selector: Selector) -> SelectorSubscription {
+        SelectorSubscription { id: Uuid::new_v4(), owner_id: Uuid::nil(), agent_id: Uuid::new_v4(), selector, channels: DeliveryChannel::all(), expires_at: None, payload_version: None }
+    }
+
+    #[test]
+    fn test_index_buckets() {
+        let mut by_schema = selector(None, None, None);
+        by_schema.schema_name = Some("tool.request.v1".into());
+        let mut by_context = selector(None, None, None);
+        by_context.context_match = Some(vec![ContextMatch { path: "$.lang".into(), op: "eq".into(), value: json!("en") }]);
+        let subs = vec![
+            subscription(selector(Some(&["a", "b"]), None, None)),
+            subscription(selector(Some(&["x"]), Some(&["*:error", "session:*"]), None)),
+            subscription(selector(Some(&["a", "*debug*"]), None, None)),
+            subscription(by_schema),
+            subscription(by_context),
+            subscription(selector(None, None, Some(&["noise"]))),
+            subscription(selector(Some(&[]), None, None)),
+        ];
+        let index = SelectorIndex::build(subs, &SelectorMatcherCache::new());
+        assert_eq!(index.by_tag["a"], vec![0]);
+        assert_eq!(index.by_tag["b"], vec![0]);
+        assert_eq!(index.by_tag_prefix["session:"], vec![1]);
+        assert_eq!(index.by_schema["tool.request.v1"], vec![3]);
+        // A suffix/contains glob, context_match or none_tags alone can't be keyed; [] never matches
+        assert_eq!(index.always, vec![2, 4, 5]);
+
+        assert_eq!(index.candidates(&tags(&["b"]), None), vec![0, 2, 4, 5]);
+        assert_eq!(index.candidates(&tags(&["session:1"]), Some("tool.request.v1")), vec![1, 2, 3, 4, 5]);
+        assert_eq!(index.candidates(&tags(&["session:"]), None), vec![1, 2, 4, 5]);
+    }
+
+    #[test]
+    fn test_expired_selectors_never_match() {
+        let live = subscription(selector(Some(&["session:abc"]), None, None));
+        let mut expired = subscription(selector(Some(&["session:abc"]), None, None));
+        expired.expires_at = Some(chrono::Utc::now() - chrono::Duration::seconds(1));
+        let live_id = live.id;
+        let index = SelectorIndex::build(vec![live, expired], &SelectorMatcherCache::new());
+        assert_eq!(index.candidates(&tags(&["session:abc"]), None).len(), 2);
+        let matched: Vec<Uuid> = index.matching(&tags(&["session:abc"]), None, &Value::Null).iter().map(|s| s.id).collect();
+        assert_eq!(matched, vec![live_id]);
+    }
+
+    /// 10k selectors, one per session plus a handful of broad ones: every event sees a few dozen
+    /// candidates at most, and the index returns exactly what the naive loop does
+    #[test]
+    fn test_index_matches_naive_fanout_with_10k_selectors() {
+        let mut subs: Vec<SelectorSubscription> = (0..10_000)
+            .map(|i| {
+                let mut sel = selector(Some(&[format!("session:{}", i).as_str()]), None, Some(&["health:check"]));
+                if i % 3 == 0 { sel.schema_name = Some("user.message.v1".into()); }
+                subscription(sel)
+            })
+            .collect();
+        let mut context_only = selector(None, None, None);
+        context_only.context_match = Some(vec![ContextMatch { path: "$.lang".into(), op: "eq".into(), value: json!("de") }]);
+        subs.push(subscription(context_only));
+        subs.push(subscription(selector(Some(&["*:error"]), None, None)));
+        subs.push(subscription(selector(None, Some(&["workspace:*", "tool:*"]), None)));
+        let mut tool_requests = selector(None, None, None);
+        tool_requests.schema_name = Some("tool.request.v1".into());
+        subs.push(subscription(tool_requests));
+
+        let matchers = SelectorMatcherCache::new();
+        let index = SelectorIndex::build(subs.clone(), &matchers);
+        assert_eq!(index.len(), 10_004);
+        let events = [
+            (tags(&["session:42"]), Some("user.message.v1"), json!({})),
+            (tags(&["session:42", "health:check"]), Some("user.message.v1"), json!({})),
+            (tags(&["session:9999", "agent:error"]), None, json!({"lang": "de"})),
+            (tags(&["workspace:tools", "tool:openrouter"]), Some("tool.request.v1"), json!({})),
+            (tags(&["unrelated"]), None, json!({"lang": "en"})),
+        ];
+        for (event_tags, schema, context) in &events {
+            let candidates = index.candidates(event_tags, *schema);
+            assert!(candidates.len() <= 5, "{} candidates for {:?}", candidates.len(), event_tags);
+
+            let naive: Vec<Uuid> = subs.iter()
+                .filter(|s| matchers.get_or_compile(s.id, &s.selector).matches(event_tags, *schema, context))
+                .map(|s| s.id)
+                .collect();
+            let indexed: Vec<Uuid> = index.matching(event_tags, *schema, context).iter().map(|s| s.id).collect();
+            assert_eq!(indexed, naive, "{:?}", event_tags);
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! Selector Handlers
+//! Selector subscriptions that route matching breadcrumb events to an agent
+
+use axum::{extract::{Query, State}, http::StatusCode, Json};
+use chrono::{DateTime, Utc};
+use rcrt_core::models::{DeliveryChannel, Selector, SelectorSubscription};
+use rcrt_core::roles::Role;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{auth::AuthContext, db_errors::db_error, payload_versions::PayloadVersion, AppState};
+
+/// `expires_at` and `ttl_seconds` are only read on create; updates keep the selector's expiry.
+/// `payload_version` pins the event version its SSE and webhook deliveries use (v1 when unset)
+#[derive(Deserialize)]
+pub struct SelectorReq { any_tags: Option<Vec<String>>, all_tags: Option<Vec<String>>, none_tags: Option<Vec<String>>, schema_name: Option<String>, context_match: Option<Vec<rcrt_core::models::ContextMatch>>, channels: Option<Vec<DeliveryChannel>>, expires_at: Option<DateTime<Utc>>, ttl_seconds: Option<i64>, payload_version: Option<u16> }
+
+impl SelectorReq {
+    /// `ttl_seconds` from now, or `expires_at` as given; either must lie in the future
+    fn expiry(&self) -> Result<Option<DateTime<Utc>>, (StatusCode, String)> {
+        let expires_at = match (self.expires_at, self.ttl_seconds) {
+            (Some(_), Some(_)) => return Err((StatusCode::BAD_REQUEST, "give expires_at or ttl_seconds, not both".into())),
+            (Some(at), None) => Some(at),
+            (None, Some(secs)) if secs > 0 => Some(Utc::now() + chrono::Duration::seconds(secs)),
+            (None, Some(_)) => return Err((StatusCode::BAD_REQUEST, "ttl_seconds must be positive".into())),
+            (None, None) => None,
+        };
+        if expires_at.is_some_and(|at| at <= Utc::now()) {
+            return Err((StatusCode::BAD_REQUEST, "expires_at must be in the future".into()));
+        }
+        Ok(expires_at)
+    }
+
+    /// Omitted channels mean every push channel; an empty list would never deliver anything
+    fn into_parts(self) -> Result<(Selector, Vec<DeliveryChannel>, Option<u16>), (StatusCode, String)> {
+        if let Some(version) = self.payload_version {
+            PayloadVersion::requested(version)?;
+        }
+        let channels = match self.channels {
+            None => DeliveryChannel::all(),
+            Some(c) if c.is_empty() => return Err((StatusCode::BAD_REQUEST, "channels must name at least one of sse, webhook, nats, inbox".into())),
+            Some(c) => DeliveryChannel::known().into_iter().filter(|ch| c.contains(ch)).collect(),
+        };
+        let selector = Selector { any_tags: self.any_tags, all_tags: self.all_tags, none_tags: self.none_tags, schema_name: self.schema_name, context_match: self.context_match };
+        Ok((selector, channels, self.payload_version))
+    }
+}
+
+pub async fn create_selector(State(state): State<AppState>, auth: AuthContext, Json(req): Json<SelectorReq>) -> Result<Json<SelectorSubscription>, (StatusCode, String)> {
+    if !(auth.has_role(Role::Subscriber) || auth.has_role(Role::Curator)) { return Err((StatusCode::FORBIDDEN, "subscriber role required".into())); }
+    let expires_at = req.expiry()?;
+    let (selector, channels, payload_version) = req.into_parts()?;
+    let created = state.db.create_selector_subscription(auth.owner_id, auth.agent_id, selector, &channels, payload_version, expires_at).await.map_err(db_error)?;
+    state.selector_index.invalidate(auth.owner_id);
+    Ok(Json(created))
+}
+
+#[derive(Deserialize)]
+pub struct ListQuery {
+    /// Also list expired selectors hygiene hasn't removed yet
+    #[serde(default)]
+    include_expired: bool,
+}
+
+pub async fn list_selectors(State(state): State<AppState>, auth: AuthContext, Query(q): Query<ListQuery>) -> Result<Json<Vec<SelectorSubscription>>, (StatusCode, String)> {
+    if !(auth.has_role(Role::Subscriber) || auth.has_role(Role::Curator)) { return Err((StatusCode::FORBIDDEN, "subscriber role required".into())); }
+    let subs = state.db.list_selector_subscriptions(auth.owner_id, auth.agent_id, q.include_expired).await.map_err(db_error)?;
+    Ok(Json(subs))
+}
+
+pub async fn update_selector(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(selector_id): axum::extract::Path<Uuid>, Json(req): Json<SelectorReq>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if !(auth.has_role(Role::Subscriber) || auth.has_role(Role::Curator)) { return Err((StatusCode::FORBIDDEN, "subscriber role required".into())); }
+    let (selector, channels, payload_version) = req.into_parts()?;
+    state.db.update_selector(auth.owner_id, auth.agent_id, selector_id, selector, &channels, payload_version).await.map_err(db_error)?;
+    state.selector_cache.invalidate(selector_id);
+    state.selector_index.invalidate(auth.owner_id);
+    Ok(Json(json!({"ok": true})))
+}
+
+pub async fn delete_selector(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(selector_id): axum::extract::Path<Uuid>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if !(auth.has_role(Role::Subscriber) || auth.has_role(Role::Curator)) { return Err((StatusCode::FORBIDDEN, "subscriber role required".into())); }
+    state.db.delete_selector(auth.owner_id, auth.agent_id, selector_id).await.map_err(db_error)?;
+    state.selector_cache.invalidate(selector_id);
+    state.selector_index.invalidate(auth.owner_id);
+    Ok(Json(json!({"ok": true})))
+}
+
+#[derive(Deserialize)]
+pub struct DeleteByTagQuery { tag: String }
+
+/// Delete the caller's selectors naming `tag` in any_tags or all_tags, e.g. everything a session set up
+pub async fn delete_selectors_by_tag(State(state): State<AppState>, auth: AuthContext, Query(q): Query<DeleteByTagQuery>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if !(auth.has_role(Role::Subscriber) || auth.has_role(Role::Curator)) { return Err((StatusCode::FORBIDDEN, "subscriber role required".into())); }
+    if q.tag.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "tag must not be empty".into()));
+    }
+    let deleted = state.db.delete_selectors_by_tag(auth.owner_id, auth.agent_id, &q.tag).await.map_err(db_error)?;
+    for id in &deleted {
+        state.selector_cache.invalidate(*id);
+    }
+    if !deleted.is_empty() {
+        state.selector_index.invalidate(auth.owner_id);
+    }
+    Ok(Json(json!({"ok": true, "deleted": deleted.len()})))
+}
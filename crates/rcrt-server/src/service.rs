@@ -0,0 +1,822 @@
+//! Breadcrumb Service
+//! The breadcrumb write path and the context view without HTTP: role, tag and TTL policy checks,
+//! idempotency keys, encryption, large value externalizing, embeddings and keywords, auto-TTL,
+//! declared references, history and checksums (in `Db`), events and fanout, cache invalidation and
+//! llm_hints transforms, plus a dry run of create (`validate`). The /breadcrumbs handlers are thin
+//! adapters over it; tests and single-binary deployments can call it in-process against the same
+//! database (see examples/embedded.rs).
+//!
+//! Events go out through the state's event bus, which needs the `nats` feature. Without it, writes
+//! still leave their outbox rows, and no fanout happens.
+
+use std::fmt;
+use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use rcrt_core::error::DbError;
+use rcrt_core::models::{AccessType, Breadcrumb, BreadcrumbContextView, BreadcrumbCreate, BreadcrumbReference, BreadcrumbUpdate, BrokenReference, DeliveryChannel, EncryptedContext, NewBreadcrumbReference, ReferencedDelete, Sensitivity, UpsertedBreadcrumb, Visibility, EMBEDDING_CONFIG_SCHEMA};
+use rcrt_core::roles::Role;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::auth::AuthContext;
+use crate::breadcrumbs::embedding_input;
+use crate::db_errors::db_error_response;
+use crate::embedding::{self, embed_text};
+use crate::events::{publish_breadcrumb_created, publish_breadcrumb_updated, publish_references_broken};
+use crate::fanout_access::{FanoutAccess, ReadScope};
+use crate::replica::ReadPreference;
+use crate::{domain_metrics, embedding_models, embedding_policy, envelope, hygiene, internal_error, keywords, large_values, references, schema_registry, session_inference, transforms, ttl_policy, AppState};
+
+// Parts of a Validation report
+pub use crate::fanout_access::Delivery;
+pub use crate::large_values::ExternalValue;
+pub use crate::schema_registry::SchemaDefMeta;
+
+/// Why a service call failed
+#[derive(Debug)]
+pub enum ServiceError {
+    /// Refused before or around the store call, with the status the HTTP API answers
+    Rejected(StatusCode, String),
+    /// The store refused it; a stale expected version is `DbError::VersionMismatch`
+    Db(DbError),
+    /// Delete of a breadcrumb other breadcrumbs declare references to, without `force`
+    Referenced(Vec<BreadcrumbReference>),
+}
+
+impl fmt::Display for ServiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServiceError::Rejected(status, message) => write!(f, "{}: {}", status, message),
+            ServiceError::Db(e) => e.fmt(f),
+            ServiceError::Referenced(refs) => write!(f, "breadcrumb_is_referenced by {} breadcrumb(s)", refs.len()),
+        }
+    }
+}
+
+impl std::error::Error for ServiceError {}
+
+impl From<(StatusCode, String)> for ServiceError {
+    fn from((status, message): (StatusCode, String)) -> Self {
+        ServiceError::Rejected(status, message)
+    }
+}
+
+impl From<DbError> for ServiceError {
+    fn from(e: DbError) -> Self {
+        ServiceError::Db(e)
+    }
+}
+
+impl IntoResponse for ServiceError {
+    fn into_response(self) -> Response {
+        match self {
+            ServiceError::Rejected(status, message) => (status, message).into_response(),
+            ServiceError::Db(e) => db_error_response(e, false),
+            ServiceError::Referenced(referenced_by) => (StatusCode::CONFLICT, Json(json!({
+                "error": "breadcrumb_is_referenced",
+                "referenced_by": referenced_by,
+                "hint": "retry with ?force=true to delete it and break these references"
+            }))).into_response(),
+        }
+    }
+}
+
+fn rejected(status: StatusCode, message: &str) -> ServiceError {
+    ServiceError::Rejected(status, message.to_string())
+}
+
+/// A new breadcrumb; the body of POST /breadcrumbs and PUT /breadcrumbs/upsert
+#[derive(Debug, Default, Deserialize)]
+pub struct CreateReq {
+    pub title: String,
+    pub description: Option<String>,        // NEW: Detailed description
+    pub semantic_version: Option<String>,   // NEW: Semantic version
+    pub context: serde_json::Value,
+    pub tags: Vec<String>,
+    pub schema_name: Option<String>,
+    pub llm_hints: Option<serde_json::Value>, // NEW: Instance-level LLM hints
+    pub visibility: Option<String>,
+    pub sensitivity: Option<String>,
+    pub ttl: Option<chrono::DateTime<chrono::Utc>>,
+    pub entity_keywords: Option<Vec<String>>, // NEW: Pre-computed keywords (see /extract/entities)
+    /// Store the context envelope-encrypted; it is then never embedded or keyword-indexed
+    #[serde(default)]
+    pub encrypt: bool,
+    /// Breadcrumbs the context points at; merged with a `$refs` block in the context
+    pub references: Option<Vec<NewBreadcrumbReference>>,
+}
+
+impl CreateReq {
+    /// A create with only the required fields, as templates produce it
+    pub fn new(title: String, context: serde_json::Value, tags: Vec<String>, schema_name: String) -> Self {
+        CreateReq { title, context, tags, schema_name: Some(schema_name), ..CreateReq::default() }
+    }
+}
+
+/// Changes to a breadcrumb; the body of PATCH /breadcrumbs/:id. Fields left None are kept
+#[derive(Debug, Default, Deserialize)]
+pub struct UpdateReq {
+    pub title: Option<String>,
+    pub description: Option<String>,        // NEW: Update description
+    pub semantic_version: Option<String>,   // NEW: Update semantic version
+    pub context: Option<serde_json::Value>,
+    pub tags: Option<Vec<String>>,
+    pub schema_name: Option<String>,
+    pub llm_hints: Option<serde_json::Value>, // NEW: Update LLM hints
+    pub visibility: Option<String>,
+    pub sensitivity: Option<String>,
+    pub ttl: Option<chrono::DateTime<chrono::Utc>>,
+    /// Encrypt the context from this version on; an encrypted breadcrumb stays encrypted
+    #[serde(default)]
+    pub encrypt: bool,
+    /// Replaces the declared references, as does a context carrying `$refs`; left alone otherwise
+    pub references: Option<Vec<NewBreadcrumbReference>>,
+}
+
+/// Tags to add to and remove from a breadcrumb's current ones; the body of POST /breadcrumbs/:id/tags
+#[derive(Debug, Default, Deserialize)]
+pub struct TagsReq {
+    #[serde(default)]
+    pub add: Vec<String>,
+    #[serde(default)]
+    pub remove: Vec<String>,
+}
+
+/// Most tags a breadcrumb carries
+pub const MAX_TAGS: usize = 64;
+/// Longest tag, in bytes
+pub const MAX_TAG_BYTES: usize = 256;
+
+/// A created breadcrumb
+#[derive(Debug)]
+pub struct Created {
+    pub breadcrumb: Breadcrumb,
+    /// Written with a deprecated schema; the write still went through
+    pub deprecated: bool,
+    /// Tags the server added (see session_inference)
+    pub tags_inferred: Vec<String>,
+}
+
+/// A check a create would fail, with the status and message it would answer
+#[derive(Debug, Serialize)]
+pub struct Rejection {
+    pub status: u16,
+    pub message: String,
+}
+
+/// A selector a new breadcrumb would match, and what its agent would be sent
+#[derive(Debug, Serialize)]
+pub struct MatchPreview {
+    pub selector_id: Uuid,
+    pub agent_id: Uuid,
+    pub channels: Vec<DeliveryChannel>,
+    pub delivery: Delivery,
+}
+
+/// What a create would do with a request; see BreadcrumbService::validate. When `rejected`, the fields
+/// after it stop at the step that failed
+#[derive(Debug, Default, Serialize)]
+pub struct Validation {
+    pub valid: bool,
+    pub rejected: Option<Rejection>,
+    /// Accepted, but worth a look: a deprecated schema, llm_hints that don't parse or don't apply
+    pub warnings: Vec<String>,
+    /// The schema.def.v1 registered for schema_name
+    pub schema_definition: Option<SchemaDefMeta>,
+    pub references: Vec<NewBreadcrumbReference>,
+    /// Context values that would be stored as attachments and referenced
+    pub externalized: Vec<ExternalValue>,
+    pub encrypted: bool,
+    /// Whether the create would compute an embedding
+    pub embedded: bool,
+    pub entity_keywords: Option<Vec<String>>,
+    pub ttl: Option<chrono::DateTime<chrono::Utc>>,
+    pub ttl_type: Option<String>,
+    pub ttl_config: Option<serde_json::Value>,
+    pub ttl_source: Option<String>,
+    /// size_bytes of the stored row
+    pub size_bytes: Option<i32>,
+    /// The context GET /breadcrumbs/:id would return, llm_hints applied
+    pub context_view: Option<serde_json::Value>,
+    /// In fanout order
+    pub matches: Vec<MatchPreview>,
+}
+
+/// Whether a write stores its context envelope-encrypted: asked for with `encrypt`, or a secret
+/// breadcrumb under ENCRYPT_SECRET_CONTEXTS. Schema definitions, TTL policies and hygiene configs are
+/// read by the server itself, so they stay plaintext (and asking for encryption is an error)
+fn wants_encryption(state: &AppState, encrypt: bool, sensitivity: Option<&Sensitivity>, schema_name: Option<&str>) -> Result<bool, (StatusCode, String)> {
+    if schema_name.is_some_and(|s| s == schema_registry::SCHEMA_DEF || ttl_policy::is_policy_schema(s)) {
+        return match encrypt {
+            true => Err((StatusCode::BAD_REQUEST, format!("{} contexts can't be encrypted", schema_name.unwrap_or_default()))),
+            false => Ok(false),
+        };
+    }
+    Ok(encrypt || (state.encrypt_secret_contexts && sensitivity == Some(&Sensitivity::Secret)))
+}
+
+/// The tag limits, for a write that stores `tags` as given
+fn check_tags(tags: &[String]) -> Result<(), ServiceError> {
+    if tags.len() > MAX_TAGS {
+        return Err(ServiceError::Rejected(StatusCode::BAD_REQUEST, format!("a breadcrumb carries at most {} tags, not {}", MAX_TAGS, tags.len())));
+    }
+    match tags.iter().find(|t| t.len() > MAX_TAG_BYTES) {
+        Some(tag) => Err(ServiceError::Rejected(StatusCode::BAD_REQUEST, format!("tags are at most {} bytes, one is {}", MAX_TAG_BYTES, tag.len()))),
+        None => Ok(()),
+    }
+}
+
+fn parse_visibility(v: Option<String>) -> Option<Visibility> {
+    v.and_then(|v| match v.as_str() {"public"=>Some(Visibility::Public),"private"=>Some(Visibility::Private),"team"=>Some(Visibility::Team),_=>None})
+}
+
+// Keywords are matched lowercased and deduplicated, same as the extractor output
+fn normalize_keywords(keywords: Vec<String>) -> Vec<String> {
+    let mut out: Vec<String> = keywords.into_iter()
+        .map(|k| k.trim().to_lowercase())
+        .filter(|k| !k.is_empty())
+        .collect();
+    out.sort();
+    out.dedup();
+    out
+}
+
+/// The row a create stores from `req`, its context already externalized: provisional keywords and the
+/// auto-TTL of the first matching policy added
+async fn prepare_create(state: &AppState, auth: &AuthContext, req: CreateReq, sensitivity: Option<Sensitivity>, encrypt: bool) -> BreadcrumbCreate {
+    let mut breadcrumb_create = BreadcrumbCreate {
+        title: req.title,
+        description: req.description,
+        semantic_version: req.semantic_version,
+        context: req.context,
+        tags: req.tags,
+        schema_name: req.schema_name,
+        llm_hints: req.llm_hints,
+        visibility: parse_visibility(req.visibility),
+        sensitivity,
+        ttl: req.ttl,
+        ttl_type: None,
+        ttl_config: None,
+        ttl_source: None,
+        entity_keywords: req.entity_keywords.map(normalize_keywords),
+        entities: None,
+    };
+
+    // Provisional keywords so hybrid search finds it before the context-builder catches up
+    if state.extract_keywords_on_create && !encrypt && breadcrumb_create.entity_keywords.is_none() {
+        if let Some((keywords, entities)) = keywords::extract_keywords(&state.entity_extractor, &breadcrumb_create.title, &breadcrumb_create.context) {
+            breadcrumb_create.entity_keywords = Some(keywords);
+            breadcrumb_create.entities = Some(entities);
+        }
+    }
+
+    // Apply automatic TTL based on schema and tags
+    let ttl_policies = state.ttl_policies.policies(auth.owner_id).await;
+    let (schema_name, tags) = (breadcrumb_create.schema_name.clone(), breadcrumb_create.tags.clone());
+    hygiene::apply_auto_ttl(&mut breadcrumb_create, schema_name.as_deref(), &tags, &ttl_policies);
+    breadcrumb_create
+}
+
+/// Count accesses for the analytics and, for reads, read_count (usage-based TTL); the access log
+/// flusher writes them within ACCESS_LOG_FLUSH_SECS
+pub(crate) fn record_access(state: &AppState, auth: &AuthContext, access_type: AccessType, ids: &[Uuid]) {
+    state.access_log.record(auth.owner_id, auth.agent_id, access_type, ids);
+}
+
+pub(crate) async fn apply_view_hints(state: &AppState, view: &mut BreadcrumbContextView) {
+    if let Err(e) = transform_view(state, view).await {
+        tracing::warn!("Failed to apply llm_hints for breadcrumb {}: {}", view.id, e);
+    }
+}
+
+/// Apply the view's llm_hints; on error the context is left as it was
+async fn transform_view(state: &AppState, view: &mut BreadcrumbContextView) -> Result<(), String> {
+    // Load llm_hints with precedence: Instance > Schema
+    // NO backward compatibility - new structure only!
+
+    // 1. Check breadcrumb-level llm_hints (instance override)
+    let instance_hints = view.llm_hints.clone()
+        .and_then(|v| serde_json::from_value::<transforms::LlmHints>(v).ok());
+
+    // 2. Load schema defaults (fallback)
+    let schema_hints = if instance_hints.is_none() {
+        if let Some(schema_name) = &view.schema_name {
+            state.schema_cache.load_schema_hints(schema_name).await
+        } else {
+            None
+        }
+    } else {
+        None
+    };
+
+    // Apply precedence: Instance > Schema (no legacy support!)
+    let final_hints = instance_hints.or(schema_hints);
+
+    // Apply hints if we found any
+    if let Some(hints) = final_hints {
+        let engine = transforms::TransformEngine::new();
+        // Continue with original context on error
+        view.context = engine.apply_llm_hints(&view.context, &hints)?;
+        tracing::debug!("Applied llm_hints transform for breadcrumb {} (schema: {:?})", view.id, view.schema_name);
+    }
+    Ok(())
+}
+
+/// Breadcrumb operations on behalf of an `AuthContext`, over an `AppState`
+#[derive(Clone)]
+pub struct BreadcrumbService {
+    state: AppState,
+}
+
+impl BreadcrumbService {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    /// Create a breadcrumb. A repeated `idempotency_key` is refused with 409
+    pub async fn create(&self, auth: &AuthContext, mut req: CreateReq, idempotency_key: Option<&str>) -> Result<Created, ServiceError> {
+        let state = &self.state;
+        if !(auth.has_role(Role::Emitter) || auth.has_role(Role::Curator)) {
+            return Err(rejected(StatusCode::FORBIDDEN, "emitter role required"));
+        }
+        check_tags(&req.tags)?;
+        if let Some(schema_name) = req.schema_name.as_deref() {
+            ttl_policy::check_write(auth, schema_name, &req.context)?;
+        }
+        if let Some(key) = idempotency_key {
+            if !state.db.record_idempotency(auth.owner_id, Some(auth.agent_id), key, "breadcrumb", None).await? {
+                return Err(rejected(StatusCode::CONFLICT, "duplicate idempotency key"));
+            }
+        }
+        // Try embedding before insert for atomicity if available
+        let sensitivity = req.sensitivity.as_deref().and_then(Sensitivity::parse);
+        let encrypt = wants_encryption(state, req.encrypt, sensitivity.as_ref(), req.schema_name.as_deref())?;
+        let declared = references::declare(state, auth, req.references.take(), Some(&req.context)).await?;
+        // Before prepare_create, whose TTL policies may match on the session tag. Skipped at the tag limit
+        let mut tags_inferred = Vec::new();
+        if let Some(session) = session_inference::infer(state, auth, &req.tags, &req.context, declared.as_deref()).await? {
+            if req.tags.len() < MAX_TAGS {
+                req.tags.push(session.clone());
+                tags_inferred.push(session);
+            }
+        }
+        // Embeddings, keywords, history and the event all see the references, not the large values
+        let externalized = match encrypt {
+            true => large_values::Externalized::default(),
+            false => large_values::externalize(state, auth.owner_id, req.schema_name.as_deref(), &mut req.context).await?,
+        };
+        let input = if !encrypt && embedding_policy::should_embed_schema(req.schema_name.as_deref())
+            && embedding_policy::within_sensitivity(sensitivity.as_ref(), &state.embed_sensitivity_max) {
+            Some(embedding_input(state, &req.title, &req.context, req.llm_hints.as_ref(), req.schema_name.as_deref()).await)
+        } else {
+            None
+        };
+        let emb = input.clone().and_then(|input| embedding_policy::get_or_fallback_embedding(input, req.schema_name.as_deref()));
+        // No zero-vector fallback here: a missing title vector is left for the backfill to fill
+        let title_emb = if state.embed_title_separately && emb.is_some() {
+            let _timer = domain_metrics::embedding_timer("ingest");
+            embed_text(embedding::standalone_text(&req.title))
+                .map_err(|e| tracing::warn!("Title embedding failed for schema {:?}: {}", req.schema_name, e))
+                .ok()
+        } else {
+            None
+        };
+
+        let breadcrumb_create = prepare_create(state, auth, req, sensitivity, encrypt).await;
+
+        let started = std::time::Instant::now();
+        let bc = if encrypt {
+            let sealed = envelope::seal_context(&envelope::local_kek()?, &breadcrumb_create.context)?;
+            state.db.create_encrypted_breadcrumb_for(auth.owner_id, Some(auth.agent_id), Some(auth.agent_id), breadcrumb_create, sealed)
+                .await?
+        } else {
+            state.db.create_breadcrumb_with_embeddings_for(
+                auth.owner_id,
+                Some(auth.agent_id),
+                Some(auth.agent_id),
+                breadcrumb_create,
+                emb,
+                title_emb
+            ).await?
+        };
+        tracing::Span::current().record("breadcrumb_id", tracing::field::display(bc.id));
+        large_values::link(state, auth.owner_id, auth.agent_id, bc.id, externalized).await?;
+        references::store(state, auth, bc.id, declared).await?;
+        if let Some(input) = input {
+            embedding_models::embed_target(state, auth, bc.id, input).await;
+        }
+        domain_metrics::record_op("create", bc.schema_name.as_deref(), auth.owner_id, started, Some(bc.size_bytes));
+        // Publish event (best-effort)
+        publish_breadcrumb_created(state, auth.owner_id, &bc).await;
+
+        // Writes to a deprecated schema still succeed, but the caller is told
+        let mut deprecated = false;
+        if let Some(schema_name) = bc.schema_name.as_deref() {
+            if schema_name == schema_registry::SCHEMA_DEF {
+                state.schema_registry.invalidate().await;
+            } else if ttl_policy::is_policy_schema(schema_name) {
+                state.ttl_policies.invalidate().await;
+            } else if schema_name == EMBEDDING_CONFIG_SCHEMA {
+                state.active_models.invalidate(auth.owner_id).await;
+            } else if let Some(def) = state.schema_registry.deprecation(schema_name).await {
+                tracing::warn!("⚠️ Agent {} wrote breadcrumb {} with deprecated schema {} (replaced_by={:?})", auth.agent_id, bc.id, schema_name, def.replaced_by);
+                deprecated = true;
+            }
+        }
+        Ok(Created { breadcrumb: bc, deprecated, tags_inferred })
+    }
+
+    /// Run `create` up to the write and report what it would do: whether it's accepted, the TTL, size,
+    /// context view and selector matches. Nothing is written: no row, attachment, idempotency key or
+    /// event, and no embedding is computed. Refusals a create answers with a 4xx are part of the report
+    pub async fn validate(&self, auth: &AuthContext, req: CreateReq) -> Result<Validation, ServiceError> {
+        let mut report = Validation::default();
+        match self.dry_run(auth, req, &mut report).await {
+            Ok(()) => report.valid = true,
+            Err(ServiceError::Rejected(status, message)) if status.is_client_error() => {
+                report.rejected = Some(Rejection { status: status.as_u16(), message });
+            }
+            Err(e) => return Err(e),
+        }
+        Ok(report)
+    }
+
+    async fn dry_run(&self, auth: &AuthContext, mut req: CreateReq, report: &mut Validation) -> Result<(), ServiceError> {
+        let state = &self.state;
+        if !(auth.has_role(Role::Emitter) || auth.has_role(Role::Curator)) {
+            return Err(rejected(StatusCode::FORBIDDEN, "emitter role required"));
+        }
+        if let Some(schema_name) = req.schema_name.as_deref() {
+            ttl_policy::check_write(auth, schema_name, &req.context)?;
+            report.schema_definition = state.schema_registry.definition(schema_name).await;
+            if let Some(def) = report.schema_definition.as_ref().filter(|d| d.deprecated) {
+                report.warnings.push(match &def.replaced_by {
+                    Some(replaced_by) => format!("schema {} is deprecated, replaced by {}", schema_name, replaced_by),
+                    None => format!("schema {} is deprecated", schema_name),
+                });
+            }
+        }
+        let sensitivity = req.sensitivity.as_deref().and_then(Sensitivity::parse);
+        let encrypt = wants_encryption(state, req.encrypt, sensitivity.as_ref(), req.schema_name.as_deref())?;
+        report.encrypted = encrypt;
+        report.references = references::declare(state, auth, req.references.take(), Some(&req.context)).await?.unwrap_or_default();
+        if !encrypt {
+            report.externalized = large_values::externalize_preview(state, req.schema_name.as_deref(), &mut req.context);
+        }
+        report.embedded = !encrypt && embedding_policy::should_embed_schema(req.schema_name.as_deref())
+            && embedding_policy::within_sensitivity(sensitivity.as_ref(), &state.embed_sensitivity_max);
+        if let Some(Err(e)) = req.llm_hints.as_ref().map(|h| serde_json::from_value::<transforms::LlmHints>(h.clone())) {
+            report.warnings.push(format!("llm_hints don't parse, so the schema's hints apply: {}", e));
+        }
+
+        let create = prepare_create(state, auth, req, sensitivity, encrypt).await;
+        report.entity_keywords = create.entity_keywords.clone();
+        report.ttl = create.ttl;
+        report.ttl_type = create.ttl_type.clone();
+        report.ttl_config = create.ttl_config.clone();
+        report.ttl_source = create.ttl_source.clone();
+        report.size_bytes = Some(match encrypt {
+            true => envelope::seal_context(&envelope::local_kek()?, &create.context)?.ciphertext.len() as i32,
+            false => serde_json::to_vec(&create.context).map_err(internal_error)?.len() as i32,
+        });
+
+        let mut view = BreadcrumbContextView {
+            id: Uuid::nil(),
+            title: create.title.clone(),
+            description: create.description.clone(),
+            semantic_version: create.semantic_version.clone(),
+            context: create.context.clone(),
+            tags: create.tags.clone(),
+            schema_name: create.schema_name.clone(),
+            llm_hints: create.llm_hints.clone(),
+            version: 1,
+            updated_at: chrono::Utc::now(),
+        };
+        if let Err(e) = transform_view(state, &mut view).await {
+            report.warnings.push(format!("llm_hints don't apply, so the context is served untransformed: {}", e));
+        }
+        report.context_view = Some(view.context);
+
+        // Fanout matches the stored context, which for an encrypted breadcrumb is the stub
+        let stored = if encrypt { EncryptedContext::stub() } else { create.context };
+        let index = state.selector_index.get(&state.db, auth.owner_id, &state.selector_cache).await.map_err(internal_error)?;
+        let matching = index.matching(&create.tags, create.schema_name.as_deref(), &stored);
+        let agent_ids: Vec<Uuid> = matching.iter().map(|sub| sub.agent_id).collect();
+        let scope = ReadScope {
+            private: matches!(create.visibility, Some(Visibility::Private)),
+            sensitive: matches!(create.sensitivity, Some(Sensitivity::Pii | Sensitivity::Secret)),
+            created_by: Some(auth.agent_id),
+        };
+        let access = FanoutAccess::load_scope(&state.db, auth.owner_id, Uuid::nil(), scope, &agent_ids).await;
+        report.matches = matching.into_iter().map(|sub| MatchPreview {
+            selector_id: sub.id,
+            agent_id: sub.agent_id,
+            channels: sub.channels.clone(),
+            delivery: access.delivery(sub.agent_id),
+        }).collect();
+        Ok(())
+    }
+
+    /// Create or update the one live breadcrumb of `schema` carrying all of `key_tags`, in a single
+    /// transaction. Key tags missing from `req` are added. Duplicates left by older
+    /// search-then-create writers are expired, keeping the newest.
+    pub async fn upsert(&self, auth: &AuthContext, schema: &str, key_tags: &[String], mut req: CreateReq) -> Result<UpsertedBreadcrumb, ServiceError> {
+        let state = &self.state;
+        if !(auth.has_role(Role::Emitter) || auth.has_role(Role::Curator)) {
+            return Err(rejected(StatusCode::FORBIDDEN, "emitter role required"));
+        }
+        if schema.trim().is_empty() || key_tags.is_empty() {
+            return Err(rejected(StatusCode::BAD_REQUEST, "schema and key_tags are required"));
+        }
+        if req.schema_name.as_deref().is_some_and(|s| s != schema) {
+            return Err(rejected(StatusCode::BAD_REQUEST, "schema_name in the body must match the schema parameter"));
+        }
+        ttl_policy::check_write(auth, schema, &req.context)?;
+        req.schema_name = Some(schema.to_string());
+        for tag in key_tags {
+            if !req.tags.contains(tag) { req.tags.push(tag.clone()); }
+        }
+        check_tags(&req.tags)?;
+        let sensitivity = req.sensitivity.as_deref().and_then(Sensitivity::parse);
+        let encrypt = wants_encryption(state, req.encrypt, sensitivity.as_ref(), Some(schema))?;
+        let declared = references::declare(state, auth, req.references.take(), Some(&req.context)).await?;
+        let externalized = match encrypt {
+            true => large_values::Externalized::default(),
+            false => large_values::externalize(state, auth.owner_id, Some(schema), &mut req.context).await?,
+        };
+        let input = if !encrypt && embedding_policy::should_embed_schema(Some(schema))
+            && embedding_policy::within_sensitivity(sensitivity.as_ref(), &state.embed_sensitivity_max) {
+            Some(embedding_input(state, &req.title, &req.context, req.llm_hints.as_ref(), Some(schema)).await)
+        } else {
+            None
+        };
+        let emb = input.clone().and_then(|input| embedding_policy::get_or_fallback_embedding(input, Some(schema)));
+        let mut breadcrumb_create = BreadcrumbCreate {
+            title: req.title,
+            description: req.description,
+            semantic_version: req.semantic_version,
+            context: req.context,
+            tags: req.tags.clone(),
+            schema_name: Some(schema.to_string()),
+            llm_hints: req.llm_hints,
+            visibility: parse_visibility(req.visibility),
+            sensitivity,
+            ttl: req.ttl,
+            ttl_type: None,
+            ttl_config: None,
+            ttl_source: None,
+            entity_keywords: req.entity_keywords.map(normalize_keywords),
+            entities: None,
+        };
+        let ttl_policies = state.ttl_policies.policies(auth.owner_id).await;
+        hygiene::apply_auto_ttl(&mut breadcrumb_create, Some(schema), &req.tags, &ttl_policies);
+
+        let sealed = match encrypt {
+            true => Some(envelope::seal_context(&envelope::local_kek()?, &breadcrumb_create.context)?),
+            false => None,
+        };
+
+        let started = std::time::Instant::now();
+        let up = state.db.upsert_breadcrumb_by_key(auth.owner_id, auth.agent_id, key_tags, breadcrumb_create, emb, sealed)
+            .await?;
+        let bc = &up.breadcrumb;
+        tracing::Span::current().record("breadcrumb_id", tracing::field::display(bc.id));
+        large_values::link(state, auth.owner_id, auth.agent_id, bc.id, externalized).await?;
+        references::store(state, auth, bc.id, declared).await?;
+        // An update without a new vector keeps the old one, which the raised sensitivity may not allow
+        if !up.created && bc.sensitivity > state.embed_sensitivity_max {
+            state.db.clear_breadcrumb_embeddings(auth.owner_id, Some(auth.agent_id), bc.id).await?;
+        } else if let Some(input) = input {
+            embedding_models::embed_target(state, auth, bc.id, input).await;
+        }
+        domain_metrics::record_op(if up.created { "create" } else { "update" }, bc.schema_name.as_deref(), auth.owner_id, started, Some(bc.size_bytes));
+        if up.created {
+            publish_breadcrumb_created(state, auth.owner_id, bc).await;
+        } else {
+            publish_breadcrumb_updated(state, auth.owner_id, bc).await;
+        }
+        if schema == schema_registry::SCHEMA_DEF {
+            state.schema_registry.invalidate().await;
+        } else if ttl_policy::is_policy_schema(schema) {
+            state.ttl_policies.invalidate().await;
+        } else if schema == EMBEDDING_CONFIG_SCHEMA {
+            state.active_models.invalidate(auth.owner_id).await;
+        }
+        Ok(up)
+    }
+
+    /// Update a breadcrumb. A stale `expected_version` fails with `DbError::VersionMismatch`;
+    /// `force` (curators only) writes past it
+    pub async fn update(&self, auth: &AuthContext, id: Uuid, mut expected_version: Option<i32>, force: bool, mut req: UpdateReq) -> Result<Breadcrumb, ServiceError> {
+        let state = &self.state;
+        tracing::info!("🔧 UPDATE_BREADCRUMB DEBUG: Starting update for breadcrumb {}", id);
+        tracing::info!("🔧 Agent: {}, Owner: {}", auth.agent_id, auth.owner_id);
+
+        if force {
+            if !auth.has_role(Role::Curator) {
+                return Err(rejected(StatusCode::FORBIDDEN, "force requires the curator role"));
+            }
+            if let Some(ev) = expected_version.take() {
+                tracing::warn!("⚠️ Curator {} forcing update of {} past If-Match {}", auth.agent_id, id, ev);
+            }
+        }
+        if let Some(tags) = &req.tags {
+            check_tags(tags)?;
+        }
+        tracing::info!("🔧 Expected version: {:?}", expected_version);
+        tracing::info!("🔧 Request payload: title={:?}, context_exists={}, tags={:?}",
+            req.title, req.context.is_some(), req.tags);
+
+        let sensitivity = req.sensitivity.as_deref().and_then(Sensitivity::parse);
+        let current_sealed = state.db.get_encrypted_context(auth.owner_id, Some(auth.agent_id), id, None).await?;
+        // A policy must still be valid after the update, so look at the stored row when the request doesn't say
+        let touches_policy = match req.schema_name.as_deref() {
+            Some(schema_name) => ttl_policy::is_policy_schema(schema_name),
+            None => req.context.is_some(),
+        };
+        // Encrypting a plaintext row may depend on its stored sensitivity, and seals its stored context if none is sent
+        let may_encrypt = current_sealed.is_none() && (req.encrypt || state.encrypt_secret_contexts);
+        let current = if touches_policy || may_encrypt {
+            state.db.get_breadcrumb_full_for(auth.owner_id, Some(auth.agent_id), id, false).await?
+        } else {
+            None
+        };
+        if touches_policy {
+            let schema_name = req.schema_name.as_deref().or(current.as_ref().and_then(|c| c.schema_name.as_deref()));
+            if let Some(schema_name) = schema_name {
+                if let Some(context) = req.context.as_ref().or(current.as_ref().map(|c| &c.context)) {
+                    ttl_policy::check_write(auth, schema_name, context)?;
+                }
+            }
+        }
+        let declared = references::declare(state, auth, req.references.take(), req.context.as_ref()).await?;
+        let encrypt = current_sealed.is_some() || wants_encryption(
+            state,
+            req.encrypt,
+            sensitivity.as_ref().or(current.as_ref().map(|c| &c.sensitivity)),
+            req.schema_name.as_deref().or(current.as_ref().and_then(|c| c.schema_name.as_deref())),
+        )?;
+        // An encrypted row without a new context keeps its ciphertext as is
+        let sealed = match (encrypt, req.context.as_ref().or(current.as_ref().filter(|_| current_sealed.is_none()).map(|c| &c.context))) {
+            (true, Some(context)) => Some(envelope::seal_context(&envelope::local_kek()?, context)?),
+            _ => None,
+        };
+        // A reference sent back unchanged is only linked again, never re-uploaded
+        let externalized = match (&mut req.context, encrypt) {
+            (Some(context), false) => {
+                let schema_name = req.schema_name.as_deref().or(current.as_ref().and_then(|c| c.schema_name.as_deref()));
+                large_values::externalize(state, auth.owner_id, schema_name, context).await?
+            }
+            _ => large_values::Externalized::default(),
+        };
+
+        if let (Some(context), false) = (&req.context, encrypt) {
+            let context_preview = serde_json::to_string(context).unwrap_or_default();
+            let preview = if context_preview.len() > 200 {
+                format!("{}...", &context_preview[..200])
+            } else {
+                context_preview
+            };
+            tracing::info!("🔧 Context payload preview: {}", preview);
+        }
+        let schema_changed = req.schema_name.is_some();
+
+        let upd = BreadcrumbUpdate {
+            title: req.title,
+            description: req.description,
+            semantic_version: req.semantic_version,
+            context: if sealed.is_some() { None } else { req.context },
+            tags: req.tags,
+            schema_name: req.schema_name,
+            llm_hints: req.llm_hints,
+            visibility: parse_visibility(req.visibility),
+            sensitivity,
+            ttl: req.ttl,
+            ttl_type: None,
+            ttl_config: None,
+            ttl_source: None,
+        };
+
+        tracing::info!("🔧 BreadcrumbUpdate created: context_is_some={}, encrypted={}", upd.context.is_some(), encrypt);
+
+        let started = std::time::Instant::now();
+        let updated = match sealed {
+            Some(sealed) => state.db.update_encrypted_breadcrumb(auth.owner_id, auth.agent_id, id, expected_version, upd, sealed).await,
+            None => state.db.update_breadcrumb(auth.owner_id, auth.agent_id, id, expected_version, upd).await,
+        };
+        let bc = updated.inspect_err(|e| tracing::error!("🔧 Database update failed: {}", e))?;
+        large_values::link(state, auth.owner_id, auth.agent_id, bc.id, externalized).await?;
+        references::store(state, auth, bc.id, declared).await?;
+        domain_metrics::record_op("update", bc.schema_name.as_deref(), auth.owner_id, started, Some(bc.size_bytes));
+        // Raised past EMBED_SENSITIVITY_MAX: the vectors computed at the old sensitivity go
+        if bc.sensitivity > state.embed_sensitivity_max {
+            state.db.clear_breadcrumb_embeddings(auth.owner_id, Some(auth.agent_id), bc.id).await?;
+        }
+
+        tracing::info!("🔧 Database update succeeded: version={}, context_preview={}",
+            bc.version,
+            serde_json::to_string(&bc.context).unwrap_or_default().chars().take(100).collect::<String>()
+        );
+
+        if bc.schema_name.as_deref() == Some(schema_registry::SCHEMA_DEF) {
+            state.schema_registry.invalidate().await;
+        }
+        // Also when a policy was moved to another schema
+        if schema_changed || bc.schema_name.as_deref().is_some_and(ttl_policy::is_policy_schema) {
+            state.ttl_policies.invalidate().await;
+        }
+        if schema_changed || bc.schema_name.as_deref() == Some(EMBEDDING_CONFIG_SCHEMA) {
+            state.active_models.invalidate(auth.owner_id).await;
+        }
+
+        // Publish update events (same as create!)
+        publish_breadcrumb_updated(state, auth.owner_id, &bc).await;
+        Ok(bc)
+    }
+
+    /// Add and remove tags without sending the whole array: applied to the stored tags in one
+    /// statement, so concurrent callers never drop each other's tags and need no If-Match. Returns
+    /// the breadcrumb as updated, its new tags and version included
+    pub async fn update_tags(&self, auth: &AuthContext, id: Uuid, req: TagsReq) -> Result<Breadcrumb, ServiceError> {
+        let state = &self.state;
+        let trimmed = |tags: Vec<String>| -> Vec<String> {
+            tags.into_iter().map(|t| t.trim().to_string()).collect()
+        };
+        let (add, remove) = (trimmed(req.add), trimmed(req.remove));
+        if add.is_empty() && remove.is_empty() {
+            return Err(rejected(StatusCode::BAD_REQUEST, "add or remove at least one tag"));
+        }
+        if add.iter().chain(&remove).any(|t| t.is_empty()) {
+            return Err(rejected(StatusCode::BAD_REQUEST, "tags can't be empty"));
+        }
+        if let Some(tag) = add.iter().find(|t| remove.contains(t)) {
+            return Err(ServiceError::Rejected(StatusCode::BAD_REQUEST, format!("'{}' is both added and removed", tag)));
+        }
+        check_tags(&add)?;
+
+        let started = std::time::Instant::now();
+        let bc = state.db.update_breadcrumb_tags(auth.owner_id, auth.agent_id, id, &add, &remove, MAX_TAGS).await?;
+        domain_metrics::record_op("update", bc.schema_name.as_deref(), auth.owner_id, started, Some(bc.size_bytes));
+        tracing::info!("🏷️ Tags of {} now {:?} (version {})", bc.id, bc.tags, bc.version);
+        publish_breadcrumb_updated(state, auth.owner_id, &bc).await;
+        Ok(bc)
+    }
+
+    /// Delete a breadcrumb and return the references that broke. One other breadcrumbs declare
+    /// references to is refused with `ServiceError::Referenced` unless `force`
+    pub async fn delete(&self, auth: &AuthContext, id: Uuid, force: bool) -> Result<Vec<BrokenReference>, ServiceError> {
+        let state = &self.state;
+        let started = std::time::Instant::now();
+        let (schema_name, broken) = match state.db.delete_breadcrumb_unless_referenced(auth.owner_id, auth.agent_id, id, force).await? {
+            ReferencedDelete::NotFound => return Err(rejected(StatusCode::NOT_FOUND, "not found")),
+            ReferencedDelete::Referenced(referenced_by) => return Err(ServiceError::Referenced(referenced_by)),
+            ReferencedDelete::Deleted { schema_name, broken } => (schema_name, broken),
+        };
+        domain_metrics::record_op("delete", schema_name.as_deref(), auth.owner_id, started, None);
+        if !broken.is_empty() {
+            tracing::warn!("🔗 Breadcrumb {} force-deleted by {}, breaking {} references", id, auth.agent_id, broken.len());
+            publish_references_broken(state, auth.owner_id, auth.agent_id, id, &broken).await;
+        }
+        if let Some(cache) = &state.view_cache {
+            cache.invalidate(id);
+        }
+        if schema_name.as_deref().is_some_and(ttl_policy::is_policy_schema) {
+            state.ttl_policies.invalidate().await;
+        } else if schema_name.as_deref() == Some(EMBEDDING_CONFIG_SCHEMA) {
+            state.active_models.invalidate(auth.owner_id).await;
+        }
+        Ok(broken)
+    }
+
+    /// The context view GET /breadcrumbs/:id returns: llm_hints applied, counted as a read, and with
+    /// `inline` its large values inlined. Read from the replica when one is configured and fresh
+    pub async fn get_context(&self, auth: &AuthContext, id: Uuid, inline: bool) -> Result<BreadcrumbContextView, ServiceError> {
+        let state = &self.state;
+        // Inlined values depend on the caller's attachment access, so only plain views are cached.
+        // The version lookup is the access check, and a view is served only for the current version
+        let cache = state.view_cache.as_ref().filter(|_| !inline);
+        let (owner_id, agent_id) = (auth.owner_id, Some(auth.agent_id));
+        if let Some(cache) = cache {
+            let Some(version) = state.reads.read(ReadPreference::Replica, |db| async move { db.get_breadcrumb_version_for(owner_id, agent_id, id).await }).await? else {
+                return Err(rejected(StatusCode::NOT_FOUND, "not found"));
+            };
+            if let Some(view) = cache.get(id, version) {
+                record_access(state, auth, AccessType::ApiRead, &[id]);
+                return Ok(view);
+            }
+        }
+        let Some(mut view) = state.reads.read(ReadPreference::Replica, |db| async move { db.get_breadcrumb_context_for(owner_id, agent_id, id).await }).await? else {
+            return Err(rejected(StatusCode::NOT_FOUND, "not found"));
+        };
+        record_access(state, auth, AccessType::ApiRead, &[id]);
+        // Before the hints, so their transforms see the text
+        if inline {
+            large_values::inline(state, auth.owner_id, auth.agent_id, &mut view.context).await?;
+        }
+        apply_view_hints(state, &mut view).await;
+        if let Some(cache) = cache {
+            cache.insert(view.clone());
+        }
+        Ok(view)
+    }
+}
@@ -0,0 +1,180 @@
+//! Session Tag Inference
+//! A breadcrumb created without a `session:` tag can inherit one (SESSION_TAG_INFERENCE): from the
+//! breadcrumbs that triggered it (`trigger_event_id` in the context, or declared triggered_by
+//! references) or from the session its agent set with PUT /agents/:id/session. A session tag the
+//! request carries always wins, triggers spread over several sessions infer nothing from the trigger,
+//! and with `both` the trigger is asked before the agent. Idempotency keys carry no session, so they
+//! aren't a source. The create response lists what was added in `tags_inferred`
+
+use rcrt_core::error::Result;
+use rcrt_core::models::{NewBreadcrumbReference, TRIGGERED_BY};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{auth::AuthContext, AppState};
+
+const SESSION_PREFIX: &str = "session:";
+
+/// Where a create's missing session tag may come from (SESSION_TAG_INFERENCE)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionInference {
+    /// Tags are stored as sent (default)
+    Off,
+    /// The session of the breadcrumb the create names as its trigger
+    Trigger,
+    /// The creating agent's active session
+    Agent,
+    /// The trigger's session, else the agent's
+    Both,
+}
+
+impl SessionInference {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "off" => Some(SessionInference::Off),
+            "trigger" => Some(SessionInference::Trigger),
+            "agent" => Some(SessionInference::Agent),
+            "both" => Some(SessionInference::Both),
+            _ => None,
+        }
+    }
+
+    fn from_trigger(self) -> bool {
+        matches!(self, SessionInference::Trigger | SessionInference::Both)
+    }
+
+    fn from_agent(self) -> bool {
+        matches!(self, SessionInference::Agent | SessionInference::Both)
+    }
+}
+
+/// The session tag a create carrying `tags` should gain, if any. `references` are the declared ones
+/// (see references::declare), so only triggers the caller can read are asked
+pub async fn infer(state: &AppState, auth: &AuthContext, tags: &[String], context: &Value, references: Option<&[NewBreadcrumbReference]>) -> Result<Option<String>> {
+    let mode = state.session_tag_inference;
+    if mode == SessionInference::Off || tags.iter().any(|t| t.starts_with(SESSION_PREFIX)) {
+        return Ok(None);
+    }
+    if mode.from_trigger() {
+        let triggers = trigger_ids(context, references);
+        if !triggers.is_empty() {
+            if let [session] = state.db.session_tags_of(auth.owner_id, Some(auth.agent_id), &triggers).await?.as_slice() {
+                return Ok(Some(session.clone()));
+            }
+        }
+    }
+    if mode.from_agent() {
+        return state.db.agent_session(auth.owner_id, auth.agent_id).await;
+    }
+    Ok(None)
+}
+
+/// Ids of the declared triggered_by targets and the context's `trigger_event_id`
+fn trigger_ids(context: &Value, references: Option<&[NewBreadcrumbReference]>) -> Vec<Uuid> {
+    let mut ids: Vec<Uuid> = references.unwrap_or_default().iter()
+        .filter(|r| r.relation == TRIGGERED_BY)
+        .map(|r| r.breadcrumb_id)
+        .collect();
+    if let Some(id) = context.get("trigger_event_id").and_then(Value::as_str).and_then(|s| Uuid::parse_str(s).ok()) {
+        ids.push(id);
+    }
+    ids.sort();
+    ids.dedup();
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_modes() {
+        assert_eq!(SessionInference::parse("both"), Some(SessionInference::Both));
+        assert_eq!(SessionInference::parse("agent"), Some(SessionInference::Agent));
+        assert_eq!(SessionInference::parse("on"), None);
+        assert!(SessionInference::Both.from_trigger() && SessionInference::Both.from_agent());
+        assert!(!SessionInference::Off.from_trigger() && !SessionInference::Off.from_agent());
+    }
+
+    #[test]
+    fn test_trigger_ids_come_from_context_and_triggered_by_references() {
+        let (a, b, c) = (Uuid::from_u128(1), Uuid::from_u128(2), Uuid::from_u128(3));
+        let refs = vec![
+            NewBreadcrumbReference { field: "cause".into(), breadcrumb_id: b, relation: TRIGGERED_BY.into() },
+            NewBreadcrumbReference { field: "see_also".into(), breadcrumb_id: c, relation: "related".into() },
+            NewBreadcrumbReference { field: "trigger_event_id".into(), breadcrumb_id: a, relation: TRIGGERED_BY.into() },
+        ];
+        assert_eq!(trigger_ids(&json!({ "trigger_event_id": a }), Some(&refs)), vec![a, b]);
+        assert_eq!(trigger_ids(&json!({ "trigger_event_id": "not-a-uuid" }), None), Vec::<Uuid>::new());
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_missing_session_tag_is_inferred_by_mode(pool: sqlx::PgPool) {
+        use crate::auth::{AuthConfig, AuthMode};
+        use crate::test_support::{request, send, state};
+        use axum::http::StatusCode;
+        use rcrt_core::db::Db;
+
+        let (owner_id, agent_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let db = Db { pool };
+        db.ensure_tenant(owner_id, "Session Inference Test").await.unwrap();
+        db.upsert_agent(owner_id, agent_id, vec!["emitter".into()]).await.unwrap();
+        let auth = AuthConfig::new(AuthMode::Disabled { owner_id, agent_id }, None, None, None, None).unwrap();
+        let base = state(db.clone(), auth).await;
+        let call = |mode: SessionInference, method: &'static str, uri: String, body: Value| {
+            let app = crate::build_app(AppState { session_tag_inference: mode, ..base.clone() });
+            async move {
+                let (status, body) = send(&app, request(method, &uri, None, Some(body))).await;
+                assert_eq!(status, StatusCode::OK, "{}", body);
+                body
+            }
+        };
+        // tags_inferred, and the tags stored
+        let create = |mode: SessionInference, body: Value| {
+            let (call, db) = (&call, &db);
+            async move {
+                let resp = call(mode, "POST", "/breadcrumbs".into(), body).await;
+                let id: Uuid = resp["id"].as_str().unwrap().parse().unwrap();
+                let stored = db.get_breadcrumb_full_for(owner_id, None, id, false).await.unwrap().unwrap();
+                (resp.get("tags_inferred").cloned(), stored.tags)
+            }
+        };
+
+        let trigger = call(SessionInference::Off, "POST", "/breadcrumbs".into(), json!({ "title": "ask", "context": {}, "tags": ["session:s1"] })).await;
+        let trigger_id = trigger["id"].as_str().unwrap().to_string();
+        let by_context = json!({ "title": "tool reply", "context": { "trigger_event_id": trigger_id }, "tags": ["tool:response"] });
+        let by_reference = json!({ "title": "tool reply", "context": {}, "tags": [], "references": [{ "field": "cause", "breadcrumb_id": trigger_id, "relation": "triggered_by" }] });
+        let untriggered = json!({ "title": "note", "context": {}, "tags": [] });
+
+        // Off changes nothing, even with both sources available
+        let set = call(SessionInference::Off, "PUT", format!("/agents/{}/session", agent_id), json!({ "session": "s2" })).await;
+        assert_eq!(set["session"], "session:s2");
+        for body in [&by_context, &by_reference, &untriggered] {
+            let (inferred, tags) = create(SessionInference::Off, body.clone()).await;
+            assert_eq!(inferred, None);
+            assert!(!tags.iter().any(|t| t.starts_with(SESSION_PREFIX)), "{:?}", tags);
+        }
+
+        let (inferred, tags) = create(SessionInference::Trigger, by_context.clone()).await;
+        assert_eq!(inferred, Some(json!(["session:s1"])));
+        assert_eq!(tags, vec!["tool:response", "session:s1"]);
+        assert_eq!(create(SessionInference::Trigger, by_reference.clone()).await.0, Some(json!(["session:s1"])));
+        assert_eq!(create(SessionInference::Trigger, untriggered.clone()).await.0, None);
+
+        assert_eq!(create(SessionInference::Agent, by_context.clone()).await.0, Some(json!(["session:s2"])));
+        assert_eq!(create(SessionInference::Agent, untriggered.clone()).await.0, Some(json!(["session:s2"])));
+        assert_eq!(create(SessionInference::Both, by_context.clone()).await.0, Some(json!(["session:s1"])));
+        assert_eq!(create(SessionInference::Both, untriggered.clone()).await.0, Some(json!(["session:s2"])));
+
+        // An explicit session tag is never overridden or joined
+        let explicit = json!({ "title": "tool reply", "context": { "trigger_event_id": trigger_id }, "tags": ["session:mine"] });
+        let (inferred, tags) = create(SessionInference::Both, explicit).await;
+        assert_eq!((inferred, tags), (None, vec!["session:mine".to_string()]));
+
+        let cleared = call(SessionInference::Both, "PUT", format!("/agents/{}/session", agent_id), json!({ "session": null })).await;
+        assert!(cleared["session"].is_null());
+        assert_eq!(create(SessionInference::Both, untriggered).await.0, None);
+    }
+}
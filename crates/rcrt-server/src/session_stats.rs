@@ -0,0 +1,53 @@
+//! Session Stats
+//! GET /sessions from the trigger-maintained session_stats table, and the admin rebuild that recounts it
+
+use axum::{extract::{Query, State}, http::StatusCode, Json};
+use rcrt_core::models::SessionOrder;
+use rcrt_core::roles::Role;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{auth::{require_admin, AuthContext}, db_errors::db_error, AppState};
+
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 500;
+
+#[derive(Deserialize)]
+pub struct SessionsQuery {
+    order: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+/// The caller's sessions with counts, last activity and participating agents; `order` is
+/// last_activity (default), breadcrumb_count or message_count, newest/largest first
+pub async fn list_sessions(State(state): State<AppState>, auth: AuthContext, Query(q): Query<SessionsQuery>) -> Result<Json<Value>, (StatusCode, String)> {
+    if !(auth.has_role(Role::Subscriber) || auth.has_role(Role::Curator)) {
+        return Err((StatusCode::FORBIDDEN, "subscriber role required".into()));
+    }
+    let order = match q.order.as_deref() {
+        None => SessionOrder::LastActivity,
+        Some(s) => SessionOrder::parse(s)
+            .ok_or((StatusCode::BAD_REQUEST, format!("order must be last_activity, breadcrumb_count or message_count, not {}", s)))?,
+    };
+    let limit = q.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let offset = q.offset.unwrap_or(0).max(0);
+
+    // One extra row tells whether there's another page
+    let mut sessions = state.db.list_session_stats(auth.owner_id, order, limit + 1, offset).await.map_err(db_error)?;
+    let has_more = sessions.len() as i64 > limit;
+    sessions.truncate(limit as usize);
+    Ok(Json(json!({
+        "sessions": sessions,
+        "has_more": has_more,
+        "next_offset": if has_more { Some(offset + limit) } else { None },
+    })))
+}
+
+/// Recount the caller's session_stats from its breadcrumbs, for when the counters are suspected to have drifted
+pub async fn rebuild_sessions(State(state): State<AppState>, auth: AuthContext) -> Result<Json<Value>, (StatusCode, String)> {
+    require_admin(&state, &auth)?;
+    let sessions = state.db.rebuild_session_stats(auth.owner_id).await.map_err(db_error)?;
+    tracing::info!("🧮 Session stats rebuilt for {} by {}: {} sessions", auth.owner_id, auth.agent_id, sessions);
+    Ok(Json(json!({ "ok": true, "sessions": sessions })))
+}
@@ -0,0 +1,296 @@
+//! Share Links
+//! Signed, expiring links to one breadcrumb's context view for someone without an agent or JWT.
+//! POST /breadcrumbs/:id/share signs `{jti, owner, breadcrumb, view, exp}` with the server secret;
+//! GET /shared/:token checks the signature and expiry, then the breadcrumb_shares row the jti names,
+//! so a link can be revoked before it expires. Each token has its own rate limit
+
+use std::time::Duration;
+use axum::{extract::{Path, State}, http::{header, HeaderValue, StatusCode}, response::{IntoResponse, Response}, Json};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use rcrt_core::models::{BreadcrumbFull, BreadcrumbShare, Sensitivity};
+use rcrt_core::roles::Role;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::{auth::AuthContext, db_errors::db_error, rate_limit::RateLimiter, replica::ReadPreference, service::apply_view_hints, AppState};
+
+/// Views a link can show; `context` is GET /breadcrumbs/:id's, llm_hints applied
+pub const VIEWS: [&str; 1] = ["context"];
+const DEFAULT_EXPIRES_IN_SECS: i64 = 24 * 3600;
+const MAX_EXPIRES_IN_SECS: i64 = 30 * 24 * 3600;
+
+/// The signing secret and the per-token limiter
+pub struct ShareLinks {
+    secret: Vec<u8>,
+    limiter: RateLimiter<Uuid>,
+}
+
+/// What a share token carries; all of it is covered by the signature
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShareClaims {
+    pub jti: Uuid,
+    pub owner_id: Uuid,
+    pub breadcrumb_id: Uuid,
+    pub view: String,
+    /// Unix seconds
+    pub exp: i64,
+}
+
+impl ShareLinks {
+    /// `per_min` requests per token per minute
+    pub fn new(secret: Vec<u8>, per_min: u32) -> Self {
+        Self { secret, limiter: RateLimiter::new(per_min, Duration::from_secs(60)) }
+    }
+
+    fn mac(&self) -> Hmac<Sha256> {
+        Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC takes a key of any length")
+    }
+
+    /// `<claims>.<signature>`, both base64url
+    pub fn sign(&self, claims: &ShareClaims) -> String {
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims).expect("claims serialize"));
+        let mut mac = self.mac();
+        mac.update(payload.as_bytes());
+        format!("{}.{}", payload, URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes()))
+    }
+
+    /// The claims of a token this server signed that hasn't expired at `now`. A malformed or
+    /// tampered token is a 404, like one that names nothing
+    pub fn verify(&self, token: &str, now: DateTime<Utc>) -> Result<ShareClaims, (StatusCode, String)> {
+        let not_found = || (StatusCode::NOT_FOUND, "share link not found".to_string());
+        let (payload, signature) = token.split_once('.').ok_or_else(not_found)?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| not_found())?;
+        let mut mac = self.mac();
+        mac.update(payload.as_bytes());
+        mac.verify_slice(&signature).map_err(|_| not_found())?;
+        let claims: ShareClaims = URL_SAFE_NO_PAD.decode(payload).ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .ok_or_else(not_found)?;
+        if claims.exp <= now.timestamp() {
+            return Err((StatusCode::GONE, "share link expired".into()));
+        }
+        Ok(claims)
+    }
+}
+
+#[derive(Deserialize, Default)]
+pub struct ShareReq {
+    /// Seconds until the link stops working; a day by default, at most 30
+    expires_in_secs: Option<i64>,
+    /// One of VIEWS; `context` by default
+    view: Option<String>,
+}
+
+/// The breadcrumb, when the caller may share it: the owner's own, written by the caller or with the caller a curator
+async fn shareable(state: &AppState, auth: &AuthContext, id: Uuid) -> Result<BreadcrumbFull, (StatusCode, String)> {
+    let (owner_id, agent_id) = (auth.owner_id, auth.agent_id);
+    let Some(full) = state.db.get_breadcrumb_full_for(owner_id, Some(agent_id), id, false).await.map_err(db_error)? else {
+        return Err((StatusCode::NOT_FOUND, "not found".into()));
+    };
+    if full.owner_id != auth.owner_id || (full.created_by != Some(auth.agent_id) && !auth.has_role(Role::Curator)) {
+        return Err((StatusCode::FORBIDDEN, "only the breadcrumb's creator or a curator can share it".into()));
+    }
+    Ok(full)
+}
+
+/// 201 with the share and its token; the token is not returned again
+pub async fn create_share(State(state): State<AppState>, auth: AuthContext, Path(id): Path<Uuid>, body: Option<Json<ShareReq>>) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, String)> {
+    let req = body.map(|Json(req)| req).unwrap_or_default();
+    let full = shareable(&state, &auth, id).await?;
+    if full.sensitivity == Sensitivity::Secret {
+        return Err((StatusCode::FORBIDDEN, "secret breadcrumbs can't be shared".into()));
+    }
+    let view = req.view.unwrap_or_else(|| VIEWS[0].to_string());
+    if !VIEWS.contains(&view.as_str()) {
+        return Err((StatusCode::BAD_REQUEST, format!("view must be one of {}, not {}", VIEWS.join(", "), view)));
+    }
+    let expires_in = req.expires_in_secs.unwrap_or(DEFAULT_EXPIRES_IN_SECS);
+    if !(1..=MAX_EXPIRES_IN_SECS).contains(&expires_in) {
+        return Err((StatusCode::BAD_REQUEST, format!("expires_in_secs must be between 1 and {}", MAX_EXPIRES_IN_SECS)));
+    }
+    // Whole seconds, so the row and the token agree on the expiry
+    let expires_at = DateTime::from_timestamp(Utc::now().timestamp() + expires_in, 0).unwrap_or_else(Utc::now);
+    let share = state.db.create_breadcrumb_share(auth.owner_id, auth.agent_id, id, &view, expires_at).await.map_err(db_error)?;
+    let token = state.share_links.sign(&ShareClaims {
+        jti: share.id,
+        owner_id: auth.owner_id,
+        breadcrumb_id: id,
+        view,
+        exp: expires_at.timestamp(),
+    });
+    let mut body = serde_json::to_value(&share).map_err(crate::internal_error)?;
+    body["url"] = format!("/shared/{}", token).into();
+    body["token"] = token.into();
+    Ok((StatusCode::CREATED, Json(body)))
+}
+
+pub async fn list_shares(State(state): State<AppState>, auth: AuthContext, Path(id): Path<Uuid>) -> Result<Json<Vec<BreadcrumbShare>>, (StatusCode, String)> {
+    shareable(&state, &auth, id).await?;
+    Ok(Json(state.db.list_breadcrumb_shares(auth.owner_id, id).await.map_err(db_error)?))
+}
+
+/// The link stops working at once, on every replica: GET /shared/:token reads the share from the primary
+pub async fn revoke_share(State(state): State<AppState>, auth: AuthContext, Path((id, share_id)): Path<(Uuid, Uuid)>) -> Result<Json<BreadcrumbShare>, (StatusCode, String)> {
+    shareable(&state, &auth, id).await?;
+    match state.db.revoke_breadcrumb_share(auth.owner_id, id, share_id).await.map_err(db_error)? {
+        Some(share) => Ok(Json(share)),
+        None => Err((StatusCode::NOT_FOUND, "share not found".into())),
+    }
+}
+
+/// The shared breadcrumb's view, for anyone holding the token. Never cached or indexed, and the token
+/// isn't sent on as a referrer. A breadcrumb raised to secret after it was shared is refused. Not counted as a read
+pub async fn get_shared(State(state): State<AppState>, Path(token): Path<String>) -> Result<Response, (StatusCode, String)> {
+    let claims = state.share_links.verify(&token, Utc::now())?;
+    if !state.share_links.limiter.check(&claims.jti) {
+        return Err((StatusCode::TOO_MANY_REQUESTS, "share link rate limit exceeded".into()));
+    }
+    let (owner_id, id) = (claims.owner_id, claims.breadcrumb_id);
+    let share = state.db.get_breadcrumb_share(owner_id, claims.jti).await.map_err(db_error)?
+        .filter(|share| share.breadcrumb_id == id)
+        .ok_or((StatusCode::NOT_FOUND, "share link not found".to_string()))?;
+    if share.revoked_at.is_some() {
+        return Err((StatusCode::GONE, "share link revoked".into()));
+    }
+    let full = state.reads.read(ReadPreference::Replica, |db| async move { db.get_breadcrumb_full_for(owner_id, None, id, false).await }).await.map_err(db_error)?;
+    let Some(full) = full.filter(|full| full.owner_id == owner_id) else {
+        return Err((StatusCode::NOT_FOUND, "not found".into()));
+    };
+    if full.sensitivity == Sensitivity::Secret {
+        return Err((StatusCode::FORBIDDEN, "secret breadcrumbs can't be shared".into()));
+    }
+    let Some(mut view) = state.reads.read(ReadPreference::Replica, |db| async move { db.get_breadcrumb_context_for(owner_id, None, id).await }).await.map_err(db_error)? else {
+        return Err((StatusCode::NOT_FOUND, "not found".into()));
+    };
+    apply_view_hints(&state, &mut view).await;
+    let mut res = Json(view).into_response();
+    let headers = res.headers_mut();
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("private, no-store"));
+    headers.insert(header::REFERRER_POLICY, HeaderValue::from_static("no-referrer"));
+    headers.insert("x-robots-tag", HeaderValue::from_static("noindex"));
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(exp: i64) -> ShareClaims {
+        ShareClaims { jti: Uuid::new_v4(), owner_id: Uuid::new_v4(), breadcrumb_id: Uuid::new_v4(), view: "context".into(), exp }
+    }
+
+    #[test]
+    fn test_token_round_trips_until_it_expires() {
+        let links = ShareLinks::new(b"secret".to_vec(), 60);
+        let now = Utc::now();
+        let c = claims(now.timestamp() + 60);
+        let token = links.sign(&c);
+        assert_eq!(links.verify(&token, now).unwrap(), c);
+        let (status, message) = links.verify(&token, now + chrono::Duration::seconds(60)).unwrap_err();
+        assert_eq!((status, message.as_str()), (StatusCode::GONE, "share link expired"));
+    }
+
+    #[test]
+    fn test_tampered_tokens_are_not_found() {
+        let links = ShareLinks::new(b"secret".to_vec(), 60);
+        let now = Utc::now();
+        let c = claims(now.timestamp() + 60);
+        let token = links.sign(&c);
+        let (payload, signature) = token.split_once('.').unwrap();
+
+        // Pointing the token at another breadcrumb, or extending it, breaks the signature
+        let other = ShareClaims { breadcrumb_id: Uuid::new_v4(), ..c.clone() };
+        let forged_payload = links.sign(&other).split_once('.').unwrap().0.to_string();
+        let longer = ShareClaims { exp: c.exp + 3600, ..c.clone() };
+        let longer_payload = links.sign(&longer).split_once('.').unwrap().0.to_string();
+        let elsewhere = ShareLinks::new(b"another secret".to_vec(), 60).sign(&c);
+        let mut flipped = signature.to_string();
+        flipped.replace_range(0..1, if flipped.starts_with('A') { "B" } else { "A" });
+        for bad in [
+            format!("{}.{}", forged_payload, signature),
+            format!("{}.{}", longer_payload, signature),
+            format!("{}.{}", payload, flipped),
+            elsewhere,
+            payload.to_string(),
+            format!("{}.", payload),
+            "not-a-token".to_string(),
+        ] {
+            assert_eq!(links.verify(&bad, now).unwrap_err().0, StatusCode::NOT_FOUND, "{}", bad);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bad_and_expired_links_are_refused_before_the_database() {
+        use crate::auth::{AuthConfig, AuthMode};
+        use crate::test_support::{offline_db, request, send, state};
+
+        let auth = AuthConfig::new(AuthMode::Disabled { owner_id: Uuid::new_v4(), agent_id: Uuid::new_v4() }, None, None, None, None).unwrap();
+        let state = state(offline_db(), auth).await;
+        let expired = state.share_links.sign(&claims(Utc::now().timestamp() - 1));
+        let app = crate::build_app(state);
+        for (token, status) in [("garbage.token".to_string(), StatusCode::NOT_FOUND), (expired, StatusCode::GONE)] {
+            assert_eq!(send(&app, request("GET", &format!("/shared/{}", token), None, None)).await.0, status);
+        }
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_share_view_revoke_and_secret_refusal(pool: sqlx::PgPool) {
+        use crate::auth::{AuthConfig, AuthMode};
+        use crate::test_support::{crumb, request, send, state};
+        use rcrt_core::db::Db;
+        use rcrt_core::models::{BreadcrumbCreate, BreadcrumbUpdate};
+        use serde_json::json;
+        use tower::ServiceExt;
+
+        let (owner_id, agent_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let db = Db { pool };
+        db.ensure_tenant(owner_id, "Sharing").await.unwrap();
+        let create = |title: &str, sensitivity: Sensitivity| BreadcrumbCreate {
+            title: title.into(), context: json!({ "body": title }), sensitivity: Some(sensitivity), ..crumb("knowledge.v1", &["kb"])
+        };
+        let article = db.create_breadcrumb_for(owner_id, Some(agent_id), Some(agent_id), create("Onboarding", Sensitivity::Low)).await.unwrap();
+        let secret = db.create_breadcrumb_for(owner_id, Some(agent_id), Some(agent_id), create("Keys", Sensitivity::Secret)).await.unwrap();
+
+        let auth = AuthConfig::new(AuthMode::Disabled { owner_id, agent_id }, None, None, None, None).unwrap();
+        let mut state = state(db.clone(), auth).await;
+        state.share_links = std::sync::Arc::new(ShareLinks::new(b"test secret".to_vec(), 3));
+        let app = crate::build_app(state);
+        let (status, message) = send(&app, request("POST", &format!("/breadcrumbs/{}/share", secret.id), None, None)).await;
+        assert_eq!((status, message), (StatusCode::FORBIDDEN, json!("secret breadcrumbs can't be shared")));
+
+        let (status, share) = send(&app, request("POST", &format!("/breadcrumbs/{}/share", article.id), None, None)).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", share);
+        let url = share["url"].as_str().unwrap().to_string();
+        // The one read that needs the headers too
+        let res = app.clone().oneshot(request("GET", &url, None, None)).await.unwrap();
+        let (status, headers) = (res.status(), res.headers().clone());
+        let view: serde_json::Value = serde_json::from_slice(&axum::body::to_bytes(res.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(status, StatusCode::OK, "{}", view);
+        assert_eq!((view["id"].clone(), view["context"]["body"].clone()), (json!(article.id), json!("Onboarding")));
+        assert_eq!(headers[header::CACHE_CONTROL], "private, no-store");
+
+        let (_, listed) = send(&app, request("GET", &format!("/breadcrumbs/{}/shares", article.id), None, None)).await;
+        assert_eq!((listed[0]["id"].clone(), listed[0]["revoked_at"].clone()), (share["id"].clone(), json!(null)));
+        assert!(listed[0].get("token").is_none());
+
+        // Raised to secret after it was shared
+        db.update_breadcrumb(owner_id, agent_id, article.id, Some(article.version), BreadcrumbUpdate {
+            title: None, description: None, semantic_version: None, context: None, tags: None, schema_name: None, llm_hints: None,
+            visibility: None, sensitivity: Some(Sensitivity::Secret), ttl: None, ttl_type: None, ttl_config: None, ttl_source: None,
+        }).await.unwrap();
+        assert_eq!(send(&app, request("GET", &url, None, None)).await.0, StatusCode::FORBIDDEN);
+
+        let (status, revoked) = send(&app, request("DELETE", &format!("/breadcrumbs/{}/shares/{}", article.id, share["id"].as_str().unwrap()), None, None)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(revoked["revoked_at"].is_string());
+        let (status, message) = send(&app, request("GET", &url, None, None)).await;
+        assert_eq!((status, message), (StatusCode::GONE, json!("share link revoked")));
+        // Three requests a minute per token, all spent above
+        assert_eq!(send(&app, request("GET", &url, None, None)).await.0, StatusCode::TOO_MANY_REQUESTS);
+    }
+}
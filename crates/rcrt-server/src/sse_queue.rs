@@ -0,0 +1,269 @@
+//! SSE Backpressure
+//! Bounded per-connection queue between the NATS bridge tasks and an SSE response
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, OnceLock};
+use prometheus::{IntCounterVec, IntGauge, IntGaugeVec, register_int_counter_vec, register_int_gauge, register_int_gauge_vec};
+use tokio::sync::{mpsc, Notify};
+use tokio_stream::wrappers::ReceiverStream;
+
+static SSE_QUEUE_DEPTH: OnceLock<IntGaugeVec> = OnceLock::new();
+static SSE_CONNECTIONS: OnceLock<IntGauge> = OnceLock::new();
+static SSE_DROPPED: OnceLock<IntCounterVec> = OnceLock::new();
+static SSE_CLOSED: OnceLock<IntCounterVec> = OnceLock::new();
+
+fn queue_depth() -> &'static IntGaugeVec {
+    SSE_QUEUE_DEPTH.get_or_init(|| register_int_gauge_vec!("sse_queue_depth", "Queued SSE events per connection", &["connection"]).unwrap())
+}
+
+fn connections() -> &'static IntGauge {
+    SSE_CONNECTIONS.get_or_init(|| register_int_gauge!("sse_connections_active", "Open SSE connections").unwrap())
+}
+
+fn dropped() -> &'static IntCounterVec {
+    SSE_DROPPED.get_or_init(|| register_int_counter_vec!("sse_events_dropped_total", "SSE events dropped on a full queue", &["policy"]).unwrap())
+}
+
+fn closed() -> &'static IntCounterVec {
+    SSE_CLOSED.get_or_init(|| register_int_counter_vec!("sse_connections_closed_total", "SSE connections closed by reason", &["reason"]).unwrap())
+}
+
+/// What to do when a client falls `capacity` events behind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Evict the oldest queued event to make room
+    DropOldest,
+    /// Send a final `overflow` event and end the stream; the client reconnects
+    Disconnect,
+}
+
+impl OverflowPolicy {
+    fn label(&self) -> &'static str {
+        match self {
+            OverflowPolicy::DropOldest => "drop_oldest",
+            OverflowPolicy::Disconnect => "disconnect",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Push {
+    Queued,
+    DroppedOldest,
+    Overflowed,
+    /// Client is gone or the stream already overflowed; producers should stop
+    Closed,
+}
+
+struct Inner {
+    items: VecDeque<String>,
+    closed: bool,
+}
+
+pub struct SseQueue {
+    connection_id: String,
+    capacity: usize,
+    policy: OverflowPolicy,
+    inner: Mutex<Inner>,
+    notify: Notify,
+    closed_signal: Notify,
+    depth: IntGauge,
+}
+
+impl SseQueue {
+    pub fn new(connection_id: String, capacity: usize, policy: OverflowPolicy) -> Arc<Self> {
+        let depth = queue_depth().with_label_values(&[&connection_id]);
+        connections().inc();
+        Arc::new(Self {
+            connection_id,
+            capacity: capacity.max(1),
+            policy,
+            inner: Mutex::new(Inner { items: VecDeque::new(), closed: false }),
+            notify: Notify::new(),
+            closed_signal: Notify::new(),
+            depth,
+        })
+    }
+
+    /// Queue an event; never blocks the caller
+    pub fn push(&self, item: String) -> Push {
+        let result = {
+            let Ok(mut inner) = self.inner.lock() else { return Push::Closed; };
+            if inner.closed {
+                return Push::Closed;
+            }
+            let result = if inner.items.len() < self.capacity {
+                inner.items.push_back(item);
+                Push::Queued
+            } else {
+                match self.policy {
+                    OverflowPolicy::DropOldest => {
+                        inner.items.pop_front();
+                        inner.items.push_back(item);
+                        Push::DroppedOldest
+                    }
+                    OverflowPolicy::Disconnect => {
+                        // The backlog is stale for a client this far behind; replace it
+                        // with a single overflow notice and stop accepting events
+                        let dropped = inner.items.len() + 1;
+                        inner.items.clear();
+                        inner.items.push_back(serde_json::json!({
+                            "type": "overflow",
+                            "capacity": self.capacity,
+                            "dropped": dropped,
+                        }).to_string());
+                        inner.closed = true;
+                        Push::Overflowed
+                    }
+                }
+            };
+            self.depth.set(inner.items.len() as i64);
+            result
+        };
+        match result {
+            Push::DroppedOldest => dropped().with_label_values(&[self.policy.label()]).inc(),
+            Push::Overflowed => {
+                self.closed_signal.notify_waiters();
+                dropped().with_label_values(&[self.policy.label()]).inc();
+                closed().with_label_values(&["overflow"]).inc();
+                tracing::warn!("🔧 SSE: ⚠️ Connection {} overflowed {} queued events, disconnecting", self.connection_id, self.capacity);
+            }
+            _ => {}
+        }
+        self.notify.notify_one();
+        result
+    }
+
+    /// Next event, waiting if the queue is empty; None once closed and drained
+    pub async fn pop(&self) -> Option<String> {
+        loop {
+            let notified = self.notify.notified();
+            {
+                let mut inner = self.inner.lock().ok()?;
+                if let Some(item) = inner.items.pop_front() {
+                    self.depth.set(inner.items.len() as i64);
+                    return Some(item);
+                }
+                if inner.closed {
+                    return None;
+                }
+            }
+            notified.await;
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.inner.lock().map(|inner| inner.closed).unwrap_or(true)
+    }
+
+    /// Resolves once the queue stops accepting events, for producers to `select!` on
+    pub async fn closed(&self) {
+        loop {
+            let notified = self.closed_signal.notified();
+            if self.is_closed() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Stop accepting events and discard anything queued
+    pub fn close(&self) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.closed = true;
+            inner.items.clear();
+        }
+        self.depth.set(0);
+        self.notify.notify_one();
+        self.closed_signal.notify_waiters();
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.inner.lock().map(|inner| inner.items.len()).unwrap_or(0)
+    }
+}
+
+impl Drop for SseQueue {
+    // Runs once every bridge, heartbeat and forwarder holding the queue has exited
+    fn drop(&mut self) {
+        connections().dec();
+        let _ = queue_depth().remove_label_values(&[&self.connection_id]);
+    }
+}
+
+/// Drain the queue into an SSE body stream.
+///
+/// Closes the queue when the client disconnects, so bridge tasks waiting on
+/// `closed()` stop and release their subscriptions.
+pub fn into_stream(queue: Arc<SseQueue>) -> ReceiverStream<String> {
+    let (tx, rx) = mpsc::channel::<String>(1);
+    tokio::spawn(async move {
+        let mut client_gone = false;
+        loop {
+            let item = tokio::select! {
+                item = queue.pop() => item,
+                _ = tx.closed() => { client_gone = true; None }
+            };
+            let Some(item) = item else { break };
+            if tx.send(item).await.is_err() {
+                client_gone = true;
+                break;
+            }
+        }
+        if client_gone {
+            closed().with_label_values(&["client_gone"]).inc();
+            tracing::info!("🔧 SSE: Client {} disconnected, stopping bridges", queue.connection_id);
+        }
+        queue.close();
+    });
+    ReceiverStream::new(rx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drop_oldest_keeps_newest() {
+        let queue = SseQueue::new("test-drop".into(), 2, OverflowPolicy::DropOldest);
+        assert_eq!(queue.push("a".into()), Push::Queued);
+        assert_eq!(queue.push("b".into()), Push::Queued);
+        assert_eq!(queue.push("c".into()), Push::DroppedOldest);
+        assert_eq!(queue.len(), 2);
+        assert!(!queue.is_closed());
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_sends_overflow_then_ends() {
+        let queue = SseQueue::new("test-disconnect".into(), 2, OverflowPolicy::Disconnect);
+        queue.push("a".into());
+        queue.push("b".into());
+        assert_eq!(queue.push("c".into()), Push::Overflowed);
+        assert_eq!(queue.push("d".into()), Push::Closed);
+
+        let last: serde_json::Value = serde_json::from_str(&queue.pop().await.unwrap()).unwrap();
+        assert_eq!(last["type"], "overflow");
+        assert_eq!(last["dropped"], 3);
+        assert_eq!(queue.pop().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_pop_waits_for_push() {
+        let queue = SseQueue::new("test-wait".into(), 4, OverflowPolicy::Disconnect);
+        let producer = queue.clone();
+        let handle = tokio::spawn(async move { queue.pop().await });
+        tokio::task::yield_now().await;
+        producer.push("x".into());
+        assert_eq!(handle.await.unwrap().as_deref(), Some("x"));
+    }
+
+    #[tokio::test]
+    async fn test_client_disconnect_closes_queue() {
+        let queue = SseQueue::new("test-gone".into(), 4, OverflowPolicy::Disconnect);
+        let stream = into_stream(queue.clone());
+        drop(stream);
+        tokio::time::timeout(std::time::Duration::from_secs(1), queue.closed()).await.expect("queue closed");
+        assert_eq!(queue.push("late".into()), Push::Closed);
+    }
+}
@@ -0,0 +1,215 @@
+//! Overview Stats
+//! Per-tenant aggregates for the dashboard overview, computed in SQL concurrently; a failed aggregate is reported in its own field
+
+use axum::{extract::State, http::StatusCode, Json};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{auth::{require_admin, AuthContext}, AppState};
+
+/// Minutes of write activity behind `events_per_minute`
+const EVENT_WINDOW_MINUTES: i32 = 15;
+/// Minutes of published contexts behind `context_latency`
+const LATENCY_WINDOW_MINUTES: i32 = 60;
+
+#[derive(Debug, Serialize)]
+pub struct SchemaCount {
+    pub schema_name: Option<String>,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MinuteCount {
+    pub minute: DateTime<Utc>,
+    pub count: i64,
+}
+
+/// The aggregate's value, or `{"error": ...}` so one failed query doesn't sink the response
+pub fn field<T: Serialize>(name: &str, result: Result<T, sqlx::Error>) -> Value {
+    match result {
+        Ok(value) => json!(value),
+        Err(e) => {
+            tracing::warn!("📊 Overview stat {} failed: {}", name, e);
+            json!({ "error": e.to_string() })
+        }
+    }
+}
+
+async fn breadcrumbs_by_schema(pool: &PgPool, owner_id: Uuid) -> Result<Vec<SchemaCount>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (Option<String>, i64)>(
+        r#"SELECT schema_name, count(*) FROM breadcrumbs
+           WHERE owner_id = $1 AND created_at > NOW() - INTERVAL '24 hours'
+           GROUP BY schema_name
+           ORDER BY count(*) DESC, schema_name"#
+    )
+    .bind(owner_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(|(schema_name, count)| SchemaCount { schema_name, count }).collect())
+}
+
+/// Creates and updates (one history row each), bucketed by minute; deletes are not counted
+async fn events_per_minute(pool: &PgPool, owner_id: Uuid) -> Result<Value, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (DateTime<Utc>, i64)>(
+        r#"SELECT date_trunc('minute', h.updated_at), count(*)
+           FROM breadcrumb_history h
+           JOIN breadcrumbs b ON b.id = h.breadcrumb_id
+           WHERE b.owner_id = $1 AND h.updated_at > NOW() - make_interval(mins => $2)
+           GROUP BY 1
+           ORDER BY 1"#
+    )
+    .bind(owner_id)
+    .bind(EVENT_WINDOW_MINUTES)
+    .fetch_all(pool)
+    .await?;
+    let total: i64 = rows.iter().map(|(_, count)| count).sum();
+    let series: Vec<MinuteCount> = rows.into_iter().map(|(minute, count)| MinuteCount { minute, count }).collect();
+    Ok(json!({
+        "window_minutes": EVENT_WINDOW_MINUTES,
+        "average": total as f64 / EVENT_WINDOW_MINUTES as f64,
+        "series": series
+    }))
+}
+
+/// Registered agents, and those that wrote a breadcrumb in the last 24 hours
+async fn active_agents(pool: &PgPool, owner_id: Uuid) -> Result<Value, sqlx::Error> {
+    let (registered, active_24h) = sqlx::query_as::<_, (i64, i64)>(
+        r#"SELECT
+             (SELECT count(*) FROM agents WHERE owner_id = $1),
+             (SELECT count(DISTINCT h.updated_by) FROM breadcrumb_history h
+              JOIN breadcrumbs b ON b.id = h.breadcrumb_id
+              WHERE b.owner_id = $1 AND h.updated_at > NOW() - INTERVAL '24 hours')"#
+    )
+    .bind(owner_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(json!({ "registered": registered, "active_24h": active_24h }))
+}
+
+/// p50/p95 seconds from a trigger's creation to the context the context-builder published for it, over
+/// every agent.context.v1 version written in the window that carries `provenance_timing`. Both ends are
+/// server clocks: the trigger's created_at and the history row's write. Negative spans (a trigger
+/// timestamp from a skewed clock) are left out
+async fn context_latency(pool: &PgPool, owner_id: Uuid) -> Result<Value, sqlx::Error> {
+    let (samples, p50, p95) = sqlx::query_as::<_, (i64, Option<f64>, Option<f64>)>(
+        r#"SELECT count(*),
+             percentile_cont(0.5) WITHIN GROUP (ORDER BY e2e),
+             percentile_cont(0.95) WITHIN GROUP (ORDER BY e2e)
+           FROM (
+             SELECT extract(epoch FROM h.updated_at - (h.context->'provenance_timing'->>'trigger_created_at')::timestamptz)::float8 AS e2e
+             FROM breadcrumb_history h
+             JOIN breadcrumbs b ON b.id = h.breadcrumb_id
+             WHERE b.owner_id = $1 AND b.schema_name = 'agent.context.v1'
+               AND h.updated_at > NOW() - make_interval(mins => $2)
+               AND jsonb_typeof(h.context->'provenance_timing'->'trigger_created_at') = 'string'
+           ) spans
+           WHERE e2e >= 0"#
+    )
+    .bind(owner_id)
+    .bind(LATENCY_WINDOW_MINUTES)
+    .fetch_one(pool)
+    .await?;
+    Ok(json!({ "window_minutes": LATENCY_WINDOW_MINUTES, "samples": samples, "p50_seconds": p50, "p95_seconds": p95 }))
+}
+
+async fn dlq_depth(pool: &PgPool, owner_id: Uuid) -> Result<Value, sqlx::Error> {
+    let (depth, oldest) = sqlx::query_as::<_, (i64, Option<DateTime<Utc>>)>(
+        "SELECT count(*), min(created_at) FROM webhook_dlq WHERE owner_id = $1"
+    )
+    .bind(owner_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(json!({ "depth": depth, "oldest": oldest }))
+}
+
+pub async fn admin_stats(State(state): State<AppState>, auth: AuthContext) -> Result<Json<Value>, (StatusCode, String)> {
+    require_admin(&state, &auth)?;
+
+    let pool = &state.db.pool;
+    let (by_schema, events, agents, dlq, latency) = tokio::join!(
+        breadcrumbs_by_schema(pool, auth.owner_id),
+        events_per_minute(pool, auth.owner_id),
+        active_agents(pool, auth.owner_id),
+        dlq_depth(pool, auth.owner_id),
+        context_latency(pool, auth.owner_id),
+    );
+    let hygiene = state.hygiene_stats.lock().map(|s| s.clone()).unwrap_or_default();
+
+    Ok(Json(json!({
+        "generated_at": Utc::now(),
+        "breadcrumbs_by_schema_24h": field("breadcrumbs_by_schema_24h", by_schema),
+        "events_per_minute": field("events_per_minute", events),
+        "active_agents": field("active_agents", agents),
+        "dlq": field("dlq", dlq),
+        "context_latency": field("context_latency", latency),
+        "hygiene": {
+            "runs_completed": hygiene.runs_completed,
+            "last_run_at": hygiene.last_run_at,
+            "last_run_duration_ms": hygiene.last_run_duration_ms,
+            "last_run_errors": hygiene.last_run_errors
+        }
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failed_field_becomes_error_marker() {
+        assert_eq!(field("dlq", Ok::<_, sqlx::Error>(json!({ "depth": 2 }))), json!({ "depth": 2 }));
+        assert_eq!(field::<Value>("dlq", Err(sqlx::Error::RowNotFound))["error"], sqlx::Error::RowNotFound.to_string());
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_admin_stats_aggregates_own_tenant(pool: sqlx::PgPool) {
+        use crate::auth::{AuthConfig, AuthMode};
+        use crate::test_support::{crumb, request, send, state};
+        use rcrt_core::db::Db;
+
+        let db = Db { pool };
+        let (owner_id, agent_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let (other_owner, other_agent) = (Uuid::new_v4(), Uuid::new_v4());
+        for (owner, agent, name) in [(owner_id, agent_id, "Stats Test"), (other_owner, other_agent, "Other Tenant")] {
+            db.ensure_tenant(owner, name).await.unwrap();
+            db.upsert_agent(owner, agent, vec!["curator".into()]).await.unwrap();
+        }
+        for schema in ["user.message.v1", "user.message.v1", "note.v1"] {
+            db.create_breadcrumb_for(owner_id, Some(agent_id), Some(agent_id), crumb(schema, &[])).await.unwrap();
+        }
+        db.create_breadcrumb_for(other_owner, Some(other_agent), Some(other_agent), crumb("note.v1", &[])).await.unwrap();
+        db.enqueue_webhook_dlq(owner_id, agent_id, "http://127.0.0.1:1/hook", &json!({}), "HTTP 500", Some(500)).await.unwrap();
+        // Contexts published 2s, 4s and 6s after their triggers, and one whose trigger is in the future
+        let context = db.create_breadcrumb_for(owner_id, Some(agent_id), Some(agent_id), crumb("agent.context.v1", &[])).await.unwrap();
+        for (version, lag) in [(2, 2), (3, 4), (4, 6), (5, -30)] {
+            let timing = json!({ "provenance_timing": { "trigger_created_at": Utc::now() - chrono::Duration::seconds(lag) } });
+            sqlx::query("insert into breadcrumb_history (breadcrumb_id, version, context, updated_at, updated_by, checksum) values ($1, $2, $3, now(), $4, '')")
+                .bind(context.id).bind(version).bind(timing).bind(agent_id)
+                .execute(&db.pool).await.unwrap();
+        }
+
+        let auth = AuthConfig::new(AuthMode::Disabled { owner_id, agent_id }, None, None, None, None).unwrap();
+        let app = crate::build_app(state(db, auth).await);
+        let (status, body) = send(&app, request("GET", "/admin/stats", None, None)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+
+        assert_eq!(body["breadcrumbs_by_schema_24h"], json!([
+            { "schema_name": "user.message.v1", "count": 2 },
+            { "schema_name": "agent.context.v1", "count": 1 },
+            { "schema_name": "note.v1", "count": 1 },
+        ]));
+        let series = body["events_per_minute"]["series"].as_array().unwrap();
+        assert_eq!(series.iter().map(|m| m["count"].as_i64().unwrap()).sum::<i64>(), 8);
+        let latency = &body["context_latency"];
+        assert_eq!(latency["samples"], 3);
+        assert!((latency["p50_seconds"].as_f64().unwrap() - 4.0).abs() < 0.5, "{}", latency);
+        assert!((latency["p95_seconds"].as_f64().unwrap() - 5.8).abs() < 0.5, "{}", latency);
+        assert_eq!(body["active_agents"], json!({ "registered": 1, "active_24h": 1 }));
+        assert_eq!(body["dlq"]["depth"], 1);
+        assert!(body["hygiene"].get("last_run_at").is_some());
+    }
+}
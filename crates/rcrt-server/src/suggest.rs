@@ -0,0 +1,187 @@
+//! Breadcrumb Suggest
+//! GET /breadcrumbs/suggest: title and tag type-ahead for the dashboard, served by the trigram indexes from 0021
+
+use axum::{extract::{Query, State}, http::StatusCode, Json};
+use rcrt_core::error::DbError;
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::auth::AuthContext;
+use crate::breadcrumbs::push_full_read_condition;
+use crate::replica::ReadPreference;
+use crate::{internal_error, AppState};
+
+const DEFAULT_LIMIT: i64 = 10;
+const MAX_LIMIT: i64 = 50;
+
+#[derive(Deserialize)]
+pub struct SuggestQuery {
+    q: Option<String>,
+    kind: Option<String>,
+    limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SuggestKind {
+    Title,
+    Tag,
+}
+
+impl SuggestKind {
+    fn parse(kind: Option<&str>) -> Result<Self, (StatusCode, String)> {
+        match kind {
+            None | Some("title") => Ok(SuggestKind::Title),
+            Some("tag") => Ok(SuggestKind::Tag),
+            Some(other) => Err((StatusCode::BAD_REQUEST, format!("kind must be title or tag, got {}", other))),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct TitleSuggestion {
+    pub id: Uuid,
+    pub title: String,
+    pub score: f32,
+}
+
+#[derive(Serialize)]
+pub struct TagSuggestion {
+    pub tag: String,
+    /// Readable breadcrumbs carrying the tag
+    pub count: i64,
+    pub score: f32,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum SuggestResult {
+    Titles(Vec<TitleSuggestion>),
+    Tags(Vec<TagSuggestion>),
+}
+
+/// `q` as an ILIKE substring pattern, with its own `%`, `_` and `\` matched literally
+fn contains_pattern(q: &str) -> String {
+    let mut pattern = String::with_capacity(q.len() + 2);
+    pattern.push('%');
+    for c in q.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+/// Titles containing `q`, those starting with it first, then by trigram similarity and recency
+fn push_title_query(qb: &mut QueryBuilder<'_, Postgres>, auth: &AuthContext, q: &str, limit: i64) {
+    let pattern = contains_pattern(q);
+    qb.push("select id, title, similarity(title, ").push_bind(q.to_string()).push(") as score from breadcrumbs where owner_id = ")
+        .push_bind(auth.owner_id)
+        .push(" and title ilike ").push_bind(pattern.clone());
+    push_full_read_condition(qb, auth);
+    qb.push(" order by title ilike ").push_bind(pattern[1..].to_string())
+        .push(" desc, score desc, updated_at desc limit ").push_bind(limit);
+}
+
+/// Tags containing `q` with how many rows carry each; the index narrows rows by their joined tags,
+/// then only the matching tags of those rows are counted
+fn push_tag_query(qb: &mut QueryBuilder<'_, Postgres>, auth: &AuthContext, q: &str, limit: i64) {
+    let pattern = contains_pattern(q);
+    qb.push("select tag, count(*) as count, similarity(tag, ").push_bind(q.to_string())
+        .push(") as score from breadcrumbs, unnest(breadcrumbs.tags) as tag where owner_id = ").push_bind(auth.owner_id)
+        .push(" and breadcrumb_tags_text(tags) ilike ").push_bind(pattern.clone());
+    push_full_read_condition(qb, auth);
+    qb.push(" and tag ilike ").push_bind(pattern.clone())
+        .push(" group by tag order by tag ilike ").push_bind(pattern[1..].to_string())
+        .push(" desc, score desc, count desc, tag limit ").push_bind(limit);
+}
+
+/// `?q=open&kind=title|tag&limit=10`. Owner-scoped, and like search only over rows the caller could
+/// read in full; titles come back with ids, tags with counts
+pub async fn suggest(State(state): State<AppState>, auth: AuthContext, Query(q): Query<SuggestQuery>) -> Result<Json<SuggestResult>, (StatusCode, String)> {
+    let kind = SuggestKind::parse(q.kind.as_deref())?;
+    let text = q.q.as_deref().map(str::trim).unwrap_or_default();
+    if text.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "q is required".into()));
+    }
+    let limit = q.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    match kind {
+        SuggestKind::Title => {
+            let rows = state.reads.read(ReadPreference::Replica, |db| {
+                let mut qb = QueryBuilder::<Postgres>::new("");
+                push_title_query(&mut qb, &auth, text, limit);
+                async move { qb.build_query_as::<(Uuid, String, f32)>().fetch_all(&db.pool).await.map_err(DbError::from) }
+            }).await.map_err(internal_error)?;
+            let titles = rows.into_iter().map(|(id, title, score)| TitleSuggestion { id, title, score }).collect();
+            Ok(Json(SuggestResult::Titles(titles)))
+        }
+        SuggestKind::Tag => {
+            let rows = state.reads.read(ReadPreference::Replica, |db| {
+                let mut qb = QueryBuilder::<Postgres>::new("");
+                push_tag_query(&mut qb, &auth, text, limit);
+                async move { qb.build_query_as::<(String, i64, f32)>().fetch_all(&db.pool).await.map_err(DbError::from) }
+            }).await.map_err(internal_error)?;
+            let tags = rows.into_iter().map(|(tag, count, score)| TagSuggestion { tag, count, score }).collect();
+            Ok(Json(SuggestResult::Tags(tags)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contains_pattern_escapes_wildcards() {
+        assert_eq!(contains_pattern("open"), "%open%");
+        assert_eq!(contains_pattern("50%_off\\"), "%50\\%\\_off\\\\%");
+    }
+
+    #[test]
+    fn test_kind_defaults_to_title() {
+        assert_eq!(SuggestKind::parse(None).unwrap(), SuggestKind::Title);
+        assert_eq!(SuggestKind::parse(Some("tag")).unwrap(), SuggestKind::Tag);
+        assert_eq!(SuggestKind::parse(Some("schema")).unwrap_err().0, StatusCode::BAD_REQUEST);
+    }
+
+    /// On a seeded table the planner picks the trigram indexes over the owner index for both queries
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_suggest_queries_use_trigram_indexes(pool: sqlx::PgPool) {
+        let db = rcrt_core::db::Db { pool: pool.clone() };
+        let owner_id = Uuid::new_v4();
+        db.ensure_tenant(owner_id, "Suggest Test").await.unwrap();
+        sqlx::query(
+            "insert into breadcrumbs (owner_id, title, context, tags, checksum, size_bytes)
+             select $1, 'note ' || g, '{}', array['kb', 'topic:' || (g % 500)], 'x', 2 from generate_series(1, 20000) g",
+        ).bind(owner_id).execute(&pool).await.unwrap();
+        sqlx::query(
+            "insert into breadcrumbs (owner_id, title, context, tags, checksum, size_bytes)
+             values ($1, 'OpenAI rollout', '{}', array['openai'], 'x', 2), ($1, 'Reopen ticket', '{}', array['openai', 'kb'], 'x', 2)",
+        ).bind(owner_id).execute(&pool).await.unwrap();
+        sqlx::query("analyze breadcrumbs").execute(&pool).await.unwrap();
+
+        let auth = AuthContext { owner_id, agent_id: Uuid::new_v4(), roles: vec![rcrt_core::roles::Role::Curator] };
+        let plan = |push: fn(&mut QueryBuilder<'_, Postgres>, &AuthContext, &str, i64)| {
+            let (pool, auth) = (pool.clone(), auth.clone());
+            async move {
+                let mut qb = QueryBuilder::<Postgres>::new("explain ");
+                push(&mut qb, &auth, "open", 10);
+                let lines: Vec<(String,)> = qb.build_query_as().fetch_all(&pool).await.unwrap();
+                lines.into_iter().map(|(line,)| line).collect::<Vec<_>>().join("\n")
+            }
+        };
+        let title_plan = plan(push_title_query).await;
+        assert!(title_plan.contains("idx_breadcrumbs_title_trgm"), "{}", title_plan);
+        let tag_plan = plan(push_tag_query).await;
+        assert!(tag_plan.contains("idx_breadcrumbs_tags_trgm"), "{}", tag_plan);
+
+        let mut qb = QueryBuilder::<Postgres>::new("");
+        push_title_query(&mut qb, &auth, "open", 10);
+        let titles: Vec<(Uuid, String, f32)> = qb.build_query_as().fetch_all(&pool).await.unwrap();
+        assert_eq!(titles.iter().map(|t| t.1.as_str()).collect::<Vec<_>>(), vec!["OpenAI rollout", "Reopen ticket"]);
+    }
+}
@@ -0,0 +1,76 @@
+//! Telemetry
+//! Log output (the fmt layer, LOG_FORMAT=json for JSON lines) over the shared tracing in
+//! `rcrt_core::telemetry`; the request span's trace is sent on with breadcrumb events so the
+//! context-builder's handling joins it
+
+use tracing_subscriber::layer::Layer;
+use tracing_subscriber::{fmt as log_fmt, EnvFilter};
+
+pub use rcrt_core::telemetry::current_traceparent;
+
+/// Set up logging and tracing for `service`; call once, inside the runtime
+pub fn init(service: &str) {
+    let log = if std::env::var("LOG_FORMAT").as_deref() == Ok("json") {
+        // One object per line, with span fields (request_id, breadcrumb_id) as keys
+        log_fmt::layer().json().with_current_span(true).with_span_list(true).boxed()
+    } else {
+        log_fmt::layer().boxed()
+    };
+    rcrt_core::telemetry::init(service, "rcrt_server", log.with_filter(EnvFilter::from_default_env()));
+}
+
+#[cfg(all(test, feature = "db-tests"))]
+mod tests {
+    use rcrt_core::telemetry::Capture;
+    use rcrt_core::trace_context::{SpanRecord, TraceContext, TRACEPARENT};
+
+    const REMOTE: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_create_breadcrumb_spans(pool: sqlx::PgPool) {
+        use crate::auth::{AuthConfig, AuthMode};
+        use crate::test_support::state;
+        use axum::body::Body;
+        use axum::http::{header, StatusCode};
+        use rcrt_core::db::Db;
+        use tower::ServiceExt;
+        use uuid::Uuid;
+
+        let (owner_id, agent_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let db = Db { pool };
+        db.ensure_tenant(owner_id, "Telemetry Test").await.unwrap();
+        db.upsert_agent(owner_id, agent_id, vec!["curator".into(), "emitter".into(), "subscriber".into()]).await.unwrap();
+        let auth = AuthConfig::new(AuthMode::Disabled { owner_id, agent_id }, None, None, None, None).unwrap();
+        let app = crate::build_app(state(db, auth).await);
+
+        let (capture, _guard) = Capture::install("rcrt_server");
+        let res = app.oneshot(axum::http::Request::post("/breadcrumbs")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(TRACEPARENT, REMOTE)
+            .body(Body::from(serde_json::json!({ "title": "traced", "schema_name": "user.message.v1", "context": { "message": "hi" }, "tags": [] }).to_string()))
+            .unwrap()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let spans = capture.spans();
+        let request = spans.iter().find(|s| s.name == "request").unwrap();
+        assert_eq!(request.parent_span_id, Some(TraceContext::parse(REMOTE).unwrap().span_id));
+        // Each phase is in the caller's trace, somewhere under the request span
+        let parent_of = |span: &SpanRecord| spans.iter().find(|p| Some(p.context.span_id) == span.parent_span_id);
+        let query = |s: &&SpanRecord| s.attributes.iter().any(|(k, v)| k == "query" && v == "create_breadcrumb_with_embeddings_for");
+        let phases = [
+            spans.iter().find(|s| s.name == "auth"),
+            spans.iter().filter(|s| s.name == "db").find(query),
+            spans.iter().find(|s| s.name == "fanout_events_and_webhooks"),
+        ];
+        for span in phases {
+            let span = span.expect("a phase has no span");
+            assert_eq!(span.context.trace_id, request.context.trace_id, "{}", span.name);
+            let mut ancestor = parent_of(span);
+            while let Some(a) = ancestor.filter(|a| a.name != "request") {
+                ancestor = parent_of(a);
+            }
+            assert!(ancestor.is_some(), "{} is outside the request span", span.name);
+        }
+        assert_eq!(phases[0].unwrap().parent_span_id, Some(request.context.span_id));
+    }
+}
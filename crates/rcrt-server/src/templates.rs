@@ -0,0 +1,192 @@
+//! Breadcrumb Templates
+//! template.v1 breadcrumbs describe a breadcrumb's shape once; POST /breadcrumbs/from_template/:name fills one in
+
+use std::sync::OnceLock;
+use axum::{extract::{Path, State}, http::StatusCode, response::{IntoResponse, Response}, Json};
+use rcrt_core::roles::Role;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::auth::AuthContext;
+use crate::breadcrumbs::{self, CreateResp};
+use crate::service::CreateReq;
+use crate::{db_errors::db_error, transforms::TransformEngine, AppState};
+
+/// `{"name": "tool-request", "schema_name": "tool.request.v1", "title": "Run {{inputs.tool}}",
+/// "tags": ["tool:request"], "context": {"tool": "{{inputs.tool}}", "input": "{{inputs.input}}"},
+/// "required": ["tool", "input"]}`; `name` defaults to the breadcrumb title
+pub const TEMPLATE: &str = "template.v1";
+
+static SKELETON_ENGINE: OnceLock<TransformEngine> = OnceLock::new();
+
+/// A parsed template.v1 context
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    pub name: String,
+    pub schema_name: String,
+    /// Handlebars; the template name when unset
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    /// Skeleton rendered with `{"inputs": {...}}` by TransformEngine::render_skeleton
+    pub context: Value,
+    /// Input fields that must be present and non-null
+    pub required: Vec<String>,
+}
+
+impl Template {
+    /// Validate a template.v1 context; the error is fit for a 422
+    pub fn from_context(name: &str, context: &Value) -> Result<Self, String> {
+        let text = |key: &str| context.get(key).and_then(|v| v.as_str()).filter(|s| !s.is_empty()).map(str::to_string);
+        let strings = |key: &str| match context.get(key) {
+            None | Some(Value::Null) => Ok(Vec::new()),
+            Some(Value::Array(items)) => items.iter()
+                .map(|v| v.as_str().filter(|s| !s.is_empty()).map(str::to_string))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| format!("{} must be non-empty strings", key)),
+            Some(_) => Err(format!("{} must be an array", key)),
+        };
+        let schema_name = text("schema_name").ok_or("schema_name must be a non-empty string")?;
+        if schema_name == TEMPLATE {
+            return Err("templates can't create templates".into());
+        }
+        let skeleton = match context.get("context") {
+            None | Some(Value::Null) => json!({}),
+            Some(v @ Value::Object(_)) => v.clone(),
+            Some(_) => return Err("context must be an object".into()),
+        };
+        Ok(Template {
+            name: text("name").unwrap_or_else(|| name.to_string()),
+            schema_name,
+            title: text("title"),
+            tags: strings("tags")?,
+            context: skeleton,
+            required: strings("required")?,
+        })
+    }
+
+    /// One `{"field": "inputs.<name>", "error": "required"}` per required input that is missing or null
+    pub fn missing_inputs(&self, inputs: &Map<String, Value>) -> Vec<Value> {
+        self.required.iter()
+            .filter(|field| inputs.get(field.as_str()).unwrap_or(&Value::Null).is_null())
+            .map(|field| json!({ "field": format!("inputs.{}", field), "error": "required" }))
+            .collect()
+    }
+
+    /// The template's tags first, then the caller's, without duplicates
+    pub fn merge_tags(&self, extra: &[String]) -> Vec<String> {
+        let mut tags = self.tags.clone();
+        for tag in extra {
+            if !tags.contains(tag) {
+                tags.push(tag.clone());
+            }
+        }
+        tags
+    }
+
+    /// (title, context) for these inputs; an explicit title wins over the template's
+    pub fn render(&self, engine: &TransformEngine, inputs: Map<String, Value>, title: Option<String>) -> Result<(String, Value), String> {
+        let data = json!({ "inputs": inputs });
+        let context = engine.render_skeleton(&self.context, &data)?;
+        let title = match (title, &self.title) {
+            (Some(title), _) => title,
+            (None, Some(template)) => match engine.render_skeleton(&Value::String(template.clone()), &data)? {
+                Value::String(s) if !s.trim().is_empty() => s,
+                Value::String(_) | Value::Null => self.name.clone(),
+                other => other.to_string(),
+            },
+            (None, None) => self.name.clone(),
+        };
+        Ok((title, context))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct FromTemplateReq {
+    #[serde(default)]
+    inputs: Map<String, Value>,
+    /// Added after the template's tags
+    #[serde(default)]
+    tags: Vec<String>,
+    /// Overrides the template's title
+    title: Option<String>,
+}
+
+fn unprocessable(body: Value) -> Response {
+    (StatusCode::UNPROCESSABLE_ENTITY, Json(body)).into_response()
+}
+
+/// Render a template.v1 and create the result through POST /breadcrumbs, so embedding, events and
+/// TTL policies apply as for any other create. 422 lists every missing required input
+#[tracing::instrument(skip_all, fields(template = %template_name))]
+pub async fn create_from_template(State(state): State<AppState>, auth: AuthContext, headers: axum::http::HeaderMap, Path(template_name): Path<String>, Json(req): Json<FromTemplateReq>) -> Result<(axum::http::HeaderMap, Json<CreateResp>), Response> {
+    if !(auth.has_role(Role::Emitter) || auth.has_role(Role::Curator)) {
+        return Err((StatusCode::FORBIDDEN, "emitter role required").into_response());
+    }
+    let Some(bc) = state.db.find_template(auth.owner_id, Some(auth.agent_id), &template_name).await.map_err(|e| db_error(e).into_response())? else {
+        return Err((StatusCode::NOT_FOUND, format!("no {} named {}", TEMPLATE, template_name)).into_response());
+    };
+    let template = Template::from_context(&bc.title, &bc.context)
+        .map_err(|e| unprocessable(json!({ "error": format!("invalid {} {}: {}", TEMPLATE, bc.id, e) })))?;
+    let missing = template.missing_inputs(&req.inputs);
+    if !missing.is_empty() {
+        return Err(unprocessable(json!({ "error": "missing template inputs", "fields": missing })));
+    }
+    let (title, context) = template.render(SKELETON_ENGINE.get_or_init(TransformEngine::new), req.inputs, req.title)
+        .map_err(|e| unprocessable(json!({ "error": e })))?;
+    let tags = template.merge_tags(&req.tags);
+    let create = CreateReq::new(title, context, tags, template.schema_name);
+    breadcrumbs::create_breadcrumb(State(state), auth, headers, Json(create)).await.map_err(IntoResponse::into_response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template() -> Template {
+        Template::from_context("tool-request", &json!({
+            "schema_name": "tool.request.v1",
+            "title": "Run {{inputs.tool}}",
+            "tags": ["tool:request", "workspace:tools"],
+            "context": { "tool": "{{inputs.tool}}", "input": "{{inputs.input}}", "note": "via {{inputs.tool}}" },
+            "required": ["tool", "input"]
+        })).unwrap()
+    }
+
+    #[test]
+    fn test_from_context_validates() {
+        let t = template();
+        assert_eq!(t.name, "tool-request");
+        assert_eq!(t.required, vec!["tool", "input"]);
+        assert!(Template::from_context("t", &json!({})).unwrap_err().contains("schema_name"));
+        assert!(Template::from_context("t", &json!({ "schema_name": "x.v1", "context": "text" })).unwrap_err().contains("context"));
+        assert!(Template::from_context("t", &json!({ "schema_name": "x.v1", "required": "tool" })).unwrap_err().contains("required"));
+        assert!(Template::from_context("t", &json!({ "schema_name": TEMPLATE })).is_err());
+        assert_eq!(Template::from_context("t", &json!({ "schema_name": "x.v1", "name": "named" })).unwrap().name, "named");
+    }
+
+    #[test]
+    fn test_missing_inputs_are_listed_by_field() {
+        let inputs = json!({ "tool": "search", "input": null }).as_object().unwrap().clone();
+        assert_eq!(template().missing_inputs(&inputs), vec![json!({ "field": "inputs.input", "error": "required" })]);
+        assert_eq!(template().missing_inputs(&Map::new()).len(), 2);
+    }
+
+    #[test]
+    fn test_render_fills_placeholders() {
+        let engine = TransformEngine::new();
+        let inputs = json!({ "tool": "search", "input": { "q": "rust" } }).as_object().unwrap().clone();
+        let (title, context) = template().render(&engine, inputs.clone(), None).unwrap();
+        assert_eq!(title, "Run search");
+        assert_eq!(context, json!({ "tool": "search", "input": { "q": "rust" }, "note": "via search" }));
+        let (title, _) = template().render(&engine, inputs, Some("Mine".into())).unwrap();
+        assert_eq!(title, "Mine");
+        let bare = Template::from_context("bare", &json!({ "schema_name": "x.v1" })).unwrap();
+        assert_eq!(bare.render(&engine, Map::new(), None).unwrap(), ("bare".to_string(), json!({})));
+    }
+
+    #[test]
+    fn test_merge_tags_keeps_template_tags_first() {
+        let merged = template().merge_tags(&["session:1".into(), "tool:request".into()]);
+        assert_eq!(merged, vec!["tool:request", "workspace:tools", "session:1"]);
+    }
+}
@@ -0,0 +1,335 @@
+//! Tenant Deletion
+//! The staged run behind DELETE /tenants/:id: the tenant is marked deleting and its writes refused,
+//! a full NDJSON snapshot is exported, the owner's rows are deleted table by table in batches
+//! (`rcrt_core::db::TENANT_TABLES`), and the tenants row goes last. Progress is kept in
+//! tenant_deletions; a failed run is resumed by requesting the deletion again
+
+use std::collections::HashSet;
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
+use axum::http::{Method, StatusCode};
+use base64::Engine;
+use reqwest::Client as HttpClient;
+use rcrt_core::db::TENANT_TABLES;
+use rcrt_core::models::TenantDeletion;
+use serde_json::json;
+use tokio::io::AsyncReadExt;
+use uuid::Uuid;
+
+use crate::AppState;
+
+/// Rows per delete statement, so no single statement holds locks for long
+const BATCH_SIZE: i64 = 500;
+/// How often each replica reloads which tenants are being deleted
+const REFRESH_EVERY: Duration = Duration::from_secs(5);
+/// First field of the snapshot's header line
+pub const EXPORT_FORMAT: &str = "rcrt.tenant_export.v1";
+
+/// Tenants being deleted, whose writes the AuthContext extractor refuses. A deletion started here
+/// is marked at once; other replicas pick it up within REFRESH_EVERY
+#[derive(Default)]
+pub struct DeletingTenants {
+    owners: RwLock<HashSet<Uuid>>,
+    /// Runs in progress on this replica, so asking again doesn't start a second one
+    running: Mutex<HashSet<Uuid>>,
+}
+
+impl DeletingTenants {
+    pub fn contains(&self, owner_id: Uuid) -> bool {
+        self.owners.read().map(|owners| owners.contains(&owner_id)).unwrap_or(false)
+    }
+
+    pub fn mark(&self, owner_id: Uuid) {
+        if let Ok(mut owners) = self.owners.write() {
+            owners.insert(owner_id);
+        }
+    }
+
+    /// Replace the set with the database's, keeping runs this replica has in progress
+    fn replace(&self, owners: Vec<Uuid>) {
+        let mut owners: HashSet<Uuid> = owners.into_iter().collect();
+        if let Ok(running) = self.running.lock() {
+            owners.extend(running.iter().copied());
+        }
+        if let Ok(mut current) = self.owners.write() {
+            *current = owners;
+        }
+    }
+
+    /// False when a run for the tenant is already in progress here
+    fn start(&self, tenant_id: Uuid) -> bool {
+        self.running.lock().map(|mut running| running.insert(tenant_id)).unwrap_or(false)
+    }
+
+    fn finish(&self, tenant_id: Uuid) {
+        if let Ok(mut running) = self.running.lock() {
+            running.remove(&tenant_id);
+        }
+    }
+}
+
+/// Refuse anything but GET/HEAD/OPTIONS by a tenant being deleted. /tenants/:id stays open so the
+/// deletion can be asked for again, to resume it, with the same credentials
+pub fn reject_writes(deleting: &DeletingTenants, method: &Method, path: &str, owner_id: Uuid) -> Result<(), (StatusCode, String)> {
+    if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) || path.starts_with("/tenants/") || !deleting.contains(owner_id) {
+        return Ok(());
+    }
+    Err((StatusCode::CONFLICT, format!("tenant {} is being deleted; writes are rejected", owner_id)))
+}
+
+/// Reload the deleting tenants every REFRESH_EVERY
+pub fn start_refresher(state: AppState) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(REFRESH_EVERY);
+        loop {
+            tick.tick().await;
+            match state.db.list_deleting_tenants().await {
+                Ok(owners) => state.deleting_tenants.replace(owners),
+                Err(e) => tracing::warn!("Failed to load deleting tenants: {}", e),
+            }
+        }
+    })
+}
+
+/// Run (or resume) the tenant's deletion in the background; does nothing while a run is in progress here
+pub fn spawn(state: AppState, tenant_id: Uuid) {
+    state.deleting_tenants.mark(tenant_id);
+    if !state.deleting_tenants.start(tenant_id) {
+        return;
+    }
+    tokio::spawn(async move {
+        if let Err(e) = run(&state, tenant_id).await {
+            tracing::error!("Deletion of tenant {} failed: {:#}", tenant_id, e);
+            if let Err(e) = state.db.fail_tenant_deletion(tenant_id, &format!("{:#}", e)).await {
+                tracing::error!("Failed to record the failed deletion of tenant {}: {}", tenant_id, e);
+            }
+        }
+        state.deleting_tenants.finish(tenant_id);
+    });
+}
+
+async fn run(state: &AppState, tenant_id: Uuid) -> anyhow::Result<()> {
+    let Some(deletion) = state.db.get_tenant_deletion(tenant_id).await? else { return Ok(()) };
+    if deletion.status == "completed" {
+        return Ok(());
+    }
+    if deletion.export_location.is_none() {
+        state.db.set_tenant_deletion_stage(tenant_id, "export").await?;
+        let location = export(state, &deletion).await?;
+        state.db.set_tenant_deletion_exported(tenant_id, &location).await?;
+        tracing::info!("📦 Tenant {} exported to {}", tenant_id, location);
+    }
+
+    // Stored blobs go first, while the attachments rows still name them; a missing key is not an error
+    state.db.set_tenant_deletion_stage(tenant_id, "blobs").await?;
+    for key in state.db.list_tenant_blob_keys(tenant_id).await? {
+        state.attachment_store.delete(&key).await?;
+    }
+    for (table, _) in TENANT_TABLES {
+        state.db.set_tenant_deletion_stage(tenant_id, table).await?;
+        loop {
+            let n = state.db.delete_tenant_rows(tenant_id, table, BATCH_SIZE).await?;
+            if n > 0 {
+                state.db.add_tenant_deletion_progress(tenant_id, table, n as i64).await?;
+            }
+            if n < BATCH_SIZE as u64 { break; }
+        }
+    }
+    state.db.set_tenant_deletion_stage(tenant_id, "tenant").await?;
+    state.db.finish_tenant_deletion(tenant_id).await?;
+    state.selector_index.invalidate(tenant_id);
+    tracing::info!("🗑️  Tenant {} ({}) deleted", tenant_id, deletion.tenant_name);
+    Ok(())
+}
+
+/// Write the snapshot to `export_to` (PUT) or the attachment store; returns where it went
+async fn export(state: &AppState, deletion: &TenantDeletion) -> anyhow::Result<String> {
+    let tenant_id = deletion.tenant_id;
+    let mut out = String::new();
+    push_line(&mut out, &json!({
+        "format": EXPORT_FORMAT,
+        "tenant": { "id": tenant_id, "name": deletion.tenant_name },
+        "exported_at": chrono::Utc::now(),
+    }));
+    for (table, _) in TENANT_TABLES {
+        for row in state.db.export_tenant_table(tenant_id, table).await? {
+            push_line(&mut out, &json!({ "table": table, "row": row }));
+        }
+    }
+    // Blobs above the inline size live in the store, which the deletion empties too
+    for key in state.db.list_tenant_blob_keys(tenant_id).await? {
+        let mut bytes = Vec::new();
+        state.attachment_store.open(&key).await?.read_to_end(&mut bytes).await?;
+        push_line(&mut out, &json!({
+            "table": "attachment_blobs",
+            "row": { "storage_path": key, "data": base64::engine::general_purpose::STANDARD.encode(&bytes) },
+        }));
+    }
+
+    match &deletion.export_to {
+        Some(url) => {
+            HttpClient::new()
+                .put(url)
+                .header(reqwest::header::CONTENT_TYPE, "application/x-ndjson")
+                .body(out)
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(url.clone())
+        }
+        None => {
+            let key = export_key(tenant_id, deletion.started_at);
+            state.attachment_store.put(&key, out.as_bytes()).await?;
+            Ok(key)
+        }
+    }
+}
+
+fn push_line(out: &mut String, line: &serde_json::Value) {
+    out.push_str(&line.to_string());
+    out.push('\n');
+}
+
+/// Outside the `{owner_id}/...` keys of the tenant's own blobs, so emptying those never touches it
+pub(crate) fn export_key(tenant_id: Uuid, started_at: chrono::DateTime<chrono::Utc>) -> String {
+    format!("tenant-exports/{}/{}.ndjson", tenant_id, started_at.format("%Y%m%dT%H%M%SZ"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_reads_and_the_deletion_itself_get_through() {
+        let deleting = DeletingTenants::default();
+        let (owner, other) = (Uuid::new_v4(), Uuid::new_v4());
+        deleting.mark(owner);
+        assert!(reject_writes(&deleting, &Method::GET, "/breadcrumbs", owner).is_ok());
+        assert!(reject_writes(&deleting, &Method::DELETE, &format!("/tenants/{}", owner), owner).is_ok());
+        assert!(reject_writes(&deleting, &Method::POST, "/breadcrumbs", other).is_ok());
+        let (status, message) = reject_writes(&deleting, &Method::POST, "/breadcrumbs", owner).unwrap_err();
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(message, format!("tenant {} is being deleted; writes are rejected", owner));
+    }
+
+    #[test]
+    fn test_refresh_keeps_runs_in_progress() {
+        let deleting = DeletingTenants::default();
+        let (running, finished, elsewhere) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        assert!(deleting.start(running));
+        assert!(!deleting.start(running));
+        deleting.mark(running);
+        deleting.mark(finished);
+        deleting.replace(vec![elsewhere]);
+        assert!(deleting.contains(running) && deleting.contains(elsewhere));
+        assert!(!deleting.contains(finished));
+        deleting.finish(running);
+        assert!(deleting.start(running));
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_deleting_a_seeded_tenant_leaves_nothing_but_the_export(pool: sqlx::PgPool) {
+        use crate::auth::{AuthConfig, AuthMode};
+        use crate::test_support::{crumb, request, send, state};
+        use rcrt_core::db::Db;
+        use rcrt_core::models::{BreadcrumbCreate, DeliveryChannel, Selector};
+
+        let (owner_id, agent_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let db = Db { pool };
+        db.ensure_tenant(owner_id, "Doomed").await.unwrap();
+        db.upsert_agent(owner_id, agent_id, vec!["curator".into()]).await.unwrap();
+        let bc = db.create_breadcrumb_for(owner_id, Some(agent_id), Some(agent_id), BreadcrumbCreate {
+            title: "Last words".into(), context: json!({ "note": "goodbye" }), ..crumb("note.v1", &["session:s1"])
+        }).await.unwrap();
+        db.update_breadcrumb(owner_id, agent_id, bc.id, Some(bc.version), rcrt_core::models::BreadcrumbUpdate {
+            title: None, description: None, semantic_version: None, context: Some(json!({ "note": "goodbye again" })), tags: None,
+            schema_name: None, llm_hints: None, visibility: None, sensitivity: None, ttl: None, ttl_type: None, ttl_config: None, ttl_source: None,
+        }).await.unwrap();
+        let selector = Selector { any_tags: Some(vec!["session:s1".into()]), all_tags: None, none_tags: None, schema_name: None, context_match: None };
+        db.create_selector_subscription(owner_id, agent_id, selector, &DeliveryChannel::all(), None, None).await.unwrap();
+        db.create_secret(owner_id, "api-token", "global", None, b"blob", b"dek", "local").await.unwrap();
+        db.create_owner_webhook(owner_id, "http://127.0.0.1:1/hook", None, None, &[], &[], false).await.unwrap();
+        db.create_api_key(owner_id, agent_id, Some("ci"), "rcrt_00000000", "hash", &["curator".to_string()]).await.unwrap();
+
+        let auth = AuthConfig::new(AuthMode::Disabled { owner_id, agent_id }, None, None, None, None).unwrap();
+        let state = state(db.clone(), auth).await;
+        let app = crate::build_app(state.clone());
+
+        let (status, dry) = send(&app, request("DELETE", &format!("/tenants/{}?dry_run=true", owner_id), None, None)).await;
+        assert_eq!(status, StatusCode::OK, "{}", dry);
+        for (table, n) in [("agents", 1), ("breadcrumbs", 1), ("breadcrumb_history", 2), ("selector_subscriptions", 1), ("secrets", 1), ("owner_webhooks", 1), ("api_keys", 1)] {
+            assert_eq!(dry["counts"][table], json!(n), "{}", table);
+        }
+        assert!(db.get_tenant(owner_id).await.unwrap().is_some());
+        for confirm in ["", "&confirm=doomed"] {
+            let (status, _) = send(&app, request("DELETE", &format!("/tenants/{}?dry_run=false{}", owner_id, confirm), None, None)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
+
+        let (status, started) = send(&app, request("DELETE", &format!("/tenants/{}?confirm=Doomed", owner_id), None, None)).await;
+        assert_eq!(status, StatusCode::ACCEPTED, "{}", started);
+        // From here the owner can't write
+        let (status, message) = send(&app, request("POST", "/breadcrumbs", None, Some(json!({ "title": "Too late", "context": {}, "tags": [] })))).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(message, json!(format!("tenant {} is being deleted; writes are rejected", owner_id)));
+
+        let mut deletion = json!(null);
+        for _ in 0..100 {
+            deletion = send(&app, request("GET", &format!("/tenants/{}/deletion", owner_id), None, None)).await.1;
+            if deletion["status"] != json!("exporting") && deletion["status"] != json!("deleting") { break; }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(deletion["status"], json!("completed"), "{}", deletion);
+        assert_eq!((deletion["deleted"]["breadcrumbs"].clone(), deletion["deleted"]["agents"].clone()), (json!(1), json!(1)));
+        assert!(db.get_tenant(owner_id).await.unwrap().is_none());
+        assert!(db.count_tenant_rows(owner_id).await.unwrap().iter().all(|(_, n)| *n == 0));
+
+        // The snapshot has everything that was deleted
+        let key = deletion["export_location"].as_str().unwrap();
+        let mut export = String::new();
+        state.attachment_store.open(key).await.unwrap().read_to_string(&mut export).await.unwrap();
+        let lines: Vec<serde_json::Value> = export.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!((lines[0]["format"].clone(), lines[0]["tenant"]["name"].clone()), (json!(EXPORT_FORMAT), json!("Doomed")));
+        let rows = |table: &str| lines.iter().filter(|l| l["table"] == json!(table)).map(|l| l["row"].clone()).collect::<Vec<_>>();
+        assert_eq!(rows("breadcrumbs")[0]["title"], json!("Last words"));
+        assert_eq!(rows("breadcrumb_history").len(), 2);
+        assert_eq!(rows("secrets")[0]["name"], json!("api-token"));
+
+        // Asking again reports the finished run
+        let (status, again) = send(&app, request("DELETE", &format!("/tenants/{}?confirm=Doomed", owner_id), None, None)).await;
+        assert_eq!((status, again["status"].clone()), (StatusCode::OK, json!("completed")));
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_failed_deletion_resumes_where_it_stopped(pool: sqlx::PgPool) {
+        use rcrt_core::db::Db;
+
+        let owner_id = Uuid::new_v4();
+        let db = Db { pool };
+        db.ensure_tenant(owner_id, "Flaky").await.unwrap();
+        db.upsert_agent(owner_id, Uuid::new_v4(), vec![]).await.unwrap();
+        // A run that exported, deleted part of the way and failed
+        db.begin_tenant_deletion(owner_id, None).await.unwrap().unwrap();
+        db.set_tenant_deletion_exported(owner_id, "tenant-exports/flaky.ndjson").await.unwrap();
+        db.set_tenant_deletion_stage(owner_id, "agents").await.unwrap();
+        db.fail_tenant_deletion(owner_id, "connection reset").await.unwrap();
+        assert!(db.list_deleting_tenants().await.unwrap().contains(&owner_id));
+
+        let resumed = db.begin_tenant_deletion(owner_id, Some("http://ignored.example/export")).await.unwrap().unwrap();
+        assert_eq!((resumed.status.as_str(), resumed.error.as_deref()), ("deleting", None));
+        assert_eq!((resumed.export_location.as_deref(), resumed.export_to.as_deref()), (Some("tenant-exports/flaky.ndjson"), None));
+
+        let state = crate::test_support::state(db.clone(), crate::auth::AuthConfig::new(
+            crate::auth::AuthMode::Disabled { owner_id, agent_id: Uuid::nil() }, None, None, None, None,
+        ).unwrap()).await;
+        // The export isn't written again: its location stays the one recorded
+        run(&state, owner_id).await.unwrap();
+        let done = db.get_tenant_deletion(owner_id).await.unwrap().unwrap();
+        assert_eq!((done.status.as_str(), done.export_location.as_deref()), ("completed", Some("tenant-exports/flaky.ndjson")));
+        assert_eq!(done.deleted.get("agents"), Some(&1));
+        assert!(db.get_tenant(owner_id).await.unwrap().is_none());
+        assert!(!db.list_deleting_tenants().await.unwrap().contains(&owner_id));
+    }
+}
@@ -0,0 +1,103 @@
+//! Tenant Handlers
+//! Tenant records; owners manage their own tenant, curators manage all and run deletions (tenant_deletion)
+
+use std::collections::BTreeMap;
+use axum::{extract::{Query, State}, http::StatusCode, Json};
+use rcrt_core::models::TenantDeletion;
+use rcrt_core::roles::Role;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{auth::AuthContext, db_errors::db_error, tenant_deletion, AppState};
+
+#[derive(Deserialize)]
+pub struct TenantReq { name: String }
+pub async fn ensure_tenant(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(tenant_id): axum::extract::Path<Uuid>, Json(req): Json<TenantReq>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if auth.owner_id != tenant_id && !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "forbidden".into())); }
+    state.db.ensure_tenant(tenant_id, &req.name).await.map_err(db_error)?;
+    Ok(Json(json!({"ok": true})))
+}
+
+pub async fn list_tenants(State(state): State<AppState>, auth: AuthContext) -> Result<Json<Vec<serde_json::Value>>, (StatusCode, String)> {
+    if !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "curator role required".into())); }
+    let tenants = state.db.list_tenants().await.map_err(db_error)?;
+    let out = tenants.into_iter().map(|(id, name, created_at)| {
+        json!({
+            "id": id,
+            "name": name,
+            "created_at": created_at
+        })
+    }).collect();
+    Ok(Json(out))
+}
+
+pub async fn get_tenant(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(tenant_id): axum::extract::Path<Uuid>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let tenant = state.db.get_tenant(tenant_id).await.map_err(db_error)?;
+    match tenant {
+        Some((id, name, created_at)) => Ok(Json(json!({
+            "id": id,
+            "name": name,
+            "created_at": created_at
+        }))),
+        None => Err((StatusCode::NOT_FOUND, "tenant not found".into()))
+    }
+}
+
+pub async fn update_tenant(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(tenant_id): axum::extract::Path<Uuid>, Json(req): Json<TenantReq>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if auth.owner_id != tenant_id && !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "forbidden".into())); }
+    state.db.update_tenant(tenant_id, &req.name).await.map_err(db_error)?;
+    Ok(Json(json!({"ok": true})))
+}
+
+#[derive(Deserialize)]
+pub struct DeleteTenantQuery {
+    /// The tenant's name, required unless this is a dry run
+    confirm: Option<String>,
+    #[serde(default)]
+    dry_run: bool,
+    /// URL to PUT the NDJSON snapshot to instead of the attachment store
+    export_to: Option<String>,
+}
+
+/// DELETE /tenants/:id?confirm=<name>: start (or resume) the staged deletion and return its status with
+/// 202; follow it at GET /tenants/:id/deletion. `dry_run=true` counts the rows per table it would remove
+pub async fn delete_tenant(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(tenant_id): axum::extract::Path<Uuid>, Query(q): Query<DeleteTenantQuery>) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, String)> {
+    if !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "curator role required".into())); }
+    let tenant = state.db.get_tenant(tenant_id).await.map_err(db_error)?;
+    let deletion = state.db.get_tenant_deletion(tenant_id).await.map_err(db_error)?;
+    let name = match (&tenant, &deletion) {
+        (Some((_, name, _)), _) => name.clone(),
+        (None, Some(d)) if d.status != "completed" => d.tenant_name.clone(),
+        (None, Some(d)) => return Ok((StatusCode::OK, Json(json!(d)))),
+        (None, None) => return Err((StatusCode::NOT_FOUND, "tenant not found".into())),
+    };
+    if q.dry_run {
+        let counts = state.db.count_tenant_rows(tenant_id).await.map_err(db_error)?;
+        let total: i64 = counts.iter().map(|(_, n)| n).sum();
+        return Ok((StatusCode::OK, Json(json!({
+            "dry_run": true,
+            "tenant_id": tenant_id,
+            "name": name,
+            "counts": counts.into_iter().collect::<BTreeMap<_, _>>(),
+            "total": total
+        }))));
+    }
+    if q.confirm.as_deref() != Some(name.as_str()) {
+        return Err((StatusCode::BAD_REQUEST, "confirm must be the tenant's name".into()));
+    }
+    let Some(deletion) = state.db.begin_tenant_deletion(tenant_id, q.export_to.as_deref()).await.map_err(db_error)? else {
+        return Err((StatusCode::NOT_FOUND, "tenant not found".into()));
+    };
+    tenant_deletion::spawn(state.clone(), tenant_id);
+    Ok((StatusCode::ACCEPTED, Json(json!(deletion))))
+}
+
+/// GET /tenants/:id/deletion: the tenant's deletion run, with rows deleted so far by table
+pub async fn get_tenant_deletion(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(tenant_id): axum::extract::Path<Uuid>) -> Result<Json<TenantDeletion>, (StatusCode, String)> {
+    if !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "curator role required".into())); }
+    match state.db.get_tenant_deletion(tenant_id).await.map_err(db_error)? {
+        Some(deletion) => Ok(Json(deletion)),
+        None => Err((StatusCode::NOT_FOUND, "no deletion for this tenant".into())),
+    }
+}
@@ -0,0 +1,80 @@
+//! Test Support
+//! Router-level test helpers: AppState built the way main builds it, and requests through tower's
+//! oneshot. Shared with tests/api.rs, which includes this file by path, so names go through `crate::`
+
+use axum::{body::Body, http::{header, Request, StatusCode}, Router};
+use rcrt_core::db::Db;
+#[cfg(feature = "db-tests")]
+use rcrt_core::models::BreadcrumbCreate;
+use serde_json::Value;
+use tower::ServiceExt;
+
+use crate::{auth::AuthConfig, AppState};
+
+/// The same AppState main builds, minus a live NATS server
+pub async fn state(db: Db, auth: AuthConfig) -> AppState {
+    #[cfg(feature = "nats")]
+    let state = {
+        let nats_conn = async_nats::ConnectOptions::new()
+            .retry_on_initial_connect()
+            .connect("nats://127.0.0.1:1")
+            .await
+            .unwrap();
+        AppState::new(db, auth, nats_conn, 120)
+    };
+    #[cfg(not(feature = "nats"))]
+    let state = AppState::new(db, auth, 120);
+    state.unwrap()
+}
+
+/// Pool that never connects; for paths that must not touch the database
+pub fn offline_db() -> Db {
+    Db { pool: sqlx::postgres::PgPoolOptions::new().connect_lazy("postgres://rcrt@127.0.0.1:1/rcrt").unwrap() }
+}
+
+/// A breadcrumb of `schema` with `tags`, titled after its schema, an empty context and nothing else
+/// set; tests override what they need with `BreadcrumbCreate { title, ..crumb(schema, tags) }`
+#[cfg(feature = "db-tests")]
+pub fn crumb(schema: &str, tags: &[&str]) -> BreadcrumbCreate {
+    BreadcrumbCreate {
+        title: schema.to_string(),
+        description: None,
+        semantic_version: None,
+        context: serde_json::json!({}),
+        tags: tags.iter().map(|t| t.to_string()).collect(),
+        schema_name: Some(schema.to_string()),
+        llm_hints: None,
+        visibility: None,
+        sensitivity: None,
+        ttl: None,
+        ttl_type: None,
+        ttl_config: None,
+        ttl_source: None,
+        entity_keywords: None,
+        entities: None,
+    }
+}
+
+/// A request with an optional bearer token and JSON body
+pub fn request(method: &str, uri: &str, token: Option<&str>, body: Option<Value>) -> Request<Body> {
+    let mut req = Request::builder().method(method).uri(uri);
+    if let Some(token) = token {
+        req = req.header(header::AUTHORIZATION, format!("Bearer {}", token));
+    }
+    match body {
+        Some(body) => req.header(header::CONTENT_TYPE, "application/json").body(Body::from(body.to_string())).unwrap(),
+        None => req.body(Body::empty()).unwrap(),
+    }
+}
+
+/// Status and body; a body that isn't JSON (a plain-text error) comes back as a JSON string
+pub async fn send(app: &Router, req: Request<Body>) -> (StatusCode, Value) {
+    let res = app.clone().oneshot(req).await.unwrap();
+    let status = res.status();
+    let bytes = axum::body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let body = serde_json::from_slice(&bytes).unwrap_or_else(|_| match bytes.is_empty() {
+        true => Value::Null,
+        false => Value::String(String::from_utf8_lossy(&bytes).into_owned()),
+    });
+    (status, body)
+}
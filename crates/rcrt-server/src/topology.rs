@@ -0,0 +1,205 @@
+//! Topology Snapshot
+//! GET /admin/topology/export and POST /admin/topology/import: an owner's agents, selector
+//! subscriptions and webhooks as one JSON document, for rebuilding or promoting an environment.
+//! Webhook secrets, API keys and breadcrumbs stay out of it
+
+use std::collections::{HashMap, HashSet};
+use axum::{extract::{Query, State}, http::StatusCode, response::{IntoResponse, Response}, Json};
+use rcrt_core::models::{Topology, TopologyAction, TopologyKind};
+use rcrt_core::roles::Role;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::auth::{require_admin, AuthContext};
+use crate::db_errors::db_error;
+use crate::payload_versions::PayloadVersion;
+use crate::transforms::TransformEngine;
+use crate::AppState;
+
+pub const TOPOLOGY_FORMAT: &str = "rcrt.topology.v1";
+
+pub async fn export_topology(State(state): State<AppState>, auth: AuthContext) -> Result<Json<Value>, (StatusCode, String)> {
+    require_admin(&state, &auth)?;
+    let topology = state.db.export_topology(auth.owner_id).await.map_err(db_error)?;
+    let mut doc = serde_json::to_value(&topology).map_err(crate::internal_error)?;
+    doc["format"] = json!(TOPOLOGY_FORMAT);
+    doc["exported_at"] = json!(chrono::Utc::now());
+    Ok(Json(doc))
+}
+
+#[derive(Deserialize)]
+pub struct ImportReq {
+    /// Checked when present, so a document from an incompatible release is refused
+    format: Option<String>,
+    #[serde(flatten)]
+    topology: Topology,
+}
+
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    /// Remove the owner's agents, selectors and webhooks the document doesn't list
+    #[serde(default)]
+    prune: bool,
+}
+
+/// One problem with one item of the document, e.g. `webhooks[2]`
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ItemError {
+    pub item: String,
+    pub message: String,
+}
+
+/// Everything wrong with the document, so one round trip fixes it all. Nothing is checked against the
+/// database here; a webhook's selector must be in the same document
+fn validate(topology: &Topology) -> Vec<ItemError> {
+    let mut errors = Vec::new();
+    let mut err = |item: String, message: String| errors.push(ItemError { item, message });
+
+    let mut agents = HashSet::new();
+    for (i, agent) in topology.agents.iter().enumerate() {
+        if !agents.insert(agent.id) {
+            err(format!("agents[{}]", i), format!("agent {} is listed twice", agent.id));
+        }
+        if let Err(e) = Role::parse_all(&agent.roles) {
+            err(format!("agents[{}]", i), e.to_string());
+        }
+    }
+
+    let mut selectors: HashMap<Uuid, Uuid> = HashMap::new();
+    for (i, sel) in topology.selectors.iter().enumerate() {
+        let item = format!("selectors[{}]", i);
+        if selectors.insert(sel.id, sel.agent_id).is_some() {
+            err(item.clone(), format!("selector {} is listed twice", sel.id));
+        }
+        if !agents.contains(&sel.agent_id) {
+            err(item.clone(), format!("agent {} is not in the document's agents", sel.agent_id));
+        }
+        if sel.channels.is_empty() {
+            err(item.clone(), "channels must name at least one of sse, webhook, nats, inbox".into());
+        }
+        if let Some(Err((_, message))) = sel.payload_version.map(PayloadVersion::requested) {
+            err(item.clone(), message);
+        }
+        if sel.expires_at.is_some_and(|at| at <= chrono::Utc::now()) {
+            err(item, "expires_at is in the past".into());
+        }
+    }
+
+    let mut hooks = HashSet::new();
+    for (i, hook) in topology.webhooks.iter().enumerate() {
+        let item = format!("webhooks[{}]", i);
+        if !hooks.insert((hook.agent_id, hook.url.as_str())) {
+            err(item.clone(), format!("{} is listed twice for agent {}", hook.url, hook.agent_id));
+        }
+        if !agents.contains(&hook.agent_id) {
+            err(item.clone(), format!("agent {} is not in the document's agents", hook.agent_id));
+        }
+        if !reqwest::Url::parse(&hook.url).is_ok_and(|u| matches!(u.scheme(), "http" | "https")) {
+            err(item.clone(), format!("url {:?} is not an http(s) URL", hook.url));
+        }
+        if let Some(Err(message)) = hook.payload_template.as_deref().map(TransformEngine::check_payload_template) {
+            err(item.clone(), message);
+        }
+        if let Some(Err((_, message))) = hook.payload_version.map(PayloadVersion::requested) {
+            err(item.clone(), message);
+        }
+        match hook.selector_id.map(|id| (id, selectors.get(&id))) {
+            Some((id, None)) => err(item, format!("selector {} is not in the document's selectors", id)),
+            Some((id, Some(owner))) if *owner != hook.agent_id => err(item, format!("selector {} belongs to agent {}, not {}", id, owner, hook.agent_id)),
+            _ => {}
+        }
+    }
+    errors
+}
+
+/// Idempotent: importing the same document again reports every item as skipped. 422 lists every
+/// invalid item before anything is written
+pub async fn import_topology(State(state): State<AppState>, auth: AuthContext, Query(q): Query<ImportQuery>, Json(mut req): Json<ImportReq>) -> Result<Json<Value>, Response> {
+    require_admin(&state, &auth).map_err(IntoResponse::into_response)?;
+    if let Some(format) = req.format.as_deref().filter(|f| *f != TOPOLOGY_FORMAT) {
+        return Err((StatusCode::BAD_REQUEST, format!("unsupported format {:?}, expected {:?}", format, TOPOLOGY_FORMAT)).into_response());
+    }
+    let errors = validate(&req.topology);
+    if !errors.is_empty() {
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, Json(json!({ "error": "invalid_topology", "errors": errors }))).into_response());
+    }
+    // Stored lowercase, so "Curator" and "curator" import as the same agent
+    for agent in &mut req.topology.agents {
+        if let Ok(roles) = Role::parse_all(&agent.roles) {
+            agent.roles = Role::names(&roles);
+        }
+    }
+
+    let imported = state.db.import_topology(auth.owner_id, &req.topology, q.prune, auth.agent_id).await.map_err(|e| db_error(e).into_response())?;
+    for item in &imported.items {
+        if item.kind == TopologyKind::Selector && item.action != TopologyAction::Skipped {
+            state.selector_cache.invalidate(item.id);
+        }
+    }
+    state.selector_index.invalidate(auth.owner_id);
+    for hashed in &imported.api_key_hashes {
+        state.api_keys.evict(hashed);
+    }
+
+    let count = |action| imported.items.iter().filter(|i| i.action == action).count();
+    let summary = json!({
+        "created": count(TopologyAction::Created),
+        "updated": count(TopologyAction::Updated),
+        "skipped": count(TopologyAction::Skipped),
+        "removed": count(TopologyAction::Removed),
+    });
+    tracing::info!("🗺️ Topology import by {} (prune: {}): {}", auth.agent_id, q.prune, summary);
+    Ok(Json(json!({ "ok": true, "prune": q.prune, "summary": summary, "items": imported.items })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rcrt_core::models::{DeliveryChannel, Selector, TopologyAgent, TopologySelector, TopologyWebhook};
+
+    fn selector(id: Uuid, agent_id: Uuid) -> TopologySelector {
+        TopologySelector {
+            id, agent_id,
+            selector: Selector { any_tags: Some(vec!["x".into()]), all_tags: None, none_tags: None, schema_name: None, context_match: None },
+            channels: DeliveryChannel::all(), payload_version: None, expires_at: None,
+        }
+    }
+
+    fn webhook(agent_id: Uuid, url: &str, selector_id: Option<Uuid>) -> TopologyWebhook {
+        TopologyWebhook { agent_id, url: url.into(), payload_template: None, payload_version: None, selector_id }
+    }
+
+    #[test]
+    fn test_validation_names_each_bad_item() {
+        let (a, b, stranger) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let (sa, sb) = (Uuid::new_v4(), Uuid::new_v4());
+        let good = Topology {
+            agents: vec![TopologyAgent { id: a, roles: vec!["subscriber".into()] }, TopologyAgent { id: b, roles: vec![] }],
+            selectors: vec![selector(sa, a), selector(sb, b)],
+            webhooks: vec![webhook(a, "https://hooks.example/a", Some(sa)), webhook(b, "http://hooks.example/b", None)],
+        };
+        assert!(validate(&good).is_empty());
+
+        let mut bad = good.clone();
+        bad.agents.push(TopologyAgent { id: a, roles: vec!["curator".into(), "root".into()] });
+        bad.selectors[1].agent_id = stranger;
+        bad.selectors[1].channels.clear();
+        bad.selectors.push(selector(Uuid::new_v4(), a));
+        bad.selectors[2].payload_version = Some(99);
+        bad.webhooks[0].selector_id = Some(sb);
+        bad.webhooks.push(webhook(a, "ftp://hooks.example", Some(Uuid::new_v4())));
+        bad.webhooks.push(webhook(a, "https://hooks.example/a", None));
+        bad.webhooks[3].payload_template = Some("{{#if}}".into());
+
+        let items: Vec<String> = validate(&bad).into_iter().map(|e| e.item).collect();
+        assert_eq!(items, vec![
+            "agents[2]", "agents[2]",
+            "selectors[1]", "selectors[1]",
+            "selectors[2]",
+            "webhooks[0]",
+            "webhooks[2]", "webhooks[2]",
+            "webhooks[3]", "webhooks[3]",
+        ]);
+    }
+}
@@ -42,10 +42,31 @@ pub struct LlmHints {
     pub include: Option<Vec<String>>,
     pub exclude: Option<Vec<String>>,
     pub mode: Option<TransformMode>,
+    /// Context paths to embed instead of the whole context (see rcrt_core::embedding_text)
+    pub embed_fields: Option<Vec<String>>,
 }
 
 pub struct TransformEngine {
     handlebars: Handlebars<'static>,
+    /// Webhook payload templates: values are JSON-string escaped instead of HTML escaped
+    payload_handlebars: Handlebars<'static>,
+    /// template.v1 skeletons: rendered strings are stored as-is, so nothing is escaped
+    skeleton_handlebars: Handlebars<'static>,
+}
+
+handlebars::handlebars_helper!(json_helper: |value: Json| value.to_string());
+
+/// Escape for interpolation inside a JSON string literal
+fn json_string_escape(raw: &str) -> String {
+    let quoted = serde_json::to_string(raw).unwrap_or_default();
+    quoted.get(1..quoted.len().saturating_sub(1)).unwrap_or_default().to_string()
+}
+
+/// `inputs.tool` for a string that is nothing but `{{inputs.tool}}` (surrounding spaces allowed)
+fn sole_placeholder(template: &str) -> Option<&str> {
+    let path = template.trim().strip_prefix("{{")?.strip_suffix("}}")?.trim();
+    let simple = !path.is_empty() && path.split('.').all(|key| !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-'));
+    simple.then_some(path)
 }
 
 /// Cache for schema definitions and their LLM hints
@@ -118,7 +139,55 @@ impl TransformEngine {
     pub fn new() -> Self {
         let mut handlebars = Handlebars::new();
         handlebars.set_strict_mode(false);
-        Self { handlebars }
+        let mut payload_handlebars = Handlebars::new();
+        payload_handlebars.set_strict_mode(false);
+        payload_handlebars.register_escape_fn(json_string_escape);
+        payload_handlebars.register_helper("json", Box::new(json_helper));
+        let mut skeleton_handlebars = Handlebars::new();
+        skeleton_handlebars.set_strict_mode(false);
+        skeleton_handlebars.register_escape_fn(handlebars::no_escape);
+        Self { handlebars, payload_handlebars, skeleton_handlebars }
+    }
+
+    /// Compile-check a webhook payload template at registration
+    pub fn check_payload_template(template: &str) -> Result<(), String> {
+        handlebars::Template::compile(template)
+            .map(|_| ())
+            .map_err(|e| format!("Template error: {}", e))
+    }
+
+    /// Render a webhook body from the event JSON. `{{title}}` is escaped for use inside a JSON
+    /// string and `{{{json context}}}` embeds a value as JSON; the result must parse as JSON
+    pub fn render_payload_template(&self, template: &str, event: &Value) -> Result<String, String> {
+        let rendered = self.payload_handlebars
+            .render_template(template, event)
+            .map_err(|e| format!("Template error: {}", e))?;
+        serde_json::from_str::<Value>(&rendered).map_err(|e| format!("Rendered payload is not JSON: {}", e))?;
+        Ok(rendered)
+    }
+
+    /// Render every string in a template.v1 context skeleton against `data`. A string that is
+    /// exactly one `{{path}}` placeholder takes the value itself, so numbers, arrays and objects
+    /// keep their type; anything else renders to a string
+    pub fn render_skeleton(&self, skeleton: &Value, data: &Value) -> Result<Value, String> {
+        match skeleton {
+            Value::String(template) => {
+                if let Some(path) = sole_placeholder(template) {
+                    let value = path.split('.').try_fold(data, |v, key| v.get(key));
+                    return Ok(value.cloned().unwrap_or(Value::Null));
+                }
+                self.skeleton_handlebars
+                    .render_template(template, data)
+                    .map(Value::String)
+                    .map_err(|e| format!("Template error: {}", e))
+            }
+            Value::Array(items) => items.iter().map(|v| self.render_skeleton(v, data)).collect::<Result<Vec<_>, _>>().map(Value::Array),
+            Value::Object(obj) => obj.iter()
+                .map(|(k, v)| self.render_skeleton(v, data).map(|v| (k.clone(), v)))
+                .collect::<Result<serde_json::Map<_, _>, _>>()
+                .map(Value::Object),
+            other => Ok(other.clone()),
+        }
     }
 
     /// Apply LLM hints to transform a context value
@@ -436,6 +505,31 @@ mod tests {
         assert_eq!(result["count"], json!(3));
     }
 
+    #[test]
+    fn test_payload_template_escapes_for_json() {
+        let engine = TransformEngine::new();
+        let event = json!({
+            "type": "breadcrumb.updated",
+            "breadcrumb_id": "8a5c1c5e-0000-0000-0000-000000000001",
+            "title": "Disk \"full\" on db-1",
+            "tags": ["alert", "prod"],
+            "context": {"host": "db-1", "used": 0.97}
+        });
+        let template = r#"{"text": "{{type}}: {{title}} [{{#each tags}}{{this}}{{#unless @last}}, {{/unless}}{{/each}}]", "details": {{{json context}}} }"#;
+        let rendered: Value = serde_json::from_str(&engine.render_payload_template(template, &event).unwrap()).unwrap();
+        assert_eq!(rendered["text"], json!("breadcrumb.updated: Disk \"full\" on db-1 [alert, prod]"));
+        assert_eq!(rendered["details"], event["context"]);
+    }
+
+    #[test]
+    fn test_payload_template_errors() {
+        let engine = TransformEngine::new();
+        assert!(TransformEngine::check_payload_template(r#"{"text": "{{title}"}"#).is_err());
+        assert!(TransformEngine::check_payload_template(r#"{"text": "{{title}}"}"#).is_ok());
+        let err = engine.render_payload_template("text={{title}}", &json!({"title": "x"})).unwrap_err();
+        assert!(err.starts_with("Rendered payload is not JSON"), "{}", err);
+    }
+
     #[test]
     fn test_format_transform() {
         let engine = TransformEngine::new();
@@ -459,4 +553,24 @@ mod tests {
         let result = engine.apply_llm_hints(&context, &hints).unwrap();
         assert_eq!(result["formatted"], json!("User (2025-11-03T00:44:43Z): Hello, world!"));
     }
+
+    #[test]
+    fn test_render_skeleton_placeholders() {
+        let engine = TransformEngine::new();
+        let skeleton = json!({
+            "tool": "{{inputs.tool}}",
+            "input": "{{ inputs.input }}",
+            "label": "Run <{{inputs.tool}}> x{{inputs.retries}}",
+            "meta": { "retries": "{{inputs.retries}}", "missing": "{{inputs.nope}}", "fixed": [1, true] }
+        });
+        let data = json!({ "inputs": { "tool": "search", "input": { "q": "rust" }, "retries": 3 } });
+        let rendered = engine.render_skeleton(&skeleton, &data).unwrap();
+        assert_eq!(rendered, json!({
+            "tool": "search",
+            "input": { "q": "rust" },
+            "label": "Run <search> x3",
+            "meta": { "retries": 3, "missing": null, "fixed": [1, true] }
+        }));
+        assert!(engine.render_skeleton(&json!("{{#if}}"), &data).is_err());
+    }
 }
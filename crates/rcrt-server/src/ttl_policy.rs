@@ -0,0 +1,848 @@
+//! TTL Policies
+//! Tenant-defined auto-TTL rules from ttl.policy.v1 breadcrumbs, the built-in fallbacks (whose durations a tenant's
+//! system.hygiene.config.v1 can change), and the implicit cleanup the hygiene runner applies
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use axum::http::StatusCode;
+use rcrt_core::db::Db;
+use rcrt_core::models::{BreadcrumbCreate, EmbeddingModelConfig, EMBEDDING_CONFIG_SCHEMA};
+use rcrt_core::roles::Role;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::sync::RwLock;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::auth::AuthContext;
+use crate::hygiene_config::{self, HygieneConfigs, TenantHygiene, HYGIENE_CONFIG};
+
+/// `{"match": {"schema_name": "<glob>", "tags": ["<glob>", ..]}, "ttl_type": "datetime", "duration": "6h", "priority": 10}`,
+/// or `"ttl_type": "keep_latest", "ttl_config": {"count": 50, "partition_by": "tag:session:"}` for a count cap
+pub const TTL_POLICY: &str = "ttl.policy.v1";
+
+const KEEP_LATEST: &str = "keep_latest";
+
+/// How long a tenant's policies are served from cache without a policy write on this instance
+const CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// One auto-TTL rule; `schema_name` and `tags` are globs (`*` any run, `?` one character)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TtlPolicy {
+    /// The defining breadcrumb; None for built-ins
+    pub id: Option<Uuid>,
+    pub name: String,
+    pub schema_name: Option<String>,
+    /// Every pattern must match at least one of the breadcrumb's tags
+    pub tags: Vec<String>,
+    pub ttl_type: String,
+    /// Seconds from creation; None for usage policies
+    pub duration_secs: Option<i64>,
+    pub ttl_config: Option<Value>,
+    /// Higher wins; ties go to the most recently updated policy
+    pub priority: i64,
+}
+
+/// `match.schema_name` and `match.tags`; at least one is required
+fn parse_match(context: &Value) -> Result<(Option<String>, Vec<String>), String> {
+    let matcher = context.get("match").and_then(|m| m.as_object()).ok_or("match must be an object")?;
+    let schema_name = match matcher.get("schema_name") {
+        None | Some(Value::Null) => None,
+        Some(Value::String(s)) if !s.is_empty() => Some(s.clone()),
+        Some(_) => return Err("match.schema_name must be a non-empty string".into()),
+    };
+    let tags = match matcher.get("tags") {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::Array(items)) => items.iter()
+            .map(|t| t.as_str().filter(|s| !s.is_empty()).map(String::from))
+            .collect::<Option<Vec<_>>>()
+            .ok_or("match.tags must be non-empty strings")?,
+        Some(_) => return Err("match.tags must be an array".into()),
+    };
+    if schema_name.is_none() && tags.is_empty() {
+        return Err("match needs schema_name or tags".into());
+    }
+    Ok((schema_name, tags))
+}
+
+fn is_keep_latest(context: &Value) -> bool {
+    context.get("ttl_type").and_then(|t| t.as_str()) == Some(KEEP_LATEST)
+}
+
+impl TtlPolicy {
+    /// Validate a ttl.policy.v1 context; the error is fit for a 422
+    pub fn from_context(id: Option<Uuid>, name: &str, context: &Value) -> Result<Self, String> {
+        let (schema_name, tags) = parse_match(context)?;
+
+        let ttl_type = match context.get("ttl_type") {
+            None | Some(Value::Null) => "datetime".to_string(),
+            Some(Value::String(t)) if matches!(t.as_str(), "datetime" | "usage" | "hybrid") => t.clone(),
+            Some(_) => return Err("ttl_type must be datetime, usage, hybrid or keep_latest".into()),
+        };
+        let ttl_config = match context.get("ttl_config") {
+            None | Some(Value::Null) => None,
+            Some(v @ Value::Object(_)) => Some(v.clone()),
+            Some(_) => return Err("ttl_config must be an object".into()),
+        };
+
+        let duration = context.get("duration").or_else(|| ttl_config.as_ref().and_then(|c| c.get("duration")));
+        let duration_secs = match duration {
+            None | Some(Value::Null) => None,
+            Some(v) => Some(parse_duration(v)?),
+        };
+        if ttl_type != "usage" && duration_secs.is_none() {
+            return Err(format!("{} policies need a duration", ttl_type));
+        }
+        if let Some(max_reads) = ttl_config.as_ref().and_then(|c| c.get("max_reads")) {
+            if !matches!(max_reads.as_i64(), Some(n) if n > 0) {
+                return Err("ttl_config.max_reads must be a positive integer".into());
+            }
+        } else if ttl_type == "usage" {
+            return Err("usage policies need ttl_config.max_reads".into());
+        }
+
+        let priority = match context.get("priority") {
+            None | Some(Value::Null) => 0,
+            Some(v) => v.as_i64().ok_or("priority must be an integer")?,
+        };
+
+        Ok(TtlPolicy { id, name: name.to_string(), schema_name, tags, ttl_type, duration_secs, ttl_config, priority })
+    }
+
+    pub fn matches(&self, schema_name: Option<&str>, tags: &[String]) -> bool {
+        if let Some(pattern) = &self.schema_name {
+            if !schema_name.is_some_and(|s| glob_match(pattern, s)) {
+                return false;
+            }
+        }
+        self.tags.iter().all(|pattern| tags.iter().any(|t| glob_match(pattern, t)))
+    }
+
+    /// Fill in the TTL fields of a create that didn't set its own
+    pub fn apply(&self, create_req: &mut BreadcrumbCreate) {
+        create_req.ttl = self.duration_secs.map(|s| chrono::Utc::now() + chrono::Duration::seconds(s));
+        create_req.ttl_type = Some(self.ttl_type.clone());
+        create_req.ttl_config = self.ttl_config.clone();
+        create_req.ttl_source = Some("auto-applied".to_string());
+    }
+}
+
+/// Which breadcrumbs a keep_latest policy counts together
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Partition {
+    /// All of the tenant's matching breadcrumbs
+    Owner,
+    /// Per creating agent; breadcrumbs without a creator are left alone
+    CreatedBy,
+    /// Per value of the first tag (in sort order) starting with the prefix, e.g. `session:`;
+    /// breadcrumbs without such a tag are left alone
+    TagPrefix(String),
+}
+
+impl Partition {
+    /// `"owner"`, `"created_by"` or `"tag:<prefix>"`
+    fn parse(value: Option<&Value>) -> Result<Self, String> {
+        match value {
+            None | Some(Value::Null) => Ok(Partition::Owner),
+            Some(Value::String(s)) if s == "owner" => Ok(Partition::Owner),
+            Some(Value::String(s)) if s == "created_by" => Ok(Partition::CreatedBy),
+            Some(Value::String(s)) if s.len() > 4 && s.starts_with("tag:") => Ok(Partition::TagPrefix(s[4..].to_string())),
+            Some(_) => Err("ttl_config.partition_by must be owner, created_by or tag:<prefix>".into()),
+        }
+    }
+
+    /// (mode, tag prefix) as the keep_latest query takes them
+    fn sql_args(&self) -> (&'static str, Option<&str>) {
+        match self {
+            Partition::Owner => ("owner", None),
+            Partition::CreatedBy => ("created_by", None),
+            Partition::TagPrefix(prefix) => ("tag", Some(prefix)),
+        }
+    }
+}
+
+/// Keeps the newest `count` matching breadcrumbs per partition; the hygiene runner deletes the rest
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KeepLatestPolicy {
+    pub id: Uuid,
+    pub name: String,
+    pub schema_name: Option<String>,
+    pub tags: Vec<String>,
+    pub partition_by: Partition,
+    pub count: i64,
+}
+
+impl KeepLatestPolicy {
+    /// Validate a `"ttl_type": "keep_latest"` context; the error is fit for a 422
+    pub fn from_context(id: Uuid, name: &str, context: &Value) -> Result<Self, String> {
+        let (schema_name, tags) = parse_match(context)?;
+        let config = context.get("ttl_config").and_then(|c| c.as_object()).ok_or("keep_latest policies need a ttl_config object")?;
+        let count = match config.get("count").and_then(|c| c.as_i64()) {
+            Some(n) if n > 0 => n,
+            _ => return Err("ttl_config.count must be a positive integer".into()),
+        };
+        let partition_by = Partition::parse(config.get("partition_by"))?;
+        Ok(KeepLatestPolicy { id, name: name.to_string(), schema_name, tags, partition_by, count })
+    }
+}
+
+/// ttl.policy.v1 and system.hygiene.config.v1: both decide when the tenant's breadcrumbs are deleted,
+/// and writing either drops the cached policies
+pub fn is_policy_schema(schema_name: &str) -> bool {
+    schema_name == TTL_POLICY || schema_name == HYGIENE_CONFIG
+}
+
+/// Gate for writes that leave a breadcrumb of a policy schema or system.embedding.config.v1 with
+/// `context`, so only curators may set one; any other schema passes
+pub fn check_write(auth: &AuthContext, schema_name: &str, context: &Value) -> Result<(), (StatusCode, String)> {
+    if !is_policy_schema(schema_name) && schema_name != EMBEDDING_CONFIG_SCHEMA {
+        return Ok(());
+    }
+    if !auth.has_role(Role::Curator) {
+        return Err((StatusCode::FORBIDDEN, format!("{} requires the curator role", schema_name)));
+    }
+    let valid = if schema_name == EMBEDDING_CONFIG_SCHEMA {
+        EmbeddingModelConfig::from_context(context).map(|_| ())
+    } else if schema_name == HYGIENE_CONFIG {
+        TenantHygiene::from_context(hygiene_config::env_defaults(), None, context).map(|_| ())
+    } else if is_keep_latest(context) {
+        KeepLatestPolicy::from_context(Uuid::nil(), "", context).map(|_| ())
+    } else {
+        TtlPolicy::from_context(None, "", context).map(|_| ())
+    };
+    valid.map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, format!("invalid {}: {}", schema_name, e)))
+}
+
+/// `"30s"`, `"5m"`, `"6h"`, `"7d"` or a number of seconds; must be positive
+fn parse_duration(value: &Value) -> Result<i64, String> {
+    let secs = match value {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => {
+            let s = s.trim();
+            let unit = match s.chars().last() {
+                Some('s') => Some(1),
+                Some('m') => Some(60),
+                Some('h') => Some(3600),
+                Some('d') => Some(86400),
+                _ => None,
+            };
+            match unit {
+                Some(unit) => s[..s.len() - 1].parse::<i64>().ok().and_then(|n| n.checked_mul(unit)),
+                None => s.parse::<i64>().ok(),
+            }
+        }
+        _ => None,
+    };
+    match secs {
+        Some(n) if n > 0 => Ok(n),
+        Some(_) => Err("duration must be positive".into()),
+        None => Err("duration must be seconds or a string like 30s, 5m, 6h or 7d".into()),
+    }
+}
+
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let (p, t): (Vec<char>, Vec<char>) = (pattern.chars().collect(), text.chars().collect());
+    let (mut pi, mut ti) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            backtrack = Some((pi, ti));
+            pi += 1;
+        } else if let Some((star, matched)) = backtrack {
+            pi = star + 1;
+            ti = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|c| *c == '*')
+}
+
+/// The same glob as a LIKE pattern for the hygiene query
+fn glob_to_like(pattern: &str) -> String {
+    let mut like = String::with_capacity(pattern.len());
+    for c in pattern.chars() {
+        match c {
+            '*' => like.push('%'),
+            '?' => like.push('_'),
+            '%' | '_' | '\\' => {
+                like.push('\\');
+                like.push(c);
+            }
+            _ => like.push(c),
+        }
+    }
+    like
+}
+
+/// What apply_auto_ttl did before policies were data, with the env durations; consulted only when no tenant policy matches
+pub fn builtin_policies() -> Vec<TtlPolicy> {
+    builtin_policies_for(hygiene_config::env_defaults())
+}
+
+/// The built-ins with a tenant's health check, temporary state and log durations
+pub fn builtin_policies_for(hygiene: &TenantHygiene) -> Vec<TtlPolicy> {
+    let builtin = |name: &str, schema_name: &str, tags: &[&str], duration_secs: i64| TtlPolicy {
+        id: None,
+        name: name.to_string(),
+        schema_name: Some(schema_name.to_string()),
+        tags: tags.iter().map(|t| t.to_string()).collect(),
+        ttl_type: "datetime".to_string(),
+        duration_secs: Some(duration_secs),
+        ttl_config: None,
+        priority: 0,
+    };
+    vec![
+        builtin("health checks", "tool.request.v1", &["health:check"], hygiene.healthcheck_ttl_minutes * 60),
+        builtin("system pings", "system.ping.v1", &[], 10 * 60),
+        builtin("temporary agent state", "*agent.temp*", &[], hygiene.temp_data_ttl_hours * 3600),
+        builtin("agent thinking", "agent.thinking.v1", &[], 6 * 3600),
+        builtin("agent analysis", "agent.analysis.v1", &[], 6 * 3600),
+        builtin("tool response logs", "tool.response.v1", &["*log:*"], hygiene.log_retention_days * 86400),
+        builtin("tool error logs", "tool.error.v1", &["*log:*"], hygiene.log_retention_days * 86400),
+        builtin("metrics", "*metrics.v1*", &[], 30 * 86400),
+    ]
+}
+
+/// A tenant's ttl.policy.v1 breadcrumbs, highest priority first, followed by the built-ins as its
+/// system.hygiene.config.v1 sets them; keep_latest policies are held apart since they never set a TTL
+#[derive(Debug, Clone, Default)]
+pub struct TtlPolicies {
+    tenants: HashMap<Uuid, Vec<TtlPolicy>>,
+    keep_latest: HashMap<Uuid, Vec<KeepLatestPolicy>>,
+    pub hygiene: HygieneConfigs,
+}
+
+impl TtlPolicies {
+    /// `rows` are (owner_id, id, title, context), newest first; invalid policies are skipped
+    pub fn from_breadcrumbs(rows: &[(Uuid, Uuid, String, Value)]) -> Self {
+        let mut policies = TtlPolicies::default();
+        for (owner_id, id, title, context) in rows {
+            let parsed = if is_keep_latest(context) {
+                KeepLatestPolicy::from_context(*id, title, context).map(|p| policies.keep_latest.entry(*owner_id).or_default().push(p))
+            } else {
+                TtlPolicy::from_context(Some(*id), title, context).map(|p| policies.tenants.entry(*owner_id).or_default().push(p))
+            };
+            if let Err(e) = parsed {
+                tracing::warn!("Ignoring invalid {} breadcrumb {}: {}", TTL_POLICY, id, e);
+            }
+        }
+        // Stable, so equal priorities keep newest first
+        for list in policies.tenants.values_mut() {
+            list.sort_by_key(|p| std::cmp::Reverse(p.priority));
+        }
+        policies
+    }
+
+    pub async fn load(db: &Db, owner_id: Option<Uuid>) -> Result<Self, sqlx::Error> {
+        // Raw pool like the hygiene runner: the runner needs every tenant's policies
+        let rows = sqlx::query_as::<_, (Uuid, Uuid, String, Value)>(
+            r#"SELECT owner_id, id, title, context FROM breadcrumbs
+               WHERE schema_name = $1 AND ($2::uuid IS NULL OR owner_id = $2)
+               ORDER BY updated_at DESC"#
+        )
+        .bind(TTL_POLICY)
+        .bind(owner_id)
+        .fetch_all(&db.pool)
+        .await?;
+        let mut policies = Self::from_breadcrumbs(&rows);
+        policies.hygiene = HygieneConfigs::load(db, owner_id).await?;
+        Ok(policies)
+    }
+
+    /// The tenant's policies in evaluation order, built-ins last
+    pub fn for_owner(&self, owner_id: Uuid) -> Vec<TtlPolicy> {
+        let mut policies = self.tenants.get(&owner_id).cloned().unwrap_or_default();
+        policies.extend(builtin_policies_for(self.hygiene.for_owner(owner_id)));
+        policies
+    }
+
+    /// (owner_id, policy) for every tenant's keep_latest policies, owners in a stable order; tenants
+    /// that turned keep_latest off are left out
+    pub fn keep_latest(&self) -> Vec<(Uuid, &KeepLatestPolicy)> {
+        let mut owners: Vec<&Uuid> = self.keep_latest.keys().filter(|o| self.hygiene.for_owner(**o).policies.keep_latest).collect();
+        owners.sort();
+        owners.into_iter().flat_map(|o| self.keep_latest[o].iter().map(move |p| (*o, p))).collect()
+    }
+
+    /// Tenant rules before the shared built-ins, so the first match in SQL is the one
+    /// apply_auto_ttl would have picked; `only` limits the tenant rules to one owner
+    fn rules(&self, only: Option<Uuid>) -> Vec<ImplicitRule> {
+        let mut owners: Vec<Uuid> = self.tenants.keys().chain(self.hygiene.overridden()).copied()
+            .filter(|o| only.is_none() || only == Some(*o))
+            .collect();
+        owners.sort();
+        owners.dedup();
+
+        let rule = |owner_id: Option<Uuid>, policy: &TtlPolicy| ImplicitRule {
+            ord: 0,
+            owner_id,
+            schema_like: policy.schema_name.as_deref().map(glob_to_like),
+            tag_likes: policy.tags.iter().map(|t| glob_to_like(t)).collect(),
+            // Usage policies never expire by age
+            max_age_secs: policy.duration_secs.filter(|_| policy.ttl_type != "usage"),
+        };
+        let mut rules = Vec::new();
+        for owner_id in owners {
+            let hygiene = self.hygiene.for_owner(owner_id);
+            if !hygiene.policies.ttl_policies {
+                // Matches everything and never expires, so nothing below reaches the tenant
+                rules.push(ImplicitRule { ord: 0, owner_id: Some(owner_id), schema_like: None, tag_likes: vec![], max_age_secs: None });
+                continue;
+            }
+            rules.extend(self.tenants.get(&owner_id).into_iter().flatten().map(|p| rule(Some(owner_id), p)));
+            if hygiene.source.is_some() {
+                rules.extend(builtin_policies_for(hygiene).iter().map(|p| rule(Some(owner_id), p)));
+            }
+        }
+        rules.extend(builtin_policies().iter().map(|p| rule(None, p)));
+        for (ord, rule) in rules.iter_mut().enumerate() {
+            rule.ord = ord as i32;
+        }
+        rules
+    }
+}
+
+/// One row of the implicit cleanup's rule table; the first matching rule (lowest `ord`) applies
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct ImplicitRule {
+    ord: i32,
+    owner_id: Option<Uuid>,
+    schema_like: Option<String>,
+    tag_likes: Vec<String>,
+    max_age_secs: Option<i64>,
+}
+
+/// Per-tenant policies for breadcrumb creation; writes to ttl.policy.v1 invalidate it
+pub struct TtlPolicyCache {
+    db: Db,
+    tenants: RwLock<HashMap<Uuid, (Arc<Vec<TtlPolicy>>, Instant)>>,
+}
+
+impl TtlPolicyCache {
+    pub fn new(db: Db) -> Self {
+        Self { db, tenants: RwLock::new(HashMap::new()) }
+    }
+
+    /// Falls back to the built-ins alone if the tenant's policies can't be read
+    pub async fn policies(&self, owner_id: Uuid) -> Arc<Vec<TtlPolicy>> {
+        {
+            let cache = self.tenants.read().await;
+            if let Some((policies, at)) = cache.get(&owner_id) {
+                if at.elapsed() < CACHE_TTL {
+                    return policies.clone();
+                }
+            }
+        }
+
+        let policies = match TtlPolicies::load(&self.db, Some(owner_id)).await {
+            Ok(loaded) => Arc::new(loaded.for_owner(owner_id)),
+            Err(e) => {
+                // Don't cache failures; the next create retries
+                tracing::warn!("Failed to load TTL policies for {}: {}", owner_id, e);
+                return Arc::new(builtin_policies());
+            }
+        };
+        self.tenants.write().await.insert(owner_id, (policies.clone(), Instant::now()));
+        policies
+    }
+
+    /// Drop cached policies after a ttl.policy.v1 write
+    pub async fn invalidate(&self) {
+        self.tenants.write().await.clear();
+    }
+}
+
+async fn delete_implicit_batch(db: &Db, rules: &Value, owner_id: Option<Uuid>, limit: i64) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        r#"WITH rules AS (
+               SELECT * FROM jsonb_to_recordset($1::jsonb)
+               AS r(ord int, owner_id uuid, schema_like text, tag_likes text[], max_age_secs bigint)
+           ),
+           doomed AS (
+               SELECT b.id
+               FROM breadcrumbs b
+               CROSS JOIN LATERAL (
+                   SELECT r.max_age_secs FROM rules r
+                   WHERE (r.owner_id IS NULL OR r.owner_id = b.owner_id)
+                   AND (r.schema_like IS NULL OR b.schema_name LIKE r.schema_like)
+                   AND NOT EXISTS (
+                       SELECT 1 FROM unnest(r.tag_likes) AS p(pattern)
+                       WHERE NOT EXISTS (SELECT 1 FROM unnest(b.tags) AS t(tag) WHERE t.tag LIKE p.pattern)
+                   )
+                   ORDER BY r.ord
+                   LIMIT 1
+               ) p
+               WHERE b.ttl IS NULL
+               AND ($3::uuid IS NULL OR b.owner_id = $3)
+               AND b.ttl_type IS DISTINCT FROM 'usage'
+               AND p.max_age_secs IS NOT NULL
+               AND b.created_at < NOW() - p.max_age_secs * INTERVAL '1 second'
+               LIMIT $2
+           )
+           DELETE FROM breadcrumbs b
+           USING doomed d
+           WHERE b.id = d.id"#
+    )
+    .bind(rules)
+    .bind(limit)
+    .bind(owner_id)
+    .execute(&db.pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Delete breadcrumbs without an explicit TTL that are older than the first policy they match
+/// (created before the policy existed, or via paths that skip apply_auto_ttl), in batches of
+/// `batch_size` up to `max_per_run`
+pub async fn cleanup_implicit_ttl(db: &Db, batch_size: i64, max_per_run: i64) -> Result<u64, sqlx::Error> {
+    let policies = TtlPolicies::load(db, None).await?;
+    let total = delete_implicit(db, &policies, None, batch_size, max_per_run).await?;
+    if total > 0 {
+        info!("Cleaned up {} breadcrumbs past their TTL policy", total);
+    }
+    Ok(total)
+}
+
+/// cleanup_implicit_ttl for one tenant, with `max_per_run` its own share of the run
+pub async fn cleanup_implicit_ttl_for(db: &Db, policies: &TtlPolicies, owner_id: Uuid, batch_size: i64, max_per_run: i64) -> Result<u64, sqlx::Error> {
+    let total = delete_implicit(db, policies, Some(owner_id), batch_size, max_per_run).await?;
+    if total > 0 {
+        info!("Cleaned up {} breadcrumbs of {} past their TTL policy", total, owner_id);
+    }
+    Ok(total)
+}
+
+async fn delete_implicit(db: &Db, policies: &TtlPolicies, owner_id: Option<Uuid>, batch_size: i64, max_per_run: i64) -> Result<u64, sqlx::Error> {
+    let rules = json!(policies.rules(owner_id));
+    let mut total = 0u64;
+    while (total as i64) < max_per_run {
+        let limit = batch_size.min(max_per_run - total as i64);
+        let deleted = delete_implicit_batch(db, &rules, owner_id, limit).await?;
+        total += deleted;
+        if (deleted as i64) < limit {
+            break;
+        }
+    }
+    Ok(total)
+}
+
+/// What one keep_latest policy removed
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KeepLatestDeletion {
+    pub policy_id: Uuid,
+    pub owner_id: Uuid,
+    pub name: String,
+    pub deleted: u64,
+}
+
+async fn delete_keep_latest_batch(db: &Db, owner_id: Uuid, policy: &KeepLatestPolicy, limit: i64) -> Result<u64, sqlx::Error> {
+    let (mode, prefix) = policy.partition_by.sql_args();
+    let tag_likes: Vec<String> = policy.tags.iter().map(|t| glob_to_like(t)).collect();
+    let result = sqlx::query(
+        r#"WITH candidates AS (
+               SELECT b.id, b.created_at, k.key
+               FROM breadcrumbs b
+               CROSS JOIN LATERAL (
+                   SELECT CASE $4
+                       WHEN 'owner' THEN ''
+                       WHEN 'created_by' THEN b.created_by::text
+                       ELSE (SELECT min(t.tag) FROM unnest(b.tags) AS t(tag) WHERE starts_with(t.tag, $5))
+                   END AS key
+               ) k
+               WHERE b.owner_id = $1
+               AND ($2::text IS NULL OR b.schema_name LIKE $2)
+               AND NOT EXISTS (
+                   SELECT 1 FROM unnest($3::text[]) AS p(pattern)
+                   WHERE NOT EXISTS (SELECT 1 FROM unnest(b.tags) AS t(tag) WHERE t.tag LIKE p.pattern)
+               )
+               AND k.key IS NOT NULL
+           ),
+           ranked AS (
+               SELECT id, row_number() OVER (PARTITION BY key ORDER BY created_at DESC, id DESC) AS rn
+               FROM candidates
+           ),
+           doomed AS (
+               SELECT id FROM ranked WHERE rn > $6 LIMIT $7
+           )
+           DELETE FROM breadcrumbs b
+           USING doomed d
+           WHERE b.id = d.id"#
+    )
+    .bind(owner_id)
+    .bind(policy.schema_name.as_deref().map(glob_to_like))
+    .bind(tag_likes)
+    .bind(mode)
+    .bind(prefix)
+    .bind(policy.count)
+    .bind(limit)
+    .execute(&db.pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Apply every tenant's keep_latest policies, each within its owner, in batches of `batch_size`
+/// up to `max_per_run` per policy; returns what each policy deleted
+pub async fn cleanup_keep_latest(db: &Db, batch_size: i64, max_per_run: i64) -> Result<Vec<KeepLatestDeletion>, sqlx::Error> {
+    let policies = TtlPolicies::load(db, None).await?;
+    apply_keep_latest(db, policies.keep_latest(), batch_size, max_per_run).await
+}
+
+/// cleanup_keep_latest for one tenant's policies out of an already loaded set
+pub async fn cleanup_keep_latest_for(db: &Db, policies: &TtlPolicies, owner_id: Uuid, batch_size: i64, max_per_run: i64) -> Result<Vec<KeepLatestDeletion>, sqlx::Error> {
+    let mine = policies.keep_latest().into_iter().filter(|(o, _)| *o == owner_id).collect();
+    apply_keep_latest(db, mine, batch_size, max_per_run).await
+}
+
+async fn apply_keep_latest(db: &Db, policies: Vec<(Uuid, &KeepLatestPolicy)>, batch_size: i64, max_per_run: i64) -> Result<Vec<KeepLatestDeletion>, sqlx::Error> {
+    let mut report = Vec::new();
+    for (owner_id, policy) in policies {
+        let mut total = 0u64;
+        while (total as i64) < max_per_run {
+            let limit = batch_size.min(max_per_run - total as i64);
+            let deleted = delete_keep_latest_batch(db, owner_id, policy, limit).await?;
+            total += deleted;
+            if (deleted as i64) < limit {
+                break;
+            }
+        }
+        if total > 0 {
+            info!("keep_latest policy {} ({}) deleted {} breadcrumbs", policy.name, policy.id, total);
+        }
+        report.push(KeepLatestDeletion { policy_id: policy.id, owner_id, name: policy.name.clone(), deleted: total });
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(owner_id: Uuid, title: &str, context: Value) -> (Uuid, Uuid, String, Value) {
+        (owner_id, Uuid::new_v4(), title.into(), context)
+    }
+
+    fn first_match<'a>(policies: &'a [TtlPolicy], schema: &str, tags: &[&str]) -> Option<&'a str> {
+        let tags: Vec<String> = tags.iter().map(|t| t.to_string()).collect();
+        policies.iter().find(|p| p.matches(Some(schema), &tags)).map(|p| p.name.as_str())
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("agent.*.v1", "agent.thinking.v1"));
+        assert!(glob_match("*metrics.v1*", "tool.metrics.v1"));
+        assert!(glob_match("log:?", "log:x"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("agent.*.v1", "agent.thinking.v2"));
+        assert!(!glob_match("log:?", "log:xy"));
+        assert_eq!(glob_to_like("tool_*.v1%"), "tool\\_%.v1\\%");
+    }
+
+    #[test]
+    fn test_policy_validation() {
+        let parse = |context: Value| TtlPolicy::from_context(None, "p", &context);
+        let ok = parse(json!({ "match": { "schema_name": "agent.*" }, "duration": "6h" })).unwrap();
+        assert_eq!((ok.ttl_type.as_str(), ok.duration_secs, ok.priority), ("datetime", Some(6 * 3600), 0));
+        assert_eq!(parse(json!({ "match": { "tags": ["x"] }, "duration": 90 })).unwrap().duration_secs, Some(90));
+        assert_eq!(parse(json!({ "match": { "tags": ["x"] }, "ttl_config": { "duration": "2d" } })).unwrap().duration_secs, Some(2 * 86400));
+        assert!(parse(json!({ "match": { "tags": ["x"] }, "ttl_type": "usage", "ttl_config": { "max_reads": 3 } })).is_ok());
+
+        assert!(parse(json!({ "duration": "5m" })).is_err());
+        assert!(parse(json!({ "match": {}, "duration": "5m" })).is_err());
+        assert!(parse(json!({ "match": { "tags": ["x"] }, "duration": "-5m" })).is_err());
+        assert!(parse(json!({ "match": { "tags": ["x"] }, "duration": -300 })).is_err());
+        assert!(parse(json!({ "match": { "tags": ["x"] }, "duration": "0s" })).is_err());
+        assert!(parse(json!({ "match": { "tags": ["x"] }, "duration": "soon" })).is_err());
+        assert!(parse(json!({ "match": { "tags": ["x"] } })).is_err());
+        assert!(parse(json!({ "match": { "tags": ["x"] }, "ttl_type": "usage" })).is_err());
+        assert!(parse(json!({ "match": { "tags": ["x"] }, "ttl_type": "usage", "ttl_config": { "max_reads": -1 } })).is_err());
+        assert!(parse(json!({ "match": { "tags": ["x"] }, "ttl_type": "forever", "duration": "5m" })).is_err());
+        assert!(parse(json!({ "match": { "tags": ["x"] }, "duration": "5m", "priority": "high" })).is_err());
+    }
+
+    #[test]
+    fn test_overlapping_policies_precedence() {
+        let (owner, other) = (Uuid::new_v4(), Uuid::new_v4());
+        // Newest first, as load() returns them
+        let policies = TtlPolicies::from_breadcrumbs(&[
+            row(owner, "newer agent-wide", json!({ "match": { "schema_name": "agent.*" }, "duration": "1h" })),
+            row(owner, "urgent thinking", json!({ "match": { "schema_name": "agent.thinking.v1", "tags": ["urgent"] }, "duration": "5m", "priority": 10 })),
+            row(owner, "older agent-wide", json!({ "match": { "schema_name": "agent.*" }, "duration": "2h" })),
+            row(owner, "broken", json!({ "match": { "schema_name": "agent.*" }, "duration": "-1h", "priority": 100 })),
+            row(other, "other tenant", json!({ "match": { "schema_name": "*" }, "duration": "1s", "priority": 100 })),
+        ]);
+        let mine = policies.for_owner(owner);
+
+        // Higher priority wins over a broader match; ties go to the newest; invalid ones never apply
+        assert_eq!(first_match(&mine, "agent.thinking.v1", &["urgent"]), Some("urgent thinking"));
+        assert_eq!(first_match(&mine, "agent.thinking.v1", &[]), Some("newer agent-wide"));
+        assert!(mine.iter().all(|p| p.name != "broken" && p.name != "other tenant"));
+        // No tenant policy matches, so the built-ins still apply
+        assert_eq!(first_match(&mine, "system.ping.v1", &[]), Some("system pings"));
+        assert_eq!(first_match(&mine, "tool.response.v1", &["log:execution"]), Some("tool response logs"));
+        assert_eq!(first_match(&mine, "note.v1", &[]), None);
+        // A tenant without policies gets exactly the built-ins
+        assert_eq!(policies.for_owner(Uuid::new_v4()), builtin_policies());
+    }
+
+    #[test]
+    fn test_rules_put_tenant_policies_before_builtins() {
+        let owner = Uuid::new_v4();
+        let policies = TtlPolicies::from_breadcrumbs(&[
+            row(owner, "usage", json!({ "match": { "schema_name": "system.ping.v1" }, "ttl_type": "usage", "ttl_config": { "max_reads": 1 } })),
+        ]);
+        let rules = policies.rules(None);
+        assert_eq!(rules.len(), 1 + builtin_policies().len());
+        assert_eq!(rules[0], ImplicitRule { ord: 0, owner_id: Some(owner), schema_like: Some("system.ping.v1".into()), tag_likes: vec![], max_age_secs: None });
+        assert!(rules[1..].iter().all(|r| r.owner_id.is_none() && r.max_age_secs.is_some()));
+    }
+
+    #[test]
+    fn test_hygiene_config_scopes_builtins_to_its_tenant() {
+        let (tuned, opted_out, other) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let mut policies = TtlPolicies::from_breadcrumbs(&[
+            row(opted_out, "pings", json!({ "match": { "schema_name": "system.ping.v1" }, "duration": "1h" })),
+        ]);
+        policies.hygiene = HygieneConfigs::from_breadcrumbs(hygiene_config::env_defaults().clone(), &[
+            (tuned, Uuid::new_v4(), json!({ "healthcheck_ttl_minutes": 60 })),
+            (opted_out, Uuid::new_v4(), json!({ "policies": { "ttl_policies": false } })),
+        ]);
+
+        let health = |policies: &[TtlPolicy]| policies.iter().find(|p| p.name == "health checks").unwrap().duration_secs;
+        assert_eq!(health(&policies.for_owner(tuned)), Some(3600));
+        assert_eq!(policies.for_owner(other), builtin_policies());
+
+        // The tuned tenant's built-ins come before the shared ones; the opted-out tenant gets one
+        // never-expiring catch-all in place of its policies and the built-ins
+        let rules = policies.rules(None);
+        let scoped = |owner: Uuid| rules.iter().filter(|r| r.owner_id == Some(owner)).collect::<Vec<_>>();
+        assert_eq!(scoped(tuned).len(), builtin_policies().len());
+        assert!(scoped(tuned).iter().any(|r| r.schema_like.as_deref() == Some("tool.request.v1") && r.max_age_secs == Some(3600)));
+        assert_eq!(scoped(opted_out), vec![&ImplicitRule { ord: scoped(opted_out)[0].ord, owner_id: Some(opted_out), schema_like: None, tag_likes: vec![], max_age_secs: None }]);
+        assert!(rules.iter().enumerate().all(|(i, r)| r.ord == i as i32));
+
+        let only_tuned = policies.rules(Some(tuned));
+        assert!(only_tuned.iter().all(|r| r.owner_id.is_none() || r.owner_id == Some(tuned)));
+        assert_eq!(only_tuned.len(), 2 * builtin_policies().len());
+    }
+
+    #[test]
+    fn test_keep_latest_policies_are_parsed_apart() {
+        let parse = |config: Value| KeepLatestPolicy::from_context(Uuid::nil(), "p", &json!({ "match": { "schema_name": "browser.*" }, "ttl_type": "keep_latest", "ttl_config": config }));
+        let policy = parse(json!({ "count": 50, "partition_by": "tag:session:" })).unwrap();
+        assert_eq!((policy.count, policy.partition_by), (50, Partition::TagPrefix("session:".into())));
+        assert_eq!(parse(json!({ "count": 5 })).unwrap().partition_by, Partition::Owner);
+        assert_eq!(parse(json!({ "count": 5, "partition_by": "created_by" })).unwrap().partition_by, Partition::CreatedBy);
+        assert!(parse(json!({ "count": 0 })).is_err());
+        assert!(parse(json!({ "partition_by": "owner" })).is_err());
+        assert!(parse(json!({ "count": 5, "partition_by": "tag:" })).is_err());
+        assert!(parse(json!({ "count": 5, "partition_by": "session" })).is_err());
+
+        // Never offered to creates, so they don't shadow a TTL policy or the built-ins
+        let owner = Uuid::new_v4();
+        let policies = TtlPolicies::from_breadcrumbs(&[
+            row(owner, "latest tabs", json!({ "match": { "schema_name": "*" }, "ttl_type": "keep_latest", "ttl_config": { "count": 50 }, "priority": 100 })),
+        ]);
+        assert_eq!(first_match(&policies.for_owner(owner), "system.ping.v1", &[]), Some("system pings"));
+        assert_eq!(policies.keep_latest().len(), 1);
+        assert_eq!(policies.keep_latest()[0].0, owner);
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_keep_latest_leaves_newest_per_session(pool: sqlx::PgPool) {
+        let db = Db { pool };
+        let (owner_id, other_owner, agent_id) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        for owner in [owner_id, other_owner] {
+            db.ensure_tenant(owner, "Keep Latest Test").await.unwrap();
+            db.upsert_agent(owner, agent_id, vec!["emitter".into(), "curator".into()]).await.unwrap();
+        }
+        let create = |title: String, schema: &str, context: Value, tags: Vec<String>| BreadcrumbCreate {
+            title, description: None, semantic_version: None, context, tags,
+            schema_name: Some(schema.into()), llm_hints: None, visibility: None, sensitivity: None,
+            ttl: None, ttl_type: None, ttl_config: None, ttl_source: None, entity_keywords: None, entities: None,
+        };
+
+        let policy = db.create_breadcrumb_for(owner_id, Some(agent_id), Some(agent_id), create(
+            "Latest tabs".into(), TTL_POLICY,
+            json!({ "match": { "schema_name": "browser.tab.context.v1" }, "ttl_type": "keep_latest", "ttl_config": { "count": 50, "partition_by": "tag:session:" } }),
+            vec![],
+        )).await.unwrap();
+        // 100 tabs in each of 3 sessions, `n` minutes old; the other tenant has the same tags but no policy
+        for (owner, sessions) in [(owner_id, 3), (other_owner, 1)] {
+            for session in 0..sessions {
+                for n in 0..100 {
+                    db.create_breadcrumb_for(owner, Some(agent_id), Some(agent_id), create(
+                        format!("Tab {}", n), "browser.tab.context.v1", json!({ "n": n }), vec![format!("session:{}", session)],
+                    )).await.unwrap();
+                }
+            }
+        }
+        sqlx::query("UPDATE breadcrumbs SET created_at = NOW() - (context->>'n')::int * INTERVAL '1 minute' WHERE schema_name = 'browser.tab.context.v1'")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        // Capped per run, then finished by the next
+        let first = cleanup_keep_latest(&db, 40, 100).await.unwrap();
+        assert_eq!(first, vec![KeepLatestDeletion { policy_id: policy.id, owner_id, name: "Latest tabs".into(), deleted: 100 }]);
+        assert_eq!(cleanup_keep_latest(&db, 40, 100).await.unwrap()[0].deleted, 50);
+        assert_eq!(cleanup_keep_latest(&db, 40, 100).await.unwrap()[0].deleted, 0);
+
+        let per_session: Vec<(Uuid, String, i64, i32)> = sqlx::query_as(
+            r#"SELECT owner_id, tags[1], COUNT(*), MAX((context->>'n')::int) FROM breadcrumbs
+               WHERE schema_name = 'browser.tab.context.v1' GROUP BY 1, 2 ORDER BY owner_id = $1 DESC, 2"#
+        )
+        .bind(owner_id)
+        .fetch_all(&db.pool)
+        .await
+        .unwrap();
+        assert_eq!(per_session, vec![
+            (owner_id, "session:0".into(), 50, 49),
+            (owner_id, "session:1".into(), 50, 49),
+            (owner_id, "session:2".into(), 50, 49),
+            (other_owner, "session:0".into(), 100, 99),
+        ]);
+    }
+
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_cleanup_follows_first_matching_policy(pool: sqlx::PgPool) {
+        let db = Db { pool };
+        let (owner_id, agent_id) = (Uuid::new_v4(), Uuid::new_v4());
+        db.ensure_tenant(owner_id, "TTL Policy Test").await.unwrap();
+        db.upsert_agent(owner_id, agent_id, vec!["emitter".into(), "curator".into()]).await.unwrap();
+        let create = |title: &str, schema: &str, context: Value, tags: Vec<String>| BreadcrumbCreate {
+            title: title.into(), description: None, semantic_version: None, context, tags,
+            schema_name: Some(schema.into()), llm_hints: None, visibility: None, sensitivity: None,
+            ttl: None, ttl_type: None, ttl_config: None, ttl_source: None, entity_keywords: None, entities: None,
+        };
+
+        db.create_breadcrumb_for(owner_id, Some(agent_id), Some(agent_id), create(
+            "Keep pings longer", TTL_POLICY, json!({ "match": { "schema_name": "system.ping.v1" }, "duration": "1d", "priority": 5 }), vec![],
+        )).await.unwrap();
+        let ping = db.create_breadcrumb_for(owner_id, Some(agent_id), Some(agent_id), create("Ping", "system.ping.v1", json!({}), vec![])).await.unwrap();
+        let thought = db.create_breadcrumb_for(owner_id, Some(agent_id), Some(agent_id), create("Thought", "agent.thinking.v1", json!({}), vec![])).await.unwrap();
+        let note = db.create_breadcrumb_for(owner_id, Some(agent_id), Some(agent_id), create("Note", "note.v1", json!({}), vec![])).await.unwrap();
+        sqlx::query("UPDATE breadcrumbs SET created_at = NOW() - INTERVAL '12 hours' WHERE id = ANY($1)")
+            .bind(vec![ping.id, thought.id, note.id])
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        // The tenant's 1d ping policy outranks the 10m built-in; thinking still falls back to 6h
+        assert_eq!(cleanup_implicit_ttl(&db, 1, 100).await.unwrap(), 1);
+        let remaining: Vec<Uuid> = sqlx::query_scalar("SELECT id FROM breadcrumbs WHERE id = ANY($1)")
+            .bind(vec![ping.id, thought.id, note.id])
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+        assert!(remaining.contains(&ping.id) && remaining.contains(&note.id) && !remaining.contains(&thought.id));
+    }
+}
@@ -0,0 +1,273 @@
+//! Version Diffs
+//! GET /breadcrumbs/:id/diff: the change between two history versions of a context as an RFC 6902
+//! JSON Patch, with a summary of added, removed and changed fields for people to read
+
+use axum::{extract::{Path, Query, State}, http::StatusCode, Json};
+use rcrt_core::models::{BreadcrumbFull, EncryptedContext};
+use rcrt_core::roles::Role;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::auth::AuthContext;
+use crate::db_errors::db_error;
+use crate::{envelope, AppState};
+
+/// Summary values longer than this (as JSON) are cut short
+const PREVIEW_CHARS: usize = 200;
+
+/// When a diff stops being worth computing in full
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub max_ops: usize,
+    /// Serialized size of the values the patch carries
+    pub max_bytes: usize,
+    /// Arrays longer than this on either side aren't diffed element by element
+    pub max_array_len: usize,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Summary {
+    /// `{"path", "value"}`
+    pub added: Vec<Value>,
+    /// `{"path", "old"}`
+    pub removed: Vec<Value>,
+    /// `{"path", "old", "new"}`
+    pub changed: Vec<Value>,
+    /// Arrays over the length limit that differ; the patch leaves them out
+    pub too_large: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Diff {
+    pub patch: Vec<Value>,
+    pub summary: Summary,
+    /// A limit was hit, so the patch covers only part of the change
+    pub truncated: bool,
+}
+
+/// The patch taking `from` to `to`; object keys are visited in sorted order, and trailing array
+/// elements are removed from the end so each index is valid when its operation applies
+pub fn diff(from: &Value, to: &Value, limits: Limits) -> Diff {
+    let mut differ = Differ { limits, bytes: 0, exhausted: false, out: Diff::default() };
+    differ.walk(String::new(), from, to);
+    differ.out.truncated = differ.exhausted || !differ.out.summary.too_large.is_empty();
+    differ.out
+}
+
+struct Differ {
+    limits: Limits,
+    bytes: usize,
+    exhausted: bool,
+    out: Diff,
+}
+
+impl Differ {
+    fn walk(&mut self, path: String, from: &Value, to: &Value) {
+        if self.exhausted || from == to {
+            return;
+        }
+        match (from, to) {
+            (Value::Object(a), Value::Object(b)) => {
+                let mut keys: Vec<&String> = a.keys().chain(b.keys()).collect();
+                keys.sort();
+                keys.dedup();
+                for key in keys {
+                    let child = format!("{}/{}", path, escape(key));
+                    match (a.get(key), b.get(key)) {
+                        (Some(x), Some(y)) => self.walk(child, x, y),
+                        (None, Some(y)) => self.add(child, y),
+                        (Some(x), None) => self.remove(child, x),
+                        (None, None) => {}
+                    }
+                }
+            }
+            (Value::Array(a), Value::Array(b)) => {
+                if a.len().max(b.len()) > self.limits.max_array_len {
+                    self.out.summary.too_large.push(path);
+                    return;
+                }
+                let common = a.len().min(b.len());
+                for i in 0..common {
+                    self.walk(format!("{}/{}", path, i), &a[i], &b[i]);
+                }
+                for (i, y) in b.iter().enumerate().skip(common) {
+                    self.add(format!("{}/{}", path, i), y);
+                }
+                for i in (common..a.len()).rev() {
+                    self.remove(format!("{}/{}", path, i), &a[i]);
+                }
+            }
+            _ => self.replace(path, from, to),
+        }
+    }
+
+    /// Whether the op still fits; once one doesn't, nothing more is added
+    fn fits(&mut self, size: usize) -> bool {
+        if self.exhausted || self.out.patch.len() >= self.limits.max_ops || self.bytes + size > self.limits.max_bytes {
+            self.exhausted = true;
+            return false;
+        }
+        self.bytes += size;
+        true
+    }
+
+    fn add(&mut self, path: String, value: &Value) {
+        if self.fits(path.len() + value.to_string().len()) {
+            self.out.patch.push(json!({ "op": "add", "path": path, "value": value }));
+            self.out.summary.added.push(json!({ "path": path, "value": preview(value) }));
+        }
+    }
+
+    fn remove(&mut self, path: String, old: &Value) {
+        if self.fits(path.len()) {
+            self.out.patch.push(json!({ "op": "remove", "path": path }));
+            self.out.summary.removed.push(json!({ "path": path, "old": preview(old) }));
+        }
+    }
+
+    fn replace(&mut self, path: String, old: &Value, new: &Value) {
+        if self.fits(path.len() + new.to_string().len()) {
+            self.out.patch.push(json!({ "op": "replace", "path": path, "value": new }));
+            self.out.summary.changed.push(json!({ "path": path, "old": preview(old), "new": preview(new) }));
+        }
+    }
+}
+
+/// JSON Pointer escaping (RFC 6901)
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// The value itself when short, else its JSON cut to PREVIEW_CHARS with an ellipsis
+fn preview(value: &Value) -> Value {
+    let text = value.to_string();
+    if text.chars().count() <= PREVIEW_CHARS {
+        return value.clone();
+    }
+    Value::String(format!("{}…", text.chars().take(PREVIEW_CHARS).collect::<String>()))
+}
+
+#[derive(Deserialize)]
+pub struct DiffQuery { from: Option<i32>, to: Option<i32> }
+
+/// `to` defaults to the current version and `from` to the one before it. With from > to the two
+/// are swapped, so the patch always goes from the older version to the newer, and `swapped` says so
+pub async fn get_breadcrumb_diff(State(state): State<AppState>, auth: AuthContext, Path(id): Path<Uuid>, Query(q): Query<DiffQuery>) -> Result<Json<Value>, (StatusCode, String)> {
+    let Some(full) = state.db.get_breadcrumb_full_for(auth.owner_id, Some(auth.agent_id), id, false).await.map_err(db_error)? else {
+        return Err((StatusCode::NOT_FOUND, "not found".into()));
+    };
+    let to = q.to.unwrap_or(full.version);
+    let from = q.from.unwrap_or(to - 1);
+    let swapped = from > to;
+    let (from, to) = if swapped { (to, from) } else { (from, to) };
+
+    let older = version_context(&state, &auth, &full, from).await?;
+    let newer = version_context(&state, &auth, &full, to).await?;
+    let diff = diff(&older, &newer, state.diff_limits);
+    Ok(Json(json!({
+        "id": id,
+        "from": from,
+        "to": to,
+        "swapped": swapped,
+        "patch": diff.patch,
+        "summary": diff.summary,
+        "truncated": diff.truncated,
+        "message": diff.truncated.then_some("diff too large: the patch covers only part of the change")
+    })))
+}
+
+/// A retained version's context, decrypted for the agents that may read it in full
+async fn version_context(state: &AppState, auth: &AuthContext, full: &BreadcrumbFull, version: i32) -> Result<Value, (StatusCode, String)> {
+    let missing = || (StatusCode::NOT_FOUND, format!("version {} of {} not found (current is {}; pruned versions can't be diffed)", version, full.id, full.version));
+    if version < 1 || version > full.version {
+        return Err(missing());
+    }
+    let Some(context) = state.db.get_breadcrumb_history_version(auth.owner_id, Some(auth.agent_id), full.id, version).await.map_err(db_error)? else {
+        return Err(missing());
+    };
+    if context != EncryptedContext::stub() {
+        return Ok(context);
+    }
+    let Some(sealed) = state.db.get_encrypted_context(auth.owner_id, Some(auth.agent_id), full.id, Some(version)).await.map_err(db_error)? else {
+        return Ok(context);
+    };
+    // Same rule as GET /breadcrumbs/:id/full
+    let may_decrypt = full.created_by == Some(auth.agent_id)
+        || auth.has_role(Role::Curator)
+        || state.db.has_acl_action(auth.owner_id, auth.agent_id, full.id, "read_full").await.map_err(db_error)?;
+    if !may_decrypt {
+        return Err((StatusCode::FORBIDDEN, "read_full required to decrypt this context".into()));
+    }
+    envelope::open_context(&envelope::local_kek()?, &sealed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: Limits = Limits { max_ops: 1000, max_bytes: 1 << 20, max_array_len: 100 };
+
+    /// Enough of RFC 6902 to check the patches this module emits
+    fn apply(mut doc: Value, patch: &[Value]) -> Value {
+        for op in patch {
+            let path = op["path"].as_str().unwrap();
+            let (parent, last) = path.rsplit_once('/').unwrap();
+            let key = last.replace("~1", "/").replace("~0", "~");
+            let target = doc.pointer_mut(parent).unwrap();
+            match (op["op"].as_str().unwrap(), target) {
+                ("add", Value::Array(items)) => items.insert(key.parse().unwrap(), op["value"].clone()),
+                ("add" | "replace", Value::Object(fields)) => { fields.insert(key, op["value"].clone()); }
+                ("replace", Value::Array(items)) => items[key.parse::<usize>().unwrap()] = op["value"].clone(),
+                ("remove", Value::Array(items)) => { items.remove(key.parse().unwrap()); }
+                ("remove", Value::Object(fields)) => { fields.remove(&key); }
+                (other, _) => panic!("unexpected op {}", other),
+            }
+        }
+        doc
+    }
+
+    #[test]
+    fn test_patch_takes_from_to_to() {
+        let from = json!({ "status": "draft", "a/b": 1, "steps": [1, 2, 3, 4], "meta": { "owner": "x", "gone": true } });
+        let to = json!({ "status": "done", "a/b": 1, "steps": [1, 5], "meta": { "owner": "x", "new": [1] }, "extra": null });
+        let changes = diff(&from, &to, LIMITS);
+        assert!(!changes.truncated);
+        assert_eq!(apply(from.clone(), &changes.patch), to);
+        assert_eq!(changes.summary.changed, vec![
+            json!({ "path": "/status", "old": "draft", "new": "done" }),
+            json!({ "path": "/steps/1", "old": 2, "new": 5 }),
+        ]);
+        assert_eq!(changes.summary.removed.iter().map(|r| r["path"].as_str().unwrap()).collect::<Vec<_>>(), vec!["/meta/gone", "/steps/3", "/steps/2"]);
+        assert_eq!(changes.summary.added.iter().map(|r| r["path"].as_str().unwrap()).collect::<Vec<_>>(), vec!["/extra", "/meta/new"]);
+
+        let grown = json!({ "steps": [1, 2, 3] });
+        assert_eq!(apply(json!({ "steps": [1] }), &diff(&json!({ "steps": [1] }), &grown, LIMITS).patch), grown);
+        let escaped = diff(&json!({}), &json!({ "a/b~c": 1 }), LIMITS);
+        assert_eq!(escaped.patch[0]["path"], "/a~1b~0c");
+        assert!(diff(&from, &from, LIMITS).patch.is_empty());
+    }
+
+    #[test]
+    fn test_limits_truncate_instead_of_growing() {
+        let long: Vec<i64> = (0..500).collect();
+        let capped = diff(&json!({ "rows": long, "title": "a" }), &json!({ "rows": [], "title": "b" }), LIMITS);
+        assert!(capped.truncated);
+        assert_eq!(capped.summary.too_large, vec!["/rows".to_string()]);
+        // The rest of the document is still diffed
+        assert_eq!(capped.patch, vec![json!({ "op": "replace", "path": "/title", "value": "b" })]);
+
+        let few_ops = diff(&json!({}), &json!({ "a": 1, "b": 2, "c": 3 }), Limits { max_ops: 2, ..LIMITS });
+        assert!(few_ops.truncated);
+        assert_eq!(few_ops.patch.len(), 2);
+
+        let big = "x".repeat(5000);
+        let few_bytes = diff(&json!({}), &json!({ "big": big, "small": 1 }), Limits { max_bytes: 1000, ..LIMITS });
+        assert!(few_bytes.truncated && few_bytes.patch.is_empty());
+
+        let previewed = diff(&json!({ "note": "" }), &json!({ "note": big }), LIMITS);
+        let shown = previewed.summary.changed[0]["new"].as_str().unwrap();
+        assert!(shown.ends_with('…') && shown.chars().count() == PREVIEW_CHARS + 1);
+        assert_eq!(previewed.patch[0]["value"].as_str().unwrap().len(), 5000);
+    }
+}
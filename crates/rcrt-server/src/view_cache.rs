@@ -0,0 +1,153 @@
+//! Context View Cache
+//! Optional in-process LRU of GET /breadcrumbs/:id context views after their llm_hints transform, so hot
+//! breadcrumbs (tool catalogs, agent definitions) skip the row fetch and the transform. Entries are keyed
+//! by breadcrumb id and version and only served for the version a cheap access-checked lookup returns,
+//! so an update made through another server instance is never served stale
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, OnceLock};
+use prometheus::{IntCounterVec, register_int_counter_vec};
+use rcrt_core::models::BreadcrumbContextView;
+use uuid::Uuid;
+
+static LOOKUPS: OnceLock<IntCounterVec> = OnceLock::new();
+
+fn lookups() -> &'static IntCounterVec {
+    LOOKUPS.get_or_init(|| register_int_counter_vec!("context_view_cache_total", "Context view cache lookups", &["result"]).unwrap())
+}
+
+struct Entry {
+    view: BreadcrumbContextView,
+    /// Position in `Lru::recency`
+    used: u64,
+}
+
+#[derive(Default)]
+struct Lru {
+    entries: HashMap<Uuid, Entry>,
+    /// Last use → id, oldest first
+    recency: BTreeMap<u64, Uuid>,
+    tick: u64,
+}
+
+impl Lru {
+    fn touch(&mut self, id: Uuid) {
+        self.tick += 1;
+        if let Some(entry) = self.entries.get_mut(&id) {
+            self.recency.remove(&entry.used);
+            entry.used = self.tick;
+            self.recency.insert(self.tick, id);
+        }
+    }
+
+    fn remove(&mut self, id: Uuid) {
+        if let Some(entry) = self.entries.remove(&id) {
+            self.recency.remove(&entry.used);
+        }
+    }
+}
+
+/// Transformed context views, at most `max_entries`, one version per breadcrumb
+pub struct ContextViewCache {
+    max_entries: usize,
+    lru: Mutex<Lru>,
+}
+
+impl ContextViewCache {
+    pub fn new(max_entries: usize) -> Self {
+        Self { max_entries: max_entries.max(1), lru: Mutex::new(Lru::default()) }
+    }
+
+    /// The cached view of `id` at `version`; counted as a hit or miss
+    pub fn get(&self, id: Uuid, version: i32) -> Option<BreadcrumbContextView> {
+        let found = self.lru.lock().ok().and_then(|mut lru| {
+            let view = lru.entries.get(&id).filter(|e| e.view.version == version).map(|e| e.view.clone())?;
+            lru.touch(id);
+            Some(view)
+        });
+        lookups().with_label_values(&[if found.is_some() { "hit" } else { "miss" }]).inc();
+        found
+    }
+
+    /// Cache a transformed view, replacing any other version of it and evicting the least recently used past the bound
+    pub fn insert(&self, view: BreadcrumbContextView) {
+        let Ok(mut lru) = self.lru.lock() else { return };
+        let id = view.id;
+        lru.remove(id);
+        while lru.entries.len() >= self.max_entries {
+            let Some((_, oldest)) = lru.recency.pop_first() else { break };
+            lru.entries.remove(&oldest);
+        }
+        lru.entries.insert(id, Entry { view, used: 0 });
+        lru.touch(id);
+    }
+
+    /// Drop a breadcrumb's view; called when it is updated or deleted in this process
+    pub fn invalidate(&self, id: Uuid) {
+        if let Ok(mut lru) = self.lru.lock() {
+            lru.remove(id);
+        }
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.lru.lock().map(|lru| lru.entries.len()).unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn view(id: Uuid, version: i32) -> BreadcrumbContextView {
+        BreadcrumbContextView {
+            id, title: "Tool catalog".into(), description: None, semantic_version: None, context: json!({ "v": version }),
+            tags: vec![], schema_name: None, llm_hints: None, version, updated_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_serves_only_the_cached_version() {
+        let cache = ContextViewCache::new(10);
+        let id = Uuid::new_v4();
+        assert!(cache.get(id, 1).is_none());
+        cache.insert(view(id, 1));
+        assert_eq!(cache.get(id, 1).unwrap().context, json!({ "v": 1 }));
+        assert!(cache.get(id, 2).is_none());
+        cache.insert(view(id, 2));
+        assert!(cache.get(id, 1).is_none());
+        assert_eq!(cache.get(id, 2).unwrap().context, json!({ "v": 2 }));
+        assert_eq!(cache.len(), 1);
+        cache.invalidate(id);
+        assert!(cache.get(id, 2).is_none());
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let cache = ContextViewCache::new(2);
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        cache.insert(view(a, 1));
+        cache.insert(view(b, 1));
+        // Reading a makes b the oldest
+        assert!(cache.get(a, 1).is_some());
+        cache.insert(view(c, 1));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(b, 1).is_none());
+        assert!(cache.get(a, 1).is_some() && cache.get(c, 1).is_some());
+    }
+
+    #[test]
+    fn test_lookups_are_counted() {
+        let cache = ContextViewCache::new(4);
+        let id = Uuid::new_v4();
+        let count = |result: &str| lookups().with_label_values(&[result]).get();
+        let (hits, misses) = (count("hit"), count("miss"));
+        cache.get(id, 1);
+        cache.insert(view(id, 1));
+        cache.get(id, 1);
+        // Other tests share the counters, so only check they moved
+        assert!(count("hit") > hits && count("miss") > misses);
+    }
+}
@@ -0,0 +1,1250 @@
+//! Webhooks
+//! Selector fanout to agent channels, inboxes and webhooks, owner webhooks, signed delivery with retries,
+//! per-breadcrumb ordering for strict webhooks, and the DLQ
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use axum::{extract::{Query, State}, http::StatusCode, Json};
+use chrono::{DateTime, SecondsFormat, Utc};
+use hmac::{Hmac, Mac};
+use prometheus::{IntCounter, IntCounterVec, HistogramVec, register_int_counter, register_int_counter_vec, register_histogram_vec};
+use rcrt_core::models::{AgentWebhook, Breadcrumb, DeliveryChannel, OwnerWebhook, SelectorSubscription, WebhookOrder, WebhookStatus};
+use rcrt_core::roles::Role;
+use reqwest::Client as HttpClient;
+use serde::Deserialize;
+use serde_json::json;
+use sha2::Sha256;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::{auth::AuthContext, db_errors::db_error, events, fanout_access, payload_versions::{self, PayloadVersion}, selector_match::CompiledSelector, transforms::TransformEngine, AppState};
+
+#[tracing::instrument(skip_all, fields(breadcrumb_id = %bc.id, version = bc.version))]
+pub async fn fanout_events_and_webhooks(state: &AppState, owner_id: Uuid, bc: &Breadcrumb, payload: &str) {
+    // Narrow the owner's selectors via the index, then run the full matcher on the candidates
+    let Ok(index) = state.selector_index.get(&state.db, owner_id, &state.selector_cache).await else { return; };
+    let matches = agent_matches(index.matching(&bc.tags, bc.schema_name.as_deref(), &bc.context));
+    let target_agents: Vec<Uuid> = matches.iter().map(|m| m.agent_id).collect();
+
+    // A selector match is not a read grant: private/pii/secret breadcrumbs reach each agent
+    // only as far as it could read them
+    let access = fanout_access::FanoutAccess::load(&state.db, owner_id, bc, &target_agents).await;
+    let metadata_payload = serde_json::from_str::<serde_json::Value>(payload).ok().map(|v| fanout_access::metadata_event(&v).to_string());
+    let mut deliveries: Vec<(AgentMatch, String)> = Vec::new();
+    for m in matches {
+        match access.delivery(m.agent_id) {
+            fanout_access::Delivery::Full => deliveries.push((m, payload.to_string())),
+            fanout_access::Delivery::Metadata => match &metadata_payload {
+                Some(meta) => deliveries.push((m, meta.clone())),
+                None => tracing::debug!("Unparseable payload for {}, skipping redacted delivery to {}", bc.id, m.agent_id),
+            },
+            fanout_access::Delivery::Skip => tracing::debug!("Agent {} cannot read {}, skipping fanout", m.agent_id, bc.id),
+        }
+    }
+
+    // NATS per-agent subjects, which also feed the agent's SSE stream
+    // Ensure payload has "type" field for agent-specific channels, and carry the selectors' version pin for SSE
+    #[cfg(feature = "nats")]
+    {
+        for (AgentMatch { agent_id, channels: agent_channels, pin, .. }, agent_payload) in &deliveries {
+            if !agent_channels.iter().any(|c| matches!(c, DeliveryChannel::Nats | DeliveryChannel::Sse)) {
+                continue;
+            }
+            // Parse payload and ensure it has type field
+            let agent_payload = if let Ok(mut event_json) = serde_json::from_str::<serde_json::Value>(agent_payload) {
+                if event_json.get("type").is_none() {
+                    // Add type field if missing (should never happen with proper create/update paths)
+                    if let Some(obj) = event_json.as_object_mut() {
+                        obj.insert("type".to_string(), serde_json::json!("breadcrumb.updated"));
+                    }
+                }
+                if let (Some(pin), Some(obj)) = (pin, event_json.as_object_mut()) {
+                    obj.insert(payload_versions::PINNED_FIELD.to_string(), json!(pin.number()));
+                }
+                event_json.to_string()
+            } else {
+                agent_payload.clone() // Fallback to original if parse fails
+            };
+        
+            let subj_agent = format!("agents.{}.events", agent_id);
+            tracing::debug!("🔧 NATS: Publishing to agent channel {} with type field ensured", subj_agent);
+            state.event_bus.publish(owner_id, subj_agent, agent_payload).await;
+        }
+    }
+
+    // Inboxes, in the selectors' pinned payload version; a version already queued (outbox replay) is kept once
+    for (m, agent_payload) in &deliveries {
+        if !m.channels.contains(&DeliveryChannel::Inbox) {
+            continue;
+        }
+        let rendered = payload_versions::render_str(agent_payload, m.pin.unwrap_or(PayloadVersion::DEFAULT));
+        let Ok(event) = serde_json::from_str::<serde_json::Value>(&rendered) else {
+            tracing::debug!("Unparseable payload for {}, skipping inbox of {}", bc.id, m.agent_id);
+            continue;
+        };
+        if let Err(e) = state.db.enqueue_inbox(owner_id, m.agent_id, bc.id, bc.version, &event).await {
+            tracing::warn!("Failed to queue {} v{} in the inbox of {}: {}", bc.id, bc.version, m.agent_id, e);
+        }
+    }
+
+    // Webhooks, each in its own pinned payload version, else the selectors' pin
+    for (m, agent_payload) in deliveries {
+        if m.webhook_selectors.is_empty() {
+            continue;
+        }
+        let agent_id = m.agent_id;
+        if let Ok(hooks) = state.db.list_agent_webhooks(owner_id, agent_id).await {
+            let secret = state.db.get_agent_webhook_secret(owner_id, agent_id).await.ok().flatten();
+            for hook in hooks {
+                let Some(pin) = hook_pin(&hook, &m) else { continue };
+                // Skip versions this webhook already received (server restart / NATS redelivery)
+                let delivery_id = match state.db.begin_webhook_delivery(owner_id, hook.id, bc.id, bc.version).await {
+                    Ok(Some(id)) => Some(id),
+                    Ok(None) => {
+                        tracing::debug!("Webhook {} already delivered {} v{}, skipping", hook.id, bc.id, bc.version);
+                        continue;
+                    }
+                    Err(e) => {
+                        tracing::warn!("Failed to record webhook delivery for {}: {}", hook.id, e);
+                        None
+                    }
+                };
+                let version = hook_version(&hook, pin);
+                let payload_str = with_delivery_id(&payload_versions::render_str(&agent_payload, version), delivery_id);
+                let (body, template_error) = render_body(hook.payload_template.as_deref(), &payload_str);
+                if let (Some(err), Some(id)) = (&template_error, delivery_id) {
+                    let _ = state.db.record_webhook_template_error(owner_id, id, err).await;
+                }
+                let target = WebhookTarget { hook: HookRef::Agent { agent_id, webhook_id: Some(hook.id) }, url: hook.url };
+                let dispatch = Dispatch { owner_id, target, body, secret: secret.clone(), delivery_id, version, order: Some(DeliveryOrder::of(bc)) };
+                send(state, dispatch, hook.strict_ordering);
+            }
+        }
+    }
+
+    fanout_owner_webhooks(state, owner_id, bc, payload, metadata_payload.as_deref()).await;
+}
+
+/// Owner webhooks aren't agents: they match on their own filters, read as an agent without grants
+/// would (private breadcrumbs skipped, pii/secret as metadata) and receive the default payload version
+async fn fanout_owner_webhooks(state: &AppState, owner_id: Uuid, bc: &Breadcrumb, payload: &str, metadata_payload: Option<&str>) {
+    let hooks = match state.db.list_owner_webhooks(owner_id).await {
+        Ok(hooks) => hooks,
+        Err(e) => {
+            tracing::warn!("Failed to list owner webhooks for {}: {}", owner_id, e);
+            return;
+        }
+    };
+    let event_type = owner_event_type(bc);
+    let hooks: Vec<OwnerWebhook> = hooks.into_iter().filter(|h| owner_hook_matches(h, event_type, &bc.tags, bc.schema_name.as_deref(), &bc.context)).collect();
+    if hooks.is_empty() {
+        return;
+    }
+    let payload = match fanout_access::ReadScope::of(bc).delivery(Uuid::nil(), &[], &[]) {
+        fanout_access::Delivery::Full => payload,
+        fanout_access::Delivery::Metadata => match metadata_payload {
+            Some(meta) => meta,
+            None => return,
+        },
+        fanout_access::Delivery::Skip => {
+            tracing::debug!("{} is private, skipping owner webhooks", bc.id);
+            return;
+        }
+    };
+    let payload = with_event_type(payload, event_type);
+    for hook in hooks {
+        let delivery_id = match state.db.begin_owner_webhook_delivery(owner_id, hook.id, bc.id, bc.version).await {
+            Ok(Some(id)) => Some(id),
+            Ok(None) => {
+                tracing::debug!("Owner webhook {} already delivered {} v{}, skipping", hook.id, bc.id, bc.version);
+                continue;
+            }
+            Err(e) => {
+                tracing::warn!("Failed to record webhook delivery for {}: {}", hook.id, e);
+                None
+            }
+        };
+        let version = PayloadVersion::DEFAULT;
+        let body = with_delivery_id(&payload_versions::render_str(&payload, version), delivery_id);
+        let target = WebhookTarget { hook: HookRef::Owner(hook.id), url: hook.url };
+        let dispatch = Dispatch { owner_id, target, body, secret: hook.secret, delivery_id, version, order: Some(DeliveryOrder::of(bc)) };
+        send(state, dispatch, hook.strict_ordering);
+    }
+}
+
+/// A breadcrumb's first version is its creation, any later one an update
+fn owner_event_type(bc: &Breadcrumb) -> &'static str {
+    if bc.version == 1 { "breadcrumb.created" } else { "breadcrumb.updated" }
+}
+
+/// Whether an owner webhook wants an event of `event_type` on a breadcrumb with these tags, schema and context
+fn owner_hook_matches(hook: &OwnerWebhook, event_type: &str, tags: &[String], schema_name: Option<&str>, context: &serde_json::Value) -> bool {
+    hook.wants(event_type) && CompiledSelector::compile(&hook.selector()).matches(tags, schema_name, context)
+}
+
+// Fanout payloads are all "breadcrumb.updated"; owner webhooks filter on, and are told, created vs updated
+fn with_event_type(payload: &str, event_type: &str) -> String {
+    match serde_json::from_str::<serde_json::Value>(payload) {
+        Ok(mut val) => {
+            if let Some(obj) = val.as_object_mut() {
+                obj.insert("type".to_string(), json!(event_type));
+            }
+            val.to_string()
+        }
+        Err(_) => payload.to_string(),
+    }
+}
+
+/// What one breadcrumb's matching selectors ask for on behalf of one agent
+#[derive(Debug, PartialEq)]
+struct AgentMatch {
+    agent_id: Uuid,
+    /// Union of the matching selectors' channels
+    channels: Vec<DeliveryChannel>,
+    /// Highest payload version any of them pins
+    pin: Option<PayloadVersion>,
+    /// The matching selectors with the webhook channel, each with its own pin
+    webhook_selectors: Vec<(Uuid, Option<PayloadVersion>)>,
+}
+
+/// One entry per matched agent, in match order
+fn agent_matches(matches: Vec<&SelectorSubscription>) -> Vec<AgentMatch> {
+    let mut out: Vec<AgentMatch> = Vec::new();
+    for sub in matches {
+        let i = match out.iter().position(|m| m.agent_id == sub.agent_id) {
+            Some(i) => i,
+            None => {
+                out.push(AgentMatch { agent_id: sub.agent_id, channels: Vec::new(), pin: None, webhook_selectors: Vec::new() });
+                out.len() - 1
+            }
+        };
+        let m = &mut out[i];
+        for channel in &sub.channels {
+            if !m.channels.contains(channel) {
+                m.channels.push(*channel);
+            }
+        }
+        let sub_pin = sub.payload_version.and_then(|v| PayloadVersion::from_number(v.into()));
+        m.pin = m.pin.max(sub_pin);
+        if sub.channels.contains(&DeliveryChannel::Webhook) {
+            m.webhook_selectors.push((sub.id, sub_pin));
+        }
+    }
+    out
+}
+
+/// None when the hook doesn't fire for this match, else the selector pin it falls back to. An unbound
+/// hook fires for any webhook match of its agent, a bound one only when its own selector matched
+fn hook_pin(hook: &AgentWebhook, m: &AgentMatch) -> Option<Option<PayloadVersion>> {
+    match hook.selector_id {
+        None => (!m.webhook_selectors.is_empty()).then_some(m.pin),
+        Some(selector_id) => m.webhook_selectors.iter().find(|(id, _)| *id == selector_id).map(|(_, pin)| *pin),
+    }
+}
+
+/// The webhook's own pin, else the matching selectors', else the default
+fn hook_version(hook: &AgentWebhook, selector_pin: Option<PayloadVersion>) -> PayloadVersion {
+    hook.payload_version.and_then(|v| PayloadVersion::from_number(v.into())).or(selector_pin).unwrap_or(PayloadVersion::DEFAULT)
+}
+
+// Stamp delivery_id into the webhook body so receivers can dedupe
+fn with_delivery_id(payload: &str, delivery_id: Option<Uuid>) -> String {
+    let Some(delivery_id) = delivery_id else { return payload.to_string(); };
+    match serde_json::from_str::<serde_json::Value>(payload) {
+        Ok(mut val) => {
+            if let Some(obj) = val.as_object_mut() {
+                obj.insert("delivery_id".to_string(), json!(delivery_id));
+            }
+            val.to_string()
+        }
+        Err(_) => payload.to_string(),
+    }
+}
+
+static PAYLOAD_ENGINE: OnceLock<TransformEngine> = OnceLock::new();
+static WEBHOOK_TEMPLATE_ERRORS: OnceLock<IntCounter> = OnceLock::new();
+
+/// The body to send: the hook's template rendered over the event, or the raw event when there
+/// is no template or it fails (the error is returned so the caller can record it)
+fn render_body(template: Option<&str>, payload: &str) -> (String, Option<String>) {
+    let Some(template) = template else { return (payload.to_string(), None); };
+    let rendered = serde_json::from_str::<serde_json::Value>(payload)
+        .map_err(|e| format!("Event is not JSON: {}", e))
+        .and_then(|event| PAYLOAD_ENGINE.get_or_init(TransformEngine::new).render_payload_template(template, &event));
+    match rendered {
+        Ok(body) => (body, None),
+        Err(err) => {
+            WEBHOOK_TEMPLATE_ERRORS
+                .get_or_init(|| register_int_counter!("webhook_template_errors_total", "Webhook payload templates that failed to render").unwrap())
+                .inc();
+            tracing::warn!("Webhook payload template failed, sending raw event: {}", err);
+            (payload.to_string(), Some(err))
+        }
+    }
+}
+
+static WEBHOOK_RESULTS: OnceLock<IntCounterVec> = OnceLock::new();
+static WEBHOOK_DURATION: OnceLock<HistogramVec> = OnceLock::new();
+
+/// How one delivery attempt ended
+#[derive(Debug, PartialEq)]
+enum AttemptOutcome {
+    Delivered,
+    /// Network error, 408, 429 or 5xx; `retry_after` comes from a 429's Retry-After header
+    Retryable { status: Option<u16>, retry_after: Option<Duration> },
+    /// Any other 4xx: the endpoint rejected the request and will keep rejecting it
+    Permanent { status: u16 },
+}
+
+fn classify(status: reqwest::StatusCode, retry_after: Option<&str>) -> AttemptOutcome {
+    if status.is_success() {
+        return AttemptOutcome::Delivered;
+    }
+    let code = status.as_u16();
+    match status {
+        reqwest::StatusCode::TOO_MANY_REQUESTS => AttemptOutcome::Retryable { status: Some(code), retry_after: retry_after.and_then(parse_retry_after) },
+        reqwest::StatusCode::REQUEST_TIMEOUT => AttemptOutcome::Retryable { status: Some(code), retry_after: None },
+        s if s.is_client_error() => AttemptOutcome::Permanent { status: code },
+        _ => AttemptOutcome::Retryable { status: Some(code), retry_after: None },
+    }
+}
+
+// Retry-After is either delta-seconds or an HTTP-date
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some((at.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().unwrap_or(Duration::ZERO))
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    max_attempts: usize,
+    base_backoff: Duration,
+    max_retry_after: Duration,
+}
+
+impl RetryPolicy {
+    /// `max_attempts` deliveries with backoff from 250ms; Retry-After is capped at `max_retry_after`
+    pub fn new(max_attempts: usize, max_retry_after: Duration) -> Self {
+        RetryPolicy { max_attempts, base_backoff: Duration::from_millis(250), max_retry_after }
+    }
+
+    fn delay(&self, attempt: usize, retry_after: Option<Duration>) -> Duration {
+        match retry_after {
+            Some(d) => d.min(self.max_retry_after),
+            None => self.base_backoff * (1u32 << attempt.min(6)), // capped exponential backoff
+        }
+    }
+}
+
+struct DeliveryResult {
+    delivered: bool,
+    attempts: usize,
+    /// Status of the last response, if there was one
+    status: Option<u16>,
+    error: Option<String>,
+}
+
+/// `headers` go on every attempt: the delivery id and the ordering headers, when there are any
+async fn deliver(client: &HttpClient, url: &str, body: &str, secret: Option<&str>, headers: &[(&'static str, String)], version: PayloadVersion, policy: &RetryPolicy) -> DeliveryResult {
+    let mut attempt: usize = 0;
+    loop {
+        let mut req = client.post(url).header("content-type", "application/json").header(payload_versions::HEADER, version.number().to_string());
+        for (name, value) in headers {
+            req = req.header(*name, value);
+        }
+        if let Some(sec) = secret {
+            let mut mac = Hmac::<Sha256>::new_from_slice(sec.as_bytes()).unwrap();
+            mac.update(body.as_bytes());
+            let sig = hex::encode(mac.finalize().into_bytes());
+            req = req.header("X-RCRT-Signature", format!("sha256={}", sig));
+        }
+        attempt += 1;
+        let (outcome, error) = match req.body(body.to_string()).send().await {
+            Ok(res) => {
+                let retry_after = res.headers().get(reqwest::header::RETRY_AFTER).and_then(|h| h.to_str().ok());
+                (classify(res.status(), retry_after), format!("HTTP {}", res.status()))
+            }
+            Err(e) => (AttemptOutcome::Retryable { status: None, retry_after: None }, e.to_string()),
+        };
+        match outcome {
+            AttemptOutcome::Delivered => {
+                return DeliveryResult { delivered: true, attempts: attempt, status: None, error: None };
+            }
+            AttemptOutcome::Permanent { status } => {
+                return DeliveryResult { delivered: false, attempts: attempt, status: Some(status), error: Some(error) };
+            }
+            AttemptOutcome::Retryable { status, .. } if attempt >= policy.max_attempts => {
+                return DeliveryResult { delivered: false, attempts: attempt, status, error: Some(error) };
+            }
+            AttemptOutcome::Retryable { retry_after, .. } => {
+                tokio::time::sleep(policy.delay(attempt, retry_after)).await;
+            }
+        }
+    }
+}
+
+/// Where a dispatch goes
+struct WebhookTarget {
+    hook: HookRef,
+    url: String,
+}
+
+/// Whose webhook a dispatch is for, which decides where its failure is dead-lettered
+#[derive(Debug, Clone, Copy)]
+enum HookRef {
+    /// An agent's webhook; DLQ retries know the agent and URL but not which webhook it was
+    Agent { agent_id: Uuid, webhook_id: Option<Uuid> },
+    Owner(Uuid),
+}
+
+impl HookRef {
+    /// The agent webhook whose delivery stats a dispatch counts toward
+    fn agent_webhook(self) -> Option<(Uuid, Uuid)> {
+        match self {
+            HookRef::Agent { agent_id, webhook_id: Some(webhook_id) } => Some((agent_id, webhook_id)),
+            _ => None,
+        }
+    }
+
+    /// The webhook itself, when the dispatch knows it
+    fn webhook_id(self) -> Option<Uuid> {
+        match self {
+            HookRef::Agent { webhook_id, .. } => webhook_id,
+            HookRef::Owner(id) => Some(id),
+        }
+    }
+}
+
+/// Which write of which breadcrumb a delivery carries. Sent as X-RCRT-Breadcrumb-Id, X-RCRT-Version and
+/// X-RCRT-Updated-At (RFC 3339, microseconds) alongside the same fields in the body; receivers keep the
+/// highest version they've seen per breadcrumb and ignore deliveries at or below it
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct DeliveryOrder {
+    breadcrumb_id: Uuid,
+    version: i32,
+    updated_at: DateTime<Utc>,
+}
+
+impl DeliveryOrder {
+    fn of(bc: &Breadcrumb) -> Self {
+        DeliveryOrder { breadcrumb_id: bc.id, version: bc.version, updated_at: bc.updated_at }
+    }
+
+    /// From a breadcrumb event, as the DLQ stores it; None for anything else, such as a templated body
+    fn from_event(event: &serde_json::Value) -> Option<Self> {
+        Some(DeliveryOrder {
+            breadcrumb_id: event.get("breadcrumb_id")?.as_str()?.parse().ok()?,
+            version: event.get("version")?.as_i64()?.try_into().ok()?,
+            updated_at: event.get("updated_at")?.as_str()?.parse().ok()?,
+        })
+    }
+
+    fn headers(&self) -> [(&'static str, String); 3] {
+        [
+            ("X-RCRT-Breadcrumb-Id", self.breadcrumb_id.to_string()),
+            ("X-RCRT-Version", self.version.to_string()),
+            ("X-RCRT-Updated-At", self.updated_at.to_rfc3339_opts(SecondsFormat::Micros, true)),
+        ]
+    }
+}
+
+/// One delivery of one body to one webhook
+struct Dispatch {
+    owner_id: Uuid,
+    target: WebhookTarget,
+    body: String,
+    secret: Option<String>,
+    delivery_id: Option<Uuid>,
+    version: PayloadVersion,
+    /// None when the body doesn't say which breadcrumb version it is
+    order: Option<DeliveryOrder>,
+}
+
+/// Deliveries to strict-ordering webhooks waiting behind an earlier delivery of the same breadcrumb,
+/// keyed by webhook and breadcrumb. A key is present while one of its deliveries is in flight
+pub struct OrderedQueues {
+    /// Waiting deliveries per key before the next one is dead-lettered instead
+    max: usize,
+    queues: Mutex<HashMap<(Uuid, Uuid), VecDeque<Dispatch>>>,
+}
+
+enum Queued {
+    /// Nothing was in flight for the key: send this now, then drain the queue
+    Send(Dispatch),
+    Waiting,
+    /// The queue was full
+    Overflow(Dispatch),
+}
+
+impl OrderedQueues {
+    pub fn new(max: usize) -> Self {
+        OrderedQueues { max, queues: Mutex::new(HashMap::new()) }
+    }
+
+    /// Waiting deliveries are kept in breadcrumb version order, whatever order they arrived in
+    fn push(&self, key: (Uuid, Uuid), dispatch: Dispatch) -> Queued {
+        let mut queues = self.queues.lock().unwrap();
+        let Some(queue) = queues.get_mut(&key) else {
+            queues.insert(key, VecDeque::new());
+            return Queued::Send(dispatch);
+        };
+        if queue.len() >= self.max {
+            return Queued::Overflow(dispatch);
+        }
+        let version = dispatch.order.map(|o| o.version);
+        let at = queue.partition_point(|d| d.order.map(|o| o.version) <= version);
+        queue.insert(at, dispatch);
+        Queued::Waiting
+    }
+
+    /// The next delivery for the key, or None once its queue is empty, which also removes the key
+    fn next(&self, key: (Uuid, Uuid)) -> Option<Dispatch> {
+        let mut queues = self.queues.lock().unwrap();
+        let next = queues.get_mut(&key).and_then(VecDeque::pop_front);
+        if next.is_none() {
+            queues.remove(&key);
+        }
+        next
+    }
+}
+
+/// Dispatch in the background. With `strict`, a delivery of a breadcrumb waits until the webhook's earlier
+/// deliveries of it have been delivered or dead-lettered; one that finds its queue full is dead-lettered.
+/// Deliveries log under the caller's span (breadcrumb id, and request id when there is one)
+fn send(state: &AppState, dispatch: Dispatch, strict: bool) {
+    let key = match (dispatch.target.hook.webhook_id(), dispatch.order) {
+        (Some(webhook_id), Some(order)) if strict => (webhook_id, order.breadcrumb_id),
+        _ => {
+            tokio::spawn(dispatch_webhook(state.clone(), dispatch).in_current_span());
+            return;
+        }
+    };
+    match state.webhook_queues.push(key, dispatch) {
+        Queued::Send(first) => {
+            let state = state.clone();
+            tokio::spawn(async move {
+                let mut next = Some(first);
+                while let Some(dispatch) = next {
+                    dispatch_webhook(state.clone(), dispatch).await;
+                    next = state.webhook_queues.next(key);
+                }
+            }.in_current_span());
+        }
+        Queued::Waiting => tracing::debug!("Webhook {} delivery of {} waits behind an earlier one", key.0, key.1),
+        Queued::Overflow(dispatch) => {
+            let err = format!("strict ordering queue full ({} waiting)", state.webhook_queues.max);
+            tracing::warn!("Webhook {} delivery of {} dead-lettered: {}", key.0, key.1, err);
+            let state = state.clone();
+            tokio::spawn(async move { dead_letter(&state, &dispatch, 0, &err, None).await }.in_current_span());
+        }
+    }
+}
+
+async fn dispatch_webhook(state: AppState, dispatch: Dispatch) {
+    let Dispatch { owner_id, target: WebhookTarget { hook, ref url }, ref body, ref secret, delivery_id, version, order } = dispatch;
+    let counter = WEBHOOK_RESULTS.get_or_init(|| register_int_counter_vec!("webhook_delivery_total", "Webhook delivery results", & ["result"]).unwrap());
+    let histo = WEBHOOK_DURATION.get_or_init(|| register_histogram_vec!(
+        "webhook_delivery_duration_seconds","Webhook delivery duration seconds", &["result"],
+        vec![0.05,0.1,0.25,0.5,1.0,2.5,5.0]
+    ).unwrap());
+    let mut headers: Vec<(&'static str, String)> = delivery_id.map(|id| ("X-RCRT-Delivery-Id", id.to_string())).into_iter().collect();
+    headers.extend(order.iter().flat_map(DeliveryOrder::headers));
+    let all_start = std::time::Instant::now();
+    let result = deliver(&HttpClient::new(), url, body, secret.as_deref(), &headers, version, &state.webhook_retry).await;
+    if result.delivered {
+        counter.with_label_values(&["success"]).inc();
+        histo.with_label_values(&["success"]).observe(all_start.elapsed().as_secs_f64());
+        if let Some(id) = delivery_id {
+            let _ = state.db.complete_webhook_delivery(owner_id, id, "delivered", result.attempts as i32, None).await;
+        }
+        if let Some((agent_id, webhook_id)) = hook.agent_webhook() {
+            record_outcome(&state, owner_id, agent_id, webhook_id, url, true).await;
+        }
+        return;
+    }
+    counter.with_label_values(&["failed"]).inc();
+    histo.with_label_values(&["failed"]).observe(all_start.elapsed().as_secs_f64());
+    let err = result.error.unwrap_or_default();
+    tracing::warn!("Webhook to {} failed after {} attempt(s): {}", url, result.attempts, err);
+    dead_letter(&state, &dispatch, result.attempts, &err, result.status).await;
+    if let Some((agent_id, webhook_id)) = hook.agent_webhook() {
+        record_outcome(&state, owner_id, agent_id, webhook_id, url, false).await;
+    }
+}
+
+/// Mark the delivery failed and put its body in the DLQ, under its agent or owner webhook
+async fn dead_letter(state: &AppState, dispatch: &Dispatch, attempts: usize, err: &str, last_status: Option<u16>) {
+    let Dispatch { owner_id, target: WebhookTarget { hook, url }, body, delivery_id, .. } = dispatch;
+    if let Some(id) = delivery_id {
+        let _ = state.db.complete_webhook_delivery(*owner_id, *id, "failed", attempts as i32, Some(err)).await;
+    }
+    if let Ok(val) = serde_json::from_str::<serde_json::Value>(body) {
+        let last_status = last_status.map(i32::from);
+        let _ = match hook {
+            HookRef::Agent { agent_id, .. } => state.db.enqueue_webhook_dlq(*owner_id, *agent_id, url, &val, err, last_status).await,
+            HookRef::Owner(id) => state.db.enqueue_owner_webhook_dlq(*owner_id, *id, url, &val, err, last_status).await,
+        };
+    }
+}
+
+/// Update the webhook's delivery stats, and tell its agent when this failure auto-disabled it
+async fn record_outcome(state: &AppState, owner_id: Uuid, agent_id: Uuid, webhook_id: Uuid, url: &str, delivered: bool) {
+    match state.db.record_webhook_outcome(owner_id, agent_id, webhook_id, delivered, state.webhook_auto_disable_after_failures).await {
+        Ok(Some(failures)) => {
+            tracing::warn!("Webhook {} ({}) disabled after {} consecutive failed deliveries", webhook_id, url, failures);
+            events::publish_webhook_deactivated(state, owner_id, agent_id, webhook_id, url, failures).await;
+        }
+        Ok(None) => {}
+        Err(e) => tracing::warn!("Failed to record webhook outcome for {}: {}", webhook_id, e),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct WebhookReq { url: String, #[serde(default)] payload_template: Option<String>, #[serde(default)] payload_version: Option<u16>, #[serde(default)] selector_id: Option<Uuid>, #[serde(default)] strict_ordering: bool }
+pub async fn register_webhook(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(agent_id): axum::extract::Path<Uuid>, Json(req): Json<WebhookReq>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if auth.agent_id != agent_id && !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "forbidden".into())); }
+    if let Some(template) = &req.payload_template {
+        TransformEngine::check_payload_template(template).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+    if let Some(version) = req.payload_version {
+        PayloadVersion::requested(version)?;
+    }
+    let id = state.db.create_agent_webhook(auth.owner_id, agent_id, &req.url, req.payload_template.as_deref(), req.payload_version, req.selector_id, req.strict_ordering).await.map_err(db_error)?;
+    let id = id.ok_or((StatusCode::UNPROCESSABLE_ENTITY, "selector_id is not a selector of this agent".into()))?;
+    Ok(Json(json!({"id": id})))
+}
+
+#[derive(Deserialize)]
+pub struct ListWebhooksQuery {
+    /// Also list deactivated webhooks, with the reason when one was recorded
+    #[serde(default)]
+    include_inactive: bool,
+    /// Only active (true) or only deactivated (false) webhooks; overrides include_inactive
+    active: Option<bool>,
+    /// Only webhooks whose last delivery failed
+    #[serde(default)]
+    failing: bool,
+    /// created_at (default, oldest first), last_success_at, last_failure_at, consecutive_failures or total_deliveries
+    order: Option<String>,
+}
+
+pub async fn list_webhooks(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(agent_id): axum::extract::Path<Uuid>, Query(q): Query<ListWebhooksQuery>) -> Result<Json<Vec<WebhookStatus>>, (StatusCode, String)> {
+    if auth.agent_id != agent_id && !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "forbidden".into())); }
+    let order = match q.order.as_deref() {
+        None => WebhookOrder::CreatedAt,
+        Some(s) => WebhookOrder::parse(s).ok_or((StatusCode::BAD_REQUEST, format!(
+            "order must be created_at, last_success_at, last_failure_at, consecutive_failures or total_deliveries, not {}", s
+        )))?,
+    };
+    let active = q.active.or((!q.include_inactive).then_some(true));
+    let hooks = state.db.list_agent_webhook_statuses(auth.owner_id, agent_id, active, q.failing, order).await.map_err(db_error)?;
+    Ok(Json(hooks))
+}
+
+#[derive(Deserialize)]
+pub struct TestDeliveryReq { event: Option<serde_json::Value> }
+
+/// Stand-in event for test deliveries when the caller doesn't supply one, in the latest payload version
+fn sample_event(owner_id: Uuid) -> serde_json::Value {
+    json!({
+        "type": "breadcrumb.updated",
+        "breadcrumb_id": Uuid::nil(),
+        "owner_id": owner_id,
+        "title": "RCRT test delivery",
+        "version": 1,
+        "tags": ["rcrt:test"],
+        "schema_name": null,
+        "updated_at": chrono::Utc::now(),
+        "context": { "message": "Webhook test delivery from RCRT" },
+        "emitted_at": chrono::Utc::now(),
+        "provenance": { "agent_id": null, "request_id": null },
+        "payload_version": PayloadVersion::LATEST.number(),
+        "test": true
+    })
+}
+
+/// Send one signed request to the webhook, in its pinned payload version and rendered through its
+/// template, and report what happened. Not retried, not recorded as a delivery and never dead-lettered
+pub async fn test_webhook(State(state): State<AppState>, auth: AuthContext, axum::extract::Path((agent_id, wid)): axum::extract::Path<(Uuid, Uuid)>, req: Option<Json<TestDeliveryReq>>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if auth.agent_id != agent_id && !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "forbidden".into())); }
+    let Some(hook) = state.db.get_agent_webhook(auth.owner_id, agent_id, wid).await.map_err(db_error)? else {
+        return Err((StatusCode::NOT_FOUND, "webhook not found".into()));
+    };
+    let event = req.and_then(|Json(r)| r.event).unwrap_or_else(|| sample_event(auth.owner_id));
+    let version = hook_version(&hook, None);
+    let (body, template_error) = render_body(hook.payload_template.as_deref(), &payload_versions::render(&event, version).to_string());
+    let secret = state.db.get_agent_webhook_secret(auth.owner_id, agent_id).await.map_err(db_error)?;
+    let policy = RetryPolicy { max_attempts: 1, ..state.webhook_retry };
+    let headers: Vec<(&'static str, String)> = DeliveryOrder::from_event(&event).iter().flat_map(DeliveryOrder::headers).collect();
+    let result = deliver(&HttpClient::new(), &hook.url, &body, secret.as_deref(), &headers, version, &policy).await;
+    Ok(Json(json!({
+        "delivered": result.delivered,
+        "status": result.status,
+        "error": result.error,
+        "template_error": template_error,
+        "body": body,
+    })))
+}
+
+pub async fn deactivate_webhook(State(state): State<AppState>, auth: AuthContext, axum::extract::Path((agent_id, wid)): axum::extract::Path<(Uuid, Uuid)>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if auth.agent_id != agent_id && !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "forbidden".into())); }
+    let rows = state.db.deactivate_agent_webhook(auth.owner_id, agent_id, wid).await.map_err(db_error)?;
+    Ok(Json(json!({"rows": rows})))
+}
+
+#[derive(Deserialize)]
+pub struct SecretReq { secret: String }
+pub async fn set_agent_secret(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(agent_id): axum::extract::Path<Uuid>, Json(req): Json<SecretReq>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if auth.agent_id != agent_id && !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "forbidden".into())); }
+    state.db.set_agent_webhook_secret(auth.owner_id, agent_id, &req.secret).await.map_err(db_error)?;
+    Ok(Json(json!({"ok": true})))
+}
+
+#[derive(Deserialize)]
+pub struct OwnerWebhookReq {
+    url: String,
+    #[serde(default)]
+    secret: Option<String>,
+    #[serde(default)]
+    schema_name: Option<String>,
+    #[serde(default)]
+    any_tags: Vec<String>,
+    #[serde(default)]
+    event_types: Vec<String>,
+    #[serde(default)]
+    strict_ordering: bool,
+}
+
+/// Register a webhook for the owner rather than an agent; the same URL again replaces it
+pub async fn register_owner_webhook(State(state): State<AppState>, auth: AuthContext, Json(req): Json<OwnerWebhookReq>) -> Result<Json<OwnerWebhook>, (StatusCode, String)> {
+    if !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "curator role required".into())); }
+    if let Some(t) = req.event_types.iter().find(|t| !OwnerWebhook::EVENT_TYPES.contains(&t.as_str())) {
+        return Err((StatusCode::BAD_REQUEST, format!("event_types may contain {}, not {}", OwnerWebhook::EVENT_TYPES.join(" and "), t)));
+    }
+    let hook = state.db.create_owner_webhook(auth.owner_id, &req.url, req.secret.as_deref(), req.schema_name.as_deref(), &req.any_tags, &req.event_types, req.strict_ordering).await.map_err(db_error)?;
+    Ok(Json(hook))
+}
+
+pub async fn list_owner_webhooks(State(state): State<AppState>, auth: AuthContext) -> Result<Json<Vec<OwnerWebhook>>, (StatusCode, String)> {
+    if !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "curator role required".into())); }
+    let hooks = state.db.list_owner_webhooks(auth.owner_id).await.map_err(db_error)?;
+    Ok(Json(hooks))
+}
+
+pub async fn get_owner_webhook(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(id): axum::extract::Path<Uuid>) -> Result<Json<OwnerWebhook>, (StatusCode, String)> {
+    if !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "curator role required".into())); }
+    match state.db.get_owner_webhook(auth.owner_id, id).await.map_err(db_error)? {
+        Some(hook) => Ok(Json(hook)),
+        None => Err((StatusCode::NOT_FOUND, "webhook not found".into())),
+    }
+}
+
+/// Its pending dead letters go with it
+pub async fn delete_owner_webhook(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(id): axum::extract::Path<Uuid>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "curator role required".into())); }
+    if !state.db.delete_owner_webhook(auth.owner_id, id).await.map_err(db_error)? {
+        return Err((StatusCode::NOT_FOUND, "webhook not found".into()));
+    }
+    Ok(Json(json!({"ok": true})))
+}
+
+pub async fn list_dlq(State(state): State<AppState>, auth: AuthContext) -> Result<Json<Vec<serde_json::Value>>, (StatusCode, String)> {
+    if !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "curator role required".into())); }
+    let rows = state.db.list_webhook_dlq(auth.owner_id).await.map_err(db_error)?;
+    let out = rows.into_iter().map(|(id, agent_id, url, payload, last_error, last_status, created_at, owner_webhook_id)| json!({"id": id, "agent_id": agent_id, "owner_webhook_id": owner_webhook_id, "url": url, "payload": payload, "last_error": last_error, "last_status": last_status, "created_at": created_at})).collect();
+    Ok(Json(out))
+}
+
+pub async fn retry_dlq(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(id): axum::extract::Path<Uuid>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "curator role required".into())); }
+    let Some((dlq_id, agent_id, url, payload, owner_webhook_id)) = state.db.get_webhook_dlq(auth.owner_id, id).await.map_err(db_error)? else {
+        return Err((StatusCode::NOT_FOUND, "not found".into()));
+    };
+    // Signed as the original was: with the agent's secret, or the owner webhook's own
+    let (hook, secret) = match (agent_id, owner_webhook_id) {
+        (Some(agent_id), _) => (HookRef::Agent { agent_id, webhook_id: None }, state.db.get_agent_webhook_secret(auth.owner_id, agent_id).await.map_err(db_error)?),
+        (None, Some(id)) => (HookRef::Owner(id), state.db.get_owner_webhook(auth.owner_id, id).await.map_err(db_error)?.and_then(|h| h.secret)),
+        (None, None) => return Err((StatusCode::INTERNAL_SERVER_ERROR, "DLQ entry has no webhook".into())),
+    };
+    // Reuse the original delivery id and ordering headers so receivers can still dedupe the retry, or
+    // drop it when they've since seen a later version. It doesn't wait behind a strict webhook's queue
+    let delivery_id = payload.get("delivery_id").and_then(|v| v.as_str()).and_then(|s| Uuid::parse_str(s).ok());
+    let version = PayloadVersion::of(&payload);
+    let target = WebhookTarget { hook, url: url.clone() };
+    let dispatch = Dispatch { owner_id: auth.owner_id, target, body: payload.to_string(), secret, delivery_id, version, order: DeliveryOrder::from_event(&payload) };
+    tokio::spawn(dispatch_webhook(state.clone(), dispatch));
+    let _ = state.db.delete_webhook_dlq(auth.owner_id, dlq_id).await;
+    Ok(Json(json!({"requeued": true})))
+}
+
+pub async fn delete_dlq(State(state): State<AppState>, auth: AuthContext, axum::extract::Path(id): axum::extract::Path<Uuid>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if !auth.has_role(Role::Curator) { return Err((StatusCode::FORBIDDEN, "curator role required".into())); }
+    state.db.delete_webhook_dlq(auth.owner_id, id).await.map_err(db_error)?;
+    Ok(Json(json!({"ok": true})))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+    use axum::{http::HeaderMap, response::IntoResponse, routing::post, Router};
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy { max_attempts: 4, base_backoff: Duration::from_millis(1), max_retry_after: Duration::from_millis(50) }
+    }
+
+    /// Local endpoint answering each hit with the next status from `statuses` (the last one repeats)
+    async fn mock_endpoint(statuses: Vec<u16>, retry_after: Option<&'static str>) -> (String, Arc<AtomicUsize>) {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let app = Router::new().route("/hook", post(move || {
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+            let status = StatusCode::from_u16(statuses[n.min(statuses.len() - 1)]).unwrap();
+            async move {
+                let mut headers = HeaderMap::new();
+                if let Some(value) = retry_after {
+                    headers.insert(axum::http::header::RETRY_AFTER, value.parse().unwrap());
+                }
+                (status, headers).into_response()
+            }
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}/hook", addr), hits)
+    }
+
+    async fn run(url: &str) -> DeliveryResult {
+        deliver(&HttpClient::new(), url, "{}", Some("secret"), &[], PayloadVersion::DEFAULT, &policy()).await
+    }
+
+    #[test]
+    fn test_classify() {
+        let status = |code: u16| reqwest::StatusCode::from_u16(code).unwrap();
+        assert_eq!(classify(status(204), None), AttemptOutcome::Delivered);
+        for code in [400, 401, 404, 410, 422] {
+            assert_eq!(classify(status(code), None), AttemptOutcome::Permanent { status: code });
+        }
+        for code in [408, 500, 502, 503] {
+            assert_eq!(classify(status(code), None), AttemptOutcome::Retryable { status: Some(code), retry_after: None });
+        }
+        assert_eq!(classify(status(429), Some("7")), AttemptOutcome::Retryable { status: Some(429), retry_after: Some(Duration::from_secs(7)) });
+        assert_eq!(classify(status(429), Some("soon")), AttemptOutcome::Retryable { status: Some(429), retry_after: None });
+    }
+
+    #[test]
+    fn test_render_body_falls_back_to_raw_event() {
+        let event = r#"{"type":"breadcrumb.updated","title":"Deploy done","delivery_id":"d-1"}"#;
+        assert_eq!(render_body(None, event), (event.to_string(), None));
+
+        let (body, err) = render_body(Some(r#"{"text": "{{title}} ({{delivery_id}})"}"#), event);
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&body).unwrap(), json!({"text": "Deploy done (d-1)"}));
+        assert_eq!(err, None);
+
+        let (body, err) = render_body(Some("{{#each}}"), event);
+        assert_eq!(body, event);
+        assert!(err.unwrap().starts_with("Template error"));
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after(" 120 "), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(Duration::ZERO));
+        let later = (chrono::Utc::now() + chrono::Duration::seconds(90)).to_rfc2822();
+        let parsed = parse_retry_after(&later).unwrap();
+        assert!(parsed > Duration::from_secs(80) && parsed <= Duration::from_secs(90), "{:?}", parsed);
+        assert_eq!(parse_retry_after("-1"), None);
+    }
+
+    #[test]
+    fn test_retry_after_is_capped() {
+        let policy = policy();
+        assert_eq!(policy.delay(1, Some(Duration::from_secs(3600))), Duration::from_millis(50));
+        assert_eq!(policy.delay(1, Some(Duration::from_millis(10))), Duration::from_millis(10));
+        assert_eq!(policy.delay(2, None), Duration::from_millis(4));
+    }
+
+    #[tokio::test]
+    async fn test_success_needs_one_attempt() {
+        let (url, hits) = mock_endpoint(vec![200], None).await;
+        let result = run(&url).await;
+        assert!(result.delivered);
+        assert_eq!((result.attempts, hits.load(Ordering::SeqCst)), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_client_errors_are_not_retried() {
+        for code in [400, 401, 410] {
+            let (url, hits) = mock_endpoint(vec![code], None).await;
+            let result = run(&url).await;
+            assert!(!result.delivered);
+            assert_eq!((result.attempts, hits.load(Ordering::SeqCst)), (1, 1), "HTTP {}", code);
+            assert_eq!(result.status, Some(code));
+            assert_eq!(result.error, Some(format!("HTTP {}", reqwest::StatusCode::from_u16(code).unwrap())));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_server_errors_and_timeouts_use_every_attempt() {
+        for code in [408, 500, 503] {
+            let (url, hits) = mock_endpoint(vec![code], None).await;
+            let result = run(&url).await;
+            assert!(!result.delivered);
+            assert_eq!((result.attempts, hits.load(Ordering::SeqCst)), (4, 4), "HTTP {}", code);
+            assert_eq!(result.status, Some(code));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recovers_after_transient_failures() {
+        let (url, hits) = mock_endpoint(vec![503, 429, 200], None).await;
+        let result = run(&url).await;
+        assert!(result.delivered);
+        assert_eq!((result.attempts, hits.load(Ordering::SeqCst)), (3, 3));
+    }
+
+    #[tokio::test]
+    async fn test_too_many_requests_waits_for_capped_retry_after() {
+        let (url, hits) = mock_endpoint(vec![429], Some("3600")).await;
+        let started = std::time::Instant::now();
+        let result = run(&url).await;
+        let elapsed = started.elapsed();
+        assert_eq!((result.attempts, hits.load(Ordering::SeqCst)), (4, 4));
+        assert_eq!(result.status, Some(429));
+        // Three waits of max_retry_after (50ms) rather than an hour or the 1ms base backoff
+        assert!(elapsed >= Duration::from_millis(150) && elapsed < Duration::from_secs(5), "{:?}", elapsed);
+    }
+
+    #[tokio::test]
+    async fn test_network_errors_are_retried_without_status() {
+        // Bind then drop to get a port nothing listens on
+        let addr = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+        let result = run(&format!("http://{}/hook", addr)).await;
+        assert!(!result.delivered);
+        assert_eq!(result.attempts, 4);
+        assert_eq!(result.status, None);
+        assert!(result.error.is_some());
+    }
+
+    #[test]
+    fn test_agent_matches_are_unioned_per_agent() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let sub = |agent_id: Uuid, channels: Vec<DeliveryChannel>, payload_version: Option<u16>| SelectorSubscription {
+            id: Uuid::new_v4(),
+            owner_id: Uuid::nil(),
+            agent_id,
+            selector: serde_json::from_value(json!({ "any_tags": ["x"] })).unwrap(),
+            channels,
+            expires_at: None,
+            payload_version,
+        };
+        let subs = [sub(a, vec![DeliveryChannel::Sse], Some(2)), sub(b, vec![DeliveryChannel::Nats], None), sub(a, vec![DeliveryChannel::Webhook, DeliveryChannel::Sse], Some(1))];
+        assert_eq!(agent_matches(subs.iter().collect()), vec![
+            AgentMatch { agent_id: a, channels: vec![DeliveryChannel::Sse, DeliveryChannel::Webhook], pin: Some(PayloadVersion::V2), webhook_selectors: vec![(subs[2].id, Some(PayloadVersion::V1))] },
+            AgentMatch { agent_id: b, channels: vec![DeliveryChannel::Nats], pin: None, webhook_selectors: vec![] },
+        ]);
+    }
+
+    #[test]
+    fn test_bound_hooks_fire_only_for_their_selector() {
+        let (orders, refunds) = (Uuid::new_v4(), Uuid::new_v4());
+        let hook = |selector_id: Option<Uuid>| AgentWebhook { id: Uuid::new_v4(), url: "http://hooks.invalid".into(), payload_template: None, payload_version: None, selector_id, strict_ordering: false };
+        let matched = |webhook_selectors: Vec<(Uuid, Option<PayloadVersion>)>| AgentMatch {
+            agent_id: Uuid::nil(),
+            channels: DeliveryChannel::all(),
+            pin: Some(PayloadVersion::V2),
+            webhook_selectors,
+        };
+        let only_orders = matched(vec![(orders, Some(PayloadVersion::V1))]);
+        assert_eq!(hook_pin(&hook(Some(orders)), &only_orders), Some(Some(PayloadVersion::V1)));
+        assert_eq!(hook_pin(&hook(Some(refunds)), &only_orders), None);
+        assert_eq!(hook_pin(&hook(None), &only_orders), Some(Some(PayloadVersion::V2)));
+        let both = matched(vec![(orders, None), (refunds, None)]);
+        assert_eq!(hook_pin(&hook(Some(refunds)), &both), Some(None));
+        // Without a webhook-channel match nothing fires, bound or not
+        let no_webhook = matched(vec![]);
+        assert_eq!(hook_pin(&hook(None), &no_webhook), None);
+        assert_eq!(hook_pin(&hook(Some(orders)), &no_webhook), None);
+    }
+
+    #[test]
+    fn test_hook_pin_beats_selector_pin() {
+        let hook = |payload_version: Option<u16>| AgentWebhook { id: Uuid::nil(), url: "http://hooks.invalid".into(), payload_template: None, payload_version, selector_id: None, strict_ordering: false };
+        assert_eq!(hook_version(&hook(None), None), PayloadVersion::DEFAULT);
+        assert_eq!(hook_version(&hook(None), Some(PayloadVersion::V2)), PayloadVersion::V2);
+        assert_eq!(hook_version(&hook(Some(1)), Some(PayloadVersion::V2)), PayloadVersion::V1);
+    }
+
+    #[test]
+    fn test_owner_hook_matches_schema_tags_and_event_type() {
+        let hook = |schema_name: Option<&str>, any_tags: &[&str], event_types: &[&str]| OwnerWebhook {
+            id: Uuid::nil(),
+            url: "http://hooks.invalid".into(),
+            schema_name: schema_name.map(String::from),
+            any_tags: any_tags.iter().map(|t| t.to_string()).collect(),
+            event_types: event_types.iter().map(|t| t.to_string()).collect(),
+            created_at: chrono::Utc::now(),
+            has_secret: false,
+            secret: None,
+            strict_ordering: false,
+        };
+        let tags = vec!["audit:login".to_string(), "team:ops".to_string()];
+        let check = |h: &OwnerWebhook, event_type: &str, schema: Option<&str>| owner_hook_matches(h, event_type, &tags, schema, &json!({}));
+        let everything = hook(None, &[], &[]);
+        assert!(check(&everything, "breadcrumb.created", None) && check(&everything, "breadcrumb.updated", Some("knowledge.v1")));
+        let knowledge = hook(Some("knowledge.v1"), &[], &[]);
+        assert!(check(&knowledge, "breadcrumb.updated", Some("knowledge.v1")));
+        assert!(!check(&knowledge, "breadcrumb.updated", Some("note.v1")) && !check(&knowledge, "breadcrumb.updated", None));
+        // any_tags take the selectors' patterns
+        assert!(check(&hook(None, &["audit:*"], &[]), "breadcrumb.created", None));
+        assert!(!check(&hook(None, &["billing:*"], &[]), "breadcrumb.created", None));
+        let creations = hook(None, &[], &["breadcrumb.created"]);
+        assert!(check(&creations, "breadcrumb.created", None) && !check(&creations, "breadcrumb.updated", None));
+    }
+
+    #[test]
+    fn test_owner_webhooks_are_told_created_or_updated() {
+        let event = r#"{"type":"breadcrumb.updated","version":1}"#;
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&with_event_type(event, "breadcrumb.created")).unwrap(), json!({"type": "breadcrumb.created", "version": 1}));
+        assert_eq!(with_event_type("not json", "breadcrumb.created"), "not json");
+    }
+
+    #[tokio::test]
+    async fn test_delivery_sends_payload_version_header() {
+        let seen = Arc::new(std::sync::Mutex::new(None));
+        let recorder = seen.clone();
+        let app = Router::new().route("/hook", post(move |headers: HeaderMap| {
+            *recorder.lock().unwrap() = headers.get(payload_versions::HEADER).and_then(|v| v.to_str().ok()).map(String::from);
+            async { StatusCode::OK }
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        let result = deliver(&HttpClient::new(), &format!("http://{}/hook", addr), "{}", None, &[], PayloadVersion::V2, &policy()).await;
+        assert!(result.delivered);
+        assert_eq!(seen.lock().unwrap().as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn test_delivery_order_round_trips_through_the_event() {
+        let breadcrumb_id = Uuid::new_v4();
+        let updated_at: DateTime<Utc> = "2025-01-01T00:00:00.123456Z".parse().unwrap();
+        let event = json!({ "type": "breadcrumb.updated", "breadcrumb_id": breadcrumb_id, "version": 7, "updated_at": updated_at });
+        let order = DeliveryOrder::from_event(&event).unwrap();
+        assert_eq!(order, DeliveryOrder { breadcrumb_id, version: 7, updated_at });
+        assert_eq!(order.headers()[1..], [("X-RCRT-Version", "7".to_string()), ("X-RCRT-Updated-At", "2025-01-01T00:00:00.123456Z".to_string())]);
+        assert_eq!(DeliveryOrder::from_event(&json!({ "text": "templated" })), None);
+    }
+
+    fn versioned(key: (Uuid, Uuid), version: i32) -> Dispatch {
+        Dispatch {
+            owner_id: Uuid::nil(), target: WebhookTarget { hook: HookRef::Owner(key.0), url: "http://hooks.invalid".into() },
+            body: json!({ "version": version }).to_string(), secret: None, delivery_id: None, version: PayloadVersion::DEFAULT,
+            order: Some(DeliveryOrder { breadcrumb_id: key.1, version, updated_at: chrono::Utc::now() }),
+        }
+    }
+
+    #[test]
+    fn test_ordered_queue_waits_in_version_order_and_overflows() {
+        let queues = OrderedQueues::new(2);
+        let key = (Uuid::new_v4(), Uuid::new_v4());
+        assert!(matches!(queues.push(key, versioned(key, 1)), Queued::Send(_)));
+        assert!(matches!(queues.push(key, versioned(key, 3)), Queued::Waiting));
+        assert!(matches!(queues.push(key, versioned(key, 2)), Queued::Waiting));
+        assert!(matches!(queues.push(key, versioned(key, 4)), Queued::Overflow(d) if d.order.unwrap().version == 4));
+        // Another breadcrumb of the same webhook has its own queue
+        assert!(matches!(queues.push((key.0, Uuid::new_v4()), versioned(key, 1)), Queued::Send(_)));
+        let waiting: Vec<i32> = std::iter::from_fn(|| queues.next(key)).map(|d| d.order.unwrap().version).collect();
+        assert_eq!(waiting, vec![2, 3]);
+        assert!(!queues.queues.lock().unwrap().contains_key(&key));
+        assert!(matches!(queues.push(key, versioned(key, 5)), Queued::Send(_)));
+    }
+
+    #[tokio::test]
+    async fn test_strict_ordering_holds_later_versions_behind_a_retry() {
+        use crate::auth::{AuthConfig, AuthMode};
+        use crate::test_support::{offline_db, state};
+
+        let auth = AuthConfig::new(AuthMode::Disabled { owner_id: Uuid::new_v4(), agent_id: Uuid::new_v4() }, None, None, None, None).unwrap();
+        let state = state(offline_db(), auth).await;
+        // v1 gets a 503 the first time and is retried; the endpoint records the order it accepts versions in
+        let run = |strict: bool| {
+            let state = state.clone();
+            async move {
+                let accepted = Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+                let refused = Arc::new(AtomicUsize::new(0));
+                let (recorder, refusals) = (accepted.clone(), refused.clone());
+                let app = Router::new().route("/hook", post(move |headers: HeaderMap| {
+                    let version = headers.get("X-RCRT-Version").and_then(|v| v.to_str().ok()).unwrap_or_default().to_string();
+                    let status = if version == "1" && refusals.fetch_add(1, Ordering::SeqCst) == 0 {
+                        StatusCode::SERVICE_UNAVAILABLE
+                    } else {
+                        recorder.lock().unwrap().push(version);
+                        StatusCode::OK
+                    };
+                    async move { status }
+                }));
+                let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+                let url = format!("http://{}/hook", listener.local_addr().unwrap());
+                tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+                let key = (Uuid::new_v4(), Uuid::new_v4());
+                for version in [1, 2] {
+                    send(&state, Dispatch { target: WebhookTarget { hook: HookRef::Owner(key.0), url: url.clone() }, ..versioned(key, version) }, strict);
+                }
+                for _ in 0..150 {
+                    if accepted.lock().unwrap().len() == 2 && !state.webhook_queues.queues.lock().unwrap().contains_key(&key) {
+                        break;
+                    }
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+                let order = accepted.lock().unwrap().clone();
+                order
+            }
+        };
+        // By default v2 overtakes the retrying v1; receivers drop the late v1 by its version
+        assert_eq!(run(false).await, vec!["2", "1"]);
+        assert_eq!(run(true).await, vec!["1", "2"]);
+        assert!(state.webhook_queues.queues.lock().unwrap().is_empty());
+    }
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_failing_webhook_is_disabled_and_listed(pool: sqlx::PgPool) {
+        use crate::auth::{AuthConfig, AuthMode};
+        use crate::test_support::{request, send, state};
+        use rcrt_core::db::Db;
+
+        let (owner_id, agent_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let db = Db { pool };
+        db.ensure_tenant(owner_id, "Webhook Stats Test").await.unwrap();
+        db.upsert_agent(owner_id, agent_id, vec!["subscriber".into()]).await.unwrap();
+        let auth = AuthConfig::new(AuthMode::Disabled { owner_id, agent_id }, None, None, None, None).unwrap();
+        let state = AppState { webhook_auto_disable_after_failures: 2, ..state(db.clone(), auth).await };
+        let (gone, _) = mock_endpoint(vec![410], None).await;
+        let (ok, _) = mock_endpoint(vec![200], None).await;
+        let gone_id = db.create_agent_webhook(owner_id, agent_id, &gone, None, None, None, false).await.unwrap().unwrap();
+        let ok_id = db.create_agent_webhook(owner_id, agent_id, &ok, None, None, None, false).await.unwrap().unwrap();
+
+        let dispatch = |webhook_id: Uuid, url: &str| dispatch_webhook(state.clone(), Dispatch {
+            owner_id, target: WebhookTarget { hook: HookRef::Agent { agent_id, webhook_id: Some(webhook_id) }, url: url.to_string() },
+            body: "{}".into(), secret: None, delivery_id: None, version: PayloadVersion::DEFAULT, order: None,
+        });
+        dispatch(gone_id, &gone).await;
+        dispatch(ok_id, &ok).await;
+        assert_eq!(db.list_agent_webhooks(owner_id, agent_id).await.unwrap().len(), 2);
+        // The second 410 in a row is the threshold
+        dispatch(gone_id, &gone).await;
+        let active: Vec<Uuid> = db.list_agent_webhooks(owner_id, agent_id).await.unwrap().into_iter().map(|h| h.id).collect();
+        assert_eq!(active, vec![ok_id]);
+
+        let app = crate::build_app(state);
+        let list = |query: &'static str| {
+            let app = &app;
+            async move {
+                let (status, body) = send(app, request("GET", &format!("/agents/{}/webhooks?{}", agent_id, query), None, None)).await;
+                assert_eq!(status, StatusCode::OK, "{}", query);
+                body.as_array().unwrap().clone()
+            }
+        };
+        let failing = list("failing=true&active=false").await;
+        assert_eq!(failing.len(), 1);
+        assert_eq!(failing[0]["id"], json!(gone_id));
+        assert_eq!((failing[0]["consecutive_failures"].clone(), failing[0]["total_deliveries"].clone()), (json!(2), json!(2)));
+        assert!(failing[0]["last_success_at"].is_null() && failing[0]["last_failure_at"].is_string());
+        let healthy = list("").await;
+        assert_eq!(healthy.len(), 1);
+        assert_eq!((healthy[0]["id"].clone(), healthy[0]["total_deliveries"].clone()), (json!(ok_id), json!(1)));
+        assert!(healthy[0]["last_success_at"].is_string());
+        assert_eq!(list("include_inactive=true&order=total_deliveries").await.iter().map(|h| h["id"].clone()).collect::<Vec<_>>(), vec![json!(gone_id), json!(ok_id)]);
+        let (status, _) = send(&app, request("GET", &format!("/agents/{}/webhooks?order=url", agent_id), None, None)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+    #[cfg(feature = "db-tests")]
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_owner_webhook_delivery_and_dlq_retry(pool: sqlx::PgPool) {
+        use crate::auth::{AuthConfig, AuthMode};
+        use crate::test_support::{crumb, request, send, state};
+        use rcrt_core::db::Db;
+        use rcrt_core::models::BreadcrumbCreate;
+
+        let (owner_id, agent_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let db = Db { pool };
+        db.ensure_tenant(owner_id, "Owner Webhook Test").await.unwrap();
+        let auth = AuthConfig::new(AuthMode::Disabled { owner_id, agent_id }, None, None, None, None).unwrap();
+        let state = state(db.clone(), auth).await;
+        let app = crate::build_app(state.clone());
+
+        // Gone on the first delivery, then back for the retry
+        let (url, hits) = mock_endpoint(vec![410, 200], None).await;
+        let (status, _) = send(&app, request("POST", "/webhooks", None, Some(json!({ "url": url, "event_types": ["breadcrumb.deleted"] })))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, hook) = send(&app, request("POST", "/webhooks", None, Some(json!({ "url": url, "secret": "s3cret", "schema_name": "knowledge.v1", "event_types": ["breadcrumb.created"] })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", hook);
+        assert_eq!((hook["has_secret"].clone(), hook.get("secret")), (json!(true), None));
+        let hook_id: Uuid = hook["id"].as_str().unwrap().parse().unwrap();
+        assert_eq!(send(&app, request("GET", &format!("/webhooks/{}", hook_id), None, None)).await.1["url"], json!(url));
+        assert_eq!(send(&app, request("GET", "/webhooks", None, None)).await.1.as_array().unwrap().len(), 1);
+
+        let create = |schema_name: &str| BreadcrumbCreate {
+            title: "Runbook".into(), context: json!({ "body": "restart it" }), ..crumb(schema_name, &["ops"])
+        };
+        let fanout = |bc: rcrt_core::models::Breadcrumb| {
+            let state = state.clone();
+            async move {
+                let payload = events::breadcrumb_event("breadcrumb.updated", owner_id, &bc).to_string();
+                fanout_events_and_webhooks(&state, owner_id, &bc, &payload).await;
+            }
+        };
+        let dlq = || {
+            let db = &db;
+            async move { db.list_webhook_dlq(owner_id).await.unwrap() }
+        };
+        async fn settle<F: std::future::Future<Output = bool>>(mut done: impl FnMut() -> F) {
+            for _ in 0..100 {
+                if done().await { return; }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            panic!("dispatch didn't settle");
+        }
+
+        // Neither another schema nor an update of the right one is for this hook
+        let note = db.create_breadcrumb_for(owner_id, None, None, create("note.v1")).await.unwrap();
+        fanout(note).await;
+        let knowledge = db.create_breadcrumb_for(owner_id, None, None, create("knowledge.v1")).await.unwrap();
+        let update = rcrt_core::models::Breadcrumb { version: 2, ..knowledge.clone() };
+        fanout(update).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+
+        // The creation is, and its 410 dead-letters under the owner webhook
+        fanout(knowledge.clone()).await;
+        settle(|| {
+            let dlq = &dlq;
+            async move { !dlq().await.is_empty() }
+        }).await;
+        let entries = dlq().await;
+        let (dlq_id, dlq_agent, _, payload, _, last_status, _, owner_webhook_id) = entries[0].clone();
+        assert_eq!((dlq_agent, owner_webhook_id, last_status), (None, Some(hook_id), Some(410)));
+        assert_eq!((payload["type"].clone(), payload["breadcrumb_id"].clone()), (json!("breadcrumb.created"), json!(knowledge.id)));
+        let (_, listed) = send(&app, request("GET", "/dlq", None, None)).await;
+        assert_eq!((listed[0]["agent_id"].clone(), listed[0]["owner_webhook_id"].clone()), (serde_json::Value::Null, json!(hook_id)));
+
+        let (status, _) = send(&app, request("POST", &format!("/dlq/{}/retry", dlq_id), None, None)).await;
+        assert_eq!(status, StatusCode::OK);
+        settle(|| {
+            let hits = hits.clone();
+            async move { hits.load(Ordering::SeqCst) == 2 }
+        }).await;
+        assert!(dlq().await.is_empty());
+
+        let (status, _) = send(&app, request("DELETE", &format!("/webhooks/{}", hook_id), None, None)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(send(&app, request("GET", &format!("/webhooks/{}", hook_id), None, None)).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(send(&app, request("DELETE", &format!("/webhooks/{}", hook_id), None, None)).await.0, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,1943 @@
+//! API Tests
+//! The full router from build_app, driven with tower's oneshot; DB-backed cases need the `db-tests` feature
+
+use axum::{body::Body, http::{header, Request, StatusCode}, Router};
+use rcrt_core::db::Db;
+use rcrt_server::{auth::{self, AuthConfig, AuthMode}, build_app, AppState};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+// state, offline_db, request and send; the file reaches `auth` and `AppState` through the imports above.
+// `crumb` is for the unit tests' fixtures
+#[path = "../src/test_support.rs"]
+#[allow(dead_code)]
+mod test_support;
+use test_support::{offline_db, request, send, state};
+
+const PUBLIC_PEM: &str = include_str!("../testdata/jwt_rs256_public.pem");
+const PRIVATE_PEM: &str = include_str!("../testdata/jwt_rs256_private.pem");
+
+fn jwt_auth() -> AuthConfig {
+    AuthConfig::new(AuthMode::Jwt, Some(PUBLIC_PEM), Some(PRIVATE_PEM), None, None).unwrap()
+}
+
+async fn app(db: Db, auth: AuthConfig) -> Router {
+    build_app(state(db, auth).await)
+}
+
+#[tokio::test]
+async fn test_health_needs_no_auth() {
+    let app = app(offline_db(), jwt_auth()).await;
+    let res = app.oneshot(request("GET", "/health", None, None)).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(&axum::body::to_bytes(res.into_body(), usize::MAX).await.unwrap()[..], b"ok");
+}
+
+#[tokio::test]
+async fn test_ready_fails_without_the_database() {
+    let app = app(offline_db(), jwt_auth()).await;
+    let (status, body) = send(&app, request("GET", "/ready", None, None)).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["status"], "not_ready");
+    assert_eq!(body["failing"][0]["dependency"], "database");
+}
+
+#[tokio::test]
+async fn test_request_id_is_echoed_or_generated() {
+    let app = app(offline_db(), jwt_auth()).await;
+    let mut req = request("GET", "/health", None, None);
+    req.headers_mut().insert("x-request-id", "dash-7".parse().unwrap());
+    let res = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(res.headers()["x-request-id"], "dash-7");
+
+    // Also on rejected requests, which are the ones worth correlating
+    let res = app.oneshot(request("GET", "/breadcrumbs", None, None)).await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert!(Uuid::parse_str(res.headers()["x-request-id"].to_str().unwrap()).is_ok());
+}
+
+#[tokio::test]
+async fn test_breadcrumb_routes_reject_missing_or_bad_tokens() {
+    let app = app(offline_db(), jwt_auth()).await;
+    let id = Uuid::new_v4();
+    for (method, uri) in [("GET", "/breadcrumbs".to_string()), ("GET", format!("/breadcrumbs/{}", id)), ("DELETE", format!("/breadcrumbs/{}", id))] {
+        let (status, _) = send(&app, request(method, &uri, None, None)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{} {} without a token", method, uri);
+        let (status, _) = send(&app, request(method, &uri, Some("not-a-jwt"), None)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{} {} with a garbage token", method, uri);
+    }
+}
+
+#[tokio::test]
+async fn test_jwt_mode_without_public_key_rejects_requests() {
+    let auth = AuthConfig::new(AuthMode::Jwt, None, None, None, None).unwrap();
+    let app = app(offline_db(), auth).await;
+    let (status, _) = send(&app, request("GET", "/breadcrumbs", Some("any"), None)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_metrics_open_without_token() {
+    let app = app(offline_db(), jwt_auth()).await;
+    let res = app.oneshot(request("GET", "/metrics", None, None)).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_metrics_token_required_when_set() {
+    let mut auth = jwt_auth();
+    auth.metrics_token = Some("scrape-me".into());
+    let app = app(offline_db(), auth).await;
+    for token in [None, Some("wrong")] {
+        let res = app.clone().oneshot(request("GET", "/metrics", token, None)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED, "token {:?}", token);
+        assert_eq!(res.headers()[header::WWW_AUTHENTICATE], "Bearer");
+    }
+
+    let res = app.oneshot(request("GET", "/metrics", Some("scrape-me"), None)).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    // The rejected scrapes were counted like any other response
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    assert!(String::from_utf8_lossy(&body).contains(r#"path="/metrics",status="401""#));
+}
+
+#[cfg(feature = "db-tests")]
+mod db {
+    use super::*;
+
+    /// Token from the server's own /auth/token endpoint, which also registers the agent
+    async fn token(app: &Router, owner_id: Uuid, roles: &[&str]) -> String {
+        let (status, body) = send(app, request("POST", "/auth/token", None, Some(json!({
+            "owner_id": owner_id.to_string(), "agent_id": Uuid::new_v4().to_string(), "roles": roles
+        })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        body["token"].as_str().unwrap().to_string()
+    }
+
+    async fn setup(pool: sqlx::PgPool) -> (Router, Uuid) {
+        let db = Db { pool };
+        let owner_id = Uuid::new_v4();
+        db.ensure_tenant(owner_id, "API Test").await.unwrap();
+        (app(db, jwt_auth()).await, owner_id)
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_ready_reports_the_schema_version(pool: sqlx::PgPool) {
+        let (app, _) = setup(pool.clone()).await;
+        let (status, body) = send(&app, request("GET", "/ready", None, None)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["schema_version"], body["expected_schema_version"]);
+
+        // A replica running ahead of the migration another one hasn't finished
+        sqlx::query("delete from _sqlx_migrations where version = (select max(version) from _sqlx_migrations)").execute(&pool).await.unwrap();
+        let (status, body) = send(&app, request("GET", "/ready", None, None)).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["failing"][0]["dependency"], "schema");
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_breadcrumb_crud(pool: sqlx::PgPool) {
+        let (app, owner_id) = setup(pool).await;
+        let emitter = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let token = token(&app, owner_id, &["curator", "emitter", "subscriber"]).await;
+        let token = Some(token.as_str());
+
+        let (status, body) = send(&app, request("POST", "/breadcrumbs", token, Some(json!({
+            "title": "API test", "context": { "step": 1 }, "tags": ["test:api"]
+        })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let id = body["id"].as_str().unwrap().to_string();
+
+        let (status, body) = send(&app, request("GET", &format!("/breadcrumbs/{}", id), token, None)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["title"], "API test");
+        assert_eq!(body["context"]["step"], 1);
+        let (status, body) = send(&app, request("GET", &format!("/breadcrumbs/{}/full", id), token, None)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["version"], 1);
+
+        let patch = |query: &str, token: Option<&str>, version: i32, step: i32| {
+            let mut req = request("PATCH", &format!("/breadcrumbs/{}{}", id, query), token, Some(json!({ "context": { "step": step } })));
+            req.headers_mut().insert(header::IF_MATCH, format!("\"{}\"", version).parse().unwrap());
+            req
+        };
+        let (status, body) = send(&app, patch("", token, 1, 2)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let (status, body) = send(&app, patch("", token, 1, 3)).await;
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+        assert_eq!(body["error"], "version_mismatch");
+        assert_eq!((body["expected_version"].as_i64(), body["current_version"].as_i64()), (Some(1), Some(2)));
+        assert!(body["updated_at"].is_string());
+        assert!(body.get("context").is_none());
+        let (status, body) = send(&app, patch("?return_current=true", token, 1, 3)).await;
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+        assert_eq!(body["context"]["step"], 2);
+        let (_, body) = send(&app, request("GET", &format!("/breadcrumbs/{}", id), token, None)).await;
+        assert_eq!(body["context"]["step"], 2);
+
+        // force skips If-Match, but only for curators
+        let (status, _) = send(&app, patch("?force=true", Some(emitter.as_str()), 1, 3)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, body) = send(&app, patch("?force=true", token, 1, 3)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let (_, body) = send(&app, request("GET", &format!("/breadcrumbs/{}/full", id), token, None)).await;
+        assert_eq!((body["version"].as_i64(), &body["context"]["step"]), (Some(3), &json!(3)));
+
+        let (status, body) = send(&app, request("GET", "/breadcrumbs?tag=test:api", token, None)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.as_array().unwrap().len(), 1);
+        assert_eq!(body[0]["id"], id.as_str());
+
+        let (status, _) = send(&app, request("DELETE", &format!("/breadcrumbs/{}", id), token, None)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&app, request("GET", &format!("/breadcrumbs/{}", id), token, None)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = send(&app, request("DELETE", &format!("/breadcrumbs/{}", id), token, None)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_rollback_and_pruned_versions(pool: sqlx::PgPool) {
+        let (app, owner_id) = setup(pool.clone()).await;
+        let token = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let token = Some(token.as_str());
+
+        let (_, body) = send(&app, request("POST", "/breadcrumbs", token, Some(json!({
+            "title": "Rollback", "context": { "step": 1 }, "tags": []
+        })))).await;
+        let id = body["id"].as_str().unwrap().to_string();
+        for step in 2..=3 {
+            let (status, _) = send(&app, request("PATCH", &format!("/breadcrumbs/{}", id), token, Some(json!({ "context": { "step": step } })))).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+
+        let rollback = |version: i32| request("POST", &format!("/breadcrumbs/{}/rollback", id), token, Some(json!({ "version": version })));
+        let (status, body) = send(&app, rollback(2)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["version"], 4);
+        let (_, body) = send(&app, request("GET", &format!("/breadcrumbs/{}", id), token, None)).await;
+        assert_eq!(body["context"]["step"], 2);
+        // A stale If-Match gets the same structured 412 as PATCH
+        let mut stale = rollback(1);
+        stale.headers_mut().insert(header::IF_MATCH, "\"3\"".parse().unwrap());
+        let (status, body) = send(&app, stale).await;
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED, "{}", body);
+        assert_eq!(body["error"], "version_mismatch");
+        assert_eq!((body["expected_version"].as_i64(), body["current_version"].as_i64()), (Some(3), Some(4)));
+        let (status, _) = send(&app, rollback(4)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = send(&app, rollback(9)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // What the hygiene prune leaves behind: the row is gone but the version existed
+        sqlx::query("DELETE FROM breadcrumb_history WHERE breadcrumb_id = $1 AND version = 3")
+            .bind(Uuid::parse_str(&id).unwrap())
+            .execute(&pool)
+            .await
+            .unwrap();
+        let (status, _) = send(&app, rollback(3)).await;
+        assert_eq!(status, StatusCode::GONE);
+
+        let (status, body) = send(&app, request("GET", &format!("/breadcrumbs/{}/retention", id), token, None)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["version"], 4);
+        assert_eq!(body["history"]["versions"], 3);
+        assert_eq!(body["history"]["oldest_version"], 1);
+        assert!(body["history"]["bytes"].as_i64().unwrap() > 0);
+        assert!(body["history"]["policy"].get("keep_versions").is_some());
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_concurrent_tag_adds_both_survive(pool: sqlx::PgPool) {
+        let (app, owner_id) = setup(pool).await;
+        let first = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let second = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let (_, body) = send(&app, request("POST", "/breadcrumbs", Some(&first), Some(json!({
+            "title": "Tagged", "context": {}, "tags": ["shared"]
+        })))).await;
+        let id = body["id"].as_str().unwrap().to_string();
+        let tags = |token: &str, body: Value| request("POST", &format!("/breadcrumbs/{}/tags", id), Some(token), Some(body));
+
+        // Interleaved writers that never saw each other's tags, and no If-Match
+        let adds = |token: String, agent: &'static str| {
+            let (app, tags) = (app.clone(), &tags);
+            async move {
+                for i in 0..10 {
+                    let (status, body) = send(&app, tags(&token, json!({ "add": [format!("{}:{}", agent, i)] }))).await;
+                    assert_eq!(status, StatusCode::OK, "{}", body);
+                }
+            }
+        };
+        tokio::join!(adds(first.clone(), "a"), adds(second, "b"));
+
+        let (status, body) = send(&app, tags(&first, json!({ "add": ["a:0", "done"], "remove": ["shared"] }))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let stored: Vec<&str> = body["tags"].as_array().unwrap().iter().map(|t| t.as_str().unwrap()).collect();
+        assert_eq!(stored.len(), 21);
+        assert!((0..10).all(|i| stored.contains(&format!("a:{}", i).as_str()) && stored.contains(&format!("b:{}", i).as_str())));
+        assert_eq!((stored.last(), body["version"].as_i64()), (Some(&"done"), Some(22)));
+        let (_, history) = send(&app, request("GET", &format!("/breadcrumbs/{}/history", id), Some(&first), None)).await;
+        assert_eq!(history.as_array().unwrap().len(), 22);
+
+        let (status, _) = send(&app, tags(&first, json!({ "add": ["x"], "remove": ["x"] }))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = send(&app, tags(&first, json!({ "add": ["x".repeat(300)] }))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let too_many: Vec<String> = (0..50).map(|i| format!("more:{}", i)).collect();
+        let (status, body) = send(&app, tags(&first, json!({ "add": too_many }))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+        let (_, body) = send(&app, request("GET", &format!("/breadcrumbs/{}", id), Some(&first), None)).await;
+        assert_eq!(body["tags"].as_array().unwrap().len(), 21);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_list_excludes_tags_and_filters_time_ranges(pool: sqlx::PgPool) {
+        let (app, owner_id) = setup(pool).await;
+        let token = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let token = Some(token.as_str());
+        for (title, tags) in [("k8s", vec!["kb"]), ("old k8s", vec!["kb", "archived"]), ("draft k8s", vec!["kb", "draft"])] {
+            let (status, _) = send(&app, request("POST", "/breadcrumbs", token, Some(json!({ "title": title, "context": {}, "tags": tags })))).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+        let titles = |body: Value| body.as_array().unwrap().iter().map(|b| b["title"].as_str().unwrap().to_string()).collect::<Vec<_>>();
+
+        let (status, body) = send(&app, request("GET", "/breadcrumbs?tag=kb&exclude_tag=archived&exclude_tag=draft", token, None)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(titles(body), vec!["k8s"]);
+        let (_, body) = send(&app, request("GET", "/breadcrumbs?tag=kb&exclude_tag=archived", token, None)).await;
+        assert_eq!(titles(body).len(), 2);
+
+        let (_, body) = send(&app, request("GET", "/breadcrumbs?tag=kb&created_after=2000-01-01T00:00:00Z&created_before=2999-01-01T00:00:00Z", token, None)).await;
+        assert_eq!(titles(body).len(), 3);
+        let (_, body) = send(&app, request("GET", "/breadcrumbs?tag=kb&updated_after=2999-01-01T00:00:00Z", token, None)).await;
+        assert!(titles(body).is_empty());
+        let (status, _) = send(&app, request("GET", "/breadcrumbs?created_after=yesterday", token, None)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_search_combines_tag_session_schema_and_time_filters(pool: sqlx::PgPool) {
+        let db = Db { pool: pool.clone() };
+        let (app, owner_id) = setup(pool).await;
+        let token = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let token = Some(token.as_str());
+        let crumbs = [
+            ("s1 note", "note.v1", vec!["kb", "session:s1"]),
+            ("s1 tool", "tool.request.v1", vec!["session:s1"]),
+            ("s2 note", "note.v1", vec!["kb", "session:s2"]),
+            ("loose note", "note.v1", vec!["kb"]),
+        ];
+        for (title, schema_name, tags) in crumbs {
+            let (status, body) = send(&app, request("POST", "/breadcrumbs", token, Some(json!({
+                "title": title, "schema_name": schema_name, "context": {}, "tags": tags
+            })))).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+            let id: Uuid = body["id"].as_str().unwrap().parse().unwrap();
+            db.set_breadcrumb_embedding(owner_id, None, id, vec![0.5; 384]).await.unwrap();
+        }
+        let qvec = vec!["0.5"; 384].join(",");
+        let search = |filters: &str| {
+            let req = request("GET", &format!("/breadcrumbs/search?nn=10&qvec={}&{}", qvec, filters), token, None);
+            let app = app.clone();
+            async move {
+                let (status, body) = send(&app, req).await;
+                assert_eq!(status, StatusCode::OK, "{}", body);
+                let mut titles: Vec<String> = body.as_array().unwrap().iter().map(|b| b["title"].as_str().unwrap().to_string()).collect();
+                titles.sort();
+                titles
+            }
+        };
+
+        assert_eq!(search("session=s1").await, vec!["s1 note", "s1 tool"]);
+        assert_eq!(search("session=session:s1").await, vec!["s1 note", "s1 tool"]);
+        assert_eq!(search("tag=kb&session=s1").await, vec!["s1 note"]);
+        assert_eq!(search("session=s1&schema_name=tool.request.v1").await, vec!["s1 tool"]);
+        assert_eq!(search("tag=kb&schema_name=note.v1").await, vec!["loose note", "s1 note", "s2 note"]);
+        assert_eq!(search("tag=kb&session=s2&schema_name=note.v1&created_after=2000-01-01T00:00:00Z").await, vec!["s2 note"]);
+        assert!(search("tag=kb&session=s1&updated_before=2000-01-01T00:00:00Z").await.is_empty());
+        assert!(search("session=s3").await.is_empty());
+        let (status, _) = send(&app, request("GET", &format!("/breadcrumbs/search?qvec={}&session=", qvec), token, None)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_search_target_ranks_by_title_content_or_both(pool: sqlx::PgPool) {
+        let db = Db { pool: pool.clone() };
+        let (app, owner_id) = setup(pool).await;
+        let token = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let token = Some(token.as_str());
+        let unit = |i: usize| { let mut v = vec![0.0f32; 384]; v[i] = 1.0; v };
+        let mut between = unit(0);
+        between[1] = 1.0;
+        let between: Vec<f32> = between.iter().map(|x| x / 2f32.sqrt()).collect();
+        // (title, content vector, title vector); the query is unit(0)
+        let crumbs = [("exact title", between, Some(unit(0))), ("exact content", unit(0), Some(unit(1))), ("untitled", unit(1), None)];
+        for (title, content, title_vec) in crumbs {
+            let (status, body) = send(&app, request("POST", "/breadcrumbs", token, Some(json!({ "title": title, "context": {}, "tags": ["ranked"] })))).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+            let id: Uuid = body["id"].as_str().unwrap().parse().unwrap();
+            db.set_breadcrumb_embedding(owner_id, None, id, content).await.unwrap();
+            if let Some(v) = title_vec {
+                db.set_breadcrumb_title_embedding(owner_id, None, id, v).await.unwrap();
+            }
+        }
+        let qvec = unit(0).iter().map(|x| x.to_string()).collect::<Vec<_>>().join(",");
+        let search = |target: &str| request("GET", &format!("/breadcrumbs/search?tag=ranked&nn=5&qvec={}{}", qvec, target), token, None);
+        let titles = |body: Value| body.as_array().unwrap().iter().map(|b| b["title"].as_str().unwrap().to_string()).collect::<Vec<_>>();
+
+        let (status, body) = send(&app, search("")).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(titles(body), vec!["exact content", "exact title", "untitled"]);
+        // Title-only search skips rows without a title vector
+        let (_, body) = send(&app, search("&target=title")).await;
+        assert_eq!(titles(body), vec!["exact title", "exact content"]);
+        // Half weight each: 0.5 * 0.29 + 0 beats 0 + 0.5 * 1
+        let (_, body) = send(&app, search("&target=both")).await;
+        assert_eq!(titles(body), vec!["exact title", "exact content", "untitled"]);
+        let (status, _) = send(&app, search("&target=nearest")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_list_preview_is_omitted_for_rows_the_caller_cannot_read_in_full(pool: sqlx::PgPool) {
+        let (app, owner_id) = setup(pool).await;
+        let author = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let other = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let curator = token(&app, owner_id, &["curator", "subscriber"]).await;
+        for (title, sensitivity, note) in [("plain", "low", "Grüße aus Köln, bis bald"), ("medical", "pii", "Diagnosis pending review")] {
+            let (status, body) = send(&app, request("POST", "/breadcrumbs", Some(&author), Some(json!({
+                "title": title, "context": { "note": note, "patient_id": "p-1" }, "tags": ["preview"], "sensitivity": sensitivity
+            })))).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+        }
+        let previews = |token: &str, query: &str| {
+            let req = request("GET", &format!("/breadcrumbs?tag=preview{}", query), Some(token), None);
+            let app = app.clone();
+            async move {
+                let (status, body) = send(&app, req).await;
+                assert_eq!(status, StatusCode::OK, "{}", body);
+                let mut previews: Vec<(String, Option<String>)> = body.as_array().unwrap().iter()
+                    .map(|b| (b["title"].as_str().unwrap().to_string(), b.get("context_preview").map(|p| p.as_str().unwrap().to_string())))
+                    .collect();
+                previews.sort();
+                previews
+            }
+        };
+
+        let medical = |preview: Option<&str>| ("medical".to_string(), preview.map(str::to_string));
+        let plain = ("plain".to_string(), Some("Grüße".to_string()));
+        assert_eq!(previews(&other, "&preview=5").await, vec![medical(None), plain.clone()]);
+        assert_eq!(previews(&author, "&preview=5").await, vec![medical(Some("Diagn")), plain.clone()]);
+        assert_eq!(previews(&curator, "&preview=5").await, vec![medical(Some("Diagn")), plain]);
+        assert_eq!(previews(&author, "").await, vec![medical(None), ("plain".to_string(), None)]);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_search_hides_sensitive_rows_from_other_agents(pool: sqlx::PgPool) {
+        let db = Db { pool: pool.clone() };
+        let (app, owner_id) = setup(pool).await;
+        let author = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let other = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let curator = token(&app, owner_id, &["curator", "subscriber"]).await;
+        for (title, sensitivity) in [("plain", "low"), ("medical", "pii"), ("launch codes", "secret")] {
+            let (status, body) = send(&app, request("POST", "/breadcrumbs", Some(&author), Some(json!({
+                "title": title, "context": {}, "tags": ["sensitive-search"], "sensitivity": sensitivity
+            })))).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+            let id: Uuid = body["id"].as_str().unwrap().parse().unwrap();
+            db.set_breadcrumb_embedding(owner_id, None, id, vec![0.5; 384]).await.unwrap();
+        }
+        let qvec = vec!["0.5"; 384].join(",");
+        let titles = |token: &str| {
+            let req = request("GET", &format!("/breadcrumbs/search?tag=sensitive-search&nn=10&qvec={}", qvec), Some(token), None);
+            let app = app.clone();
+            async move {
+                let (status, body) = send(&app, req).await;
+                assert_eq!(status, StatusCode::OK, "{}", body);
+                let mut titles: Vec<String> = body.as_array().unwrap().iter().map(|b| b["title"].as_str().unwrap().to_string()).collect();
+                titles.sort();
+                titles
+            }
+        };
+
+        assert_eq!(titles(&other).await, vec!["plain"]);
+        assert_eq!(titles(&author).await, vec!["launch codes", "medical", "plain"]);
+        assert_eq!(titles(&curator).await, vec!["launch codes", "medical", "plain"]);
+        let (status, _) = send(&app, request("POST", "/admin/embeddings/clear-sensitive", Some(&other), None)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_encrypted_context_is_sealed_at_rest_and_opened_for_read_full(pool: sqlx::PgPool) {
+        // 32 zero bytes
+        std::env::set_var("LOCAL_KEK_BASE64", "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=");
+        let (app, owner_id) = setup(pool.clone()).await;
+        let author = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let other = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let curator = token(&app, owner_id, &["curator", "subscriber"]).await;
+        let (status, body) = send(&app, request("POST", "/breadcrumbs", Some(&author), Some(json!({
+            "title": "vault", "context": { "api_key": "sk-live-123" }, "tags": ["encrypted"], "encrypt": true
+        })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let id = body["id"].as_str().unwrap().to_string();
+
+        let (stored, ciphertext): (Value, Vec<u8>) = sqlx::query_as("select context, context_encrypted from breadcrumbs where id = $1::uuid")
+            .bind(&id).fetch_one(&pool).await.unwrap();
+        assert_eq!(stored, json!({ "encrypted": true }));
+        assert!(!String::from_utf8_lossy(&ciphertext).contains("sk-live-123"));
+
+        let (_, body) = send(&app, request("GET", &format!("/breadcrumbs/{}", id), Some(&author), None)).await;
+        assert_eq!(body["context"], json!({ "encrypted": true }));
+        let (_, body) = send(&app, request("GET", "/breadcrumbs?tag=encrypted&include_context=true", Some(&author), None)).await;
+        assert!(!body.to_string().contains("sk-live-123"));
+        for token in [&author, &curator] {
+            let (status, body) = send(&app, request("GET", &format!("/breadcrumbs/{}/full", id), Some(token), None)).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+            assert_eq!(body["context"]["api_key"], "sk-live-123");
+        }
+        let (status, _) = send(&app, request("GET", &format!("/breadcrumbs/{}/full", id), Some(&other), None)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // Stays encrypted: a new context is sealed, and rollback restores the old ciphertext
+        let (status, body) = send(&app, request("PATCH", &format!("/breadcrumbs/{}", id), Some(&author), Some(json!({ "context": { "api_key": "sk-live-456" } })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let (status, body) = send(&app, request("POST", &format!("/breadcrumbs/{}/rollback", id), Some(&author), Some(json!({ "version": 1 })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let (_, body) = send(&app, request("GET", &format!("/breadcrumbs/{}/full", id), Some(&author), None)).await;
+        assert_eq!((body["version"].as_i64(), &body["context"]["api_key"]), (Some(3), &json!("sk-live-123")));
+        let (_, body) = send(&app, request("GET", &format!("/breadcrumbs/{}/history", id), Some(&author), None)).await;
+        assert!(!body.to_string().contains("sk-live"));
+
+        // Server-read schemas can't be encrypted
+        let (status, _) = send(&app, request("POST", "/breadcrumbs", Some(&curator), Some(json!({
+            "title": "schema", "context": {}, "tags": [], "schema_name": "schema.def.v1", "encrypt": true
+        })))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_large_context_values_are_stored_as_references(pool: sqlx::PgPool) {
+        let (app, owner_id) = setup(pool.clone()).await;
+        let emitter = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let transcript = "speaker: hello there\n".repeat(2000);
+        let (status, body) = send(&app, request("POST", "/breadcrumbs", Some(&emitter), Some(json!({
+            "title": "call", "context": { "transcript": transcript, "summary": "short" }, "tags": ["call"]
+        })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let id = body["id"].as_str().unwrap().to_string();
+
+        let stored: Value = sqlx::query_scalar("select context from breadcrumbs where id = $1::uuid").bind(&id).fetch_one(&pool).await.unwrap();
+        let reference = stored["transcript"].clone();
+        let sha256 = reference["$rcrt_ref"].as_str().unwrap().to_string();
+        assert_eq!((sha256.len(), &reference["bytes"], &reference["content_type"]), (64, &json!(transcript.len()), &json!("text/plain")));
+        assert_eq!(stored["summary"], "short");
+
+        // The context view shows the reference unless asked; /full inlines unless told not to
+        let (_, body) = send(&app, request("GET", &format!("/breadcrumbs/{}", id), Some(&emitter), None)).await;
+        assert_eq!(body["context"]["transcript"], reference);
+        let (_, body) = send(&app, request("GET", &format!("/breadcrumbs/{}?inline=true", id), Some(&emitter), None)).await;
+        assert_eq!(body["context"]["transcript"], transcript);
+        let (_, body) = send(&app, request("GET", &format!("/breadcrumbs/{}/full", id), Some(&emitter), None)).await;
+        assert_eq!(body["context"]["transcript"], transcript);
+        let (_, body) = send(&app, request("GET", &format!("/breadcrumbs/{}/full?inline=false", id), Some(&emitter), None)).await;
+        assert_eq!(body["context"]["transcript"], reference);
+
+        // Writing the reference back, or the same text again, stores nothing new
+        let (status, body) = send(&app, request("PATCH", &format!("/breadcrumbs/{}", id), Some(&emitter), Some(json!({
+            "context": { "transcript": reference, "summary": "edited" }
+        })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let (status, body) = send(&app, request("PATCH", &format!("/breadcrumbs/{}", id), Some(&emitter), Some(json!({
+            "context": { "transcript": transcript, "summary": "again" }
+        })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let blobs = || sqlx::query_scalar::<_, i64>("select count(*) from attachments where owner_id = $1").bind(owner_id).fetch_one(&pool);
+        assert_eq!(blobs().await.unwrap(), 1);
+
+        let longer = format!("{}speaker: bye\n", transcript);
+        let (status, body) = send(&app, request("PATCH", &format!("/breadcrumbs/{}", id), Some(&emitter), Some(json!({
+            "context": { "transcript": longer, "summary": "final" }
+        })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(blobs().await.unwrap(), 2);
+        let (_, body) = send(&app, request("GET", &format!("/breadcrumbs/{}/full", id), Some(&emitter), None)).await;
+        assert_eq!((body["version"].as_i64(), &body["context"]["transcript"]), (Some(4), &json!(longer)));
+
+        // History keeps references, and the value of an older version still resolves
+        let (_, body) = send(&app, request("GET", &format!("/breadcrumbs/{}/history", id), Some(&emitter), None)).await;
+        assert!(!body.to_string().contains("speaker: hello"));
+        assert!(body.as_array().unwrap().iter().any(|v| v["context"]["transcript"] == reference));
+        let res = app.clone().oneshot(request("GET", &format!("/attachments/{}", sha256), Some(&emitter), None)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(axum::body::to_bytes(res.into_body(), usize::MAX).await.unwrap(), transcript.as_bytes());
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_as_of_returns_the_version_current_at_a_time(pool: sqlx::PgPool) {
+        let (app, owner_id) = setup(pool.clone()).await;
+        let emitter = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let (_, body) = send(&app, request("POST", "/breadcrumbs", Some(&emitter), Some(json!({
+            "title": "plan", "context": { "step": 1 }, "tags": ["plan"]
+        })))).await;
+        let id = body["id"].as_str().unwrap().to_string();
+        for step in [2, 3] {
+            let (status, body) = send(&app, request("PATCH", &format!("/breadcrumbs/{}", id), Some(&emitter), Some(json!({ "context": { "step": step } })))).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+        }
+        let (_, history) = send(&app, request("GET", &format!("/breadcrumbs/{}/history", id), Some(&emitter), None)).await;
+        let written: Vec<chrono::DateTime<chrono::Utc>> = history.as_array().unwrap().iter().rev()
+            .map(|v| v["updated_at"].as_str().unwrap().parse().unwrap())
+            .collect();
+        let ts = |t: chrono::DateTime<chrono::Utc>| t.to_rfc3339_opts(chrono::SecondsFormat::Micros, true);
+        let as_of = |t: chrono::DateTime<chrono::Utc>| request("GET", &format!("/breadcrumbs/{}/as_of?ts={}", id, ts(t)), Some(&emitter), None);
+
+        // Exactly at a version boundary the new version applies, just before it the old one
+        let (status, body) = send(&app, as_of(written[1])).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!((body["version"].as_i64(), &body["context"]["step"]), (Some(2), &json!(2)));
+        assert_eq!(body["superseded_at"].as_str().unwrap().parse::<chrono::DateTime<chrono::Utc>>().unwrap(), written[2]);
+        assert_eq!(body["title"], "plan");
+        assert_eq!(body["llm_hints_version"], "current");
+        let (_, body) = send(&app, as_of(written[1] - chrono::Duration::microseconds(1))).await;
+        assert_eq!((body["version"].as_i64(), &body["context"]["step"]), (Some(1), &json!(1)));
+
+        let (_, body) = send(&app, as_of(chrono::Utc::now())).await;
+        assert_eq!((body["version"].as_i64(), &body["superseded_at"]), (Some(3), &Value::Null));
+
+        let (status, _) = send(&app, as_of(written[0] - chrono::Duration::seconds(1))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // Same read permissions as the context view
+        let other_owner = Uuid::new_v4();
+        Db { pool }.ensure_tenant(other_owner, "Other Tenant").await.unwrap();
+        let stranger = token(&app, other_owner, &["emitter", "subscriber"]).await;
+        let (status, _) = send(&app, request("GET", &format!("/breadcrumbs/{}/as_of?ts={}", id, ts(chrono::Utc::now())), Some(&stranger), None)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_verify_reports_checksum_mismatches(pool: sqlx::PgPool) {
+        let (app, owner_id) = setup(pool.clone()).await;
+        let emitter = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let curator = token(&app, owner_id, &["curator", "subscriber"]).await;
+        let (_, body) = send(&app, request("POST", "/breadcrumbs", Some(&emitter), Some(json!({
+            "title": "ledger", "context": { "total": 10, "currency": "EUR" }, "tags": []
+        })))).await;
+        let id = body["id"].as_str().unwrap().to_string();
+
+        let (status, body) = send(&app, request("GET", &format!("/breadcrumbs/{}/verify?history=true", id), Some(&emitter), None)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["ok"], true);
+        assert_eq!(body["checks"].as_array().unwrap().len(), 2);
+
+        sqlx::query("update breadcrumbs set context = '{\"total\": 1000, \"currency\": \"EUR\"}' where id = $1::uuid").bind(&id).execute(&pool).await.unwrap();
+        let (_, body) = send(&app, request("GET", &format!("/breadcrumbs/{}/verify", id), Some(&emitter), None)).await;
+        assert_eq!(body["ok"], false);
+        let mismatch = &body["mismatches"][0];
+        assert_eq!((mismatch["source"].as_str(), mismatch["version"].as_i64()), (Some("current"), Some(1)));
+        assert_ne!(mismatch["stored_checksum"], mismatch["computed_checksum"]);
+
+        let (status, _) = send(&app, request("POST", "/admin/checksums/verify", Some(&emitter), None)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, body) = send(&app, request("POST", "/admin/checksums/verify?history=true", Some(&curator), None)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!((body["scanned"].as_i64(), body["mismatches"].as_array().unwrap().len()), (Some(1), 1));
+        let report = body["report_id"].as_str().expect("report recorded");
+        let (_, body) = send(&app, request("GET", &format!("/breadcrumbs/{}/full", report), Some(&curator), None)).await;
+        assert_eq!(body["schema_name"], "system.checksum.mismatch.v1");
+        assert_eq!(body["context"]["mismatches"][0]["breadcrumb_id"], id.as_str());
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_suggest_titles_and_tags(pool: sqlx::PgPool) {
+        let db = Db { pool: pool.clone() };
+        let (app, owner_id) = setup(pool).await;
+        let author = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let other = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let crumbs = [
+            ("Reopen the ticket", vec!["openai", "support"], "low"),
+            ("OpenAI rollout", vec!["openai", "rollout"], "low"),
+            ("Open questions", vec!["open-source"], "secret"),
+            ("Closed", vec!["closed"], "low"),
+        ];
+        for (title, tags, sensitivity) in crumbs {
+            let (status, body) = send(&app, request("POST", "/breadcrumbs", Some(&author), Some(json!({
+                "title": title, "context": {}, "tags": tags, "sensitivity": sensitivity
+            })))).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+        }
+        // Another tenant's matches stay out
+        let stranger = Uuid::new_v4();
+        db.ensure_tenant(stranger, "Other Tenant").await.unwrap();
+        let stranger = token(&app, stranger, &["emitter"]).await;
+        let (status, _) = send(&app, request("POST", "/breadcrumbs", Some(&stranger), Some(json!({ "title": "Open elsewhere", "context": {}, "tags": ["openai"] })))).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let suggest = |token: &str, query: &str| {
+            let req = request("GET", &format!("/breadcrumbs/suggest?{}", query), Some(token), None);
+            let app = app.clone();
+            async move {
+                let (status, body) = send(&app, req).await;
+                assert_eq!(status, StatusCode::OK, "{}", body);
+                body.as_array().unwrap().clone()
+            }
+        };
+
+        // Prefix matches first; the secret row only for its author
+        let titles = suggest(&author, "q=open").await;
+        let names: Vec<&str> = titles.iter().map(|t| t["title"].as_str().unwrap()).collect();
+        assert_eq!(names.len(), 3);
+        assert_eq!(names[2], "Reopen the ticket");
+        assert!(titles.iter().all(|t| t["id"].is_string() && t["score"].is_number()));
+        let titles = suggest(&other, "q=OPEN&kind=title").await;
+        assert_eq!(titles.iter().map(|t| t["title"].as_str().unwrap()).collect::<Vec<_>>(), vec!["OpenAI rollout", "Reopen the ticket"]);
+        assert_eq!(suggest(&other, "q=open&limit=1").await.len(), 1);
+
+        let tags = suggest(&author, "q=open&kind=tag").await;
+        let tags: Vec<(&str, i64)> = tags.iter().map(|t| (t["tag"].as_str().unwrap(), t["count"].as_i64().unwrap())).collect();
+        assert_eq!(tags, vec![("openai", 2), ("open-source", 1)]);
+        assert!(suggest(&author, "q=open_&kind=tag").await.is_empty());
+
+        for query in ["q=", "kind=tag", "q=open&kind=schema"] {
+            let (status, _) = send(&app, request("GET", &format!("/breadcrumbs/suggest?{}", query), Some(&author), None)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", query);
+        }
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_missed_events_match_selectors_and_page(pool: sqlx::PgPool) {
+        let (app, owner_id) = setup(pool).await;
+        let token = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let token = Some(token.as_str());
+        let (status, body) = send(&app, request("POST", "/subscriptions/selectors", token, Some(json!({ "any_tags": ["missed:yes"] })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        for (title, tag) in [("one", "missed:yes"), ("other", "missed:no"), ("two", "missed:yes")] {
+            let (status, body) = send(&app, request("POST", "/breadcrumbs", token, Some(json!({ "title": title, "context": {}, "tags": [tag] })))).await;
+            assert_eq!(status, StatusCode::OK);
+            assert!(body["created_at"].is_string() && body["received_at"].is_string(), "{}", body);
+        }
+        let since = (chrono::Utc::now() - chrono::Duration::hours(1)).to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+
+        let (status, body) = send(&app, request("GET", &format!("/events/missed?since={}&limit=1", since), token, None)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["events"][0]["title"], "one");
+        assert_eq!(body["events"][0]["payload_version"], 1);
+        assert!(body["events"][0].get("provenance").is_none());
+        assert_eq!(body["has_more"], true);
+        let cursor = body["next_cursor"].as_str().unwrap().to_string();
+        let (_, body) = send(&app, request("GET", &format!("/events/missed?cursor={}", cursor), token, None)).await;
+        let titles: Vec<&str> = body["events"].as_array().unwrap().iter().map(|e| e["title"].as_str().unwrap()).collect();
+        assert_eq!(titles, vec!["two"]);
+        assert_eq!(body["has_more"], false);
+
+        // Filter params stand in for the selectors
+        let (_, body) = send(&app, request("GET", &format!("/events/missed?since={}&any_tags=missed:no", since), token, None)).await;
+        assert_eq!(body["events"][0]["title"], "other");
+        let (status, _) = send(&app, request("GET", "/events/missed?since=2000-01-01T00:00:00Z", token, None)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // Payload versions: asked for per request, or pinned per selector
+        let (_, body) = send(&app, request("GET", &format!("/events/missed?since={}&payload_version=2", since), token, None)).await;
+        assert_eq!(body["events"][0]["payload_version"], 2);
+        assert!(body["events"][0]["provenance"].is_object() && body["events"][0]["emitted_at"].is_string());
+        assert!(body["events"][0].get("created_at").is_none());
+        let (_, body) = send(&app, request("GET", &format!("/events/missed?since={}&payload_version=3", since), token, None)).await;
+        assert_eq!(body["events"][0]["payload_version"], 3);
+        assert!(body["events"][0]["created_at"].is_string() && body["events"][0]["received_at"].is_string());
+        let (status, _) = send(&app, request("GET", &format!("/events/missed?since={}&payload_version=9", since), token, None)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = send(&app, request("POST", "/subscriptions/selectors", token, Some(json!({ "any_tags": ["missed:no"], "payload_version": 9 })))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, body) = send(&app, request("POST", "/subscriptions/selectors", token, Some(json!({ "any_tags": ["missed:no"], "payload_version": 2 })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["payload_version"], 2);
+        let (_, body) = send(&app, request("GET", &format!("/events/missed?since={}", since), token, None)).await;
+        let versions: Vec<(&str, u64)> = body["events"].as_array().unwrap().iter().map(|e| (e["title"].as_str().unwrap(), e["payload_version"].as_u64().unwrap())).collect();
+        assert_eq!(versions, vec![("one", 1), ("other", 2), ("two", 1)]);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_create_requires_emitter_role(pool: sqlx::PgPool) {
+        let (app, owner_id) = setup(pool).await;
+        let subscriber = token(&app, owner_id, &["subscriber"]).await;
+        let body = json!({ "title": "Not allowed", "context": {}, "tags": [] });
+        let (status, _) = send(&app, request("POST", "/breadcrumbs", Some(&subscriber), Some(body.clone()))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let emitter = token(&app, owner_id, &["emitter"]).await;
+        let (status, _) = send(&app, request("POST", "/breadcrumbs", Some(&emitter), Some(body))).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_upsert_by_key_tags(pool: sqlx::PgPool) {
+        let (app, owner_id) = setup(pool).await;
+        let token = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let token = Some(token.as_str());
+        let uri = "/breadcrumbs/upsert?schema=agent.context.v1&key_tags=consumer:x,session:y";
+        let payload = |step: i32| json!({ "title": "Context", "context": { "step": step }, "tags": ["agent:context"] });
+
+        let (status, first) = send(&app, request("PUT", uri, token, Some(payload(1)))).await;
+        assert_eq!(status, StatusCode::OK, "{}", first);
+        assert_eq!((first["created"].as_bool(), first["version"].as_i64()), (Some(true), Some(1)));
+        let (status, second) = send(&app, request("PUT", uri, token, Some(payload(2)))).await;
+        assert_eq!(status, StatusCode::OK, "{}", second);
+        assert_eq!(second["id"], first["id"]);
+        assert_eq!((second["created"].as_bool(), second["version"].as_i64()), (Some(false), Some(2)));
+
+        let (_, body) = send(&app, request("GET", &format!("/breadcrumbs/{}/full", first["id"].as_str().unwrap()), token, None)).await;
+        assert_eq!(body["context"]["step"], 2);
+        let tags: Vec<&str> = body["tags"].as_array().unwrap().iter().filter_map(Value::as_str).collect();
+        assert!(tags.contains(&"consumer:x") && tags.contains(&"session:y"), "key tags are added: {:?}", tags);
+
+        let (status, _) = send(&app, request("PUT", "/breadcrumbs/upsert?schema=agent.context.v1&key_tags=", token, Some(payload(3)))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_registration_rejects_unknown_roles(pool: sqlx::PgPool) {
+        let (app, owner_id) = setup(pool).await;
+        let curator = token(&app, owner_id, &["curator"]).await;
+        let curator = Some(curator.as_str());
+        let agent_id = Uuid::new_v4();
+        let uri = format!("/agents/{}", agent_id);
+
+        let (status, _) = send(&app, request("POST", &uri, curator, Some(json!({ "roles": ["subscriber", "Emiter"] })))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let (status, _) = send(&app, request("GET", &uri, curator, None)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // Case doesn't matter; the stored name is lowercase
+        let (status, _) = send(&app, request("POST", &uri, curator, Some(json!({ "roles": ["Subscriber", "EMITTER"] })))).await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, agent) = send(&app, request("GET", &uri, curator, None)).await;
+        assert_eq!(agent["roles"], json!(["subscriber", "emitter"]));
+
+        let (status, _) = send(&app, request("POST", &format!("{}/api-keys", uri), curator, Some(json!({ "roles": ["root"] })))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    fn api_key_request(method: &str, uri: &str, key: &str, body: Option<Value>) -> Request<Body> {
+        let mut req = request(method, uri, None, body);
+        req.headers_mut().insert(header::AUTHORIZATION, format!("ApiKey {}", key).parse().unwrap());
+        req
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_api_keys_act_like_tokens_until_revoked(pool: sqlx::PgPool) {
+        let (app, owner_id) = setup(pool).await;
+        let curator = token(&app, owner_id, &["curator", "subscriber"]).await;
+        let curator = Some(curator.as_str());
+        let agent_id = Uuid::new_v4();
+        let (status, _) = send(&app, request("POST", &format!("/agents/{}", agent_id), curator, Some(json!({ "roles": ["subscriber"] })))).await;
+        assert_eq!(status, StatusCode::OK);
+        let keys = format!("/agents/{}/api-keys", agent_id);
+
+        let emitter = token(&app, owner_id, &["emitter"]).await;
+        let (status, _) = send(&app, request("POST", &keys, Some(&emitter), Some(json!({})))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send(&app, request("POST", &format!("/agents/{}/api-keys", Uuid::new_v4()), curator, Some(json!({})))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // A key carries only roles its creator holds; admin keys need an admin
+        let (status, body) = send(&app, request("POST", &keys, curator, Some(json!({ "roles": ["admin"] })))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{}", body);
+        let (status, _) = send(&app, request("POST", &keys, curator, Some(json!({ "roles": ["emitter"] })))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // Roles default to the agent's
+        let (status, created) = send(&app, request("POST", &keys, curator, Some(json!({ "name": "ingest" })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", created);
+        assert_eq!(created["roles"], json!(["subscriber"]));
+        let key = created["key"].as_str().unwrap().to_string();
+        assert!(key.starts_with(created["prefix"].as_str().unwrap()));
+
+        // Same role checks as a JWT with these roles
+        let (status, _) = send(&app, api_key_request("POST", "/breadcrumbs", &key, Some(json!({ "title": "Not allowed", "context": {}, "tags": [] })))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send(&app, api_key_request("GET", "/breadcrumbs", &key, None)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, selector) = send(&app, api_key_request("POST", "/subscriptions/selectors", &key, Some(json!({ "any_tags": ["a"] })))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(selector["agent_id"], agent_id.to_string());
+        let (status, _) = send(&app, api_key_request("GET", &keys, &key, None)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // The listing never shows the key again
+        let (_, listed) = send(&app, request("GET", &keys, curator, None)).await;
+        assert_eq!(listed.as_array().unwrap().len(), 1);
+        assert_eq!(listed[0]["prefix"], created["prefix"]);
+        assert!(listed[0].get("key").is_none() && listed[0].get("hashed_key").is_none());
+        assert!(!listed[0]["last_used_at"].is_null());
+
+        let revoke = format!("{}/{}", keys, created["id"].as_str().unwrap());
+        let (status, _) = send(&app, request("DELETE", &revoke, curator, None)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&app, api_key_request("GET", "/breadcrumbs", &key, None)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = send(&app, request("DELETE", &revoke, curator, None)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = send(&app, api_key_request("GET", "/breadcrumbs", "rcrt_not-a-key", None)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_ttl_policies_are_validated_and_applied_on_create(pool: sqlx::PgPool) {
+        let (app, owner_id) = setup(pool).await;
+        let emitter = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let curator = token(&app, owner_id, &["curator", "emitter", "subscriber"]).await;
+        let policy = |duration: Value| json!({
+            "title": "Short-lived notes", "schema_name": "ttl.policy.v1", "tags": [],
+            "context": { "match": { "schema_name": "note.*" }, "duration": duration, "priority": 1 }
+        });
+
+        let (status, _) = send(&app, request("POST", "/breadcrumbs", Some(&emitter), Some(policy(json!("1h"))))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, body) = send(&app, request("POST", "/breadcrumbs", Some(&curator), Some(policy(json!("-1h"))))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", body);
+        let (status, body) = send(&app, request("POST", "/breadcrumbs", Some(&curator), Some(policy(json!("1h"))))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let policy_id = body["id"].as_str().unwrap().to_string();
+
+        let (_, body) = send(&app, request("POST", "/breadcrumbs", Some(&emitter), Some(json!({
+            "title": "Note", "schema_name": "note.v1", "context": {}, "tags": []
+        })))).await;
+        let (_, retention) = send(&app, request("GET", &format!("/breadcrumbs/{}/retention", body["id"].as_str().unwrap()), Some(&emitter), None)).await;
+        assert_eq!(retention["ttl_source"], "auto-applied");
+        assert_eq!(retention["ttl_type"], "datetime");
+        assert!(retention["ttl"].is_string());
+
+        // Updates are checked against the stored schema
+        let (status, _) = send(&app, request("PATCH", &format!("/breadcrumbs/{}", policy_id), Some(&curator), Some(json!({
+            "context": { "match": { "schema_name": "note.*" }, "duration": 0 }
+        })))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_validate_reports_what_create_then_does(pool: sqlx::PgPool) {
+        let (app, owner_id) = setup(pool.clone()).await;
+        let emitter = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let curator = token(&app, owner_id, &["curator", "emitter", "subscriber"]).await;
+        let watcher_id = Uuid::new_v4();
+        let (_, body) = send(&app, request("POST", "/auth/token", None, Some(json!({
+            "owner_id": owner_id.to_string(), "agent_id": watcher_id.to_string(), "roles": ["subscriber"]
+        })))).await;
+        let watcher = body["token"].as_str().unwrap().to_string();
+        let (status, selector) = send(&app, request("POST", "/subscriptions/selectors", Some(&watcher), Some(json!({ "any_tags": ["orders"] })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", selector);
+        let (status, _) = send(&app, request("POST", "/breadcrumbs", Some(&curator), Some(json!({
+            "title": "Orders expire", "schema_name": "ttl.policy.v1", "tags": [],
+            "context": { "match": { "tags": ["orders"] }, "duration": "1d" }
+        })))).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let order = json!({
+            "title": "Order 7", "schema_name": "order.v1", "tags": ["orders"],
+            "context": { "order": 7, "internal_id": "x-7" },
+            "llm_hints": { "exclude": ["internal_id"] }
+        });
+        let (status, report) = send(&app, request("POST", "/breadcrumbs/validate", Some(&emitter), Some(order.clone()))).await;
+        assert_eq!(status, StatusCode::OK, "{}", report);
+        assert_eq!(report["valid"], true, "{}", report);
+        assert_eq!(report["warnings"], json!([]));
+        assert_eq!(report["context_view"], json!({ "order": 7 }));
+        let matches = report["matches"].as_array().unwrap();
+        assert_eq!(matches.len(), 1, "{}", report);
+        assert_eq!(matches[0]["selector_id"], selector["id"]);
+        assert_eq!(matches[0]["agent_id"], watcher_id.to_string());
+        assert_eq!(matches[0]["delivery"], "full");
+        // Nothing was written
+        let stored: i64 = sqlx::query_scalar("select count(*) from breadcrumbs where owner_id = $1 and schema_name = 'order.v1'")
+            .bind(owner_id).fetch_one(&pool).await.unwrap();
+        assert_eq!(stored, 0);
+
+        // The create does what the report said
+        let (status, created) = send(&app, request("POST", "/breadcrumbs", Some(&emitter), Some(order))).await;
+        assert_eq!(status, StatusCode::OK, "{}", created);
+        let id = created["id"].as_str().unwrap();
+        let (_, full) = send(&app, request("GET", &format!("/breadcrumbs/{}/full", id), Some(&emitter), None)).await;
+        assert_eq!(full["size_bytes"], report["size_bytes"]);
+        assert_eq!(full["ttl_type"], report["ttl_type"]);
+        assert_eq!(full["ttl_source"], "auto-applied");
+        assert_eq!(full["ttl_source"], report["ttl_source"]);
+        let ttl = |v: &Value| chrono::DateTime::parse_from_rfc3339(v.as_str().unwrap()).unwrap();
+        assert!((ttl(&full["ttl"]) - ttl(&report["ttl"])).num_seconds().abs() < 60);
+        let (_, view) = send(&app, request("GET", &format!("/breadcrumbs/{}", id), Some(&emitter), None)).await;
+        assert_eq!(view["context"], report["context_view"]);
+
+        // Refusals are reported, not raised
+        let (status, report) = send(&app, request("POST", "/breadcrumbs/validate", Some(&watcher), Some(json!({
+            "title": "Order 8", "context": {}, "tags": ["orders"]
+        })))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(report["valid"], false);
+        assert_eq!(report["rejected"]["status"], 403);
+        let (_, report) = send(&app, request("POST", "/breadcrumbs/validate", Some(&emitter), Some(json!({
+            "title": "Order 9", "context": {}, "tags": ["orders"],
+            "references": [{ "field": "parent", "breadcrumb_id": Uuid::new_v4(), "relation": "follows" }]
+        })))).await;
+        assert_eq!(report["rejected"]["status"], 422, "{}", report);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_create_from_template(pool: sqlx::PgPool) {
+        let (app, owner_id) = setup(pool).await;
+        let token = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let (status, body) = send(&app, request("POST", "/breadcrumbs", Some(&token), Some(json!({
+            "title": "tool-request", "schema_name": "template.v1", "tags": [],
+            "context": {
+                "schema_name": "tool.request.v1",
+                "title": "Run {{inputs.tool}}",
+                "tags": ["tool:request", "workspace:tools"],
+                "context": { "tool": "{{inputs.tool}}", "input": "{{inputs.input}}", "requested_by": "{{inputs.agent}} via template" },
+                "required": ["tool", "input"]
+            }
+        })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+
+        let (status, body) = send(&app, request("POST", "/breadcrumbs/from_template/tool-request", Some(&token), Some(json!({
+            "inputs": { "tool": "search" }
+        })))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["fields"], json!([{ "field": "inputs.input", "error": "required" }]));
+        let (status, _) = send(&app, request("POST", "/breadcrumbs/from_template/nope", Some(&token), Some(json!({ "inputs": {} })))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, body) = send(&app, request("POST", "/breadcrumbs/from_template/tool-request", Some(&token), Some(json!({
+            "inputs": { "tool": "search", "input": { "q": "rust", "limit": 5 }, "agent": "planner" },
+            "tags": ["session:1", "tool:request"]
+        })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let (_, full) = send(&app, request("GET", &format!("/breadcrumbs/{}/full", body["id"].as_str().unwrap()), Some(&token), None)).await;
+        assert_eq!(full["schema_name"], "tool.request.v1");
+        assert_eq!(full["title"], "Run search");
+        assert_eq!(full["context"], json!({ "tool": "search", "input": { "q": "rust", "limit": 5 }, "requested_by": "planner via template" }));
+        assert_eq!(full["tags"], json!(["tool:request", "workspace:tools", "session:1"]));
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_tenants_do_not_see_each_others_breadcrumbs(pool: sqlx::PgPool) {
+        let (app, owner_id) = setup(pool.clone()).await;
+        let other_owner = Uuid::new_v4();
+        Db { pool }.ensure_tenant(other_owner, "Other Tenant").await.unwrap();
+        let mine = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let theirs = token(&app, other_owner, &["emitter", "subscriber"]).await;
+
+        let (_, body) = send(&app, request("POST", "/breadcrumbs", Some(&mine), Some(json!({
+            "title": "Private", "context": {}, "tags": []
+        })))).await;
+        let uri = format!("/breadcrumbs/{}", body["id"].as_str().unwrap());
+        let (status, _) = send(&app, request("GET", &uri, Some(&theirs), None)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = send(&app, request("GET", &uri, Some(&mine), None)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_attachments_dedupe_and_follow_breadcrumb_access(pool: sqlx::PgPool) {
+        let (app, owner_id) = setup(pool.clone()).await;
+        let other_owner = Uuid::new_v4();
+        Db { pool }.ensure_tenant(other_owner, "Other Tenant").await.unwrap();
+        let mine = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let theirs = token(&app, other_owner, &["emitter", "subscriber"]).await;
+
+        let mut ids = Vec::new();
+        for title in ["Screenshot", "Same screenshot"] {
+            let (_, body) = send(&app, request("POST", "/breadcrumbs", Some(&mine), Some(json!({ "title": title, "context": {}, "tags": [] })))).await;
+            ids.push(body["id"].as_str().unwrap().to_string());
+        }
+        let upload = |id: &str, token: &str, content_type: &str, body: Vec<u8>| {
+            Request::builder().method("POST").uri(format!("/breadcrumbs/{}/attachments?filename=shot.png", id))
+                .header(header::AUTHORIZATION, format!("Bearer {}", token))
+                .header(header::CONTENT_TYPE, content_type)
+                .body(Body::from(body))
+                .unwrap()
+        };
+        let png = b"\x89PNG not really a png".to_vec();
+
+        let (status, first) = send(&app, upload(&ids[0], &mine, "image/png", png.clone())).await;
+        assert_eq!(status, StatusCode::OK, "{}", first);
+        assert_eq!((first["deduplicated"].as_bool(), first["size_bytes"].as_i64()), (Some(false), Some(png.len() as i64)));
+        let sha = first["sha256"].as_str().unwrap().to_string();
+        let (_, second) = send(&app, upload(&ids[1], &mine, "image/png", png.clone())).await;
+        assert_eq!((second["deduplicated"].as_bool(), second["sha256"].as_str()), (Some(true), Some(sha.as_str())));
+
+        let form = "--b0undary\r\nContent-Disposition: form-data; name=\"file\"; filename=\"notes.txt\"\r\nContent-Type: text/plain\r\n\r\nmeeting notes\r\n--b0undary--\r\n";
+        let mut req = upload(&ids[0], &mine, "multipart/form-data; boundary=b0undary", form.as_bytes().to_vec());
+        *req.uri_mut() = format!("/breadcrumbs/{}/attachments", ids[0]).parse().unwrap();
+        let (status, notes) = send(&app, req).await;
+        assert_eq!(status, StatusCode::OK, "{}", notes);
+        assert_eq!((notes["filename"].as_str(), notes["content_type"].as_str()), (Some("notes.txt"), Some("text/plain")));
+
+        let (status, list) = send(&app, request("GET", &format!("/breadcrumbs/{}/attachments", ids[0]), Some(&mine), None)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(list.as_array().unwrap().len(), 2);
+        assert_eq!(list[0]["sha256"], sha.as_str());
+
+        let res = app.clone().oneshot(request("GET", &format!("/attachments/{}", sha), Some(&mine), None)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "image/png");
+        assert_eq!(&axum::body::to_bytes(res.into_body(), usize::MAX).await.unwrap()[..], &png[..]);
+
+        // Another tenant can't read the breadcrumb, so not its attachments either
+        let (status, _) = send(&app, request("GET", &format!("/attachments/{}", sha), Some(&theirs), None)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = send(&app, request("GET", &format!("/breadcrumbs/{}/attachments", ids[0]), Some(&theirs), None)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = send(&app, upload(&ids[0], &theirs, "image/png", png)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = send(&app, request("GET", "/attachments/not-a-hash", Some(&mine), None)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_webhook_test_delivery_renders_template_and_signs_it(pool: sqlx::PgPool) {
+        use hmac::{Hmac, Mac};
+        use std::sync::{Arc, Mutex};
+
+        let (app, owner_id) = setup(pool).await;
+        let agent_id = Uuid::new_v4();
+        let (_, body) = send(&app, request("POST", "/auth/token", None, Some(json!({
+            "owner_id": owner_id.to_string(), "agent_id": agent_id.to_string(), "roles": ["subscriber"]
+        })))).await;
+        let token = body["token"].as_str().unwrap().to_string();
+        let token = Some(token.as_str());
+
+        // Receiver standing in for a Slack incoming webhook
+        let received: Arc<Mutex<Option<(String, String)>>> = Arc::default();
+        let sink = received.clone();
+        let receiver = Router::new().route("/slack", axum::routing::post(move |headers: axum::http::HeaderMap, body: String| {
+            let signature = headers["x-rcrt-signature"].to_str().unwrap().to_string();
+            *sink.lock().unwrap() = Some((signature, body));
+            async { StatusCode::OK }
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/slack", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, receiver).await.unwrap() });
+
+        let hooks = format!("/agents/{}/webhooks", agent_id);
+        let (status, _) = send(&app, request("POST", &hooks, token, Some(json!({ "url": url, "payload_template": "{\"text\": \"{{title" })))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let template = r#"{"text": "*{{title}}* ({{type}}) {{#each tags}}`{{this}}` {{/each}}"}"#;
+        let (status, body) = send(&app, request("POST", &hooks, token, Some(json!({ "url": url, "payload_template": template })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let wid = body["id"].as_str().unwrap().to_string();
+        let (_, listed) = send(&app, request("GET", &hooks, token, None)).await;
+        assert_eq!(listed[0]["payload_template"], template);
+        assert_eq!(listed[0]["payload_version"], Value::Null);
+        let (status, _) = send(&app, request("POST", &hooks, token, Some(json!({ "url": url, "payload_template": template, "payload_version": 3 })))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = send(&app, request("POST", &format!("/agents/{}/secret", agent_id), token, Some(json!({ "secret": "s3cret" })))).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, result) = send(&app, request("POST", &format!("{}/{}/test", hooks, wid), token, Some(json!({
+            "event": { "type": "breadcrumb.created", "title": "Deploy \"api\" done", "tags": ["deploy", "prod"] }
+        })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", result);
+        assert_eq!(result["delivered"], true);
+        assert_eq!(result["template_error"], Value::Null);
+
+        let (signature, sent) = received.lock().unwrap().clone().expect("receiver was called");
+        assert_eq!(sent, result["body"].as_str().unwrap());
+        let sent: Value = serde_json::from_str(&sent).unwrap();
+        assert_eq!(sent, json!({ "text": "*Deploy \"api\" done* (breadcrumb.created) `deploy` `prod` " }));
+        let mut mac = Hmac::<sha2::Sha256>::new_from_slice(b"s3cret").unwrap();
+        mac.update(result["body"].as_str().unwrap().as_bytes());
+        assert_eq!(signature, format!("sha256={}", hex::encode(mac.finalize().into_bytes())));
+
+        // Without an event the sample event is rendered
+        let (_, result) = send(&app, request("POST", &format!("{}/{}/test", hooks, wid), token, None)).await;
+        assert_eq!(result["delivered"], true);
+        assert!(result["body"].as_str().unwrap().contains("RCRT test delivery"));
+        let (status, _) = send(&app, request("POST", &format!("{}/{}/test", hooks, Uuid::new_v4()), token, None)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_selector_channels_gate_webhook_delivery(pool: sqlx::PgPool) {
+        use std::sync::{Arc, Mutex};
+
+        let (app, owner_id) = setup(pool).await;
+        let agent_id = Uuid::new_v4();
+        let (_, body) = send(&app, request("POST", "/auth/token", None, Some(json!({
+            "owner_id": owner_id.to_string(), "agent_id": agent_id.to_string(), "roles": ["emitter", "subscriber"]
+        })))).await;
+        let token = body["token"].as_str().unwrap().to_string();
+        let token = Some(token.as_str());
+
+        let received: Arc<Mutex<Vec<String>>> = Arc::default();
+        let sink = received.clone();
+        let receiver = Router::new().route("/hook", axum::routing::post(move |body: String| {
+            let event: Value = serde_json::from_str(&body).unwrap();
+            sink.lock().unwrap().push(event["title"].as_str().unwrap_or_default().to_string());
+            async { StatusCode::OK }
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, receiver).await.unwrap() });
+        let (status, _) = send(&app, request("POST", &format!("/agents/{}/webhooks", agent_id), token, Some(json!({ "url": url })))).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Omitted channels mean all; an empty list is refused
+        let (status, sse_only) = send(&app, request("POST", "/subscriptions/selectors", token, Some(json!({ "any_tags": ["chat"], "channels": ["sse"] })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", sse_only);
+        let (status, all) = send(&app, request("POST", "/subscriptions/selectors", token, Some(json!({ "any_tags": ["alert"] })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", all);
+        assert_eq!(all["channels"], json!(["sse", "webhook", "nats"]));
+        let (status, _) = send(&app, request("POST", "/subscriptions/selectors", token, Some(json!({ "any_tags": ["x"], "channels": [] })))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (_, listed) = send(&app, request("GET", "/subscriptions/selectors", token, None)).await;
+        let listed_sse = listed.as_array().unwrap().iter().find(|s| s["id"] == sse_only["id"]).unwrap();
+        assert_eq!(listed_sse["channels"], json!(["sse"]));
+
+        for (title, tag) in [("quiet", "chat"), ("loud", "alert")] {
+            let (status, body) = send(&app, request("POST", "/breadcrumbs", token, Some(json!({ "title": title, "context": {}, "tags": [tag] })))).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+        }
+        for _ in 0..50 {
+            if !received.lock().unwrap().is_empty() { break; }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        assert_eq!(*received.lock().unwrap(), vec!["loud".to_string()]);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_inbox_channel_queues_events_until_acked(pool: sqlx::PgPool) {
+        let (app, owner_id) = setup(pool).await;
+        let outsider = token(&app, owner_id, &["subscriber"]).await;
+        let agent_id = Uuid::new_v4();
+        let (_, body) = send(&app, request("POST", "/auth/token", None, Some(json!({
+            "owner_id": owner_id.to_string(), "agent_id": agent_id.to_string(), "roles": ["emitter", "subscriber"]
+        })))).await;
+        let token = body["token"].as_str().unwrap().to_string();
+        let token = Some(token.as_str());
+
+        let (status, sel) = send(&app, request("POST", "/subscriptions/selectors", token, Some(json!({ "any_tags": ["jobs"], "channels": ["inbox"] })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", sel);
+        assert_eq!(sel["channels"], json!(["inbox"]));
+        for title in ["job 1", "job 2"] {
+            let (status, body) = send(&app, request("POST", "/breadcrumbs", token, Some(json!({ "title": title, "context": {}, "tags": ["jobs"] })))).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+        }
+
+        let inbox = format!("/agents/{}/inbox", agent_id);
+        let mut depth = 0;
+        for _ in 0..50 {
+            let (_, agents) = send(&app, request("GET", "/agents", token, None)).await;
+            depth = agents.as_array().unwrap().iter().find(|a| a["id"] == json!(agent_id)).unwrap()["inbox_depth"].as_i64().unwrap();
+            if depth == 2 { break; }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        assert_eq!(depth, 2);
+        let (status, _) = send(&app, request("GET", &inbox, Some(outsider.as_str()), None)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // Oldest first; leased entries stay hidden until acked or expired
+        let (status, first) = send(&app, request("GET", &format!("{}?limit=1", inbox), token, None)).await;
+        assert_eq!(status, StatusCode::OK, "{}", first);
+        assert_eq!(first["entries"][0]["event"]["title"], "job 1");
+        let (_, second) = send(&app, request("GET", &inbox, token, None)).await;
+        let titles: Vec<&str> = second["entries"].as_array().unwrap().iter().map(|e| e["event"]["title"].as_str().unwrap()).collect();
+        assert_eq!(titles, vec!["job 2"]);
+
+        let ids = json!([first["entries"][0]["id"], second["entries"][0]["id"]]);
+        let (status, acked) = send(&app, request("POST", &format!("{}/ack", inbox), token, Some(json!({ "ids": ids })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", acked);
+        assert_eq!(acked["acked"], 2);
+        let (status, _) = send(&app, request("POST", &format!("{}/ack", inbox), token, Some(json!({ "ids": [] })))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (_, agents) = send(&app, request("GET", "/agents", token, None)).await;
+        assert_eq!(agents.as_array().unwrap().iter().find(|a| a["id"] == json!(agent_id)).unwrap()["inbox_depth"], 0);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_embedded_service_shares_the_api_write_path(pool: sqlx::PgPool) {
+        use rcrt_core::error::DbError;
+        use rcrt_server::{auth::AuthContext, service::{BreadcrumbService, CreateReq, ServiceError, UpdateReq}};
+
+        let db = Db { pool };
+        let owner_id = Uuid::new_v4();
+        db.ensure_tenant(owner_id, "Embedded Test").await.unwrap();
+        let agent_id = Uuid::new_v4();
+        db.upsert_agent(owner_id, agent_id, vec!["emitter".into()]).await.unwrap();
+        let service = BreadcrumbService::new(state(db.clone(), jwt_auth()).await);
+        let app = app(db, jwt_auth()).await;
+        let token = token(&app, owner_id, &["curator", "subscriber"]).await;
+        let token = Some(token.as_str());
+        let me = AuthContext { owner_id, agent_id, roles: vec![Role::Emitter] };
+
+        let create = || CreateReq {
+            title: "Embedded".into(),
+            context: json!({ "step": 1, "internal_id": "x-1" }),
+            tags: vec!["test:embedded".into()],
+            llm_hints: Some(json!({ "exclude": ["internal_id"] })),
+            ..CreateReq::default()
+        };
+        let created = service.create(&me, create(), Some("once")).await.unwrap();
+        assert!(!created.deprecated);
+        match service.create(&me, create(), Some("once")).await {
+            Err(ServiceError::Rejected(status, _)) => assert_eq!(status, StatusCode::CONFLICT),
+            other => panic!("repeated idempotency key: {:?}", other.map(|c| c.breadcrumb.id)),
+        }
+        let reader = AuthContext { roles: vec![Role::Subscriber], ..me.clone() };
+        assert!(matches!(service.create(&reader, create(), None).await, Err(ServiceError::Rejected(StatusCode::FORBIDDEN, _))));
+
+        // Written in-process, read over HTTP, with the hints applied on both sides
+        let id = created.breadcrumb.id;
+        let (status, body) = send(&app, request("GET", &format!("/breadcrumbs/{}", id), token, None)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["context"], json!({ "step": 1 }));
+        assert_eq!(service.get_context(&me, id, false).await.unwrap().context, json!({ "step": 1 }));
+
+        let update = || UpdateReq { context: Some(json!({ "step": 2 })), ..UpdateReq::default() };
+        let updated = service.update(&me, id, Some(1), false, update()).await.unwrap();
+        assert_eq!(updated.version, 2);
+        assert!(matches!(service.update(&me, id, Some(1), false, update()).await, Err(ServiceError::Db(DbError::VersionMismatch(_)))));
+        let (_, history) = send(&app, request("GET", &format!("/breadcrumbs/{}/history", id), token, None)).await;
+        assert!(!history.as_array().unwrap().is_empty());
+
+        assert!(service.delete(&me, id, false).await.unwrap().is_empty());
+        let (status, _) = send(&app, request("GET", &format!("/breadcrumbs/{}", id), token, None)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(matches!(service.delete(&me, id, false).await, Err(ServiceError::Rejected(StatusCode::NOT_FOUND, _))));
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_selector_bound_webhooks_get_only_their_selectors_matches(pool: sqlx::PgPool) {
+        use std::sync::{Arc, Mutex};
+
+        let (app, owner_id) = setup(pool).await;
+        let agent_id = Uuid::new_v4();
+        let (_, body) = send(&app, request("POST", "/auth/token", None, Some(json!({
+            "owner_id": owner_id.to_string(), "agent_id": agent_id.to_string(), "roles": ["emitter", "subscriber"]
+        })))).await;
+        let token = body["token"].as_str().unwrap().to_string();
+        let token = Some(token.as_str());
+
+        // One receiver, a path per webhook, recording (path, title)
+        let received: Arc<Mutex<Vec<(String, String)>>> = Arc::default();
+        let sink = received.clone();
+        let receiver = Router::new().route("/:hook", axum::routing::post(move |axum::extract::Path(hook): axum::extract::Path<String>, body: String| {
+            let event: Value = serde_json::from_str(&body).unwrap();
+            sink.lock().unwrap().push((hook, event["title"].as_str().unwrap_or_default().to_string()));
+            async { StatusCode::OK }
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, receiver).await.unwrap() });
+
+        let (_, orders) = send(&app, request("POST", "/subscriptions/selectors", token, Some(json!({ "any_tags": ["orders"] })))).await;
+        let (_, refunds) = send(&app, request("POST", "/subscriptions/selectors", token, Some(json!({ "any_tags": ["refunds"] })))).await;
+        let hooks = format!("/agents/{}/webhooks", agent_id);
+        for (path, selector) in [("orders", &orders), ("refunds", &refunds)] {
+            let (status, body) = send(&app, request("POST", &hooks, token, Some(json!({ "url": format!("{}/{}", base, path), "selector_id": selector["id"] })))).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+        }
+        let (status, _) = send(&app, request("POST", &hooks, token, Some(json!({ "url": format!("{}/stray", base), "selector_id": Uuid::new_v4() })))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let (_, listed) = send(&app, request("GET", &hooks, token, None)).await;
+        let bound_to = |path: &str| listed.as_array().unwrap().iter().find(|h| h["url"] == format!("{}/{}", base, path)).unwrap()["selector_id"].clone();
+        assert_eq!((bound_to("orders"), bound_to("refunds")), (orders["id"].clone(), refunds["id"].clone()));
+
+        for (title, tag) in [("order 1", "orders"), ("refund 1", "refunds")] {
+            let (status, body) = send(&app, request("POST", "/breadcrumbs", token, Some(json!({ "title": title, "context": {}, "tags": [tag] })))).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+        }
+        for _ in 0..50 {
+            if received.lock().unwrap().len() >= 2 { break; }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        let mut got = received.lock().unwrap().clone();
+        got.sort();
+        assert_eq!(got, vec![("orders".to_string(), "order 1".to_string()), ("refunds".to_string(), "refund 1".to_string())]);
+
+        // Deleting a selector deactivates its webhook instead of letting it receive everything
+        let (status, _) = send(&app, request("DELETE", &format!("/subscriptions/selectors/{}", orders["id"].as_str().unwrap()), token, None)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, listed) = send(&app, request("GET", &hooks, token, None)).await;
+        assert_eq!(listed.as_array().unwrap().len(), 1);
+        assert_eq!(listed[0]["selector_id"], refunds["id"]);
+        let (_, listed) = send(&app, request("GET", &format!("{}?include_inactive=true", hooks), token, None)).await;
+        let inactive = listed.as_array().unwrap().iter().find(|h| h["active"] == false).unwrap();
+        assert_eq!(inactive["url"], format!("{}/orders", base));
+        assert_eq!(inactive["deactivated_reason"], format!("selector {} was deleted", orders["id"].as_str().unwrap()));
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_delete_agent_refuses_dependents_unless_cascading(pool: sqlx::PgPool) {
+        use std::sync::{Arc, Mutex};
+
+        let (app, owner_id) = setup(pool.clone()).await;
+        let curator = token(&app, owner_id, &["curator", "emitter"]).await;
+        let curator = Some(curator.as_str());
+        let agent_id = Uuid::new_v4();
+        let (_, body) = send(&app, request("POST", "/auth/token", None, Some(json!({
+            "owner_id": owner_id.to_string(), "agent_id": agent_id.to_string(), "roles": ["subscriber"]
+        })))).await;
+        let leaving = body["token"].as_str().unwrap().to_string();
+        let leaving = Some(leaving.as_str());
+
+        let received: Arc<Mutex<Vec<String>>> = Arc::default();
+        let sink = received.clone();
+        let receiver = Router::new().route("/hook", axum::routing::post(move |body: String| {
+            sink.lock().unwrap().push(body);
+            async { StatusCode::OK }
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, receiver).await.unwrap() });
+        let (status, _) = send(&app, request("POST", &format!("/agents/{}/webhooks", agent_id), leaving, Some(json!({ "url": url })))).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&app, request("POST", "/subscriptions/selectors", leaving, Some(json!({ "any_tags": ["alert"] })))).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let uri = format!("/agents/{}", agent_id);
+        let (status, body) = send(&app, request("DELETE", &uri, curator, None)).await;
+        assert_eq!(status, StatusCode::CONFLICT, "{}", body);
+        assert_eq!(body["error"], "agent_has_dependents");
+        assert_eq!(body["dependents"]["selector_subscriptions"], 1);
+        assert_eq!(body["dependents"]["webhooks"], 1);
+        let (status, _) = send(&app, request("DELETE", &format!("{}?cascade=true", uri), leaving, None)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, body) = send(&app, request("DELETE", &format!("{}?cascade=true", uri), curator, None)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["removed"]["selector_subscriptions"], 1);
+        assert_eq!(body["removed"]["webhooks"], 1);
+        let (status, _) = send(&app, request("DELETE", &uri, curator, None)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let orphaned: i64 = sqlx::query_scalar("select count(*) from selector_subscriptions where agent_id = $1")
+            .bind(agent_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(orphaned, 0);
+
+        // A breadcrumb the old selector matched fans out to nobody
+        let (status, body) = send(&app, request("POST", "/breadcrumbs", curator, Some(json!({ "title": "after", "context": {}, "tags": ["alert"] })))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        assert!(received.lock().unwrap().is_empty());
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_filtered_purge_needs_a_filter_and_the_dry_runs_token(pool: sqlx::PgPool) {
+        let (app, owner_id) = setup(pool).await;
+        let curator = token(&app, owner_id, &["curator", "emitter"]).await;
+        let curator = Some(curator.as_str());
+        let emitter = token(&app, owner_id, &["emitter"]).await;
+        let create = |title: &str, tag: &str| request("POST", "/breadcrumbs", curator, Some(json!({ "title": title, "context": {}, "tags": [tag] })));
+        for n in 0..4 {
+            let (status, _) = send(&app, create(&format!("import {}", n), "import:batch-7")).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+        let (_, body) = send(&app, create("keep me", "import:batch-8")).await;
+        let kept = body["id"].as_str().unwrap().to_string();
+
+        let purge = |token: Option<&str>, body: Value| request("POST", "/admin/breadcrumbs/purge", token, Some(body));
+        let (status, _) = send(&app, purge(Some(emitter.as_str()), json!({ "all_tags": ["import:batch-7"] }))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send(&app, purge(curator, json!({ "dry_run": false }))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = send(&app, purge(curator, json!({ "all_tags": [] }))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // Dry run by default
+        let filter = json!({ "all_tags": ["import:batch-7"] });
+        let (status, dry) = send(&app, purge(curator, filter.clone())).await;
+        assert_eq!(status, StatusCode::OK, "{}", dry);
+        assert_eq!(dry["dry_run"], true);
+        assert_eq!(dry["matched"], 4);
+        assert_eq!(dry["sample"].as_array().unwrap().len(), 4);
+        let stale_token = dry["confirmation_token"].as_str().unwrap().to_string();
+
+        let (status, _) = send(&app, purge(curator, json!({ "all_tags": ["import:batch-7"], "dry_run": false }))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = send(&app, purge(curator, json!({ "all_tags": ["import:batch-7"], "dry_run": false, "confirmation_token": "0000" }))).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        // Another token's filter, or a match count that moved since the dry run, is refused too
+        let (status, _) = send(&app, purge(curator, json!({ "all_tags": ["import:batch-8"], "dry_run": false, "confirmation_token": stale_token }))).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        send(&app, create("late import", "import:batch-7")).await;
+        let (status, _) = send(&app, purge(curator, json!({ "all_tags": ["import:batch-7"], "dry_run": false, "confirmation_token": stale_token }))).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (_, dry) = send(&app, purge(curator, filter)).await;
+        assert_eq!(dry["matched"], 5);
+        let (status, body) = send(&app, purge(curator, json!({
+            "all_tags": ["import:batch-7"], "dry_run": false, "confirmation_token": dry["confirmation_token"]
+        }))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["deleted"], 5);
+        assert_eq!(body["remaining"], 0);
+        assert!(body["confirmation_token"].is_null());
+
+        let (status, _) = send(&app, request("GET", &format!("/breadcrumbs/{}", kept), curator, None)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, audit) = send(&app, request("GET", &format!("/breadcrumbs/{}", body["audit_id"].as_str().unwrap()), curator, None)).await;
+        assert_eq!(status, StatusCode::OK, "{}", audit);
+        assert_eq!(audit["context"]["deleted"], 5);
+        assert_eq!(audit["context"]["filter"]["all_tags"], json!(["import:batch-7"]));
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_topology_import_is_idempotent_and_prunes(pool: sqlx::PgPool) {
+        let (app, owner_id) = setup(pool).await;
+        let curator = token(&app, owner_id, &["curator"]).await;
+        let curator = Some(curator.as_str());
+        let (watcher, relay, alerts) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let doc = json!({
+            "format": "rcrt.topology.v1",
+            "agents": [{ "id": watcher, "roles": ["subscriber"] }, { "id": relay, "roles": ["emitter"] }],
+            "selectors": [{ "id": alerts, "agent_id": watcher, "selector": { "any_tags": ["alerts"] }, "channels": ["webhook"] }],
+            "webhooks": [{ "agent_id": watcher, "url": "https://hooks.example/alerts", "selector_id": alerts }]
+        });
+        let import = |query: &str, body: Value| request("POST", &format!("/admin/topology/import{}", query), curator, Some(body));
+
+        let (status, body) = send(&app, import("", doc.clone())).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["summary"], json!({ "created": 4, "updated": 0, "skipped": 0, "removed": 0 }));
+        assert_eq!(body["items"][3]["kind"], "webhook");
+        assert_eq!(body["items"][3]["index"], 0);
+        let (status, body) = send(&app, import("", doc.clone())).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["summary"], json!({ "created": 0, "updated": 0, "skipped": 4, "removed": 0 }));
+
+        // The export holds the same, plus the curator's own agent, and imports back as a no-op
+        let (status, exported) = send(&app, request("GET", "/admin/topology/export", curator, None)).await;
+        assert_eq!(status, StatusCode::OK, "{}", exported);
+        assert_eq!(exported["format"], "rcrt.topology.v1");
+        assert_eq!(exported["agents"].as_array().unwrap().len(), 3);
+        assert_eq!(exported["selectors"][0]["id"], json!(alerts));
+        assert_eq!(exported["selectors"][0]["channels"], json!(["webhook"]));
+        assert_eq!(exported["webhooks"][0]["selector_id"], json!(alerts));
+        let (_, body) = send(&app, import("", exported)).await;
+        assert_eq!(body["summary"]["skipped"], 5, "{}", body);
+
+        // Bad documents name the items at fault and change nothing
+        let (status, body) = send(&app, import("", json!({
+            "agents": [{ "id": watcher, "roles": ["subscriber"] }],
+            "webhooks": [{ "agent_id": watcher, "url": "https://hooks.example/ok" }, { "agent_id": relay, "url": "not a url" }]
+        }))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", body);
+        assert_eq!(body["errors"].as_array().unwrap().iter().map(|e| e["item"].as_str().unwrap()).collect::<Vec<_>>(), vec!["webhooks[1]", "webhooks[1]"]);
+        let (status, _) = send(&app, import("", json!({ "format": "rcrt.topology.v9", "agents": [] }))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // Changed roles update in place; with prune the relay agent and the webhook go, the curator stays
+        let (status, body) = send(&app, import("?prune=true", json!({
+            "agents": [{ "id": watcher, "roles": ["subscriber", "emitter"] }],
+            "selectors": doc["selectors"].clone()
+        }))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["summary"], json!({ "created": 0, "updated": 1, "skipped": 1, "removed": 2 }));
+        let removed: Vec<&str> = body["items"].as_array().unwrap().iter().filter(|i| i["action"] == "removed").map(|i| i["kind"].as_str().unwrap()).collect();
+        assert_eq!(removed, vec!["webhook", "agent"]);
+        let (status, _) = send(&app, request("GET", &format!("/agents/{}", relay), curator, None)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (_, hooks) = send(&app, request("GET", &format!("/agents/{}/webhooks?include_inactive=true", watcher), curator, None)).await;
+        assert_eq!(hooks[0]["active"], false, "{}", hooks);
+        assert_eq!(hooks[0]["deactivated_reason"], "pruned by topology import");
+
+        let emitter = token(&app, owner_id, &["emitter"]).await;
+        let (status, _) = send(&app, request("GET", "/admin/topology/export", Some(emitter.as_str()), None)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_expired_selectors_stop_matching_and_are_cleaned_up(pool: sqlx::PgPool) {
+        use std::sync::{Arc, Mutex};
+
+        let (app, owner_id) = setup(pool).await;
+        let curator = token(&app, owner_id, &["curator"]).await;
+        let agent_id = Uuid::new_v4();
+        let (_, body) = send(&app, request("POST", "/auth/token", None, Some(json!({
+            "owner_id": owner_id.to_string(), "agent_id": agent_id.to_string(), "roles": ["emitter", "subscriber"]
+        })))).await;
+        let token = body["token"].as_str().unwrap().to_string();
+        let token = Some(token.as_str());
+
+        let received: Arc<Mutex<Vec<String>>> = Arc::default();
+        let sink = received.clone();
+        let receiver = Router::new().route("/hook", axum::routing::post(move |body: String| {
+            let event: Value = serde_json::from_str(&body).unwrap();
+            sink.lock().unwrap().push(event["title"].as_str().unwrap_or_default().to_string());
+            async { StatusCode::OK }
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, receiver).await.unwrap() });
+        let (status, _) = send(&app, request("POST", &format!("/agents/{}/webhooks", agent_id), token, Some(json!({ "url": url })))).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let selector = |body: Value| request("POST", "/subscriptions/selectors", token, Some(body));
+        let (status, _) = send(&app, selector(json!({ "any_tags": ["x"], "ttl_seconds": 0 }))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = send(&app, selector(json!({ "any_tags": ["x"], "expires_at": "2020-01-01T00:00:00Z" }))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, short) = send(&app, selector(json!({ "any_tags": ["session:abc"], "ttl_seconds": 1 }))).await;
+        assert_eq!(status, StatusCode::OK, "{}", short);
+        assert!(short["expires_at"].is_string());
+        let (status, _) = send(&app, selector(json!({ "all_tags": ["session:def", "alert"] }))).await;
+        assert_eq!(status, StatusCode::OK);
+
+        tokio::time::sleep(std::time::Duration::from_millis(1200)).await;
+        for (title, tag) in [("expired", "session:abc"), ("live", "alert")] {
+            let tags = if tag == "alert" { json!(["session:def", "alert"]) } else { json!([tag]) };
+            let (status, body) = send(&app, request("POST", "/breadcrumbs", token, Some(json!({ "title": title, "context": {}, "tags": tags })))).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+        }
+        for _ in 0..50 {
+            if !received.lock().unwrap().is_empty() { break; }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        assert_eq!(*received.lock().unwrap(), vec!["live".to_string()]);
+
+        let (_, listed) = send(&app, request("GET", "/subscriptions/selectors", token, None)).await;
+        assert_eq!(listed.as_array().unwrap().len(), 1);
+        let (_, listed) = send(&app, request("GET", "/subscriptions/selectors?include_expired=true", token, None)).await;
+        assert_eq!(listed.as_array().unwrap().len(), 2);
+
+        // Hygiene removes the expired one; the session's remaining selector goes by tag
+        let (status, run) = send(&app, request("POST", "/hygiene/run", Some(curator.as_str()), None)).await;
+        assert_eq!(status, StatusCode::OK, "{}", run);
+        assert!(run["expired_selectors_removed"].as_u64().unwrap() >= 1);
+        let (_, listed) = send(&app, request("GET", "/subscriptions/selectors?include_expired=true", token, None)).await;
+        assert_eq!(listed.as_array().unwrap().len(), 1);
+        let (status, body) = send(&app, request("DELETE", "/subscriptions/selectors?tag=session:def", token, None)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["deleted"], 1);
+        let (_, listed) = send(&app, request("GET", "/subscriptions/selectors?include_expired=true", token, None)).await;
+        assert!(listed.as_array().unwrap().is_empty());
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_tenant_hygiene_config_overrides_env_for_that_tenant(pool: sqlx::PgPool) {
+        let (app, owner_id) = setup(pool.clone()).await;
+        let other_owner = Uuid::new_v4();
+        Db { pool: pool.clone() }.ensure_tenant(other_owner, "Other Tenant").await.unwrap();
+        let emitter = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let curator = token(&app, owner_id, &["curator", "emitter", "subscriber"]).await;
+        let other = token(&app, other_owner, &["curator", "emitter", "subscriber"]).await;
+        let config = |context: Value| json!({ "title": "Hygiene", "schema_name": "system.hygiene.config.v1", "context": context, "tags": [] });
+
+        // Curators only, and only values the runner can use
+        let (status, _) = send(&app, request("POST", "/breadcrumbs", Some(&emitter), Some(config(json!({ "log_retention_days": 14 }))))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        for bad in [json!({ "healthcheck_ttl_minutes": 0 }), json!({ "policies": { "expired_ttl": "no" } }), json!({ "run_interval_seconds": 5 })] {
+            let (status, body) = send(&app, request("POST", "/breadcrumbs", Some(&curator), Some(config(bad)))).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", body);
+        }
+        let (status, body) = send(&app, request("POST", "/breadcrumbs", Some(&curator), Some(config(json!({
+            "healthcheck_ttl_minutes": 60, "policies": { "expired_ttl": false }
+        }))))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let config_id = body["id"].as_str().unwrap().to_string();
+
+        // The tenant's health checks get its TTL from the next create on
+        let (_, body) = send(&app, request("POST", "/breadcrumbs", Some(&emitter), Some(json!({
+            "title": "Health", "schema_name": "tool.request.v1", "context": {}, "tags": ["health:check"]
+        })))).await;
+        let (_, retention) = send(&app, request("GET", &format!("/breadcrumbs/{}/retention", body["id"].as_str().unwrap()), Some(&emitter), None)).await;
+        let ttl: chrono::DateTime<chrono::Utc> = retention["ttl"].as_str().unwrap().parse().unwrap();
+        assert!(ttl > chrono::Utc::now() + chrono::Duration::minutes(30), "{}", retention);
+
+        // Both tenants hold an expired breadcrumb; the run leaves the one whose tenant turned expired_ttl off
+        let mut ids = Vec::new();
+        for token in [&curator, &other] {
+            let (_, body) = send(&app, request("POST", "/breadcrumbs", Some(token), Some(json!({ "title": "stale", "context": {}, "tags": [] })))).await;
+            ids.push(body["id"].as_str().unwrap().to_string());
+        }
+        sqlx::query("update breadcrumbs set ttl = now() - interval '1 minute' where id = any($1::uuid[])").bind(&ids).execute(&pool).await.unwrap();
+        let (status, run) = send(&app, request("POST", "/hygiene/run", Some(&curator), None)).await;
+        assert_eq!(status, StatusCode::OK, "{}", run);
+        let left: Vec<String> = sqlx::query_scalar("select id::text from breadcrumbs where id = any($1::uuid[])").bind(&ids).fetch_all(&pool).await.unwrap();
+        assert_eq!(left, vec![ids[0].clone()]);
+
+        let (_, stats) = send(&app, request("GET", "/hygiene/stats", Some(&curator), None)).await;
+        assert_eq!(stats["tenant_config"]["source"], config_id.as_str());
+        assert_eq!(stats["tenant_config"]["healthcheck_ttl_minutes"], 60);
+        assert_eq!(stats["tenant_config"]["policies"]["expired_ttl"], false);
+        let (_, stats) = send(&app, request("GET", "/hygiene/stats", Some(&other), None)).await;
+        assert_eq!(stats["tenant_config"]["source"], Value::Null);
+        assert_eq!(stats["tenant_config"]["policies"]["expired_ttl"], true);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_diff_between_versions(pool: sqlx::PgPool) {
+        let (app, owner_id) = setup(pool).await;
+        let token = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let token = Some(token.as_str());
+        let (_, body) = send(&app, request("POST", "/breadcrumbs", token, Some(json!({
+            "title": "plan", "context": { "status": "draft", "steps": ["a", "b"] }, "tags": []
+        })))).await;
+        let id = body["id"].as_str().unwrap().to_string();
+        for (version, context) in [(1, json!({ "status": "review", "steps": ["a", "b", "c"] })), (2, json!({ "status": "done", "steps": ["a"], "owner": "x" }))] {
+            let mut req = request("PATCH", &format!("/breadcrumbs/{}", id), token, Some(json!({ "context": context })));
+            req.headers_mut().insert(header::IF_MATCH, format!("\"{}\"", version).parse().unwrap());
+            let (status, body) = send(&app, req).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+        }
+
+        // Defaults to the latest change
+        let (status, body) = send(&app, request("GET", &format!("/breadcrumbs/{}/diff", id), token, None)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!((body["from"].as_i64(), body["to"].as_i64(), &body["swapped"]), (Some(2), Some(3), &json!(false)));
+        assert_eq!(body["patch"], json!([
+            { "op": "add", "path": "/owner", "value": "x" },
+            { "op": "replace", "path": "/status", "value": "done" },
+            { "op": "remove", "path": "/steps/2" },
+            { "op": "remove", "path": "/steps/1" }
+        ]));
+        assert_eq!(body["summary"]["changed"], json!([{ "path": "/status", "old": "review", "new": "done" }]));
+        assert_eq!(body["truncated"], false);
+
+        // from > to is answered older to newer, flagged
+        let (_, body) = send(&app, request("GET", &format!("/breadcrumbs/{}/diff?from=3&to=1", id), token, None)).await;
+        assert_eq!((body["from"].as_i64(), body["to"].as_i64(), &body["swapped"]), (Some(1), Some(3), &json!(true)));
+        assert_eq!(body["summary"]["changed"][0], json!({ "path": "/status", "old": "draft", "new": "done" }));
+
+        let (status, _) = send(&app, request("GET", &format!("/breadcrumbs/{}/diff?from=1&to=9", id), token, None)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = send(&app, request("GET", &format!("/breadcrumbs/{}/diff", Uuid::new_v4()), token, None)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_references_are_validated_listed_and_guard_deletes(pool: sqlx::PgPool) {
+        let (app, owner_id) = setup(pool.clone()).await;
+        let other_owner = Uuid::new_v4();
+        Db { pool }.ensure_tenant(other_owner, "Other Tenant").await.unwrap();
+        let theirs = token(&app, other_owner, &["emitter", "subscriber"]).await;
+        let token = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let token = Some(token.as_str());
+        let create = |body: Value| {
+            let req = request("POST", "/breadcrumbs", token, Some(body));
+            let app = app.clone();
+            async move { send(&app, req).await }
+        };
+        let (_, body) = create(json!({ "title": "question", "context": {}, "tags": [] })).await;
+        let trigger = body["id"].as_str().unwrap().to_string();
+        let (_, body) = send(&app, request("POST", "/breadcrumbs", Some(&theirs), Some(json!({ "title": "elsewhere", "context": {}, "tags": [] })))).await;
+        let foreign = body["id"].as_str().unwrap().to_string();
+
+        // Missing and other tenants' breadcrumbs can't be referenced
+        for target in [Uuid::new_v4().to_string(), foreign] {
+            let (status, _) = create(json!({ "title": "answer", "context": {}, "tags": [], "references": [{ "field": "parent_id", "breadcrumb_id": target }] })).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        }
+        let (status, _) = create(json!({ "title": "answer", "context": { "$refs": "nope" }, "tags": [] })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // `$refs` in the context declares them too
+        let (status, body) = create(json!({
+            "title": "answer", "tags": [],
+            "context": { "trigger_event_id": trigger, "$refs": [{ "field": "trigger_event_id", "breadcrumb_id": trigger, "relation": "triggered_by" }] },
+        })).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let answer = body["id"].as_str().unwrap().to_string();
+        let (status, body) = send(&app, request("GET", &format!("/breadcrumbs/{}/references", answer), token, None)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!((body[0]["target_id"].as_str(), body[0]["field"].as_str(), body[0]["relation"].as_str()), (Some(trigger.as_str()), Some("trigger_event_id"), Some("triggered_by")));
+        let (_, body) = send(&app, request("GET", &format!("/breadcrumbs/{}/referenced_by", trigger), token, None)).await;
+        assert_eq!(body.as_array().unwrap().iter().map(|r| r["source_id"].as_str().unwrap()).collect::<Vec<_>>(), vec![answer.as_str()]);
+        let (status, _) = send(&app, request("GET", &format!("/breadcrumbs/{}/referenced_by", trigger), Some(&theirs), None)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // A PATCH without references or $refs leaves them; an unknown target fails the whole update
+        let patch = |body: Value| {
+            let req = request("PATCH", &format!("/breadcrumbs/{}", answer), token, Some(body));
+            let app = app.clone();
+            async move { send(&app, req).await }
+        };
+        let (status, _) = patch(json!({ "title": "answer v2" })).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = patch(json!({ "title": "answer v3", "references": [{ "field": "x", "breadcrumb_id": Uuid::new_v4() }] })).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let (_, body) = send(&app, request("GET", &format!("/breadcrumbs/{}", answer), token, None)).await;
+        assert_eq!(body["title"], "answer v2");
+        let (_, body) = send(&app, request("GET", &format!("/breadcrumbs/{}/references", answer), token, None)).await;
+        assert_eq!(body.as_array().unwrap().len(), 1);
+
+        // Deleting the referenced breadcrumb needs force, and then the reference is gone
+        let uri = format!("/breadcrumbs/{}", trigger);
+        let (status, body) = send(&app, request("DELETE", &uri, token, None)).await;
+        assert_eq!(status, StatusCode::CONFLICT, "{}", body);
+        assert_eq!(body["error"], "breadcrumb_is_referenced");
+        assert_eq!(body["referenced_by"][0]["source_id"].as_str(), Some(answer.as_str()));
+        let (status, _) = send(&app, request("GET", &uri, token, None)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, body) = send(&app, request("DELETE", &format!("{}?force=true", uri), token, None)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["broken_references"], 1);
+        let (_, body) = send(&app, request("GET", &format!("/breadcrumbs/{}/references", answer), token, None)).await;
+        assert_eq!(body, json!([]));
+        let (status, _) = send(&app, request("DELETE", &format!("/breadcrumbs/{}", answer), token, None)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_admin_routes_need_admin_or_compat_curator(pool: sqlx::PgPool) {
+        let db = Db { pool };
+        let owner_id = Uuid::new_v4();
+        db.ensure_tenant(owner_id, "API Test").await.unwrap();
+        let compat = app(db.clone(), jwt_auth()).await;
+        let mut auth = jwt_auth();
+        auth.admin_accepts_curator = false;
+        let strict = app(db, auth).await;
+
+        let admin = token(&compat, owner_id, &["admin"]).await;
+        let curator = token(&compat, owner_id, &["curator", "emitter", "subscriber"]).await;
+        let emitter = token(&compat, owner_id, &["emitter", "subscriber"]).await;
+        for (app, token, expected) in [
+            (&compat, &admin, StatusCode::OK),
+            (&compat, &curator, StatusCode::OK),
+            (&compat, &emitter, StatusCode::FORBIDDEN),
+            (&strict, &admin, StatusCode::OK),
+            (&strict, &curator, StatusCode::FORBIDDEN),
+            (&strict, &emitter, StatusCode::FORBIDDEN),
+        ] {
+            let (status, body) = send(app, request("GET", "/admin/stats", Some(token), None)).await;
+            assert_eq!(status, expected, "{}", body);
+        }
+
+        // Curator-only routes outside /admin don't take the admin role
+        let (status, _) = send(&strict, request("GET", "/hygiene/stats", Some(&admin), None)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send(&strict, request("GET", "/hygiene/stats", Some(&curator), None)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_access_analytics_are_curator_views_of_the_flushed_counts(pool: sqlx::PgPool) {
+        let (app, owner_id) = setup(pool.clone()).await;
+        let user = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let curator = token(&app, owner_id, &["curator", "emitter", "subscriber"]).await;
+        let mut ids = Vec::new();
+        for title in ["used", "unused"] {
+            let (status, body) = send(&app, request("POST", "/breadcrumbs", Some(&user), Some(json!({ "title": title, "context": {}, "schema_name": "knowledge.v1" })))).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+            ids.push(body["id"].as_str().unwrap().to_string());
+        }
+        // What the flusher would have written after two days of assemblies by two agents
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        for (agent, access_type, days_ago, count) in [(a, "context_assembly", 0, 3), (b, "context_assembly", 1, 2), (a, "api_read", 0, 1)] {
+            sqlx::query("insert into breadcrumb_access (owner_id, breadcrumb_id, agent_id, access_type, day, count) values ($1, $2::uuid, $3, $4, current_date - $5::int, $6)")
+                .bind(owner_id).bind(&ids[0]).bind(agent).bind(access_type).bind(days_ago).bind(count as i64)
+                .execute(&pool).await.unwrap();
+        }
+
+        let uri = format!("/breadcrumbs/{}/analytics", ids[0]);
+        let (status, _) = send(&app, request("GET", &uri, Some(&user), None)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, body) = send(&app, request("GET", &format!("{}?window=2d", uri), Some(&curator), None)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["totals"], json!({ "api_read": 1, "context_assembly": 5, "search_hit": 0 }));
+        assert_eq!(body["by_day"][0]["counts"], json!({ "api_read": 1, "context_assembly": 3 }));
+        assert_eq!(body["by_agent"].as_array().unwrap().len(), 2);
+        let (status, body) = send(&app, request("GET", &format!("{}?window=1d", uri), Some(&curator), None)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["totals"]["context_assembly"], 3);
+        for bad in ["?window=12h", "?window=0d"] {
+            let (status, _) = send(&app, request("GET", &format!("{}{}", uri, bad), Some(&curator), None)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", bad);
+        }
+        let (status, _) = send(&app, request("GET", &format!("/breadcrumbs/{}/analytics", Uuid::new_v4()), Some(&curator), None)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, body) = send(&app, request("GET", "/analytics/top_breadcrumbs?window=7d&by=context_assembly", Some(&curator), None)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let top = body["breadcrumbs"].as_array().unwrap();
+        assert_eq!(top.len(), 1, "never assembled breadcrumbs don't rank");
+        assert_eq!((top[0]["breadcrumb_id"].as_str(), top[0]["count"].as_i64(), top[0]["agents"].as_i64()), (Some(ids[0].as_str()), Some(5), Some(2)));
+        let (status, _) = send(&app, request("GET", "/analytics/top_breadcrumbs?by=popularity", Some(&curator), None)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = send(&app, request("GET", "/analytics/top_breadcrumbs", Some(&user), None)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // Only reads can be claimed through bulk_get
+        let (status, _) = send(&app, request("POST", "/breadcrumbs/bulk_get", Some(&user), Some(json!({ "ids": [ids[1]], "access": "context_assembly" })))).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&app, request("POST", "/breadcrumbs/bulk_get", Some(&user), Some(json!({ "ids": [ids[1]], "access": "search_hit" })))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        // Dry runs read without counting
+        let (status, _) = send(&app, request("POST", "/breadcrumbs/bulk_get", Some(&user), Some(json!({ "ids": [ids[1]], "access": "none" })))).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[sqlx::test(migrations = "../../migrations")]
+    async fn test_session_stats_follow_writes_and_rebuild_converges(pool: sqlx::PgPool) {
+        let (app, owner_id) = setup(pool.clone()).await;
+        let user = token(&app, owner_id, &["emitter", "subscriber"]).await;
+        let curator = token(&app, owner_id, &["curator", "emitter", "subscriber"]).await;
+        let create = |token: &str, schema: &str, tags: Value| {
+            let req = request("POST", "/breadcrumbs", Some(token), Some(json!({ "title": schema, "context": {}, "schema_name": schema, "tags": tags })));
+            let app = app.clone();
+            async move {
+                let (status, body) = send(&app, req).await;
+                assert_eq!(status, StatusCode::OK, "{}", body);
+                body["id"].as_str().unwrap().to_string()
+            }
+        };
+        let sessions = |query: &str| {
+            let req = request("GET", &format!("/sessions{}", query), Some(&curator), None);
+            let app = app.clone();
+            async move {
+                let (status, body) = send(&app, req).await;
+                assert_eq!(status, StatusCode::OK, "{}", body);
+                body
+            }
+        };
+
+        create(&user, "user.message.v1", json!(["session:a"])).await;
+        let response = create(&curator, "agent.response.v1", json!(["session:a"])).await;
+        create(&user, "note.v1", json!(["session:a", "session:b"])).await;
+        let expiring = create(&user, "user.message.v1", json!(["session:b"])).await;
+        create(&user, "note.v1", json!(["unrelated"])).await;
+
+        let body = sessions("?order=breadcrumb_count").await;
+        let list = body["sessions"].as_array().unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0]["session_tag"], "session:a");
+        assert_eq!((list[0]["breadcrumb_count"].as_i64(), list[0]["message_count"].as_i64()), (Some(3), Some(2)));
+        assert_eq!(list[0]["agents"].as_array().unwrap().len(), 2);
+        assert_eq!((list[1]["breadcrumb_count"].as_i64(), list[1]["message_count"].as_i64()), (Some(2), Some(1)));
+
+        let (status, _) = send(&app, request("DELETE", &format!("/breadcrumbs/{}", response), Some(&curator), None)).await;
+        assert_eq!(status, StatusCode::OK);
+        let body = sessions("?order=breadcrumb_count").await;
+        assert_eq!((body["sessions"][0]["breadcrumb_count"].as_i64(), body["sessions"][0]["message_count"].as_i64()), (Some(2), Some(1)));
+        assert_eq!(body["sessions"][0]["agents"].as_array().unwrap().len(), 1);
+
+        // Hygiene deletes count too
+        sqlx::query("update breadcrumbs set ttl = now() - interval '1 minute' where id = $1::uuid").bind(&expiring).execute(&pool).await.unwrap();
+        let (status, body) = send(&app, request("POST", "/hygiene/run", Some(&curator), None)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let body = sessions("?order=breadcrumb_count").await;
+        assert_eq!(body["sessions"][1]["session_tag"], "session:b");
+        assert_eq!((body["sessions"][1]["breadcrumb_count"].as_i64(), body["sessions"][1]["message_count"].as_i64()), (Some(1), Some(0)));
+
+        let page = sessions("?limit=1").await;
+        assert_eq!((page["sessions"].as_array().unwrap().len(), page["has_more"].as_bool(), page["next_offset"].as_i64()), (1, Some(true), Some(1)));
+        let page = sessions("?limit=1&offset=1").await;
+        assert_eq!((page["sessions"].as_array().unwrap().len(), page["has_more"].as_bool()), (1, Some(false)));
+        let (status, _) = send(&app, request("GET", "/sessions?order=title", Some(&curator), None)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // A rebuild from scratch lands on the numbers the triggers kept, and repairs drift
+        let before = sessions("").await;
+        sqlx::query("update session_stats set breadcrumb_count = 99, message_count = 99").execute(&pool).await.unwrap();
+        sqlx::query("delete from session_stat_agents").execute(&pool).await.unwrap();
+        let (status, _) = send(&app, request("POST", "/admin/sessions/rebuild", Some(&user), None)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, body) = send(&app, request("POST", "/admin/sessions/rebuild", Some(&curator), None)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["sessions"], 2);
+        assert_eq!(sessions("").await, before);
+    }
+}
@@ -0,0 +1,317 @@
+/*!
+ * RCRT Echo Agent
+ *
+ * Minimal end-to-end example of an agent talking to rcrt-server:
+ * - Obtains a JWT (or runs tokenless against AUTH_MODE=disabled)
+ * - Registers itself and a selector for the demo tag
+ * - Listens on the SSE stream for user.message.v1 breadcrumbs, catching up on
+ *   what it missed while offline via /events/missed on every (re)connect
+ * - Answers each one with an agent.response.v1 breadcrumb
+ *
+ * Environment:
+ * - RCRT_URL   (default http://localhost:8081)
+ * - OWNER_ID   (default 00000000-0000-0000-0000-000000000001)
+ * - AGENT_ID   (default 00000000-0000-0000-0000-0000000000e0)
+ * - ECHO_TAG   (default demo:echo)
+ */
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use futures::stream::StreamExt;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashSet;
+use std::env;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+const MESSAGE_SCHEMA: &str = "user.message.v1";
+const RESPONSE_SCHEMA: &str = "agent.response.v1";
+/// How far back the first connection catches up
+const STARTUP_CATCHUP_MINS: i64 = 60;
+/// Re-scan this much before the last sign of life to absorb clock skew with the server
+const CATCHUP_OVERLAP_SECS: i64 = 30;
+/// Oldest `since` the server's /events/missed accepts
+const MISSED_WINDOW_DAYS: i64 = 7;
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SelectorResponse {
+    id: Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+struct BreadcrumbEvent {
+    #[serde(rename = "type")]
+    event_type: String,
+    breadcrumb_id: Option<Uuid>,
+    schema_name: Option<String>,
+    tags: Option<Vec<String>>,
+    context: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MissedPage {
+    events: Vec<BreadcrumbEvent>,
+    next_cursor: Option<String>,
+    has_more: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct BreadcrumbView {
+    id: Uuid,
+    tags: Vec<String>,
+    context: serde_json::Value,
+}
+
+struct EchoAgent {
+    base_url: String,
+    http: reqwest::Client,
+    token: Option<String>,
+    agent_id: Uuid,
+    tag: String,
+}
+
+impl EchoAgent {
+    async fn connect(base_url: String, owner_id: Uuid, agent_id: Uuid, tag: String) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(30))
+            .build()?;
+        let mut agent = EchoAgent { base_url, http, token: None, agent_id, tag };
+        agent.token = agent.request_token(owner_id).await?;
+        Ok(agent)
+    }
+
+    // 503 means the server has no signing key (AUTH_MODE=disabled), so run tokenless
+    async fn request_token(&self, owner_id: Uuid) -> Result<Option<String>> {
+        let response = self.http
+            .post(format!("{}/auth/token", self.base_url))
+            .json(&json!({
+                "owner_id": owner_id.to_string(),
+                "agent_id": self.agent_id.to_string(),
+                "roles": ["emitter", "subscriber"],
+            }))
+            .send()
+            .await
+            .context("Failed to request token")?;
+        if response.status() == reqwest::StatusCode::SERVICE_UNAVAILABLE {
+            warn!("🔐 Token endpoint unavailable, continuing without auth");
+            return Ok(None);
+        }
+        let response = response.error_for_status().context("Token request failed")?;
+        let token: TokenResponse = response.json().await?;
+        info!("🔐 JWT token acquired");
+        Ok(Some(token.token))
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let builder = self.http.request(method, format!("{}{}", self.base_url, path));
+        match &self.token {
+            Some(token) => builder.bearer_auth(token),
+            None => builder,
+        }
+    }
+
+    async fn register(&self) -> Result<Uuid> {
+        self.request(reqwest::Method::POST, &format!("/agents/{}", self.agent_id))
+            .json(&json!({ "roles": ["emitter", "subscriber"] }))
+            .send()
+            .await?
+            .error_for_status()
+            .context("Agent registration failed")?;
+
+        let selector: SelectorResponse = self.request(reqwest::Method::POST, "/subscriptions/selectors")
+            .json(&json!({ "any_tags": [self.tag], "schema_name": MESSAGE_SCHEMA }))
+            .send()
+            .await?
+            .error_for_status()
+            .context("Selector creation failed")?
+            .json()
+            .await?;
+        info!("✅ Registered agent {} with selector {} on '{}'", self.agent_id, selector.id, self.tag);
+        Ok(selector.id)
+    }
+
+    async fn unregister(&self, selector_id: Uuid) {
+        let result = self.request(reqwest::Method::DELETE, &format!("/subscriptions/selectors/{}", selector_id))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+        if let Err(e) = result {
+            warn!("Failed to delete selector {}: {}", selector_id, e);
+        }
+    }
+
+    async fn run(&self) -> Result<()> {
+        // The same message can arrive via the filtered stream and the selector fanout,
+        // and edits re-emit it; answer each message once
+        let mut handled: HashSet<Uuid> = HashSet::new();
+        let mut last_seen = Utc::now() - chrono::Duration::minutes(STARTUP_CATCHUP_MINS);
+        loop {
+            if let Err(e) = self.stream_events(&mut last_seen, &mut handled).await {
+                error!("SSE connection error: {}, reconnecting in 5s...", e);
+            } else {
+                warn!("SSE stream ended, reconnecting in 5s...");
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+        }
+    }
+
+    /// Subscribes first and only then catches up, so nothing falls between the two;
+    /// the overlap is dropped by `handled`. `last_seen` follows received chunks (pings
+    /// arrive every few seconds), so a reconnect only catches up on the outage
+    async fn stream_events(&self, last_seen: &mut DateTime<Utc>, handled: &mut HashSet<Uuid>) -> Result<()> {
+        let response = self.request(reqwest::Method::GET, "/events/stream")
+            .query(&self.filter())
+            .header("Accept", "text/event-stream")
+            // SSE is long-lived; don't inherit the client's request timeout
+            .timeout(std::time::Duration::from_secs(60 * 60 * 24))
+            .send()
+            .await?
+            .error_for_status()?;
+        info!("📡 SSE stream connected");
+        self.catch_up(*last_seen - chrono::Duration::seconds(CATCHUP_OVERLAP_SECS), handled).await?;
+
+        let mut stream = response.bytes_stream();
+        let mut buffer = String::new();
+        while let Some(chunk) = stream.next().await {
+            buffer.push_str(&String::from_utf8_lossy(&chunk?));
+            *last_seen = Utc::now();
+            while let Some(newline_pos) = buffer.find('\n') {
+                let line = buffer[..newline_pos].trim().to_string();
+                buffer.drain(..=newline_pos);
+                let Some(data) = line.strip_prefix("data:") else { continue };
+                let Ok(event) = serde_json::from_str::<BreadcrumbEvent>(data.trim()) else { continue };
+                self.handle(event, handled).await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Pages through GET /events/missed with the stream's filter
+    async fn catch_up(&self, since: DateTime<Utc>, handled: &mut HashSet<Uuid>) -> Result<()> {
+        // Older than the server's window is a 400; answer what's still there
+        let since = since.max(Utc::now() - chrono::Duration::days(MISSED_WINDOW_DAYS) + chrono::Duration::minutes(1));
+        let mut query = self.filter();
+        query.push(("since", since.to_rfc3339()));
+        let mut caught_up = 0;
+        loop {
+            let page: MissedPage = self.request(reqwest::Method::GET, "/events/missed")
+                .query(&query)
+                .send()
+                .await?
+                .error_for_status()
+                .context("Catch-up failed")?
+                .json()
+                .await?;
+            caught_up += page.events.len();
+            for event in page.events {
+                self.handle(event, handled).await;
+            }
+            match page.next_cursor {
+                Some(cursor) if page.has_more => {
+                    query.retain(|(key, _)| *key != "since" && *key != "cursor");
+                    query.push(("cursor", cursor));
+                }
+                _ => break,
+            }
+        }
+        if caught_up > 0 {
+            info!("⏪ Caught up on {} missed events since {}", caught_up, since);
+        }
+        Ok(())
+    }
+
+    fn filter(&self) -> Vec<(&'static str, String)> {
+        vec![("any_tags", self.tag.clone()), ("schema_name", MESSAGE_SCHEMA.to_string())]
+    }
+
+    async fn handle(&self, event: BreadcrumbEvent, handled: &mut HashSet<Uuid>) {
+        if !self.is_echo_request(&event) {
+            return;
+        }
+        let Some(id) = event.breadcrumb_id else { return };
+        if !handled.insert(id) {
+            return;
+        }
+        if let Err(e) = self.respond(id, event).await {
+            error!("Failed to answer {}: {}", id, e);
+        }
+    }
+
+    fn is_echo_request(&self, event: &BreadcrumbEvent) -> bool {
+        event.event_type != "ping"
+            && event.schema_name.as_deref() == Some(MESSAGE_SCHEMA)
+            && event.tags.as_ref().is_some_and(|tags| tags.contains(&self.tag))
+    }
+
+    async fn respond(&self, message_id: Uuid, event: BreadcrumbEvent) -> Result<()> {
+        // Events carry the raw context; only agent-channel events without it need a fetch
+        let message = match (event.tags, event.context) {
+            (Some(tags), Some(context)) => BreadcrumbView { id: message_id, tags, context },
+            _ => self.request(reqwest::Method::GET, &format!("/breadcrumbs/{}", message_id))
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?,
+        };
+        let content = message.context.get("content").and_then(|c| c.as_str()).unwrap_or_default();
+
+        // Keep the demo and session tags so the reply lands in the same conversation
+        let mut tags: Vec<String> = message.tags.iter()
+            .filter(|t| *t == &self.tag || t.starts_with("session:"))
+            .cloned()
+            .collect();
+        tags.push("echo:response".to_string());
+
+        self.request(reqwest::Method::POST, "/breadcrumbs")
+            .json(&json!({
+                "title": "Echo response",
+                "schema_name": RESPONSE_SCHEMA,
+                "tags": tags,
+                "context": {
+                    "content": format!("echo: {}", content),
+                    "in_reply_to": message.id,
+                },
+            }))
+            .send()
+            .await?
+            .error_for_status()
+            .context("Failed to create response")?;
+        info!("💬 Answered {}", message.id);
+        Ok(())
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
+        .init();
+
+    let base_url = env::var("RCRT_URL").unwrap_or_else(|_| "http://localhost:8081".to_string());
+    let owner_id = env::var("OWNER_ID").ok().and_then(|s| s.parse().ok())
+        .unwrap_or_else(|| Uuid::parse_str("00000000-0000-0000-0000-000000000001").unwrap());
+    let agent_id = env::var("AGENT_ID").ok().and_then(|s| s.parse().ok())
+        .unwrap_or_else(|| Uuid::parse_str("00000000-0000-0000-0000-0000000000e0").unwrap());
+    let tag = env::var("ECHO_TAG").unwrap_or_else(|_| "demo:echo".to_string());
+
+    info!("🚀 Echo agent connecting to {}", base_url);
+    let agent = EchoAgent::connect(base_url, owner_id, agent_id, tag).await?;
+    let selector_id = agent.register().await?;
+
+    tokio::select! {
+        result = agent.run() => result?,
+        _ = tokio::signal::ctrl_c() => info!("🛑 Shutting down"),
+    }
+    agent.unregister(selector_id).await;
+    Ok(())
+}
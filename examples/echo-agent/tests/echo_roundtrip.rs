@@ -0,0 +1,111 @@
+//! End-to-end roundtrip against a running rcrt-server.
+//!
+//! Needs Postgres, NATS and the server up (see examples/echo-agent/README.md), then:
+//! `RCRT_E2E_URL=http://localhost:8081 cargo test -p echo-agent -- --ignored`
+
+use serde_json::json;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+use uuid::Uuid;
+
+const OWNER_ID: &str = "00000000-0000-0000-0000-000000000001";
+
+async fn token_for(http: &reqwest::Client, base_url: &str, agent_id: Uuid) -> Option<String> {
+    let resp = http
+        .post(format!("{}/auth/token", base_url))
+        .json(&json!({ "owner_id": OWNER_ID, "agent_id": agent_id.to_string(), "roles": ["emitter", "subscriber"] }))
+        .send()
+        .await
+        .expect("token request");
+    if resp.status() == reqwest::StatusCode::SERVICE_UNAVAILABLE {
+        return None;
+    }
+    let body: serde_json::Value = resp.error_for_status().expect("token status").json().await.expect("token body");
+    body["token"].as_str().map(|s| s.to_string())
+}
+
+fn authed(builder: reqwest::RequestBuilder, token: &Option<String>) -> reqwest::RequestBuilder {
+    match token {
+        Some(t) => builder.bearer_auth(t),
+        None => builder,
+    }
+}
+
+#[tokio::test]
+#[ignore = "requires a running rcrt stack; set RCRT_E2E_URL"]
+async fn test_echo_agent_answers_user_message() {
+    let base_url = std::env::var("RCRT_E2E_URL").unwrap_or_else(|_| "http://localhost:8081".to_string());
+    let timeout = Duration::from_secs(
+        std::env::var("RCRT_E2E_TIMEOUT_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(30),
+    );
+    // Unique tag per run so stale breadcrumbs and other agents don't interfere
+    let tag = format!("demo:echo-e2e-{}", Uuid::new_v4());
+    let http = reqwest::Client::new();
+
+    let mut agent = Command::new(env!("CARGO_BIN_EXE_echo-agent"))
+        .env("RCRT_URL", &base_url)
+        .env("OWNER_ID", OWNER_ID)
+        .env("AGENT_ID", Uuid::new_v4().to_string())
+        .env("ECHO_TAG", &tag)
+        .env("RUST_LOG", "info")
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .expect("spawn echo-agent");
+
+    // Don't post until the agent's SSE stream is live, or the message is missed
+    let mut lines = BufReader::new(agent.stdout.take().unwrap()).lines();
+    tokio::time::timeout(timeout, async {
+        while let Some(line) = lines.next_line().await.expect("agent stdout") {
+            if line.contains("SSE stream connected") {
+                return;
+            }
+        }
+        panic!("echo-agent exited before connecting");
+    })
+    .await
+    .expect("echo-agent did not connect in time");
+    tokio::spawn(async move { while let Ok(Some(_)) = lines.next_line().await {} });
+
+    let token = token_for(&http, &base_url, Uuid::new_v4()).await;
+    let created: serde_json::Value = authed(http.post(format!("{}/breadcrumbs", base_url)), &token)
+        .json(&json!({
+            "title": "E2E message",
+            "schema_name": "user.message.v1",
+            "tags": [tag],
+            "context": { "content": "hello" }
+        }))
+        .send()
+        .await
+        .expect("create message")
+        .error_for_status()
+        .expect("create status")
+        .json()
+        .await
+        .expect("create body");
+    let message_id = created["id"].as_str().expect("message id").to_string();
+
+    let response = tokio::time::timeout(timeout, async {
+        loop {
+            let items: Vec<serde_json::Value> = authed(http.get(format!("{}/breadcrumbs", base_url)), &token)
+                .query(&[("tag", tag.as_str()), ("schema_name", "agent.response.v1"), ("include_context", "true")])
+                .send()
+                .await
+                .expect("list responses")
+                .json()
+                .await
+                .expect("list body");
+            if let Some(found) = items.into_iter().find(|i| i["context"]["in_reply_to"] == json!(message_id)) {
+                return found;
+            }
+            tokio::time::sleep(Duration::from_millis(250)).await;
+        }
+    })
+    .await
+    .expect("no agent.response.v1 within timeout");
+
+    assert_eq!(response["context"]["content"], json!("echo: hello"));
+    assert!(response["tags"].as_array().unwrap().contains(&json!("echo:response")));
+}
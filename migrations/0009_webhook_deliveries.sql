@@ -0,0 +1,21 @@
+-- Webhook delivery log: one row per (webhook, breadcrumb version)
+-- Lets the server skip re-delivery after a crash or NATS redelivery,
+-- and gives receivers a stable delivery_id to dedupe on.
+create table if not exists webhook_deliveries (
+  delivery_id uuid primary key default uuid_generate_v4(),
+  owner_id uuid not null references tenants(id),
+  webhook_id uuid not null references agent_webhooks(id) on delete cascade,
+  breadcrumb_id uuid not null,
+  version integer not null,
+  status text not null default 'pending', -- 'pending' | 'delivered' | 'failed'
+  attempts integer not null default 0,
+  last_error text,
+  created_at timestamptz not null default now(),
+  completed_at timestamptz
+);
+
+create unique index if not exists uq_webhook_deliveries_target
+  on webhook_deliveries (webhook_id, breadcrumb_id, version);
+
+create index if not exists idx_webhook_deliveries_created_at
+  on webhook_deliveries (created_at);
@@ -0,0 +1,10 @@
+-- Durable buffer for NATS events that failed to publish (EVENT_BUFFER=outbox).
+-- Rows are replayed in id order by a background flusher and deleted once sent;
+-- replicas share the table via FOR UPDATE SKIP LOCKED.
+create table if not exists event_outbox (
+  id bigserial primary key,
+  owner_id uuid not null references tenants(id) on delete cascade,
+  subject text not null,
+  payload text not null,
+  failed_at timestamptz not null default now()
+);
@@ -0,0 +1,3 @@
+-- Per-schema usage aggregates (GET /schemas): count, first created, last updated per owner
+create index if not exists idx_breadcrumbs_owner_schema_usage
+  on breadcrumbs(owner_id, schema_name) include (created_at, updated_at);
@@ -0,0 +1,2 @@
+-- Final HTTP status of a dead-lettered webhook (null when the last attempt never got a response)
+alter table webhook_dlq add column if not exists last_status integer;
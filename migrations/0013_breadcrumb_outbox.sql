@@ -0,0 +1,22 @@
+-- Transactional outbox: one row per breadcrumb insert/update, written in the same
+-- transaction, so a committed change is always announced even if the process dies
+-- before publishing. The request path marks its row published after fanout; a
+-- background dispatcher (FOR UPDATE SKIP LOCKED) publishes whatever is left.
+create table if not exists breadcrumb_outbox (
+  id uuid primary key default uuid_generate_v4(),
+  owner_id uuid not null references tenants(id) on delete cascade,
+  breadcrumb_id uuid not null,
+  version integer not null,
+  event_type text not null, -- 'created' | 'updated'
+  created_at timestamptz not null default now(),
+  published_at timestamptz
+);
+
+create index if not exists idx_breadcrumb_outbox_pending
+  on breadcrumb_outbox (created_at) where published_at is null;
+
+create index if not exists idx_breadcrumb_outbox_target
+  on breadcrumb_outbox (breadcrumb_id, version);
+
+create index if not exists idx_breadcrumb_outbox_published_at
+  on breadcrumb_outbox (published_at) where published_at is not null;
@@ -0,0 +1,51 @@
+-- Content-addressed attachments: one row per (tenant, sha256), so identical uploads dedupe.
+-- Small blobs live inline in `data`; larger ones are written to the server's attachment
+-- store and only their key is kept in `storage_path`. breadcrumb_attachments links blobs to
+-- breadcrumbs and goes with them on delete; the hygiene runner removes blobs nothing links
+-- to any more (after a grace period measured from last_linked_at).
+create table if not exists attachments (
+  owner_id uuid not null references tenants(id) on delete cascade,
+  sha256 text not null,
+  content_type text not null,
+  size_bytes bigint not null,
+  data bytea,
+  storage_path text,
+  created_at timestamptz not null default now(),
+  last_linked_at timestamptz not null default now(),
+  primary key (owner_id, sha256),
+  check ((data is null) <> (storage_path is null))
+);
+
+create index if not exists idx_attachments_sha256 on attachments (sha256);
+
+create table if not exists breadcrumb_attachments (
+  breadcrumb_id uuid not null references breadcrumbs(id) on delete cascade,
+  owner_id uuid not null,
+  sha256 text not null,
+  filename text,
+  created_by uuid,
+  created_at timestamptz not null default now(),
+  primary key (breadcrumb_id, sha256),
+  foreign key (owner_id, sha256) references attachments (owner_id, sha256)
+);
+
+create index if not exists idx_breadcrumb_attachments_blob on breadcrumb_attachments (owner_id, sha256);
+
+-- Readers inherit access from the parent breadcrumb: a link is visible when its breadcrumb is
+-- (tenant or read_context ACL), and a blob when the tenant owns it or a visible link points at it
+alter table attachments enable row level security;
+alter table breadcrumb_attachments enable row level security;
+
+create policy breadcrumb_attachments_via_breadcrumb on breadcrumb_attachments
+  using (exists (select 1 from breadcrumbs b where b.id = breadcrumb_attachments.breadcrumb_id))
+  with check (owner_id = app_current_owner_id());
+
+create policy attachments_via_link on attachments
+  using (
+    owner_id = app_current_owner_id()
+    or exists (
+      select 1 from breadcrumb_attachments l
+      where l.owner_id = attachments.owner_id and l.sha256 = attachments.sha256
+    )
+  )
+  with check (owner_id = app_current_owner_id());
@@ -0,0 +1,3 @@
+-- created_after/created_before on /breadcrumbs and /breadcrumbs/search; updated_at ranges
+-- use idx_breadcrumbs_updated from 0001, exclude_tag the existing gin(tags) index
+create index if not exists idx_breadcrumbs_created on breadcrumbs(created_at desc);
@@ -0,0 +1,5 @@
+-- Optional handlebars template rendering the outgoing webhook body from the event (Slack,
+-- PagerDuty and other fixed-shape receivers); null keeps the raw event JSON. A delivery whose
+-- template failed to render goes out with the raw event and keeps the error here.
+alter table agent_webhooks add column if not exists payload_template text;
+alter table webhook_deliveries add column if not exists template_error text;
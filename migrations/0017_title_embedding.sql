@@ -0,0 +1,6 @@
+-- Optional second vector over the title alone (EMBED_TITLE_SEPARATELY), so short titles aren't
+-- drowned out by large contexts; searched with /breadcrumbs/search?target=title|both.
+-- Same dimension as embedding; rows from before this migration fill in via
+-- POST /admin/embeddings/backfill?which=title
+alter table breadcrumbs add column if not exists title_embedding vector(384);
+create index if not exists idx_breadcrumbs_title_embedding on breadcrumbs using ivfflat (title_embedding vector_cosine_ops);
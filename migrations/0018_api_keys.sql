@@ -0,0 +1,18 @@
+-- Long-lived API keys for server-to-server agents (`Authorization: ApiKey <key>`), as an
+-- alternative to RS256 JWTs. Only the BLAKE3 hash of the key is stored; `prefix` is its first
+-- characters, kept so curators can tell keys apart. Revoked keys stay for audit.
+-- Looked up by hash before the owner is known, so no RLS; queries filter owner_id themselves.
+create table if not exists api_keys (
+  id uuid primary key default uuid_generate_v4(),
+  owner_id uuid not null references tenants(id) on delete cascade,
+  agent_id uuid not null references agents(id) on delete cascade,
+  hashed_key text not null unique,
+  prefix text not null,
+  roles text[] not null,
+  name text,
+  created_at timestamptz not null default now(),
+  last_used_at timestamptz,
+  revoked_at timestamptz
+);
+
+create index if not exists idx_api_keys_agent on api_keys (owner_id, agent_id);
@@ -0,0 +1,8 @@
+-- Key for PUT /breadcrumbs/upsert: the sorted, comma-joined key tags (e.g. "consumer:x,session:y").
+-- At most one breadcrumb per (tenant, schema, key); rows written before keys existed are matched
+-- by tags on their next upsert, and the extras expired then.
+alter table breadcrumbs add column if not exists upsert_key text;
+
+create unique index if not exists idx_breadcrumbs_upsert_key
+  on breadcrumbs (owner_id, schema_name, upsert_key)
+  where upsert_key is not null;
@@ -0,0 +1,21 @@
+-- Runs of the POST /agents/run pipeline. Stages execute in a background task and each is saved
+-- as it starts and finishes, so a client whose connection drops can poll GET /agents/run/{id}.
+-- Runs left 'running' by a server that died are failed by the stale sweep, and finished runs
+-- are deleted once expires_at passes. The sweep spans tenants, so no RLS; queries filter owner_id themselves.
+create table if not exists agent_runs (
+  id uuid primary key default uuid_generate_v4(),
+  owner_id uuid not null references tenants(id) on delete cascade,
+  agent_id uuid not null,
+  status text not null default 'running' check (status in ('running', 'succeeded', 'failed', 'cancelled')),
+  input jsonb not null,
+  stages jsonb not null default '[]'::jsonb,
+  result jsonb,
+  error text,
+  created_at timestamptz not null default now(),
+  updated_at timestamptz not null default now(),
+  finished_at timestamptz,
+  expires_at timestamptz
+);
+
+create index if not exists idx_agent_runs_running on agent_runs (updated_at) where status = 'running';
+create index if not exists idx_agent_runs_expires on agent_runs (expires_at) where expires_at is not null;
@@ -0,0 +1,13 @@
+-- Trigram indexes for GET /breadcrumbs/suggest (type-ahead on titles and tags). Both serve
+-- `ilike '%q%'` and similarity(); an array can't carry a trigram index, so tags are indexed as
+-- one space-joined string and the handler unnests the rows that string matched.
+create extension if not exists pg_trgm;
+
+create index if not exists idx_breadcrumbs_title_trgm on breadcrumbs using gin (title gin_trgm_ops);
+
+-- array_to_string is only stable; index expressions must be immutable
+create or replace function breadcrumb_tags_text(tags text[]) returns text
+  language sql immutable parallel safe
+  as $$ select array_to_string(tags, ' ') $$;
+
+create index if not exists idx_breadcrumbs_tags_trgm on breadcrumbs using gin (breadcrumb_tags_text(tags) gin_trgm_ops);
@@ -0,0 +1,13 @@
+-- Envelope-encrypted contexts (sensitivity secret with ENCRYPT_SECRET_CONTEXTS, or encrypt: true).
+-- context keeps the {"encrypted": true} stub; the real context is AES-GCM under a per-breadcrumb
+-- DEK, itself wrapped by the KEK named in context_kek_id, laid out like secrets.enc_blob/dek_encrypted.
+-- History rows carry the ciphertext of their version so rollback never needs plaintext.
+alter table breadcrumbs
+  add column if not exists context_encrypted bytea,
+  add column if not exists context_dek_encrypted bytea,
+  add column if not exists context_kek_id text;
+
+alter table breadcrumb_history
+  add column if not exists context_encrypted bytea,
+  add column if not exists context_dek_encrypted bytea,
+  add column if not exists context_kek_id text;
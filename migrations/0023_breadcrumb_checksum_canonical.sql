@@ -0,0 +1,8 @@
+-- Checksums are now taken over the context with object keys sorted, so they can be recomputed from
+-- what jsonb hands back. Older rows hashed the client's key order, which jsonb doesn't keep; they
+-- stay false and verification only reports them as unverifiable when they no longer match.
+alter table breadcrumbs
+  add column if not exists checksum_canonical boolean not null default false;
+
+alter table breadcrumb_history
+  add column if not exists checksum_canonical boolean not null default false;
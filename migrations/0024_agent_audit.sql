@@ -0,0 +1,14 @@
+-- Agent lifecycle audit; DELETE /agents/:id and the hygiene idle-agent sweep write an
+-- 'offboard' entry with what was removed. No foreign key to agents, so entries outlive the agent.
+-- Queries filter owner_id themselves, like api_keys.
+create table if not exists agent_audit (
+  id uuid primary key default uuid_generate_v4(),
+  owner_id uuid not null references tenants(id) on delete cascade,
+  agent_id uuid not null,
+  actor_agent_id uuid,
+  action text not null,
+  detail jsonb not null default '{}'::jsonb,
+  created_at timestamptz not null default now()
+);
+
+create index if not exists idx_agent_audit_agent on agent_audit (owner_id, agent_id, created_at desc);
@@ -0,0 +1,3 @@
+-- GET /breadcrumbs/:id/as_of looks up the latest version written at or before a timestamp; the
+-- (breadcrumb_id, version) primary key doesn't cover updated_at
+create index if not exists idx_breadcrumb_history_updated_at on breadcrumb_history (breadcrumb_id, updated_at);
@@ -0,0 +1,4 @@
+-- Session-scoped selectors can expire: the fanout skips them once expires_at passes and hygiene
+-- deletes them. Null never expires
+alter table selector_subscriptions add column if not exists expires_at timestamptz;
+create index if not exists idx_selector_subscriptions_expires_at on selector_subscriptions (expires_at) where expires_at is not null;
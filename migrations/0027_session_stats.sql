@@ -0,0 +1,145 @@
+-- Per-session counters for GET /sessions, so listing sessions doesn't group every breadcrumb by
+-- its `session:` tag. A breadcrumb counts toward each session tag it carries. Statement-level
+-- triggers keep the counters in step with every insert, update and delete on breadcrumbs,
+-- including hygiene, purge and cascade deletes; session_stats_rebuild(owner) recomputes an
+-- owner's rows from scratch (POST /admin/sessions/rebuild).
+create table if not exists session_stats (
+  owner_id uuid not null references tenants(id) on delete cascade,
+  session_tag text not null,
+  breadcrumb_count bigint not null default 0,
+  message_count bigint not null default 0,
+  -- Latest updated_at among the session's breadcrumbs
+  last_activity_at timestamptz not null,
+  primary key (owner_id, session_tag)
+);
+
+create index if not exists idx_session_stats_activity on session_stats (owner_id, last_activity_at desc);
+
+-- Breadcrumbs per creating agent, so an agent drops off a session when its last one goes
+create table if not exists session_stat_agents (
+  owner_id uuid not null,
+  session_tag text not null,
+  agent_id uuid not null,
+  breadcrumb_count bigint not null,
+  primary key (owner_id, session_tag, agent_id),
+  foreign key (owner_id, session_tag) references session_stats (owner_id, session_tag) on delete cascade
+);
+
+create or replace function session_stats_is_message(schema_name text) returns boolean
+  language sql immutable parallel safe
+  as $$ select coalesce(schema_name in ('user.message.v1', 'agent.response.v1'), false) $$;
+
+-- What one breadcrumb contributes to one of its sessions
+do $$ begin
+  create type session_stats_entry as (owner_id uuid, session_tag text, created_by uuid, is_message boolean, updated_at timestamptz);
+exception when duplicate_object then null; end $$;
+
+create or replace function session_stats_apply(removed session_stats_entry[], added session_stats_entry[]) returns void
+  language plpgsql
+  as $$
+begin
+  -- Removals first, so a breadcrumb updated within a session nets out
+  update session_stat_agents a set breadcrumb_count = a.breadcrumb_count - r.n
+  from (select owner_id, session_tag, created_by, count(*) as n from unnest(removed) where created_by is not null group by 1, 2, 3) r
+  where a.owner_id = r.owner_id and a.session_tag = r.session_tag and a.agent_id = r.created_by;
+
+  update session_stats s set breadcrumb_count = s.breadcrumb_count - r.n, message_count = s.message_count - r.m
+  from (select owner_id, session_tag, count(*) as n, count(*) filter (where is_message) as m from unnest(removed) group by 1, 2) r
+  where s.owner_id = r.owner_id and s.session_tag = r.session_tag;
+
+  insert into session_stats (owner_id, session_tag, breadcrumb_count, message_count, last_activity_at)
+  select owner_id, session_tag, count(*), count(*) filter (where is_message), max(updated_at) from unnest(added) group by 1, 2
+  on conflict (owner_id, session_tag) do update set
+    breadcrumb_count = session_stats.breadcrumb_count + excluded.breadcrumb_count,
+    message_count = session_stats.message_count + excluded.message_count,
+    last_activity_at = greatest(session_stats.last_activity_at, excluded.last_activity_at);
+
+  insert into session_stat_agents (owner_id, session_tag, agent_id, breadcrumb_count)
+  select owner_id, session_tag, created_by, count(*) from unnest(added) where created_by is not null group by 1, 2, 3
+  on conflict (owner_id, session_tag, agent_id) do update set
+    breadcrumb_count = session_stat_agents.breadcrumb_count + excluded.breadcrumb_count;
+
+  -- Emptied sessions go, and their agent rows with them
+  delete from session_stats s
+  using (select distinct owner_id, session_tag from unnest(removed)) r
+  where s.owner_id = r.owner_id and s.session_tag = r.session_tag and s.breadcrumb_count <= 0;
+  delete from session_stat_agents a
+  using (select distinct owner_id, session_tag, created_by from unnest(removed) where created_by is not null) r
+  where a.owner_id = r.owner_id and a.session_tag = r.session_tag and a.agent_id = r.created_by and a.breadcrumb_count <= 0;
+
+  -- A removed breadcrumb may have been the latest activity; look again unless something newer was just added
+  update session_stats s
+  set last_activity_at = coalesce(
+    (select max(b.updated_at) from breadcrumbs b where b.owner_id = s.owner_id and b.tags @> array[s.session_tag]),
+    s.last_activity_at)
+  from (select owner_id, session_tag, max(updated_at) as latest from unnest(removed) group by 1, 2) r
+  where s.owner_id = r.owner_id and s.session_tag = r.session_tag and r.latest >= s.last_activity_at;
+end $$;
+
+-- One function for the three triggers: each branch only reads the transition tables its event has.
+-- Updates that leave tags, schema, creator and updated_at alone (embeddings, TTL stamps) are skipped
+create or replace function session_stats_sync() returns trigger
+  language plpgsql
+  as $$
+begin
+  if tg_op = 'INSERT' then
+    perform session_stats_apply('{}', array(
+      select (n.owner_id, t.tag, n.created_by, session_stats_is_message(n.schema_name), n.updated_at)::session_stats_entry
+      from new_rows n, unnest(n.tags) as t(tag) where t.tag like 'session:%'));
+  elsif tg_op = 'DELETE' then
+    perform session_stats_apply(array(
+      select (o.owner_id, t.tag, o.created_by, session_stats_is_message(o.schema_name), o.updated_at)::session_stats_entry
+      from old_rows o, unnest(o.tags) as t(tag) where t.tag like 'session:%'), '{}');
+  else
+    perform session_stats_apply(
+      array(
+        select (o.owner_id, t.tag, o.created_by, session_stats_is_message(o.schema_name), o.updated_at)::session_stats_entry
+        from old_rows o join new_rows n on n.id = o.id, unnest(o.tags) as t(tag)
+        where t.tag like 'session:%'
+          and (o.tags, o.schema_name, o.created_by, o.updated_at) is distinct from (n.tags, n.schema_name, n.created_by, n.updated_at)),
+      array(
+        select (n.owner_id, t.tag, n.created_by, session_stats_is_message(n.schema_name), n.updated_at)::session_stats_entry
+        from new_rows n join old_rows o on o.id = n.id, unnest(n.tags) as t(tag)
+        where t.tag like 'session:%'
+          and (o.tags, o.schema_name, o.created_by, o.updated_at) is distinct from (n.tags, n.schema_name, n.created_by, n.updated_at)));
+  end if;
+  return null;
+end $$;
+
+drop trigger if exists breadcrumbs_session_stats_insert on breadcrumbs;
+create trigger breadcrumbs_session_stats_insert after insert on breadcrumbs
+  referencing new table as new_rows
+  for each statement execute function session_stats_sync();
+drop trigger if exists breadcrumbs_session_stats_update on breadcrumbs;
+create trigger breadcrumbs_session_stats_update after update on breadcrumbs
+  referencing old table as old_rows new table as new_rows
+  for each statement execute function session_stats_sync();
+drop trigger if exists breadcrumbs_session_stats_delete on breadcrumbs;
+create trigger breadcrumbs_session_stats_delete after delete on breadcrumbs
+  referencing old table as old_rows
+  for each statement execute function session_stats_sync();
+
+-- Returns the number of sessions the owner has. Counts rows as of the statement that reads them;
+-- writes committed meanwhile land on top through the triggers
+create or replace function session_stats_rebuild(p_owner_id uuid) returns bigint
+  language plpgsql
+  as $$
+declare
+  sessions bigint;
+begin
+  delete from session_stats where owner_id = p_owner_id;
+  insert into session_stats (owner_id, session_tag, breadcrumb_count, message_count, last_activity_at)
+  select b.owner_id, t.tag, count(*), count(*) filter (where session_stats_is_message(b.schema_name)), max(b.updated_at)
+  from breadcrumbs b, unnest(b.tags) as t(tag)
+  where b.owner_id = p_owner_id and t.tag like 'session:%'
+  group by 1, 2;
+  get diagnostics sessions = row_count;
+  insert into session_stat_agents (owner_id, session_tag, agent_id, breadcrumb_count)
+  select b.owner_id, t.tag, b.created_by, count(*)
+  from breadcrumbs b, unnest(b.tags) as t(tag)
+  where b.owner_id = p_owner_id and t.tag like 'session:%' and b.created_by is not null
+  group by 1, 2, 3;
+  return sessions;
+end $$;
+
+select session_stats_rebuild(id) from tenants;
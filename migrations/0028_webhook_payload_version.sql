@@ -0,0 +1,3 @@
+-- Event payload version a webhook receives (X-RCRT-Payload-Version). Null defers to the matching
+-- selectors' pin, then the server default (1)
+alter table agent_webhooks add column if not exists payload_version smallint;
@@ -0,0 +1,24 @@
+-- Declared references from one breadcrumb's context to another of the same tenant: the
+-- `references` array on create/update, or a `$refs` block in the context. Both ends go with
+-- their breadcrumb: deleting the source drops its references, and a target can only be deleted
+-- with ?force=true while other breadcrumbs reference it (hygiene, purge and cascades skip that
+-- check). GET /breadcrumbs/:id/references and /referenced_by read it, and the context-builder
+-- takes causal edges from `triggered_by` rows.
+create table if not exists breadcrumb_references (
+  owner_id uuid not null references tenants(id) on delete cascade,
+  source_id uuid not null references breadcrumbs(id) on delete cascade,
+  -- Where in the source's context the id lives, e.g. trigger_event_id
+  field text not null,
+  target_id uuid not null references breadcrumbs(id) on delete cascade,
+  relation text not null,
+  created_at timestamptz not null default now(),
+  primary key (source_id, field, target_id)
+);
+
+create index if not exists idx_breadcrumb_references_target on breadcrumb_references (target_id);
+
+alter table breadcrumb_references enable row level security;
+
+create policy tenant_isolation_breadcrumb_references on breadcrumb_references
+  using (owner_id = app_current_owner_id())
+  with check (owner_id = app_current_owner_id());
@@ -0,0 +1,23 @@
+-- A webhook can be bound to one of its agent's selectors, and then fires only for that selector's
+-- matches; unbound webhooks fire for every match of their agent. Deleting the selector (by id, by
+-- tag or as expired) deactivates its webhooks and records why, rather than leaving them to fire
+-- for everything once the binding is gone.
+alter table agent_webhooks add column if not exists selector_id uuid references selector_subscriptions(id) on delete set null;
+alter table agent_webhooks add column if not exists deactivated_reason text;
+create index if not exists idx_agent_webhooks_selector_id on agent_webhooks (selector_id) where selector_id is not null;
+
+-- Runs before the foreign key clears selector_id, so the bound webhooks can still be found
+create or replace function agent_webhooks_deactivate_for_selector() returns trigger
+  language plpgsql
+  as $$
+begin
+  update agent_webhooks
+     set active = false, deactivated_reason = 'selector ' || old.id || ' was deleted'
+   where selector_id = old.id and active;
+  return old;
+end;
+$$;
+
+drop trigger if exists agent_webhooks_deactivate_for_selector on selector_subscriptions;
+create trigger agent_webhooks_deactivate_for_selector before delete on selector_subscriptions
+  for each row execute function agent_webhooks_deactivate_for_selector();
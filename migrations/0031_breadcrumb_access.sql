@@ -0,0 +1,19 @@
+-- Daily access counters per breadcrumb, reading agent and kind of access, behind
+-- GET /breadcrumbs/:id/analytics and GET /analytics/top_breadcrumbs. The server adds reads up in
+-- memory and flushes them here as batched upserts (ACCESS_LOG_FLUSH_SECS); the hygiene runner drops
+-- days older than HYGIENE_ACCESS_LOG_RETENTION_DAYS. Day granularity keeps it to one row per breadcrumb,
+-- agent and kind per day however often it is read.
+create table if not exists breadcrumb_access (
+  owner_id uuid not null references tenants(id) on delete cascade,
+  breadcrumb_id uuid not null references breadcrumbs(id) on delete cascade,
+  agent_id uuid not null,
+  access_type text not null check (access_type in ('api_read', 'context_assembly', 'search_hit')),
+  day date not null,
+  count bigint not null,
+  primary key (breadcrumb_id, agent_id, access_type, day)
+);
+
+create index if not exists idx_breadcrumb_access_top on breadcrumb_access (owner_id, access_type, day);
+create index if not exists idx_breadcrumb_access_day on breadcrumb_access (day);
+
+comment on column breadcrumbs.read_count is 'Context view, full and bulk reads plus context assemblies, flushed from the access log (for usage-based TTL and analytics)';
@@ -0,0 +1,25 @@
+-- Switching embedding models without a flag day. breadcrumbs.embedding stays vector(384) and
+-- embedding_model names the model that wrote it; vectors of any other model (e.g. a 768-dim one
+-- being migrated to) live in breadcrumb_embeddings, one row per breadcrumb and model, filled by
+-- POST /admin/embeddings/backfill?model=. Search and the context-builder rank by the model an
+-- owner's system.embedding.config.v1 breadcrumb names, written by POST /admin/embeddings/cutover.
+alter table breadcrumbs add column if not exists embedding_model text not null default 'all-MiniLM-L6-v2';
+
+create table if not exists breadcrumb_embeddings (
+  owner_id uuid not null references tenants(id) on delete cascade,
+  breadcrumb_id uuid not null references breadcrumbs(id) on delete cascade,
+  model text not null,
+  -- No fixed dimension: each model has its own. An ivfflat index per model needs an expression
+  -- index with a cast, e.g. ((embedding::vector(768))) where model = '...'
+  embedding vector not null,
+  created_at timestamptz not null default now(),
+  primary key (breadcrumb_id, model)
+);
+
+create index if not exists idx_breadcrumb_embeddings_model on breadcrumb_embeddings (owner_id, model);
+
+alter table breadcrumb_embeddings enable row level security;
+
+create policy tenant_isolation_breadcrumb_embeddings on breadcrumb_embeddings
+  using (owner_id = app_current_owner_id())
+  with check (owner_id = app_current_owner_id());
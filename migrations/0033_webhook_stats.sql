@@ -0,0 +1,8 @@
+-- Per-webhook delivery stats, updated as each delivery finishes (delivered, or failed after its
+-- retries), so a long-running webhook's health shows in its listing without scanning
+-- webhook_deliveries, whose rows hygiene purges. consecutive_failures resets on a success and on
+-- registering the URL again, and drives WEBHOOK_AUTO_DISABLE_AFTER_FAILURES.
+alter table agent_webhooks add column if not exists last_success_at timestamptz;
+alter table agent_webhooks add column if not exists last_failure_at timestamptz;
+alter table agent_webhooks add column if not exists consecutive_failures integer not null default 0;
+alter table agent_webhooks add column if not exists total_deliveries bigint not null default 0;
@@ -0,0 +1,4 @@
+-- The session an agent is working in, set by PUT /agents/:id/session. With SESSION_TAG_INFERENCE
+-- at agent or both, breadcrumbs the agent creates without a session: tag get this one.
+alter table agents add column if not exists active_session text;
+alter table agents add column if not exists active_session_at timestamptz;
@@ -0,0 +1,34 @@
+-- Webhooks registered by a tenant rather than one of its agents, for integrations (a Slack
+-- notifier, an audit sink) that shouldn't need an agent, roles and a selector. POST /webhooks
+-- (curator) writes one; fanout matches it with the same matcher as selectors and delivers it
+-- through the same dispatch, delivery log and DLQ, signed with its own secret. Registering the
+-- URL again replaces the filters and secret. An empty any_tags or event_types matches anything.
+create table if not exists owner_webhooks (
+  id uuid primary key default uuid_generate_v4(),
+  owner_id uuid not null references tenants(id) on delete cascade,
+  url text not null,
+  secret text,
+  schema_name text,
+  any_tags text[] not null default '{}',
+  event_types text[] not null default '{}',
+  created_at timestamptz not null default now(),
+  unique (owner_id, url)
+);
+
+alter table owner_webhooks enable row level security;
+
+create policy tenant_isolation_owner_webhooks on owner_webhooks
+  using (owner_id = app_current_owner_id())
+  with check (owner_id = app_current_owner_id());
+
+-- Deliveries to owner webhooks are logged and deduplicated like agent webhooks'
+alter table webhook_deliveries alter column webhook_id drop not null;
+alter table webhook_deliveries add column if not exists owner_webhook_id uuid references owner_webhooks(id) on delete cascade;
+create unique index if not exists uq_webhook_deliveries_owner_target
+  on webhook_deliveries (owner_webhook_id, breadcrumb_id, version);
+
+-- A dead letter belongs to an agent or to an owner webhook; deleting the owner webhook drops its
+-- dead letters, since a retry would have nowhere to go
+alter table webhook_dlq alter column agent_id drop not null;
+alter table webhook_dlq add column if not exists owner_webhook_id uuid references owner_webhooks(id) on delete cascade;
+alter table webhook_dlq add constraint webhook_dlq_one_target check ((agent_id is null) <> (owner_webhook_id is null));
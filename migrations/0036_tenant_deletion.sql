@@ -0,0 +1,42 @@
+-- Staged tenant offboarding behind DELETE /tenants/:id. The tenant is marked deleting_at first, and
+-- every replica stops accepting writes for the owner; then a full NDJSON snapshot is exported, the
+-- owner's rows are deleted table by table in batches, and the tenants row goes last.
+-- tenant_deletions tracks the run and outlives the tenant, so there is no foreign key. A failed run
+-- keeps its status, export location and counts; requesting the deletion again resumes it.
+-- Curators read it across tenants, so no RLS, like api_keys.
+alter table tenants add column if not exists deleting_at timestamptz;
+
+create table if not exists tenant_deletions (
+  tenant_id uuid primary key,
+  tenant_name text not null,
+  status text not null default 'exporting' check (status in ('exporting', 'deleting', 'completed', 'failed')),
+  stage text,
+  -- A URL to PUT the snapshot to instead of the attachment store
+  export_to text,
+  export_location text,
+  deleted jsonb not null default '{}',
+  error text,
+  started_at timestamptz not null default now(),
+  updated_at timestamptz not null default now(),
+  finished_at timestamptz
+);
+
+-- Rows that tie another tenant to this one don't hold the deletion up: ACL grants to the tenant or
+-- its agents go with them, and breadcrumbs its agents wrote through a write grant keep their content
+-- with the attribution cleared, as agent offboarding does. Referential actions bypass RLS, which
+-- would otherwise hide those rows from a run scoped to the tenant
+alter table acl_entries
+  drop constraint if exists acl_entries_grantee_owner_id_fkey,
+  add constraint acl_entries_grantee_owner_id_fkey foreign key (grantee_owner_id) references tenants(id) on delete cascade,
+  drop constraint if exists acl_entries_grantee_agent_id_fkey,
+  add constraint acl_entries_grantee_agent_id_fkey foreign key (grantee_agent_id) references agents(id) on delete cascade;
+
+alter table breadcrumbs
+  drop constraint if exists breadcrumbs_created_by_fkey,
+  add constraint breadcrumbs_created_by_fkey foreign key (created_by) references agents(id) on delete set null,
+  drop constraint if exists breadcrumbs_updated_by_fkey,
+  add constraint breadcrumbs_updated_by_fkey foreign key (updated_by) references agents(id) on delete set null;
+
+alter table breadcrumb_history
+  drop constraint if exists breadcrumb_history_updated_by_fkey,
+  add constraint breadcrumb_history_updated_by_fkey foreign key (updated_by) references agents(id) on delete set null;
@@ -0,0 +1,24 @@
+-- Share links: POST /breadcrumbs/:id/share (the breadcrumb's creator or a curator) signs a token
+-- naming this row, and GET /shared/:token serves the breadcrumb's context view to whoever holds
+-- it, without an agent or JWT. The token carries its own expiry and is checked against the server
+-- secret; the row is what lists a breadcrumb's links and revokes one before it expires.
+-- Shares go with their breadcrumb.
+create table if not exists breadcrumb_shares (
+  id uuid primary key,
+  owner_id uuid not null references tenants(id) on delete cascade,
+  breadcrumb_id uuid not null references breadcrumbs(id) on delete cascade,
+  -- The agent that made the link; no foreign key, since disabled auth acts as an agent that may not be registered
+  created_by uuid,
+  view text not null default 'context' check (view in ('context')),
+  expires_at timestamptz not null,
+  created_at timestamptz not null default now(),
+  revoked_at timestamptz
+);
+
+create index if not exists idx_breadcrumb_shares_breadcrumb on breadcrumb_shares (breadcrumb_id, created_at);
+
+alter table breadcrumb_shares enable row level security;
+
+create policy tenant_isolation_breadcrumb_shares on breadcrumb_shares
+  using (owner_id = app_current_owner_id())
+  with check (owner_id = app_current_owner_id());
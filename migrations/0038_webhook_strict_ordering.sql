@@ -0,0 +1,5 @@
+-- Opt-in strict ordering per webhook: while a delivery of a breadcrumb is still retrying, later
+-- versions of the same breadcrumb wait behind it instead of overtaking it. The queue lives in the
+-- server's memory; a delivery that finds its queue full goes straight to the DLQ
+alter table agent_webhooks add column if not exists strict_ordering boolean not null default false;
+alter table owner_webhooks add column if not exists strict_ordering boolean not null default false;
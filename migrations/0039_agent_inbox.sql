@@ -0,0 +1,28 @@
+-- A pull-based delivery channel: selectors with the "inbox" channel queue their events here, one
+-- row per (agent, breadcrumb, version), for agents that poll rather than hold a stream open.
+-- GET /agents/:id/inbox leases the oldest unacknowledged rows until leased_until, so concurrent
+-- pollers get disjoint entries; a lease that runs out without an ack makes the row due again.
+-- Acked rows stay until hygiene prunes them. breadcrumb_id has no foreign key: the event of a
+-- deleted breadcrumb is still worth delivering.
+create table if not exists agent_inbox (
+  id uuid primary key default uuid_generate_v4(),
+  owner_id uuid not null references tenants(id) on delete cascade,
+  agent_id uuid not null references agents(id) on delete cascade,
+  breadcrumb_id uuid not null,
+  version int not null,
+  event jsonb not null,
+  created_at timestamptz not null default now(),
+  leased_until timestamptz,
+  deliveries int not null default 0,
+  acked_at timestamptz,
+  unique (agent_id, breadcrumb_id, version)
+);
+
+create index if not exists idx_agent_inbox_pending on agent_inbox (agent_id, created_at) where acked_at is null;
+create index if not exists idx_agent_inbox_acked on agent_inbox (acked_at) where acked_at is not null;
+
+alter table agent_inbox enable row level security;
+
+create policy tenant_isolation_agent_inbox on agent_inbox
+  using (owner_id = app_current_owner_id())
+  with check (owner_id = app_current_owner_id());
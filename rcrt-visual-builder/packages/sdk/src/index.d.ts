@@ -81,6 +81,19 @@ export interface Tenant {
     name: string;
     created_at: string;
 }
+export interface TenantDeletion {
+    tenant_id: string;
+    tenant_name: string;
+    status: 'exporting' | 'deleting' | 'completed' | 'failed';
+    stage: string | null;
+    export_to: string | null;
+    export_location: string | null;
+    deleted: Record<string, number>;
+    error: string | null;
+    started_at: string;
+    updated_at: string;
+    finished_at: string | null;
+}
 export interface ACL {
     id: string;
     breadcrumb_id: string;
@@ -138,9 +151,8 @@ export declare class RcrtClientEnhanced {
     createOrUpdateTenant(tenantId: string, name: string): Promise<{
         ok: boolean;
     }>;
-    deleteTenant(tenantId: string): Promise<{
-        ok: boolean;
-    }>;
+    deleteTenant(tenantId: string, confirm: string, exportTo?: string): Promise<TenantDeletion>;
+    getTenantDeletion(tenantId: string): Promise<TenantDeletion>;
     listAcls(): Promise<ACL[]>;
     listDlq(): Promise<DLQItem[]>;
     deleteDlqItem(dlqId: string): Promise<{
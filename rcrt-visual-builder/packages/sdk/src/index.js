@@ -227,8 +227,11 @@ export class RcrtClientEnhanced {
         }
         return { ok: true };
     }
-    async deleteTenant(tenantId) {
-        const response = await fetch(`${this.baseUrl}/tenants/${tenantId}`, {
+    async deleteTenant(tenantId, confirm, exportTo) {
+        const params = new URLSearchParams({ confirm });
+        if (exportTo)
+            params.set('export_to', exportTo);
+        const response = await fetch(`${this.baseUrl}/tenants/${tenantId}?${params}`, {
             method: 'DELETE',
             headers: this.defaultHeaders,
         });
@@ -236,7 +239,17 @@ export class RcrtClientEnhanced {
             const error = await response.text();
             throw new Error(`Failed to delete tenant: ${error}`);
         }
-        return { ok: true };
+        return response.json();
+    }
+    async getTenantDeletion(tenantId) {
+        const response = await fetch(`${this.baseUrl}/tenants/${tenantId}/deletion`, {
+            headers: this.defaultHeaders,
+        });
+        if (!response.ok) {
+            const error = await response.text();
+            throw new Error(`Failed to get tenant deletion: ${error}`);
+        }
+        return response.json();
     }
     // ============ ACL Operations ============
     async listAcls() {
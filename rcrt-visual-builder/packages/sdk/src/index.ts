@@ -114,6 +114,20 @@ export interface Tenant {
   created_at: string;
 }
 
+export interface TenantDeletion {
+  tenant_id: string;
+  tenant_name: string;
+  status: 'exporting' | 'deleting' | 'completed' | 'failed';
+  stage: string | null;
+  export_to: string | null;
+  export_location: string | null;
+  deleted: Record<string, number>;
+  error: string | null;
+  started_at: string;
+  updated_at: string;
+  finished_at: string | null;
+}
+
 export interface ACL {
   id: string;
   breadcrumb_id: string;
@@ -557,8 +571,11 @@ export class RcrtClientEnhanced {
     return { ok: true };
   }
 
-  async deleteTenant(tenantId: string): Promise<{ ok: boolean }> {
-    const response = await this.fetchWithAuth(`${this.baseUrl}/tenants/${tenantId}`, {
+  /** Starts (or resumes) the staged deletion; `confirm` must be the tenant's name. Follow it with getTenantDeletion */
+  async deleteTenant(tenantId: string, confirm: string, exportTo?: string): Promise<TenantDeletion> {
+    const params = new URLSearchParams({ confirm });
+    if (exportTo) params.set('export_to', exportTo);
+    const response = await this.fetchWithAuth(`${this.baseUrl}/tenants/${tenantId}?${params}`, {
       method: 'DELETE',
     });
 
@@ -567,7 +584,18 @@ export class RcrtClientEnhanced {
       throw new Error(`Failed to delete tenant: ${error}`);
     }
 
-    return { ok: true };
+    return response.json();
+  }
+
+  async getTenantDeletion(tenantId: string): Promise<TenantDeletion> {
+    const response = await this.fetchWithAuth(`${this.baseUrl}/tenants/${tenantId}/deletion`);
+
+    if (!response.ok) {
+      const error = await response.text();
+      throw new Error(`Failed to get tenant deletion: ${error}`);
+    }
+
+    return response.json();
   }
 
   // ============ ACL Operations ============